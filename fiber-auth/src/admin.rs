@@ -0,0 +1,140 @@
+//! Operator authentication: a bearer token configured on the service.
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts},
+};
+use fiber_errors::ApiError;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::sync::Arc;
+
+/// Router state that knows the secrets callers are checked against
+pub trait AuthState {
+    fn admin_secret(&self) -> &AdminSecret;
+}
+
+impl<T: AuthState> AuthState for Arc<T> {
+    fn admin_secret(&self) -> &AdminSecret {
+        (**self).admin_secret()
+    }
+}
+
+/// The admin token a service accepts. Without one, [`AdminToken`] rejects
+/// every request.
+#[derive(Clone, Default)]
+pub struct AdminSecret(Option<String>);
+
+impl AdminSecret {
+    pub fn new(token: Option<String>) -> Self {
+        Self(token)
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// Whether `given` is the token
+    pub fn accepts(&self, given: &str) -> bool {
+        // Compare digests so the time taken doesn't depend on the token
+        let digest = |token: &str| Sha256::digest(token.as_bytes());
+        self.0
+            .as_deref()
+            .is_some_and(|token| digest(given) == digest(token))
+    }
+}
+
+impl fmt::Debug for AdminSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let shown = if self.is_set() {
+            "<redacted>"
+        } else {
+            "<none>"
+        };
+        f.debug_tuple("AdminSecret").field(&shown).finish()
+    }
+}
+
+/// Proof the request carried `Authorization: Bearer <token>` with the
+/// service's [`AdminSecret`]. Take it as a handler argument, or guard a
+/// whole router with `middleware::from_extractor_with_state::<AdminToken, _>`.
+#[derive(Debug)]
+pub struct AdminToken;
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AdminToken
+where
+    S: AuthState + Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let given = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        match given {
+            Some(given) if state.admin_secret().accepts(given) => Ok(AdminToken),
+            _ => Err(ApiError::unauthorized("Admin token required")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, extract::Request, http::StatusCode, routing::get, Router};
+    use tower::ServiceExt;
+
+    struct Service(AdminSecret);
+
+    impl AuthState for Service {
+        fn admin_secret(&self) -> &AdminSecret {
+            &self.0
+        }
+    }
+
+    async fn status(secret: Option<&str>, header: Option<&str>) -> StatusCode {
+        let app = Router::new()
+            .route("/", get(|_: AdminToken| async { "ok" }))
+            .with_state(Arc::new(Service(AdminSecret::new(
+                secret.map(String::from),
+            ))));
+        let mut req = Request::get("/");
+        if let Some(header) = header {
+            req = req.header(AUTHORIZATION, header);
+        }
+        let resp = app.oneshot(req.body(Body::empty()).unwrap()).await.unwrap();
+        resp.status()
+    }
+
+    #[tokio::test]
+    async fn test_admin_token() {
+        assert_eq!(
+            status(Some("s3cret"), Some("Bearer s3cret")).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(Some("s3cret"), Some("Bearer wrong")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status(Some("s3cret"), Some("s3cret")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(status(Some("s3cret"), None).await, StatusCode::UNAUTHORIZED);
+        // No token configured lets nobody in, not everybody
+        assert_eq!(
+            status(None, Some("Bearer ")).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[test]
+    fn test_secret_is_not_printed() {
+        let secret = AdminSecret::new(Some("s3cret".to_string()));
+        assert!(!format!("{:?}", secret).contains("s3cret"));
+    }
+}
@@ -0,0 +1,196 @@
+//! Shared identity: one keypair a user signs in with at every Fiber app.
+//!
+//! A user proves they hold a secp256k1 key with a token they sign
+//! themselves and send as [`IDENTITY_HEADER`]. Services keep no passwords
+//! or sessions, and one token works at the oracle, the escrow and any other
+//! app that checks it. [`Identity::user_id`] derives a stable ID from the
+//! key, so the same key is the same user everywhere: the escrow's
+//! [`crate::AuthedUser`] and a game player's ID alike.
+//!
+//! A token is `<public key hex>.<expiry, Unix seconds>.<signature hex>`,
+//! the signature being ECDSA over the SHA-256 of the key and expiry.
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, HeaderMap},
+};
+use fiber_errors::{ApiError, ErrorCode};
+use secp256k1::{ecdsa::Signature, Message, PublicKey, Secp256k1, SecretKey};
+use sha2::{Digest, Sha256};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// Header carrying an identity token
+pub const IDENTITY_HEADER: &str = "X-Fiber-Identity";
+
+/// Furthest ahead a token's expiry may be, so a leaked one doesn't stay
+/// good for long
+pub const MAX_IDENTITY_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Keeps identity signatures from being valid as anything else
+const DOMAIN: &[u8] = b"fiber-identity/v1";
+
+/// The holder of a key
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Identity {
+    pub key: PublicKey,
+}
+
+impl Identity {
+    pub fn of(secret: &SecretKey) -> Self {
+        Self {
+            key: PublicKey::from_secret_key(&Secp256k1::signing_only(), secret),
+        }
+    }
+
+    /// The user ID every service knows this identity by
+    pub fn user_id(&self) -> Uuid {
+        let digest = Sha256::new()
+            .chain_update(DOMAIN)
+            .chain_update(b"/user")
+            .chain_update(self.key.serialize())
+            .finalize();
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&digest[..16]);
+        uuid::Builder::from_custom_bytes(bytes).into_uuid()
+    }
+
+    /// A token for `secret` that is good for `ttl`
+    pub fn sign_in(secret: &SecretKey, ttl: Duration) -> String {
+        Self::token(secret, SystemTime::now() + ttl)
+    }
+
+    /// A token for `secret` that is good until `expires_at`
+    pub fn token(secret: &SecretKey, expires_at: SystemTime) -> String {
+        let key = Self::of(secret).key;
+        let expires = unix_secs(expires_at);
+        let signature = Secp256k1::signing_only()
+            .sign_ecdsa(&digest(&key, expires), secret)
+            .serialize_compact();
+        format!(
+            "{}.{}.{}",
+            hex::encode(key.serialize()),
+            expires,
+            hex::encode(signature)
+        )
+    }
+
+    /// The identity `token` proves, if it is well-formed, signed and current
+    pub fn verify(token: &str) -> Result<Self, ApiError> {
+        Self::verify_at(token, SystemTime::now())
+    }
+
+    /// [`Identity::verify`] as of `now`
+    pub fn verify_at(token: &str, now: SystemTime) -> Result<Self, ApiError> {
+        let malformed = || ApiError::unauthorized("Malformed identity token");
+        let mut parts = token.trim().split('.');
+        let (Some(key), Some(expires), Some(signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(malformed());
+        };
+        let key = hex::decode(key)
+            .ok()
+            .and_then(|bytes| PublicKey::from_slice(&bytes).ok())
+            .ok_or_else(malformed)?;
+        let expires: u64 = expires.parse().map_err(|_| malformed())?;
+        let signature = hex::decode(signature)
+            .ok()
+            .and_then(|bytes| Signature::from_compact(&bytes).ok())
+            .ok_or_else(malformed)?;
+
+        Secp256k1::verification_only()
+            .verify_ecdsa(&digest(&key, expires), &signature, &key)
+            .map_err(|_| ApiError::unauthorized("Invalid identity token signature"))?;
+        let now = unix_secs(now);
+        if expires < now {
+            return Err(ApiError::new(ErrorCode::Expired, "Identity token has expired"));
+        }
+        if expires > now.saturating_add(MAX_IDENTITY_TTL.as_secs()) {
+            return Err(ApiError::unauthorized(format!(
+                "Identity token must expire within {}s",
+                MAX_IDENTITY_TTL.as_secs()
+            )));
+        }
+        Ok(Self { key })
+    }
+}
+
+fn digest(key: &PublicKey, expires: u64) -> Message {
+    let digest: [u8; 32] = Sha256::new()
+        .chain_update(DOMAIN)
+        .chain_update(key.serialize())
+        .chain_update(expires.to_be_bytes())
+        .finalize()
+        .into();
+    Message::from_digest(digest)
+}
+
+fn unix_secs(at: SystemTime) -> u64 {
+    at.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// The caller's [`Identity`], from a valid token. Requests without one are
+/// rejected with 401.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AuthedIdentity(pub Identity);
+
+impl AuthedIdentity {
+    /// The identity `headers` prove, for callers outside an axum handler
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, ApiError> {
+        let token = headers
+            .get(IDENTITY_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| ApiError::unauthorized("Missing X-Fiber-Identity header"))?;
+        Identity::verify(token).map(AuthedIdentity)
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for AuthedIdentity {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Self::from_headers(&parts.headers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fiber_test_fixtures::Keypair;
+
+    #[test]
+    fn test_token_proves_the_key() {
+        let key = Keypair::player_a();
+        let token = Identity::sign_in(&key.secret, Duration::from_secs(60));
+        let identity = Identity::verify(&token).unwrap();
+        assert_eq!(identity.key, key.public);
+        assert_eq!(identity.user_id(), Identity::of(&key.secret).user_id());
+        assert_ne!(identity.user_id(), Identity::of(&Keypair::player_b().secret).user_id());
+    }
+
+    #[test]
+    fn test_rejects_tampered_stale_and_long_lived_tokens() {
+        let key = Keypair::player_a();
+        let now = SystemTime::now();
+        let token = Identity::token(&key.secret, now + Duration::from_secs(60));
+
+        // Moving the expiry breaks the signature
+        let mut parts: Vec<String> = token.split('.').map(str::to_string).collect();
+        parts[1] = (parts[1].parse::<u64>().unwrap() + 3600).to_string();
+        let err = Identity::verify_at(&parts.join("."), now).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Unauthorized);
+
+        let err = Identity::verify_at(&token, now + Duration::from_secs(120)).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Expired);
+
+        let token = Identity::token(&key.secret, now + MAX_IDENTITY_TTL * 2);
+        assert!(Identity::verify_at(&token, now).is_err());
+
+        for junk in ["", "a.b.c", "a.b.c.d"] {
+            assert_eq!(Identity::verify(junk).unwrap_err().code, ErrorCode::Unauthorized);
+        }
+    }
+}
@@ -0,0 +1,33 @@
+//! Fiber Auth
+//!
+//! Who is calling, as axum extractors, so every router checks it the same
+//! way and a handler states what it needs in its signature:
+//! - [`AuthedUser`] is an escrow user, named by the `X-User-Id` header or
+//!   (with the `identity` feature) by an identity token
+//! - [`AuthedIdentity`] is the holder of a key shared across the services,
+//!   proven by a token they signed (with the `identity` feature)
+//! - [`AuthedPlayer`] is a game submission signed by a player's key (with the
+//!   `game` feature)
+//! - [`AdminToken`] is an operator holding the service's admin token
+//!
+//! The other way round, the [`webhook`] module (with the `webhook` feature)
+//! signs the events a service POSTs to others, and checks them on receipt.
+//!
+//! Rejections are [`fiber_errors::ApiError`]s, so clients see the usual
+//! `{"error", "code"}` body.
+
+mod admin;
+#[cfg(feature = "identity")]
+mod identity;
+#[cfg(feature = "game")]
+mod player;
+mod user;
+#[cfg(feature = "webhook")]
+pub mod webhook;
+
+pub use admin::{AdminSecret, AdminToken, AuthState};
+#[cfg(feature = "identity")]
+pub use identity::{AuthedIdentity, Identity, IDENTITY_HEADER, MAX_IDENTITY_TTL};
+#[cfg(feature = "game")]
+pub use player::{AuthedPlayer, MAX_SUBMISSION_TTL};
+pub use user::{AuthedUser, USER_ID_HEADER};
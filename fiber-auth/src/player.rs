@@ -0,0 +1,149 @@
+//! Player authentication for the game oracle.
+//!
+//! Players have no accounts: each submission is an
+//! [`Envelope`](fiber_game_core::protocol::Envelope) signed with the key the
+//! player joined the game with, sent as JSON or CBOR. Whether that key
+//! belongs to the game is for the handler to decide; this only proves who
+//! signed and that the submission is fresh.
+
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{FromRequest, Request},
+    http::header,
+};
+use fiber_errors::{ApiError, ErrorCode, Validator};
+use fiber_game_core::protocol::{Encoding, Envelope};
+use secp256k1::PublicKey;
+use serde::de::DeserializeOwned;
+use std::time::Duration;
+
+/// Furthest ahead a submission's expiry may be. Players seal with a couple
+/// of minutes; the slack is for clock skew.
+pub const MAX_SUBMISSION_TTL: Duration = Duration::from_secs(600);
+
+/// A signed submission with a verified signature and an expiry that hasn't
+/// passed and isn't too far off
+pub struct AuthedPlayer<T> {
+    /// Key the submission was signed with
+    pub key: PublicKey,
+    /// The sender's nonce, for replay checks against earlier submissions
+    pub nonce: u64,
+    pub payload: T,
+    /// The submission as it arrived, for recording or handing on
+    pub envelope: Envelope<serde_json::Value>,
+}
+
+impl<T: DeserializeOwned> AuthedPlayer<T> {
+    /// Check a submission that arrived some other way than as an HTTP body,
+    /// such as over gRPC
+    pub fn from_bytes(encoding: Encoding, body: &[u8]) -> Result<Self, ApiError> {
+        let envelope: Envelope<serde_json::Value> = encoding
+            .decode(body)
+            .map_err(|e| ApiError::bad_request(e.to_string()))?;
+
+        if envelope.expires_at_ms.is_none() {
+            return Err(ApiError::bad_request("Submission has no expiry"));
+        }
+        if envelope.is_expired() {
+            return Err(ApiError::new(ErrorCode::Expired, "Submission has expired"));
+        }
+        let mut v = Validator::default();
+        v.check(
+            !envelope.expires_after(MAX_SUBMISSION_TTL),
+            "expires_at_ms",
+            format!("must be at most {}s away", MAX_SUBMISSION_TTL.as_secs()),
+        );
+        v.finish()?;
+
+        let (key, payload) = envelope.clone().open_as()?;
+        Ok(AuthedPlayer {
+            key,
+            nonce: envelope.nonce,
+            payload,
+            envelope,
+        })
+    }
+}
+
+#[async_trait]
+impl<S, T> FromRequest<S> for AuthedPlayer<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let encoding = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(Encoding::from_content_type)
+            .ok_or_else(|| {
+                ApiError::new(
+                    ErrorCode::UnsupportedMediaType,
+                    "Expected application/json or application/cbor",
+                )
+            })?;
+        let body = Bytes::from_request(req, state)
+            .await
+            .map_err(|e| ApiError::bad_request(e.body_text()))?;
+        Self::from_bytes(encoding, &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::StatusCode, response::Response, routing::post, Router};
+    use fiber_game_core::protocol::{CBOR_CONTENT_TYPE, JSON_CONTENT_TYPE};
+    use fiber_test_fixtures::{game::seal, Keypair};
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    async fn signer(player: AuthedPlayer<Value>) -> String {
+        player.key.to_string()
+    }
+
+    async fn call(content_type: &str, body: Vec<u8>) -> Response {
+        Router::new()
+            .route("/", post(signer))
+            .oneshot(
+                Request::post("/")
+                    .header(header::CONTENT_TYPE, content_type)
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_either_encoding_names_the_signer() {
+        let key = Keypair::player_a();
+        let envelope = seal(json!({ "player": "A" }), &key.secret);
+
+        for encoding in [Encoding::Json, Encoding::Cbor] {
+            let resp = call(encoding.content_type(), encoding.encode(&envelope).unwrap()).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert_eq!(body, key.public.to_string());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejects_forged_and_unknown_bodies() {
+        let mut envelope = seal(json!({ "player": "A" }), &Keypair::player_a().secret);
+        envelope.payload = json!({ "player": "B" });
+        let resp = call(JSON_CONTENT_TYPE, serde_json::to_vec(&envelope).unwrap()).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let resp = call("text/plain", b"{}".to_vec()).await;
+        assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        let resp = call(CBOR_CONTENT_TYPE, b"not cbor".to_vec()).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+}
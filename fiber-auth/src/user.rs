@@ -0,0 +1,48 @@
+//! User authentication for the escrow marketplace.
+//!
+//! The demo trusts the `X-User-Id` header its web UI sends; a real session
+//! or token check would replace the body of [`AuthedUser`]'s extractor and
+//! leave handlers unchanged. With the `identity` feature, a request with an
+//! identity token is the user [`crate::Identity::user_id`] names instead.
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, HeaderMap},
+};
+use fiber_errors::ApiError;
+use uuid::Uuid;
+
+/// Header naming the calling user
+pub const USER_ID_HEADER: &str = "X-User-Id";
+
+/// The calling user's ID. Requests without a valid one are rejected with 401.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AuthedUser(pub Uuid);
+
+impl AuthedUser {
+    /// The user named by `headers`, for callers outside an axum handler
+    /// (gRPC metadata converts into a `HeaderMap`)
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, ApiError> {
+        #[cfg(feature = "identity")]
+        if headers.contains_key(crate::IDENTITY_HEADER) {
+            let crate::AuthedIdentity(identity) = crate::AuthedIdentity::from_headers(headers)?;
+            return Ok(AuthedUser(identity.user_id()));
+        }
+        headers
+            .get(USER_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|s| Uuid::parse_str(s).ok())
+            .map(AuthedUser)
+            .ok_or_else(|| ApiError::unauthorized("Missing X-User-Id header"))
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for AuthedUser {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Self::from_headers(&parts.headers)
+    }
+}
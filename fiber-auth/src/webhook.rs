@@ -0,0 +1,395 @@
+//! Signed webhooks: proof that an event POSTed by a Fiber service came
+//! from it and was not changed or replayed on the way.
+//!
+//! A sender holds a [`WebhookSigner`] and sends each event as
+//! [`canonical`] JSON with a [`SIGNATURE_HEADER`] of
+//! `t=<Unix seconds>,<scheme>=<signature hex>`. The signature covers
+//! `fiber-webhook/v1.<t>.<body>`, so the timestamp can't be swapped and a
+//! signature can't be passed off as anything else. Two schemes are
+//! supported:
+//! - `hmac-sha256`, with a secret shared between sender and receiver
+//! - `ed25519`, with a key only the sender holds; receivers need only its
+//!   public key, so one key can serve integrators who don't trust each other
+//!
+//! A receiver embeds a [`WebhookVerifier`] and calls
+//! [`WebhookVerifier::verify`] with the headers and the body exactly as
+//! received, before parsing it. Events older than [`MAX_WEBHOOK_AGE`] are
+//! rejected, so a captured one can't be replayed later.
+//!
+//! Keys are configured as `hmac:<secret>` or `ed25519:<hex>`: a 32-byte
+//! seed for a signer, a public key for a verifier.
+
+use ed25519_dalek::{Signer, SigningKey, Verifier, VerifyingKey};
+use fiber_errors::{ApiError, ErrorCode};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::Sha256;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Header carrying a webhook's timestamp and signature
+pub const SIGNATURE_HEADER: &str = "X-Fiber-Signature";
+
+/// How far a webhook's timestamp may be from the receiver's clock, either
+/// way
+pub const MAX_WEBHOOK_AGE: Duration = Duration::from_secs(5 * 60);
+
+/// Keeps webhook signatures from being valid as anything else
+const DOMAIN: &str = "fiber-webhook/v1";
+
+const HMAC_SCHEME: &str = "hmac-sha256";
+const ED25519_SCHEME: &str = "ed25519";
+
+/// `payload` as canonical JSON: object keys sorted, no whitespace. The same
+/// value always serializes to the same bytes, whatever order its fields
+/// were built in.
+pub fn canonical<T: Serialize>(payload: &T) -> Result<Vec<u8>, serde_json::Error> {
+    let mut out = Vec::new();
+    write_canonical(&serde_json::to_value(payload)?, &mut out)?;
+    Ok(out)
+}
+
+fn write_canonical(value: &Value, out: &mut Vec<u8>) -> Result<(), serde_json::Error> {
+    match value {
+        Value::Array(items) => {
+            out.push(b'[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                write_canonical(item, out)?;
+            }
+            out.push(b']');
+        }
+        Value::Object(fields) => {
+            let mut fields: Vec<_> = fields.iter().collect();
+            fields.sort_by_key(|(key, _)| *key);
+            out.push(b'{');
+            for (i, (key, value)) in fields.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                serde_json::to_writer(&mut *out, key)?;
+                out.push(b':');
+                write_canonical(value, out)?;
+            }
+            out.push(b'}');
+        }
+        scalar => serde_json::to_writer(&mut *out, scalar)?,
+    }
+    Ok(())
+}
+
+/// What is signed for a body sent at `timestamp`
+fn signed_message(timestamp: u64, body: &[u8]) -> Vec<u8> {
+    let mut message = format!("{}.{}.", DOMAIN, timestamp).into_bytes();
+    message.extend_from_slice(body);
+    message
+}
+
+fn hmac(secret: &[u8], message: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC takes keys of any length");
+    mac.update(message);
+    mac
+}
+
+fn unix_secs(at: SystemTime) -> u64 {
+    at.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// A key that doesn't parse as `hmac:<secret>` or `ed25519:<hex>`
+#[derive(Debug)]
+pub struct KeyError(usize);
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "webhook key must be hmac:<secret> or ed25519:<hex of {} bytes>",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for KeyError {}
+
+fn parse_key(s: &str, ed25519_len: usize) -> Result<(&str, Vec<u8>), KeyError> {
+    let error = || KeyError(ed25519_len);
+    match s.split_once(':').ok_or_else(error)? {
+        ("hmac", secret) if !secret.is_empty() => Ok((HMAC_SCHEME, secret.as_bytes().to_vec())),
+        ("ed25519", key) => {
+            let key = hex::decode(key).map_err(|_| error())?;
+            if key.len() != ed25519_len {
+                return Err(error());
+            }
+            Ok((ED25519_SCHEME, key))
+        }
+        _ => Err(error()),
+    }
+}
+
+/// An event body and the [`SIGNATURE_HEADER`] value to send it with
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignedWebhook {
+    pub body: Vec<u8>,
+    pub signature: String,
+}
+
+/// The key a service signs its webhooks with
+#[derive(Clone)]
+pub enum WebhookSigner {
+    Hmac(Vec<u8>),
+    Ed25519(SigningKey),
+}
+
+impl WebhookSigner {
+    /// The public key receivers verify with, `ed25519:<hex>`, for an
+    /// Ed25519 signer
+    pub fn public_key(&self) -> Option<String> {
+        match self {
+            Self::Hmac(_) => None,
+            Self::Ed25519(key) => Some(format!(
+                "{}:{}",
+                ED25519_SCHEME,
+                hex::encode(key.verifying_key().as_bytes())
+            )),
+        }
+    }
+
+    /// `payload` signed now
+    pub fn sign<T: Serialize>(&self, payload: &T) -> Result<SignedWebhook, serde_json::Error> {
+        self.sign_at(payload, SystemTime::now())
+    }
+
+    /// [`WebhookSigner::sign`] as of `now`
+    pub fn sign_at<T: Serialize>(
+        &self,
+        payload: &T,
+        now: SystemTime,
+    ) -> Result<SignedWebhook, serde_json::Error> {
+        let body = canonical(payload)?;
+        let timestamp = unix_secs(now);
+        let message = signed_message(timestamp, &body);
+        let (scheme, signature) = match self {
+            Self::Hmac(secret) => (
+                HMAC_SCHEME,
+                hmac(secret, &message).finalize().into_bytes().to_vec(),
+            ),
+            Self::Ed25519(key) => (ED25519_SCHEME, key.sign(&message).to_bytes().to_vec()),
+        };
+        Ok(SignedWebhook {
+            body,
+            signature: format!("t={},{}={}", timestamp, scheme, hex::encode(signature)),
+        })
+    }
+}
+
+impl FromStr for WebhookSigner {
+    type Err = KeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match parse_key(s, ed25519_dalek::SECRET_KEY_LENGTH)? {
+            (HMAC_SCHEME, secret) => Ok(Self::Hmac(secret)),
+            (_, seed) => Ok(Self::Ed25519(SigningKey::from_bytes(
+                &seed.try_into().expect("length checked"),
+            ))),
+        }
+    }
+}
+
+impl fmt::Debug for WebhookSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scheme = match self {
+            Self::Hmac(_) => HMAC_SCHEME,
+            Self::Ed25519(_) => ED25519_SCHEME,
+        };
+        f.debug_tuple("WebhookSigner")
+            .field(&format_args!("{}:<redacted>", scheme))
+            .finish()
+    }
+}
+
+/// What a receiver checks webhooks from a service against
+#[derive(Clone)]
+pub enum WebhookVerifier {
+    Hmac(Vec<u8>),
+    Ed25519(VerifyingKey),
+}
+
+impl WebhookVerifier {
+    /// Whether `body` is what the service signed, going by the
+    /// [`SIGNATURE_HEADER`] in `headers`, and was sent within
+    /// [`MAX_WEBHOOK_AGE`]
+    pub fn verify(&self, headers: &axum::http::HeaderMap, body: &[u8]) -> Result<(), ApiError> {
+        let signature = headers
+            .get(SIGNATURE_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| ApiError::unauthorized("Missing X-Fiber-Signature header"))?;
+        self.verify_at(signature, body, SystemTime::now())
+    }
+
+    /// [`WebhookVerifier::verify`] for a header value, as of `now`
+    pub fn verify_at(&self, signature: &str, body: &[u8], now: SystemTime) -> Result<(), ApiError> {
+        let malformed = || ApiError::unauthorized("Malformed webhook signature");
+        let scheme = match self {
+            Self::Hmac(_) => HMAC_SCHEME,
+            Self::Ed25519(_) => ED25519_SCHEME,
+        };
+        let mut timestamp = None;
+        let mut expected = None;
+        for part in signature.trim().split(',') {
+            match part.split_once('=') {
+                Some(("t", t)) => timestamp = Some(t.parse::<u64>().map_err(|_| malformed())?),
+                Some((name, hex)) if name == scheme => {
+                    expected = Some(hex::decode(hex).map_err(|_| malformed())?)
+                }
+                _ => {}
+            }
+        }
+        let (Some(timestamp), Some(expected)) = (timestamp, expected) else {
+            return Err(malformed());
+        };
+
+        let message = signed_message(timestamp, body);
+        let valid = match self {
+            Self::Hmac(secret) => hmac(secret, &message).verify_slice(&expected).is_ok(),
+            Self::Ed25519(key) => ed25519_dalek::Signature::from_slice(&expected)
+                .is_ok_and(|sig| key.verify(&message, &sig).is_ok()),
+        };
+        if !valid {
+            return Err(ApiError::unauthorized("Invalid webhook signature"));
+        }
+        if unix_secs(now).abs_diff(timestamp) > MAX_WEBHOOK_AGE.as_secs() {
+            return Err(ApiError::new(
+                ErrorCode::Expired,
+                format!("Webhook must be sent within {}s", MAX_WEBHOOK_AGE.as_secs()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for WebhookVerifier {
+    type Err = KeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match parse_key(s, ed25519_dalek::PUBLIC_KEY_LENGTH)? {
+            (HMAC_SCHEME, secret) => Ok(Self::Hmac(secret)),
+            (_, key) => VerifyingKey::from_bytes(&key.try_into().expect("length checked"))
+                .map(Self::Ed25519)
+                .map_err(|_| KeyError(ed25519_dalek::PUBLIC_KEY_LENGTH)),
+        }
+    }
+}
+
+impl fmt::Debug for WebhookVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Hmac(_) => f.write_str("WebhookVerifier(hmac-sha256:<redacted>)"),
+            Self::Ed25519(key) => write!(
+                f,
+                "WebhookVerifier(ed25519:{})",
+                hex::encode(key.as_bytes())
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SEED: &str = "ed25519:0101010101010101010101010101010101010101010101010101010101010101";
+
+    fn signers() -> Vec<(WebhookSigner, WebhookVerifier)> {
+        let ed25519: WebhookSigner = SEED.parse().unwrap();
+        let public = ed25519.public_key().unwrap();
+        vec![
+            (
+                "hmac:s3cret".parse().unwrap(),
+                "hmac:s3cret".parse().unwrap(),
+            ),
+            (ed25519, public.parse().unwrap()),
+        ]
+    }
+
+    #[test]
+    fn test_canonical_sorts_keys() {
+        let a = canonical(&json!({"b": 1, "a": {"d": [true, null], "c": "x"}})).unwrap();
+        let b = canonical(&json!({"a": {"c": "x", "d": [true, null]}, "b": 1})).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a, br#"{"a":{"c":"x","d":[true,null]},"b":1}"#);
+    }
+
+    #[test]
+    fn test_signed_webhook_verifies() {
+        let now = SystemTime::now();
+        for (signer, verifier) in signers() {
+            let signed = signer.sign_at(&json!({"order_id": 7}), now).unwrap();
+            verifier
+                .verify_at(&signed.signature, &signed.body, now)
+                .unwrap();
+
+            let mut headers = axum::http::HeaderMap::new();
+            headers.insert(SIGNATURE_HEADER, signed.signature.parse().unwrap());
+            assert!(verifier.verify(&headers, &signed.body).is_ok());
+            let err = verifier
+                .verify(&Default::default(), &signed.body)
+                .unwrap_err();
+            assert_eq!(err.code, ErrorCode::Unauthorized);
+        }
+    }
+
+    #[test]
+    fn test_rejects_tampered_stale_and_foreign_webhooks() {
+        let now = SystemTime::now();
+        for (signer, verifier) in signers() {
+            let signed = signer.sign_at(&json!({"amount": 100}), now).unwrap();
+
+            let err = verifier
+                .verify_at(&signed.signature, br#"{"amount":1000}"#, now)
+                .unwrap_err();
+            assert_eq!(err.code, ErrorCode::Unauthorized);
+
+            // Moving the timestamp breaks the signature
+            let (t, rest) = signed.signature.split_once(',').unwrap();
+            let t: u64 = t.trim_start_matches("t=").parse().unwrap();
+            let moved = format!("t={},{}", t + 60, rest);
+            assert!(verifier.verify_at(&moved, &signed.body, now).is_err());
+
+            let late = now + MAX_WEBHOOK_AGE * 2;
+            let err = verifier
+                .verify_at(&signed.signature, &signed.body, late)
+                .unwrap_err();
+            assert_eq!(err.code, ErrorCode::Expired);
+
+            for junk in ["", "t=1", "t=x,hmac-sha256=00", "ed25519=zz"] {
+                assert!(verifier.verify_at(junk, &signed.body, now).is_err());
+            }
+        }
+
+        let signed = WebhookSigner::from_str("hmac:s3cret")
+            .unwrap()
+            .sign_at(&json!({}), now)
+            .unwrap();
+        let other: WebhookVerifier = "hmac:other".parse().unwrap();
+        assert!(other
+            .verify_at(&signed.signature, &signed.body, now)
+            .is_err());
+    }
+
+    #[test]
+    fn test_keys_parse_and_are_not_printed() {
+        for bad in ["", "s3cret", "hmac:", "ed25519:00", "rsa:abc"] {
+            assert!(bad.parse::<WebhookSigner>().is_err(), "{}", bad);
+        }
+        let signer: WebhookSigner = "hmac:s3cret".parse().unwrap();
+        assert!(signer.public_key().is_none());
+        assert!(!format!("{:?}", signer).contains("s3cret"));
+        let signer: WebhookSigner = SEED.parse().unwrap();
+        assert!(!format!("{:?}", signer).contains("0101"));
+    }
+}
@@ -0,0 +1,317 @@
+//! Fiber Service Configuration
+//!
+//! Every demo binary takes its settings from four layers, highest first:
+//! 1. command-line flags
+//! 2. environment variables
+//! 3. the service's section of a YAML file given with `--config` (or
+//!    `FIBER_CONFIG`)
+//! 4. built-in defaults
+//!
+//! A service describes its settings once, as a typed `Config` struct that is
+//! both `clap::Args` (flags, env vars, defaults) and serde (file keys, which
+//! are the field names). [`ServiceConfig`] adds the file section and the
+//! checks run at startup, and [`load`] does the layering, validation and
+//! `--print-config`. One file can configure every service:
+//!
+//! ```yaml
+//! oracle:
+//!   port: 3000
+//!   db_path: oracle.db
+//! player:
+//!   port: 3001
+//!   oracle_url: http://localhost:3000
+//! escrow:
+//!   currency: Fibd
+//!   order_timeout_hours: 48
+//! ```
+
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Args, CommandFactory, FromArgMatches};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Env var naming the config file when `--config` is not given
+pub const CONFIG_FILE_ENV: &str = "FIBER_CONFIG";
+
+/// `--config` and `--print-config`, added to a binary's CLI by [`command`]
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct ConfigArgs {
+    /// YAML file with a section of settings per service; flags and
+    /// environment variables override it
+    #[arg(long = "config", env = CONFIG_FILE_ENV, global = true, value_name = "FILE")]
+    pub config_file: Option<PathBuf>,
+    /// Print the resolved configuration as YAML and exit
+    #[arg(long, global = true)]
+    pub print_config: bool,
+}
+
+/// Settings of one service
+pub trait ServiceConfig: FromArgMatches + Serialize + DeserializeOwned {
+    /// Section of the config file holding these settings
+    const SECTION: &'static str;
+
+    /// Reject settings that parse but can't work, such as a malformed URL.
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Why the configuration could not be loaded
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The config file can't be read or has a setting that doesn't fit
+    File { path: PathBuf, reason: String },
+    /// The resolved settings fail [`ServiceConfig::validate`]
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::File { path, reason } => write!(f, "{}: {}", path.display(), reason),
+            ConfigError::Invalid(reason) => write!(f, "Invalid configuration: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// The clap command of `P` with [`ConfigArgs`] added.
+pub fn command<P: CommandFactory>() -> clap::Command {
+    // Only the args: augmenting `P`'s command would replace its description
+    // with `ConfigArgs`'s doc
+    let config_args = ConfigArgs::augment_args(clap::Command::new("config"));
+    P::command().args(config_args.get_arguments().cloned())
+}
+
+/// Layer the config file under `cli`, the settings clap parsed into
+/// `matches`, and validate the result.
+///
+/// `matches` must come from a [`command`]; only the values it got from a
+/// flag or an environment variable take precedence over the file.
+pub fn resolve<C: ServiceConfig>(cli: C, matches: &ArgMatches) -> Result<C, ConfigError> {
+    let args = ConfigArgs::from_arg_matches(matches).unwrap_or_default();
+    let config = match &args.config_file {
+        Some(path) => {
+            let file_error = |reason: String| ConfigError::File {
+                path: path.clone(),
+                reason,
+            };
+            let section = read_section(path, C::SECTION).map_err(file_error)?;
+            merge(cli, matches, section).map_err(file_error)?
+        }
+        None => cli,
+    };
+    config.validate().map_err(ConfigError::Invalid)?;
+    Ok(config)
+}
+
+/// [`resolve`] for a binary's `main`: exits with the error if there is one,
+/// or after printing the configuration if `--print-config` was given.
+pub fn load<C: ServiceConfig>(cli: C, matches: &ArgMatches) -> C {
+    let config = match resolve(cli, matches) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(2);
+        }
+    };
+    if ConfigArgs::from_arg_matches(matches).is_ok_and(|args| args.print_config) {
+        print!("{}", to_yaml(&config));
+        std::process::exit(0);
+    }
+    config
+}
+
+/// `config` as a config file would hold it, under its section
+pub fn to_yaml<C: ServiceConfig>(config: &C) -> String {
+    let mut file = Mapping::new();
+    file.insert(
+        C::SECTION.into(),
+        serde_yaml::to_value(config).expect("config serializes"),
+    );
+    serde_yaml::to_string(&file).expect("config serializes")
+}
+
+/// Check that `value` of setting `name` is a URL with one of `schemes`.
+pub fn check_url(name: &str, value: &str, schemes: &[&str]) -> Result<(), String> {
+    let url = url::Url::parse(value).map_err(|e| format!("{} {:?}: {}", name, value, e))?;
+    if schemes.contains(&url.scheme()) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} {:?}: scheme must be {}",
+            name,
+            value,
+            schemes.join(" or ")
+        ))
+    }
+}
+
+fn read_section(path: &Path, section: &str) -> Result<Mapping, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    section_of(&text, section)
+}
+
+/// Settings under `section` of a config file; none if it has no such section.
+fn section_of(text: &str, section: &str) -> Result<Mapping, String> {
+    let file: Option<Mapping> = serde_yaml::from_str(text).map_err(|e| e.to_string())?;
+    match file.and_then(|mut file| file.remove(section)) {
+        None | Some(Value::Null) => Ok(Mapping::new()),
+        Some(Value::Mapping(settings)) => Ok(settings),
+        Some(_) => Err(format!("`{}` must be a map of settings", section)),
+    }
+}
+
+/// Start from the file's settings and let every value clap did not fall
+/// back to a default for override them.
+fn merge<C: ServiceConfig>(cli: C, matches: &ArgMatches, mut file: Mapping) -> Result<C, String> {
+    let Value::Mapping(cli) = serde_yaml::to_value(&cli).map_err(|e| e.to_string())? else {
+        return Err(format!("`{}` settings are not a map", C::SECTION));
+    };
+    if let Some(key) = file.keys().find(|key| !cli.contains_key(*key)) {
+        return Err(format!(
+            "unknown setting {} in `{}`",
+            serde_yaml::to_string(key).unwrap_or_default().trim(),
+            C::SECTION
+        ));
+    }
+
+    let given: HashSet<&str> = matches
+        .ids()
+        .map(|id| id.as_str())
+        .filter(|id| {
+            matches!(
+                matches.value_source(id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            )
+        })
+        .collect();
+    for (key, value) in cli {
+        let from_cli = key.as_str().is_some_and(|key| given.contains(key));
+        if from_cli || !file.contains_key(&key) {
+            file.insert(key, value);
+        }
+    }
+    serde_yaml::from_value(Value::Mapping(file)).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use serde::Deserialize;
+
+    #[derive(clap::Args, Serialize, Deserialize, Debug, PartialEq)]
+    struct Network {
+        /// Port
+        #[arg(long, env = "FIBER_CONFIG_TEST_PORT")]
+        port: Option<u16>,
+    }
+
+    #[derive(clap::Args, Serialize, Deserialize, Debug, PartialEq)]
+    struct TestConfig {
+        #[command(flatten)]
+        #[serde(flatten)]
+        network: Network,
+        #[arg(long, default_value = "Player")]
+        name: String,
+        #[arg(long, default_value_t = 2)]
+        players: usize,
+    }
+
+    impl ServiceConfig for TestConfig {
+        const SECTION: &'static str = "test";
+
+        fn validate(&self) -> Result<(), String> {
+            if self.players < 2 {
+                return Err("players must be at least 2".to_string());
+            }
+            Ok(())
+        }
+    }
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        config: TestConfig,
+    }
+
+    fn layered(args: &[&str], file: &str) -> Result<TestConfig, String> {
+        let matches = command::<Cli>()
+            .try_get_matches_from(std::iter::once("test").chain(args.iter().copied()))
+            .unwrap();
+        let cli = Cli::from_arg_matches(&matches).unwrap().config;
+        merge(cli, &matches, section_of(file, "test")?)
+    }
+
+    #[test]
+    fn test_file_overrides_defaults() {
+        let config = layered(&[], "test:\n  name: Alice\n  port: 4000\n").unwrap();
+        assert_eq!(config.name, "Alice");
+        assert_eq!(config.network.port, Some(4000));
+        assert_eq!(config.players, 2);
+    }
+
+    #[test]
+    fn test_flags_and_env_override_file() {
+        std::env::set_var("FIBER_CONFIG_TEST_PORT", "5000");
+        let config = layered(&["--name", "Bob"], "test:\n  name: Alice\n  port: 4000\n").unwrap();
+        std::env::remove_var("FIBER_CONFIG_TEST_PORT");
+        assert_eq!(config.name, "Bob");
+        assert_eq!(config.network.port, Some(5000));
+    }
+
+    #[test]
+    fn test_other_sections_and_empty_file_ignored() {
+        let config = layered(&[], "oracle:\n  port: 4000\n").unwrap();
+        assert_eq!(config.network.port, None);
+        assert_eq!(layered(&[], "").unwrap().name, "Player");
+    }
+
+    #[test]
+    fn test_bad_file_settings_rejected() {
+        let unknown = layered(&[], "test:\n  prot: 4000\n").unwrap_err();
+        assert!(unknown.contains("unknown setting prot"), "{}", unknown);
+        assert!(layered(&[], "test:\n  port: many\n").is_err());
+        assert!(layered(&[], "test: 3\n").is_err());
+    }
+
+    #[test]
+    fn test_validation_runs_on_resolved_settings() {
+        let matches = command::<Cli>()
+            .try_get_matches_from(["test", "--players", "1"])
+            .unwrap();
+        let cli = Cli::from_arg_matches(&matches).unwrap().config;
+        let err = resolve(cli, &matches).unwrap_err();
+        assert!(matches!(err, ConfigError::Invalid(_)));
+    }
+
+    #[test]
+    fn test_binary_keeps_its_about() {
+        /// The binary's own description
+        #[derive(Parser)]
+        struct Described {}
+
+        let about = command::<Described>().get_about().map(|a| a.to_string());
+        assert_eq!(about.as_deref(), Some("The binary's own description"));
+    }
+
+    #[test]
+    fn test_printed_config_is_a_config_file() {
+        let config = layered(&["--players", "3"], "").unwrap();
+        let yaml = to_yaml(&config);
+        assert_eq!(layered(&[], &yaml).unwrap(), config);
+    }
+
+    #[test]
+    fn test_check_url() {
+        assert!(check_url("oracle_url", "http://localhost:3000", &["http", "https"]).is_ok());
+        assert!(check_url("oracle_url", "localhost:3000", &["http", "https"]).is_err());
+        assert!(check_url("p2p_url", "http://x", &["ws", "wss"]).is_err());
+    }
+}
@@ -0,0 +1,118 @@
+//! Time source for everything that expires.
+//!
+//! Services and the mock client read the time through a [`Clock`] instead
+//! of asking the OS, so tests can swap in a [`TestClock`] and move time
+//! forward by hand rather than sleeping past a deadline.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Source of wall-clock time
+pub trait Clock: Send + Sync {
+    /// The current time
+    fn now(&self) -> SystemTime;
+
+    /// The current time in milliseconds since the Unix epoch
+    fn now_ms(&self) -> u64 {
+        self.now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default()
+    }
+
+    /// Time passed since `earlier`, or zero if it is in the future
+    fn since(&self, earlier: SystemTime) -> Duration {
+        self.now().duration_since(earlier).unwrap_or_default()
+    }
+}
+
+/// A clock shared between the parts of a service
+pub type SharedClock = Arc<dyn Clock>;
+
+/// The OS clock
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl SystemClock {
+    /// The OS clock, ready to share
+    pub fn shared() -> SharedClock {
+        Arc::new(SystemClock)
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time.
+#[derive(Clone, Debug)]
+pub struct TestClock {
+    now: Arc<Mutex<SystemTime>>,
+}
+
+impl TestClock {
+    /// Where [`TestClock::new`] starts: 2024-01-01T00:00:00Z
+    pub const START: Duration = Duration::from_secs(1_704_067_200);
+
+    /// A clock stopped at [`TestClock::START`]
+    pub fn new() -> Self {
+        Self::at(UNIX_EPOCH + Self::START)
+    }
+
+    /// A clock stopped at `now`
+    pub fn at(now: SystemTime) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    /// Move the clock forward by `by`
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap() += by;
+    }
+
+    /// Jump to `now`, which may be in the past
+    pub fn set(&self, now: SystemTime) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    /// This clock, ready to share
+    pub fn shared(&self) -> SharedClock {
+        Arc::new(self.clone())
+    }
+}
+
+impl Default for TestClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_only_moves_when_advanced() {
+        let clock = TestClock::new();
+        let start = clock.now();
+        assert_eq!(clock.now(), start);
+        assert_eq!(clock.now_ms(), TestClock::START.as_millis() as u64);
+
+        let shared = clock.shared();
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(shared.since(start), Duration::from_secs(90));
+
+        // Nothing has passed since a time still ahead
+        clock.set(start);
+        assert_eq!(shared.since(start + Duration::from_secs(1)), Duration::ZERO);
+    }
+}
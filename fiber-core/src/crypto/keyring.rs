@@ -0,0 +1,351 @@
+//! Envelope encryption for secrets kept at rest.
+//!
+//! Each value is encrypted with its own random data key, and the data key is
+//! encrypted ("wrapped") with a storage key from the [`Keyring`]. Rotating
+//! the storage key then only means rewrapping the data keys; the values
+//! themselves are left alone.
+//!
+//! A sealed value is text, so it fits the columns plaintext used to sit in:
+//!
+//! ```text
+//! sealed:v1:<key id>:<hex nonce + wrapped data key>:<hex nonce + ciphertext>
+//! ```
+//!
+//! Both layers are ChaCha20-Poly1305. The key ID names the storage key the
+//! data key is wrapped with, so a keyring holding old keys alongside the
+//! current one still opens values sealed before a rotation.
+
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::path::Path;
+
+/// Prefix every sealed value starts with
+const PREFIX: &str = "sealed:v1:";
+
+/// Storage keys are 32 random bytes
+pub const STORAGE_KEY_LEN: usize = 32;
+
+const NONCE_LEN: usize = 12;
+
+/// Errors from loading storage keys or opening sealed values
+#[derive(Debug, thiserror::Error)]
+pub enum KeyringError {
+    #[error("invalid storage key: {0}")]
+    InvalidKey(String),
+
+    #[error("no storage key configured")]
+    Empty,
+
+    #[error("failed to read storage key file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("sealed with storage key {0}, which is not in the keyring")]
+    UnknownKey(String),
+
+    #[error("malformed sealed value")]
+    Malformed,
+
+    #[error("sealed value failed to decrypt")]
+    Decryption,
+}
+
+/// A storage key, identified by a short hash of itself
+#[derive(Clone)]
+struct StorageKey {
+    id: String,
+    cipher: ChaCha20Poly1305,
+}
+
+impl StorageKey {
+    fn new(bytes: &[u8; STORAGE_KEY_LEN]) -> Self {
+        let id = Sha256::new()
+            .chain_update(b"fiber-storage-key")
+            .chain_update(bytes)
+            .finalize();
+        Self {
+            id: hex::encode(&id[..4]),
+            cipher: ChaCha20Poly1305::new(Key::from_slice(bytes)),
+        }
+    }
+}
+
+/// The current storage key, which seals, and any older ones, which only open
+#[derive(Clone)]
+pub struct Keyring {
+    /// Current key first
+    keys: Vec<StorageKey>,
+}
+
+impl Keyring {
+    /// A keyring sealing with `current`
+    pub fn new(current: [u8; STORAGE_KEY_LEN]) -> Self {
+        Self {
+            keys: vec![StorageKey::new(&current)],
+        }
+    }
+
+    /// A keyring with a fresh random key, for tests and throwaway stores
+    pub fn random() -> Self {
+        Self::new(ChaCha20Poly1305::generate_key(&mut OsRng).into())
+    }
+
+    /// Also open values sealed with `key`, a key rotated out
+    pub fn with_old_key(mut self, key: [u8; STORAGE_KEY_LEN]) -> Self {
+        self.keys.push(StorageKey::new(&key));
+        self
+    }
+
+    /// Parse hex keys separated by commas or whitespace, the current one
+    /// first. Lines starting with `#` are comments.
+    pub fn parse(text: &str) -> Result<Self, KeyringError> {
+        let mut keys = text
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .flat_map(|line| line.split(|c: char| c == ',' || c.is_whitespace()))
+            .filter(|key| !key.is_empty())
+            .map(parse_key);
+        let mut keyring = Self::new(keys.next().ok_or(KeyringError::Empty)??);
+        for key in keys {
+            keyring = keyring.with_old_key(key?);
+        }
+        Ok(keyring)
+    }
+
+    /// The keyring configured by `keys` (as for [`Keyring::parse`]) or
+    /// else the key file at `file`; `None` if neither is set
+    pub fn load(keys: Option<&str>, file: Option<&Path>) -> Result<Option<Self>, KeyringError> {
+        match (keys, file) {
+            (Some(keys), _) => Self::parse(keys).map(Some),
+            (None, Some(file)) => Self::parse(&std::fs::read_to_string(file)?).map(Some),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// ID of the key new values are sealed with
+    pub fn current_id(&self) -> &str {
+        &self.keys[0].id
+    }
+
+    /// Whether `value` is sealed at all, rather than plaintext
+    pub fn is_sealed(value: &str) -> bool {
+        value.starts_with(PREFIX)
+    }
+
+    /// Whether `value` is sealed with the current key
+    pub fn is_current(&self, value: &str) -> bool {
+        Envelope::parse(value).is_ok_and(|envelope| envelope.key_id == self.current_id())
+    }
+
+    /// Encrypt `plaintext` under a fresh data key wrapped with the current key
+    pub fn seal(&self, plaintext: &[u8]) -> String {
+        let data_key = ChaCha20Poly1305::generate_key(&mut OsRng);
+        let data = encrypt(&ChaCha20Poly1305::new(&data_key), plaintext);
+        let wrapped = encrypt(&self.keys[0].cipher, &data_key);
+        Envelope {
+            key_id: self.current_id(),
+            wrapped,
+            data,
+        }
+        .to_string()
+    }
+
+    /// Decrypt a value [`Keyring::seal`] produced with any key in the keyring
+    pub fn open(&self, value: &str) -> Result<Vec<u8>, KeyringError> {
+        let envelope = Envelope::parse(value)?;
+        decrypt(&self.data_cipher(&envelope)?, &envelope.data)
+    }
+
+    /// `value` with its data key rewrapped under the current key; the
+    /// ciphertext is unchanged
+    pub fn rewrap(&self, value: &str) -> Result<String, KeyringError> {
+        let envelope = Envelope::parse(value)?;
+        let data_key = self.unwrap_data_key(&envelope)?;
+        Ok(Envelope {
+            key_id: self.current_id(),
+            wrapped: encrypt(&self.keys[0].cipher, &data_key),
+            data: envelope.data,
+        }
+        .to_string())
+    }
+
+    fn unwrap_data_key(&self, envelope: &Envelope) -> Result<Vec<u8>, KeyringError> {
+        let key = self
+            .keys
+            .iter()
+            .find(|key| key.id == envelope.key_id)
+            .ok_or_else(|| KeyringError::UnknownKey(envelope.key_id.to_string()))?;
+        let data_key = decrypt(&key.cipher, &envelope.wrapped)?;
+        if data_key.len() != STORAGE_KEY_LEN {
+            return Err(KeyringError::Malformed);
+        }
+        Ok(data_key)
+    }
+
+    fn data_cipher(&self, envelope: &Envelope) -> Result<ChaCha20Poly1305, KeyringError> {
+        Ok(ChaCha20Poly1305::new(Key::from_slice(
+            &self.unwrap_data_key(envelope)?,
+        )))
+    }
+}
+
+impl fmt::Debug for Keyring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ids: Vec<_> = self.keys.iter().map(|key| key.id.as_str()).collect();
+        f.debug_struct("Keyring").field("keys", &ids).finish()
+    }
+}
+
+fn parse_key(hex_key: &str) -> Result<[u8; STORAGE_KEY_LEN], KeyringError> {
+    let bytes = hex::decode(hex_key.trim_start_matches("0x"))
+        .map_err(|e| KeyringError::InvalidKey(e.to_string()))?;
+    bytes.try_into().map_err(|bytes: Vec<u8>| {
+        KeyringError::InvalidKey(format!(
+            "expected {} bytes, got {}",
+            STORAGE_KEY_LEN,
+            bytes.len()
+        ))
+    })
+}
+
+/// The parts of a sealed value; nonces are kept in front of each ciphertext
+struct Envelope<'a> {
+    key_id: &'a str,
+    wrapped: Vec<u8>,
+    data: Vec<u8>,
+}
+
+impl<'a> Envelope<'a> {
+    fn parse(value: &'a str) -> Result<Self, KeyringError> {
+        let rest = value.strip_prefix(PREFIX).ok_or(KeyringError::Malformed)?;
+        let mut parts = rest.split(':');
+        let (Some(key_id), Some(wrapped), Some(data), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(KeyringError::Malformed);
+        };
+        Ok(Self {
+            key_id,
+            wrapped: hex::decode(wrapped).map_err(|_| KeyringError::Malformed)?,
+            data: hex::decode(data).map_err(|_| KeyringError::Malformed)?,
+        })
+    }
+}
+
+impl fmt::Display for Envelope<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}:{}:{}",
+            PREFIX,
+            self.key_id,
+            hex::encode(&self.wrapped),
+            hex::encode(&self.data)
+        )
+    }
+}
+
+fn encrypt(cipher: &ChaCha20Poly1305, plaintext: &[u8]) -> Vec<u8> {
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .expect("ChaCha20-Poly1305 encryption does not fail");
+    [nonce.as_slice(), &ciphertext].concat()
+}
+
+fn decrypt(cipher: &ChaCha20Poly1305, sealed: &[u8]) -> Result<Vec<u8>, KeyringError> {
+    if sealed.len() < NONCE_LEN {
+        return Err(KeyringError::Malformed);
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| KeyringError::Decryption)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_roundtrip() {
+        let keyring = Keyring::random();
+        let sealed = keyring.seal(b"salt and preimage");
+        assert!(Keyring::is_sealed(&sealed));
+        assert!(keyring.is_current(&sealed));
+        assert!(!sealed.contains(&hex::encode(b"salt and preimage")));
+        assert_eq!(keyring.open(&sealed).unwrap(), b"salt and preimage");
+
+        // Fresh data key and nonces every time
+        assert_ne!(keyring.seal(b"salt and preimage"), sealed);
+
+        let other = Keyring::random();
+        assert!(matches!(
+            other.open(&sealed),
+            Err(KeyringError::UnknownKey(_))
+        ));
+    }
+
+    #[test]
+    fn test_rotation_rewraps_under_the_new_key() {
+        let old = [1u8; STORAGE_KEY_LEN];
+        let sealed = Keyring::new(old).seal(b"secret");
+
+        let rotated = Keyring::new([2u8; STORAGE_KEY_LEN]).with_old_key(old);
+        assert!(!rotated.is_current(&sealed));
+        assert_eq!(rotated.open(&sealed).unwrap(), b"secret");
+
+        let rewrapped = rotated.rewrap(&sealed).unwrap();
+        assert!(rotated.is_current(&rewrapped));
+        // Only the wrapped data key changes
+        assert_eq!(rewrapped.rsplit(':').next(), sealed.rsplit(':').next());
+        let new_only = Keyring::new([2u8; STORAGE_KEY_LEN]);
+        assert_eq!(new_only.open(&rewrapped).unwrap(), b"secret");
+    }
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let keyring = Keyring::random();
+        let sealed = keyring.seal(b"secret");
+        let mut bytes = sealed.into_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] = if bytes[last] == b'0' { b'1' } else { b'0' };
+        let tampered = String::from_utf8(bytes).unwrap();
+        assert!(matches!(
+            keyring.open(&tampered),
+            Err(KeyringError::Decryption)
+        ));
+        assert!(matches!(
+            keyring.open("plain"),
+            Err(KeyringError::Malformed)
+        ));
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        let current = hex::encode([1u8; STORAGE_KEY_LEN]);
+        let old = hex::encode([2u8; STORAGE_KEY_LEN]);
+        let keyring =
+            Keyring::parse(&format!("# rotated 2026-10\n{}\n{}\n", current, old)).unwrap();
+        assert_eq!(keyring.keys.len(), 2);
+        assert_eq!(
+            keyring.current_id(),
+            Keyring::new([1u8; STORAGE_KEY_LEN]).current_id()
+        );
+        assert_eq!(
+            Keyring::parse(&format!("{},{}", current, old))
+                .unwrap()
+                .keys
+                .len(),
+            2
+        );
+
+        assert!(matches!(Keyring::parse(""), Err(KeyringError::Empty)));
+        assert!(matches!(
+            Keyring::parse("abcd"),
+            Err(KeyringError::InvalidKey(_))
+        ));
+        assert!(Keyring::load(None, None).unwrap().is_none());
+    }
+}
@@ -1,5 +1,7 @@
 //! Cryptographic primitives for Fiber Network.
 
+mod keyring;
 mod payment;
 
+pub use keyring::{Keyring, KeyringError, STORAGE_KEY_LEN};
 pub use payment::{PaymentHash, Preimage};
@@ -1,6 +1,6 @@
 //! Preimage and PaymentHash for hold invoices.
 
-use blake2b_rs::Blake2bBuilder;
+use blake2b_simd::Params;
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use std::fmt;
@@ -10,12 +10,12 @@ const CKB_HASH_PERSONALIZATION: &[u8] = b"ckb-default-hash";
 
 /// Create a CKB hash (Blake2b-256 with CKB personalization)
 fn ckb_hash(data: &[u8]) -> [u8; 32] {
-    let mut blake2b = Blake2bBuilder::new(32)
+    let digest = Params::new()
+        .hash_length(32)
         .personal(CKB_HASH_PERSONALIZATION)
-        .build();
-    blake2b.update(data);
+        .hash(data);
     let mut hash = [0u8; 32];
-    blake2b.finalize(&mut hash);
+    hash.copy_from_slice(digest.as_bytes());
     hash
 }
 
@@ -102,6 +102,11 @@ impl PaymentHash {
         format!("0x{}", hex::encode(&self.0))
     }
 
+    /// First 8 bytes in hex: enough to tell payments apart in logs
+    pub fn short_hex(&self) -> String {
+        hex::encode(&self.0[..8])
+    }
+
     /// Verify that a preimage matches this hash
     pub fn verify(&self, preimage: &Preimage) -> bool {
         preimage.payment_hash() == *self
@@ -110,7 +115,7 @@ impl PaymentHash {
 
 impl fmt::Debug for PaymentHash {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "PaymentHash({})", hex::encode(&self.0[..8]))
+        write!(f, "PaymentHash({})", self.short_hex())
     }
 }
 
@@ -140,6 +145,19 @@ mod tests {
         assert_ne!(preimage1.payment_hash(), preimage2.payment_hash());
     }
 
+    #[test]
+    fn test_hash_matches_ckb() {
+        // CKB's blake2b_256 of the empty message and of 32 zero bytes
+        assert_eq!(
+            hex::encode(ckb_hash(&[])),
+            "44f4c69744d5f8c55d642062949dcae49bc4e7ef43d388c5a12f42b5633d163e"
+        );
+        assert_eq!(
+            Preimage::from_bytes([0; 32]).payment_hash().to_hex(),
+            "0x266cec97cbede2cfbce73666f08deed9560bdf7841a7a5a51b3a3f09da249e21"
+        );
+    }
+
     #[test]
     fn test_wrong_preimage_fails_verification() {
         let preimage1 = Preimage::random();
@@ -148,4 +166,64 @@ mod tests {
 
         assert!(!hash1.verify(&preimage2));
     }
+
+    mod properties {
+        use super::*;
+        use proptest::prelude::*;
+
+        proptest! {
+            #[test]
+            fn test_preimage_hex_roundtrip(bytes in any::<[u8; 32]>()) {
+                let preimage = Preimage::from_bytes(bytes);
+                let parsed = Preimage::from_hex(&preimage.to_hex()).unwrap();
+                prop_assert_eq!(parsed.as_bytes(), &bytes);
+                prop_assert_eq!(parsed.payment_hash(), preimage.payment_hash());
+            }
+
+            #[test]
+            fn test_payment_hash_hex_roundtrip(bytes in any::<[u8; 32]>()) {
+                let hash = PaymentHash::from_bytes(bytes);
+                prop_assert_eq!(PaymentHash::from_hex(&hash.to_hex()).unwrap(), hash);
+                // Display has no prefix and still parses
+                prop_assert_eq!(PaymentHash::from_hex(&hash.to_string()).unwrap(), hash);
+            }
+
+            #[test]
+            fn test_hex_parsing_ignores_case(bytes in any::<[u8; 32]>()) {
+                let upper = hex::encode_upper(bytes);
+                prop_assert_eq!(*Preimage::from_hex(&upper).unwrap().as_bytes(), bytes);
+                prop_assert_eq!(
+                    PaymentHash::from_hex(&format!("0x{}", upper)).unwrap(),
+                    PaymentHash::from_bytes(bytes)
+                );
+            }
+
+            #[test]
+            fn test_hex_parsing_rejects_wrong_length(
+                bytes in prop::collection::vec(any::<u8>(), 0..64)
+                    .prop_filter("not 32 bytes", |b| b.len() != 32),
+            ) {
+                let s = hex::encode(&bytes);
+                prop_assert_eq!(
+                    Preimage::from_hex(&s).unwrap_err(),
+                    hex::FromHexError::InvalidStringLength
+                );
+                prop_assert_eq!(
+                    PaymentHash::from_hex(&format!("0x{}", s)).unwrap_err(),
+                    hex::FromHexError::InvalidStringLength
+                );
+            }
+
+            #[test]
+            fn test_hex_parsing_rejects_malformed(s in "(0x)?[0-9a-fA-FxXg ]{0,66}") {
+                // Exactly one optional prefix followed by 64 hex digits is the
+                // only accepted shape
+                let digits = s.strip_prefix("0x").unwrap_or(&s);
+                let well_formed =
+                    digits.len() == 64 && digits.chars().all(|c| c.is_ascii_hexdigit());
+                prop_assert_eq!(Preimage::from_hex(&s).is_ok(), well_formed);
+                prop_assert_eq!(PaymentHash::from_hex(&s).is_ok(), well_formed);
+            }
+        }
+    }
 }
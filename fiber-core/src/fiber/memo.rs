@@ -0,0 +1,72 @@
+//! Tagging invoices with the game or order they pay for.
+//!
+//! A node only knows an invoice by its payment hash, so an operator looking
+//! at one it still holds has no way back to the game or order it was made
+//! for. Invoices created by the demos therefore carry an [`InvoiceMemo`] in
+//! their description, e.g. `Fiber Game stake [fiber-game:<game id>]`, which
+//! [`InvoiceMemo::find`] reads back out of whatever the node returns.
+
+use std::fmt;
+use uuid::Uuid;
+
+const GAME_TAG: &str = "fiber-game:";
+const ORDER_TAG: &str = "fiber-escrow:";
+
+/// The application entity an invoice pays for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum InvoiceMemo {
+    /// A stake in a Fiber Game
+    Game(Uuid),
+    /// An escrow order
+    Order(Uuid),
+}
+
+impl InvoiceMemo {
+    /// A description for an invoice that names this entity
+    pub fn description(&self) -> String {
+        match self {
+            Self::Game(_) => format!("Fiber Game stake [{}]", self),
+            Self::Order(_) => format!("Fiber Escrow order [{}]", self),
+        }
+    }
+
+    /// The memo in an invoice's `description`, wherever it appears
+    pub fn find(description: &str) -> Option<Self> {
+        [
+            (GAME_TAG, Self::Game as fn(Uuid) -> Self),
+            (ORDER_TAG, Self::Order),
+        ]
+        .into_iter()
+        .find_map(|(tag, memo)| {
+            let start = description.find(tag)? + tag.len();
+            let id = description.get(start..start + 36)?;
+            Uuid::parse_str(id).ok().map(memo)
+        })
+    }
+}
+
+impl fmt::Display for InvoiceMemo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Game(id) => write!(f, "{}{}", GAME_TAG, id),
+            Self::Order(id) => write!(f, "{}{}", ORDER_TAG, id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memo_round_trips_through_a_description() {
+        for memo in [InvoiceMemo::Game(Uuid::new_v4()), InvoiceMemo::Order(Uuid::new_v4())] {
+            assert_eq!(InvoiceMemo::find(&memo.description()), Some(memo));
+            // Whatever the frontend wrapped it in
+            assert_eq!(InvoiceMemo::find(&format!("Stake ({})", memo)), Some(memo));
+        }
+        assert_eq!(InvoiceMemo::find("Fiber Escrow Payment"), None);
+        assert_eq!(InvoiceMemo::find("fiber-game:not-a-uuid"), None);
+        assert_eq!(InvoiceMemo::find("fiber-escrow:"), None);
+    }
+}
@@ -2,10 +2,12 @@
 
 use super::traits::{FiberClient, FiberError, HoldInvoice, PaymentId, PaymentStatus};
 use async_trait::async_trait;
+use crate::clock::{Clock, SharedClock, SystemClock};
 use crate::crypto::{PaymentHash, Preimage};
-use std::collections::HashMap;
+use crate::money::{self, MoneyError};
+use std::collections::{HashMap, VecDeque};
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
+use std::time::{Duration, SystemTime};
 
 /// State of a mock invoice
 #[derive(Clone, Debug)]
@@ -14,13 +16,16 @@ struct MockInvoiceState {
     payment_hash: PaymentHash,
     amount: u64,
     status: PaymentStatus,
-    created_at: Instant,
+    created_at: SystemTime,
     expiry_secs: u64,
+    /// Held until settled or cancelled, rather than settled on payment
+    hold: bool,
+    description: Option<String>,
 }
 
 impl MockInvoiceState {
-    fn is_expired(&self) -> bool {
-        self.created_at.elapsed() > Duration::from_secs(self.expiry_secs)
+    fn is_expired(&self, clock: &dyn Clock) -> bool {
+        clock.since(self.created_at) > Duration::from_secs(self.expiry_secs)
     }
 }
 
@@ -33,6 +38,34 @@ pub struct MockFiberClient {
     preimages: Arc<Mutex<HashMap<PaymentHash, Preimage>>>,
     /// Simulated balance
     balance: Arc<Mutex<u64>>,
+    /// Simulated inbound capacity; `None` receives any amount
+    inbound_capacity: Arc<Mutex<Option<u64>>>,
+    /// Failures queued per call by `fail_next` and `lose_next_response`
+    faults: Arc<Mutex<HashMap<MockCall, VecDeque<Fault>>>>,
+    /// What invoice expiry is measured against
+    clock: SharedClock,
+}
+
+/// A [`FiberClient`] call on [`MockFiberClient`], for injecting failures
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MockCall {
+    CreateHoldInvoice,
+    CreateInvoice,
+    PayHoldInvoice,
+    SettleInvoice,
+    CancelInvoice,
+    GetPaymentStatus,
+    GetInvoiceDescription,
+    GetBalance,
+    GetInboundCapacity,
+}
+
+/// An injected failure
+enum Fault {
+    /// The node refuses the call; nothing changes
+    Reject(FiberError),
+    /// The node carries the call out, but the caller only sees an error
+    LoseResponse(FiberError),
 }
 
 impl MockFiberClient {
@@ -42,16 +75,78 @@ impl MockFiberClient {
             invoices: Arc::new(Mutex::new(HashMap::new())),
             preimages: Arc::new(Mutex::new(HashMap::new())),
             balance: Arc::new(Mutex::new(initial_balance)),
+            inbound_capacity: Arc::new(Mutex::new(None)),
+            faults: Arc::new(Mutex::new(HashMap::new())),
+            clock: SystemClock::shared(),
         }
     }
 
+    /// Measure invoice expiry against `clock` instead of the OS clock
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Make the next `call` fail with `error` without any effect, as when
+    /// the node is unreachable or refuses the request.
+    ///
+    /// Failures queue up: calling this twice fails the next two calls.
+    pub fn fail_next(&self, call: MockCall, error: FiberError) {
+        self.push_fault(call, Fault::Reject(error));
+    }
+
+    /// Carry out the next `call` but return `error` to the caller, as when
+    /// the connection drops after the node acted on the request. The caller
+    /// can only find out what happened by asking again.
+    pub fn lose_next_response(&self, call: MockCall, error: FiberError) {
+        self.push_fault(call, Fault::LoseResponse(error));
+    }
+
+    fn push_fault(&self, call: MockCall, fault: Fault) {
+        self.faults
+            .lock()
+            .unwrap()
+            .entry(call)
+            .or_default()
+            .push_back(fault);
+    }
+
+    /// Run `op` for `call`, or fail it as queued by `fail_next` or
+    /// `lose_next_response`.
+    fn with_faults<T>(
+        &self,
+        call: MockCall,
+        op: impl FnOnce() -> Result<T, FiberError>,
+    ) -> Result<T, FiberError> {
+        let fault = self
+            .faults
+            .lock()
+            .unwrap()
+            .get_mut(&call)
+            .and_then(VecDeque::pop_front);
+        match fault {
+            None => op(),
+            Some(Fault::Reject(error)) => Err(error),
+            Some(Fault::LoseResponse(error)) => {
+                let _ = op();
+                Err(error)
+            }
+        }
+    }
+
+    /// Limit what our channels can receive, so invoices for more fail
+    /// with [`FiberError::InsufficientInboundCapacity`]
+    pub fn set_inbound_capacity(&self, capacity: u64) {
+        *self.inbound_capacity.lock().unwrap() = Some(capacity);
+    }
+
     /// Get current balance
     pub fn balance(&self) -> u64 {
         *self.balance.lock().unwrap()
     }
 
-    /// Register a preimage for an invoice we created
-    /// This is called internally when we create an invoice
+    /// Register a preimage for an invoice we created, so that paying it
+    /// settles it on the spot. `create_invoice` does this for its preimage.
     pub fn register_preimage(&self, preimage: Preimage) {
         let payment_hash = preimage.payment_hash();
         self.preimages.lock().unwrap().insert(payment_hash, preimage);
@@ -67,15 +162,51 @@ impl MockFiberClient {
             .collect()
     }
 
+    /// Both ways of creating a hold invoice, which the node doesn't tell
+    /// apart
+    fn hold_invoice(
+        &self,
+        payment_hash: &PaymentHash,
+        amount: u64,
+        expiry_secs: u64,
+        description: Option<&str>,
+    ) -> Result<HoldInvoice, FiberError> {
+        self.with_faults(MockCall::CreateHoldInvoice, || {
+            if let Some(available) = *self.inbound_capacity.lock().unwrap() {
+                if available < amount {
+                    return Err(FiberError::InsufficientInboundCapacity {
+                        needed: amount,
+                        available,
+                    });
+                }
+            }
+            let state = MockInvoiceState {
+                payment_hash: *payment_hash,
+                amount,
+                status: PaymentStatus::Pending,
+                created_at: self.clock.now(),
+                expiry_secs,
+                hold: true,
+                description: description.map(str::to_string),
+            };
+
+            self.invoices.lock().unwrap().insert(*payment_hash, state);
+
+            Ok(HoldInvoice {
+                payment_hash: *payment_hash,
+                amount,
+                expiry_secs,
+                invoice_string: format!("mock_invoice_{}", hex::encode(payment_hash.as_bytes())),
+            })
+        })
+    }
+
     /// Adjust balance by the given amount (can be positive or negative)
     /// Used for settlement simulation
-    pub fn adjust_balance(&self, amount: i64) {
+    pub fn adjust_balance(&self, amount: i64) -> Result<(), MoneyError> {
         let mut balance = self.balance.lock().unwrap();
-        if amount >= 0 {
-            *balance = balance.saturating_add(amount as u64);
-        } else {
-            *balance = balance.saturating_sub((-amount) as u64);
-        }
+        *balance = money::apply(*balance, amount)?;
+        Ok(())
     }
 }
 
@@ -91,51 +222,88 @@ impl FiberClient for MockFiberClient {
         amount: u64,
         expiry_secs: u64,
     ) -> Result<HoldInvoice, FiberError> {
-        let state = MockInvoiceState {
-            payment_hash: *payment_hash,
-            amount,
-            status: PaymentStatus::Pending,
-            created_at: Instant::now(),
-            expiry_secs,
-        };
-
-        self.invoices.lock().unwrap().insert(*payment_hash, state);
-
-        Ok(HoldInvoice {
-            payment_hash: *payment_hash,
-            amount,
-            expiry_secs,
-            invoice_string: format!("mock_invoice_{}", hex::encode(payment_hash.as_bytes())),
+        self.hold_invoice(payment_hash, amount, expiry_secs, None)
+    }
+
+    async fn create_described_hold_invoice(
+        &self,
+        payment_hash: &PaymentHash,
+        amount: u64,
+        expiry_secs: u64,
+        description: &str,
+    ) -> Result<HoldInvoice, FiberError> {
+        self.hold_invoice(payment_hash, amount, expiry_secs, Some(description))
+    }
+
+    async fn create_invoice(
+        &self,
+        amount: u64,
+        expiry_secs: u64,
+        preimage: Option<&Preimage>,
+    ) -> Result<HoldInvoice, FiberError> {
+        self.with_faults(MockCall::CreateInvoice, || {
+            let preimage = preimage.cloned().unwrap_or_else(Preimage::random);
+            let payment_hash = preimage.payment_hash();
+            self.register_preimage(preimage);
+
+            let state = MockInvoiceState {
+                payment_hash,
+                amount,
+                status: PaymentStatus::Pending,
+                created_at: self.clock.now(),
+                expiry_secs,
+                hold: false,
+                description: None,
+            };
+            self.invoices.lock().unwrap().insert(payment_hash, state);
+
+            Ok(HoldInvoice {
+                payment_hash,
+                amount,
+                expiry_secs,
+                invoice_string: format!("mock_invoice_{}", hex::encode(payment_hash.as_bytes())),
+            })
         })
     }
 
     async fn pay_hold_invoice(&self, invoice: &HoldInvoice) -> Result<PaymentId, FiberError> {
-        // Check balance
-        {
-            let balance = self.balance.lock().unwrap();
-            if *balance < invoice.amount {
-                return Err(FiberError::InsufficientFunds);
-            }
-        }
-
-        // Deduct balance (locked)
-        {
+        self.with_faults(MockCall::PayHoldInvoice, || {
+            let mut invoices = self.invoices.lock().unwrap();
             let mut balance = self.balance.lock().unwrap();
-            *balance -= invoice.amount;
-        }
 
-        // Update invoice status to Held
-        {
-            let mut invoices = self.invoices.lock().unwrap();
             if let Some(state) = invoices.get_mut(&invoice.payment_hash) {
-                if state.is_expired() {
-                    // Refund
-                    let mut balance = self.balance.lock().unwrap();
-                    *balance += invoice.amount;
+                // An invoice can only be paid once
+                match state.status {
+                    PaymentStatus::Pending => {}
+                    PaymentStatus::Held | PaymentStatus::Settled => {
+                        return Err(FiberError::PaymentFailed(
+                            "Invoice already paid".to_string(),
+                        ))
+                    }
+                    PaymentStatus::Cancelled => return Err(FiberError::AlreadyCancelled),
+                }
+                if state.is_expired(self.clock.as_ref()) {
                     return Err(FiberError::Expired);
                 }
+                if *balance < invoice.amount {
+                    return Err(FiberError::InsufficientFunds);
+                }
+                let settles = !state.hold
+                    && self
+                        .preimages
+                        .lock()
+                        .unwrap()
+                        .contains_key(&invoice.payment_hash);
+                if settles {
+                    // Paid out to us straight away, as settling would
+                    state.status = PaymentStatus::Settled;
+                    return Ok(PaymentId::new());
+                }
                 state.status = PaymentStatus::Held;
             } else {
+                if *balance < invoice.amount {
+                    return Err(FiberError::InsufficientFunds);
+                }
                 // Create state for remote invoice
                 invoices.insert(
                     invoice.payment_hash,
@@ -143,14 +311,18 @@ impl FiberClient for MockFiberClient {
                         payment_hash: invoice.payment_hash,
                         amount: invoice.amount,
                         status: PaymentStatus::Held,
-                        created_at: Instant::now(),
+                        created_at: self.clock.now(),
                         expiry_secs: invoice.expiry_secs,
+                        hold: true,
+                        description: None,
                     },
                 );
             }
-        }
 
-        Ok(PaymentId::new())
+            // Funds are locked until the invoice is settled or cancelled
+            *balance = money::sub(*balance, invoice.amount)?;
+            Ok(PaymentId::new())
+        })
     }
 
     async fn settle_invoice(
@@ -158,76 +330,102 @@ impl FiberClient for MockFiberClient {
         payment_hash: &PaymentHash,
         preimage: &Preimage,
     ) -> Result<(), FiberError> {
-        // Verify preimage
-        if !payment_hash.verify(preimage) {
-            return Err(FiberError::InvalidPreimage);
-        }
-
-        let mut invoices = self.invoices.lock().unwrap();
-        let state = invoices
-            .get_mut(payment_hash)
-            .ok_or_else(|| FiberError::InvoiceNotFound(*payment_hash))?;
-
-        match state.status {
-            PaymentStatus::Pending => {
-                // Can't settle a pending invoice (not paid yet)
-                Err(FiberError::PaymentFailed(
-                    "Invoice not yet paid".to_string(),
-                ))
+        self.with_faults(MockCall::SettleInvoice, || {
+            // Verify preimage
+            if !payment_hash.verify(preimage) {
+                return Err(FiberError::InvalidPreimage);
             }
-            PaymentStatus::Held => {
-                // Add funds to our balance (we're the receiver settling)
-                let mut balance = self.balance.lock().unwrap();
-                *balance += state.amount;
-                state.status = PaymentStatus::Settled;
-                Ok(())
+
+            let mut invoices = self.invoices.lock().unwrap();
+            let state = invoices
+                .get_mut(payment_hash)
+                .ok_or_else(|| FiberError::InvoiceNotFound(*payment_hash))?;
+
+            match state.status {
+                PaymentStatus::Pending => {
+                    // Can't settle a pending invoice (not paid yet)
+                    Err(FiberError::PaymentFailed(
+                        "Invoice not yet paid".to_string(),
+                    ))
+                }
+                PaymentStatus::Held => {
+                    // Add funds to our balance (we're the receiver settling)
+                    let mut balance = self.balance.lock().unwrap();
+                    *balance = money::add(*balance, state.amount)?;
+                    state.status = PaymentStatus::Settled;
+                    Ok(())
+                }
+                PaymentStatus::Settled => Err(FiberError::AlreadySettled),
+                PaymentStatus::Cancelled => Err(FiberError::AlreadyCancelled),
             }
-            PaymentStatus::Settled => Err(FiberError::AlreadySettled),
-            PaymentStatus::Cancelled => Err(FiberError::AlreadyCancelled),
-        }
+        })
     }
 
     async fn cancel_invoice(&self, payment_hash: &PaymentHash) -> Result<(), FiberError> {
-        let mut invoices = self.invoices.lock().unwrap();
-        let state = invoices
-            .get_mut(payment_hash)
-            .ok_or_else(|| FiberError::InvoiceNotFound(*payment_hash))?;
-
-        match state.status {
-            PaymentStatus::Pending | PaymentStatus::Held => {
-                // Refund is handled by the payer side
-                state.status = PaymentStatus::Cancelled;
-                Ok(())
+        self.with_faults(MockCall::CancelInvoice, || {
+            let mut invoices = self.invoices.lock().unwrap();
+            let state = invoices
+                .get_mut(payment_hash)
+                .ok_or_else(|| FiberError::InvoiceNotFound(*payment_hash))?;
+
+            match state.status {
+                PaymentStatus::Pending | PaymentStatus::Held => {
+                    // Refund is handled by the payer side
+                    state.status = PaymentStatus::Cancelled;
+                    Ok(())
+                }
+                PaymentStatus::Settled => Err(FiberError::AlreadySettled),
+                PaymentStatus::Cancelled => Err(FiberError::AlreadyCancelled),
             }
-            PaymentStatus::Settled => Err(FiberError::AlreadySettled),
-            PaymentStatus::Cancelled => Err(FiberError::AlreadyCancelled),
-        }
+        })
     }
 
     async fn get_payment_status(
         &self,
         payment_hash: &PaymentHash,
     ) -> Result<PaymentStatus, FiberError> {
-        let invoices = self.invoices.lock().unwrap();
-        let state = invoices
-            .get(payment_hash)
-            .ok_or_else(|| FiberError::InvoiceNotFound(*payment_hash))?;
+        self.with_faults(MockCall::GetPaymentStatus, || {
+            let invoices = self.invoices.lock().unwrap();
+            let state = invoices
+                .get(payment_hash)
+                .ok_or_else(|| FiberError::InvoiceNotFound(*payment_hash))?;
+
+            if state.is_expired(self.clock.as_ref()) && state.status == PaymentStatus::Pending {
+                return Ok(PaymentStatus::Cancelled);
+            }
 
-        if state.is_expired() && state.status == PaymentStatus::Pending {
-            return Ok(PaymentStatus::Cancelled);
-        }
+            Ok(state.status)
+        })
+    }
 
-        Ok(state.status)
+    async fn get_invoice_description(
+        &self,
+        payment_hash: &PaymentHash,
+    ) -> Result<Option<String>, FiberError> {
+        self.with_faults(MockCall::GetInvoiceDescription, || {
+            let invoices = self.invoices.lock().unwrap();
+            let state = invoices
+                .get(payment_hash)
+                .ok_or(FiberError::InvoiceNotFound(*payment_hash))?;
+            Ok(state.description.clone())
+        })
     }
 
     async fn get_balance(&self) -> Result<u64, FiberError> {
-        Ok(self.balance())
+        self.with_faults(MockCall::GetBalance, || Ok(self.balance()))
+    }
+
+    async fn get_inbound_capacity(&self) -> Result<u64, FiberError> {
+        self.with_faults(MockCall::GetInboundCapacity, || {
+            Ok(self.inbound_capacity.lock().unwrap().unwrap_or(u64::MAX))
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::TestClock;
 
     #[tokio::test]
     async fn test_hold_invoice_lifecycle() {
@@ -359,6 +557,28 @@ mod tests {
         assert!(matches!(result, Err(FiberError::InsufficientFunds)));
     }
 
+    #[tokio::test]
+    async fn test_hold_invoice_needs_inbound_capacity() {
+        let client = MockFiberClient::new(10000);
+        client.set_inbound_capacity(500);
+
+        let payment_hash = Preimage::random().payment_hash();
+        let result = client.create_hold_invoice(&payment_hash, 1000, 3600).await;
+        assert!(matches!(
+            result,
+            Err(FiberError::InsufficientInboundCapacity {
+                needed: 1000,
+                available: 500
+            })
+        ));
+        assert!(client.get_all_invoices().is_empty());
+
+        client
+            .create_hold_invoice(&payment_hash, 500, 3600)
+            .await
+            .unwrap();
+    }
+
     #[tokio::test]
     async fn test_double_settle_fails() {
         let client = MockFiberClient::new(10000);
@@ -378,4 +598,155 @@ mod tests {
         let result = client.settle_invoice(&payment_hash, &preimage).await;
         assert!(matches!(result, Err(FiberError::AlreadySettled)));
     }
+
+    #[tokio::test]
+    async fn test_invoice_is_paid_once() {
+        let client = MockFiberClient::new(10000);
+        let payment_hash = Preimage::random().payment_hash();
+        let invoice = client
+            .create_hold_invoice(&payment_hash, 1000, 3600)
+            .await
+            .unwrap();
+
+        client.pay_hold_invoice(&invoice).await.unwrap();
+        let result = client.pay_hold_invoice(&invoice).await;
+        assert!(matches!(result, Err(FiberError::PaymentFailed(_))));
+        assert_eq!(client.balance(), 9000);
+
+        client.cancel_invoice(&payment_hash).await.unwrap();
+        let result = client.pay_hold_invoice(&invoice).await;
+        assert!(matches!(result, Err(FiberError::AlreadyCancelled)));
+        assert_eq!(client.balance(), 9000);
+    }
+
+    #[tokio::test]
+    async fn test_standard_invoice_settles_with_registered_preimage() {
+        let client = MockFiberClient::new(10000);
+        let preimage = Preimage::random();
+
+        let invoice = client
+            .create_invoice(1000, 3600, Some(&preimage))
+            .await
+            .unwrap();
+        assert_eq!(invoice.payment_hash, preimage.payment_hash());
+        assert_eq!(
+            client.get_payment_status(&invoice.payment_hash).await.unwrap(),
+            PaymentStatus::Pending
+        );
+
+        // Paying settles it at once, so there is nothing left to settle
+        client.pay_hold_invoice(&invoice).await.unwrap();
+        assert_eq!(
+            client.get_payment_status(&invoice.payment_hash).await.unwrap(),
+            PaymentStatus::Settled
+        );
+        assert_eq!(client.balance(), 10000);
+        let result = client.settle_invoice(&invoice.payment_hash, &preimage).await;
+        assert!(matches!(result, Err(FiberError::AlreadySettled)));
+
+        // Without a preimage the client picks its own
+        let other = client.create_invoice(1000, 3600, None).await.unwrap();
+        assert_ne!(other.payment_hash, invoice.payment_hash);
+    }
+
+    #[tokio::test]
+    async fn test_fail_next_has_no_effect() {
+        let client = MockFiberClient::new(10000);
+        let payment_hash = Preimage::random().payment_hash();
+        let invoice = client
+            .create_hold_invoice(&payment_hash, 1000, 3600)
+            .await
+            .unwrap();
+
+        client.fail_next(
+            MockCall::PayHoldInvoice,
+            FiberError::NetworkError("connection refused".to_string()),
+        );
+        let result = client.pay_hold_invoice(&invoice).await;
+        assert!(matches!(result, Err(FiberError::NetworkError(_))));
+        assert_eq!(client.balance(), 10000);
+        assert_eq!(
+            client.get_payment_status(&payment_hash).await.unwrap(),
+            PaymentStatus::Pending
+        );
+
+        // Only the next call fails
+        client.pay_hold_invoice(&invoice).await.unwrap();
+        assert_eq!(client.balance(), 9000);
+    }
+
+    #[tokio::test]
+    async fn test_lost_response_still_takes_effect() {
+        let client = MockFiberClient::new(10000);
+        let preimage = Preimage::random();
+        let payment_hash = preimage.payment_hash();
+        let invoice = client
+            .create_hold_invoice(&payment_hash, 1000, 3600)
+            .await
+            .unwrap();
+        client.pay_hold_invoice(&invoice).await.unwrap();
+
+        client.lose_next_response(
+            MockCall::SettleInvoice,
+            FiberError::NetworkError("connection reset".to_string()),
+        );
+        let result = client.settle_invoice(&payment_hash, &preimage).await;
+        assert!(matches!(result, Err(FiberError::NetworkError(_))));
+        assert_eq!(
+            client.get_payment_status(&payment_hash).await.unwrap(),
+            PaymentStatus::Settled
+        );
+        assert_eq!(client.balance(), 10000);
+    }
+
+    #[tokio::test]
+    async fn test_unpaid_invoice_expires_on_clock() {
+        let clock = TestClock::new();
+        let client = MockFiberClient::new(10000).with_clock(clock.shared());
+        let payment_hash = Preimage::random().payment_hash();
+        let invoice = client
+            .create_hold_invoice(&payment_hash, 1000, 3600)
+            .await
+            .unwrap();
+
+        clock.advance(Duration::from_secs(3600));
+        assert_eq!(
+            client.get_payment_status(&payment_hash).await.unwrap(),
+            PaymentStatus::Pending
+        );
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(
+            client.get_payment_status(&payment_hash).await.unwrap(),
+            PaymentStatus::Cancelled
+        );
+        assert!(matches!(
+            client.pay_hold_invoice(&invoice).await,
+            Err(FiberError::Expired)
+        ));
+        assert_eq!(client.balance(), 10000);
+    }
+
+    #[tokio::test]
+    async fn test_invoice_description_read_back() {
+        let client = MockFiberClient::new(10000);
+        let described = Preimage::random().payment_hash();
+        let plain = Preimage::random().payment_hash();
+        let memo = crate::fiber::InvoiceMemo::Game(uuid::Uuid::new_v4());
+        client
+            .create_described_hold_invoice(&described, 1000, 3600, &memo.description())
+            .await
+            .unwrap();
+        client.create_hold_invoice(&plain, 1000, 3600).await.unwrap();
+
+        let description = client.get_invoice_description(&described).await.unwrap();
+        assert_eq!(description.as_deref().and_then(crate::fiber::InvoiceMemo::find), Some(memo));
+        assert_eq!(client.get_invoice_description(&plain).await.unwrap(), None);
+        assert!(matches!(
+            client
+                .get_invoice_description(&Preimage::random().payment_hash())
+                .await,
+            Err(FiberError::InvoiceNotFound(_))
+        ));
+    }
 }
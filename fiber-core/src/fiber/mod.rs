@@ -1,9 +1,13 @@
 //! Fiber Network client abstraction.
 
+mod memo;
 mod mock;
+#[cfg(feature = "rpc")]
 mod rpc;
 mod traits;
 
-pub use mock::MockFiberClient;
+pub use memo::InvoiceMemo;
+pub use mock::{MockCall, MockFiberClient};
+#[cfg(feature = "rpc")]
 pub use rpc::{CkbInvoiceStatus, Currency, RpcFiberClient};
 pub use traits::{FiberClient, FiberError, HoldInvoice, PaymentId, PaymentStatus};
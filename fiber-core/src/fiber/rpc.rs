@@ -5,13 +5,19 @@
 
 use crate::crypto::{PaymentHash, Preimage};
 use crate::fiber::traits::{FiberClient, FiberError, HoldInvoice, PaymentId, PaymentStatus};
+use crate::money;
 use async_trait::async_trait;
+use reqwest::header::HeaderMap;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::sync::Arc;
+
+/// Description of invoices created without one
+const DEFAULT_DESCRIPTION: &str = "Fiber Escrow Payment";
 
 /// Currency for Fiber invoices
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub enum Currency {
     /// Mainnet
@@ -28,6 +34,20 @@ impl Default for Currency {
     }
 }
 
+/// Case-insensitive, so `fibt` names [`Currency::Fibt`] on a command line
+impl std::str::FromStr for Currency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "fibb" => Ok(Self::Fibb),
+            "fibt" => Ok(Self::Fibt),
+            "fibd" => Ok(Self::Fibd),
+            _ => Err(format!("Unknown currency {:?} (expected Fibb, Fibt or Fibd)", s)),
+        }
+    }
+}
+
 /// Invoice status from Fiber RPC
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "PascalCase")]
@@ -52,6 +72,8 @@ pub struct RpcFiberClient {
     rpc_url: String,
     /// Currency to use for invoices
     currency: Currency,
+    /// Extra headers sent with every call, see [`RpcFiberClient::with_headers`]
+    headers: Option<Arc<dyn Fn() -> HeaderMap + Send + Sync>>,
 }
 
 impl RpcFiberClient {
@@ -61,6 +83,7 @@ impl RpcFiberClient {
             client: Client::new(),
             rpc_url: rpc_url.into(),
             currency: Currency::default(),
+            headers: None,
         }
     }
 
@@ -70,12 +93,23 @@ impl RpcFiberClient {
             client: Client::new(),
             rpc_url: rpc_url.into(),
             currency,
+            headers: None,
         }
     }
 
+    /// Send the headers `headers` returns with every call, e.g. the caller's
+    /// trace context so the node's work shows up in the same trace
+    pub fn with_headers(
+        mut self,
+        headers: impl Fn() -> HeaderMap + Send + Sync + 'static,
+    ) -> Self {
+        self.headers = Some(Arc::new(headers));
+        self
+    }
+
     /// Make a JSON-RPC call
     /// Note: Fiber RPC expects params as an array containing a single object
-    async fn call(&self, method: &str, params: Value) -> Result<Value, FiberError> {
+    pub(crate) async fn call(&self, method: &str, params: Value) -> Result<Value, FiberError> {
         // Wrap params in array as required by Fiber RPC
         let params_array = json!([params]);
         
@@ -89,9 +123,11 @@ impl RpcFiberClient {
         // Debug: log the request
         println!("[RpcFiberClient] {} -> {}", method, serde_json::to_string(&request).unwrap_or_default());
 
-        let response = self
-            .client
-            .post(&self.rpc_url)
+        let mut builder = self.client.post(&self.rpc_url);
+        if let Some(headers) = &self.headers {
+            builder = builder.headers(headers());
+        }
+        let response = builder
             .json(&request)
             .send()
             .await
@@ -133,6 +169,26 @@ impl FiberClient for RpcFiberClient {
         amount: u64,
         expiry_secs: u64,
     ) -> Result<HoldInvoice, FiberError> {
+        self.create_described_hold_invoice(payment_hash, amount, expiry_secs, DEFAULT_DESCRIPTION)
+            .await
+    }
+
+    async fn create_described_hold_invoice(
+        &self,
+        payment_hash: &PaymentHash,
+        amount: u64,
+        expiry_secs: u64,
+        description: &str,
+    ) -> Result<HoldInvoice, FiberError> {
+        // Refuse up front rather than hand out an invoice nobody can pay
+        let available = self.get_inbound_capacity().await?;
+        if available < amount {
+            return Err(FiberError::InsufficientInboundCapacity {
+                needed: amount,
+                available,
+            });
+        }
+
         // amount is in shannons (CKB base unit)
         let amount_shannons = amount;
 
@@ -147,7 +203,7 @@ impl FiberClient for RpcFiberClient {
             "payment_hash": payment_hash.to_hex(),
             "expiry": format!("0x{:x}", expiry_secs),
             "final_expiry_delta": format!("0x{:x}", final_expiry_delta_ms),
-            "description": "Fiber Escrow Payment",
+            "description": description,
         });
 
         let result = self.call("new_invoice", params).await?;
@@ -166,6 +222,43 @@ impl FiberClient for RpcFiberClient {
         })
     }
 
+    /// Create a standard invoice, handing the node the preimage to settle with
+    ///
+    /// The preimage is made up here when not given, so the payment hash is
+    /// known without reading it back out of the invoice.
+    async fn create_invoice(
+        &self,
+        amount: u64,
+        expiry_secs: u64,
+        preimage: Option<&Preimage>,
+    ) -> Result<HoldInvoice, FiberError> {
+        let preimage = preimage.cloned().unwrap_or_else(Preimage::random);
+        let payment_hash = preimage.payment_hash();
+
+        let params = json!({
+            "amount": format!("0x{:x}", amount),
+            "currency": self.currency,
+            "payment_preimage": preimage.to_hex(),
+            "expiry": format!("0x{:x}", expiry_secs),
+            "description": DEFAULT_DESCRIPTION,
+        });
+
+        let result = self.call("new_invoice", params).await?;
+
+        let invoice_address = result
+            .get("invoice_address")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| FiberError::NetworkError("No invoice_address in response".to_string()))?
+            .to_string();
+
+        Ok(HoldInvoice {
+            payment_hash,
+            amount,
+            expiry_secs,
+            invoice_string: invoice_address,
+        })
+    }
+
     /// Pay a hold invoice
     ///
     /// This sends a payment to the invoice. For hold invoices, the payment will
@@ -278,8 +371,46 @@ impl FiberClient for RpcFiberClient {
         })
     }
 
+    /// The `Description` attribute of the invoice `get_invoice` returns
+    async fn get_invoice_description(
+        &self,
+        payment_hash: &PaymentHash,
+    ) -> Result<Option<String>, FiberError> {
+        let params = json!({
+            "payment_hash": payment_hash.to_hex(),
+        });
+        let result = self.call("get_invoice", params).await?;
+        Ok(invoice_description(&result))
+    }
+
     /// Get total local balance across all channels in shannons
     async fn get_balance(&self) -> Result<u64, FiberError> {
+        self.channel_total("local_balance").await
+    }
+
+    /// Get total remote balance across all channels in shannons
+    async fn get_inbound_capacity(&self) -> Result<u64, FiberError> {
+        self.channel_total("remote_balance").await
+    }
+}
+
+/// The description among a `get_invoice` result's invoice attributes,
+/// each an object of one attribute name
+fn invoice_description(result: &Value) -> Option<String> {
+    result
+        .pointer("/invoice/data/attrs")?
+        .as_array()?
+        .iter()
+        .filter_map(Value::as_object)
+        .flat_map(|attr| attr.iter())
+        .find(|(name, _)| name.eq_ignore_ascii_case("description"))
+        .and_then(|(_, value)| value.as_str())
+        .map(str::to_string)
+}
+
+impl RpcFiberClient {
+    /// Sum a balance field over all channels from `list_channels`
+    async fn channel_total(&self, field: &str) -> Result<u64, FiberError> {
         // list_channels returns a list of channels
         let result = self.call("list_channels", json!({})).await?;
         
@@ -290,18 +421,18 @@ impl FiberClient for RpcFiberClient {
 
         let mut total_shannons: u64 = 0;
         for channel in channels {
-            let local_balance_str = channel
-                .get("local_balance")
+            let balance_str = channel
+                .get(field)
                 .and_then(|v| v.as_str())
                 .unwrap_or("0x0");
             
             // Parse hex string (0x...)
-            let shannons = if local_balance_str.starts_with("0x") {
-                u64::from_str_radix(&local_balance_str[2..], 16).unwrap_or(0)
+            let shannons = if let Some(hex) = balance_str.strip_prefix("0x") {
+                u64::from_str_radix(hex, 16).unwrap_or(0)
             } else {
-                local_balance_str.parse::<u64>().unwrap_or(0)
+                balance_str.parse::<u64>().unwrap_or(0)
             };
-            total_shannons += shannons;
+            total_shannons = money::add(total_shannons, shannons)?;
         }
 
         Ok(total_shannons)
@@ -324,6 +455,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_description_read_from_invoice_attrs() {
+        let result = json!({
+            "invoice_address": "fibt1...",
+            "invoice": {"data": {"attrs": [
+                {"expiry_time": "0xe10"},
+                {"description": "Fiber Game stake [fiber-game:x]"}
+            ]}},
+            "status": "Open"
+        });
+        assert_eq!(
+            invoice_description(&result).as_deref(),
+            Some("Fiber Game stake [fiber-game:x]")
+        );
+        assert_eq!(invoice_description(&json!({"status": "Open"})), None);
+    }
+
+    #[test]
+    fn test_currency_from_str() {
+        assert_eq!("fibd".parse(), Ok(Currency::Fibd));
+        assert_eq!("Fibt".parse(), Ok(Currency::Fibt));
+        assert!("ckb".parse::<Currency>().is_err());
+    }
+
     #[test]
     fn test_invoice_status_deserialization() {
         let status: CkbInvoiceStatus = serde_json::from_str("\"Open\"").unwrap();
@@ -1,6 +1,7 @@
 //! Fiber client trait definition.
 
 use crate::crypto::{PaymentHash, Preimage};
+use crate::money::MoneyError;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -27,14 +28,20 @@ pub enum FiberError {
     #[error("Insufficient funds")]
     InsufficientFunds,
 
+    #[error("Insufficient inbound capacity: need {needed} shannons, channels can receive {available}")]
+    InsufficientInboundCapacity { needed: u64, available: u64 },
+
     #[error("Payment failed: {0}")]
     PaymentFailed(String),
 
     #[error("Network error: {0}")]
     NetworkError(String),
+
+    #[error(transparent)]
+    Money(#[from] MoneyError),
 }
 
-/// Hold invoice information
+/// Invoice information, for hold and standard invoices alike
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct HoldInvoice {
     /// Payment hash (derived from preimage)
@@ -88,7 +95,11 @@ pub trait FiberClient: Send + Sync {
     /// Support downcasting to concrete types
     fn as_any(&self) -> &dyn std::any::Any;
 
-    /// Create a hold invoice that locks funds until settled or cancelled
+    /// Create a hold invoice that locks funds until settled or cancelled.
+    ///
+    /// Fails with [`FiberError::InsufficientInboundCapacity`] if our
+    /// channels can't receive `amount`, since the payer's attempt would
+    /// only fail later.
     async fn create_hold_invoice(
         &self,
         payment_hash: &PaymentHash,
@@ -96,6 +107,28 @@ pub trait FiberClient: Send + Sync {
         expiry_secs: u64,
     ) -> Result<HoldInvoice, FiberError>;
 
+    /// [`FiberClient::create_hold_invoice`] with a `description` the node
+    /// keeps with the invoice, e.g. an
+    /// [`InvoiceMemo`](crate::fiber::InvoiceMemo)'s, so the invoice can be
+    /// traced back to what it was for.
+    async fn create_described_hold_invoice(
+        &self,
+        payment_hash: &PaymentHash,
+        amount: u64,
+        expiry_secs: u64,
+        description: &str,
+    ) -> Result<HoldInvoice, FiberError>;
+
+    /// Create a standard invoice, which the node settles itself as soon as
+    /// it is paid. The node keeps `preimage` to settle with; without one it
+    /// makes up its own, which the payer learns once the payment completes.
+    async fn create_invoice(
+        &self,
+        amount: u64,
+        expiry_secs: u64,
+        preimage: Option<&Preimage>,
+    ) -> Result<HoldInvoice, FiberError>;
+
     /// Pay a hold invoice (funds locked on our side)
     async fn pay_hold_invoice(&self, invoice: &HoldInvoice) -> Result<PaymentId, FiberError>;
 
@@ -113,6 +146,17 @@ pub trait FiberClient: Send + Sync {
     async fn get_payment_status(&self, payment_hash: &PaymentHash)
         -> Result<PaymentStatus, FiberError>;
 
+    /// The description the node keeps for one of its invoices, if it was
+    /// given one
+    async fn get_invoice_description(
+        &self,
+        payment_hash: &PaymentHash,
+    ) -> Result<Option<String>, FiberError>;
+
     /// Get the total local balance in shannons across all open channels
     async fn get_balance(&self) -> Result<u64, FiberError>;
+
+    /// Get the total remote balance in shannons across all open channels,
+    /// i.e. how much we can receive
+    async fn get_inbound_capacity(&self) -> Result<u64, FiberError>;
 }
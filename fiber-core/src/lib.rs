@@ -2,13 +2,32 @@
 //!
 //! Shared primitives for Fiber Network applications:
 //! - Cryptographic primitives (Preimage, PaymentHash)
+//! - A `Keyring` that encrypts secrets kept at rest
+//! - A `Clock` services read the time from, and a `TestClock` to drive it
+//! - Checked arithmetic on amounts of shannons (`money`)
 //! - FiberClient trait and MockFiberClient
+//! - RpcFiberClient for a real Fiber node (`rpc` feature, on by default)
+//! - Versioned schemas for SQLite stores (`sqlite` feature)
+//! - Regtest Fiber nodes for integration tests (`testkit` feature)
+//!
+//! Without the `rpc` feature the crate builds for `wasm32-unknown-unknown`,
+//! so a browser can make its own preimages.
 
+pub mod clock;
 pub mod crypto;
 pub mod fiber;
+pub mod money;
+#[cfg(feature = "sqlite")]
+pub mod storage;
+#[cfg(feature = "testkit")]
+pub mod testkit;
 
-pub use crypto::{PaymentHash, Preimage};
+pub use clock::{Clock, SharedClock, SystemClock, TestClock};
+pub use crypto::{Keyring, KeyringError, PaymentHash, Preimage};
+pub use money::MoneyError;
 pub use fiber::{
-    FiberClient, FiberError, HoldInvoice, MockFiberClient, PaymentId, PaymentStatus,
-    RpcFiberClient,
+    FiberClient, FiberError, HoldInvoice, InvoiceMemo, MockCall, MockFiberClient, PaymentId,
+    PaymentStatus,
 };
+#[cfg(feature = "rpc")]
+pub use fiber::RpcFiberClient;
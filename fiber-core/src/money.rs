@@ -0,0 +1,184 @@
+//! Checked arithmetic on amounts of shannons.
+//!
+//! Amounts are plain `u64`s of shannons everywhere, and balances that can
+//! go negative (what a player won, what a user owes) are `i64`s. Every sum,
+//! difference, share and sign change of an amount goes through this module
+//! so that one too large for its type is a [`MoneyError`], not a wrapped or
+//! silently clamped number.
+//!
+//! The one invariant: no single amount exceeds [`MAX_SHANNONS`], so any
+//! amount can also be taken from or added to a balance. Request bodies
+//! check it on the way in.
+
+use fiber_errors::{Coded, ErrorCode};
+use thiserror::Error;
+
+/// Largest single amount, so that it converts to a signed balance change
+pub const MAX_SHANNONS: u64 = i64::MAX as u64;
+
+/// Basis points in a whole: 100 basis points are one percent
+pub const BASIS_POINTS: u32 = 10_000;
+
+/// Arithmetic that would leave an amount's type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum MoneyError {
+    #[error("amount overflows: {0} shannons and {1} more")]
+    Overflow(u64, u64),
+
+    #[error("amount underflows: {amount} shannons taken from {from}")]
+    Underflow { from: u64, amount: u64 },
+
+    #[error("amount of {0} shannons exceeds the largest allowed")]
+    TooLarge(u64),
+
+    #[error("balance overflows: {balance} shannons changed by {delta}")]
+    BalanceOverflow { balance: i64, delta: i64 },
+
+    #[error("share of {0} basis points is over 100%")]
+    InvalidShare(u32),
+}
+
+impl Coded for MoneyError {
+    fn code(&self) -> ErrorCode {
+        // Amounts are checked on the way in, so one that overflows later is
+        // the service's fault
+        ErrorCode::Internal
+    }
+}
+
+/// `a + b`
+pub fn add(a: u64, b: u64) -> Result<u64, MoneyError> {
+    a.checked_add(b).ok_or(MoneyError::Overflow(a, b))
+}
+
+/// `from - amount`
+pub fn sub(from: u64, amount: u64) -> Result<u64, MoneyError> {
+    from.checked_sub(amount)
+        .ok_or(MoneyError::Underflow { from, amount })
+}
+
+/// The total of `amounts`
+pub fn sum(amounts: impl IntoIterator<Item = u64>) -> Result<u64, MoneyError> {
+    amounts.into_iter().try_fold(0, add)
+}
+
+/// `amount` as a balance change: what is gained by receiving it
+pub fn signed(amount: u64) -> Result<i64, MoneyError> {
+    i64::try_from(amount).map_err(|_| MoneyError::TooLarge(amount))
+}
+
+/// `balance` after receiving `amount`
+pub fn credit(balance: i64, amount: u64) -> Result<i64, MoneyError> {
+    change(balance, signed(amount)?)
+}
+
+/// `balance` after paying `amount`
+pub fn debit(balance: i64, amount: u64) -> Result<i64, MoneyError> {
+    change(balance, -signed(amount)?)
+}
+
+/// `balance` changed by `delta`
+pub fn change(balance: i64, delta: i64) -> Result<i64, MoneyError> {
+    balance
+        .checked_add(delta)
+        .ok_or(MoneyError::BalanceOverflow { balance, delta })
+}
+
+/// An unsigned `balance` changed by `delta`, which may not take it below
+/// zero
+pub fn apply(balance: u64, delta: i64) -> Result<u64, MoneyError> {
+    if delta >= 0 {
+        add(balance, delta.unsigned_abs())
+    } else {
+        sub(balance, delta.unsigned_abs())
+    }
+}
+
+/// `basis_points` / 10 000 of `amount`, rounded down
+pub fn share(amount: u64, basis_points: u32) -> Result<u64, MoneyError> {
+    if basis_points > BASIS_POINTS {
+        return Err(MoneyError::InvalidShare(basis_points));
+    }
+    // Fits: the product is below 2^64 * 10^4 and the quotient below `amount`
+    let part = amount as u128 * basis_points as u128 / BASIS_POINTS as u128;
+    Ok(part as u64)
+}
+
+/// An amount divided between a payee and a fee
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeSplit {
+    /// What the payee receives
+    pub net: u64,
+    pub fee: u64,
+}
+
+/// Take a fee of `fee_basis_points` out of `amount`. The fee is rounded
+/// down, so the two parts always add up to `amount`.
+pub fn split_fee(amount: u64, fee_basis_points: u32) -> Result<FeeSplit, MoneyError> {
+    let fee = share(amount, fee_basis_points)?;
+    Ok(FeeSplit {
+        net: sub(amount, fee)?,
+        fee,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_overflow_is_an_error() {
+        assert_eq!(add(u64::MAX, 1), Err(MoneyError::Overflow(u64::MAX, 1)));
+        assert_eq!(sub(1, 2), Err(MoneyError::Underflow { from: 1, amount: 2 }));
+        assert_eq!(
+            sum([u64::MAX / 2, u64::MAX / 2, 2]).unwrap_err(),
+            MoneyError::Overflow(u64::MAX - 1, 2)
+        );
+        assert_eq!(
+            signed(MAX_SHANNONS + 1),
+            Err(MoneyError::TooLarge(MAX_SHANNONS + 1))
+        );
+        assert_eq!(
+            debit(i64::MIN, 1).unwrap_err(),
+            MoneyError::BalanceOverflow {
+                balance: i64::MIN,
+                delta: -1
+            }
+        );
+        assert_eq!(
+            apply(5, -6),
+            Err(MoneyError::Underflow { from: 5, amount: 6 })
+        );
+        assert_eq!(
+            share(100, BASIS_POINTS + 1),
+            Err(MoneyError::InvalidShare(BASIS_POINTS + 1))
+        );
+    }
+
+    #[test]
+    fn test_balances_and_shares() {
+        assert_eq!(credit(-5, 10), Ok(5));
+        assert_eq!(debit(5, 10), Ok(-5));
+        assert_eq!(debit(0, MAX_SHANNONS), Ok(-i64::MAX));
+        assert_eq!(apply(5, -5), Ok(0));
+        assert_eq!(share(1_000, 250), Ok(25));
+        assert_eq!(share(u64::MAX, BASIS_POINTS), Ok(u64::MAX));
+        assert_eq!(split_fee(999, 100), Ok(FeeSplit { net: 990, fee: 9 }));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_fee_split_adds_up(amount: u64, bps in 0..=BASIS_POINTS) {
+            let split = split_fee(amount, bps).unwrap();
+            prop_assert_eq!(split.net + split.fee, amount);
+            prop_assert!(split.fee <= amount);
+        }
+
+        #[test]
+        fn prop_credit_then_debit_is_identity(balance: i32, amount in 0..=u32::MAX as u64) {
+            let balance = balance as i64;
+            prop_assert_eq!(debit(credit(balance, amount).unwrap(), amount), Ok(balance));
+        }
+    }
+}
@@ -0,0 +1,56 @@
+//! Versioned schemas for the services' SQLite stores.
+//!
+//! Each store embeds its own `migrations/` (`V1__initial_schema.sql`,
+//! `V2__what_changed.sql`, ...) with refinery and records which have run
+//! in a history table of its own, so several stores can share a database
+//! file. [`prepare`] brings a database up to date, or checks that it is.
+
+use refinery::Runner;
+use rusqlite::Connection;
+
+/// A database schema that couldn't be brought up to date
+#[derive(Debug, thiserror::Error)]
+pub enum SchemaError {
+    #[error("database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("schema migration failed: {0}")]
+    Migration(#[from] refinery::Error),
+    #[error("database schema is out of date: {0} migration(s) not applied")]
+    Pending(usize),
+}
+
+/// Apply the migrations of `runner` that the database `conn` is open on is
+/// missing, recording them in `history_table`, and return their names.
+/// Unless `migrate`, apply none and fail with [`SchemaError::Pending`] if
+/// any are missing.
+pub fn prepare(
+    conn: &mut Connection,
+    mut runner: Runner,
+    history_table: &str,
+    migrate: bool,
+) -> Result<Vec<String>, SchemaError> {
+    runner.set_migration_table_name(history_table);
+    if migrate {
+        let report = runner.run(conn)?;
+        return Ok(report
+            .applied_migrations()
+            .iter()
+            .map(|m| m.to_string())
+            .collect());
+    }
+
+    let has_history: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+        [history_table],
+        |row| row.get(0),
+    )?;
+    let applied = if has_history {
+        runner.get_applied_migrations(conn)?.len()
+    } else {
+        0
+    };
+    match runner.get_migrations().len().saturating_sub(applied) {
+        0 => Ok(Vec::new()),
+        pending => Err(SchemaError::Pending(pending)),
+    }
+}
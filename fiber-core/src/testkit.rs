@@ -0,0 +1,305 @@
+//! Regtest Fiber nodes for integration tests.
+//!
+//! `RegtestNetwork::start` runs a CKB dev chain and two Fiber nodes in
+//! docker (via testcontainers), connects the nodes and opens a funded
+//! channel from A to B, so tests can drive `RpcFiberClient` against real
+//! nodes. Only built with the `testkit` feature.
+//!
+//! The images are not published by this repo and are picked through the
+//! environment, as `name:tag`:
+//!
+//! - `FIBER_TESTKIT_CKB_IMAGE`: a CKB dev chain with the Fiber scripts
+//!   deployed and a miner running, serving RPC on port 8114.
+//! - `FIBER_TESTKIT_FNN_IMAGE`: an `fnn` node configured for that chain,
+//!   serving RPC on 8227 and P2P on 8228. It reads the chain RPC from
+//!   `CKB_RPC_URL` and the pre-funded dev account to use from
+//!   `FIBER_NODE_INDEX`.
+//!
+//! Containers are removed when the network is dropped.
+
+use crate::fiber::{Currency, FiberError, RpcFiberClient};
+use serde_json::{json, Value};
+use std::time::Duration;
+use testcontainers::{
+    core::{ContainerPort, IntoContainerPort},
+    runners::AsyncRunner,
+    ContainerAsync, GenericImage, ImageExt,
+};
+use thiserror::Error;
+
+/// CKB dev chain RPC port
+const CKB_RPC_PORT: u16 = 8114;
+/// Fiber node RPC port
+const FNN_RPC_PORT: u16 = 8227;
+/// Fiber node P2P port
+const FNN_P2P_PORT: u16 = 8228;
+
+/// Channel funding when not set through `FIBER_TESTKIT_FUNDING` (1000 CKB)
+const DEFAULT_CHANNEL_FUNDING: u64 = 100_000_000_000;
+
+/// How often readiness is polled
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Errors from starting the regtest network
+#[derive(Debug, Error)]
+pub enum TestkitError {
+    #[error("Testkit config error: {0}")]
+    Config(String),
+
+    #[error("Container error: {0}")]
+    Container(#[from] testcontainers::TestcontainersError),
+
+    #[error("Node RPC error: {0}")]
+    Rpc(#[from] FiberError),
+
+    #[error("Timed out waiting for {0}")]
+    Timeout(String),
+}
+
+/// Images and channel parameters for the regtest network
+#[derive(Clone, Debug)]
+pub struct TestkitConfig {
+    /// CKB dev chain image, as `(name, tag)`
+    pub ckb_image: (String, String),
+    /// Fiber node image, as `(name, tag)`
+    pub fnn_image: (String, String),
+    /// Amount A funds the channel to B with, in shannons
+    pub channel_funding: u64,
+    /// How long to wait for nodes and the channel to come up
+    pub startup_timeout: Duration,
+}
+
+impl TestkitConfig {
+    /// Read the config from `FIBER_TESTKIT_*` environment variables
+    pub fn from_env() -> Result<Self, TestkitError> {
+        let channel_funding = match std::env::var("FIBER_TESTKIT_FUNDING") {
+            Ok(v) => v.parse().map_err(|_| {
+                TestkitError::Config(format!("Invalid FIBER_TESTKIT_FUNDING: {}", v))
+            })?,
+            Err(_) => DEFAULT_CHANNEL_FUNDING,
+        };
+
+        Ok(Self {
+            ckb_image: image_from_env("FIBER_TESTKIT_CKB_IMAGE")?,
+            fnn_image: image_from_env("FIBER_TESTKIT_FNN_IMAGE")?,
+            channel_funding,
+            startup_timeout: Duration::from_secs(300),
+        })
+    }
+}
+
+fn image_from_env(var: &str) -> Result<(String, String), TestkitError> {
+    let image =
+        std::env::var(var).map_err(|_| TestkitError::Config(format!("{} is not set", var)))?;
+    let (name, tag) = image.rsplit_once(':').unwrap_or((&image, "latest"));
+    Ok((name.to_string(), tag.to_string()))
+}
+
+/// A Fiber node running in a container
+pub struct FiberNode {
+    /// Held so the container lives as long as the node
+    _container: ContainerAsync<GenericImage>,
+    rpc_url: String,
+    peer_id: String,
+    p2p_addr: String,
+}
+
+impl FiberNode {
+    async fn start(
+        config: &TestkitConfig,
+        ckb_rpc_url: &str,
+        index: usize,
+    ) -> Result<Self, TestkitError> {
+        let (name, tag) = &config.fnn_image;
+        let container = GenericImage::new(name.as_str(), tag.as_str())
+            .with_exposed_port(FNN_RPC_PORT.tcp())
+            .with_exposed_port(FNN_P2P_PORT.tcp())
+            .with_env_var("CKB_RPC_URL", ckb_rpc_url)
+            .with_env_var("FIBER_NODE_INDEX", index.to_string())
+            .start()
+            .await?;
+
+        let rpc_url = host_url(&container, FNN_RPC_PORT.tcp()).await?;
+        let rpc = RpcFiberClient::new(rpc_url.clone());
+        let info = wait_for(config.startup_timeout, "Fiber node RPC", || async {
+            rpc.call("node_info", json!({})).await.ok()
+        })
+        .await?;
+
+        // Peers are dialled over the docker bridge, not the host port
+        let peer_id = peer_id_from_node_info(&info)?;
+        let p2p_addr = format!(
+            "/ip4/{}/tcp/{}/p2p/{}",
+            container.get_bridge_ip_address().await?,
+            FNN_P2P_PORT,
+            peer_id
+        );
+
+        Ok(Self {
+            _container: container,
+            rpc_url,
+            peer_id,
+            p2p_addr,
+        })
+    }
+
+    /// The node's JSON-RPC URL, reachable from the host
+    pub fn rpc_url(&self) -> &str {
+        &self.rpc_url
+    }
+
+    /// The node's libp2p peer id
+    pub fn peer_id(&self) -> &str {
+        &self.peer_id
+    }
+
+    /// A client for the node, using devnet invoices
+    pub fn client(&self) -> RpcFiberClient {
+        RpcFiberClient::with_currency(self.rpc_url.clone(), Currency::Fibd)
+    }
+}
+
+/// A dev chain and two Fiber nodes with a ready channel from A to B
+pub struct RegtestNetwork {
+    /// Paying side of the channel, holding its funding
+    pub node_a: FiberNode,
+    /// Receiving side of the channel
+    pub node_b: FiberNode,
+    /// Dropped last, after the nodes using it
+    _ckb: ContainerAsync<GenericImage>,
+}
+
+impl RegtestNetwork {
+    /// Start the network with images from the environment
+    pub async fn start() -> Result<Self, TestkitError> {
+        Self::start_with(TestkitConfig::from_env()?).await
+    }
+
+    /// Start the network with the given config
+    pub async fn start_with(config: TestkitConfig) -> Result<Self, TestkitError> {
+        let (name, tag) = &config.ckb_image;
+        let ckb = GenericImage::new(name.as_str(), tag.as_str())
+            .with_exposed_port(CKB_RPC_PORT.tcp())
+            .start()
+            .await?;
+        let ckb_rpc_url = format!(
+            "http://{}:{}",
+            ckb.get_bridge_ip_address().await?,
+            CKB_RPC_PORT
+        );
+
+        let node_a = FiberNode::start(&config, &ckb_rpc_url, 0).await?;
+        let node_b = FiberNode::start(&config, &ckb_rpc_url, 1).await?;
+
+        let rpc_a = RpcFiberClient::new(node_a.rpc_url.clone());
+        rpc_a
+            .call("connect_peer", json!({ "address": node_b.p2p_addr }))
+            .await?;
+        // The peer is only known once the handshake is done
+        wait_for(config.startup_timeout, "peer connection", || async {
+            rpc_a
+                .call(
+                    "open_channel",
+                    json!({
+                        "peer_id": node_b.peer_id,
+                        "funding_amount": format!("0x{:x}", config.channel_funding),
+                        "public": true,
+                    }),
+                )
+                .await
+                .ok()
+        })
+        .await?;
+        wait_for(config.startup_timeout, "channel to be ready", || async {
+            let result = rpc_a
+                .call("list_channels", json!({ "peer_id": node_b.peer_id }))
+                .await
+                .ok()?;
+            channel_ready(&result).then_some(())
+        })
+        .await?;
+
+        Ok(Self {
+            node_a,
+            node_b,
+            _ckb: ckb,
+        })
+    }
+}
+
+async fn host_url(
+    container: &ContainerAsync<GenericImage>,
+    port: ContainerPort,
+) -> Result<String, TestkitError> {
+    Ok(format!(
+        "http://{}:{}",
+        container.get_host().await?,
+        container.get_host_port_ipv4(port).await?
+    ))
+}
+
+/// Poll `check` until it yields a value or `timeout` passes
+async fn wait_for<T, F, Fut>(timeout: Duration, what: &str, mut check: F) -> Result<T, TestkitError>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Option<T>>,
+{
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        if let Some(value) = check().await {
+            return Ok(value);
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Err(TestkitError::Timeout(what.to_string()));
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Take the peer id from the `/p2p/<id>` suffix of the node's first address
+fn peer_id_from_node_info(info: &Value) -> Result<String, TestkitError> {
+    info.get("addresses")
+        .and_then(|v| v.as_array())
+        .and_then(|addrs| addrs.first())
+        .and_then(|addr| addr.as_str())
+        .and_then(|addr| addr.rsplit_once("/p2p/"))
+        .map(|(_, id)| id.to_string())
+        .ok_or_else(|| TestkitError::Config(format!("No peer id in node_info: {}", info)))
+}
+
+fn channel_ready(list_channels: &Value) -> bool {
+    list_channels
+        .get("channels")
+        .and_then(|v| v.as_array())
+        .is_some_and(|channels| {
+            channels.iter().any(|c| {
+                c.pointer("/state/state_name").and_then(|s| s.as_str()) == Some("CHANNEL_READY")
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peer_id_from_node_info() {
+        let info = json!({
+            "addresses": ["/ip4/127.0.0.1/tcp/8228/p2p/QmXen3eUHhywmutEzydCsW4hXBoeVmdet2ftHLzSaMW6p3"]
+        });
+        assert_eq!(
+            peer_id_from_node_info(&info).unwrap(),
+            "QmXen3eUHhywmutEzydCsW4hXBoeVmdet2ftHLzSaMW6p3"
+        );
+        assert!(peer_id_from_node_info(&json!({ "addresses": [] })).is_err());
+    }
+
+    #[test]
+    fn test_channel_ready() {
+        let pending = json!({ "channels": [{ "state": { "state_name": "NEGOTIATING_FUNDING" } }] });
+        let ready = json!({ "channels": [{ "state": { "state_name": "CHANNEL_READY" } }] });
+        assert!(!channel_ready(&pending));
+        assert!(channel_ready(&ready));
+        assert!(!channel_ready(&json!({ "channels": [] })));
+    }
+}
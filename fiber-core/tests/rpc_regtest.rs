@@ -0,0 +1,110 @@
+//! `RpcFiberClient` against real Fiber nodes.
+//!
+//! Needs docker and the images described in `fiber_core::testkit`.
+//!
+//! Run with: cargo test -p fiber-core --features testkit --test rpc_regtest
+
+#![cfg(feature = "testkit")]
+
+use fiber_core::testkit::RegtestNetwork;
+use fiber_core::{FiberClient, PaymentHash, PaymentStatus, Preimage};
+use std::time::Duration;
+
+const AMOUNT: u64 = 10_000_000_000; // 100 CKB
+const EXPIRY_SECS: u64 = 3600;
+
+/// Poll the invoice until it reaches `expected`
+async fn wait_for_status(client: &dyn FiberClient, hash: &PaymentHash, expected: PaymentStatus) {
+    for _ in 0..60 {
+        if client.get_payment_status(hash).await.ok() == Some(expected) {
+            return;
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    panic!("Invoice never reached {:?}", expected);
+}
+
+#[tokio::test]
+async fn test_hold_invoice_settles() {
+    let network = RegtestNetwork::start()
+        .await
+        .expect("Failed to start regtest network");
+    let (a, b) = (network.node_a.client(), network.node_b.client());
+    let balance_a = a.get_balance().await.unwrap();
+    assert!(
+        balance_a >= AMOUNT,
+        "A's channel is not funded: {}",
+        balance_a
+    );
+
+    let preimage = Preimage::random();
+    let hash = preimage.payment_hash();
+    let invoice = b
+        .create_hold_invoice(&hash, AMOUNT, EXPIRY_SECS)
+        .await
+        .unwrap();
+    assert_eq!(
+        b.get_payment_status(&hash).await.unwrap(),
+        PaymentStatus::Pending
+    );
+
+    a.pay_hold_invoice(&invoice).await.unwrap();
+    wait_for_status(&b, &hash, PaymentStatus::Held).await;
+
+    // Settling needs the matching preimage
+    let wrong = Preimage::random();
+    assert!(b.settle_invoice(&hash, &wrong).await.is_err());
+    b.settle_invoice(&hash, &preimage).await.unwrap();
+    wait_for_status(&b, &hash, PaymentStatus::Settled).await;
+
+    assert!(a.get_balance().await.unwrap() <= balance_a - AMOUNT);
+}
+
+#[tokio::test]
+async fn test_hold_invoice_cancel_refunds() {
+    let network = RegtestNetwork::start()
+        .await
+        .expect("Failed to start regtest network");
+    let (a, b) = (network.node_a.client(), network.node_b.client());
+    let balance_a = a.get_balance().await.unwrap();
+
+    let hash = Preimage::random().payment_hash();
+    let invoice = b
+        .create_hold_invoice(&hash, AMOUNT, EXPIRY_SECS)
+        .await
+        .unwrap();
+    a.pay_hold_invoice(&invoice).await.unwrap();
+    wait_for_status(&b, &hash, PaymentStatus::Held).await;
+
+    b.cancel_invoice(&hash).await.unwrap();
+    wait_for_status(&b, &hash, PaymentStatus::Cancelled).await;
+
+    // The held amount goes back to A; routing fees are not charged on a
+    // direct channel
+    for _ in 0..60 {
+        if a.get_balance().await.unwrap() == balance_a {
+            return;
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    panic!("A was not refunded");
+}
+
+#[tokio::test]
+async fn test_standard_invoice_settles_on_payment() {
+    let network = RegtestNetwork::start()
+        .await
+        .expect("Failed to start regtest network");
+    let (a, b) = (network.node_a.client(), network.node_b.client());
+
+    // B's node was given the preimage, so it settles without being asked
+    let preimage = Preimage::random();
+    let invoice = b
+        .create_invoice(AMOUNT, EXPIRY_SECS, Some(&preimage))
+        .await
+        .unwrap();
+    assert_eq!(invoice.payment_hash, preimage.payment_hash());
+
+    a.pay_hold_invoice(&invoice).await.unwrap();
+    wait_for_status(&b, &invoice.payment_hash, PaymentStatus::Settled).await;
+}
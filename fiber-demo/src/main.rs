@@ -0,0 +1,71 @@
+//! Fiber Demo
+//!
+//! One binary for every demo service. Each subcommand accepts the same
+//! flags and environment variables as the service's own binary:
+//!
+//! ```text
+//! fiber-demo oracle   [--port 3000] [--db-path oracle.db]
+//! fiber-demo player   [--port 3001] [--oracle-url ...] [--player-name ...]
+//! fiber-demo escrow   [--port 3000] [--seed seeds/demo.yaml]
+//! fiber-demo combined [--port 3000] [--players 2] [--db-path demo.db]
+//! fiber-demo combined --script games.yaml
+//! ```
+//!
+//! `--config fiber.yaml` reads settings for any of them from one file (see
+//! `fiber_config`), and `--print-config` shows what a subcommand would run
+//! with.
+
+use clap::{FromArgMatches, Parser, Subcommand};
+
+#[derive(Parser)]
+#[command(name = "fiber-demo", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Game Oracle service
+    Oracle(fiber_game_oracle::Config),
+    /// Game Player service with Web UI
+    Player(fiber_game_player::Config),
+    /// Escrow marketplace with multi-role Web UI
+    Escrow(fiber_escrow_service::Config),
+    /// Game Oracle and Players on a single port
+    Combined(fiber_game_demo::Config),
+}
+
+#[tokio::main]
+async fn main() {
+    let matches = fiber_config::command::<Cli>().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let (_, sub) = matches.subcommand().expect("subcommand is required");
+
+    let result = match cli.command {
+        Command::Oracle(config) => {
+            let config = fiber_config::load(config, sub);
+            fiber_service::init_logging("fiber-game-oracle");
+            fiber_game_oracle::run(config).await
+        }
+        Command::Player(config) => {
+            let config = fiber_config::load(config, sub);
+            fiber_service::init_logging("fiber-game-player");
+            fiber_game_player::run(config).await
+        }
+        Command::Escrow(config) => {
+            let config = fiber_config::load(config, sub);
+            fiber_service::init_logging("fiber-escrow");
+            fiber_escrow_service::run(config).await
+        }
+        Command::Combined(config) => {
+            let config = fiber_config::load(config, sub);
+            fiber_service::init_logging("fiber-game-demo");
+            fiber_game_demo::run(config).await
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,59 @@
+//! gRPC statuses for services that answer over tonic as well as HTTP.
+//!
+//! gRPC status codes are coarser than [`ErrorCode`], so the code also travels
+//! in the `error-code` metadata entry; clients branch on that, as HTTP
+//! clients do on the body's `code`.
+
+use crate::{ApiError, ErrorCode};
+use tonic::{metadata::MetadataValue, Code, Status};
+
+/// Metadata key of a failed call's [`ErrorCode`]
+pub const ERROR_CODE_METADATA: &str = "error-code";
+
+impl ErrorCode {
+    /// The gRPC status code this error is reported with
+    pub fn grpc_code(self) -> Code {
+        match self {
+            ErrorCode::BadRequest
+            | ErrorCode::ValidationFailed
+            | ErrorCode::Expired
+            | ErrorCode::UnsupportedMediaType => Code::InvalidArgument,
+            ErrorCode::Unauthorized | ErrorCode::InvalidSignature => Code::Unauthenticated,
+            ErrorCode::Forbidden | ErrorCode::FeatureDisabled => Code::PermissionDenied,
+            ErrorCode::NotFound => Code::NotFound,
+            ErrorCode::Gone => Code::NotFound,
+            ErrorCode::Conflict => Code::AlreadyExists,
+            ErrorCode::InvalidState | ErrorCode::InsufficientFunds => Code::FailedPrecondition,
+            ErrorCode::RateLimited => Code::ResourceExhausted,
+            ErrorCode::Upstream => Code::Unavailable,
+            ErrorCode::Internal => Code::Internal,
+        }
+    }
+}
+
+impl From<ApiError> for Status {
+    fn from(err: ApiError) -> Self {
+        let mut status = Status::new(err.code.grpc_code(), err.message);
+        status.metadata_mut().insert(
+            ERROR_CODE_METADATA,
+            MetadataValue::from_static(err.code.as_str()),
+        );
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_keeps_the_error_code() {
+        let status = Status::from(ApiError::invalid_state("Order not in Funded status"));
+        assert_eq!(status.code(), Code::FailedPrecondition);
+        assert_eq!(status.message(), "Order not in Funded status");
+        assert_eq!(
+            status.metadata().get(ERROR_CODE_METADATA).unwrap(),
+            "invalid_state"
+        );
+    }
+}
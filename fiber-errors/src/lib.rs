@@ -0,0 +1,311 @@
+//! Fiber Error Codes
+//!
+//! One error shape for the HTTP APIs of every demo service, so clients branch
+//! on a stable code rather than on message text:
+//! - [`ErrorCode`] is the machine-readable code and the status it maps to
+//! - [`ApiError`] pairs a code with a human-readable message; with the `axum`
+//!   feature it is the error half of a handler's `Result`
+//! - [`ErrorBody`] is the JSON an `ApiError` is sent as
+//! - [`Coded`] lets a domain error name its own code, so `?` converts it
+//! - [`Validate`] checks a request body field by field, reporting every
+//!   problem at once as a 422 with [`FieldError`]s
+//! - with the `axum` feature, `ValidPath` takes IDs from the route and
+//!   rejects one that doesn't parse with an `ApiError` naming it
+//! - with the `tonic` feature, an `ApiError` converts into a gRPC `Status`
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[cfg(feature = "tonic")]
+mod grpc;
+#[cfg(feature = "axum")]
+mod path;
+mod validate;
+
+#[cfg(feature = "tonic")]
+pub use grpc::ERROR_CODE_METADATA;
+#[cfg(feature = "axum")]
+pub use path::ValidPath;
+#[cfg(feature = "axum")]
+pub use validate::ValidJson;
+pub use validate::{FieldError, Validate, Validator};
+
+/// Stable, machine-readable error code
+///
+/// Serialized in snake_case (`"not_found"`); new variants may be added, but
+/// existing ones keep their name and status.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The request is malformed or refers to something invalid
+    BadRequest,
+    /// The request is well-formed but some fields break their rules; the
+    /// body lists them in `fields`
+    ValidationFailed,
+    /// The caller did not say who they are, or is unknown
+    Unauthorized,
+    /// A signature or token that does not verify
+    InvalidSignature,
+    /// The caller is known but may not do this
+    Forbidden,
+    NotFound,
+    /// Clashes with another request: already taken, replayed or a duplicate
+    Conflict,
+    /// Not allowed in the resource's current state
+    InvalidState,
+    /// A signed submission past its expiry
+    Expired,
+    /// The route exists but an operator has switched its feature off
+    FeatureDisabled,
+    /// The caller's funds don't cover what the request would lock up
+    InsufficientFunds,
+    /// The resource existed, but the service holding it lost it for good
+    Gone,
+    UnsupportedMediaType,
+    /// The caller sent too many requests; `Retry-After` says when to retry
+    RateLimited,
+    /// A service this one relies on failed
+    Upstream,
+    Internal,
+}
+
+impl ErrorCode {
+    /// HTTP status code for this error
+    pub fn status(self) -> u16 {
+        match self {
+            ErrorCode::BadRequest | ErrorCode::Expired => 400,
+            ErrorCode::Unauthorized | ErrorCode::InvalidSignature => 401,
+            ErrorCode::Forbidden | ErrorCode::FeatureDisabled => 403,
+            ErrorCode::NotFound => 404,
+            ErrorCode::Gone => 410,
+            ErrorCode::Conflict | ErrorCode::InvalidState | ErrorCode::InsufficientFunds => 409,
+            ErrorCode::UnsupportedMediaType => 415,
+            ErrorCode::RateLimited => 429,
+            ErrorCode::ValidationFailed => 422,
+            ErrorCode::Internal => 500,
+            ErrorCode::Upstream => 502,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::BadRequest => "bad_request",
+            ErrorCode::ValidationFailed => "validation_failed",
+            ErrorCode::Unauthorized => "unauthorized",
+            ErrorCode::InvalidSignature => "invalid_signature",
+            ErrorCode::Forbidden => "forbidden",
+            ErrorCode::NotFound => "not_found",
+            ErrorCode::Conflict => "conflict",
+            ErrorCode::InvalidState => "invalid_state",
+            ErrorCode::Expired => "expired",
+            ErrorCode::FeatureDisabled => "feature_disabled",
+            ErrorCode::InsufficientFunds => "insufficient_funds",
+            ErrorCode::Gone => "gone",
+            ErrorCode::UnsupportedMediaType => "unsupported_media_type",
+            ErrorCode::RateLimited => "rate_limited",
+            ErrorCode::Upstream => "upstream",
+            ErrorCode::Internal => "internal",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A domain error that knows which [`ErrorCode`] it is reported as
+pub trait Coded: fmt::Display {
+    fn code(&self) -> ErrorCode;
+}
+
+/// An error as a service reports it: a code plus a message for humans
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+    /// The offending fields, for [`ErrorCode::ValidationFailed`]
+    pub fields: Vec<FieldError>,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// A 422 listing every field that failed validation
+    pub fn validation(fields: Vec<FieldError>) -> Self {
+        let message = fields
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("; ");
+        Self {
+            code: ErrorCode::ValidationFailed,
+            message: format!("Invalid request: {}", message),
+            fields,
+        }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::BadRequest, message)
+    }
+
+    /// A 400 for an ID or other parameter that doesn't parse, e.g.
+    /// `Invalid game_id: "nope"`
+    pub fn invalid_param(name: &str, value: &str) -> Self {
+        Self::bad_request(format!("Invalid {}: {:?}", name, value))
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Unauthorized, message)
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Forbidden, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::NotFound, message)
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Conflict, message)
+    }
+
+    pub fn invalid_state(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidState, message)
+    }
+
+    pub fn upstream(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Upstream, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Internal, message)
+    }
+
+    pub fn status(&self) -> u16 {
+        self.code.status()
+    }
+
+    pub fn body(&self) -> ErrorBody {
+        ErrorBody {
+            error: self.message.clone(),
+            code: self.code,
+            fields: self.fields.clone(),
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl<E: Coded> From<E> for ApiError {
+    fn from(e: E) -> Self {
+        ApiError::new(e.code(), e.to_string())
+    }
+}
+
+/// JSON body of an error response: `{"error": "...", "code": "..."}`, plus
+/// `"fields"` when validation failed
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorBody {
+    pub error: String,
+    pub code: ErrorCode,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>,
+}
+
+impl From<ErrorBody> for ApiError {
+    fn from(body: ErrorBody) -> Self {
+        ApiError {
+            code: body.code,
+            message: body.error,
+            fields: body.fields,
+        }
+    }
+}
+
+#[cfg(feature = "axum")]
+mod response {
+    use super::ApiError;
+    use axum::http::StatusCode;
+    use axum::response::{IntoResponse, Response};
+    use axum::Json;
+
+    impl IntoResponse for ApiError {
+        fn into_response(self) -> Response {
+            let status =
+                StatusCode::from_u16(self.status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(self.body())).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Taken;
+
+    impl fmt::Display for Taken {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("seat is taken")
+        }
+    }
+
+    impl Coded for Taken {
+        fn code(&self) -> ErrorCode {
+            ErrorCode::Conflict
+        }
+    }
+
+    #[test]
+    fn test_body_round_trips() {
+        let err = ApiError::not_found("Order not found");
+        let json = serde_json::to_value(err.body()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "error": "Order not found", "code": "not_found" })
+        );
+        let body: ErrorBody = serde_json::from_value(json).unwrap();
+        assert_eq!(ApiError::from(body), err);
+    }
+
+    #[test]
+    fn test_coded_errors_convert() {
+        let err = ApiError::from(Taken);
+        assert_eq!(err.code, ErrorCode::Conflict);
+        assert_eq!(err.status(), 409);
+        assert_eq!(err.message, "seat is taken");
+    }
+
+    #[test]
+    fn test_code_names_match_serde() {
+        for code in [
+            ErrorCode::BadRequest,
+            ErrorCode::ValidationFailed,
+            ErrorCode::InvalidSignature,
+            ErrorCode::InvalidState,
+            ErrorCode::UnsupportedMediaType,
+            ErrorCode::FeatureDisabled,
+            ErrorCode::InsufficientFunds,
+            ErrorCode::Gone,
+            ErrorCode::RateLimited,
+        ] {
+            let json = serde_json::to_value(code).unwrap();
+            assert_eq!(json, code.as_str());
+        }
+    }
+}
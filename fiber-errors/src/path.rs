@@ -0,0 +1,140 @@
+//! Path parameter extraction.
+//!
+//! Services take IDs such as a `GameId` or an `OrderId` straight from the
+//! route. [`ValidPath`] parses them like axum's `Path`, but a segment that
+//! doesn't parse is answered with an [`ApiError`] naming the parameter,
+//! `{"error": "Invalid game_id: \"nope\"", "code": "bad_request"}`, rather
+//! than axum's plain-text rejection.
+
+use crate::ApiError;
+use axum::{
+    async_trait,
+    extract::{path::ErrorKind, rejection::PathRejection, FromRequestParts, Path, RawPathParams},
+    http::request::Parts,
+};
+use serde::de::DeserializeOwned;
+
+/// Path parameters that parsed as `T`, or an [`ApiError`]
+#[derive(Debug)]
+pub struct ValidPath<T>(pub T);
+
+#[async_trait]
+impl<S, T> FromRequestParts<S> for ValidPath<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned + Send,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match Path::<T>::from_request_parts(parts, state).await {
+            Ok(Path(value)) => Ok(ValidPath(value)),
+            Err(rejection) => {
+                let params = RawPathParams::from_request_parts(parts, state).await.ok();
+                let params: Vec<(&str, &str)> = params.iter().flat_map(|p| p.iter()).collect();
+                Err(self::rejection(&params, rejection))
+            }
+        }
+    }
+}
+
+/// The error for `rejection`, naming the parameter at fault where `params`
+/// (the route's parameters, in order) tell which one it is
+fn rejection(params: &[(&str, &str)], rejection: PathRejection) -> ApiError {
+    let PathRejection::FailedToDeserializePathParams(e) = rejection else {
+        // The route has no parameters for the handler to take
+        return ApiError::internal(rejection.body_text());
+    };
+    let at = |index: usize| params.get(index).copied();
+    let param = match e.kind() {
+        ErrorKind::ParseErrorAtKey { key, value, .. } => Some((key.as_str(), value.as_str())),
+        ErrorKind::ParseErrorAtIndex { index, .. } => at(*index),
+        // A type with its own parser, like a UUID, doesn't say where it
+        // failed. Routes name their IDs `*_id`, so the one parameter, or
+        // the one ID among them, is the one at fault.
+        ErrorKind::ParseError { .. } | ErrorKind::Message(_) => {
+            let mut ids = params.iter().filter(|(name, _)| name.ends_with("_id"));
+            match (params, ids.next(), ids.next()) {
+                ([param], _, _) => Some(*param),
+                (_, Some(id), None) => Some(*id),
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+    match param {
+        Some((name, value)) => ApiError::invalid_param(name, value),
+        None => ApiError::bad_request(e.body_text()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::{routing::get, Router};
+    use serde::{Deserialize, Deserializer};
+    use tower::ServiceExt;
+
+    /// Parsed by its own code, like a UUID, so axum can't tell where it
+    /// failed
+    struct Hex(u32);
+
+    impl<'de> Deserialize<'de> for Hex {
+        fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+            let s = String::deserialize(d)?;
+            u32::from_str_radix(&s, 16)
+                .map(Hex)
+                .map_err(serde::de::Error::custom)
+        }
+    }
+
+    async fn get_json(app: Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let request = Request::get(uri).body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_names_the_parameter_at_fault() {
+        let app = Router::new()
+            .route(
+                "/games/:game_id",
+                get(|ValidPath(Hex(id)): ValidPath<Hex>| async move { axum::Json(id) }),
+            )
+            .route(
+                "/games/:game_id/:seat",
+                get(|ValidPath((_, seat)): ValidPath<(String, u32)>| async move {
+                    axum::Json(seat)
+                }),
+            )
+            .route(
+                "/games/:game_id/seats/:seat",
+                get(|ValidPath((Hex(id), _)): ValidPath<(Hex, String)>| async move {
+                    axum::Json(id)
+                }),
+            );
+
+        let (status, body) = get_json(app.clone(), "/games/1f").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, 31);
+
+        let (status, body) = get_json(app.clone(), "/games/nope").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body,
+            serde_json::json!({ "error": "Invalid game_id: \"nope\"", "code": "bad_request" })
+        );
+
+        let (_, body) = get_json(app.clone(), "/games/x/a").await;
+        assert_eq!(body["error"], "Invalid seat: \"a\"");
+
+        let (_, body) = get_json(app, "/games/x/seats/a").await;
+        assert_eq!(body["error"], "Invalid game_id: \"x\"");
+    }
+}
@@ -0,0 +1,197 @@
+//! Field-level request validation.
+//!
+//! A request type implements [`Validate`] by running its fields through a
+//! [`Validator`], which collects every broken rule instead of stopping at the
+//! first, so a client fixing a form sees all of its mistakes in one answer.
+//! With the `axum` feature, [`ValidJson`] does this as part of extraction.
+
+use crate::ApiError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::RangeInclusive;
+
+/// One field that broke a rule, as listed in a 422's `fields`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldError {
+    /// Name of the field in the request body
+    pub field: String,
+    pub message: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.field, self.message)
+    }
+}
+
+/// A request body with rules on its fields
+pub trait Validate {
+    /// Record every rule this value breaks
+    fn check(&self, v: &mut Validator);
+
+    /// `Ok`, or a [`ErrorCode::ValidationFailed`](crate::ErrorCode) error
+    /// listing every broken rule
+    fn validate(&self) -> Result<(), ApiError> {
+        let mut v = Validator::default();
+        self.check(&mut v);
+        v.finish()
+    }
+}
+
+/// Collects [`FieldError`]s; each check records one when its rule is broken
+#[derive(Debug, Default)]
+pub struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    /// Record `message` against `field` unless `ok`
+    pub fn check(&mut self, ok: bool, field: &str, message: impl Into<String>) -> &mut Self {
+        if !ok {
+            self.errors.push(FieldError {
+                field: field.to_string(),
+                message: message.into(),
+            });
+        }
+        self
+    }
+
+    pub fn positive(&mut self, field: &str, value: u64) -> &mut Self {
+        self.check(value > 0, field, "must be greater than 0")
+    }
+
+    pub fn range<T: PartialOrd + fmt::Display>(
+        &mut self,
+        field: &str,
+        value: T,
+        range: RangeInclusive<T>,
+    ) -> &mut Self {
+        let message = format!("must be between {} and {}", range.start(), range.end());
+        self.check(range.contains(&value), field, message)
+    }
+
+    pub fn not_blank(&mut self, field: &str, value: &str) -> &mut Self {
+        self.check(!value.trim().is_empty(), field, "must not be empty")
+    }
+
+    /// At most `max` characters (not bytes)
+    pub fn max_len(&mut self, field: &str, value: &str, max: usize) -> &mut Self {
+        let message = format!("must be at most {} characters", max);
+        self.check(value.chars().count() <= max, field, message)
+    }
+
+    /// `bytes` bytes of hex, with or without a `0x` prefix
+    pub fn hex(&mut self, field: &str, value: &str, bytes: usize) -> &mut Self {
+        let digits = value.strip_prefix("0x").unwrap_or(value);
+        let ok = digits.len() == bytes * 2 && digits.bytes().all(|b| b.is_ascii_hexdigit());
+        self.check(ok, field, format!("must be {} bytes of hex", bytes))
+    }
+
+    /// `Ok` if nothing was recorded
+    pub fn finish(self) -> Result<(), ApiError> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ApiError::validation(self.errors))
+        }
+    }
+}
+
+#[cfg(feature = "axum")]
+mod extract {
+    use super::Validate;
+    use crate::{ApiError, ErrorCode};
+    use axum::{
+        async_trait,
+        extract::{rejection::JsonRejection, FromRequest, Request},
+        http::StatusCode,
+        Json,
+    };
+    use serde::de::DeserializeOwned;
+
+    /// A JSON body that parsed and passed [`Validate`]; anything else is
+    /// rejected with an [`ApiError`] instead of axum's plain-text rejection
+    pub struct ValidJson<T>(pub T);
+
+    #[async_trait]
+    impl<S, T> FromRequest<S> for ValidJson<T>
+    where
+        S: Send + Sync,
+        T: DeserializeOwned + Validate,
+    {
+        type Rejection = ApiError;
+
+        async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+            let Json(value) = Json::<T>::from_request(req, state)
+                .await
+                .map_err(rejection)?;
+            value.validate()?;
+            Ok(ValidJson(value))
+        }
+    }
+
+    fn rejection(rejection: JsonRejection) -> ApiError {
+        let code = match rejection.status() {
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => ErrorCode::UnsupportedMediaType,
+            StatusCode::UNPROCESSABLE_ENTITY => ErrorCode::ValidationFailed,
+            _ => ErrorCode::BadRequest,
+        };
+        ApiError::new(code, rejection.body_text())
+    }
+}
+
+#[cfg(feature = "axum")]
+pub use extract::ValidJson;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorCode;
+
+    struct Listing {
+        title: String,
+        price: u64,
+        preimage: String,
+    }
+
+    impl Validate for Listing {
+        fn check(&self, v: &mut Validator) {
+            v.not_blank("title", &self.title)
+                .max_len("title", &self.title, 5)
+                .positive("price", self.price)
+                .hex("preimage", &self.preimage, 2);
+        }
+    }
+
+    #[test]
+    fn test_reports_every_broken_field() {
+        let listing = Listing {
+            title: "too long".to_string(),
+            price: 0,
+            preimage: "0xabcd".to_string(),
+        };
+        let err = listing.validate().unwrap_err();
+        assert_eq!(err.code, ErrorCode::ValidationFailed);
+        assert_eq!(err.status(), 422);
+        let fields: Vec<_> = err.fields.iter().map(|f| f.field.as_str()).collect();
+        assert_eq!(fields, ["title", "price"]);
+        assert_eq!(
+            err.message,
+            "Invalid request: title must be at most 5 characters; price must be greater than 0"
+        );
+    }
+
+    #[test]
+    fn test_hex_and_range() {
+        let mut v = Validator::default();
+        v.hex("a", "abcd", 2)
+            .hex("b", "0xabc", 2)
+            .hex("c", "zzzz", 2)
+            .range("d", 7, 1..=10)
+            .range("e", 0, 1..=10);
+        let err = v.finish().unwrap_err();
+        let fields: Vec<_> = err.fields.iter().map(|f| f.field.as_str()).collect();
+        assert_eq!(fields, ["b", "c", "e"]);
+        assert_eq!(err.fields[2].message, "must be between 1 and 10");
+    }
+}
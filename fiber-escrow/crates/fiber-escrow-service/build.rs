@@ -0,0 +1,48 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    // The SQL files under migrations/ are embedded at compile time; rebuild
+    // when one is added or edited.
+    println!("cargo:rerun-if-changed=migrations");
+    #[cfg(feature = "grpc")]
+    grpc::generate();
+}
+
+/// Server and client stubs for the service in proto/escrow.proto. Its
+/// messages are written out in src/grpc.rs, so building needs no `protoc`.
+#[cfg(feature = "grpc")]
+mod grpc {
+    use tonic_build::manual::{Builder, Method, Service};
+
+    /// Method, route, request and reply message
+    #[rustfmt::skip]
+    const METHODS: &[(&str, &str, &str, &str)] = &[
+        ("create_order", "CreateOrder", "CreateOrderRequest", "Order"),
+        ("get_order", "GetOrder", "OrderRef", "Order"),
+        ("list_my_orders", "ListMyOrders", "ListMyOrdersRequest", "OrderList"),
+        ("submit_invoice", "SubmitInvoice", "SubmitInvoiceRequest", "Order"),
+        ("pay_order", "PayOrder", "OrderRef", "Order"),
+        ("ship_order", "ShipOrder", "OrderRef", "Order"),
+        ("confirm_order", "ConfirmOrder", "OrderRef", "Order"),
+        ("dispute_order", "DisputeOrder", "DisputeOrderRequest", "Order"),
+    ];
+
+    pub fn generate() {
+        let service = METHODS.iter().fold(
+            Service::builder().name("Escrow").package("fiber.escrow.v1"),
+            |service, (name, route, request, reply)| {
+                service.method(
+                    Method::builder()
+                        .name(name)
+                        .route_name(route)
+                        .input_type(format!("super::{}", request))
+                        .output_type(format!("super::{}", reply))
+                        .codec_path("tonic::codec::ProstCodec")
+                        .build(),
+                )
+            },
+        );
+        Builder::new()
+            .build_transport(false)
+            .compile(&[service.build()]);
+    }
+}
@@ -0,0 +1,13 @@
+-- Subscriptions and the orders billed for them, as JSON; an order's
+-- preimage is kept apart since its JSON leaves it out. IF NOT EXISTS lets
+-- databases from before migrations adopt this history as they are.
+CREATE TABLE IF NOT EXISTS escrow_subscriptions (
+    subscription_id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS escrow_orders (
+    order_id TEXT PRIMARY KEY,
+    data TEXT NOT NULL,
+    preimage TEXT
+);
@@ -0,0 +1,16 @@
+-- Users, categories and products, as JSON, so the subscriptions and orders
+-- restored at startup still point at a buyer, seller and product.
+CREATE TABLE IF NOT EXISTS escrow_users (
+    user_id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS escrow_categories (
+    category_id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS escrow_products (
+    product_id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
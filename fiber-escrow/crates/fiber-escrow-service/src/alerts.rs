@@ -0,0 +1,138 @@
+//! Alerts for held orders nearing their hold invoice's expiry.
+//!
+//! The seller's node cancels a hold invoice once it expires, and the buyer
+//! has their money back whatever the order says. An order that is still
+//! waiting to be shipped, confirmed or decided by then is refunded without
+//! anyone having chosen to. [`spawn`] checks every [`CHECK_INTERVAL`] how
+//! long each held order's invoice has left and raises a warning, then a
+//! critical alert, as it crosses the configured thresholds
+//! ([`AppState::with_deadline_alerts`]). Each level is raised once per
+//! order: buyer and seller are notified, an
+//! [`Event::SettlementDeadline`](fiber_service::Event::SettlementDeadline)
+//! is counted in the metrics, the order is flagged at `/api/admin/alerts`,
+//! and the alert is POSTed to each configured [`Notifier`]. A failed
+//! notification is logged, not retried. With a
+//! [`WebhookSigner`] the POSTs carry a signature receivers check with
+//! [`fiber_auth::webhook::WebhookVerifier`].
+
+use crate::models::{AlertLevel, SettlementAlert};
+use crate::state::AppState;
+use async_trait::async_trait;
+use fiber_auth::webhook::{WebhookSigner, SIGNATURE_HEADER};
+use reqwest::header::CONTENT_TYPE;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// How often held orders are checked for invoices about to expire
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long a webhook gets to accept an alert
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Notification error
+#[derive(Debug, thiserror::Error)]
+pub enum NotifyError {
+    #[error("http error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+/// Somewhere alerts are sent for operators
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Short description for logs, e.g. the URL notified
+    fn name(&self) -> String;
+
+    async fn notify(&self, alert: &SettlementAlert) -> Result<(), NotifyError>;
+}
+
+/// POSTs each alert as JSON to a URL
+pub struct WebhookNotifier {
+    url: String,
+    client: reqwest::Client,
+    signer: Option<WebhookSigner>,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            client: reqwest::Client::new(),
+            signer: None,
+        }
+    }
+
+    /// Sign each alert with `signer`, if there is one
+    pub fn with_signer(mut self, signer: Option<WebhookSigner>) -> Self {
+        self.signer = signer;
+        self
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> String {
+        self.url.clone()
+    }
+
+    async fn notify(&self, alert: &SettlementAlert) -> Result<(), NotifyError> {
+        let request = self.client.post(&self.url).timeout(NOTIFY_TIMEOUT);
+        let request = match &self.signer {
+            Some(signer) => {
+                let signed = signer.sign(alert)?;
+                request
+                    .header(CONTENT_TYPE, "application/json")
+                    .header(SIGNATURE_HEADER, signed.signature)
+                    .body(signed.body)
+            }
+            None => request.json(alert),
+        };
+        request
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Log a raised alert, as a warning once it is critical
+pub fn log(alert: &SettlementAlert) {
+    let order_id = alert.order_id.0;
+    let status = alert.status;
+    let seconds_left = alert.seconds_left;
+    match alert.level {
+        AlertLevel::Warning => {
+            info!(%order_id, ?status, seconds_left, "Held order's invoice expires soon")
+        }
+        AlertLevel::Critical => {
+            warn!(%order_id, ?status, seconds_left, "Held order is about to be refunded")
+        }
+    }
+}
+
+/// Raise alerts every [`CHECK_INTERVAL`] for as long as the process runs,
+/// passing each on to `notifiers` as well.
+pub fn spawn(state: AppState, notifiers: Vec<Arc<dyn Notifier>>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            for alert in state.raise_deadline_alerts().await {
+                log(&alert);
+                for notifier in &notifiers {
+                    match notifier.notify(&alert).await {
+                        Ok(()) => debug!(order_id = %alert.order_id.0, to = %notifier.name(), "Alert sent"),
+                        Err(e) => warn!(
+                            order_id = %alert.order_id.0,
+                            to = %notifier.name(),
+                            error = %e,
+                            "Failed to send alert"
+                        ),
+                    }
+                }
+            }
+        }
+    });
+}
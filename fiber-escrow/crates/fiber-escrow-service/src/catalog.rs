@@ -0,0 +1,251 @@
+//! Bulk import and export of a seller's catalog.
+//!
+//! A seller moving an existing catalog over sends it in one request, as CSV
+//! with a header row or as a JSON array, each row what `POST /api/products`
+//! takes. Every row is checked on its own: valid rows are created, broken
+//! ones are skipped, and the [`ImportReport`] says which row became which
+//! product and what was wrong with the others. A dry run checks the rows
+//! without creating anything. An export lists the seller's products in the
+//! same columns, plus their ID and status, so it can be edited and imported
+//! again (the ID and status are ignored on the way back in).
+
+use fiber_errors::{ApiError, ErrorCode, FieldError, Validate};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::handlers::{CreateProductRequest, ImportReport, ImportedRow};
+use crate::models::*;
+use crate::products;
+use crate::state::AppState;
+
+/// Most rows one import may carry
+pub const MAX_IMPORT_ROWS: usize = 1_000;
+
+/// Columns of a CSV export, written even when there are no products
+const EXPORT_COLUMNS: [&str; 8] = [
+    "id",
+    "status",
+    "title",
+    "description",
+    "price_shannons",
+    "billing_period_secs",
+    "category_id",
+    "draft",
+];
+
+/// The formats a catalog is imported and exported in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CatalogFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+impl CatalogFormat {
+    /// The format of a body sent as `content_type`
+    pub fn of_content_type(content_type: Option<&str>) -> Result<Self, ApiError> {
+        let essence = content_type
+            .and_then(|t| t.split(';').next())
+            .map(str::trim)
+            .unwrap_or_default();
+        match essence {
+            "application/json" => Ok(Self::Json),
+            "text/csv" => Ok(Self::Csv),
+            _ => Err(ApiError::new(
+                ErrorCode::UnsupportedMediaType,
+                "Import a catalog as application/json or text/csv",
+            )),
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Csv => "text/csv",
+        }
+    }
+}
+
+/// A row as parsed: the product it asks for, or what kept it from parsing
+type Row = Result<CreateProductRequest, Vec<FieldError>>;
+
+fn row_error(field: &str, message: impl Into<String>) -> Vec<FieldError> {
+    vec![FieldError {
+        field: field.to_string(),
+        message: message.into(),
+    }]
+}
+
+/// The rows of an import body. Only a body that can't be read as rows at
+/// all (not a JSON array, no CSV header, too many rows) is an error; a row
+/// that doesn't parse is reported with that row.
+pub fn parse(format: CatalogFormat, body: &[u8]) -> Result<Vec<Row>, ApiError> {
+    let rows = match format {
+        CatalogFormat::Json => parse_json(body)?,
+        CatalogFormat::Csv => parse_csv(body)?,
+    };
+    if rows.is_empty() {
+        return Err(ApiError::bad_request("No products to import"));
+    }
+    if rows.len() > MAX_IMPORT_ROWS {
+        return Err(ApiError::bad_request(format!(
+            "At most {} products can be imported at once",
+            MAX_IMPORT_ROWS
+        )));
+    }
+    Ok(rows)
+}
+
+fn parse_json(body: &[u8]) -> Result<Vec<Row>, ApiError> {
+    let values: Vec<serde_json::Value> = serde_json::from_slice(body)
+        .map_err(|e| ApiError::bad_request(format!("Expected a JSON array of products: {}", e)))?;
+    Ok(values
+        .into_iter()
+        .map(|value| serde_json::from_value(value).map_err(|e| row_error("row", e.to_string())))
+        .collect())
+}
+
+fn parse_csv(body: &[u8]) -> Result<Vec<Row>, ApiError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(body);
+    let headers = reader
+        .headers()
+        .map_err(|e| ApiError::bad_request(format!("Expected a CSV header row: {}", e)))?
+        .clone();
+    Ok(reader
+        .records()
+        .map(|record| {
+            record
+                .and_then(|record| record.deserialize(Some(&headers)))
+                .map_err(|e| match e.kind() {
+                    // Name the column that didn't parse, if csv knows it
+                    csv::ErrorKind::Deserialize { err, .. } => row_error(
+                        err.field()
+                            .and_then(|i| headers.get(i as usize))
+                            .unwrap_or("row"),
+                        err.kind().to_string(),
+                    ),
+                    _ => row_error("row", e.to_string()),
+                })
+        })
+        .collect())
+}
+
+/// Create a product for `seller_id` from each valid row, unless this is a
+/// dry run, and report on every row
+pub async fn import(
+    state: &AppState,
+    seller_id: UserId,
+    rows: Vec<Row>,
+    dry_run: bool,
+) -> ImportReport {
+    let mut report = ImportReport {
+        dry_run,
+        created: 0,
+        failed: 0,
+        rows: Vec::with_capacity(rows.len()),
+    };
+    for (i, row) in rows.into_iter().enumerate() {
+        let mut imported = ImportedRow {
+            row: i + 1,
+            product_id: None,
+            status: None,
+            errors: Vec::new(),
+        };
+        match import_row(state, seller_id, row, dry_run).await {
+            Ok(Some(product)) => {
+                imported.product_id = Some(product.id.0);
+                imported.status = Some(product.status);
+                report.created += 1;
+            }
+            Ok(None) => {}
+            Err(errors) => {
+                imported.errors = errors;
+                report.failed += 1;
+            }
+        }
+        report.rows.push(imported);
+    }
+    if !dry_run {
+        tracing::info!(
+            seller_id = %seller_id.0,
+            created = report.created,
+            failed = report.failed,
+            "Products imported"
+        );
+    }
+    report
+}
+
+/// The product created from `row`, or `None` on a dry run
+async fn import_row(
+    state: &AppState,
+    seller_id: UserId,
+    row: Row,
+    dry_run: bool,
+) -> Result<Option<Product>, Vec<FieldError>> {
+    let req = row?;
+    req.validate().map_err(|e| e.fields)?;
+    let category = |e: ApiError| row_error("category_id", e.message);
+    if dry_run {
+        products::check_category(state, req.category_id.map(CategoryId))
+            .await
+            .map_err(category)?;
+        return Ok(None);
+    }
+    products::create(state, seller_id, req)
+        .await
+        .map(Some)
+        .map_err(category)
+}
+
+/// A product as exported: the columns an import takes, after its ID and
+/// status
+#[derive(Serialize)]
+struct ExportedProduct {
+    id: Uuid,
+    status: ProductStatus,
+    title: String,
+    description: String,
+    price_shannons: u64,
+    billing_period_secs: Option<u64>,
+    category_id: Option<Uuid>,
+    draft: bool,
+}
+
+impl From<Product> for ExportedProduct {
+    fn from(p: Product) -> Self {
+        Self {
+            id: p.id.0,
+            status: p.status,
+            title: p.title,
+            description: p.description,
+            price_shannons: p.price_shannons,
+            billing_period_secs: p.billing_period_secs,
+            category_id: p.category_id.map(|id| id.0),
+            draft: p.status == ProductStatus::Draft,
+        }
+    }
+}
+
+/// `products`, oldest first, as a file in `format`
+pub fn export(mut products: Vec<Product>, format: CatalogFormat) -> Result<Vec<u8>, ApiError> {
+    products.sort_by_key(|p| (p.created_at, p.id.0));
+    let rows = products.into_iter().map(ExportedProduct::from);
+    let failed = |e: &dyn std::fmt::Display| ApiError::internal(format!("Export failed: {}", e));
+    match format {
+        CatalogFormat::Json => serde_json::to_vec(&rows.collect::<Vec<_>>()).map_err(|e| failed(&e)),
+        CatalogFormat::Csv => {
+            let mut writer = csv::WriterBuilder::new()
+                .has_headers(false)
+                .from_writer(Vec::new());
+            writer.write_record(EXPORT_COLUMNS).map_err(|e| failed(&e))?;
+            for row in rows {
+                writer.serialize(row).map_err(|e| failed(&e))?;
+            }
+            writer.into_inner().map_err(|e| failed(&e))
+        }
+    }
+}
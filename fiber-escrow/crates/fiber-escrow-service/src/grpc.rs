@@ -0,0 +1,304 @@
+//! gRPC interface to the order lifecycle, for integrators that would rather
+//! call the escrow than speak its HTTP API; see `proto/escrow.proto`.
+//!
+//! Calls go through [`crate::orders`] like the HTTP handlers do, and act
+//! for the user in the `x-user-id` metadata entry.
+
+use axum::Router;
+use fiber_auth::AuthedUser;
+use fiber_errors::{ApiError, Validate};
+use fiber_paging::PageRequest;
+use serde::Serialize;
+use serde_json::Value;
+use tonic::{server::NamedService, Request, Response, Status};
+use uuid::Uuid;
+
+use crate::handlers::{
+    order_to_response, CreateOrderRequest, DisputeRequest, ListMyOrdersQuery,
+    SubmitInvoiceRequest,
+};
+use crate::models::{OrderId, UserId};
+use crate::orders;
+use crate::state::AppState;
+use pb::escrow_server::{Escrow, EscrowServer};
+
+/// Messages of `fiber.escrow.v1`, with the generated client and server
+pub mod pb {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct CreateOrderRequest {
+        #[prost(string, tag = "1")]
+        pub product_id: String,
+        #[prost(string, tag = "2")]
+        pub preimage: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct OrderRef {
+        #[prost(string, tag = "1")]
+        pub order_id: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ListMyOrdersRequest {
+        /// Empty for the first page
+        #[prost(string, tag = "1")]
+        pub cursor: String,
+        /// 0 for the default page size
+        #[prost(uint32, tag = "2")]
+        pub limit: u32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SubmitInvoiceRequest {
+        #[prost(string, tag = "1")]
+        pub order_id: String,
+        #[prost(string, tag = "2")]
+        pub invoice: String,
+        /// 0 for the default expiry
+        #[prost(uint64, tag = "3")]
+        pub expiry_secs: u64,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct DisputeOrderRequest {
+        #[prost(string, tag = "1")]
+        pub order_id: String,
+        #[prost(string, tag = "2")]
+        pub reason: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Order {
+        #[prost(string, tag = "1")]
+        pub id: String,
+        #[prost(string, tag = "2")]
+        pub product_id: String,
+        #[prost(string, tag = "3")]
+        pub product_title: String,
+        #[prost(string, tag = "4")]
+        pub seller_id: String,
+        #[prost(string, tag = "5")]
+        pub buyer_id: String,
+        #[prost(uint64, tag = "6")]
+        pub amount_shannons: u64,
+        #[prost(string, tag = "7")]
+        pub payment_hash: String,
+        #[prost(string, optional, tag = "8")]
+        pub invoice: Option<String>,
+        #[prost(string, tag = "9")]
+        pub status: String,
+        #[prost(string, tag = "10")]
+        pub created_at: String,
+        #[prost(string, tag = "11")]
+        pub expires_at: String,
+        #[prost(string, optional, tag = "12")]
+        pub subscription_id: Option<String>,
+        #[prost(message, optional, tag = "13")]
+        pub dispute: Option<Dispute>,
+        /// Only for the seller, once the order is completed
+        #[prost(string, optional, tag = "14")]
+        pub preimage: Option<String>,
+        #[prost(string, optional, tag = "15")]
+        pub invoice_expires_at: Option<String>,
+        /// "warning" or "critical" once the invoice is close to expiring
+        #[prost(string, optional, tag = "16")]
+        pub deadline_alert: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Dispute {
+        #[prost(string, tag = "1")]
+        pub reason: String,
+        #[prost(string, tag = "2")]
+        pub created_at: String,
+        #[prost(string, optional, tag = "3")]
+        pub resolution: Option<String>,
+        #[prost(string, optional, tag = "4")]
+        pub resolution_reason: Option<String>,
+        #[prost(string, optional, tag = "5")]
+        pub resolution_note: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct OrderList {
+        #[prost(message, repeated, tag = "1")]
+        pub orders: Vec<Order>,
+        /// Empty on the last page
+        #[prost(string, tag = "2")]
+        pub next_cursor: String,
+    }
+
+    include!(concat!(env!("OUT_DIR"), "/fiber.escrow.v1.Escrow.rs"));
+}
+
+/// gRPC routes, to merge into the escrow's HTTP router
+pub fn router(state: AppState) -> Router {
+    let path = format!("/{}/*rpc", EscrowServer::<EscrowService>::NAME);
+    Router::new().route_service(&path, EscrowServer::new(EscrowService { state }))
+}
+
+struct EscrowService {
+    state: AppState,
+}
+
+fn caller<T>(request: &Request<T>) -> Result<UserId, ApiError> {
+    let headers = request.metadata().clone().into_headers();
+    AuthedUser::from_headers(&headers).map(UserId::from)
+}
+
+fn uuid(field: &str, id: &str) -> Result<Uuid, ApiError> {
+    Uuid::parse_str(id).map_err(|_| ApiError::invalid_param(field, id))
+}
+
+fn order_id(id: &str) -> Result<OrderId, ApiError> {
+    uuid("order_id", id).map(OrderId)
+}
+
+/// A unit enum as the HTTP API spells it, e.g. `waiting_payment`
+fn wire_name<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(Value::String(name)) => name,
+        _ => String::new(),
+    }
+}
+
+impl EscrowService {
+    /// The order as the caller sees it after a call changed it
+    async fn order(&self, user_id: UserId, order_id: OrderId) -> Result<pb::Order, ApiError> {
+        let (order, preimage) = orders::view(&self.state, user_id, order_id).await?;
+        let order = order_to_response(&order);
+        Ok(pb::Order {
+            id: order.id.to_string(),
+            product_id: order.product_id.to_string(),
+            product_title: order.product_title,
+            seller_id: order.seller_id.to_string(),
+            buyer_id: order.buyer_id.to_string(),
+            amount_shannons: order.amount_shannons,
+            payment_hash: order.payment_hash,
+            invoice: order.invoice_string,
+            status: wire_name(&order.status),
+            created_at: order.created_at,
+            expires_at: order.expires_at,
+            subscription_id: order.subscription_id.map(|id| id.to_string()),
+            dispute: order.dispute.map(|dispute| pb::Dispute {
+                reason: dispute.reason,
+                created_at: dispute.created_at,
+                resolution: dispute.resolution.as_ref().map(wire_name),
+                resolution_reason: dispute.resolution_reason.as_ref().map(wire_name),
+                resolution_note: dispute.resolution_note,
+            }),
+            preimage: preimage.map(|p| format!("0x{}", hex::encode(p.as_bytes()))),
+            invoice_expires_at: order.invoice_expires_at,
+            deadline_alert: order.deadline_alert.map(|level| level.as_str().to_string()),
+        })
+    }
+}
+
+#[tonic::async_trait]
+impl Escrow for EscrowService {
+    async fn create_order(
+        &self,
+        request: Request<pb::CreateOrderRequest>,
+    ) -> Result<Response<pb::Order>, Status> {
+        let buyer_id = caller(&request)?;
+        let req = CreateOrderRequest {
+            product_id: uuid("product_id", &request.get_ref().product_id)?,
+            preimage: request.into_inner().preimage,
+        };
+        req.validate()?;
+        let order = orders::create(&self.state, buyer_id, &req).await?;
+        Ok(Response::new(self.order(buyer_id, order.id).await?))
+    }
+
+    async fn get_order(
+        &self,
+        request: Request<pb::OrderRef>,
+    ) -> Result<Response<pb::Order>, Status> {
+        let user_id = caller(&request)?;
+        let order_id = order_id(&request.get_ref().order_id)?;
+        Ok(Response::new(self.order(user_id, order_id).await?))
+    }
+
+    async fn list_my_orders(
+        &self,
+        request: Request<pb::ListMyOrdersRequest>,
+    ) -> Result<Response<pb::OrderList>, Status> {
+        let user_id = caller(&request)?;
+        // proto3 has no unset scalars: empty and 0 mean "not given"
+        let req = request.into_inner();
+        let page = PageRequest {
+            cursor: Some(req.cursor).filter(|cursor| !cursor.is_empty()),
+            limit: Some(req.limit).filter(|limit| *limit > 0),
+        };
+        let query = ListMyOrdersQuery::default();
+        let page = orders::list(&self.state, user_id, &query, &page).await?;
+        let mut orders = Vec::new();
+        for order in page.items {
+            orders.push(self.order(user_id, order.id).await?);
+        }
+        Ok(Response::new(pb::OrderList {
+            orders,
+            next_cursor: page.next_cursor.unwrap_or_default(),
+        }))
+    }
+
+    async fn submit_invoice(
+        &self,
+        request: Request<pb::SubmitInvoiceRequest>,
+    ) -> Result<Response<pb::Order>, Status> {
+        let user_id = caller(&request)?;
+        let order_id = order_id(&request.get_ref().order_id)?;
+        let req = request.into_inner();
+        let req = SubmitInvoiceRequest {
+            invoice: req.invoice,
+            expiry_secs: Some(req.expiry_secs).filter(|secs| *secs > 0),
+        };
+        req.validate()?;
+        orders::submit_invoice(&self.state, user_id, order_id, &req).await?;
+        Ok(Response::new(self.order(user_id, order_id).await?))
+    }
+
+    async fn pay_order(
+        &self,
+        request: Request<pb::OrderRef>,
+    ) -> Result<Response<pb::Order>, Status> {
+        let user_id = caller(&request)?;
+        let order_id = order_id(&request.get_ref().order_id)?;
+        orders::pay(&self.state, user_id, order_id).await?;
+        Ok(Response::new(self.order(user_id, order_id).await?))
+    }
+
+    async fn ship_order(
+        &self,
+        request: Request<pb::OrderRef>,
+    ) -> Result<Response<pb::Order>, Status> {
+        let user_id = caller(&request)?;
+        let order_id = order_id(&request.get_ref().order_id)?;
+        orders::ship(&self.state, user_id, order_id).await?;
+        Ok(Response::new(self.order(user_id, order_id).await?))
+    }
+
+    async fn confirm_order(
+        &self,
+        request: Request<pb::OrderRef>,
+    ) -> Result<Response<pb::Order>, Status> {
+        let user_id = caller(&request)?;
+        let order_id = order_id(&request.get_ref().order_id)?;
+        orders::confirm(&self.state, user_id, order_id).await?;
+        Ok(Response::new(self.order(user_id, order_id).await?))
+    }
+
+    async fn dispute_order(
+        &self,
+        request: Request<pb::DisputeOrderRequest>,
+    ) -> Result<Response<pb::Order>, Status> {
+        let user_id = caller(&request)?;
+        let order_id = order_id(&request.get_ref().order_id)?;
+        let req = DisputeRequest {
+            reason: request.into_inner().reason,
+        };
+        req.validate()?;
+        orders::dispute(&self.state, user_id, order_id, req.reason).await?;
+        Ok(Response::new(self.order(user_id, order_id).await?))
+    }
+}
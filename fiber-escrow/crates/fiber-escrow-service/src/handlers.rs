@@ -4,24 +4,57 @@
 //! The backend manages order state and reveals preimage when appropriate.
 
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    body::Bytes,
+    extract::{Query, State},
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        HeaderMap,
+    },
     response::IntoResponse,
     Json,
 };
+use fiber_auth::{AuthedIdentity, AuthedUser, IDENTITY_HEADER};
+use chrono::{DateTime, Utc};
+use fiber_core::money;
+use fiber_errors::{ApiError, FieldError, ValidJson, ValidPath, Validate, Validator};
+use fiber_paging::{Page, PageRequest};
 use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
 use uuid::Uuid;
 
+use crate::catalog::{self, CatalogFormat};
 use crate::models::*;
-use crate::state::AppState;
+use crate::orders;
+use crate::products;
+use crate::state::{AppState, AUTO_SETTLE, HELD};
 
 // ============ Request/Response types ============
 
+const MAX_USERNAME_LEN: usize = 64;
+const MAX_NAME_LEN: usize = 100;
+const MAX_TITLE_LEN: usize = 200;
+const MAX_DESCRIPTION_LEN: usize = 5_000;
+const MAX_REASON_LEN: usize = 2_000;
+/// Fiber invoices are a few hundred characters; this only stops junk
+const MAX_INVOICE_LEN: usize = 4_096;
+/// Longest jump `/api/system/tick` takes at once
+const MAX_TICK_SECS: i64 = 366 * 86_400;
+/// Longest hold invoice expiry the escrow keeps track of
+const MAX_INVOICE_EXPIRY_SECS: u64 = 30 * 86_400;
+
 #[derive(Deserialize)]
 pub struct RegisterRequest {
     pub username: String,
 }
 
+impl Validate for RegisterRequest {
+    fn check(&self, v: &mut Validator) {
+        v.not_blank("username", &self.username)
+            .max_len("username", &self.username, MAX_USERNAME_LEN);
+    }
+}
+
 #[derive(Serialize)]
 pub struct UserResponse {
     pub id: Uuid,
@@ -44,6 +77,110 @@ pub struct CreateProductRequest {
     pub title: String,
     pub description: String,
     pub price_shannons: u64,
+    /// Set to sell the product as a subscription billed every period
+    pub billing_period_secs: Option<u64>,
+    pub category_id: Option<Uuid>,
+    /// Keep the product as a draft, hidden from buyers until published
+    #[serde(default)]
+    pub draft: bool,
+}
+
+/// More than nothing, and within [`money::MAX_SHANNONS`] so buyers' and
+/// sellers' balances can count it
+fn check_price(v: &mut Validator, price: u64) {
+    v.positive("price_shannons", price).check(
+        price <= money::MAX_SHANNONS,
+        "price_shannons",
+        format!("must be at most {}", money::MAX_SHANNONS),
+    );
+}
+
+impl Validate for CreateProductRequest {
+    fn check(&self, v: &mut Validator) {
+        v.not_blank("title", &self.title)
+            .max_len("title", &self.title, MAX_TITLE_LEN)
+            .max_len("description", &self.description, MAX_DESCRIPTION_LEN);
+        check_price(v, self.price_shannons);
+        if let Some(period) = self.billing_period_secs {
+            v.positive("billing_period_secs", period);
+        }
+    }
+}
+
+/// Changes to a draft; fields left out stay as they are
+#[derive(Deserialize)]
+pub struct EditProductRequest {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub price_shannons: Option<u64>,
+    pub billing_period_secs: Option<u64>,
+    pub category_id: Option<Uuid>,
+}
+
+impl Validate for EditProductRequest {
+    fn check(&self, v: &mut Validator) {
+        if let Some(title) = &self.title {
+            v.not_blank("title", title).max_len("title", title, MAX_TITLE_LEN);
+        }
+        if let Some(description) = &self.description {
+            v.max_len("description", description, MAX_DESCRIPTION_LEN);
+        }
+        if let Some(price) = self.price_shannons {
+            check_price(v, price);
+        }
+        if let Some(period) = self.billing_period_secs {
+            v.positive("billing_period_secs", period);
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ListProductsQuery {
+    /// Category ID or slug; includes products in subcategories
+    pub category: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ListMyProductsQuery {
+    /// Only products in this status, e.g. `draft`
+    pub status: Option<ProductStatus>,
+}
+
+#[derive(Deserialize)]
+pub struct ImportProductsQuery {
+    /// Check every row without creating any products
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Deserialize)]
+pub struct ExportProductsQuery {
+    #[serde(default)]
+    pub format: CatalogFormat,
+    /// Only products in this status, e.g. `draft`
+    pub status: Option<ProductStatus>,
+}
+
+/// What became of each row of a product import
+#[derive(Serialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    /// Products created (none on a dry run)
+    pub created: usize,
+    /// Rows skipped because they were invalid
+    pub failed: usize,
+    pub rows: Vec<ImportedRow>,
+}
+
+#[derive(Serialize)]
+pub struct ImportedRow {
+    /// 1-based, not counting a CSV header row
+    pub row: usize,
+    pub product_id: Option<Uuid>,
+    pub status: Option<ProductStatus>,
+    /// Why the row was skipped, in the shape of a 422's `fields`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
 }
 
 #[derive(Serialize)]
@@ -54,9 +191,57 @@ pub struct ProductResponse {
     pub title: String,
     pub description: String,
     pub price_shannons: u64,
+    pub billing_period_secs: Option<u64>,
+    pub category_id: Option<Uuid>,
     pub status: ProductStatus,
 }
 
+impl ProductResponse {
+    fn new(p: Product, seller_username: Option<String>) -> Self {
+        Self {
+            id: p.id.0,
+            seller_id: p.seller_id.0,
+            seller_username,
+            title: p.title,
+            description: p.description,
+            price_shannons: p.price_shannons,
+            billing_period_secs: p.billing_period_secs,
+            category_id: p.category_id.map(|id| id.0),
+            status: p.status,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CreateCategoryRequest {
+    pub name: String,
+    /// Derived from the name when omitted
+    pub slug: Option<String>,
+    pub parent_id: Option<Uuid>,
+}
+
+impl Validate for CreateCategoryRequest {
+    fn check(&self, v: &mut Validator) {
+        v.not_blank("name", &self.name)
+            .max_len("name", &self.name, MAX_NAME_LEN);
+        if let Some(slug) = &self.slug {
+            v.max_len("slug", slug, MAX_NAME_LEN);
+        }
+    }
+}
+
+/// Category with its subcategories, for the navigation tree
+#[derive(Serialize)]
+pub struct CategoryNode {
+    pub id: Uuid,
+    pub name: String,
+    pub slug: String,
+    pub parent_id: Option<Uuid>,
+    /// Available products in this category and its subcategories
+    pub product_count: usize,
+    pub children: Vec<CategoryNode>,
+}
+
 #[derive(Deserialize)]
 pub struct CreateOrderRequest {
     pub product_id: Uuid,
@@ -65,10 +250,74 @@ pub struct CreateOrderRequest {
     pub preimage: String,
 }
 
+impl Validate for CreateOrderRequest {
+    fn check(&self, v: &mut Validator) {
+        v.hex("preimage", &self.preimage, 32);
+    }
+}
+
 #[derive(Deserialize)]
 pub struct SubmitInvoiceRequest {
     /// Hold invoice string created by seller
     pub invoice: String,
+    /// The expiry the invoice was created with, if not
+    /// [`DEFAULT_INVOICE_EXPIRY_SECS`](crate::state::DEFAULT_INVOICE_EXPIRY_SECS)
+    #[serde(default)]
+    pub expiry_secs: Option<u64>,
+}
+
+impl Validate for SubmitInvoiceRequest {
+    fn check(&self, v: &mut Validator) {
+        v.not_blank("invoice", &self.invoice)
+            .max_len("invoice", &self.invoice, MAX_INVOICE_LEN);
+        if let Some(expiry_secs) = self.expiry_secs {
+            v.range("expiry_secs", expiry_secs, 1..=MAX_INVOICE_EXPIRY_SECS);
+        }
+    }
+}
+
+/// How `GET /api/orders/mine` sorts its orders
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderSort {
+    #[default]
+    Newest,
+    Oldest,
+    /// Largest amount first
+    Amount,
+    /// Nearest deadline first: the order's timeout or, if sooner, its
+    /// invoice's expiry
+    Deadline,
+}
+
+#[derive(Default, Deserialize)]
+pub struct ListMyOrdersQuery {
+    /// Only orders in this status, e.g. `funded`
+    pub status: Option<OrderStatus>,
+    pub product_id: Option<Uuid>,
+    pub buyer_id: Option<Uuid>,
+    /// Only orders created at or after this time
+    pub from: Option<DateTime<Utc>>,
+    /// Only orders created before this time
+    pub to: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub sort: OrderSort,
+}
+
+impl Validate for ListMyOrdersQuery {
+    fn check(&self, v: &mut Validator) {
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            v.check(from <= to, "to", "must not be before from");
+        }
+    }
+}
+
+/// How many of the caller's orders are in each status
+#[derive(Serialize)]
+pub struct OrderSummaryResponse {
+    pub total: usize,
+    /// Every status, including those with no orders
+    pub by_status: BTreeMap<OrderStatus, usize>,
 }
 
 #[derive(Serialize)]
@@ -84,6 +333,10 @@ pub struct OrderResponse {
     pub status: OrderStatus,
     pub created_at: String,
     pub expires_at: String,
+    pub invoice_expires_at: Option<String>,
+    /// Set once the invoice is close to expiring with the order still held
+    pub deadline_alert: Option<AlertLevel>,
+    pub subscription_id: Option<Uuid>,
     pub dispute: Option<DisputeResponse>,
 }
 
@@ -92,6 +345,8 @@ pub struct DisputeResponse {
     pub reason: String,
     pub created_at: String,
     pub resolution: Option<DisputeResolution>,
+    pub resolution_reason: Option<ResolutionReason>,
+    pub resolution_note: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -99,6 +354,13 @@ pub struct DisputeRequest {
     pub reason: String,
 }
 
+impl Validate for DisputeRequest {
+    fn check(&self, v: &mut Validator) {
+        v.not_blank("reason", &self.reason)
+            .max_len("reason", &self.reason, MAX_REASON_LEN);
+    }
+}
+
 #[derive(Deserialize)]
 pub struct ConfirmOrderRequest {
     // Preimage is no longer needed - escrow already holds it from order creation
@@ -107,6 +369,58 @@ pub struct ConfirmOrderRequest {
 #[derive(Deserialize)]
 pub struct ResolveDisputeRequest {
     pub resolution: String, // "seller" or "buyer"
+    pub reason: ResolutionReason,
+    /// Required when the reason is `other`
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+impl ResolveDisputeRequest {
+    fn ruling(&self) -> Option<DisputeResolution> {
+        match self.resolution.as_str() {
+            "seller" => Some(DisputeResolution::ToSeller),
+            "buyer" => Some(DisputeResolution::ToBuyer),
+            _ => None,
+        }
+    }
+}
+
+impl Validate for ResolveDisputeRequest {
+    fn check(&self, v: &mut Validator) {
+        let note = self.note.as_deref().unwrap_or_default();
+        v.check(self.ruling().is_some(), "resolution", "must be 'seller' or 'buyer'")
+            .check(
+                self.reason.ruling().is_none() || self.reason.ruling() == self.ruling(),
+                "reason",
+                "decides for the other side",
+            )
+            .max_len("note", note, MAX_REASON_LEN);
+        if self.reason == ResolutionReason::Other {
+            v.not_blank("note", note);
+        }
+    }
+}
+
+/// A reason an arbiter can pick, with the decision it comes with
+#[derive(Serialize)]
+pub struct DecisionTemplate {
+    pub reason: ResolutionReason,
+    /// Unset if the reason fits either side
+    pub resolution: Option<DisputeResolution>,
+    pub note: &'static str,
+}
+
+/// `GET /api/admin/invoices/:payment_hash`: the order an invoice on a
+/// node pays for
+#[derive(Serialize)]
+pub struct InvoiceOwnerResponse {
+    pub payment_hash: String,
+    pub order_id: OrderId,
+    pub product_title: String,
+    pub amount_shannons: u64,
+    pub status: OrderStatus,
+    /// Whether the order still counts on the invoice holding its payment
+    pub held: bool,
 }
 
 #[derive(Deserialize)]
@@ -114,150 +428,409 @@ pub struct TickRequest {
     pub seconds: i64,
 }
 
+impl Validate for TickRequest {
+    fn check(&self, v: &mut Validator) {
+        v.range("seconds", self.seconds, 0..=MAX_TICK_SECS);
+    }
+}
+
 #[derive(Serialize)]
 pub struct TickResponse {
     pub expired_orders: Vec<Uuid>,
+    pub renewal_orders: Vec<Uuid>,
+    pub suspended_subscriptions: Vec<Uuid>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateSubscriptionRequest {
+    pub product_id: Uuid,
+    /// Preimage (hex string with 0x prefix) for the first period's order
+    pub preimage: String,
+}
+
+impl Validate for CreateSubscriptionRequest {
+    fn check(&self, v: &mut Validator) {
+        v.hex("preimage", &self.preimage, 32);
+    }
+}
+
+#[derive(Serialize)]
+pub struct SubscriptionResponse {
+    pub id: Uuid,
+    pub product_id: Uuid,
+    pub product_title: String,
+    pub seller_id: Uuid,
+    pub buyer_id: Uuid,
+    pub amount_shannons: u64,
+    pub billing_period_secs: u64,
+    pub status: SubscriptionStatus,
+    pub has_access: bool,
+    pub current_order_id: Option<Uuid>,
+    pub order_ids: Vec<Uuid>,
+    pub next_billing_at: String,
+    pub created_at: String,
+}
+
+impl From<Subscription> for SubscriptionResponse {
+    fn from(s: Subscription) -> Self {
+        Self {
+            id: s.id.0,
+            product_id: s.product_id.0,
+            has_access: s.has_access(),
+            product_title: s.product_title,
+            seller_id: s.seller_id.0,
+            buyer_id: s.buyer_id.0,
+            amount_shannons: s.amount_shannons,
+            billing_period_secs: s.billing_period_secs,
+            status: s.status,
+            current_order_id: s.current_order_id.map(|id| id.0),
+            order_ids: s.order_ids.iter().map(|id| id.0).collect(),
+            next_billing_at: s.next_billing_at.to_rfc3339(),
+            created_at: s.created_at.to_rfc3339(),
+        }
+    }
 }
 
-// ============ Helper to get user from header ============
+#[derive(Serialize)]
+pub struct NotificationResponse {
+    pub id: Uuid,
+    pub message: String,
+    pub order_id: Option<Uuid>,
+    pub created_at: String,
+}
 
-fn get_user_id_from_header(headers: &axum::http::HeaderMap) -> Option<UserId> {
-    headers
-        .get("X-User-Id")
-        .and_then(|v| v.to_str().ok())
-        .and_then(|s| Uuid::parse_str(s).ok())
-        .map(UserId)
+/// An entry of `GET /api/orders/:id/timeline`
+#[derive(Serialize)]
+pub struct OrderEventResponse {
+    pub kind: OrderEventKind,
+    pub at: String,
+    pub detail: Option<String>,
+}
+
+impl From<OrderEvent> for OrderEventResponse {
+    fn from(e: OrderEvent) -> Self {
+        Self {
+            kind: e.kind,
+            at: e.at.to_rfc3339(),
+            detail: e.detail,
+        }
+    }
+}
+
+impl From<Notification> for NotificationResponse {
+    fn from(n: Notification) -> Self {
+        Self {
+            id: n.id,
+            message: n.message,
+            order_id: n.order_id.map(|id| id.0),
+            created_at: n.created_at.to_rfc3339(),
+        }
+    }
 }
 
 // ============ User handlers ============
 
+/// Register a user. With an identity token, the user takes the ID of the
+/// identity, and registering again just signs them in.
 pub async fn register_user(
     State(state): State<AppState>,
-    Json(req): Json<RegisterRequest>,
-) -> impl IntoResponse {
+    headers: HeaderMap,
+    ValidJson(req): ValidJson<RegisterRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let id = if headers.contains_key(IDENTITY_HEADER) {
+        let AuthedIdentity(identity) = AuthedIdentity::from_headers(&headers)?;
+        let id = UserId(identity.user_id());
+        if let Some(user) = state.get_user(id).await? {
+            return Ok(Json(serde_json::json!(UserResponse::from(user))));
+        }
+        Some(id)
+    } else {
+        None
+    };
+
     // Check if username already exists
-    if state.get_user_by_username(&req.username).is_some() {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({"error": "Username already exists"})),
-        );
+    if state.get_user_by_username(&req.username).await.is_some() {
+        return Err(ApiError::conflict("Username already exists"));
     }
 
-    let user = state.register_user(req.username);
-    (
-        StatusCode::OK,
-        Json(serde_json::json!(UserResponse::from(user))),
-    )
+    let user = match id {
+        Some(id) => state.register_user_as(id, req.username).await,
+        None => state.register_user(req.username).await,
+    };
+    Ok(Json(serde_json::json!(UserResponse::from(user))))
 }
 
 pub async fn get_current_user(
     State(state): State<AppState>,
-    headers: axum::http::HeaderMap,
-) -> impl IntoResponse {
-    let user_id = match get_user_id_from_header(&headers) {
-        Some(id) => id,
-        None => {
-            return (
-                StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({"error": "Missing X-User-Id header"})),
-            )
-        }
-    };
+    user: AuthedUser,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let user_id = UserId::from(user);
 
-    match state.get_user(user_id) {
-        Some(user) => (
-            StatusCode::OK,
-            Json(serde_json::json!(UserResponse::from(user))),
-        ),
-        None => (
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({"error": "User not found"})),
-        ),
+    match state.get_user(user_id).await? {
+        Some(user) => Ok(Json(serde_json::json!(UserResponse::from(user)))),
+        None => Err(ApiError::not_found("User not found")),
     }
 }
 
-pub async fn list_users(State(state): State<AppState>) -> impl IntoResponse {
-    let users: Vec<UserResponse> = state.list_users().into_iter().map(Into::into).collect();
-    Json(serde_json::json!({"users": users}))
+pub async fn list_users(
+    State(state): State<AppState>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let users: Vec<UserResponse> = state
+        .list_users()
+        .await?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    Ok(Json(serde_json::json!({"users": users})))
 }
 
 // ============ Product handlers ============
 
 pub async fn create_product(
     State(state): State<AppState>,
-    headers: axum::http::HeaderMap,
-    Json(req): Json<CreateProductRequest>,
-) -> impl IntoResponse {
-    let seller_id = match get_user_id_from_header(&headers) {
-        Some(id) => id,
-        None => {
-            return (
-                StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({"error": "Missing X-User-Id header"})),
-            )
-        }
+    user: AuthedUser,
+    ValidJson(req): ValidJson<CreateProductRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let product = products::create(&state, UserId::from(user), req).await?;
+    Ok(Json(serde_json::json!({"product_id": product.id.0, "status": product.status})))
+}
+
+/// Newest first
+pub async fn list_products(
+    State(state): State<AppState>,
+    Query(query): Query<ListProductsQuery>,
+    Query(page): Query<PageRequest>,
+) -> Result<Json<Page<ProductResponse>>, ApiError> {
+    let categories = match query.category.as_deref() {
+        Some(key) => match find_category(&state, key).await {
+            Some(category) => Some(state.category_subtree(category.id).await),
+            None => return Err(ApiError::not_found("Category not found")),
+        },
+        None => None,
     };
 
-    let product = state.create_product(seller_id, req.title, req.description, req.price_shannons);
-    (
-        StatusCode::OK,
-        Json(serde_json::json!({"product_id": product.id.0})),
-    )
-}
+    let listed = state.list_available_products().await.into_iter().filter(|p| {
+        categories
+            .as_ref()
+            .is_none_or(|categories| p.category_id.is_some_and(|id| categories.contains(&id)))
+    });
+    let page = Page::of(listed, &page, newest_product_first)?;
 
-pub async fn list_products(State(state): State<AppState>) -> impl IntoResponse {
     let mut products = Vec::new();
-    for p in state.list_available_products() {
-        let seller = state.get_user(p.seller_id);
-        products.push(ProductResponse {
-            id: p.id.0,
-            seller_id: p.seller_id.0,
-            seller_username: seller.map(|u| u.username),
-            title: p.title,
-            description: p.description,
-            price_shannons: p.price_shannons,
-            status: p.status,
-        });
+    for p in page.items {
+        let seller = state.get_user(p.seller_id).await?;
+        products.push(ProductResponse::new(p, seller.map(|u| u.username)));
     }
-    Json(serde_json::json!({"products": products}))
+    Ok(Json(Page {
+        items: products,
+        next_cursor: page.next_cursor,
+    }))
+}
+
+fn newest_product_first(p: &Product) -> (Reverse<DateTime<Utc>>, Uuid) {
+    (Reverse(p.created_at), p.id.0)
 }
 
 pub async fn list_my_products(
     State(state): State<AppState>,
-    headers: axum::http::HeaderMap,
-) -> impl IntoResponse {
-    let seller_id = match get_user_id_from_header(&headers) {
-        Some(id) => id,
-        None => {
-            return (
-                StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({"error": "Missing X-User-Id header"})),
-            )
-        }
-    };
+    user: AuthedUser,
+    Query(query): Query<ListMyProductsQuery>,
+    Query(page): Query<PageRequest>,
+) -> Result<Json<Page<ProductResponse>>, ApiError> {
+    let seller_id = UserId::from(user);
 
-    let products: Vec<ProductResponse> = state
+    let products = state
         .list_products_by_seller(seller_id)
+        .await
         .into_iter()
-        .map(|p| ProductResponse {
-            id: p.id.0,
-            seller_id: p.seller_id.0,
-            seller_username: None,
-            title: p.title,
-            description: p.description,
-            price_shannons: p.price_shannons,
-            status: p.status,
-        })
+        .filter(|p| query.status.is_none_or(|status| p.status == status));
+    let page = Page::of(products, &page, newest_product_first)?;
+    Ok(Json(page.map(|p| ProductResponse::new(p, None))))
+}
+
+/// Anyone may see a published or sold product; drafts and archived
+/// products only their seller
+pub async fn get_product(
+    State(state): State<AppState>,
+    viewer: Option<AuthedUser>,
+    ValidPath(product_id): ValidPath<ProductId>,
+) -> Result<Json<ProductResponse>, ApiError> {
+    let viewer = viewer.map(UserId::from);
+    let product = products::view(&state, viewer, product_id).await?;
+    let seller = state.get_user(product.seller_id).await?;
+    Ok(Json(ProductResponse::new(product, seller.map(|u| u.username))))
+}
+
+pub async fn edit_product(
+    State(state): State<AppState>,
+    user: AuthedUser,
+    ValidPath(product_id): ValidPath<ProductId>,
+    ValidJson(req): ValidJson<EditProductRequest>,
+) -> Result<Json<ProductResponse>, ApiError> {
+    let product = products::edit(&state, UserId::from(user), product_id, req).await?;
+    Ok(Json(ProductResponse::new(product, None)))
+}
+
+pub async fn publish_product(
+    State(state): State<AppState>,
+    user: AuthedUser,
+    ValidPath(product_id): ValidPath<ProductId>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    products::publish(&state, UserId::from(user), product_id).await?;
+    Ok(Json(serde_json::json!({"status": "available"})))
+}
+
+pub async fn unpublish_product(
+    State(state): State<AppState>,
+    user: AuthedUser,
+    ValidPath(product_id): ValidPath<ProductId>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    products::unpublish(&state, UserId::from(user), product_id).await?;
+    Ok(Json(serde_json::json!({"status": "draft"})))
+}
+
+pub async fn archive_product(
+    State(state): State<AppState>,
+    user: AuthedUser,
+    ValidPath(product_id): ValidPath<ProductId>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    products::archive(&state, UserId::from(user), product_id).await?;
+    Ok(Json(serde_json::json!({"status": "archived"})))
+}
+
+/// Create products from a CSV (`text/csv`, with a header row) or JSON
+/// array body, each row a `POST /api/products` body; invalid rows are
+/// skipped and reported
+pub async fn import_products(
+    State(state): State<AppState>,
+    user: AuthedUser,
+    Query(query): Query<ImportProductsQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<ImportReport>, ApiError> {
+    let format = CatalogFormat::of_content_type(
+        headers.get(CONTENT_TYPE).and_then(|v| v.to_str().ok()),
+    )?;
+    let rows = catalog::parse(format, &body)?;
+    Ok(Json(
+        catalog::import(&state, UserId::from(user), rows, query.dry_run).await,
+    ))
+}
+
+/// The seller's products, as a file an import takes back
+pub async fn export_products(
+    State(state): State<AppState>,
+    user: AuthedUser,
+    Query(query): Query<ExportProductsQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let products = state
+        .list_products_by_seller(UserId::from(user))
+        .await
+        .into_iter()
+        .filter(|p| query.status.is_none_or(|status| p.status == status))
         .collect();
-    (
-        StatusCode::OK,
-        Json(serde_json::json!({"products": products})),
-    )
+    let body = catalog::export(products, query.format)?;
+    let filename = match query.format {
+        CatalogFormat::Json => "products.json",
+        CatalogFormat::Csv => "products.csv",
+    };
+    Ok((
+        [
+            (CONTENT_TYPE, query.format.content_type().to_string()),
+            (
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        body,
+    ))
+}
+
+// ============ Category handlers ============
+
+/// Look up a category by UUID or slug
+async fn find_category(state: &AppState, key: &str) -> Option<Category> {
+    match Uuid::parse_str(key) {
+        Ok(id) => state.get_category(CategoryId(id)).await,
+        Err(_) => state.get_category_by_slug(key).await,
+    }
+}
+
+/// Build the subtree rooted at `category` from a flat category list
+fn category_node(category: &Category, all: &[Category], products: &[Product]) -> CategoryNode {
+    let children: Vec<CategoryNode> = all
+        .iter()
+        .filter(|c| c.parent_id == Some(category.id))
+        .map(|c| category_node(c, all, products))
+        .collect();
+    let own_products = products
+        .iter()
+        .filter(|p| p.category_id == Some(category.id))
+        .count();
+
+    CategoryNode {
+        id: category.id.0,
+        name: category.name.clone(),
+        slug: category.slug.clone(),
+        parent_id: category.parent_id.map(|id| id.0),
+        product_count: own_products + children.iter().map(|c| c.product_count).sum::<usize>(),
+        children,
+    }
+}
+
+pub async fn list_categories(State(state): State<AppState>) -> impl IntoResponse {
+    let all = state.list_categories().await;
+    let products = state.list_available_products().await;
+    let tree: Vec<CategoryNode> = all
+        .iter()
+        .filter(|c| c.parent_id.is_none())
+        .map(|c| category_node(c, &all, &products))
+        .collect();
+    Json(serde_json::json!({"categories": tree}))
+}
+
+pub async fn get_category(
+    State(state): State<AppState>,
+    ValidPath(key): ValidPath<String>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let category = find_category(&state, &key)
+        .await
+        .ok_or_else(|| ApiError::not_found("Category not found"))?;
+
+    // Breadcrumb from the root down to this category
+    let mut path = vec![category.clone()];
+    while let Some(parent_id) = path.last().and_then(|c| c.parent_id) {
+        let Some(parent) = state.get_category(parent_id).await else {
+            break;
+        };
+        path.push(parent);
+    }
+    path.reverse();
+
+    let all = state.list_categories().await;
+    let products = state.list_available_products().await;
+    let mut response = serde_json::json!(category_node(&category, &all, &products));
+    response["path"] = serde_json::json!(path
+        .iter()
+        .map(|c| serde_json::json!({"id": c.id.0, "name": c.name, "slug": c.slug}))
+        .collect::<Vec<_>>());
+
+    Ok(Json(response))
+}
+
+pub async fn create_category(
+    State(state): State<AppState>,
+    ValidJson(req): ValidJson<CreateCategoryRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let category = state
+        .create_category(req.name, req.slug, req.parent_id.map(CategoryId))
+        .await?;
+    Ok(Json(serde_json::json!({"category_id": category.id.0, "slug": category.slug})))
 }
 
 // ============ Order handlers ============
 
-fn order_to_response(order: &Order) -> OrderResponse {
+pub(crate) fn order_to_response(order: &Order) -> OrderResponse {
     OrderResponse {
         id: order.id.0,
         product_id: order.product_id.0,
@@ -270,443 +843,279 @@ fn order_to_response(order: &Order) -> OrderResponse {
         status: order.status,
         created_at: order.created_at.to_rfc3339(),
         expires_at: order.expires_at.to_rfc3339(),
+        invoice_expires_at: order.invoice_expires_at.map(|at| at.to_rfc3339()),
+        deadline_alert: order.deadline_alert,
+        subscription_id: order.subscription_id.map(|id| id.0),
         dispute: order.dispute.as_ref().map(|d| DisputeResponse {
             reason: d.reason.clone(),
             created_at: d.created_at.to_rfc3339(),
             resolution: d.resolution,
+            resolution_reason: d.resolution_reason,
+            resolution_note: d.resolution_note.clone(),
         }),
     }
 }
 
 pub async fn create_order(
     State(state): State<AppState>,
-    headers: axum::http::HeaderMap,
-    Json(req): Json<CreateOrderRequest>,
-) -> impl IntoResponse {
-    let buyer_id = match get_user_id_from_header(&headers) {
-        Some(id) => id,
-        None => {
-            return (
-                StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({"error": "Missing X-User-Id header"})),
-            )
-        }
-    };
-
-    // Parse preimage from hex and compute payment_hash
-    let preimage = match fiber_core::Preimage::from_hex(&req.preimage) {
-        Ok(p) => p,
-        Err(_) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({"error": "Invalid preimage format, expected hex string"})),
-            )
-        }
-    };
-    let payment_hash = preimage.payment_hash();
-
-    let product_id = ProductId(req.product_id);
-    let product = match state.get_product(product_id) {
-        Some(p) => p,
-        None => {
-            return (
-                StatusCode::NOT_FOUND,
-                Json(serde_json::json!({"error": "Product not found"})),
-            )
-        }
-    };
-
-    if product.seller_id == buyer_id {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({"error": "Cannot buy your own product"})),
-        );
-    }
-
-    // Create order with computed payment_hash
-    let order = state.create_order(&product, buyer_id, payment_hash);
-
-    // Store preimage immediately (escrow holds it for timeout/dispute settlement)
-    tracing::info!(
-        "Storing preimage for order {}: preimage_hash={}, order_payment_hash={}",
-        order.id.0,
-        preimage.payment_hash().to_hex(),
-        order.payment_hash.to_hex()
-    );
-    state.set_revealed_preimage(order.id, preimage);
-
-    // No Fiber RPC calls — seller's frontend will create the hold invoice
-    // using the payment_hash, and submit it back via /api/orders/:id/invoice
-
-    (
-        StatusCode::OK,
-        Json(serde_json::json!({
-            "order_id": order.id.0,
-            "payment_hash": order.payment_hash.to_hex(),
-            "amount_shannons": order.amount_shannons,
-            "expires_at": order.expires_at.to_rfc3339()
-        })),
-    )
+    user: AuthedUser,
+    ValidJson(req): ValidJson<CreateOrderRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let order = orders::create(&state, UserId::from(user), &req).await?;
+
+    Ok(Json(serde_json::json!({
+        "order_id": order.id.0,
+        "payment_hash": order.payment_hash.to_hex(),
+        "amount_shannons": order.amount_shannons,
+        "expires_at": order.expires_at.to_rfc3339()
+    })))
 }
 
 pub async fn list_my_orders(
     State(state): State<AppState>,
-    headers: axum::http::HeaderMap,
-) -> impl IntoResponse {
-    let user_id = match get_user_id_from_header(&headers) {
-        Some(id) => id,
-        None => {
-            return (
-                StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({"error": "Missing X-User-Id header"})),
-            )
-        }
-    };
+    user: AuthedUser,
+    Query(query): Query<ListMyOrdersQuery>,
+    Query(page): Query<PageRequest>,
+) -> Result<Json<Page<OrderResponse>>, ApiError> {
+    let page = orders::list(&state, UserId::from(user), &query, &page).await?;
+    Ok(Json(page.map(|order| order_to_response(&order))))
+}
 
-    let orders: Vec<OrderResponse> = state
-        .list_orders_for_user(user_id)
-        .iter()
-        .map(order_to_response)
-        .collect();
-    (StatusCode::OK, Json(serde_json::json!({"orders": orders})))
+/// Counts by status of the orders `GET /api/orders/mine` would list for the
+/// same filters, leaving out `status` itself
+pub async fn summarize_my_orders(
+    State(state): State<AppState>,
+    user: AuthedUser,
+    Query(query): Query<ListMyOrdersQuery>,
+) -> Result<Json<OrderSummaryResponse>, ApiError> {
+    let by_status = orders::count_by_status(&state, UserId::from(user), &query).await?;
+    Ok(Json(OrderSummaryResponse {
+        total: by_status.values().sum(),
+        by_status,
+    }))
 }
 
 pub async fn get_order(
     State(state): State<AppState>,
-    headers: axum::http::HeaderMap,
-    Path(order_id): Path<Uuid>,
-) -> impl IntoResponse {
-    let user_id = match get_user_id_from_header(&headers) {
-        Some(id) => id,
-        None => {
-            return (
-                StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({"error": "Missing X-User-Id header"})),
-            )
-        }
-    };
-
-    let order_id = OrderId(order_id);
-    let order = match state.get_order(order_id) {
-        Some(o) => o,
-        None => {
-            return (
-                StatusCode::NOT_FOUND,
-                Json(serde_json::json!({"error": "Order not found"})),
-            )
-        }
-    };
-
-    // Only buyer or seller can view order details
-    if order.buyer_id != user_id && order.seller_id != user_id {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(serde_json::json!({"error": "Not authorized to view this order"})),
-        );
-    }
+    user: AuthedUser,
+    ValidPath(order_id): ValidPath<OrderId>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let (order, preimage) = orders::view(&state, UserId::from(user), order_id).await?;
 
-    // Include preimage for seller if order is completed (for Fiber settlement)
     let mut response = serde_json::json!(order_to_response(&order));
-    
-    if order.seller_id == user_id && order.status == OrderStatus::Completed {
-        if let Some(preimage) = state.get_revealed_preimage(order_id) {
-            response["preimage"] = serde_json::json!(format!("0x{}", hex::encode(preimage.as_bytes())));
-        }
+    if let Some(preimage) = preimage {
+        response["preimage"] = serde_json::json!(format!("0x{}", hex::encode(preimage.as_bytes())));
     }
 
-    (StatusCode::OK, Json(response))
+    Ok(Json(response))
 }
 
-pub async fn submit_invoice(
+pub async fn get_order_timeline(
     State(state): State<AppState>,
-    headers: axum::http::HeaderMap,
-    Path(order_id): Path<Uuid>,
-    Json(req): Json<SubmitInvoiceRequest>,
-) -> impl IntoResponse {
-    let user_id = match get_user_id_from_header(&headers) {
-        Some(id) => id,
-        None => {
-            return (
-                StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({"error": "Missing X-User-Id header"})),
-            )
-        }
-    };
-
-    let order_id = OrderId(order_id);
-    let order = match state.get_order(order_id) {
-        Some(o) => o,
-        None => {
-            return (
-                StatusCode::NOT_FOUND,
-                Json(serde_json::json!({"error": "Order not found"})),
-            )
-        }
-    };
-
-    // Only seller can submit invoice
-    if order.seller_id != user_id {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(serde_json::json!({"error": "Only seller can submit invoice"})),
-        );
-    }
+    user: AuthedUser,
+    ValidPath(order_id): ValidPath<OrderId>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let events: Vec<OrderEventResponse> =
+        orders::timeline(&state, UserId::from(user), order_id)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect();
+    Ok(Json(serde_json::json!({"order_id": order_id, "events": events})))
+}
 
-    // Can only submit invoice for orders waiting payment
-    if order.status != OrderStatus::WaitingPayment {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({"error": "Order not in WaitingPayment status"})),
-        );
-    }
+pub async fn submit_invoice(
+    State(state): State<AppState>,
+    user: AuthedUser,
+    ValidPath(order_id): ValidPath<OrderId>,
+    ValidJson(req): ValidJson<SubmitInvoiceRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    orders::submit_invoice(&state, UserId::from(user), order_id, &req).await?;
+    Ok(Json(serde_json::json!({"status": "invoice_submitted"})))
+}
 
-    // Validate invoice is not empty
-    if req.invoice.trim().is_empty() {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({"error": "Invoice cannot be empty"})),
-        );
-    }
+pub async fn pay_order(
+    State(state): State<AppState>,
+    user: AuthedUser,
+    ValidPath(order_id): ValidPath<OrderId>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    orders::pay(&state, UserId::from(user), order_id).await?;
+    Ok(Json(serde_json::json!({"status": "funded"})))
+}
 
-    state.set_order_invoice(order_id, req.invoice);
+pub async fn ship_order(
+    State(state): State<AppState>,
+    user: AuthedUser,
+    ValidPath(order_id): ValidPath<OrderId>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    orders::ship(&state, UserId::from(user), order_id).await?;
+    Ok(Json(serde_json::json!({"status": "shipped"})))
+}
 
-    (
-        StatusCode::OK,
-        Json(serde_json::json!({"status": "invoice_submitted"})),
-    )
+pub async fn confirm_order(
+    State(state): State<AppState>,
+    user: AuthedUser,
+    ValidPath(order_id): ValidPath<OrderId>,
+    Json(_req): Json<ConfirmOrderRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    orders::confirm(&state, UserId::from(user), order_id).await?;
+    Ok(Json(serde_json::json!({
+        "status": "completed"
+    })))
 }
 
-pub async fn pay_order(
+pub async fn dispute_order(
     State(state): State<AppState>,
-    headers: axum::http::HeaderMap,
-    Path(order_id): Path<Uuid>,
-) -> impl IntoResponse {
-    let user_id = match get_user_id_from_header(&headers) {
-        Some(id) => id,
-        None => {
-            return (
-                StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({"error": "Missing X-User-Id header"})),
-            )
-        }
-    };
+    user: AuthedUser,
+    ValidPath(order_id): ValidPath<OrderId>,
+    ValidJson(req): ValidJson<DisputeRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    orders::dispute(&state, UserId::from(user), order_id, req.reason).await?;
+    Ok(Json(serde_json::json!({"status": "disputed"})))
+}
 
-    let order_id = OrderId(order_id);
-    let order = match state.get_order(order_id) {
-        Some(o) => o,
-        None => {
-            return (
-                StatusCode::NOT_FOUND,
-                Json(serde_json::json!({"error": "Order not found"})),
-            )
-        }
-    };
+// ============ Subscription handlers ============
 
-    if order.buyer_id != user_id {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(serde_json::json!({"error": "Not the buyer"})),
-        );
-    }
+pub async fn create_subscription(
+    State(state): State<AppState>,
+    user: AuthedUser,
+    ValidJson(req): ValidJson<CreateSubscriptionRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let buyer_id = UserId::from(user);
 
-    if order.status != OrderStatus::WaitingPayment {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({"error": "Order not in WaitingPayment status"})),
-        );
-    }
+    let preimage = fiber_core::Preimage::from_hex(&req.preimage)
+        .map_err(|_| ApiError::bad_request("Invalid preimage format, expected hex string"))?;
 
-    // Require invoice to be submitted before payment can be confirmed
-    if order.invoice_string.is_none() {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({"error": "Seller has not submitted invoice yet"})),
-        );
+    let product = products::for_sale(&state, ProductId(req.product_id)).await?;
+
+    if product.seller_id == buyer_id {
+        return Err(ApiError::bad_request("Cannot subscribe to your own product"));
     }
 
-    // No Fiber RPC calls — buyer's frontend sends payment directly to their node.
-    // This endpoint is called after the buyer's frontend confirms payment was sent.
+    let (subscription, order) = state
+        .create_subscription(&product, buyer_id, preimage)
+        .await
+        .ok_or_else(|| ApiError::bad_request("Product is not a subscription product"))?;
 
-    // Update order status to funded
-    state.update_order_status(order_id, OrderStatus::Funded);
+    tracing::info!(
+        subscription_id = %subscription.id.0,
+        product_id = %product.id.0,
+        order_id = %order.id.0,
+        "Subscription created"
+    );
 
-    (
-        StatusCode::OK,
-        Json(serde_json::json!({"status": "funded"})),
-    )
+    Ok(Json(serde_json::json!({
+        "subscription_id": subscription.id.0,
+        "order_id": order.id.0,
+        "payment_hash": order.payment_hash.to_hex(),
+        "amount_shannons": order.amount_shannons,
+        "next_billing_at": subscription.next_billing_at.to_rfc3339()
+    })))
 }
 
-pub async fn ship_order(
+pub async fn list_my_subscriptions(
     State(state): State<AppState>,
-    headers: axum::http::HeaderMap,
-    Path(order_id): Path<Uuid>,
-) -> impl IntoResponse {
-    let user_id = match get_user_id_from_header(&headers) {
-        Some(id) => id,
-        None => {
-            return (
-                StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({"error": "Missing X-User-Id header"})),
-            )
-        }
-    };
-
-    let order_id = OrderId(order_id);
-    let order = match state.get_order(order_id) {
-        Some(o) => o,
-        None => {
-            return (
-                StatusCode::NOT_FOUND,
-                Json(serde_json::json!({"error": "Order not found"})),
-            )
-        }
-    };
+    user: AuthedUser,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let user_id = UserId::from(user);
 
-    if order.seller_id != user_id {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(serde_json::json!({"error": "Not the seller"})),
-        );
-    }
+    let subscriptions: Vec<SubscriptionResponse> = state
+        .list_subscriptions_for_user(user_id)
+        .await
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    Ok(Json(serde_json::json!({"subscriptions": subscriptions})))
+}
 
-    if order.status != OrderStatus::Funded {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({"error": "Order not in Funded status"})),
-        );
+pub async fn get_subscription(
+    State(state): State<AppState>,
+    user: AuthedUser,
+    ValidPath(subscription_id): ValidPath<SubscriptionId>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let user_id = UserId::from(user);
+
+    let subscription = state
+        .get_subscription(subscription_id)
+        .await
+        .ok_or_else(|| ApiError::not_found("Subscription not found"))?;
+
+    if subscription.buyer_id != user_id && subscription.seller_id != user_id {
+        return Err(ApiError::forbidden("Not authorized to view this subscription"));
     }
 
-    state.update_order_status(order_id, OrderStatus::Shipped);
-
-    (
-        StatusCode::OK,
-        Json(serde_json::json!({"status": "shipped"})),
-    )
+    Ok(Json(serde_json::json!(SubscriptionResponse::from(subscription))))
 }
 
-pub async fn confirm_order(
+pub async fn pause_subscription(
     State(state): State<AppState>,
-    headers: axum::http::HeaderMap,
-    Path(order_id): Path<Uuid>,
-    Json(_req): Json<ConfirmOrderRequest>,
-) -> impl IntoResponse {
-    let user_id = match get_user_id_from_header(&headers) {
-        Some(id) => id,
-        None => {
-            return (
-                StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({"error": "Missing X-User-Id header"})),
-            )
-        }
-    };
+    user: AuthedUser,
+    ValidPath(subscription_id): ValidPath<SubscriptionId>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let id = authorize_subscription_change(&state, user, subscription_id, false).await?;
+    subscription_json(state.pause_subscription(id).await?)
+}
 
-    let order_id = OrderId(order_id);
-    let order = match state.get_order(order_id) {
-        Some(o) => o,
-        None => {
-            return (
-                StatusCode::NOT_FOUND,
-                Json(serde_json::json!({"error": "Order not found"})),
-            )
-        }
-    };
+pub async fn resume_subscription(
+    State(state): State<AppState>,
+    user: AuthedUser,
+    ValidPath(subscription_id): ValidPath<SubscriptionId>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let id = authorize_subscription_change(&state, user, subscription_id, false).await?;
+    subscription_json(state.resume_subscription(id).await?)
+}
 
-    if order.buyer_id != user_id {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(serde_json::json!({"error": "Not the buyer"})),
-        );
-    }
+pub async fn cancel_subscription(
+    State(state): State<AppState>,
+    user: AuthedUser,
+    ValidPath(subscription_id): ValidPath<SubscriptionId>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let id = authorize_subscription_change(&state, user, subscription_id, true).await?;
+    subscription_json(state.cancel_subscription(id).await?)
+}
 
-    if order.status != OrderStatus::Shipped {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({"error": "Order not in Shipped status"})),
-        );
+/// Shared checks for subscription state changes. Only the buyer may pause or
+/// resume; either party may cancel.
+async fn authorize_subscription_change(
+    state: &AppState,
+    user: AuthedUser,
+    subscription_id: SubscriptionId,
+    seller_allowed: bool,
+) -> Result<SubscriptionId, ApiError> {
+    let user_id = UserId::from(user);
+
+    let subscription = state
+        .get_subscription(subscription_id)
+        .await
+        .ok_or_else(|| ApiError::not_found("Subscription not found"))?;
+
+    let is_seller = seller_allowed && subscription.seller_id == user_id;
+    if subscription.buyer_id != user_id && !is_seller {
+        return Err(ApiError::forbidden("Not authorized to change this subscription"));
     }
 
-    // Get preimage from escrow storage (stored at order creation)
-    let preimage = match state.get_revealed_preimage(order_id) {
-        Some(p) => p,
-        None => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({"error": "Preimage not found in escrow"})),
-            )
-        }
-    };
-
-    // Debug: verify preimage matches payment_hash
-    tracing::info!(
-        "Settling order {}: preimage_hash={}, order_payment_hash={}",
-        order_id.0,
-        preimage.payment_hash().to_hex(),
-        order.payment_hash.to_hex()
-    );
-
-    // Mark order as completed
-    state.update_order_status(order_id, OrderStatus::Completed);
-
-    // No Fiber RPC calls — seller's frontend will call settle_invoice
-    // after seeing the preimage in the order details.
-    tracing::info!("Order {} completed, preimage available for seller settlement", order_id.0);
-
-    (
-        StatusCode::OK,
-        Json(serde_json::json!({
-            "status": "completed"
-        })),
-    )
+    Ok(subscription_id)
 }
 
-pub async fn dispute_order(
-    State(state): State<AppState>,
-    headers: axum::http::HeaderMap,
-    Path(order_id): Path<Uuid>,
-    Json(req): Json<DisputeRequest>,
-) -> impl IntoResponse {
-    let user_id = match get_user_id_from_header(&headers) {
-        Some(id) => id,
-        None => {
-            return (
-                StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({"error": "Missing X-User-Id header"})),
-            )
-        }
-    };
-
-    let order_id = OrderId(order_id);
-    let order = match state.get_order(order_id) {
-        Some(o) => o,
-        None => {
-            return (
-                StatusCode::NOT_FOUND,
-                Json(serde_json::json!({"error": "Order not found"})),
-            )
-        }
-    };
-
-    if order.buyer_id != user_id {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(serde_json::json!({"error": "Not the buyer"})),
-        );
-    }
+fn subscription_json(subscription: Subscription) -> Result<Json<serde_json::Value>, ApiError> {
+    Ok(Json(serde_json::json!(SubscriptionResponse::from(subscription))))
+}
 
-    // Can only dispute funded or shipped orders
-    if order.status != OrderStatus::Funded && order.status != OrderStatus::Shipped {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({"error": "Cannot dispute this order"})),
-        );
-    }
+// ============ Notification handlers ============
 
-    state.add_dispute(order_id, req.reason);
+pub async fn list_notifications(
+    State(state): State<AppState>,
+    user: AuthedUser,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let user_id = UserId::from(user);
 
-    (
-        StatusCode::OK,
-        Json(serde_json::json!({"status": "disputed"})),
-    )
+    let notifications: Vec<NotificationResponse> = state
+        .list_notifications(user_id)
+        .await
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    Ok(Json(serde_json::json!({"notifications": notifications})))
 }
 
 // ============ Arbiter handlers ============
@@ -714,45 +1123,78 @@ pub async fn dispute_order(
 pub async fn list_disputes(State(state): State<AppState>) -> impl IntoResponse {
     let disputes: Vec<OrderResponse> = state
         .list_disputed_orders()
+        .await
         .iter()
         .map(order_to_response)
         .collect();
     Json(serde_json::json!({"disputes": disputes}))
 }
 
+/// The order whose payment hash an invoice found on a node carries; none
+/// means the invoice is nothing the escrow knows of
+pub async fn get_invoice_owner(
+    State(state): State<AppState>,
+    ValidPath(payment_hash): ValidPath<String>,
+) -> Result<Json<InvoiceOwnerResponse>, ApiError> {
+    let hash = fiber_core::PaymentHash::from_hex(&payment_hash).map_err(|_| {
+        ApiError::bad_request(format!("Invalid payment_hash: {:?}", payment_hash))
+    })?;
+    let order = state
+        .find_order_by_payment_hash(&hash)
+        .await
+        .ok_or_else(|| ApiError::not_found("No order pays into this invoice"))?;
+    Ok(Json(InvoiceOwnerResponse {
+        payment_hash: hash.to_hex(),
+        order_id: order.id,
+        product_title: order.product_title,
+        amount_shannons: order.amount_shannons,
+        held: HELD.contains(&order.status),
+        status: order.status,
+    }))
+}
+
+/// Held orders whose invoice is close to expiring, the soonest first
+pub async fn list_deadline_alerts(State(state): State<AppState>) -> impl IntoResponse {
+    Json(serde_json::json!({"alerts": state.list_deadline_alerts().await}))
+}
+
+/// The reasons an arbiter picks from, each with the side it decides for and
+/// the wording the decision starts from
+pub async fn list_decision_templates() -> Json<serde_json::Value> {
+    let templates: Vec<DecisionTemplate> = ResolutionReason::ALL
+        .into_iter()
+        .map(|reason| DecisionTemplate {
+            reason,
+            resolution: reason.ruling(),
+            note: reason.template(),
+        })
+        .collect();
+    Json(serde_json::json!({"templates": templates}))
+}
+
 pub async fn resolve_dispute(
     State(state): State<AppState>,
-    Path(order_id): Path<Uuid>,
-    Json(req): Json<ResolveDisputeRequest>,
-) -> impl IntoResponse {
-    let order_id = OrderId(order_id);
-    let order = match state.get_order(order_id) {
-        Some(o) => o,
-        None => {
-            return (
-                StatusCode::NOT_FOUND,
-                Json(serde_json::json!({"error": "Order not found"})),
-            )
-        }
-    };
+    ValidPath(order_id): ValidPath<OrderId>,
+    ValidJson(req): ValidJson<ResolveDisputeRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let order = state
+        .get_order(order_id)
+        .await
+        .ok_or_else(|| ApiError::not_found("Order not found"))?;
 
     if order.status != OrderStatus::Disputed {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({"error": "Order not disputed"})),
-        );
+        return Err(ApiError::invalid_state("Order not disputed"));
     }
 
-    let resolution = match req.resolution.as_str() {
-        "seller" => DisputeResolution::ToSeller,
-        "buyer" => DisputeResolution::ToBuyer,
-        _ => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({"error": "Invalid resolution, use 'seller' or 'buyer'"})),
-            )
-        }
-    };
+    // Validation only lets "seller" and "buyer" through
+    let resolution = req.ruling().unwrap_or(DisputeResolution::ToBuyer);
+    let note = req.note.clone().filter(|note| !note.trim().is_empty());
+
+    // Only one resolution can win; a second arbiter call finds the order
+    // already resolved
+    if !state.resolve_dispute(order_id, resolution, req.reason, note).await {
+        return Err(ApiError::invalid_state("Order not disputed"));
+    }
 
     // Return preimage if resolving to seller (seller's frontend will call settle_invoice)
     // If resolving to buyer, seller's frontend should call cancel_invoice
@@ -760,55 +1202,73 @@ pub async fn resolve_dispute(
 
     match resolution {
         DisputeResolution::ToSeller => {
-            if let Some(preimage) = state.get_revealed_preimage(order_id) {
+            if let Some(preimage) = state.get_revealed_preimage(order_id).await {
                 preimage_hex = Some(format!("0x{}", hex::encode(preimage.as_bytes())));
                 tracing::info!(
-                    "Dispute resolved to seller for order {} - preimage available for settlement",
-                    order_id.0
+                    order_id = %order_id.0,
+                    "Dispute resolved to seller, preimage available for settlement"
                 );
             } else {
                 tracing::warn!(
-                    "No preimage found for disputed order {} - cannot provide for settlement",
-                    order_id.0
+                    order_id = %order_id.0,
+                    "No preimage found for disputed order, cannot provide for settlement"
                 );
             }
         }
         DisputeResolution::ToBuyer => {
             tracing::info!(
-                "Dispute resolved to buyer for order {} - seller's frontend should cancel invoice",
-                order_id.0
+                order_id = %order_id.0,
+                "Dispute resolved to buyer, seller's frontend should cancel invoice"
             );
         }
     }
 
-    state.resolve_dispute(order_id, resolution);
-
-    (
-        StatusCode::OK,
-        Json(serde_json::json!({
-            "status": "resolved",
-            "resolution": req.resolution,
-            "preimage": preimage_hex
-        })),
-    )
+    Ok(Json(serde_json::json!({
+        "status": "resolved",
+        "resolution": req.resolution,
+        "reason": req.reason,
+        "preimage": preimage_hex
+    })))
 }
 
 // ============ System handlers ============
 
-pub async fn tick(State(state): State<AppState>, Json(req): Json<TickRequest>) -> impl IntoResponse {
-    state.advance_time(req.seconds);
+pub async fn tick(State(state): State<AppState>, ValidJson(req): ValidJson<TickRequest>) -> impl IntoResponse {
+    state.advance_time(req.seconds).await;
 
     // Process expired orders (auto-confirm shipped orders)
-    let expired_orders = state.process_expired_orders();
+    let expired_orders = if state.features().is_enabled(&AUTO_SETTLE) {
+        state.process_expired_orders().await
+    } else {
+        Vec::new()
+    };
 
     // No Fiber RPC calls — seller's frontend will see completed status
     // and call settle_invoice using the preimage from order details.
     for order_id in &expired_orders {
-        tracing::info!("Order {} expired and auto-completed, awaiting seller settlement", order_id.0);
+        tracing::info!(
+            order_id = %order_id.0,
+            "Order expired and auto-completed, awaiting seller settlement"
+        );
+    }
+
+    // Bill subscriptions whose period ended (seller's frontend creates the invoices)
+    let billing = state.process_subscription_billing().await;
+    for order_id in &billing.renewal_orders {
+        tracing::info!(
+            order_id = %order_id.0,
+            "Subscription renewal order created, buyer notified"
+        );
+    }
+    for sub_id in &billing.suspended {
+        tracing::info!(subscription_id = %sub_id.0, "Subscription suspended for non-payment");
     }
 
-    let expired: Vec<Uuid> = expired_orders.iter().map(|id| id.0).collect();
-    Json(serde_json::json!(TickResponse { expired_orders: expired }))
+    Json(serde_json::json!(TickResponse {
+        expired_orders: expired_orders.iter().map(|id| id.0).collect(),
+        renewal_orders: billing.renewal_orders.iter().map(|id| id.0).collect(),
+        suspended_subscriptions: billing.suspended.iter().map(|id| id.0).collect(),
+    }))
 }
 
 // ============ Config handler ============
@@ -817,6 +1277,7 @@ pub async fn tick(State(state): State<AppState>, Json(req): Json<TickRequest>) -
 pub async fn get_config(State(state): State<AppState>) -> impl IntoResponse {
     Json(serde_json::json!({
         "seller_fiber_rpc_url": state.seller_fiber_rpc_url(),
-        "buyer_fiber_rpc_url": state.buyer_fiber_rpc_url()
+        "buyer_fiber_rpc_url": state.buyer_fiber_rpc_url(),
+        "currency": state.currency()
     }))
 }
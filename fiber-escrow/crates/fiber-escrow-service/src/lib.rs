@@ -0,0 +1,393 @@
+//! Fiber Escrow Service
+//!
+//! A hold invoice based escrow system with multi-role Web UI.
+//! All Fiber payments are made by the frontend.
+//! The backend manages order state and reveals preimage when appropriate,
+//! checks held orders against the seller's node ([`reconcile`]) and alerts
+//! on those whose hold invoice is about to expire ([`alerts`]).
+//! It starts empty unless given a seed file of users, categories and
+//! products ([`seed`]), and keeps subscriptions and their orders in a
+//! SQLite file if given one ([`storage`]).
+//! With the `grpc` feature (on by default) the order lifecycle can also be
+//! driven over gRPC, on the same port.
+
+pub mod alerts;
+mod catalog;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+mod handlers;
+pub mod models;
+mod orders;
+mod products;
+pub mod reconcile;
+pub mod seed;
+pub mod state;
+pub mod storage;
+
+use axum::{
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use clap::ArgAction;
+use fiber_auth::{AdminToken, AuthState};
+use fiber_config::ServiceConfig;
+use fiber_core::fiber::{Currency, RpcFiberClient};
+use fiber_core::Keyring;
+use fiber_flags::{FeatureFlags, FlagArgs};
+use fiber_auth::webhook::WebhookSigner;
+use fiber_service::{InstrumentedFiberClient, ServerArgs};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tower_http::cors::{Any, CorsLayer};
+
+use alerts::{Notifier, WebhookNotifier};
+use handlers::*;
+use seed::Seed;
+use storage::SqliteEscrowStore;
+pub use state::AppState;
+
+/// Escrow service configuration, the `escrow` section of a config file
+#[derive(clap::Args, Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    #[command(flatten)]
+    #[serde(flatten)]
+    pub server: ServerArgs,
+    /// Seller's Fiber node RPC URL (passed to frontend, and checked for
+    /// hold invoices it dropped)
+    #[arg(long, env = "FIBER_SELLER_RPC_URL")]
+    pub seller_rpc_url: Option<String>,
+    /// Buyer's Fiber node RPC URL (passed to frontend)
+    #[arg(long, env = "FIBER_BUYER_RPC_URL")]
+    pub buyer_rpc_url: Option<String>,
+    /// Currency the frontend creates invoices in: Fibb (mainnet), Fibt
+    /// (testnet) or Fibd (devnet)
+    #[arg(long, env = "ESCROW_CURRENCY", default_value = "Fibt")]
+    pub currency: Currency,
+    /// Hours from placing an order until it completes on its own, if it has
+    /// shipped and the buyer hasn't confirmed or disputed it by then
+    #[arg(
+        long,
+        env = "ESCROW_ORDER_TIMEOUT_HOURS",
+        default_value_t = state::DEFAULT_ORDER_TIMEOUT_HOURS
+    )]
+    pub order_timeout_hours: i64,
+    /// Hours before a held order's hold invoice expires that operators are
+    /// warned it will be refunded
+    #[arg(
+        long,
+        env = "ESCROW_DEADLINE_WARNING_HOURS",
+        default_value_t = state::DEFAULT_DEADLINE_WARNING_HOURS
+    )]
+    pub deadline_warning_hours: i64,
+    /// Hours before it that the alert turns critical
+    #[arg(
+        long,
+        env = "ESCROW_DEADLINE_CRITICAL_HOURS",
+        default_value_t = state::DEFAULT_DEADLINE_CRITICAL_HOURS
+    )]
+    pub deadline_critical_hours: i64,
+    /// URLs to POST settlement deadline alerts to
+    #[arg(long = "alert-webhook", env = "ESCROW_ALERT_WEBHOOKS", value_delimiter = ',')]
+    #[serde(default)]
+    pub alert_webhooks: Vec<String>,
+    /// Key alert webhooks are signed with, `hmac:<secret>` or
+    /// `ed25519:<seed hex>`; without one they are sent unsigned
+    #[arg(long, env = "ESCROW_WEBHOOK_SIGNING_KEY")]
+    pub webhook_signing_key: Option<String>,
+    /// Bearer token the arbiter, admin and system routes require; without
+    /// one they are open, as the demo UI's arbiter tab expects
+    #[arg(long, env = "ESCROW_ADMIN_TOKEN")]
+    pub admin_token: Option<String>,
+    /// YAML file of users, categories and products to start an empty escrow
+    /// with, such as `seeds/demo.yaml`; nothing is seeded without one
+    #[arg(long, env = "ESCROW_SEED")]
+    pub seed: Option<PathBuf>,
+    /// SQLite file to persist users, products, subscriptions and their
+    /// orders to, so billing schedules survive a restart (in-memory if unset)
+    #[arg(long, env = "ESCROW_DB_PATH")]
+    pub db_path: Option<PathBuf>,
+    /// Bring the database schema up to date at startup; when off, the
+    /// escrow refuses to start on a database with migrations not applied
+    #[arg(long, env = "ESCROW_AUTO_MIGRATE", default_value_t = true, action = ArgAction::Set)]
+    pub auto_migrate: bool,
+    /// Hex storage keys to encrypt orders' preimages in the database with,
+    /// current first; older keys after it are only read, and rewritten under
+    /// the current one at startup (stored in plaintext if neither this nor
+    /// `storage_key_file` is set)
+    #[arg(long, env = "ESCROW_STORAGE_KEY")]
+    pub storage_key: Option<String>,
+    /// File holding the storage keys, one per line, current first
+    #[arg(long, env = "ESCROW_STORAGE_KEY_FILE")]
+    pub storage_key_file: Option<PathBuf>,
+    /// `auto_settle` and `three_party_escrow`, both on unless switched off
+    #[command(flatten)]
+    #[serde(flatten)]
+    pub features: FlagArgs,
+}
+
+impl Config {
+    /// The storage keys configured, if any
+    fn keyring(&self) -> Result<Option<Keyring>, String> {
+        Keyring::load(self.storage_key.as_deref(), self.storage_key_file.as_deref())
+            .map_err(|e| format!("storage_key: {}", e))
+    }
+}
+
+impl ServiceConfig for Config {
+    const SECTION: &'static str = "escrow";
+
+    fn validate(&self) -> Result<(), String> {
+        for (name, url) in [
+            ("seller_rpc_url", &self.seller_rpc_url),
+            ("buyer_rpc_url", &self.buyer_rpc_url),
+        ] {
+            if let Some(url) = url {
+                fiber_config::check_url(name, url, &["http", "https"])?;
+            }
+        }
+        if self.order_timeout_hours < 1 {
+            return Err("order_timeout_hours must be at least 1".to_string());
+        }
+        if self.deadline_critical_hours < 1 {
+            return Err("deadline_critical_hours must be at least 1".to_string());
+        }
+        if self.deadline_warning_hours <= self.deadline_critical_hours {
+            return Err(
+                "deadline_warning_hours must be more than deadline_critical_hours".to_string(),
+            );
+        }
+        for url in &self.alert_webhooks {
+            fiber_config::check_url("alert_webhooks", url, &["http", "https"])?;
+        }
+        if let Some(key) = &self.webhook_signing_key {
+            key.parse::<WebhookSigner>()
+                .map_err(|e| format!("webhook_signing_key: {}", e))?;
+        }
+        self.keyring()?;
+        self.features.check(state::FEATURES)
+    }
+}
+
+/// Run the escrow service, seeded from the seed file if its store is empty,
+/// until the process exits.
+pub async fn run(config: Config) -> std::io::Result<()> {
+    let keyring = config
+        .keyring()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let Config {
+        server,
+        seller_rpc_url,
+        buyer_rpc_url,
+        currency,
+        order_timeout_hours,
+        deadline_warning_hours,
+        deadline_critical_hours,
+        alert_webhooks,
+        webhook_signing_key,
+        admin_token,
+        seed,
+        db_path,
+        auto_migrate,
+        features,
+        storage_key: _,
+        storage_key_file: _,
+    } = config;
+
+    if let Some(ref url) = seller_rpc_url {
+        tracing::info!("Seller Fiber RPC URL configured: {} (used by seller's frontend)", url);
+    } else {
+        tracing::info!("Seller Fiber RPC not configured (set FIBER_SELLER_RPC_URL for real payments)");
+    }
+
+    if let Some(ref url) = buyer_rpc_url {
+        tracing::info!("Buyer Fiber RPC URL configured: {} (used by buyer's frontend)", url);
+    } else {
+        tracing::info!("Buyer Fiber RPC not configured (set FIBER_BUYER_RPC_URL for real payments)");
+    }
+
+    let seller_node = seller_rpc_url
+        .clone()
+        .map(|url| Arc::new(RpcFiberClient::with_currency(url, currency)));
+    let state = AppState::with_fiber_rpc_urls(seller_rpc_url, buyer_rpc_url)
+        .with_currency(currency)
+        .with_order_timeout_hours(order_timeout_hours)
+        .with_deadline_alerts(deadline_warning_hours, deadline_critical_hours)
+        .with_admin_token(admin_token)
+        .with_features(
+            FeatureFlags::configured(state::FEATURES, &features)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?,
+        );
+    let state = match db_path {
+        Some(path) => {
+            let store = if auto_migrate {
+                SqliteEscrowStore::open(&path)
+            } else {
+                SqliteEscrowStore::open_without_migrating(&path)
+            }
+            .expect("failed to open escrow database");
+            let store = match keyring {
+                Some(keyring) => {
+                    tracing::info!(
+                        "Encrypting stored preimages with storage key {}",
+                        keyring.current_id()
+                    );
+                    store.with_keyring(keyring).expect("failed to encrypt escrow database")
+                }
+                None => store,
+            };
+            tracing::info!("Persisting subscriptions to {}", path.display());
+            state
+                .with_store(Arc::new(store))
+                .expect("failed to restore escrow state")
+        }
+        None => state,
+    };
+    match seed {
+        Some(path) => {
+            let seed_error = |e: String| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("seed {}: {}", path.display(), e),
+                )
+            };
+            let applied = Seed::load(&path)
+                .map_err(|e| seed_error(e.to_string()))?
+                .apply(&state)
+                .await
+                .map_err(seed_error)?;
+            if applied {
+                tracing::info!("Seeded from {}", path.display());
+            } else {
+                tracing::info!("Store not empty, seed {} not applied", path.display());
+            }
+        }
+        None => tracing::info!("No seed file set (ESCROW_SEED), starting with no users or products"),
+    }
+    if let Some(node) = seller_node {
+        reconcile::spawn(
+            state.clone(),
+            InstrumentedFiberClient::wrap(node, state.metrics().clone()),
+        );
+    }
+    let signer = webhook_signing_key
+        .map(|key| key.parse::<WebhookSigner>())
+        .transpose()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    if let Some(key) = signer.as_ref().and_then(WebhookSigner::public_key) {
+        tracing::info!("Signing alert webhooks, verify with {}", key);
+    }
+    let notifiers: Vec<Arc<dyn Notifier>> = alert_webhooks
+        .into_iter()
+        .map(|url| {
+            tracing::info!("Sending settlement deadline alerts to {}", url);
+            Arc::new(WebhookNotifier::new(url).with_signer(signer.clone())) as Arc<dyn Notifier>
+        })
+        .collect();
+    alerts::spawn(state.clone(), notifiers);
+
+    let port = server.port_or(3000);
+    tracing::info!("Escrow service starting on http://0.0.0.0:{}", port);
+
+    fiber_service::serve(create_app(state), port).await
+}
+
+/// Build the escrow HTTP app: API routes, `/metrics`, gRPC plus the Web UI.
+pub fn create_app(state: AppState) -> Router {
+    let cors = CorsLayer::new()
+        .allow_origin(Any)
+        .allow_methods(Any)
+        .allow_headers(Any);
+
+    let app = Router::new()
+        // User
+        .route("/api/user/register", post(register_user))
+        .route("/api/user/me", get(get_current_user))
+        .route("/api/users", get(list_users))
+        // Products
+        .route("/api/products", post(create_product))
+        .route("/api/products", get(list_products))
+        .route("/api/products/mine", get(list_my_products))
+        .route("/api/products/mine/export", get(export_products))
+        .route("/api/products/import", post(import_products))
+        .route("/api/products/:product_id", get(get_product).patch(edit_product))
+        .route("/api/products/:product_id/publish", post(publish_product))
+        .route("/api/products/:product_id/unpublish", post(unpublish_product))
+        .route("/api/products/:product_id/archive", post(archive_product))
+        // Categories
+        .route("/api/categories", get(list_categories))
+        .route("/api/categories/:id", get(get_category))
+        // Orders
+        .route("/api/orders", post(create_order))
+        .route("/api/orders/mine", get(list_my_orders))
+        .route("/api/orders/mine/summary", get(summarize_my_orders))
+        .route("/api/orders/:order_id", get(get_order))
+        .route("/api/orders/:order_id/timeline", get(get_order_timeline))
+        .route("/api/orders/:order_id/invoice", post(submit_invoice))
+        .route("/api/orders/:order_id/pay", post(pay_order))
+        .route("/api/orders/:order_id/ship", post(ship_order))
+        .route("/api/orders/:order_id/confirm", post(confirm_order))
+        .route("/api/orders/:order_id/dispute", post(dispute_order))
+        // Subscriptions
+        .route("/api/subscriptions", post(create_subscription))
+        .route("/api/subscriptions/mine", get(list_my_subscriptions))
+        .route("/api/subscriptions/:subscription_id", get(get_subscription))
+        .route("/api/subscriptions/:subscription_id/pause", post(pause_subscription))
+        .route("/api/subscriptions/:subscription_id/resume", post(resume_subscription))
+        .route("/api/subscriptions/:subscription_id/cancel", post(cancel_subscription))
+        // Notifications
+        .route("/api/notifications", get(list_notifications))
+        .merge(operator_routes(&state))
+        // Config (returns Fiber RPC URLs for frontend)
+        .route("/api/config", get(get_config))
+        // Health
+        .route("/api/health", get(health))
+        // Static files
+        .fallback_service(static_ui())
+        .with_state(state.clone());
+    #[cfg(feature = "grpc")]
+    let app = app.merge(grpc::router(state.clone()));
+
+    let app = fiber_service::with_metrics(app, state.metrics().clone());
+    fiber_service::request_tracing(app).layer(cors)
+}
+
+/// Admin, arbiter and system routes, behind the admin token if there is one
+fn operator_routes(state: &AppState) -> Router<AppState> {
+    let routes = Router::new()
+        .route("/api/admin/categories", post(create_category))
+        .route("/api/admin/alerts", get(list_deadline_alerts))
+        .route("/api/admin/invoices/:payment_hash", get(get_invoice_owner))
+        .nest("/api/admin", fiber_flags::router(state.features().clone()))
+        .route("/api/arbiter/disputes", get(list_disputes))
+        .route("/api/arbiter/templates", get(list_decision_templates))
+        .route("/api/arbiter/disputes/:order_id/resolve", post(resolve_dispute))
+        .route("/api/system/tick", post(tick));
+    if !state.admin_secret().is_set() {
+        return routes;
+    }
+    routes.route_layer(middleware::from_extractor_with_state::<AdminToken, _>(
+        state.clone(),
+    ))
+}
+
+/// Web UI assets, compiled in with the `embed-ui` feature
+#[cfg(feature = "embed-ui")]
+#[derive(rust_embed::RustEmbed)]
+#[folder = "static"]
+struct StaticAssets;
+
+#[cfg(feature = "embed-ui")]
+fn static_ui() -> Router {
+    fiber_service::embedded_ui::<StaticAssets>()
+}
+
+#[cfg(not(feature = "embed-ui"))]
+fn static_ui() -> Router {
+    fiber_service::static_dir("static")
+}
+
+async fn health() -> &'static str {
+    "ok"
+}
@@ -1,7 +1,9 @@
 //! Data models for the escrow service.
 
 use chrono::{DateTime, Utc};
+use fiber_auth::AuthedUser;
 use fiber_core::{PaymentHash, Preimage};
+pub use fiber_service::AlertLevel;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -21,6 +23,12 @@ impl Default for UserId {
     }
 }
 
+impl From<AuthedUser> for UserId {
+    fn from(user: AuthedUser) -> Self {
+        Self(user.0)
+    }
+}
+
 /// Product ID
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ProductId(pub Uuid);
@@ -53,6 +61,38 @@ impl Default for OrderId {
     }
 }
 
+/// Subscription ID
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SubscriptionId(pub Uuid);
+
+impl SubscriptionId {
+    pub fn new() -> Self {
+        Self(Uuid::new_v4())
+    }
+}
+
+impl Default for SubscriptionId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Category ID
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CategoryId(pub Uuid);
+
+impl CategoryId {
+    pub fn new() -> Self {
+        Self(Uuid::new_v4())
+    }
+}
+
+impl Default for CategoryId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// User
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct User {
@@ -71,12 +111,54 @@ impl User {
     }
 }
 
+/// Product category (operator-managed, forms a tree via `parent_id`)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Category {
+    pub id: CategoryId,
+    pub name: String,
+    /// URL-friendly unique identifier, e.g. `digital-art`
+    pub slug: String,
+    pub parent_id: Option<CategoryId>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Category {
+    pub fn new(
+        name: String,
+        slug: String,
+        parent_id: Option<CategoryId>,
+        now: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            id: CategoryId::new(),
+            name,
+            slug,
+            parent_id,
+            created_at: now,
+        }
+    }
+}
+
+/// Derive a slug from a display name ("Digital Art" -> "digital-art")
+pub fn slugify(name: &str) -> String {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(|part| part.to_ascii_lowercase())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
 /// Product status
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum ProductStatus {
+    /// Being prepared: only the seller sees it, and may still edit it
+    Draft,
+    /// Published and listed for buyers
     Available,
     Sold,
+    /// Off the market for good; existing orders carry on
+    Archived,
 }
 
 /// Product
@@ -87,26 +169,42 @@ pub struct Product {
     pub title: String,
     pub description: String,
     pub price_shannons: u64,
+    /// Billing period for subscription products (`None` for one-off purchases)
+    pub billing_period_secs: Option<u64>,
+    pub category_id: Option<CategoryId>,
     pub status: ProductStatus,
     pub created_at: DateTime<Utc>,
 }
 
 impl Product {
-    pub fn new(seller_id: UserId, title: String, description: String, price_shannons: u64) -> Self {
+    pub fn new(
+        seller_id: UserId,
+        title: String,
+        description: String,
+        price_shannons: u64,
+        now: DateTime<Utc>,
+    ) -> Self {
         Self {
             id: ProductId::new(),
             seller_id,
             title,
             description,
             price_shannons,
+            billing_period_secs: None,
+            category_id: None,
             status: ProductStatus::Available,
-            created_at: Utc::now(),
+            created_at: now,
         }
     }
+
+    /// Whether this product is sold as a recurring subscription
+    pub fn is_subscription(&self) -> bool {
+        self.billing_period_secs.is_some()
+    }
 }
 
 /// Order status
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum OrderStatus {
     WaitingPayment,
@@ -117,6 +215,17 @@ pub enum OrderStatus {
     Refunded,
 }
 
+impl OrderStatus {
+    pub const ALL: [OrderStatus; 6] = [
+        OrderStatus::WaitingPayment,
+        OrderStatus::Funded,
+        OrderStatus::Shipped,
+        OrderStatus::Completed,
+        OrderStatus::Disputed,
+        OrderStatus::Refunded,
+    ];
+}
+
 /// Dispute resolution
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -125,12 +234,75 @@ pub enum DisputeResolution {
     ToBuyer,
 }
 
+/// Why the arbiter decided a dispute the way they did
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResolutionReason {
+    ItemNotReceived,
+    NotAsDescribed,
+    SellerUnresponsive,
+    DeliveredAsDescribed,
+    BuyerAbuse,
+    /// Anything else, explained in the resolution note
+    Other,
+}
+
+impl ResolutionReason {
+    pub const ALL: [ResolutionReason; 6] = [
+        ResolutionReason::ItemNotReceived,
+        ResolutionReason::NotAsDescribed,
+        ResolutionReason::SellerUnresponsive,
+        ResolutionReason::DeliveredAsDescribed,
+        ResolutionReason::BuyerAbuse,
+        ResolutionReason::Other,
+    ];
+
+    /// The side this reason decides for; `None` if it fits either
+    pub fn ruling(self) -> Option<DisputeResolution> {
+        match self {
+            ResolutionReason::ItemNotReceived
+            | ResolutionReason::NotAsDescribed
+            | ResolutionReason::SellerUnresponsive => Some(DisputeResolution::ToBuyer),
+            ResolutionReason::DeliveredAsDescribed | ResolutionReason::BuyerAbuse => {
+                Some(DisputeResolution::ToSeller)
+            }
+            ResolutionReason::Other => None,
+        }
+    }
+
+    /// Wording the arbiter's decision starts from
+    pub fn template(self) -> &'static str {
+        match self {
+            ResolutionReason::ItemNotReceived => {
+                "The seller could not show the item was delivered; the buyer is refunded."
+            }
+            ResolutionReason::NotAsDescribed => {
+                "The item differs materially from its listing; the buyer is refunded."
+            }
+            ResolutionReason::SellerUnresponsive => {
+                "The seller did not respond to the dispute; the buyer is refunded."
+            }
+            ResolutionReason::DeliveredAsDescribed => {
+                "The item was delivered as described; payment is released to the seller."
+            }
+            ResolutionReason::BuyerAbuse => {
+                "The dispute was not raised in good faith; payment is released to the seller."
+            }
+            ResolutionReason::Other => "",
+        }
+    }
+}
+
 /// Dispute
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Dispute {
     pub reason: String,
     pub created_at: DateTime<Utc>,
     pub resolution: Option<DisputeResolution>,
+    /// Set with the resolution
+    pub resolution_reason: Option<ResolutionReason>,
+    /// The arbiter's own words on the decision, if any
+    pub resolution_note: Option<String>,
 }
 
 /// Order
@@ -147,6 +319,11 @@ pub struct Order {
     pub payment_hash: PaymentHash,
     /// Hold invoice string from Fiber RPC
     pub invoice_string: Option<String>,
+    /// When the seller's node cancels the hold invoice, refunding the buyer
+    /// unless the order has been settled by then
+    pub invoice_expires_at: Option<DateTime<Utc>>,
+    /// The most urgent settlement deadline alert raised for the order
+    pub deadline_alert: Option<AlertLevel>,
     /// Preimage revealed by buyer when confirming receipt
     #[serde(skip_serializing)]
     pub revealed_preimage: Option<Preimage>,
@@ -158,6 +335,9 @@ pub struct Order {
 
     // Dispute
     pub dispute: Option<Dispute>,
+
+    /// Subscription this order bills for, if any
+    pub subscription_id: Option<SubscriptionId>,
 }
 
 impl Order {
@@ -166,6 +346,7 @@ impl Order {
         product: &Product,
         buyer_id: UserId,
         payment_hash: PaymentHash,
+        now: DateTime<Utc>,
         timeout_hours: i64,
     ) -> Self {
         Self {
@@ -177,11 +358,190 @@ impl Order {
             amount_shannons: product.price_shannons,
             payment_hash,
             invoice_string: None,
+            invoice_expires_at: None,
+            deadline_alert: None,
+            revealed_preimage: None,
+            status: OrderStatus::WaitingPayment,
+            created_at: now,
+            expires_at: now + chrono::Duration::hours(timeout_hours),
+            dispute: None,
+            subscription_id: None,
+        }
+    }
+
+    /// Create the order billing the next period of a subscription
+    pub fn renewal(
+        subscription: &Subscription,
+        payment_hash: PaymentHash,
+        now: DateTime<Utc>,
+        timeout_hours: i64,
+    ) -> Self {
+        Self {
+            id: OrderId::new(),
+            product_id: subscription.product_id,
+            product_title: subscription.product_title.clone(),
+            seller_id: subscription.seller_id,
+            buyer_id: subscription.buyer_id,
+            amount_shannons: subscription.amount_shannons,
+            payment_hash,
+            invoice_string: None,
+            invoice_expires_at: None,
+            deadline_alert: None,
             revealed_preimage: None,
             status: OrderStatus::WaitingPayment,
-            created_at: Utc::now(),
-            expires_at: Utc::now() + chrono::Duration::hours(timeout_hours),
+            created_at: now,
+            expires_at: now + chrono::Duration::hours(timeout_hours),
             dispute: None,
+            subscription_id: Some(subscription.id),
         }
     }
 }
+
+/// Subscription status
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriptionStatus {
+    /// Current period is paid
+    Active,
+    /// A renewal order was created and is waiting for the buyer's payment
+    PaymentDue,
+    /// Billing paused by the buyer
+    Paused,
+    /// Renewal was not paid before the next billing date
+    Suspended,
+    /// Terminated, no further orders are created
+    Cancelled,
+}
+
+/// Recurring subscription to a product.
+///
+/// The escrow creates a new order on every billing period; each order
+/// goes through the regular hold invoice flow.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Subscription {
+    pub id: SubscriptionId,
+    pub product_id: ProductId,
+    pub product_title: String,
+    pub seller_id: UserId,
+    pub buyer_id: UserId,
+    pub amount_shannons: u64,
+    pub billing_period_secs: u64,
+    pub status: SubscriptionStatus,
+    /// Order for the current billing period
+    pub current_order_id: Option<OrderId>,
+    /// All orders created for this subscription, oldest first
+    pub order_ids: Vec<OrderId>,
+    pub next_billing_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Subscription {
+    pub fn new(product: &Product, buyer_id: UserId, now: DateTime<Utc>) -> Option<Self> {
+        let billing_period_secs = product.billing_period_secs?;
+        Some(Self {
+            id: SubscriptionId::new(),
+            product_id: product.id,
+            product_title: product.title.clone(),
+            seller_id: product.seller_id,
+            buyer_id,
+            amount_shannons: product.price_shannons,
+            billing_period_secs,
+            status: SubscriptionStatus::PaymentDue,
+            current_order_id: None,
+            order_ids: Vec::new(),
+            next_billing_at: now + chrono::Duration::seconds(billing_period_secs as i64),
+            created_at: now,
+        })
+    }
+
+    /// Whether the buyer currently has access to the subscribed product
+    pub fn has_access(&self) -> bool {
+        matches!(
+            self.status,
+            SubscriptionStatus::Active | SubscriptionStatus::PaymentDue
+        )
+    }
+}
+
+/// In-app notification for a user
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Notification {
+    pub id: Uuid,
+    pub user_id: UserId,
+    pub message: String,
+    pub order_id: Option<OrderId>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Notification {
+    pub fn new(
+        user_id: UserId,
+        message: String,
+        order_id: Option<OrderId>,
+        now: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            user_id,
+            message,
+            order_id,
+            created_at: now,
+        }
+    }
+}
+
+/// Something that happened to an order
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderEventKind {
+    Created,
+    InvoiceSubmitted,
+    /// The buyer's payment is held by the seller's invoice
+    PaymentHeld,
+    Shipped,
+    Disputed,
+    /// The arbiter decided the dispute
+    Resolved,
+    /// The preimage was released to the seller
+    Completed,
+    Refunded,
+}
+
+/// An order still holding the buyer's payment close to its hold invoice's
+/// expiry, for operators to step in before the buyer is refunded
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SettlementAlert {
+    pub order_id: OrderId,
+    pub product_title: String,
+    pub amount_shannons: u64,
+    /// What the order waits on: shipping, the buyer's confirmation or the
+    /// arbiter
+    pub status: OrderStatus,
+    pub level: AlertLevel,
+    pub invoice_expires_at: DateTime<Utc>,
+    pub seconds_left: i64,
+}
+
+impl SettlementAlert {
+    pub fn new(order: &Order, level: AlertLevel, now: DateTime<Utc>) -> Option<Self> {
+        let invoice_expires_at = order.invoice_expires_at?;
+        Some(Self {
+            order_id: order.id,
+            product_title: order.product_title.clone(),
+            amount_shannons: order.amount_shannons,
+            status: order.status,
+            level,
+            invoice_expires_at,
+            seconds_left: (invoice_expires_at - now).num_seconds().max(0),
+        })
+    }
+}
+
+/// An entry in an order's timeline
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OrderEvent {
+    pub kind: OrderEventKind,
+    pub at: DateTime<Utc>,
+    /// What set it off or what it decided, where the kind doesn't say
+    pub detail: Option<String>,
+}
@@ -0,0 +1,317 @@
+//! Order lifecycle: who may move an order along, and from which status.
+//!
+//! The HTTP handlers and the gRPC service both call these, so an order
+//! follows the same rules whichever API drives it. Request bodies are
+//! validated by the caller.
+
+use chrono::{DateTime, Utc};
+use fiber_core::Preimage;
+use fiber_errors::{ApiError, Validate};
+use fiber_paging::{Page, PageRequest};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use crate::handlers::{CreateOrderRequest, ListMyOrdersQuery, OrderSort, SubmitInvoiceRequest};
+use crate::models::*;
+use crate::products;
+use crate::state::{AppState, DEFAULT_INVOICE_EXPIRY_SECS, THREE_PARTY_ESCROW};
+
+/// Open an order for `buyer`. The escrow keeps the buyer's preimage until
+/// the order is settled or refunded.
+pub async fn create(
+    state: &AppState,
+    buyer_id: UserId,
+    req: &CreateOrderRequest,
+) -> Result<Order, ApiError> {
+    // Parse preimage from hex and compute payment_hash
+    let preimage = Preimage::from_hex(&req.preimage)
+        .map_err(|_| ApiError::bad_request("Invalid preimage format, expected hex string"))?;
+    let payment_hash = preimage.payment_hash();
+
+    let product_id = ProductId(req.product_id);
+    let product = products::for_sale(state, product_id).await?;
+
+    if product.seller_id == buyer_id {
+        return Err(ApiError::bad_request("Cannot buy your own product"));
+    }
+
+    if product.is_subscription() {
+        return Err(ApiError::bad_request(
+            "Subscription products are purchased via /api/subscriptions",
+        ));
+    }
+
+    // Create order with computed payment_hash
+    let order = state.create_order(&product, buyer_id, payment_hash).await;
+
+    // Store preimage immediately (escrow holds it for timeout/dispute settlement)
+    tracing::info!(
+        order_id = %order.id.0,
+        payment_hash = %order.payment_hash.short_hex(),
+        preimage_hash = %preimage.payment_hash().short_hex(),
+        "Storing preimage"
+    );
+    state.set_revealed_preimage(order.id, preimage).await;
+
+    // No Fiber RPC calls — seller's frontend will create the hold invoice
+    // using the payment_hash, and submit it back via /api/orders/:id/invoice
+    Ok(order)
+}
+
+/// Orders `user` buys or sells that match `query`'s filters other than
+/// the status
+async fn matching(
+    state: &AppState,
+    user_id: UserId,
+    query: &ListMyOrdersQuery,
+) -> Result<Vec<Order>, ApiError> {
+    query.validate()?;
+    let mut orders = state.list_orders_for_user(user_id).await;
+    orders.retain(|o| {
+        query.product_id.is_none_or(|id| o.product_id.0 == id)
+            && query.buyer_id.is_none_or(|id| o.buyer_id.0 == id)
+            && query.from.is_none_or(|from| o.created_at >= from)
+            && query.to.is_none_or(|to| o.created_at < to)
+    });
+    Ok(orders)
+}
+
+/// Orders `user` buys or sells that match `query`, sorted as it asks
+pub async fn list(
+    state: &AppState,
+    user_id: UserId,
+    query: &ListMyOrdersQuery,
+    page: &PageRequest,
+) -> Result<Page<Order>, ApiError> {
+    let orders = matching(state, user_id, query)
+        .await?
+        .into_iter()
+        .filter(|o| query.status.is_none_or(|status| o.status == status));
+    match query.sort {
+        OrderSort::Newest => Page::of(orders, page, |o| (Reverse(o.created_at), o.id.0)),
+        OrderSort::Oldest => Page::of(orders, page, |o| (o.created_at, o.id.0)),
+        OrderSort::Amount => Page::of(orders, page, |o| (Reverse(o.amount_shannons), o.id.0)),
+        OrderSort::Deadline => Page::of(orders, page, |o| (deadline(o), o.id.0)),
+    }
+}
+
+/// How many of the orders `list` would give for `query` are in each
+/// status, whatever status it asks for
+pub async fn count_by_status(
+    state: &AppState,
+    user_id: UserId,
+    query: &ListMyOrdersQuery,
+) -> Result<BTreeMap<OrderStatus, usize>, ApiError> {
+    let mut counts: BTreeMap<_, _> = OrderStatus::ALL.into_iter().map(|s| (s, 0)).collect();
+    for order in matching(state, user_id, query).await? {
+        *counts.entry(order.status).or_default() += 1;
+    }
+    Ok(counts)
+}
+
+/// When the order next needs someone: its timeout, or its invoice's expiry
+/// if that comes first
+fn deadline(order: &Order) -> DateTime<Utc> {
+    order
+        .invoice_expires_at
+        .map_or(order.expires_at, |expiry| expiry.min(order.expires_at))
+}
+
+async fn find(state: &AppState, order_id: OrderId) -> Result<Order, ApiError> {
+    state
+        .get_order(order_id)
+        .await
+        .ok_or_else(|| ApiError::not_found("Order not found"))
+}
+
+/// The order as `user` may see it, with the preimage once the seller may
+/// settle with it
+pub async fn view(
+    state: &AppState,
+    user_id: UserId,
+    order_id: OrderId,
+) -> Result<(Order, Option<Preimage>), ApiError> {
+    let order = find(state, order_id).await?;
+
+    // Only buyer or seller can view order details
+    if order.buyer_id != user_id && order.seller_id != user_id {
+        return Err(ApiError::forbidden("Not authorized to view this order"));
+    }
+
+    // Include preimage for seller if order is completed (for Fiber settlement)
+    let preimage = if order.seller_id == user_id && order.status == OrderStatus::Completed {
+        state.get_revealed_preimage(order_id).await
+    } else {
+        None
+    };
+    Ok((order, preimage))
+}
+
+/// What happened to the order so far, for its buyer and seller only
+pub async fn timeline(
+    state: &AppState,
+    user_id: UserId,
+    order_id: OrderId,
+) -> Result<Vec<OrderEvent>, ApiError> {
+    let order = find(state, order_id).await?;
+    if order.buyer_id != user_id && order.seller_id != user_id {
+        return Err(ApiError::forbidden("Not authorized to view this order"));
+    }
+    Ok(state.order_events(order_id).await)
+}
+
+/// The seller hands over the hold invoice the buyer is to pay
+pub async fn submit_invoice(
+    state: &AppState,
+    user_id: UserId,
+    order_id: OrderId,
+    req: &SubmitInvoiceRequest,
+) -> Result<(), ApiError> {
+    let order = find(state, order_id).await?;
+
+    // Only seller can submit invoice
+    if order.seller_id != user_id {
+        return Err(ApiError::forbidden("Only seller can submit invoice"));
+    }
+
+    // Can only submit invoice for orders waiting payment
+    if order.status != OrderStatus::WaitingPayment {
+        return Err(ApiError::invalid_state(
+            "Order not in WaitingPayment status",
+        ));
+    }
+
+    let expiry_secs = req.expiry_secs.unwrap_or(DEFAULT_INVOICE_EXPIRY_SECS);
+    state
+        .set_order_invoice(order_id, req.invoice.clone(), expiry_secs)
+        .await;
+    Ok(())
+}
+
+/// The buyer reports the invoice paid
+pub async fn pay(state: &AppState, user_id: UserId, order_id: OrderId) -> Result<(), ApiError> {
+    let order = find(state, order_id).await?;
+
+    if order.buyer_id != user_id {
+        return Err(ApiError::forbidden("Not the buyer"));
+    }
+
+    if order.status != OrderStatus::WaitingPayment {
+        return Err(ApiError::invalid_state(
+            "Order not in WaitingPayment status",
+        ));
+    }
+
+    // Require invoice to be submitted before payment can be confirmed
+    if order.invoice_string.is_none() {
+        return Err(ApiError::invalid_state(
+            "Seller has not submitted invoice yet",
+        ));
+    }
+
+    // No Fiber RPC calls — buyer's frontend sends payment directly to their node.
+    // This is called after the buyer's frontend confirms payment was sent.
+
+    // Update order status to funded
+    if !state
+        .transition_order(
+            order_id,
+            &[OrderStatus::WaitingPayment],
+            OrderStatus::Funded,
+        )
+        .await
+    {
+        return Err(ApiError::invalid_state(
+            "Order not in WaitingPayment status",
+        ));
+    }
+    Ok(())
+}
+
+pub async fn ship(state: &AppState, user_id: UserId, order_id: OrderId) -> Result<(), ApiError> {
+    let order = find(state, order_id).await?;
+
+    if order.seller_id != user_id {
+        return Err(ApiError::forbidden("Not the seller"));
+    }
+
+    if order.status != OrderStatus::Funded {
+        return Err(ApiError::invalid_state("Order not in Funded status"));
+    }
+
+    if !state
+        .transition_order(order_id, &[OrderStatus::Funded], OrderStatus::Shipped)
+        .await
+    {
+        return Err(ApiError::invalid_state("Order not in Funded status"));
+    }
+    Ok(())
+}
+
+/// The buyer confirms delivery, which releases the preimage to the seller
+pub async fn confirm(state: &AppState, user_id: UserId, order_id: OrderId) -> Result<(), ApiError> {
+    let order = find(state, order_id).await?;
+
+    if order.buyer_id != user_id {
+        return Err(ApiError::forbidden("Not the buyer"));
+    }
+
+    if order.status != OrderStatus::Shipped {
+        return Err(ApiError::invalid_state("Order not in Shipped status"));
+    }
+
+    // Get preimage from escrow storage (stored at order creation)
+    let preimage = state
+        .get_revealed_preimage(order_id)
+        .await
+        .ok_or_else(|| ApiError::internal("Preimage not found in escrow"))?;
+
+    // Debug: verify preimage matches payment_hash
+    tracing::info!(
+        order_id = %order_id.0,
+        payment_hash = %order.payment_hash.short_hex(),
+        preimage_hash = %preimage.payment_hash().short_hex(),
+        "Settling order"
+    );
+
+    // Mark order as completed, unless a concurrent dispute or confirm got
+    // there first
+    if !state
+        .transition_order(order_id, &[OrderStatus::Shipped], OrderStatus::Completed)
+        .await
+    {
+        return Err(ApiError::invalid_state("Order not in Shipped status"));
+    }
+
+    // No Fiber RPC calls — seller's frontend will call settle_invoice
+    // after seeing the preimage in the order details.
+    tracing::info!(
+        order_id = %order_id.0,
+        "Order completed, preimage available for seller settlement"
+    );
+    Ok(())
+}
+
+pub async fn dispute(
+    state: &AppState,
+    user_id: UserId,
+    order_id: OrderId,
+    reason: String,
+) -> Result<(), ApiError> {
+    state.features().require(&THREE_PARTY_ESCROW)?;
+    let order = find(state, order_id).await?;
+
+    if order.buyer_id != user_id {
+        return Err(ApiError::forbidden("Not the buyer"));
+    }
+
+    // Can only dispute funded or shipped orders
+    if order.status != OrderStatus::Funded && order.status != OrderStatus::Shipped {
+        return Err(ApiError::invalid_state("Cannot dispute this order"));
+    }
+
+    if !state.add_dispute(order_id, reason).await {
+        return Err(ApiError::invalid_state("Cannot dispute this order"));
+    }
+    Ok(())
+}
@@ -0,0 +1,200 @@
+//! Product lifecycle: drafts, publishing and archiving.
+//!
+//! A seller may create a product as a draft, which only they can see and
+//! which they may edit until they publish it. Publishing lists it for
+//! buyers; unpublishing takes it back to a draft, and archiving takes it off
+//! the market for good. Orders and subscriptions already placed carry on
+//! whatever happens to the product. Request bodies are validated by the
+//! caller.
+
+use fiber_errors::ApiError;
+
+use crate::handlers::{CreateProductRequest, EditProductRequest};
+use crate::models::*;
+use crate::state::AppState;
+
+/// List a product for `seller`, or keep it as a draft if they asked to
+pub async fn create(
+    state: &AppState,
+    seller_id: UserId,
+    req: CreateProductRequest,
+) -> Result<Product, ApiError> {
+    let category_id = req.category_id.map(CategoryId);
+    check_category(state, category_id).await?;
+
+    let mut product = Product::new(
+        seller_id,
+        req.title,
+        req.description,
+        req.price_shannons,
+        state.now().await,
+    );
+    product.billing_period_secs = req.billing_period_secs;
+    product.category_id = category_id;
+    if req.draft {
+        product.status = ProductStatus::Draft;
+    }
+    Ok(state.add_product(product).await)
+}
+
+/// The product as `viewer` may see it: drafts and archived products only
+/// for their seller
+pub async fn view(
+    state: &AppState,
+    viewer: Option<UserId>,
+    product_id: ProductId,
+) -> Result<Product, ApiError> {
+    let product = find(state, product_id).await?;
+    let hidden = matches!(product.status, ProductStatus::Draft | ProductStatus::Archived);
+    if hidden && viewer != Some(product.seller_id) {
+        return Err(ApiError::not_found("Product not found"));
+    }
+    Ok(product)
+}
+
+/// A product buyers may order right now
+pub async fn for_sale(state: &AppState, product_id: ProductId) -> Result<Product, ApiError> {
+    let product = view(state, None, product_id).await?;
+    if product.status != ProductStatus::Available {
+        return Err(ApiError::invalid_state("Product is not for sale"));
+    }
+    Ok(product)
+}
+
+/// The seller changes a draft; fields left out stay as they are
+pub async fn edit(
+    state: &AppState,
+    seller_id: UserId,
+    product_id: ProductId,
+    req: EditProductRequest,
+) -> Result<Product, ApiError> {
+    let product = owned(state, seller_id, product_id).await?;
+    if product.status != ProductStatus::Draft {
+        return Err(ApiError::invalid_state("Only drafts can be edited"));
+    }
+    let category_id = req.category_id.map(CategoryId);
+    check_category(state, category_id).await?;
+
+    let edited = state
+        .update_product(product_id, &[ProductStatus::Draft], |p| {
+            if let Some(title) = req.title {
+                p.title = title;
+            }
+            if let Some(description) = req.description {
+                p.description = description;
+            }
+            if let Some(price) = req.price_shannons {
+                p.price_shannons = price;
+            }
+            if let Some(period) = req.billing_period_secs {
+                p.billing_period_secs = Some(period);
+            }
+            if category_id.is_some() {
+                p.category_id = category_id;
+            }
+        })
+        .await;
+    if !edited {
+        return Err(ApiError::invalid_state("Only drafts can be edited"));
+    }
+    find(state, product_id).await
+}
+
+/// The seller lists a draft for buyers
+pub async fn publish(
+    state: &AppState,
+    seller_id: UserId,
+    product_id: ProductId,
+) -> Result<Product, ApiError> {
+    move_to(
+        state,
+        seller_id,
+        product_id,
+        &[ProductStatus::Draft],
+        ProductStatus::Available,
+        "Only drafts can be published",
+    )
+    .await
+}
+
+/// The seller takes a listed product back to a draft
+pub async fn unpublish(
+    state: &AppState,
+    seller_id: UserId,
+    product_id: ProductId,
+) -> Result<Product, ApiError> {
+    move_to(
+        state,
+        seller_id,
+        product_id,
+        &[ProductStatus::Available],
+        ProductStatus::Draft,
+        "Product is not published",
+    )
+    .await
+}
+
+/// The seller takes a product off the market for good
+pub async fn archive(
+    state: &AppState,
+    seller_id: UserId,
+    product_id: ProductId,
+) -> Result<Product, ApiError> {
+    move_to(
+        state,
+        seller_id,
+        product_id,
+        &[ProductStatus::Draft, ProductStatus::Available, ProductStatus::Sold],
+        ProductStatus::Archived,
+        "Product is already archived",
+    )
+    .await
+}
+
+async fn move_to(
+    state: &AppState,
+    seller_id: UserId,
+    product_id: ProductId,
+    from: &[ProductStatus],
+    to: ProductStatus,
+    refusal: &str,
+) -> Result<Product, ApiError> {
+    owned(state, seller_id, product_id).await?;
+    if !state
+        .update_product(product_id, from, |p| p.status = to)
+        .await
+    {
+        return Err(ApiError::invalid_state(refusal));
+    }
+    tracing::info!(product_id = %product_id.0, status = ?to, "Product status changed");
+    find(state, product_id).await
+}
+
+async fn find(state: &AppState, product_id: ProductId) -> Result<Product, ApiError> {
+    state
+        .get_product(product_id)
+        .await
+        .ok_or_else(|| ApiError::not_found("Product not found"))
+}
+
+/// The product, if `seller` is the one selling it
+async fn owned(
+    state: &AppState,
+    seller_id: UserId,
+    product_id: ProductId,
+) -> Result<Product, ApiError> {
+    let product = view(state, Some(seller_id), product_id).await?;
+    if product.seller_id != seller_id {
+        return Err(ApiError::forbidden("Not the seller"));
+    }
+    Ok(product)
+}
+
+pub async fn check_category(state: &AppState, category_id: Option<CategoryId>) -> Result<(), ApiError> {
+    if let Some(category_id) = category_id {
+        if state.get_category(category_id).await.is_none() {
+            return Err(ApiError::bad_request("Category not found"));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,62 @@
+//! Reconciling held orders with the seller's node.
+//!
+//! The escrow only learns about payments from what the frontends report, so
+//! an order counts as funded until someone moves it on. If the seller's node
+//! cancels the hold invoice or lets it expire, the buyer has their money
+//! back while the order still waits to be shipped or confirmed. [`spawn`]
+//! asks the node every [`RECONCILE_INTERVAL`] about each order whose payment
+//! should be held, refunds those whose invoice is gone, and raises the alarm:
+//! a warning in the log, a notification to buyer and seller, and an
+//! [`Event::InvoiceDiverged`](fiber_service::Event::InvoiceDiverged) counted
+//! in the metrics.
+
+use crate::models::OrderId;
+use crate::state::AppState;
+use fiber_core::fiber::{FiberClient, PaymentStatus};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// How often held orders are checked against the seller's node
+pub const RECONCILE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Check held orders against `node` every [`RECONCILE_INTERVAL`] for as
+/// long as the process runs.
+pub fn spawn(state: AppState, node: Arc<dyn FiberClient>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RECONCILE_INTERVAL);
+        loop {
+            interval.tick().await;
+            reconcile(&state, node.as_ref()).await;
+        }
+    });
+}
+
+/// One pass over the held orders; returns those refunded because `node`
+/// no longer holds their payment.
+pub async fn reconcile(state: &AppState, node: &dyn FiberClient) -> Vec<OrderId> {
+    let mut refunded = Vec::new();
+    for order in state.list_held_orders().await {
+        let status = match node.get_payment_status(&order.payment_hash).await {
+            Ok(status) => status,
+            // Unknown to the node, or the node is down: nothing to correct
+            Err(e) => {
+                debug!(order_id = %order.id.0, error = %e, "Could not check order invoice");
+                continue;
+            }
+        };
+        if status != PaymentStatus::Cancelled {
+            continue;
+        }
+        if state.refund_dropped_order(order.id).await {
+            warn!(
+                order_id = %order.id.0,
+                payment_hash = %order.payment_hash.to_hex(),
+                was = ?order.status,
+                "Seller's node dropped a held invoice; order refunded"
+            );
+            refunded.push(order.id);
+        }
+    }
+    refunded
+}
@@ -0,0 +1,188 @@
+//! Seed data for an empty escrow.
+//!
+//! The users, categories and products an escrow starts with come from a
+//! YAML seed file (`--seed`) rather than from the service, so the demo, the
+//! tests and production each bring their own: `seeds/demo.yaml` for the
+//! demo, a smaller one for the test fixtures, none at all in production. A
+//! seed is only applied to an empty store; one that already has users,
+//! categories, products or orders is left as it is. The same file may list
+//! pre-made `games` for the game demo, which the escrow ignores.
+//!
+//! ```yaml
+//! users:
+//!   - { username: buyer, role: buyer }
+//!   - { username: seller, role: seller }
+//!   - { username: arbiter, role: arbiter }
+//! categories:
+//!   - name: Digital Goods
+//!   - { name: Art, parent: digital-goods }   # parent by slug, listed before
+//! products:
+//!   - seller: seller                          # a user with the seller role
+//!     title: Digital Art NFT
+//!     price_shannons: 1000
+//!     category: art
+//! ```
+
+use fiber_errors::Validate;
+use serde::Deserialize;
+use std::path::Path;
+
+use crate::handlers::{CreateCategoryRequest, CreateProductRequest};
+use crate::products;
+use crate::state::AppState;
+
+/// What a seed file lists for the escrow
+#[derive(Debug, Default, Deserialize)]
+pub struct Seed {
+    #[serde(default)]
+    pub users: Vec<SeedUser>,
+    /// Parents before their children
+    #[serde(default)]
+    pub categories: Vec<SeedCategory>,
+    #[serde(default)]
+    pub products: Vec<SeedProduct>,
+}
+
+/// What a seeded user does in the demo
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Buyer,
+    /// The only role a seed lists products for
+    Seller,
+    Arbiter,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SeedUser {
+    pub username: String,
+    pub role: Role,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SeedCategory {
+    pub name: String,
+    /// Derived from the name when omitted
+    #[serde(default)]
+    pub slug: Option<String>,
+    /// Slug of a category listed before this one
+    #[serde(default)]
+    pub parent: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SeedProduct {
+    /// Username of a seeded seller
+    pub seller: String,
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    pub price_shannons: u64,
+    /// Set to sell the product as a subscription billed every period
+    #[serde(default)]
+    pub billing_period_secs: Option<u64>,
+    /// Slug of the category to list it in
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub draft: bool,
+}
+
+impl Seed {
+    /// Load a seed from a YAML file.
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    /// Read a seed from YAML text.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        serde_yaml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    /// Add the seed to `state` if it is empty; returns whether it did. Fails
+    /// on the first entry that can't be added, naming it.
+    pub async fn apply(&self, state: &AppState) -> Result<bool, String> {
+        if !state.is_empty().await {
+            return Ok(false);
+        }
+
+        let mut sellers = Vec::new();
+        for (i, user) in self.users.iter().enumerate() {
+            let entry = || format!("user {} ({})", i + 1, user.username);
+            if user.username.trim().is_empty() {
+                return Err(format!("{}: username cannot be empty", entry()));
+            }
+            if state.get_user_by_username(&user.username).await.is_some() {
+                return Err(format!("{}: username is listed twice", entry()));
+            }
+            let registered = state.register_user(user.username.clone()).await;
+            if user.role == Role::Seller {
+                sellers.push(registered);
+            }
+        }
+
+        for (i, category) in self.categories.iter().enumerate() {
+            let entry = || format!("category {} ({})", i + 1, category.name);
+            let parent_id = match &category.parent {
+                Some(slug) => Some(
+                    state
+                        .get_category_by_slug(slug)
+                        .await
+                        .ok_or_else(|| format!("{}: no category {} listed before it", entry(), slug))?
+                        .id,
+                ),
+                None => None,
+            };
+            let req = CreateCategoryRequest {
+                name: category.name.clone(),
+                slug: category.slug.clone(),
+                parent_id: parent_id.map(|id| id.0),
+            };
+            req.validate().map_err(|e| format!("{}: {}", entry(), e))?;
+            state
+                .create_category(req.name, req.slug, parent_id)
+                .await
+                .map_err(|e| format!("{}: {}", entry(), e))?;
+        }
+
+        for (i, product) in self.products.iter().enumerate() {
+            let entry = || format!("product {} ({})", i + 1, product.title);
+            let seller = sellers
+                .iter()
+                .find(|u| u.username == product.seller)
+                .ok_or_else(|| format!("{}: {} is not a seeded seller", entry(), product.seller))?;
+            let category_id = match &product.category {
+                Some(slug) => Some(
+                    state
+                        .get_category_by_slug(slug)
+                        .await
+                        .ok_or_else(|| format!("{}: no category {}", entry(), slug))?
+                        .id
+                        .0,
+                ),
+                None => None,
+            };
+            let req = CreateProductRequest {
+                title: product.title.clone(),
+                description: product.description.clone(),
+                price_shannons: product.price_shannons,
+                billing_period_secs: product.billing_period_secs,
+                category_id,
+                draft: product.draft,
+            };
+            req.validate().map_err(|e| format!("{}: {}", entry(), e))?;
+            products::create(state, seller.id, req)
+                .await
+                .map_err(|e| format!("{}: {}", entry(), e))?;
+        }
+
+        tracing::info!(
+            users = self.users.len(),
+            categories = self.categories.len(),
+            products = self.products.len(),
+            "Seeded empty escrow"
+        );
+        Ok(true)
+    }
+}
@@ -1,43 +1,130 @@
 //! Application state management.
 
 use crate::models::*;
+use crate::storage::{EscrowStore, StorageError};
 use chrono::{DateTime, Utc};
+use fiber_auth::{AdminSecret, AuthState};
+use fiber_core::fiber::Currency;
+use fiber_core::money::{self, MoneyError};
+use fiber_core::{Preimage, SharedClock, SystemClock};
+use fiber_errors::ApiError;
+use fiber_flags::{Feature, FeatureFlags};
+use fiber_service::{Event, EventBus, Metrics};
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Hours an order has to be confirmed or disputed in, unless configured
+/// otherwise
+pub const DEFAULT_ORDER_TIMEOUT_HOURS: i64 = 24;
+
+/// Seconds a hold invoice lasts if the seller doesn't say; the web UI
+/// creates them with this expiry
+pub const DEFAULT_INVOICE_EXPIRY_SECS: u64 = 24 * 3600;
+
+/// Hours before a held order's invoice expires that operators are warned,
+/// unless configured otherwise
+pub const DEFAULT_DEADLINE_WARNING_HOURS: i64 = 6;
+
+/// Hours before a held order's invoice expires that the warning turns
+/// critical, unless configured otherwise
+pub const DEFAULT_DEADLINE_CRITICAL_HOURS: i64 = 1;
+
+/// Shipped orders complete on their own once past the order timeout
+pub const AUTO_SETTLE: Feature = Feature {
+    name: "auto_settle",
+    description: "Complete shipped orders the buyer left past the order timeout",
+    default: true,
+};
+
+/// Buyers can dispute an order for the arbiter to resolve
+pub const THREE_PARTY_ESCROW: Feature = Feature {
+    name: "three_party_escrow",
+    description: "Let buyers open disputes for the arbiter to resolve",
+    default: true,
+};
+
+/// Statuses in which the buyer's payment is held by the seller's invoice
+pub const HELD: &[OrderStatus] = &[OrderStatus::Funded, OrderStatus::Shipped, OrderStatus::Disputed];
+
+/// Features an operator can switch off
+pub const FEATURES: &[Feature] = &[AUTO_SETTLE, THREE_PARTY_ESCROW];
 
 /// Shared application state
 ///
-/// Note: All Fiber node interactions are handled by the frontend.
-/// The backend stores RPC URLs only to pass them to the frontend.
+/// Note: All Fiber payments are made by the frontend. The backend passes
+/// the RPC URLs on to it, and only reads the seller's node to reconcile
+/// held orders (see [`crate::reconcile`]).
 #[derive(Clone)]
 pub struct AppState {
-    inner: Arc<Mutex<AppStateInner>>,
+    inner: Arc<RwLock<AppStateInner>>,
+    /// Source of real time; `/api/system/tick` is added on top of it
+    clock: SharedClock,
     /// Seller's Fiber RPC URL (passed to frontend for direct node calls)
     seller_fiber_rpc_url: Option<String>,
     /// Buyer's Fiber RPC URL (passed to frontend for direct node calls)
     buyer_fiber_rpc_url: Option<String>,
+    /// Order, dispute and request counters served at `/metrics`
+    metrics: Arc<Metrics>,
+    /// Where order events are published; the metrics follow it
+    events: EventBus,
+    /// Currency the frontend creates invoices in
+    currency: Currency,
+    /// Hours from placing an order until, once shipped, it auto-completes
+    order_timeout_hours: i64,
+    /// How long before a held order's invoice expires a warning is raised
+    deadline_warning: chrono::Duration,
+    /// How long before it the alert turns critical
+    deadline_critical: chrono::Duration,
+    /// Bearer token the arbiter, admin and system routes require, if any
+    admin_token: AdminSecret,
+    /// Which of [`FEATURES`] are on
+    features: FeatureFlags,
+    /// Where subscriptions, their orders and what they refer to are saved,
+    /// if anywhere
+    store: Option<Arc<dyn EscrowStore>>,
 }
 
 struct AppStateInner {
     users: HashMap<UserId, User>,
     products: HashMap<ProductId, Product>,
+    categories: HashMap<CategoryId, Category>,
     orders: HashMap<OrderId, Order>,
-    /// Simulated current time (for timeout testing)
-    current_time: Option<DateTime<Utc>>,
+    subscriptions: HashMap<SubscriptionId, Subscription>,
+    notifications: Vec<Notification>,
+    /// What happened to each order, oldest first
+    order_events: HashMap<OrderId, Vec<OrderEvent>>,
+    /// Simulated time skipped ahead of the clock (for timeout testing)
+    time_offset: chrono::Duration,
 }
 
 impl AppState {
     /// Create new state without Fiber integration (for testing)
     pub fn new() -> Self {
+        let (metrics, events) = followed_metrics();
         Self {
-            inner: Arc::new(Mutex::new(AppStateInner {
+            inner: Arc::new(RwLock::new(AppStateInner {
                 users: HashMap::new(),
                 products: HashMap::new(),
+                categories: HashMap::new(),
                 orders: HashMap::new(),
-                current_time: None,
+                subscriptions: HashMap::new(),
+                notifications: Vec::new(),
+                order_events: HashMap::new(),
+                time_offset: chrono::Duration::zero(),
             })),
+            clock: SystemClock::shared(),
             seller_fiber_rpc_url: None,
             buyer_fiber_rpc_url: None,
+            metrics,
+            events,
+            currency: Currency::default(),
+            order_timeout_hours: DEFAULT_ORDER_TIMEOUT_HOURS,
+            deadline_warning: chrono::Duration::hours(DEFAULT_DEADLINE_WARNING_HOURS),
+            deadline_critical: chrono::Duration::hours(DEFAULT_DEADLINE_CRITICAL_HOURS),
+            admin_token: AdminSecret::default(),
+            features: FeatureFlags::new(FEATURES),
+            store: None,
         }
     }
 
@@ -46,15 +133,30 @@ impl AppState {
         seller_rpc_url: Option<String>,
         buyer_rpc_url: Option<String>,
     ) -> Self {
+        let (metrics, events) = followed_metrics();
         Self {
-            inner: Arc::new(Mutex::new(AppStateInner {
+            inner: Arc::new(RwLock::new(AppStateInner {
                 users: HashMap::new(),
                 products: HashMap::new(),
+                categories: HashMap::new(),
                 orders: HashMap::new(),
-                current_time: None,
+                subscriptions: HashMap::new(),
+                notifications: Vec::new(),
+                order_events: HashMap::new(),
+                time_offset: chrono::Duration::zero(),
             })),
+            clock: SystemClock::shared(),
             seller_fiber_rpc_url: seller_rpc_url,
             buyer_fiber_rpc_url: buyer_rpc_url,
+            metrics,
+            events,
+            currency: Currency::default(),
+            order_timeout_hours: DEFAULT_ORDER_TIMEOUT_HOURS,
+            deadline_warning: chrono::Duration::hours(DEFAULT_DEADLINE_WARNING_HOURS),
+            deadline_critical: chrono::Duration::hours(DEFAULT_DEADLINE_CRITICAL_HOURS),
+            admin_token: AdminSecret::default(),
+            features: FeatureFlags::new(FEATURES),
+            store: None,
         }
     }
 
@@ -68,103 +170,290 @@ impl AppState {
         self.buyer_fiber_rpc_url.as_deref()
     }
 
-    /// Get current time (real or simulated)
-    pub fn now(&self) -> DateTime<Utc> {
-        self.inner
-            .lock()
-            .unwrap()
-            .current_time
-            .unwrap_or_else(Utc::now)
+    /// Have the frontend create invoices in `currency`
+    pub fn with_currency(mut self, currency: Currency) -> Self {
+        self.currency = currency;
+        self
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    /// Auto-complete shipped orders `hours` after they were placed rather than
+    /// after [`DEFAULT_ORDER_TIMEOUT_HOURS`]
+    pub fn with_order_timeout_hours(mut self, hours: i64) -> Self {
+        self.order_timeout_hours = hours;
+        self
+    }
+
+    /// Alert on held orders `warning_hours` before their invoice expires,
+    /// and again `critical_hours` before, rather than after
+    /// [`DEFAULT_DEADLINE_WARNING_HOURS`] and
+    /// [`DEFAULT_DEADLINE_CRITICAL_HOURS`]
+    pub fn with_deadline_alerts(mut self, warning_hours: i64, critical_hours: i64) -> Self {
+        self.deadline_warning = chrono::Duration::hours(warning_hours);
+        self.deadline_critical = chrono::Duration::hours(critical_hours);
+        self
+    }
+
+    /// Require `token` as a bearer token on the arbiter, admin and system
+    /// routes. Without one they are open, for the demo UI's arbiter tab.
+    pub fn with_admin_token(mut self, token: Option<String>) -> Self {
+        self.admin_token = AdminSecret::new(token);
+        self
+    }
+
+    /// Start with `features` instead of every feature at its default
+    pub fn with_features(mut self, features: FeatureFlags) -> Self {
+        self.features = features;
+        self
+    }
+
+    pub fn features(&self) -> &FeatureFlags {
+        &self.features
+    }
+
+    /// Persist subscriptions to `store`, restoring what a previous run saved
+    pub fn with_store(mut self, store: Arc<dyn EscrowStore>) -> Result<Self, StorageError> {
+        let users = store.load_users()?;
+        let categories = store.load_categories()?;
+        let products = store.load_products()?;
+        let subscriptions = store.load_subscriptions()?;
+        let orders = store.load_orders()?;
+        if !users.is_empty() {
+            tracing::info!(
+                users = users.len(),
+                products = products.len(),
+                subscriptions = subscriptions.len(),
+                orders = orders.len(),
+                "Restored escrow state"
+            );
+        }
+        let inner = Arc::get_mut(&mut self.inner)
+            .expect("store is set before the state is shared")
+            .get_mut();
+        inner.users.extend(users.into_iter().map(|u| (u.id, u)));
+        inner
+            .categories
+            .extend(categories.into_iter().map(|c| (c.id, c)));
+        inner.products.extend(products.into_iter().map(|p| (p.id, p)));
+        inner.orders.extend(orders.into_iter().map(|o| (o.id, o)));
+        inner
+            .subscriptions
+            .extend(subscriptions.into_iter().map(|s| (s.id, s)));
+        self.store = Some(store);
+        Ok(self)
+    }
+
+    /// Read time from `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// Get current time (clock plus any simulated advance)
+    pub async fn now(&self) -> DateTime<Utc> {
+        let inner = self.inner.read().await;
+        self.now_in(&inner)
+    }
+
+    /// [`AppState::now`] for a caller already holding the lock
+    fn now_in(&self, inner: &AppStateInner) -> DateTime<Utc> {
+        DateTime::<Utc>::from(self.clock.now()) + inner.time_offset
     }
 
     /// Advance simulated time by seconds
-    pub fn advance_time(&self, seconds: i64) {
-        let mut inner = self.inner.lock().unwrap();
-        let current = inner.current_time.unwrap_or_else(Utc::now);
-        inner.current_time = Some(current + chrono::Duration::seconds(seconds));
+    pub async fn advance_time(&self, seconds: i64) {
+        self.inner.write().await.time_offset += chrono::Duration::seconds(seconds);
+    }
+
+    /// Whether nothing has been added yet: no users, categories, products
+    /// or orders
+    pub async fn is_empty(&self) -> bool {
+        let inner = self.inner.read().await;
+        inner.users.is_empty()
+            && inner.categories.is_empty()
+            && inner.products.is_empty()
+            && inner.orders.is_empty()
     }
 
     // User operations
 
-    pub fn register_user(&self, username: String) -> User {
-        let user = User::new(username);
-        let mut inner = self.inner.lock().unwrap();
+    pub async fn register_user(&self, username: String) -> User {
+        self.register_user_as(UserId::new(), username).await
+    }
+
+    /// Register a user under an ID decided elsewhere, such as by their
+    /// shared identity
+    pub async fn register_user_as(&self, id: UserId, username: String) -> User {
+        let user = User { id, ..User::new(username) };
+        let mut inner = self.inner.write().await;
         inner.users.insert(user.id, user.clone());
+        self.persist("user", user.id.0, |store| store.save_user(&user));
         user
     }
 
-    pub fn get_user(&self, id: UserId) -> Option<User> {
-        let mut user = {
-            let inner = self.inner.lock().unwrap();
-            inner.users.get(&id).cloned()?
-        };
+    pub async fn get_user(&self, id: UserId) -> Result<Option<User>, MoneyError> {
+        let inner = self.inner.read().await;
+        inner.users.get(&id).map(|user| inner.with_balance(user)).transpose()
+    }
 
-        // Calculate simulated balance based on orders
-        // Real balance comes from frontend calling Fiber node directly
-        let inner = self.inner.lock().unwrap();
-        let mut balance: i64 = 0;
-        for order in inner.orders.values() {
-            if order.seller_id == id && order.status == OrderStatus::Completed {
-                balance += order.amount_shannons as i64;
-            }
-            if order.buyer_id == id {
-                match order.status {
-                    OrderStatus::Funded
-                    | OrderStatus::Shipped
-                    | OrderStatus::Completed
-                    | OrderStatus::Disputed => {
-                        balance -= order.amount_shannons as i64;
-                    }
-                    _ => {}
-                }
+    pub async fn get_user_by_username(&self, username: &str) -> Option<User> {
+        let inner = self.inner.read().await;
+        inner
+            .users
+            .values()
+            .find(|u| u.username == username)
+            .cloned()
+    }
+
+    pub async fn list_users(&self) -> Result<Vec<User>, MoneyError> {
+        let inner = self.inner.read().await;
+        inner
+            .users
+            .values()
+            .map(|user| inner.with_balance(user))
+            .collect()
+    }
+
+    // Category operations
+
+    /// Create a category. Fails if the slug is taken or the parent is unknown.
+    pub async fn create_category(
+        &self,
+        name: String,
+        slug: Option<String>,
+        parent_id: Option<CategoryId>,
+    ) -> Result<Category, ApiError> {
+        let slug = slug.unwrap_or_else(|| slugify(&name));
+        if slug.is_empty() {
+            return Err(ApiError::bad_request("Category slug cannot be empty"));
+        }
+
+        let mut inner = self.inner.write().await;
+        if inner.categories.values().any(|c| c.slug == slug) {
+            return Err(ApiError::conflict("Category slug already exists"));
+        }
+        if let Some(parent_id) = parent_id {
+            if !inner.categories.contains_key(&parent_id) {
+                return Err(ApiError::bad_request("Parent category not found"));
             }
         }
-        user.balance_shannons = balance;
 
-        Some(user)
+        let category = Category::new(name, slug, parent_id, self.now_in(&inner));
+        inner.categories.insert(category.id, category.clone());
+        self.persist("category", category.id.0, |store| store.save_category(&category));
+        Ok(category)
     }
 
-    pub fn get_user_by_username(&self, username: &str) -> Option<User> {
-        let inner = self.inner.lock().unwrap();
-        inner
-            .users
+    pub async fn get_category(&self, id: CategoryId) -> Option<Category> {
+        self.inner.read().await.categories.get(&id).cloned()
+    }
+
+    pub async fn get_category_by_slug(&self, slug: &str) -> Option<Category> {
+        self.inner
+            .read()
+            .await
+            .categories
             .values()
-            .find(|u| u.username == username)
+            .find(|c| c.slug == slug)
             .cloned()
     }
 
-    pub fn list_users(&self) -> Vec<User> {
-        let ids: Vec<UserId> = {
-            let inner = self.inner.lock().unwrap();
-            inner.users.keys().cloned().collect()
-        };
+    pub async fn list_categories(&self) -> Vec<Category> {
+        let mut categories: Vec<Category> = self
+            .inner
+            .read()
+            .await
+            .categories
+            .values()
+            .cloned()
+            .collect();
+        categories.sort_by(|a, b| a.name.cmp(&b.name));
+        categories
+    }
 
-        ids.iter().filter_map(|id| self.get_user(*id)).collect()
+    /// The category and all of its descendants
+    pub async fn category_subtree(&self, id: CategoryId) -> Vec<CategoryId> {
+        let inner = self.inner.read().await;
+        let mut subtree = vec![id];
+        let mut i = 0;
+        while i < subtree.len() {
+            let parent = subtree[i];
+            subtree.extend(
+                inner
+                    .categories
+                    .values()
+                    .filter(|c| c.parent_id == Some(parent))
+                    .map(|c| c.id),
+            );
+            i += 1;
+        }
+        subtree
     }
 
     // Product operations
 
-    pub fn create_product(
+    pub async fn create_product(
         &self,
         seller_id: UserId,
         title: String,
         description: String,
         price_shannons: u64,
+        billing_period_secs: Option<u64>,
+        category_id: Option<CategoryId>,
     ) -> Product {
-        let product = Product::new(seller_id, title, description, price_shannons);
-        let mut inner = self.inner.lock().unwrap();
+        let now = self.now().await;
+        let mut product = Product::new(seller_id, title, description, price_shannons, now);
+        product.billing_period_secs = billing_period_secs;
+        product.category_id = category_id;
+        self.add_product(product).await
+    }
+
+    /// Store a product built by the caller, e.g. a draft
+    pub async fn add_product(&self, product: Product) -> Product {
+        let mut inner = self.inner.write().await;
         inner.products.insert(product.id, product.clone());
+        self.persist("product", product.id.0, |store| store.save_product(&product));
         product
     }
 
-    pub fn get_product(&self, id: ProductId) -> Option<Product> {
-        self.inner.lock().unwrap().products.get(&id).cloned()
+    /// Change a product, unless it has moved on from `from` since the caller
+    /// looked at it. Returns whether it was changed.
+    pub async fn update_product(
+        &self,
+        id: ProductId,
+        from: &[ProductStatus],
+        change: impl FnOnce(&mut Product),
+    ) -> bool {
+        let mut inner = self.inner.write().await;
+        match inner.products.get_mut(&id) {
+            Some(product) if from.contains(&product.status) => {
+                change(product);
+                self.persist("product", id.0, |store| store.save_product(product));
+                true
+            }
+            _ => false,
+        }
     }
 
-    pub fn list_available_products(&self) -> Vec<Product> {
+    pub async fn get_product(&self, id: ProductId) -> Option<Product> {
+        self.inner.read().await.products.get(&id).cloned()
+    }
+
+    pub async fn list_available_products(&self) -> Vec<Product> {
         self.inner
-            .lock()
-            .unwrap()
+            .read()
+            .await
             .products
             .values()
             .filter(|p| p.status == ProductStatus::Available)
@@ -172,10 +461,10 @@ impl AppState {
             .collect()
     }
 
-    pub fn list_products_by_seller(&self, seller_id: UserId) -> Vec<Product> {
+    pub async fn list_products_by_seller(&self, seller_id: UserId) -> Vec<Product> {
         self.inner
-            .lock()
-            .unwrap()
+            .read()
+            .await
             .products
             .values()
             .filter(|p| p.seller_id == seller_id)
@@ -185,33 +474,73 @@ impl AppState {
 
     // Order operations
 
-    pub fn create_order(
+    pub async fn create_order(
         &self,
         product: &Product,
         buyer_id: UserId,
         payment_hash: fiber_core::PaymentHash,
     ) -> Order {
-        let order = Order::new(product, buyer_id, payment_hash, 24); // 24 hour timeout
-        let mut inner = self.inner.lock().unwrap();
+        let mut inner = self.inner.write().await;
+        let now = self.now_in(&inner);
+        let order = Order::new(product, buyer_id, payment_hash, now, self.order_timeout_hours);
+        inner.record(order.id, OrderEventKind::Created, now, None);
         inner.orders.insert(order.id, order.clone());
         order
     }
 
-    pub fn get_order(&self, id: OrderId) -> Option<Order> {
-        self.inner.lock().unwrap().orders.get(&id).cloned()
+    /// What happened to an order so far, oldest first
+    pub async fn order_events(&self, id: OrderId) -> Vec<OrderEvent> {
+        self.inner
+            .read()
+            .await
+            .order_events
+            .get(&id)
+            .cloned()
+            .unwrap_or_default()
     }
 
-    pub fn update_order_status(&self, id: OrderId, status: OrderStatus) {
-        let mut inner = self.inner.lock().unwrap();
-        if let Some(order) = inner.orders.get_mut(&id) {
-            order.status = status;
+    pub async fn get_order(&self, id: OrderId) -> Option<Order> {
+        self.inner.read().await.orders.get(&id).cloned()
+    }
+
+    pub async fn update_order_status(&self, id: OrderId, status: OrderStatus) {
+        let mut inner = self.inner.write().await;
+        let now = self.now_in(&inner);
+        inner.set_order_status(id, status, now);
+        self.persist_billed(&inner, id);
+    }
+
+    /// Move an order to `to` only if it is still in one of `from`.
+    ///
+    /// Handlers check the status before acting on it; doing the check again
+    /// under the lock means only one of several racing requests wins.
+    pub async fn transition_order(
+        &self,
+        id: OrderId,
+        from: &[OrderStatus],
+        to: OrderStatus,
+    ) -> bool {
+        let mut inner = self.inner.write().await;
+        let now = self.now_in(&inner);
+        let current = inner.orders.get(&id).map(|o| o.status);
+        if !current.is_some_and(|status| from.contains(&status)) {
+            return false;
+        }
+        inner.set_order_status(id, to, now);
+        self.persist_billed(&inner, id);
+        let order_id = id.0;
+        match to {
+            OrderStatus::Funded => self.events.publish(Event::OrderFunded { order_id }),
+            OrderStatus::Completed => self.events.publish(Event::OrderSettled { order_id }),
+            _ => {}
         }
+        true
     }
 
-    pub fn list_orders_for_user(&self, user_id: UserId) -> Vec<Order> {
+    pub async fn list_orders_for_user(&self, user_id: UserId) -> Vec<Order> {
         self.inner
-            .lock()
-            .unwrap()
+            .read()
+            .await
             .orders
             .values()
             .filter(|o| o.buyer_id == user_id || o.seller_id == user_id)
@@ -219,10 +548,10 @@ impl AppState {
             .collect()
     }
 
-    pub fn list_disputed_orders(&self) -> Vec<Order> {
+    pub async fn list_disputed_orders(&self) -> Vec<Order> {
         self.inner
-            .lock()
-            .unwrap()
+            .read()
+            .await
             .orders
             .values()
             .filter(|o| o.status == OrderStatus::Disputed)
@@ -230,52 +559,229 @@ impl AppState {
             .collect()
     }
 
-    pub fn add_dispute(&self, order_id: OrderId, reason: String) {
-        let mut inner = self.inner.lock().unwrap();
-        if let Some(order) = inner.orders.get_mut(&order_id) {
-            order.dispute = Some(Dispute {
-                reason,
-                created_at: Utc::now(),
-                resolution: None,
-            });
-            order.status = OrderStatus::Disputed;
+    /// Open a dispute on a funded or shipped order; false if the order has
+    /// since moved on
+    pub async fn add_dispute(&self, order_id: OrderId, reason: String) -> bool {
+        let mut inner = self.inner.write().await;
+        let now = self.now_in(&inner);
+        let Some(order) = inner.orders.get_mut(&order_id) else {
+            return false;
+        };
+        if !matches!(order.status, OrderStatus::Funded | OrderStatus::Shipped) {
+            return false;
         }
+        order.dispute = Some(Dispute {
+            reason,
+            created_at: now,
+            resolution: None,
+            resolution_reason: None,
+            resolution_note: None,
+        });
+        order.status = OrderStatus::Disputed;
+        let reason = order.dispute.as_ref().map(|d| d.reason.clone());
+        inner.record(order_id, OrderEventKind::Disputed, now, reason);
+        self.persist_billed(&inner, order_id);
+        self.events.publish(Event::DisputeOpened {
+            order_id: order_id.0,
+        });
+        true
     }
 
-    pub fn resolve_dispute(&self, order_id: OrderId, resolution: DisputeResolution) {
-        let mut inner = self.inner.lock().unwrap();
-        if let Some(order) = inner.orders.get_mut(&order_id) {
-            if let Some(ref mut dispute) = order.dispute {
-                dispute.resolution = Some(resolution);
-            }
-            order.status = match resolution {
-                DisputeResolution::ToSeller => OrderStatus::Completed,
-                DisputeResolution::ToBuyer => OrderStatus::Refunded,
-            };
+    /// Close a dispute; false if the order is not (or no longer) disputed
+    pub async fn resolve_dispute(
+        &self,
+        order_id: OrderId,
+        resolution: DisputeResolution,
+        reason: ResolutionReason,
+        note: Option<String>,
+    ) -> bool {
+        let mut inner = self.inner.write().await;
+        let now = self.now_in(&inner);
+        let Some(order) = inner.orders.get_mut(&order_id) else {
+            return false;
+        };
+        if order.status != OrderStatus::Disputed {
+            return false;
         }
+        let detail = note
+            .clone()
+            .or_else(|| Some(reason.template().to_string()).filter(|t| !t.is_empty()));
+        if let Some(ref mut dispute) = order.dispute {
+            dispute.resolution = Some(resolution);
+            dispute.resolution_reason = Some(reason);
+            dispute.resolution_note = note;
+        }
+        let (status, outcome) = match resolution {
+            DisputeResolution::ToSeller => (OrderStatus::Completed, OrderEventKind::Completed),
+            DisputeResolution::ToBuyer => (OrderStatus::Refunded, OrderEventKind::Refunded),
+        };
+        order.status = status;
+        inner.record(order_id, OrderEventKind::Resolved, now, detail);
+        inner.record(order_id, outcome, now, None);
+        self.persist_billed(&inner, order_id);
+        let order_id = order_id.0;
+        self.events.publish(Event::DisputeResolved { order_id });
+        if resolution == DisputeResolution::ToSeller {
+            self.events.publish(Event::OrderSettled { order_id });
+        }
+        true
     }
 
     /// Check for expired orders and auto-confirm them
     /// Returns list of expired OrderIds (settlement is handled by frontend)
-    pub fn process_expired_orders(&self) -> Vec<OrderId> {
-        let now = self.now();
+    pub async fn process_expired_orders(&self) -> Vec<OrderId> {
+        let mut inner = self.inner.write().await;
+        let now = self.now_in(&inner);
         let mut expired = Vec::new();
-
-        let mut inner = self.inner.lock().unwrap();
         for order in inner.orders.values_mut() {
             // Only auto-confirm shipped orders that have expired
             if order.status == OrderStatus::Shipped && order.expires_at <= now {
                 order.status = OrderStatus::Completed;
                 expired.push(order.id);
+                self.events.publish(Event::OrderSettled {
+                    order_id: order.id.0,
+                });
             }
         }
+        for &id in &expired {
+            let detail = Some("Not confirmed or disputed in time".to_string());
+            inner.record(id, OrderEventKind::Completed, now, detail);
+            self.persist_billed(&inner, id);
+        }
 
         expired
     }
 
+    /// Orders whose payment the seller's invoice should be holding
+    pub async fn list_held_orders(&self) -> Vec<Order> {
+        self.inner
+            .read()
+            .await
+            .orders
+            .values()
+            .filter(|o| HELD.contains(&o.status))
+            .cloned()
+            .collect()
+    }
+
+    /// The order paying into the invoice for `payment_hash`, whatever its
+    /// status, so an invoice found on a node can be traced to its order
+    pub async fn find_order_by_payment_hash(
+        &self,
+        payment_hash: &fiber_core::PaymentHash,
+    ) -> Option<Order> {
+        self.inner
+            .read()
+            .await
+            .orders
+            .values()
+            .find(|o| o.payment_hash == *payment_hash)
+            .cloned()
+    }
+
+    /// Refund an order whose hold invoice the seller's node cancelled or
+    /// let expire, if it still counts as held, and tell buyer and seller.
+    /// Returns whether it was refunded.
+    pub async fn refund_dropped_order(&self, id: OrderId) -> bool {
+        let mut inner = self.inner.write().await;
+        let now = self.now_in(&inner);
+        let Some(order) = inner.orders.get_mut(&id) else {
+            return false;
+        };
+        let was = order.status;
+        if !HELD.contains(&was) {
+            return false;
+        }
+        order.status = OrderStatus::Refunded;
+        let order = order.clone();
+
+        let detail = "The seller's node cancelled the hold invoice or let it expire";
+        inner.record(id, OrderEventKind::Refunded, now, Some(detail.to_string()));
+        self.persist_billed(&inner, id);
+        let message = format!(
+            "Order for \"{}\" refunded: the payment is no longer held",
+            order.product_title
+        );
+        for user_id in [order.buyer_id, order.seller_id] {
+            inner
+                .notifications
+                .push(Notification::new(user_id, message.clone(), Some(id), now));
+        }
+        self.events.publish(Event::InvoiceDiverged {
+            payment_hash: order.payment_hash,
+        });
+        if was == OrderStatus::Disputed {
+            // Nothing left for the arbiter to decide
+            self.events.publish(Event::DisputeResolved { order_id: id.0 });
+        }
+        true
+    }
+
+    /// Raise an alert for each held order whose invoice expires within the
+    /// warning window, once per level: buyer and seller are notified and an
+    /// [`Event::SettlementDeadline`] is published. Returns the alerts raised.
+    pub async fn raise_deadline_alerts(&self) -> Vec<SettlementAlert> {
+        let mut inner = self.inner.write().await;
+        let now = self.now_in(&inner);
+        let mut raised = Vec::new();
+        let mut notifications = Vec::new();
+        for order in inner.orders.values_mut() {
+            let Some(expires_at) = order.invoice_expires_at else {
+                continue;
+            };
+            if !HELD.contains(&order.status) {
+                continue;
+            }
+            let left = expires_at - now;
+            let level = if left <= self.deadline_critical {
+                AlertLevel::Critical
+            } else if left <= self.deadline_warning {
+                AlertLevel::Warning
+            } else {
+                continue;
+            };
+            if order.deadline_alert >= Some(level) {
+                continue;
+            }
+            order.deadline_alert = Some(level);
+            let Some(alert) = SettlementAlert::new(order, level, now) else {
+                continue;
+            };
+            let message = deadline_message(&alert);
+            for user_id in [order.buyer_id, order.seller_id] {
+                notifications.push(Notification::new(user_id, message.clone(), Some(order.id), now));
+            }
+            raised.push(alert);
+        }
+        inner.notifications.extend(notifications);
+
+        for alert in &raised {
+            self.persist_billed(&inner, alert.order_id);
+            self.events.publish(Event::SettlementDeadline {
+                order_id: alert.order_id.0,
+                level: alert.level,
+            });
+        }
+        raised
+    }
+
+    /// Held orders an alert was raised for, the soonest to expire first
+    pub async fn list_deadline_alerts(&self) -> Vec<SettlementAlert> {
+        let inner = self.inner.read().await;
+        let now = self.now_in(&inner);
+        let mut alerts: Vec<_> = inner
+            .orders
+            .values()
+            .filter(|o| HELD.contains(&o.status))
+            .filter_map(|o| SettlementAlert::new(o, o.deadline_alert?, now))
+            .collect();
+        alerts.sort_by_key(|a| a.invoice_expires_at);
+        alerts
+    }
+
     /// Get revealed preimage for a completed order (for settlement)
-    pub fn get_revealed_preimage(&self, order_id: OrderId) -> Option<fiber_core::Preimage> {
-        let inner = self.inner.lock().unwrap();
+    pub async fn get_revealed_preimage(&self, order_id: OrderId) -> Option<fiber_core::Preimage> {
+        let inner = self.inner.read().await;
         inner
             .orders
             .get(&order_id)
@@ -283,17 +789,382 @@ impl AppState {
     }
 
     /// Set revealed preimage when buyer confirms receipt
-    pub fn set_revealed_preimage(&self, order_id: OrderId, preimage: fiber_core::Preimage) {
-        let mut inner = self.inner.lock().unwrap();
+    pub async fn set_revealed_preimage(&self, order_id: OrderId, preimage: fiber_core::Preimage) {
+        let mut inner = self.inner.write().await;
         if let Some(order) = inner.orders.get_mut(&order_id) {
             order.revealed_preimage = Some(preimage);
         }
+        self.persist_billed(&inner, order_id);
+    }
+
+    // Subscription operations
+
+    /// Start a subscription and create the order for its first period.
+    ///
+    /// Returns `None` if the product is not a subscription product.
+    pub async fn create_subscription(
+        &self,
+        product: &Product,
+        buyer_id: UserId,
+        preimage: Preimage,
+    ) -> Option<(Subscription, Order)> {
+        let mut inner = self.inner.write().await;
+        let now = self.now_in(&inner);
+        let mut subscription = Subscription::new(product, buyer_id, now)?;
+        let mut order = Order::new(
+            product,
+            buyer_id,
+            preimage.payment_hash(),
+            now,
+            self.order_timeout_hours,
+        );
+        order.subscription_id = Some(subscription.id);
+        order.revealed_preimage = Some(preimage);
+        subscription.current_order_id = Some(order.id);
+        subscription.order_ids.push(order.id);
+        inner.record(order.id, OrderEventKind::Created, now, None);
+
+        inner.orders.insert(order.id, order.clone());
+        inner.subscriptions.insert(subscription.id, subscription.clone());
+        self.persist_order(&order);
+        self.persist_subscription(&subscription);
+        Some((subscription, order))
+    }
+
+    pub async fn get_subscription(&self, id: SubscriptionId) -> Option<Subscription> {
+        self.inner.read().await.subscriptions.get(&id).cloned()
+    }
+
+    pub async fn list_subscriptions_for_user(&self, user_id: UserId) -> Vec<Subscription> {
+        self.inner
+            .read()
+            .await
+            .subscriptions
+            .values()
+            .filter(|s| s.buyer_id == user_id || s.seller_id == user_id)
+            .cloned()
+            .collect()
+    }
+
+    /// Pause billing. No renewal orders are created while paused.
+    pub async fn pause_subscription(&self, id: SubscriptionId) -> Result<Subscription, ApiError> {
+        let mut inner = self.inner.write().await;
+        let sub = inner
+            .subscriptions
+            .get_mut(&id)
+            .ok_or_else(|| ApiError::not_found("Subscription not found"))?;
+        match sub.status {
+            SubscriptionStatus::Active | SubscriptionStatus::PaymentDue => {
+                sub.status = SubscriptionStatus::Paused;
+                self.persist_subscription(sub);
+                Ok(sub.clone())
+            }
+            _ => Err(ApiError::invalid_state(
+                "Only active subscriptions can be paused",
+            )),
+        }
     }
 
-    pub fn set_order_invoice(&self, id: OrderId, invoice: String) {
-        let mut inner = self.inner.lock().unwrap();
+    /// Resume a paused subscription. A billing date missed while paused
+    /// is rescheduled to now, so the next tick bills immediately.
+    pub async fn resume_subscription(&self, id: SubscriptionId) -> Result<Subscription, ApiError> {
+        let mut inner = self.inner.write().await;
+        let now = self.now_in(&inner);
+        let unpaid = inner
+            .subscriptions
+            .get(&id)
+            .and_then(|s| s.current_order_id)
+            .and_then(|order_id| inner.orders.get(&order_id))
+            .is_some_and(|o| o.status == OrderStatus::WaitingPayment);
+        let sub = inner
+            .subscriptions
+            .get_mut(&id)
+            .ok_or_else(|| ApiError::not_found("Subscription not found"))?;
+        if sub.status != SubscriptionStatus::Paused {
+            return Err(ApiError::invalid_state("Subscription is not paused"));
+        }
+        sub.status = if unpaid {
+            SubscriptionStatus::PaymentDue
+        } else {
+            SubscriptionStatus::Active
+        };
+        if sub.next_billing_at < now {
+            sub.next_billing_at = now;
+        }
+        self.persist_subscription(sub);
+        Ok(sub.clone())
+    }
+
+    pub async fn cancel_subscription(&self, id: SubscriptionId) -> Result<Subscription, ApiError> {
+        let mut inner = self.inner.write().await;
+        let sub = inner
+            .subscriptions
+            .get_mut(&id)
+            .ok_or_else(|| ApiError::not_found("Subscription not found"))?;
+        if sub.status == SubscriptionStatus::Cancelled {
+            return Err(ApiError::invalid_state("Subscription already cancelled"));
+        }
+        sub.status = SubscriptionStatus::Cancelled;
+        self.persist_subscription(sub);
+        Ok(sub.clone())
+    }
+
+    /// Bill subscriptions whose period has ended.
+    ///
+    /// Creates a renewal order and notifies the buyer for every paid-up
+    /// subscription; subscriptions whose previous order is still unpaid are
+    /// suspended instead. Renewal preimages are generated by the escrow since
+    /// the buyer is not online when the period rolls over.
+    pub async fn process_subscription_billing(&self) -> SubscriptionBilling {
+        let mut inner = self.inner.write().await;
+        let now = self.now_in(&inner);
+        let mut billing = SubscriptionBilling::default();
+        let inner = &mut *inner;
+        for sub in inner.subscriptions.values_mut() {
+            let due = matches!(
+                sub.status,
+                SubscriptionStatus::Active | SubscriptionStatus::PaymentDue
+            ) && sub.next_billing_at <= now;
+            if !due {
+                continue;
+            }
+
+            let unpaid = sub
+                .current_order_id
+                .and_then(|id| inner.orders.get(&id))
+                .is_some_and(|o| o.status == OrderStatus::WaitingPayment);
+            if unpaid {
+                sub.status = SubscriptionStatus::Suspended;
+                inner.notifications.push(Notification::new(
+                    sub.buyer_id,
+                    format!(
+                        "Subscription to \"{}\" suspended: payment not received",
+                        sub.product_title
+                    ),
+                    sub.current_order_id,
+                    now,
+                ));
+                billing.suspended.push(sub.id);
+                self.persist_subscription(sub);
+                continue;
+            }
+
+            let preimage = Preimage::random();
+            let mut order =
+                Order::renewal(sub, preimage.payment_hash(), now, self.order_timeout_hours);
+            order.revealed_preimage = Some(preimage);
+
+            sub.status = SubscriptionStatus::PaymentDue;
+            sub.current_order_id = Some(order.id);
+            sub.order_ids.push(order.id);
+            sub.next_billing_at += chrono::Duration::seconds(sub.billing_period_secs as i64);
+            inner.notifications.push(Notification::new(
+                sub.buyer_id,
+                format!(
+                    "Payment due for your \"{}\" subscription ({} shannons)",
+                    sub.product_title, sub.amount_shannons
+                ),
+                Some(order.id),
+                now,
+            ));
+            billing.renewal_orders.push(order.id);
+            inner.order_events.entry(order.id).or_default().push(OrderEvent {
+                kind: OrderEventKind::Created,
+                at: now,
+                detail: Some("Subscription renewal".to_string()),
+            });
+            self.persist_order(&order);
+            self.persist_subscription(sub);
+            inner.orders.insert(order.id, order);
+        }
+
+        billing
+    }
+
+    // Notification operations
+
+    pub async fn list_notifications(&self, user_id: UserId) -> Vec<Notification> {
+        let mut notifications: Vec<Notification> = self
+            .inner
+            .read()
+            .await
+            .notifications
+            .iter()
+            .filter(|n| n.user_id == user_id)
+            .cloned()
+            .collect();
+        notifications.reverse();
+        notifications
+    }
+
+    /// Keep the seller's hold invoice, which their node cancels
+    /// `expiry_secs` from now
+    pub async fn set_order_invoice(&self, id: OrderId, invoice: String, expiry_secs: u64) {
+        let mut inner = self.inner.write().await;
+        let now = self.now_in(&inner);
         if let Some(order) = inner.orders.get_mut(&id) {
             order.invoice_string = Some(invoice);
+            order.invoice_expires_at = Some(now + chrono::Duration::seconds(expiry_secs as i64));
+            self.events.publish(Event::InvoiceCreated {
+                payment_hash: order.payment_hash,
+            });
+            inner.record(id, OrderEventKind::InvoiceSubmitted, now, None);
+        }
+        self.persist_billed(&inner, id);
+    }
+
+    // Persistence
+
+    /// Save a user, category or product through `save`
+    fn persist(
+        &self,
+        kind: &str,
+        id: uuid::Uuid,
+        save: impl FnOnce(&dyn EscrowStore) -> Result<(), StorageError>,
+    ) {
+        if let Some(store) = &self.store {
+            if let Err(e) = save(store.as_ref()) {
+                tracing::warn!(kind, %id, error = %e, "Failed to persist record");
+            }
+        }
+    }
+
+    fn persist_subscription(&self, subscription: &Subscription) {
+        if let Some(store) = &self.store {
+            if let Err(e) = store.save_subscription(subscription) {
+                tracing::warn!(subscription_id = %subscription.id.0, error = %e, "Failed to persist subscription");
+            }
+        }
+    }
+
+    /// Save `order` if it bills for a subscription
+    fn persist_order(&self, order: &Order) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        if order.subscription_id.is_some() {
+            if let Err(e) = store.save_order(order) {
+                tracing::warn!(order_id = %order.id.0, error = %e, "Failed to persist subscription order");
+            }
+        }
+    }
+
+    /// Save order `id` if it bills for a subscription, and the subscription
+    /// too, since paying the order can change its status
+    fn persist_billed(&self, inner: &AppStateInner, id: OrderId) {
+        let Some(order) = inner.orders.get(&id) else {
+            return;
+        };
+        self.persist_order(order);
+        if let Some(sub) = order.subscription_id.and_then(|s| inner.subscriptions.get(&s)) {
+            self.persist_subscription(sub);
+        }
+    }
+}
+
+/// Outcome of a subscription billing run
+#[derive(Debug, Default)]
+pub struct SubscriptionBilling {
+    /// Renewal orders created this run
+    pub renewal_orders: Vec<OrderId>,
+    /// Subscriptions suspended for non-payment
+    pub suspended: Vec<SubscriptionId>,
+}
+
+/// What buyer and seller are told when an alert is raised for their order
+fn deadline_message(alert: &SettlementAlert) -> String {
+    let waiting_on = match alert.status {
+        OrderStatus::Funded => "the seller to ship it",
+        OrderStatus::Shipped => "the buyer to confirm receipt",
+        _ => "the arbiter's decision",
+    };
+    format!(
+        "Order for \"{}\" goes back to the buyer in {} minutes unless it completes first; it waits on {}",
+        alert.product_title,
+        alert.seconds_left / 60,
+        waiting_on
+    )
+}
+
+/// A fresh bus and metrics that count what is published on it
+fn followed_metrics() -> (Arc<Metrics>, EventBus) {
+    let events = EventBus::new();
+    let metrics = Arc::new(Metrics::new());
+    metrics.follow(&events);
+    (metrics, events)
+}
+
+impl AppStateInner {
+    /// `user` with a balance simulated from their orders; the real balance
+    /// comes from the frontend calling the Fiber node directly
+    fn with_balance(&self, user: &User) -> Result<User, MoneyError> {
+        let mut balance: i64 = 0;
+        for order in self.orders.values() {
+            if order.seller_id == user.id && order.status == OrderStatus::Completed {
+                balance = money::credit(balance, order.amount_shannons)?;
+            }
+            if order.buyer_id == user.id {
+                match order.status {
+                    OrderStatus::Funded
+                    | OrderStatus::Shipped
+                    | OrderStatus::Completed
+                    | OrderStatus::Disputed => {
+                        balance = money::debit(balance, order.amount_shannons)?;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(User {
+            balance_shannons: balance,
+            ..user.clone()
+        })
+    }
+
+    /// Add to the order's timeline
+    fn record(
+        &mut self,
+        id: OrderId,
+        kind: OrderEventKind,
+        at: DateTime<Utc>,
+        detail: Option<String>,
+    ) {
+        let event = OrderEvent { kind, at, detail };
+        self.order_events.entry(id).or_default().push(event);
+    }
+
+    fn set_order_status(&mut self, id: OrderId, status: OrderStatus, now: DateTime<Utc>) {
+        let Some(order) = self.orders.get_mut(&id) else {
+            return;
+        };
+        order.status = status;
+        let kind = match status {
+            OrderStatus::WaitingPayment => None,
+            OrderStatus::Funded => Some(OrderEventKind::PaymentHeld),
+            OrderStatus::Shipped => Some(OrderEventKind::Shipped),
+            OrderStatus::Completed => Some(OrderEventKind::Completed),
+            OrderStatus::Disputed => Some(OrderEventKind::Disputed),
+            OrderStatus::Refunded => Some(OrderEventKind::Refunded),
+        };
+
+        // Paying a subscription order restores access for the current period
+        if status == OrderStatus::Funded {
+            if let Some(sub_id) = order.subscription_id {
+                if let Some(sub) = self.subscriptions.get_mut(&sub_id) {
+                    match sub.status {
+                        SubscriptionStatus::PaymentDue => sub.status = SubscriptionStatus::Active,
+                        SubscriptionStatus::Suspended => {
+                            sub.status = SubscriptionStatus::Active;
+                            sub.next_billing_at =
+                                now + chrono::Duration::seconds(sub.billing_period_secs as i64);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        if let Some(kind) = kind {
+            self.record(id, kind, now, None);
         }
     }
 }
@@ -303,3 +1174,9 @@ impl Default for AppState {
         Self::new()
     }
 }
+
+impl AuthState for AppState {
+    fn admin_secret(&self) -> &AdminSecret {
+        &self.admin_token
+    }
+}
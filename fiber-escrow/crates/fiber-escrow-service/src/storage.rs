@@ -0,0 +1,444 @@
+//! Escrow persistence.
+//!
+//! [`EscrowStore`] abstracts where the escrow keeps its subscriptions and
+//! the orders billed for them, so billing schedules survive a restart, along
+//! with the users, categories and products they refer to;
+//! [`SqliteEscrowStore`] is the SQLite implementation. Other orders are not
+//! stored. Records are JSON blobs keyed by ID, so the table layout does not
+//! need to change whenever a model gains a field.
+//!
+//! When the layout does change, it does so through a new migration in
+//! `migrations/`, applied by [`fiber_core::storage`] as the oracle's and
+//! players' are.
+//!
+//! Given a [`Keyring`], the store encrypts orders' preimages before they
+//! reach the database.
+
+use crate::models::{Category, Order, Product, Subscription, User};
+use fiber_core::{Keyring, KeyringError, Preimage};
+use fiber_core::storage::{self, SchemaError};
+use rusqlite::{params, Connection};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::info;
+
+mod embedded {
+    refinery::embed_migrations!("migrations");
+}
+
+/// Table recording which of the escrow's migrations have run, apart from
+/// other stores' so they can share a database file
+const MIGRATION_TABLE: &str = "escrow_schema_history";
+
+/// Columns holding secrets, encrypted when the store has a [`Keyring`]
+const SECRET_COLUMNS: [(&str, &str); 1] = [("escrow_orders", "preimage")];
+
+/// Storage error
+#[derive(Debug, thiserror::Error)]
+pub enum StorageError {
+    #[error("database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("corrupt record: {0}")]
+    Corrupt(String),
+    #[error(transparent)]
+    Schema(#[from] SchemaError),
+    #[error("encrypted record: {0}")]
+    Encryption(#[from] KeyringError),
+    #[error("record is encrypted but no storage key is configured")]
+    Locked,
+}
+
+/// Persistent storage for subscription billing
+pub trait EscrowStore: Send + Sync {
+    /// Load all saved users.
+    fn load_users(&self) -> Result<Vec<User>, StorageError>;
+
+    /// Insert or replace a user.
+    fn save_user(&self, user: &User) -> Result<(), StorageError>;
+
+    /// Load all saved categories.
+    fn load_categories(&self) -> Result<Vec<Category>, StorageError>;
+
+    /// Insert or replace a category.
+    fn save_category(&self, category: &Category) -> Result<(), StorageError>;
+
+    /// Load all saved products.
+    fn load_products(&self) -> Result<Vec<Product>, StorageError>;
+
+    /// Insert or replace a product.
+    fn save_product(&self, product: &Product) -> Result<(), StorageError>;
+
+    /// Load all saved subscriptions.
+    fn load_subscriptions(&self) -> Result<Vec<Subscription>, StorageError>;
+
+    /// Insert or replace a subscription.
+    fn save_subscription(&self, subscription: &Subscription) -> Result<(), StorageError>;
+
+    /// Load all saved subscription orders, with their preimages.
+    fn load_orders(&self) -> Result<Vec<Order>, StorageError>;
+
+    /// Insert or replace a subscription order, with its preimage.
+    fn save_order(&self, order: &Order) -> Result<(), StorageError>;
+}
+
+/// SQLite-backed [`EscrowStore`]
+///
+/// Uses the `escrow_*` tables, so the same database file can be shared with
+/// other stores.
+pub struct SqliteEscrowStore {
+    conn: Mutex<Connection>,
+    keyring: Option<Keyring>,
+}
+
+impl SqliteEscrowStore {
+    /// Open (or create) the database at `path`, applying any schema
+    /// migrations it is missing.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StorageError> {
+        Self::from_connection(Connection::open(path)?, true)
+    }
+
+    /// Open the database at `path` as it is: fails with
+    /// [`SchemaError::Pending`] unless its schema is up to date.
+    pub fn open_without_migrating(path: impl AsRef<Path>) -> Result<Self, StorageError> {
+        Self::from_connection(Connection::open(path)?, false)
+    }
+
+    /// Open a private in-memory database.
+    pub fn open_in_memory() -> Result<Self, StorageError> {
+        Self::from_connection(Connection::open_in_memory()?, true)
+    }
+
+    fn from_connection(mut conn: Connection, migrate: bool) -> Result<Self, StorageError> {
+        let applied =
+            storage::prepare(&mut conn, embedded::migrations::runner(), MIGRATION_TABLE, migrate)?;
+        for migration in applied {
+            info!(%migration, "Applied escrow schema migration");
+        }
+        Ok(Self {
+            conn: Mutex::new(conn),
+            keyring: None,
+        })
+    }
+
+    /// Encrypt preimages with `keyring` from now on, and re-encrypt those
+    /// already stored that aren't under its current key: plaintext ones
+    /// from before encryption was set up, and ones under a rotated-out key.
+    pub fn with_keyring(mut self, keyring: Keyring) -> Result<Self, StorageError> {
+        self.keyring = Some(keyring);
+        let resealed = self.reseal()?;
+        if resealed > 0 {
+            info!(resealed, "Re-encrypted escrow secrets under the current storage key");
+        }
+        Ok(self)
+    }
+
+    /// Bring every secret column under the current storage key
+    fn reseal(&self) -> Result<usize, StorageError> {
+        let Some(keyring) = &self.keyring else {
+            return Ok(0);
+        };
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut resealed = 0;
+        for (table, column) in SECRET_COLUMNS {
+            let rows = tx
+                .prepare(&format!("SELECT rowid, {column} FROM {table} WHERE {column} IS NOT NULL"))?
+                .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+                .collect::<Result<Vec<_>, _>>()?;
+            for (rowid, value) in rows {
+                let value = if keyring.is_current(&value) {
+                    continue;
+                } else if Keyring::is_sealed(&value) {
+                    keyring.rewrap(&value)?
+                } else {
+                    keyring.seal(value.as_bytes())
+                };
+                tx.execute(
+                    &format!("UPDATE {table} SET {column} = ?1 WHERE rowid = ?2"),
+                    params![value, rowid],
+                )?;
+                resealed += 1;
+            }
+        }
+        tx.commit()?;
+        Ok(resealed)
+    }
+
+    /// `value` as it is to be stored in a secret column
+    fn seal(&self, value: String) -> String {
+        match &self.keyring {
+            Some(keyring) => keyring.seal(value.as_bytes()),
+            None => value,
+        }
+    }
+
+    /// A secret column's `value` as it was before [`Self::seal`]
+    fn unseal(&self, value: String) -> Result<String, StorageError> {
+        if !Keyring::is_sealed(&value) {
+            return Ok(value);
+        }
+        let keyring = self.keyring.as_ref().ok_or(StorageError::Locked)?;
+        String::from_utf8(keyring.open(&value)?).map_err(|e| StorageError::Corrupt(e.to_string()))
+    }
+
+    /// Every record in `table`, a table of JSON `data` keyed by ID
+    fn load_all<T: DeserializeOwned>(&self, table: &str) -> Result<Vec<T>, StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!("SELECT data FROM {table}"))?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+
+        let mut records = Vec::new();
+        for row in rows {
+            records.push(serde_json::from_str(&row?)?);
+        }
+        Ok(records)
+    }
+
+    /// Insert or replace `record` in `table`, under `id` in its `key` column
+    fn save(
+        &self,
+        table: &str,
+        key: &str,
+        id: uuid::Uuid,
+        record: &impl Serialize,
+    ) -> Result<(), StorageError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            &format!("INSERT OR REPLACE INTO {table} ({key}, data) VALUES (?1, ?2)"),
+            params![id.to_string(), serde_json::to_string(record)?],
+        )?;
+        Ok(())
+    }
+}
+
+impl EscrowStore for SqliteEscrowStore {
+    fn load_users(&self) -> Result<Vec<User>, StorageError> {
+        self.load_all("escrow_users")
+    }
+
+    fn save_user(&self, user: &User) -> Result<(), StorageError> {
+        self.save("escrow_users", "user_id", user.id.0, user)
+    }
+
+    fn load_categories(&self) -> Result<Vec<Category>, StorageError> {
+        self.load_all("escrow_categories")
+    }
+
+    fn save_category(&self, category: &Category) -> Result<(), StorageError> {
+        self.save("escrow_categories", "category_id", category.id.0, category)
+    }
+
+    fn load_products(&self) -> Result<Vec<Product>, StorageError> {
+        self.load_all("escrow_products")
+    }
+
+    fn save_product(&self, product: &Product) -> Result<(), StorageError> {
+        self.save("escrow_products", "product_id", product.id.0, product)
+    }
+
+    fn load_subscriptions(&self) -> Result<Vec<Subscription>, StorageError> {
+        self.load_all("escrow_subscriptions")
+    }
+
+    fn save_subscription(&self, subscription: &Subscription) -> Result<(), StorageError> {
+        self.save("escrow_subscriptions", "subscription_id", subscription.id.0, subscription)
+    }
+
+    fn load_orders(&self) -> Result<Vec<Order>, StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT data, preimage FROM escrow_orders")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+        })?;
+
+        let mut orders = Vec::new();
+        for row in rows {
+            let (data, preimage) = row?;
+            let mut order: Order = serde_json::from_str(&data)?;
+            // The order's JSON leaves the preimage out, as the API does
+            order.revealed_preimage = match preimage {
+                Some(p) => Some(serde_json::from_str::<Preimage>(&self.unseal(p)?)?),
+                None => None,
+            };
+            orders.push(order);
+        }
+        Ok(orders)
+    }
+
+    fn save_order(&self, order: &Order) -> Result<(), StorageError> {
+        let preimage = order
+            .revealed_preimage
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?
+            .map(|p| self.seal(p));
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO escrow_orders (order_id, data, preimage) VALUES (?1, ?2, ?3)",
+            params![order.id.0.to_string(), serde_json::to_string(order)?, preimage],
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{OrderStatus, Product, SubscriptionStatus, UserId};
+    use crate::state::AppState;
+    use chrono::Utc;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_subscription_and_order_roundtrip() {
+        let store = SqliteEscrowStore::open_in_memory().unwrap();
+        let now = Utc::now();
+        let mut product = Product::new(UserId::new(), "Newsletter".into(), String::new(), 200, now);
+        product.billing_period_secs = Some(3600);
+        let mut subscription = Subscription::new(&product, UserId::new(), now).unwrap();
+        let preimage = Preimage::random();
+        let mut order = Order::renewal(&subscription, preimage.payment_hash(), now, 24);
+        order.revealed_preimage = Some(preimage.clone());
+        subscription.current_order_id = Some(order.id);
+        store.save_order(&order).unwrap();
+        store.save_subscription(&subscription).unwrap();
+
+        subscription.status = SubscriptionStatus::Paused;
+        store.save_subscription(&subscription).unwrap();
+        let loaded = store.load_subscriptions().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].status, SubscriptionStatus::Paused);
+        assert_eq!(loaded[0].next_billing_at, subscription.next_billing_at);
+        assert_eq!(loaded[0].current_order_id, Some(order.id));
+
+        let orders = store.load_orders().unwrap();
+        assert_eq!(orders[0].id, order.id);
+        let restored = orders[0].revealed_preimage.as_ref().map(Preimage::payment_hash);
+        assert_eq!(restored, Some(preimage.payment_hash()));
+    }
+
+    #[test]
+    fn test_preimages_sealed_with_keyring() {
+        let store = SqliteEscrowStore::open_in_memory().unwrap();
+        let now = Utc::now();
+        let mut product = Product::new(UserId::new(), "Newsletter".into(), String::new(), 200, now);
+        product.billing_period_secs = Some(3600);
+        let subscription = Subscription::new(&product, UserId::new(), now).unwrap();
+        let preimage = Preimage::random();
+        let mut order = Order::renewal(&subscription, preimage.payment_hash(), now, 24);
+        order.revealed_preimage = Some(preimage.clone());
+        store.save_order(&order).unwrap();
+        let stored = |store: &SqliteEscrowStore| -> String {
+            store
+                .conn
+                .lock()
+                .unwrap()
+                .query_row("SELECT preimage FROM escrow_orders", [], |row| row.get(0))
+                .unwrap()
+        };
+        assert!(!Keyring::is_sealed(&stored(&store)));
+
+        // A preimage saved before encryption was set up is sealed on first use
+        let old = Keyring::new([1u8; 32]);
+        let store = store.with_keyring(old.clone()).unwrap();
+        assert!(old.is_current(&stored(&store)));
+        let restored = store.load_orders().unwrap()[0].revealed_preimage.clone();
+        assert_eq!(restored.map(|p| p.payment_hash()), Some(preimage.payment_hash()));
+
+        // Rotating rewraps it under the new key
+        let store = store
+            .with_keyring(Keyring::new([2u8; 32]).with_old_key([1u8; 32]))
+            .unwrap();
+        let store = store.with_keyring(Keyring::new([2u8; 32])).unwrap();
+        assert!(store.load_orders().unwrap()[0].revealed_preimage.is_some());
+
+        // Without a key the sealed preimage can't be read
+        let locked = SqliteEscrowStore {
+            conn: store.conn,
+            keyring: None,
+        };
+        assert!(matches!(locked.load_orders(), Err(StorageError::Locked)));
+    }
+
+    #[test]
+    fn test_unmigrated_database_refused_without_auto_migrate() {
+        let conn = Connection::open_in_memory().unwrap();
+        let result = SqliteEscrowStore::from_connection(conn, false);
+        assert!(matches!(result, Err(StorageError::Schema(SchemaError::Pending(2)))));
+
+        let store = SqliteEscrowStore::from_connection(Connection::open_in_memory().unwrap(), true);
+        let conn = store.unwrap().conn.into_inner().unwrap();
+        assert!(SqliteEscrowStore::from_connection(conn, false).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_billing_schedule_survives_restart() {
+        let store = Arc::new(SqliteEscrowStore::open_in_memory().unwrap());
+        let state = AppState::new().with_store(store.clone()).unwrap();
+        let seller = state.register_user("seller".into()).await;
+        let buyer = state.register_user("buyer".into()).await;
+        let product = state
+            .create_product(seller.id, "Newsletter".into(), String::new(), 200, Some(3600), None)
+            .await;
+        let (sub, first) = state
+            .create_subscription(&product, buyer.id, Preimage::random())
+            .await
+            .unwrap();
+        state.update_order_status(first.id, OrderStatus::Funded).await;
+        state.advance_time(3600).await;
+        let billing = state.process_subscription_billing().await;
+        assert_eq!(billing.renewal_orders.len(), 1);
+        let due = state.get_subscription(sub.id).await.unwrap();
+
+        // A restarted escrow picks up the schedule and the unpaid renewal
+        let restarted = AppState::new().with_store(store).unwrap();
+        let restored = restarted.get_subscription(sub.id).await.unwrap();
+        assert_eq!(restored.status, SubscriptionStatus::PaymentDue);
+        assert_eq!(restored.next_billing_at, due.next_billing_at);
+        let renewal = restarted.get_order(billing.renewal_orders[0]).await.unwrap();
+        assert!(renewal.revealed_preimage.is_some());
+
+        restarted.advance_time(2 * 3600).await;
+        let billing = restarted.process_subscription_billing().await;
+        assert_eq!(billing.suspended, [sub.id]);
+    }
+
+    #[tokio::test]
+    async fn test_restored_subscription_still_resolves() {
+        let store = Arc::new(SqliteEscrowStore::open_in_memory().unwrap());
+        let state = AppState::new().with_store(store.clone()).unwrap();
+        let seller = state.register_user("seller".into()).await;
+        let buyer = state.register_user("buyer".into()).await;
+        let category = state.create_category("News".into(), None, None).await.unwrap();
+        let product = state
+            .create_product(
+                seller.id,
+                "Newsletter".into(),
+                String::new(),
+                200,
+                Some(3600),
+                Some(category.id),
+            )
+            .await;
+        let (sub, _) = state
+            .create_subscription(&product, buyer.id, Preimage::random())
+            .await
+            .unwrap();
+
+        // The buyer, seller and product the subscription points at come
+        // back with it, so the seed isn't needed to fill them in
+        let restarted = AppState::new().with_store(store).unwrap();
+        assert!(!restarted.is_empty().await);
+        let restored = restarted.get_subscription(sub.id).await.unwrap();
+        let buyer = restarted.get_user(restored.buyer_id).await.unwrap().unwrap();
+        assert_eq!(buyer.username, "buyer");
+        let product = restarted.get_product(restored.product_id).await.unwrap();
+        assert_eq!(product.seller_id, seller.id);
+        assert!(restarted.get_user(product.seller_id).await.unwrap().is_some());
+        assert_eq!(restarted.get_category(category.id).await.unwrap().slug, "news");
+    }
+}
@@ -0,0 +1,65 @@
+//! Alerts for held orders nearing their hold invoice's expiry.
+//!
+//! Run with: cargo test --test alerts
+
+use fiber_core::TestClock;
+use fiber_escrow_service::models::{AlertLevel, OrderStatus};
+use fiber_escrow_service::AppState;
+use fiber_test_fixtures::escrow::Marketplace;
+use std::time::Duration;
+
+const HOUR: u64 = 3600;
+
+#[tokio::test]
+async fn test_alerts_escalate_as_the_invoice_expiry_nears() {
+    let clock = TestClock::new();
+    let state = AppState::new()
+        .with_clock(clock.shared())
+        .with_deadline_alerts(6, 1);
+    let market = Marketplace::with_state(state).await;
+    let state = &market.state;
+    let (funded, _) = market.order().await;
+    let (completed, _) = market.order().await;
+    for order in [&funded, &completed] {
+        state
+            .set_order_invoice(order.id, "invoice".to_string(), 12 * HOUR)
+            .await;
+        state.update_order_status(order.id, OrderStatus::Funded).await;
+    }
+    state.update_order_status(completed.id, OrderStatus::Completed).await;
+
+    // Half a day left: nothing to say yet
+    assert!(state.raise_deadline_alerts().await.is_empty());
+
+    clock.advance(Duration::from_secs(7 * HOUR));
+    let alerts = state.raise_deadline_alerts().await;
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0].order_id, funded.id);
+    assert_eq!(alerts[0].level, AlertLevel::Warning);
+    assert_eq!(alerts[0].seconds_left, 5 * HOUR as i64);
+    // Raised once per level
+    assert!(state.raise_deadline_alerts().await.is_empty());
+
+    clock.advance(Duration::from_secs(4 * HOUR + 1));
+    let alerts = state.raise_deadline_alerts().await;
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0].level, AlertLevel::Critical);
+
+    // Flagged for operators, and both parties told each time
+    let flagged = state.list_deadline_alerts().await;
+    assert_eq!(flagged.len(), 1);
+    assert_eq!(flagged[0].level, AlertLevel::Critical);
+    assert_eq!(state.get_order(funded.id).await.unwrap().deadline_alert, Some(AlertLevel::Critical));
+    for user in [&market.buyer, &market.seller] {
+        let notifications = state.list_notifications(user.id).await;
+        assert_eq!(notifications.len(), 2);
+        assert!(notifications[0].message.contains("the seller to ship it"));
+    }
+    let metrics = state.metrics().render();
+    assert!(metrics.contains(r#"fiber_settlement_alerts_total{level="warning"} 1"#));
+    assert!(metrics.contains(r#"fiber_settlement_alerts_total{level="critical"} 1"#));
+
+    // Once shipped and confirmed the order no longer needs anyone
+    state.update_order_status(funded.id, OrderStatus::Completed).await;
+    assert!(state.list_deadline_alerts().await.is_empty());
+}
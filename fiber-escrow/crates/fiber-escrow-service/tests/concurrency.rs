@@ -0,0 +1,177 @@
+//! Racing requests against the same order.
+//!
+//! Each test fires conflicting transitions at one order from many threads at
+//! once and checks that exactly one of them took effect.
+//!
+//! Run with: cargo test --test concurrency
+
+use fiber_escrow_service::models::{Order, OrderStatus, UserId};
+use fiber_test_fixtures::escrow::{EscrowServer, Marketplace};
+use serde_json::{json, Value};
+use std::future::Future;
+use std::sync::{Arc, Barrier};
+use std::thread;
+
+/// Requests fired at each order
+const RACERS: usize = 16;
+/// Orders raced per test, to give the scheduler more chances to interleave
+const ROUNDS: usize = 10;
+
+/// One racing request: path, caller and body
+struct Request {
+    path: String,
+    user: UserId,
+    body: Value,
+}
+
+/// Send all `requests` at the same moment, one thread each, and return
+/// whether each one succeeded, in order
+fn race(url: &str, requests: Vec<Request>) -> Vec<(bool, Value)> {
+    let client = reqwest::blocking::Client::new();
+    let start = Arc::new(Barrier::new(requests.len()));
+    let threads: Vec<_> = requests
+        .into_iter()
+        .map(|req| {
+            let url = format!("{}{}", url, req.path);
+            let (client, start) = (client.clone(), start.clone());
+            thread::spawn(move || {
+                start.wait();
+                let resp = client
+                    .post(url)
+                    .header("X-User-Id", req.user.0.to_string())
+                    .json(&req.body)
+                    .send()
+                    .expect("Request failed");
+                let ok = resp.status().is_success();
+                (ok, resp.json().unwrap_or(Value::Null))
+            })
+        })
+        .collect();
+    threads.into_iter().map(|t| t.join().unwrap()).collect()
+}
+
+/// Run a state call from these blocking tests; the service has its own
+/// runtime
+fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+/// A new order moved straight to `status`, with the buyer's preimage held
+/// in escrow as the create endpoint would
+fn order_in(market: &Marketplace, status: OrderStatus) -> Order {
+    block_on(async {
+        let (order, preimage) = market.order().await;
+        market.state.set_revealed_preimage(order.id, preimage).await;
+        market.state.update_order_status(order.id, status).await;
+        order
+    })
+}
+
+#[test]
+fn test_concurrent_confirms_complete_once() {
+    let market = block_on(Marketplace::new());
+    let service = EscrowServer::start_with(market.state.clone());
+
+    for _ in 0..ROUNDS {
+        let order = order_in(&market, OrderStatus::Shipped);
+        let confirms = (0..RACERS)
+            .map(|_| Request {
+                path: format!("/api/orders/{}/confirm", order.id.0),
+                user: market.buyer.id,
+                body: json!({}),
+            })
+            .collect();
+        let outcomes = race(&service.url(), confirms);
+
+        let won = outcomes.iter().filter(|(ok, _)| *ok).count();
+        assert_eq!(won, 1, "{} confirms succeeded", won);
+        assert_eq!(
+            block_on(market.state.get_order(order.id)).unwrap().status,
+            OrderStatus::Completed
+        );
+    }
+}
+
+#[test]
+fn test_confirm_races_dispute() {
+    let market = block_on(Marketplace::new());
+    let service = EscrowServer::start_with(market.state.clone());
+
+    for _ in 0..ROUNDS {
+        let order = order_in(&market, OrderStatus::Shipped);
+        // Even racers confirm receipt, odd ones dispute
+        let requests = (0..RACERS)
+            .map(|i| {
+                let action = if i % 2 == 0 { "confirm" } else { "dispute" };
+                Request {
+                    path: format!("/api/orders/{}/{}", order.id.0, action),
+                    user: market.buyer.id,
+                    body: json!({ "reason": "never arrived" }),
+                }
+            })
+            .collect();
+        let outcomes = race(&service.url(), requests);
+
+        let winners: Vec<_> = outcomes
+            .iter()
+            .enumerate()
+            .filter(|(_, (ok, _))| *ok)
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(winners.len(), 1, "{} transitions succeeded", winners.len());
+        let order = block_on(market.state.get_order(order.id)).unwrap();
+        if winners[0] % 2 == 0 {
+            assert_eq!(order.status, OrderStatus::Completed);
+            assert!(order.dispute.is_none());
+        } else {
+            assert_eq!(order.status, OrderStatus::Disputed);
+        }
+    }
+}
+
+#[test]
+fn test_conflicting_resolutions_apply_once() {
+    let market = block_on(Marketplace::new());
+    let service = EscrowServer::start_with(market.state.clone());
+
+    for _ in 0..ROUNDS {
+        let order = order_in(&market, OrderStatus::Shipped);
+        assert!(block_on(
+            market.state.add_dispute(order.id, "damaged".to_string())
+        ));
+        // Even racers award the funds to the seller, odd ones refund the buyer
+        let requests = (0..RACERS)
+            .map(|i| Request {
+                path: format!("/api/arbiter/disputes/{}/resolve", order.id.0),
+                user: market.arbiter.id,
+                body: json!({
+                    "resolution": if i % 2 == 0 { "seller" } else { "buyer" },
+                    "reason": "other",
+                    "note": "racer",
+                }),
+            })
+            .collect();
+        let outcomes = race(&service.url(), requests);
+
+        let winners: Vec<_> = outcomes.iter().filter(|(ok, _)| *ok).collect();
+        assert_eq!(winners.len(), 1, "{} resolutions succeeded", winners.len());
+        let body = &winners[0].1;
+        let status = block_on(market.state.get_order(order.id)).unwrap().status;
+        // The seller can only ever be handed the preimage of an order that
+        // ends up completed, never of one the buyer is refunded for
+        match body["resolution"].as_str() {
+            Some("seller") => {
+                assert_eq!(status, OrderStatus::Completed);
+                assert!(body["preimage"].is_string());
+            }
+            Some("buyer") => {
+                assert_eq!(status, OrderStatus::Refunded);
+                assert!(body["preimage"].is_null());
+            }
+            other => panic!("Unexpected resolution {:?}", other),
+        }
+    }
+}
@@ -2,55 +2,10 @@
 //!
 //! These tests verify the full HTTP interaction for escrow trading.
 //!
-//! Run with: cargo test --test e2e_escrow_flow -- --nocapture --test-threads=1
+//! Run with: cargo test --test e2e_escrow_flow -- --nocapture
 
-use std::process::{Child, Command};
-use std::time::Duration;
-
-/// Helper to start the escrow service process
-struct ServiceProcess {
-    child: Child,
-    name: String,
-}
-
-impl ServiceProcess {
-    fn start(crate_dir: &str, port: u16) -> Self {
-        let mut cmd = Command::new("cargo");
-        cmd.args(["run", "-p", "fiber-escrow-service"])
-            .current_dir(crate_dir)
-            .env("PORT", port.to_string())
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null());
-
-        let child = cmd.spawn().expect("Failed to start escrow service");
-
-        Self {
-            child,
-            name: format!("fiber-escrow-service:{}", port),
-        }
-    }
-
-    fn wait_for_ready(&self, url: &str, timeout: Duration) -> bool {
-        let client = reqwest::blocking::Client::new();
-        let start = std::time::Instant::now();
-
-        while start.elapsed() < timeout {
-            if client.get(url).send().is_ok() {
-                return true;
-            }
-            std::thread::sleep(Duration::from_millis(100));
-        }
-        false
-    }
-}
-
-impl Drop for ServiceProcess {
-    fn drop(&mut self) {
-        let _ = self.child.kill();
-        let _ = self.child.wait();
-        println!("Stopped {}", self.name);
-    }
-}
+use fiber_escrow_service::{models::OrderStatus, seed::Seed, AppState};
+use fiber_test_fixtures::escrow::{EscrowServer, Marketplace};
 
 /// Helper struct to manage API calls with user context
 struct EscrowClient {
@@ -88,6 +43,14 @@ impl EscrowClient {
         }
         req
     }
+
+    fn patch(&self, path: &str) -> reqwest::blocking::RequestBuilder {
+        let mut req = self.client.patch(format!("{}{}", self.base_url, path));
+        if let Some(ref user_id) = self.user_id {
+            req = req.header("X-User-Id", user_id);
+        }
+        req
+    }
 }
 
 /// Get user ID by username from the users list
@@ -123,19 +86,8 @@ fn generate_preimage_and_hash() -> (String, String) {
 /// Test complete happy path: seller creates product, buyer purchases, seller ships, buyer confirms
 #[test]
 fn test_escrow_happy_path() {
-    // CARGO_MANIFEST_DIR is fiber-escrow-service, go up to fiber-escrow workspace
-    let crate_dir = env!("CARGO_MANIFEST_DIR");
-    let workspace_dir = format!("{}/../../", crate_dir);
-
-    const PORT: u16 = 15000;
-    let base_url = format!("http://localhost:{}", PORT);
-
-    // Start escrow service
-    let service = ServiceProcess::start(&workspace_dir, PORT);
-    assert!(
-        service.wait_for_ready(&format!("{}/api/health", base_url), Duration::from_secs(30)),
-        "Escrow service failed to start"
-    );
+    let service = EscrowServer::start();
+    let base_url = service.url();
 
     let client = EscrowClient::new(&base_url);
 
@@ -282,24 +234,42 @@ fn test_escrow_happy_path() {
         seller_preimage
     );
 
+    // 9. Both parties see the same timeline, in order; nobody else does
+    let timeline_path = format!("/api/orders/{}/timeline", order_id);
+    for party in [&buyer_client, &seller_client] {
+        let timeline: serde_json::Value = party
+            .get(&timeline_path)
+            .send()
+            .unwrap()
+            .json()
+            .unwrap();
+        let kinds: Vec<&str> = timeline["events"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["kind"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            kinds,
+            ["created", "invoice_submitted", "payment_held", "shipped", "completed"]
+        );
+    }
+    let arbiter_id = get_user_id_by_username(&client, "arbiter");
+    let resp = client
+        .get(&timeline_path)
+        .header("X-User-Id", &arbiter_id)
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+
     println!("Test passed: Happy path escrow flow completed successfully");
 }
 
 /// Test dispute resolution flow: buyer disputes, arbiter resolves to buyer (refund)
 #[test]
 fn test_escrow_dispute_refund_to_buyer() {
-    let crate_dir = env!("CARGO_MANIFEST_DIR");
-    let workspace_dir = format!("{}/../../", crate_dir);
-
-    const PORT: u16 = 15001;
-    let base_url = format!("http://localhost:{}", PORT);
-
-    // Start escrow service
-    let service = ServiceProcess::start(&workspace_dir, PORT);
-    assert!(
-        service.wait_for_ready(&format!("{}/api/health", base_url), Duration::from_secs(30)),
-        "Escrow service failed to start"
-    );
+    let service = EscrowServer::start();
+    let base_url = service.url();
 
     let client = EscrowClient::new(&base_url);
 
@@ -401,7 +371,10 @@ fn test_escrow_dispute_refund_to_buyer() {
     // 7. Arbiter resolves in favor of buyer
     let resolve_resp: serde_json::Value = client
         .post(&format!("/api/arbiter/disputes/{}/resolve", order_id))
-        .json(&serde_json::json!({ "resolution": "buyer" }))
+        .json(&serde_json::json!({
+            "resolution": "buyer",
+            "reason": "seller_unresponsive"
+        }))
         .send()
         .unwrap()
         .json()
@@ -425,18 +398,8 @@ fn test_escrow_dispute_refund_to_buyer() {
 /// Test dispute resolution to seller: buyer reveals preimage to arbiter
 #[test]
 fn test_escrow_dispute_resolved_to_seller() {
-    let crate_dir = env!("CARGO_MANIFEST_DIR");
-    let workspace_dir = format!("{}/../../", crate_dir);
-
-    const PORT: u16 = 15003;
-    let base_url = format!("http://localhost:{}", PORT);
-
-    // Start escrow service
-    let service = ServiceProcess::start(&workspace_dir, PORT);
-    assert!(
-        service.wait_for_ready(&format!("{}/api/health", base_url), Duration::from_secs(30)),
-        "Escrow service failed to start"
-    );
+    let service = EscrowServer::start();
+    let base_url = service.url();
 
     let client = EscrowClient::new(&base_url);
 
@@ -522,13 +485,17 @@ fn test_escrow_dispute_resolved_to_seller() {
         confirm_resp.get("error").is_some(),
         "Should fail to confirm disputed order"
     );
+    assert_eq!(confirm_resp["code"], "invalid_state");
     println!("Cannot confirm disputed order (expected)");
 
     // 8. Arbiter resolves to seller
     // In escrow-holds-preimage model, preimage is always available for settlement
     let resolve_resp: serde_json::Value = client
         .post(&format!("/api/arbiter/disputes/{}/resolve", order_id))
-        .json(&serde_json::json!({ "resolution": "seller" }))
+        .json(&serde_json::json!({
+            "resolution": "seller",
+            "reason": "delivered_as_described"
+        }))
         .send()
         .unwrap()
         .json()
@@ -548,6 +515,12 @@ fn test_escrow_dispute_resolved_to_seller() {
         resolved_preimage
     );
 
+    // 9. The settled order and closed dispute show up in the metrics
+    let metrics = client.get("/metrics").send().unwrap().text().unwrap();
+    assert!(metrics.contains("fiber_invoices_created_total 1"));
+    assert!(metrics.contains("fiber_orders_settled_total 1"));
+    assert!(metrics.contains("fiber_disputes_open 0"));
+
     println!("Test passed: Dispute resolved to seller with preimage from escrow");
 }
 
@@ -555,18 +528,8 @@ fn test_escrow_dispute_resolved_to_seller() {
 /// means escrow auto-settles using stored preimage. This favors the seller who shipped.
 #[test]
 fn test_escrow_order_timeout() {
-    let crate_dir = env!("CARGO_MANIFEST_DIR");
-    let workspace_dir = format!("{}/../../", crate_dir);
-
-    const PORT: u16 = 15002;
-    let base_url = format!("http://localhost:{}", PORT);
-
-    // Start escrow service
-    let service = ServiceProcess::start(&workspace_dir, PORT);
-    assert!(
-        service.wait_for_ready(&format!("{}/api/health", base_url), Duration::from_secs(30)),
-        "Escrow service failed to start"
-    );
+    let service = EscrowServer::start();
+    let base_url = service.url();
 
     let client = EscrowClient::new(&base_url);
 
@@ -696,3 +659,871 @@ fn test_escrow_order_timeout() {
     // 2. On timeout (shipped but not confirmed), escrow auto-settles the invoice
     // 3. Seller gets paid, buyer gets the shipped goods
 }
+
+/// Test subscription billing: renewal orders are created each period and
+/// the subscription is suspended when a renewal is left unpaid.
+#[test]
+fn test_escrow_subscription_renewal_and_suspension() {
+    let service = EscrowServer::start();
+    let base_url = service.url();
+
+    let client = EscrowClient::new(&base_url);
+
+    let seller_id = get_user_id_by_username(&client, "seller");
+    let buyer_id = get_user_id_by_username(&client, "buyer");
+
+    let seller_client = EscrowClient::new(&base_url).with_user(&seller_id);
+    let buyer_client = EscrowClient::new(&base_url).with_user(&buyer_id);
+
+    // 1. Seller creates a daily subscription product
+    let create_product_resp: serde_json::Value = seller_client
+        .post("/api/products")
+        .json(&serde_json::json!({
+            "title": "Daily Digest",
+            "description": "Billed every day",
+            "price_shannons": 100,
+            "billing_period_secs": 86400
+        }))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    let product_id = create_product_resp["product_id"].as_str().unwrap();
+
+    // One-off orders are rejected for subscription products
+    let (preimage, _) = generate_preimage_and_hash();
+    let one_off = buyer_client
+        .post("/api/orders")
+        .json(&serde_json::json!({ "product_id": product_id, "preimage": preimage }))
+        .send()
+        .unwrap();
+    assert_eq!(one_off.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    // 2. Buyer subscribes, first order is created right away
+    let (preimage, payment_hash) = generate_preimage_and_hash();
+    let subscribe_resp: serde_json::Value = buyer_client
+        .post("/api/subscriptions")
+        .json(&serde_json::json!({ "product_id": product_id, "preimage": preimage }))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    let subscription_id = subscribe_resp["subscription_id"].as_str().unwrap();
+    let first_order_id = subscribe_resp["order_id"].as_str().unwrap();
+    assert_eq!(subscribe_resp["payment_hash"].as_str(), Some(payment_hash.as_str()));
+
+    // 3. First period is paid through the regular order flow
+    seller_client
+        .post(&format!("/api/orders/{}/invoice", first_order_id))
+        .json(&serde_json::json!({ "invoice": format!("test_invoice_{}", payment_hash) }))
+        .send()
+        .unwrap();
+    buyer_client
+        .post(&format!("/api/orders/{}/pay", first_order_id))
+        .send()
+        .unwrap();
+
+    let sub: serde_json::Value = buyer_client
+        .get(&format!("/api/subscriptions/{}", subscription_id))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(sub["status"].as_str(), Some("active"));
+    assert_eq!(sub["has_access"].as_bool(), Some(true));
+
+    // 4. Next period: a renewal order is created and the buyer is notified
+    let tick_resp: serde_json::Value = client
+        .post("/api/system/tick")
+        .json(&serde_json::json!({ "seconds": 86400 }))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    let renewals = tick_resp["renewal_orders"].as_array().unwrap();
+    assert_eq!(renewals.len(), 1);
+    let renewal_order_id = renewals[0].as_str().unwrap();
+
+    let renewal: serde_json::Value = buyer_client
+        .get(&format!("/api/orders/{}", renewal_order_id))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(renewal["status"].as_str(), Some("waiting_payment"));
+    assert_eq!(renewal["subscription_id"].as_str(), Some(subscription_id));
+
+    let notifications: serde_json::Value = buyer_client
+        .get("/api/notifications")
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(
+        notifications["notifications"][0]["order_id"].as_str(),
+        Some(renewal_order_id)
+    );
+
+    // 5. Renewal stays unpaid through the following period: access is suspended
+    let tick_resp: serde_json::Value = client
+        .post("/api/system/tick")
+        .json(&serde_json::json!({ "seconds": 86400 }))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(
+        tick_resp["suspended_subscriptions"][0].as_str(),
+        Some(subscription_id)
+    );
+    assert!(tick_resp["renewal_orders"].as_array().unwrap().is_empty());
+
+    let sub: serde_json::Value = buyer_client
+        .get(&format!("/api/subscriptions/{}", subscription_id))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(sub["status"].as_str(), Some("suspended"));
+    assert_eq!(sub["has_access"].as_bool(), Some(false));
+
+    // 6. Paying the outstanding renewal restores access
+    seller_client
+        .post(&format!("/api/orders/{}/invoice", renewal_order_id))
+        .json(&serde_json::json!({ "invoice": "test_invoice_renewal" }))
+        .send()
+        .unwrap();
+    buyer_client
+        .post(&format!("/api/orders/{}/pay", renewal_order_id))
+        .send()
+        .unwrap();
+    let sub: serde_json::Value = buyer_client
+        .get(&format!("/api/subscriptions/{}", subscription_id))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(sub["status"].as_str(), Some("active"));
+
+    // 7. Pause stops billing, cancel is terminal
+    let paused: serde_json::Value = buyer_client
+        .post(&format!("/api/subscriptions/{}/pause", subscription_id))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(paused["status"].as_str(), Some("paused"));
+
+    let tick_resp: serde_json::Value = client
+        .post("/api/system/tick")
+        .json(&serde_json::json!({ "seconds": 3 * 86400 }))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert!(tick_resp["renewal_orders"].as_array().unwrap().is_empty());
+
+    let cancelled: serde_json::Value = seller_client
+        .post(&format!("/api/subscriptions/{}/cancel", subscription_id))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(cancelled["status"].as_str(), Some("cancelled"));
+
+    let resume = buyer_client
+        .post(&format!("/api/subscriptions/{}/resume", subscription_id))
+        .send()
+        .unwrap();
+    assert_eq!(resume.status(), reqwest::StatusCode::CONFLICT);
+}
+
+/// Test category taxonomy: operator creates categories, products are filtered
+/// by category including subcategories.
+#[test]
+fn test_escrow_product_categories() {
+    let service = EscrowServer::start();
+    let base_url = service.url();
+
+    let client = EscrowClient::new(&base_url);
+    let seller_id = get_user_id_by_username(&client, "seller");
+    let seller_client = EscrowClient::new(&base_url).with_user(&seller_id);
+
+    // 1. Operator creates a category with a subcategory
+    let hardware: serde_json::Value = client
+        .post("/api/admin/categories")
+        .json(&serde_json::json!({ "name": "Hardware" }))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(hardware["slug"].as_str(), Some("hardware"));
+    let hardware_id = hardware["category_id"].as_str().unwrap();
+
+    let keyboards: serde_json::Value = client
+        .post("/api/admin/categories")
+        .json(&serde_json::json!({ "name": "Mechanical Keyboards", "parent_id": hardware_id }))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    let keyboards_id = keyboards["category_id"].as_str().unwrap();
+
+    // Duplicate slugs are rejected
+    let duplicate = client
+        .post("/api/admin/categories")
+        .json(&serde_json::json!({ "name": "hardware" }))
+        .send()
+        .unwrap();
+    assert_eq!(duplicate.status(), reqwest::StatusCode::CONFLICT);
+
+    // 2. Seller lists a product in the subcategory; unknown categories are rejected
+    let product: serde_json::Value = seller_client
+        .post("/api/products")
+        .json(&serde_json::json!({
+            "title": "Split Keyboard",
+            "description": "Ergonomic",
+            "price_shannons": 3000,
+            "category_id": keyboards_id
+        }))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    let product_id = product["product_id"].as_str().unwrap();
+
+    let bad = seller_client
+        .post("/api/products")
+        .json(&serde_json::json!({
+            "title": "Nowhere",
+            "description": "Bad category",
+            "price_shannons": 1,
+            "category_id": "00000000-0000-0000-0000-000000000000"
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(bad.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    // 3. Filtering by the parent slug includes subcategory products only
+    let products: serde_json::Value = client
+        .get("/api/products?category=hardware")
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    let products = products["items"].as_array().unwrap();
+    assert_eq!(products.len(), 1);
+    assert_eq!(products[0]["id"].as_str(), Some(product_id));
+
+    let missing = client.get("/api/products?category=nope").send().unwrap();
+    assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+
+    // 4. Tree and detail endpoints
+    let tree: serde_json::Value = client.get("/api/categories").send().unwrap().json().unwrap();
+    let hardware_node = tree["categories"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|c| c["slug"].as_str() == Some("hardware"))
+        .expect("hardware should be a root category");
+    assert_eq!(hardware_node["product_count"].as_u64(), Some(1));
+    assert_eq!(
+        hardware_node["children"][0]["slug"].as_str(),
+        Some("mechanical-keyboards")
+    );
+
+    let detail: serde_json::Value = client
+        .get("/api/categories/mechanical-keyboards")
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    let path: Vec<&str> = detail["path"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|c| c["slug"].as_str().unwrap())
+        .collect();
+    assert_eq!(path, vec!["hardware", "mechanical-keyboards"]);
+}
+
+#[test]
+fn test_escrow_product_drafts_and_publishing() {
+    let service = EscrowServer::start();
+    let base_url = service.url();
+
+    let client = EscrowClient::new(&base_url);
+    let seller_id = get_user_id_by_username(&client, "seller");
+    let buyer_id = get_user_id_by_username(&client, "buyer");
+    let seller_client = EscrowClient::new(&base_url).with_user(&seller_id);
+    let buyer_client = EscrowClient::new(&base_url).with_user(&buyer_id);
+    let listed = |product_id: &str| {
+        let products: serde_json::Value =
+            client.get("/api/products").send().unwrap().json().unwrap();
+        products["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|p| p["id"].as_str() == Some(product_id))
+    };
+
+    // 1. Seller prepares a draft: only they can see it, and buyers can't order it
+    let product: serde_json::Value = seller_client
+        .post("/api/products")
+        .json(&serde_json::json!({
+            "title": "Prototype",
+            "description": "Not ready yet",
+            "price_shannons": 1000,
+            "draft": true
+        }))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(product["status"], "draft");
+    let product_id = product["product_id"].as_str().unwrap();
+    let product_path = format!("/api/products/{}", product_id);
+
+    assert!(!listed(product_id));
+    let hidden = buyer_client.get(&product_path).send().unwrap();
+    assert_eq!(hidden.status(), reqwest::StatusCode::NOT_FOUND);
+    let mine: serde_json::Value = seller_client
+        .get("/api/products/mine?status=draft")
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(mine["items"][0]["id"].as_str(), Some(product_id));
+
+    let (preimage, _) = generate_preimage_and_hash();
+    let order = buyer_client
+        .post("/api/orders")
+        .json(&serde_json::json!({ "product_id": product_id, "preimage": preimage }))
+        .send()
+        .unwrap();
+    assert_eq!(order.status(), reqwest::StatusCode::NOT_FOUND);
+
+    // 2. Only the seller may edit it
+    let edit = serde_json::json!({ "title": "Widget v2", "price_shannons": 1500 });
+    let resp = buyer_client.patch(&product_path).json(&edit).send().unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+    let edited: serde_json::Value = seller_client
+        .patch(&product_path)
+        .json(&edit)
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(edited["title"], "Widget v2");
+    assert_eq!(edited["price_shannons"], 1500);
+    assert_eq!(edited["description"], "Not ready yet");
+
+    // 3. Publishing lists it; published products can't be edited
+    let resp = buyer_client
+        .post(&format!("{}/publish", product_path))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+    let resp = seller_client
+        .post(&format!("{}/publish", product_path))
+        .send()
+        .unwrap();
+    assert!(resp.status().is_success());
+    assert!(listed(product_id));
+    let resp = seller_client.patch(&product_path).json(&edit).send().unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::CONFLICT);
+
+    // 4. Unpublishing hides it again, and archiving takes it off the market
+    let resp = seller_client
+        .post(&format!("{}/unpublish", product_path))
+        .send()
+        .unwrap();
+    assert!(resp.status().is_success());
+    assert!(!listed(product_id));
+
+    let resp = seller_client
+        .post(&format!("{}/archive", product_path))
+        .send()
+        .unwrap();
+    assert!(resp.status().is_success());
+    let resp = seller_client
+        .post(&format!("{}/publish", product_path))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::CONFLICT);
+    let archived: serde_json::Value = seller_client
+        .get(&product_path)
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(archived["status"], "archived");
+}
+
+#[test]
+fn test_escrow_rejects_invalid_fields() {
+    let service = EscrowServer::start();
+    let base_url = service.url();
+
+    let client = EscrowClient::new(&base_url);
+    let seller_id = get_user_id_by_username(&client, "seller");
+    let seller_client = EscrowClient::new(&base_url).with_user(&seller_id);
+
+    // Every broken field is reported at once
+    let resp = seller_client
+        .post("/api/products")
+        .json(&serde_json::json!({
+            "title": " ",
+            "description": "x".repeat(10_000),
+            "price_shannons": 0
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    let body: serde_json::Value = resp.json().unwrap();
+    assert_eq!(body["code"], "validation_failed");
+    let fields: Vec<&str> = body["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| f["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(fields, ["title", "description", "price_shannons"]);
+
+    let resp = client
+        .post("/api/orders")
+        .header("X-User-Id", &seller_id)
+        .json(&serde_json::json!({
+            "product_id": "00000000-0000-0000-0000-000000000000",
+            "preimage": "0xnothex"
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    let body: serde_json::Value = resp.json().unwrap();
+    assert_eq!(body["fields"][0]["field"], "preimage");
+
+    // An ID in the path that isn't one is named in the same error shape
+    let resp = seller_client.get("/api/orders/nope").send().unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = resp.json().unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({ "error": "Invalid order_id: \"nope\"", "code": "bad_request" })
+    );
+
+    // Time can't be moved backwards or far enough to overflow
+    for seconds in [-1, i64::MAX] {
+        let resp = client
+            .post("/api/system/tick")
+            .json(&serde_json::json!({ "seconds": seconds }))
+            .send()
+            .unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
+
+#[test]
+fn test_escrow_operator_routes_need_the_admin_token() {
+    let state = fiber_escrow_service::AppState::new().with_admin_token(Some("s3cret".to_string()));
+    let service = EscrowServer::start_with(state);
+    let client = reqwest::blocking::Client::new();
+    let url = format!("{}/api/arbiter/disputes", service.url());
+
+    let resp = client.get(&url).send().unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let resp = client.get(&url).bearer_auth("wrong").send().unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let resp = client.get(&url).bearer_auth("s3cret").send().unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+
+    // Users' own routes don't take it
+    let resp = client
+        .get(format!("{}/api/products", service.url()))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+}
+
+#[test]
+fn test_escrow_features_switched_off_at_runtime() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let market = runtime.block_on(Marketplace::new());
+    let order = runtime.block_on(async {
+        let (order, preimage) = market.order().await;
+        market.state.set_revealed_preimage(order.id, preimage).await;
+        market.state.update_order_status(order.id, OrderStatus::Shipped).await;
+        order
+    });
+    let service = EscrowServer::start_with(market.state.clone());
+    let client = reqwest::blocking::Client::new();
+    let toggle = |name: &str, enabled: bool| {
+        let resp = client
+            .put(format!("{}/api/admin/features/{}", service.url(), name))
+            .json(&serde_json::json!({ "enabled": enabled }))
+            .send()
+            .unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    };
+    let tick = |seconds: i64| -> serde_json::Value {
+        client
+            .post(format!("{}/api/system/tick", service.url()))
+            .json(&serde_json::json!({ "seconds": seconds }))
+            .send()
+            .unwrap()
+            .json()
+            .unwrap()
+    };
+    toggle("auto_settle", false);
+    toggle("three_party_escrow", false);
+
+    let buyer = EscrowClient::new(&service.url()).with_user(&market.buyer.id.0.to_string());
+    let resp = buyer
+        .post(&format!("/api/orders/{}/dispute", order.id.0))
+        .json(&serde_json::json!({ "reason": "never arrived" }))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+    let body: serde_json::Value = resp.json().unwrap();
+    assert_eq!(body["code"], "feature_disabled");
+
+    // Past the timeout the shipped order is left alone until switched back on
+    assert!(tick(90_000)["expired_orders"].as_array().unwrap().is_empty());
+    toggle("auto_settle", true);
+    let expired = tick(0);
+    assert_eq!(expired["expired_orders"][0].as_str(), Some(order.id.0.to_string().as_str()));
+}
+
+#[test]
+fn test_escrow_dispute_resolution_reasons() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let market = runtime.block_on(Marketplace::new());
+    let order = runtime.block_on(async {
+        let (order, preimage) = market.order().await;
+        market.state.set_revealed_preimage(order.id, preimage).await;
+        market.state.update_order_status(order.id, OrderStatus::Shipped).await;
+        market.state.add_dispute(order.id, "never arrived".to_string()).await;
+        order
+    });
+    let service = EscrowServer::start_with(market.state.clone());
+    let arbiter = EscrowClient::new(&service.url());
+
+    let templates: serde_json::Value = arbiter
+        .get("/api/arbiter/templates")
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    let template = templates["templates"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|t| t["reason"] == "item_not_received")
+        .unwrap();
+    assert_eq!(template["resolution"], "to_buyer");
+
+    // A reason deciding for the buyer can't release funds to the seller,
+    // and `other` needs a note
+    let resolve = format!("/api/arbiter/disputes/{}/resolve", order.id.0);
+    for body in [
+        serde_json::json!({ "resolution": "seller", "reason": "item_not_received" }),
+        serde_json::json!({ "resolution": "seller", "reason": "other" }),
+    ] {
+        let resp = arbiter.post(&resolve).json(&body).send().unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    let resp = arbiter
+        .post(&resolve)
+        .json(&serde_json::json!({
+            "resolution": "buyer",
+            "reason": "item_not_received",
+            "note": template["note"]
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+
+    let buyer = EscrowClient::new(&service.url()).with_user(&market.buyer.id.0.to_string());
+    let order: serde_json::Value = buyer
+        .get(&format!("/api/orders/{}", order.id.0))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(order["status"], "refunded");
+    assert_eq!(order["dispute"]["resolution"], "to_buyer");
+    assert_eq!(order["dispute"]["resolution_reason"], "item_not_received");
+    assert_eq!(order["dispute"]["resolution_note"], template["note"]);
+
+    let timeline: serde_json::Value = buyer
+        .get(&format!("/api/orders/{}/timeline", order["id"].as_str().unwrap()))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    let events = timeline["events"].as_array().unwrap();
+    assert_eq!(events[events.len() - 3]["kind"], "disputed");
+    assert_eq!(events[events.len() - 3]["detail"], "never arrived");
+    assert_eq!(events[events.len() - 2]["kind"], "resolved");
+    assert_eq!(events[events.len() - 2]["detail"], template["note"]);
+    assert_eq!(events[events.len() - 1]["kind"], "refunded");
+}
+
+#[test]
+fn test_escrow_users_sign_in_with_a_shared_identity() {
+    let service = EscrowServer::start();
+    let client = reqwest::blocking::Client::new();
+    let key = fiber_test_fixtures::Keypair::player_a();
+    let token = fiber_auth::Identity::sign_in(&key.secret, std::time::Duration::from_secs(60));
+    let register = || -> serde_json::Value {
+        client
+            .post(format!("{}/api/user/register", service.url()))
+            .header(fiber_auth::IDENTITY_HEADER, &token)
+            .json(&serde_json::json!({ "username": "dave" }))
+            .send()
+            .unwrap()
+            .json()
+            .unwrap()
+    };
+
+    // The identity decides the user ID; registering again signs back in
+    let user = register();
+    let user_id = fiber_auth::Identity::of(&key.secret).user_id().to_string();
+    assert_eq!(user["id"].as_str(), Some(user_id.as_str()));
+    assert_eq!(register()["id"], user["id"]);
+
+    let me: serde_json::Value = client
+        .get(format!("{}/api/user/me", service.url()))
+        .header(fiber_auth::IDENTITY_HEADER, &token)
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(me["username"], "dave");
+
+    let resp = client
+        .get(format!("{}/api/user/me", service.url()))
+        .header(fiber_auth::IDENTITY_HEADER, "not-a-token")
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn test_escrow_seller_searches_orders() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let market = runtime.block_on(Marketplace::new());
+    let (shipped, funded, pricier) = runtime.block_on(async {
+        let state = &market.state;
+        market.order().await;
+        let (funded, _) = market.order().await;
+        let (shipped, _) = market.order().await;
+        state.update_order_status(funded.id, OrderStatus::Funded).await;
+        state.update_order_status(shipped.id, OrderStatus::Shipped).await;
+        let carol = state.register_user("carol".to_string()).await;
+        let product = state
+            .create_product(
+                market.seller.id,
+                "Pricier".to_string(),
+                String::new(),
+                5_000,
+                None,
+                None,
+            )
+            .await;
+        let payment_hash = fiber_core::Preimage::random().payment_hash();
+        let pricier = state.create_order(&product, carol.id, payment_hash).await;
+        (shipped, funded, pricier)
+    });
+    let service = EscrowServer::start_with(market.state.clone());
+    let seller = EscrowClient::new(&service.url()).with_user(&market.seller.id.0.to_string());
+    let list = |query: &str| -> Vec<String> {
+        let page: serde_json::Value = seller
+            .get(&format!("/api/orders/mine?{}", query))
+            .send()
+            .unwrap()
+            .json()
+            .unwrap();
+        page["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|o| o["id"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    // Funded, not yet shipped: the ones waiting on the seller
+    assert_eq!(list("status=funded"), [funded.id.0.to_string()]);
+    assert_eq!(list(&format!("product_id={}", market.product.id.0)).len(), 3);
+    assert_eq!(list(&format!("buyer_id={}", pricier.buyer_id.0)), [pricier.id.0.to_string()]);
+    assert_eq!(list("sort=oldest").len(), 4);
+    assert_eq!(list("sort=oldest")[2], shipped.id.0.to_string());
+    assert_eq!(list("sort=amount")[0], pricier.id.0.to_string());
+    assert!(list("from=2100-01-01T00:00:00Z").is_empty());
+
+    let resp = seller
+        .get("/api/orders/mine?from=2030-01-01T00:00:00Z&to=2020-01-01T00:00:00Z")
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+
+    // Counts cover every status, whichever one is asked for
+    let query = format!("status=funded&product_id={}", market.product.id.0);
+    let summary: serde_json::Value = seller
+        .get(&format!("/api/orders/mine/summary?{}", query))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(summary["total"], 3);
+    assert_eq!(summary["by_status"]["waiting_payment"], 1);
+    assert_eq!(summary["by_status"]["funded"], 1);
+    assert_eq!(summary["by_status"]["shipped"], 1);
+    assert_eq!(summary["by_status"]["refunded"], 0);
+}
+
+#[test]
+fn test_escrow_seller_imports_and_exports_catalog() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let market = runtime.block_on(Marketplace::new());
+    let service = EscrowServer::start_with(market.state.clone());
+    let seller = EscrowClient::new(&service.url()).with_user(&market.seller.id.0.to_string());
+    let import = |query: &str, content_type: &str, body: String| {
+        seller
+            .post(&format!("/api/products/import{}", query))
+            .header("Content-Type", content_type)
+            .body(body)
+            .send()
+            .unwrap()
+    };
+    let my_products = || -> usize {
+        let page: serde_json::Value = seller.get("/api/products/mine").send().unwrap().json().unwrap();
+        page["items"].as_array().unwrap().len()
+    };
+    let csv = format!(
+        "title,description,price_shannons,category_id,draft\n\
+         Poster,A2 print,50000,,false\n\
+         Free sample,,0,,false\n\
+         Sticker pack,,lots,,true\n\
+         Mug,,9000,{},false\n\
+         Zine,Issue 1,8000,,true\n",
+        uuid::Uuid::new_v4()
+    );
+
+    // A dry run reports on every row and creates nothing
+    let report: serde_json::Value = import("?dry_run=true", "text/csv", csv.clone()).json().unwrap();
+    assert_eq!(report["created"], 0);
+    assert_eq!(report["failed"], 3);
+    assert_eq!(my_products(), 1);
+
+    let report: serde_json::Value = import("", "text/csv", csv).json().unwrap();
+    assert_eq!(report["created"], 2);
+    assert_eq!(report["failed"], 3);
+    let rows = report["rows"].as_array().unwrap();
+    assert_eq!(rows[0]["status"], "available");
+    assert_eq!(rows[1]["errors"][0]["field"], "price_shannons");
+    assert_eq!(rows[2]["errors"][0]["field"], "price_shannons");
+    assert_eq!(rows[3]["errors"][0]["field"], "category_id");
+    assert!(rows[3]["product_id"].is_null());
+    assert_eq!(rows[4]["status"], "draft");
+    assert_eq!(my_products(), 3);
+
+    // The export goes back in as it came out
+    let resp = seller.get("/api/products/mine/export?format=csv").send().unwrap();
+    assert_eq!(resp.headers()["content-type"], "text/csv");
+    let exported = resp.text().unwrap();
+    let mut lines = exported.lines();
+    assert_eq!(
+        lines.next().unwrap(),
+        "id,status,title,description,price_shannons,billing_period_secs,category_id,draft"
+    );
+    assert_eq!(lines.count(), 3);
+    assert!(exported.contains("draft,Zine,Issue 1,8000,,,true"));
+
+    let drafts: serde_json::Value = seller
+        .get("/api/products/mine/export?status=draft")
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(drafts.as_array().unwrap().len(), 1);
+    let report: serde_json::Value = import("", "application/json", drafts.to_string()).json().unwrap();
+    assert_eq!(report["created"], 1);
+    assert_eq!(report["rows"][0]["status"], "draft");
+
+    let resp = import("", "text/plain", "Poster".to_string());
+    assert_eq!(resp.status(), reqwest::StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    let resp = import("", "application/json", "{}".to_string());
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn test_escrow_traces_an_invoice_to_its_order() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let market = runtime.block_on(Marketplace::new());
+    let (order, _) = runtime.block_on(async {
+        let (order, preimage) = market.order().await;
+        market.state.update_order_status(order.id, OrderStatus::Shipped).await;
+        (order, preimage)
+    });
+    let service = EscrowServer::start_with(market.state.clone());
+    let admin = EscrowClient::new(&service.url());
+
+    let path = format!("/api/admin/invoices/{}", order.payment_hash.to_hex());
+    let owner: serde_json::Value = admin.get(&path).send().unwrap().json().unwrap();
+    assert_eq!(owner["order_id"], order.id.0.to_string());
+    assert_eq!(owner["status"], "shipped");
+    assert_eq!(owner["held"], true);
+
+    // An invoice no order pays into
+    let (_, unknown) = generate_preimage_and_hash();
+    let resp = admin
+        .get(&format!("/api/admin/invoices/{}", unknown))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+    let resp = admin.get("/api/admin/invoices/nope").send().unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn test_escrow_seeds_only_an_empty_store() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../../seeds/demo.yaml");
+    let seed = Seed::load(&path).unwrap();
+
+    runtime.block_on(async {
+        // The demo seed lists its users, category tree and products
+        let state = AppState::new();
+        assert!(seed.apply(&state).await.unwrap());
+        assert_eq!(state.list_users().await.unwrap().len(), 3);
+        let seller = state.get_user_by_username("seller").await.unwrap();
+        let products = state.list_products_by_seller(seller.id).await;
+        assert_eq!(products.len(), 4);
+        let art = state.get_category_by_slug("art").await.unwrap();
+        let digital = state.get_category_by_slug("digital-goods").await.unwrap();
+        assert_eq!(art.parent_id, Some(digital.id));
+        let newsletter = products.iter().find(|p| p.title == "Premium Newsletter").unwrap();
+        assert_eq!(newsletter.billing_period_secs, Some(7 * 24 * 3600));
+
+        // Applying it again, or to a store with data of its own, adds nothing
+        assert!(!seed.apply(&state).await.unwrap());
+        assert_eq!(state.list_users().await.unwrap().len(), 3);
+        let market = Marketplace::new().await;
+        assert!(!seed.apply(&market.state).await.unwrap());
+        assert!(market.state.get_category_by_slug("art").await.is_none());
+
+        // A broken entry is named
+        let not_a_seller = Seed::parse(
+            "users: [{ username: bob, role: buyer }]\n\
+             products: [{ seller: bob, title: Widget, price_shannons: 10 }]",
+        )
+        .unwrap();
+        let err = not_a_seller.apply(&AppState::new()).await.unwrap_err();
+        assert_eq!(err, "product 1 (Widget): bob is not a seeded seller");
+        let orphan = Seed::parse("categories: [{ name: Art, parent: digital-goods }]").unwrap();
+        let err = orphan.apply(&AppState::new()).await.unwrap_err();
+        assert!(err.starts_with("category 1 (Art): no category digital-goods"), "{}", err);
+    });
+}
@@ -0,0 +1,174 @@
+//! The order lifecycle driven over gRPC, with the HTTP app as transport.
+
+#![cfg(feature = "grpc")]
+
+use fiber_core::Preimage;
+use fiber_errors::ERROR_CODE_METADATA;
+use fiber_escrow_service::{
+    create_app,
+    grpc::pb::{self, escrow_client::EscrowClient},
+    models::User,
+};
+use fiber_test_fixtures::escrow::Marketplace;
+use tonic::{Code, Request};
+
+fn as_user<T>(user: &User, message: T) -> Request<T> {
+    let mut request = Request::new(message);
+    request
+        .metadata_mut()
+        .insert("x-user-id", user.id.0.to_string().parse().unwrap());
+    request
+}
+
+fn order_ref(order: &pb::Order) -> pb::OrderRef {
+    pb::OrderRef {
+        order_id: order.id.clone(),
+    }
+}
+
+#[tokio::test]
+async fn test_order_lifecycle_over_grpc() {
+    let market = Marketplace::new().await;
+    let mut client = EscrowClient::new(create_app(market.state.clone()));
+    let preimage = Preimage::random();
+
+    let create = pb::CreateOrderRequest {
+        product_id: market.product.id.0.to_string(),
+        preimage: hex::encode(preimage.as_bytes()),
+    };
+    let order = client
+        .create_order(as_user(&market.buyer, create))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(order.status, "waiting_payment");
+    assert_eq!(order.payment_hash, preimage.payment_hash().to_hex());
+
+    let invoice = pb::SubmitInvoiceRequest {
+        order_id: order.id.clone(),
+        invoice: "fibt1000".to_string(),
+        expiry_secs: 3600,
+    };
+    let order = client
+        .submit_invoice(as_user(&market.seller, invoice))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(order.invoice.as_deref(), Some("fibt1000"));
+    assert!(order.invoice_expires_at.is_some());
+
+    let order = client
+        .pay_order(as_user(&market.buyer, order_ref(&order)))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(order.status, "funded");
+
+    // Only the seller ships
+    let status = client
+        .ship_order(as_user(&market.buyer, order_ref(&order)))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+    assert_eq!(
+        status.metadata().get(ERROR_CODE_METADATA).unwrap(),
+        "forbidden"
+    );
+
+    client
+        .ship_order(as_user(&market.seller, order_ref(&order)))
+        .await
+        .unwrap();
+    let order = client
+        .confirm_order(as_user(&market.buyer, order_ref(&order)))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(order.status, "completed");
+    // The buyer knows the preimage already; only the seller is handed it
+    assert_eq!(order.preimage, None);
+
+    let list = pb::ListMyOrdersRequest::default();
+    let page = client
+        .list_my_orders(as_user(&market.seller, list))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(page.next_cursor, "");
+    let orders = page.orders;
+    assert_eq!(orders.len(), 1);
+    let expected = format!("0x{}", hex::encode(preimage.as_bytes()));
+    assert_eq!(orders[0].preimage.as_deref(), Some(expected.as_str()));
+}
+
+#[tokio::test]
+async fn test_grpc_rejects_anonymous_and_invalid_calls() {
+    let market = Marketplace::new().await;
+    let mut client = EscrowClient::new(create_app(market.state.clone()));
+    let (order, _) = market.order().await;
+
+    let status = client
+        .get_order(pb::OrderRef {
+            order_id: order.id.0.to_string(),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let dispute = pb::DisputeOrderRequest {
+        order_id: order.id.0.to_string(),
+        reason: " ".to_string(),
+    };
+    let status = client
+        .dispute_order(as_user(&market.buyer, dispute))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+    assert_eq!(
+        status.metadata().get(ERROR_CODE_METADATA).unwrap(),
+        "validation_failed"
+    );
+}
+
+#[tokio::test]
+async fn test_orders_are_listed_a_page_at_a_time() {
+    let market = Marketplace::new().await;
+    let mut client = EscrowClient::new(create_app(market.state.clone()));
+    let mut placed = Vec::new();
+    for _ in 0..3 {
+        placed.push(market.order().await.0.id.0.to_string());
+    }
+
+    let mut listed = Vec::new();
+    let mut list = pb::ListMyOrdersRequest {
+        cursor: String::new(),
+        limit: 2,
+    };
+    loop {
+        let page = client
+            .list_my_orders(as_user(&market.buyer, list.clone()))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(page.orders.len() <= 2);
+        listed.extend(page.orders);
+        if page.next_cursor.is_empty() {
+            break;
+        }
+        list.cursor = page.next_cursor;
+    }
+
+    // Newest first, each order once
+    assert!(listed.windows(2).all(|w| w[0].created_at >= w[1].created_at));
+    let mut ids: Vec<String> = listed.into_iter().map(|o| o.id).collect();
+    ids.sort();
+    placed.sort();
+    assert_eq!(ids, placed);
+
+    list.cursor = "not a cursor".to_string();
+    let status = client
+        .list_my_orders(as_user(&market.buyer, list))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
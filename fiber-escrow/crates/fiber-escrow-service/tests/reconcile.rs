@@ -0,0 +1,54 @@
+//! Held orders checked against the seller's node.
+//!
+//! Run with: cargo test --test reconcile
+
+use fiber_core::fiber::{FiberClient, MockFiberClient};
+use fiber_escrow_service::models::{Order, OrderEventKind, OrderStatus};
+use fiber_escrow_service::reconcile;
+use fiber_test_fixtures::escrow::Marketplace;
+
+/// A new order the buyer paid through a hold invoice on `node`, moved on
+/// to `status`
+async fn held_order(market: &Marketplace, node: &MockFiberClient, status: OrderStatus) -> Order {
+    let (order, _) = market.order().await;
+    let invoice = node
+        .create_hold_invoice(&order.payment_hash, order.amount_shannons, 3600)
+        .await
+        .unwrap();
+    node.pay_hold_invoice(&invoice).await.unwrap();
+    market.state.update_order_status(order.id, status).await;
+    order
+}
+
+#[tokio::test]
+async fn test_orders_the_node_dropped_are_refunded() {
+    let market = Marketplace::new().await;
+    let node = MockFiberClient::new(1_000_000);
+    let kept = held_order(&market, &node, OrderStatus::Shipped).await;
+    let dropped = held_order(&market, &node, OrderStatus::Funded).await;
+
+    // Nothing to correct while the node holds both payments
+    assert!(reconcile::reconcile(&market.state, &node).await.is_empty());
+
+    node.cancel_invoice(&dropped.payment_hash).await.unwrap();
+    assert_eq!(reconcile::reconcile(&market.state, &node).await, [dropped.id]);
+
+    let order = market.state.get_order(dropped.id).await.unwrap();
+    assert_eq!(order.status, OrderStatus::Refunded);
+    let events = market.state.order_events(dropped.id).await;
+    assert_eq!(events.last().unwrap().kind, OrderEventKind::Refunded);
+    for user in [&market.buyer, &market.seller] {
+        let notifications = market.state.list_notifications(user.id).await;
+        assert_eq!(notifications[0].order_id, Some(dropped.id));
+    }
+    let kept = market.state.get_order(kept.id).await.unwrap();
+    assert_eq!(kept.status, OrderStatus::Shipped);
+
+    // Counted once, and not refunded again on the next pass
+    assert!(reconcile::reconcile(&market.state, &node).await.is_empty());
+    assert!(market
+        .state
+        .metrics()
+        .render()
+        .contains("fiber_invoices_diverged_total 1"));
+}
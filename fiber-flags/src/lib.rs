@@ -0,0 +1,402 @@
+//! Fiber Feature Flags
+//!
+//! Switches for the riskier parts of a service, such as escrow auto-settlement
+//! or the player's direct P2P link, so an operator can turn one off in an
+//! environment without a new build:
+//! - each service declares its [`Feature`]s, each with a default
+//! - [`FlagArgs`] sets them at startup: `--feature auto_settle=off`,
+//!   `FIBER_FEATURES=auto_settle=off,p2p_transport=on` or a `features` list in
+//!   the service's section of the config file
+//! - [`FeatureFlags`] is what handlers check
+//! - [`router`] serves `GET /features` and `PUT /features/:name` so operators
+//!   can flip one while the service runs; mount it behind the admin token
+//!
+//! Toggles are kept in memory: a restart goes back to the configured settings.
+
+use axum::{
+    extract::State,
+    routing::get,
+    Json, Router,
+};
+use fiber_errors::{ApiError, ErrorCode, ValidPath};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::info;
+
+/// Env var with comma-separated settings when `--feature` is not given
+pub const FEATURES_ENV: &str = "FIBER_FEATURES";
+
+/// A part of a service that can be switched off
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Feature {
+    /// Name in settings and the admin API, in snake_case
+    pub name: &'static str,
+    /// What the feature does, shown to operators
+    pub description: &'static str,
+    /// Whether it is on when no setting names it
+    pub default: bool,
+}
+
+/// A `name=on` or `name=off` setting; a bare `name` means on
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FlagSetting {
+    pub name: String,
+    pub enabled: bool,
+}
+
+impl FromStr for FlagSetting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = s.split_once('=').unwrap_or((s, "on"));
+        let enabled = match value.trim() {
+            "on" | "true" => true,
+            "off" | "false" => false,
+            other => return Err(format!("feature {}: {:?} is not on or off", name, other)),
+        };
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(format!("{:?} names no feature", s));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            enabled,
+        })
+    }
+}
+
+impl fmt::Display for FlagSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = if self.enabled { "on" } else { "off" };
+        write!(f, "{}={}", self.name, value)
+    }
+}
+
+impl Serialize for FlagSetting {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for FlagSetting {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Feature settings a service starts with
+///
+/// Flattened into each service's config, so the file key is `features`:
+///
+/// ```yaml
+/// escrow:
+///   features: [auto_settle=off]
+/// ```
+#[derive(clap::Args, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FlagArgs {
+    /// Turn a feature on or off, as NAME=on|off (repeatable; comma-separated
+    /// in the environment variable)
+    #[arg(
+        long = "feature",
+        env = FEATURES_ENV,
+        value_delimiter = ',',
+        value_name = "NAME=on|off"
+    )]
+    #[serde(default)]
+    pub features: Vec<FlagSetting>,
+}
+
+impl FlagArgs {
+    /// Reject settings naming none of `features`, for a config's `validate`
+    pub fn check(&self, features: &[Feature]) -> Result<(), String> {
+        match self
+            .features
+            .iter()
+            .find(|setting| !features.iter().any(|f| f.name == setting.name))
+        {
+            Some(setting) => Err(format!(
+                "unknown feature {:?}; this service has {}",
+                setting.name,
+                names(features)
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+fn names(features: &[Feature]) -> String {
+    if features.is_empty() {
+        return "none".to_string();
+    }
+    features
+        .iter()
+        .map(|f| f.name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The features of a service and whether each is on right now
+///
+/// Clones share the switches, so a toggle through [`router`] is seen by every
+/// handler holding one.
+#[derive(Clone, Default)]
+pub struct FeatureFlags {
+    flags: Arc<Vec<(Feature, AtomicBool)>>,
+}
+
+impl FeatureFlags {
+    /// `features`, each at its default
+    pub fn new(features: &[Feature]) -> Self {
+        Self {
+            flags: Arc::new(
+                features
+                    .iter()
+                    .map(|f| (*f, AtomicBool::new(f.default)))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// `features` as `args` sets them
+    pub fn configured(features: &[Feature], args: &FlagArgs) -> Result<Self, String> {
+        args.check(features)?;
+        let flags = Self::new(features);
+        for setting in &args.features {
+            flags.set(&setting.name, setting.enabled);
+        }
+        Ok(flags)
+    }
+
+    /// Whether `feature` is on; one this service didn't declare is at its
+    /// default
+    pub fn is_enabled(&self, feature: &Feature) -> bool {
+        self.switch(feature.name)
+            .map_or(feature.default, |on| on.load(Ordering::Relaxed))
+    }
+
+    /// A 403 `feature_disabled` if `feature` is off
+    pub fn require(&self, feature: &Feature) -> Result<(), ApiError> {
+        if self.is_enabled(feature) {
+            Ok(())
+        } else {
+            Err(ApiError::new(
+                ErrorCode::FeatureDisabled,
+                format!("Feature {} is turned off", feature.name),
+            ))
+        }
+    }
+
+    /// Switch the feature called `name`; `None` if there is none
+    pub fn set(&self, name: &str, enabled: bool) -> Option<FlagStatus> {
+        let (feature, on) = self.flags.iter().find(|(f, _)| f.name == name)?;
+        on.store(enabled, Ordering::Relaxed);
+        Some(FlagStatus::of(feature, on))
+    }
+
+    /// Every feature, in the order the service declared them
+    pub fn list(&self) -> Vec<FlagStatus> {
+        self.flags.iter().map(|(f, on)| FlagStatus::of(f, on)).collect()
+    }
+
+    fn switch(&self, name: &str) -> Option<&AtomicBool> {
+        self.flags
+            .iter()
+            .find(|(f, _)| f.name == name)
+            .map(|(_, on)| on)
+    }
+}
+
+impl fmt::Debug for FeatureFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.list().into_iter().map(|s| (s.name, s.enabled)))
+            .finish()
+    }
+}
+
+/// A feature as the admin API shows it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlagStatus {
+    pub name: String,
+    pub description: String,
+    pub enabled: bool,
+    /// Whether it is on when not configured
+    pub default: bool,
+}
+
+impl FlagStatus {
+    fn of(feature: &Feature, on: &AtomicBool) -> Self {
+        Self {
+            name: feature.name.to_string(),
+            description: feature.description.to_string(),
+            enabled: on.load(Ordering::Relaxed),
+            default: feature.default,
+        }
+    }
+}
+
+/// `GET /features`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FeaturesResponse {
+    pub features: Vec<FlagStatus>,
+}
+
+/// `PUT /features/:name`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SetFlagRequest {
+    pub enabled: bool,
+}
+
+/// `GET /features` and `PUT /features/:name` over `flags`, to merge into a
+/// service's operator routes
+pub fn router<S: Clone + Send + Sync + 'static>(flags: FeatureFlags) -> Router<S> {
+    Router::new()
+        .route("/features", get(list_features))
+        .route("/features/:name", get(get_feature).put(set_feature))
+        .with_state(flags)
+}
+
+async fn list_features(State(flags): State<FeatureFlags>) -> Json<FeaturesResponse> {
+    Json(FeaturesResponse {
+        features: flags.list(),
+    })
+}
+
+async fn get_feature(
+    State(flags): State<FeatureFlags>,
+    ValidPath(name): ValidPath<String>,
+) -> Result<Json<FlagStatus>, ApiError> {
+    flags
+        .list()
+        .into_iter()
+        .find(|f| f.name == name)
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found("Feature not found"))
+}
+
+async fn set_feature(
+    State(flags): State<FeatureFlags>,
+    ValidPath(name): ValidPath<String>,
+    Json(req): Json<SetFlagRequest>,
+) -> Result<Json<FlagStatus>, ApiError> {
+    let status = flags
+        .set(&name, req.enabled)
+        .ok_or_else(|| ApiError::not_found("Feature not found"))?;
+    info!(feature = %name, enabled = req.enabled, "Feature toggled");
+    Ok(Json(status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    const RISKY: Feature = Feature {
+        name: "risky",
+        description: "Something that may go wrong",
+        default: true,
+    };
+    const NEW: Feature = Feature {
+        name: "new",
+        description: "Not ready yet",
+        default: false,
+    };
+
+    fn args(settings: &[&str]) -> FlagArgs {
+        FlagArgs {
+            features: settings.iter().map(|s| s.parse().unwrap()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_settings_parse_and_print() {
+        let off: FlagSetting = "risky=off".parse().unwrap();
+        assert!(!off.enabled);
+        assert_eq!(off.to_string(), "risky=off");
+        assert!("risky".parse::<FlagSetting>().unwrap().enabled);
+        assert!("risky=maybe".parse::<FlagSetting>().is_err());
+        assert!("=on".parse::<FlagSetting>().is_err());
+
+        let yaml = serde_yaml::to_string(&args(&["risky=off", "new=on"])).unwrap();
+        let back: FlagArgs = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(back.features, args(&["risky=off", "new=on"]).features);
+    }
+
+    #[test]
+    fn test_configured_flags_override_defaults() {
+        let flags = FeatureFlags::new(&[RISKY, NEW]);
+        assert!(flags.is_enabled(&RISKY));
+        assert!(!flags.is_enabled(&NEW));
+
+        let flags = FeatureFlags::configured(&[RISKY, NEW], &args(&["risky=off", "new"])).unwrap();
+        assert!(!flags.is_enabled(&RISKY));
+        assert!(flags.is_enabled(&NEW));
+        let err = flags.require(&RISKY).unwrap_err();
+        assert_eq!(err.code, ErrorCode::FeatureDisabled);
+
+        let err = FeatureFlags::configured(&[RISKY], &args(&["riksy=off"])).unwrap_err();
+        assert!(err.contains("unknown feature \"riksy\""), "{}", err);
+    }
+
+    #[test]
+    fn test_clones_share_switches() {
+        let flags = FeatureFlags::new(&[RISKY]);
+        let handler_copy = flags.clone();
+        assert!(flags.set("risky", false).is_some());
+        assert!(!handler_copy.is_enabled(&RISKY));
+        assert!(flags.set("unknown", false).is_none());
+        // Undeclared features stay at their default
+        assert!(!handler_copy.is_enabled(&NEW));
+    }
+
+    #[tokio::test]
+    async fn test_router_toggles_features() {
+        let flags = FeatureFlags::new(&[RISKY, NEW]);
+        let app: Router = router(flags.clone());
+
+        let resp = app
+            .clone()
+            .oneshot(
+                Request::put("/features/risky")
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"enabled": false}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(!flags.is_enabled(&RISKY));
+
+        let resp = app
+            .clone()
+            .oneshot(Request::get("/features").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let listed: FeaturesResponse = serde_json::from_slice(&bytes).unwrap();
+        let enabled: Vec<(&str, bool)> = listed
+            .features
+            .iter()
+            .map(|f| (f.name.as_str(), f.enabled))
+            .collect();
+        assert_eq!(enabled, vec![("risky", false), ("new", false)]);
+
+        let resp = app
+            .oneshot(
+                Request::put("/features/unknown")
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"enabled": true}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,30 @@
+//! Fiber Game API types
+//!
+//! Request and response bodies of the oracle and player HTTP APIs, shared by
+//! the services that serve them, the clients that call them (the player
+//! calling the oracle, the demo's scripted frontend) and the tests. Keeping
+//! one definition per message means a field renamed on the server can't
+//! quietly turn into a missing value on the client.
+//!
+//! Protocol messages that are signed as they are, such as
+//! [`CommitMessage`](fiber_game_core::protocol::CommitMessage), live in
+//! `fiber-game-core`; this crate holds the rest.
+
+pub mod oracle;
+pub mod player;
+
+/// Longest invoice string either service stores. Fiber invoices are a few
+/// hundred characters; this only keeps junk out.
+const MAX_INVOICE_LEN: usize = 4_096;
+
+/// Check a stake: more than nothing, and within
+/// [`MAX_SHANNONS`](fiber_game_core::money::MAX_SHANNONS) so winnings and
+/// losses can be counted
+fn check_stake(v: &mut fiber_errors::Validator, amount_shannons: u64) {
+    let max = fiber_game_core::money::MAX_SHANNONS;
+    v.positive("amount_shannons", amount_shannons).check(
+        amount_shannons <= max,
+        "amount_shannons",
+        format!("must be at most {}", max),
+    );
+}
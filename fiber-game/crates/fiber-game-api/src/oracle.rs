@@ -0,0 +1,591 @@
+//! Oracle API, served under `/api/oracle` in the combined demo.
+//!
+//! Requests from players arrive sealed in an
+//! [`Envelope`](fiber_game_core::protocol::Envelope); the types here are the
+//! payloads inside it. Payment hashes, encrypted preimages, snapshots and
+//! results come back sealed by the oracle in the same way.
+
+use crate::MAX_INVOICE_LEN;
+use chrono::{DateTime, Utc};
+use fiber_errors::{Validate, Validator};
+use fiber_game_core::{
+    crypto::{Commitment, EncryptedPreimage, PaymentHash, Preimage, Salt},
+    games::{GameAction, GameType},
+    protocol::{
+        AbortMessage, AbortReason, DrawPolicy, Envelope, GameData, GameId, GameResult, MatchRound,
+        Player, ResumptionToken, SettlementAction, TimeoutClaim,
+    },
+};
+use fiber_paging::Page;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A game as an operator sees it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AdminGame {
+    pub game_id: GameId,
+    pub game_type: GameType,
+    pub amount_shannons: u64,
+    /// As in [`GameStatusResponse::status`]
+    pub status: String,
+    pub player_a_id: Uuid,
+    pub player_b_id: Option<Uuid>,
+    /// Hash of the invoice player A's node holds (paid by B)
+    pub payment_hash_a: Option<PaymentHash>,
+    /// Hash of the invoice player B's node holds (paid by A)
+    pub payment_hash_b: Option<PaymentHash>,
+    pub created_at: DateTime<Utc>,
+    /// When the last protocol step was taken, or the game created
+    pub updated_at: DateTime<Utc>,
+    /// Seconds since the last protocol step
+    pub idle_secs: u64,
+    /// Where the hold invoices stand, once the game has a result
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settlement: Option<SettlementStatus>,
+}
+
+/// `GET /admin/games`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AdminGamesResponse {
+    pub games: Vec<AdminGame>,
+}
+
+/// Header on every oracle response naming the oracle's public keys, hex and
+/// comma separated, the one new games are signed with first. Games are
+/// bound to the key they were created under, so a player whose key isn't
+/// listed knows the oracle restarted without them.
+pub const ORACLE_KEY_HEADER: &str = "x-oracle-key";
+
+/// `GET /oracle/pubkey`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OraclePubkeyResponse {
+    /// Compressed public key new games are signed with, hex
+    pub pubkey: String,
+}
+
+/// Where an oracle key is in its rotation
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyStatus {
+    /// Announced; new games are signed with it from `activates_at_ms`
+    Next,
+    /// New games are signed with it
+    Active,
+    /// Replaced by a newer key; still signs the games created under it
+    Retired,
+}
+
+/// One of the oracle's signing keys
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OracleKey {
+    /// Compressed public key, hex
+    pub pubkey: String,
+    /// First 8 bytes of the SHA-256 of the public key, hex
+    pub fingerprint: String,
+    /// When new games started or start being signed with it, in
+    /// milliseconds since the epoch
+    pub activates_at_ms: u64,
+    pub status: KeyStatus,
+}
+
+/// `GET /oracle/keys`: every key the oracle signed or will sign games with,
+/// oldest first
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OracleKeysResponse {
+    pub keys: Vec<OracleKey>,
+}
+
+/// `POST /admin/keys`: announce the key new games are signed with after
+/// `activate_in_secs`. Players see it at `/oracle/keys` until then, and
+/// games created before keep the key they were created under.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AnnounceKeyRequest {
+    pub activate_in_secs: u64,
+}
+
+/// A game waiting for its second player
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AvailableGame {
+    pub game_id: GameId,
+    pub game_type: GameType,
+    pub amount_shannons: u64,
+    pub created_at: DateTime<Utc>,
+    /// When the offer is withdrawn from the listing if no one has joined
+    pub expires_at: DateTime<Utc>,
+}
+
+/// `GET /games/available`, oldest first
+pub type AvailableGamesResponse = Page<AvailableGame>;
+
+/// `GET /explorer/stats`: every game the oracle holds that has ended
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExplorerStats {
+    pub completed: u64,
+    pub cancelled: u64,
+    /// Completed games per game type, in [`GameType`] order
+    pub game_types: Vec<GameTypeStats>,
+}
+
+/// Completed games of one type
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameTypeStats {
+    pub game_type: GameType,
+    pub games: u64,
+    pub a_wins: u64,
+    pub b_wins: u64,
+    pub draws: u64,
+    /// Mean stake per player, rounded down
+    pub average_stake_shannons: u64,
+}
+
+/// How a completed game got its result
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DecidedBy {
+    /// The oracle judged both revealed actions
+    Reveals,
+    /// Both players of a private game sent the same verdict
+    Verdicts,
+    /// A player stopped responding after the other committed
+    Forfeit,
+}
+
+/// A completed game with nothing that tells who played it, as listed by
+/// `GET /explorer/attestations` sealed by the oracle
+///
+/// For a game decided by reveals, judging the actions in `game_data` must
+/// give `result`. With the game ID and payment hashes the commitments are
+/// bound to, which only its players know, the salts open both commitments.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameAttestation {
+    /// SHA-256 of the game ID, hex: a player can find their own game, and
+    /// nobody else can tell which game it is
+    pub reference: String,
+    pub game_type: GameType,
+    pub amount_shannons: u64,
+    pub result: GameResult,
+    pub decided_by: DecidedBy,
+    pub commitment_a: Option<Commitment>,
+    pub commitment_b: Option<Commitment>,
+    /// The actions and oracle secret the result was judged from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game_data: Option<GameData>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub salt_a: Option<Salt>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub salt_b: Option<Salt>,
+}
+
+/// `GET /explorer/attestations`, oldest first
+pub type AttestationsResponse = Page<Envelope<GameAttestation>>;
+
+/// `POST /game/create`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CreateGameRequest {
+    pub game_type: GameType,
+    pub player_a_id: Uuid,
+    pub amount_shannons: u64,
+    /// WebSocket URL player A accepts direct connections on, if any
+    #[serde(default)]
+    pub p2p_url: Option<String>,
+    /// Players reveal to each other and only send the oracle their verdict,
+    /// see [`SubmitVerdictRequest`]; needs a game without an oracle secret
+    #[serde(default)]
+    pub private: bool,
+    /// What a draw leads to
+    #[serde(default)]
+    pub draw_policy: DrawPolicy,
+    /// The drawn game this one replays, as the next round of its match.
+    /// Only its player A may create it, with the same terms; its seat B is
+    /// kept for the same opponent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rematch_of: Option<GameId>,
+}
+
+impl Validate for CreateGameRequest {
+    fn check(&self, v: &mut Validator) {
+        crate::check_stake(v, self.amount_shannons);
+        v.check(
+            !(self.private && self.game_type.requires_oracle_secret()),
+            "private",
+            "games with an oracle secret are judged by the oracle",
+        );
+        if let Some(url) = &self.p2p_url {
+            v.max_len("p2p_url", url, 2_048);
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CreateGameResponse {
+    pub game_id: GameId,
+    /// Compressed public key, hex
+    pub oracle_pubkey: String,
+    /// Compressed point, hex
+    pub commitment_point: String,
+    /// Hash of the oracle's secret, hex; only for games that need one
+    pub oracle_commitment: Option<String>,
+    /// Absent from oracles that predate resumption
+    pub resume_token: Option<Envelope<ResumptionToken>>,
+    /// Which round of which match the game is; absent from oracles that
+    /// predate rematches
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub round: Option<MatchRound>,
+}
+
+/// `POST /game/:game_id/join`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JoinGameRequest {
+    pub player_b_id: Uuid,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JoinGameResponse {
+    pub status: String,
+    pub game_type: GameType,
+    /// Compressed public key, hex
+    pub oracle_pubkey: String,
+    /// Compressed point, hex
+    pub commitment_point: String,
+    /// Hash of the oracle's secret, hex; only for games that need one
+    pub oracle_commitment: Option<String>,
+    pub amount_shannons: u64,
+    /// Player A's direct-connection URL; absent if B must relay through us
+    pub peer_url: Option<String>,
+    /// Whether player A created the game in privacy mode
+    #[serde(default)]
+    pub private: bool,
+    /// Absent from oracles that predate resumption
+    pub resume_token: Option<Envelope<ResumptionToken>>,
+    /// What a draw leads to, as player A chose
+    #[serde(default)]
+    pub draw_policy: DrawPolicy,
+    /// Which round of which match the game is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub round: Option<MatchRound>,
+}
+
+/// `POST /game/:game_id/resume`, answered with a sealed
+/// [`GameSnapshot`](fiber_game_core::protocol::GameSnapshot)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ResumeRequest {
+    /// Token from the create or join response, as the oracle signed it
+    pub token: Envelope<ResumptionToken>,
+}
+
+/// `POST /game/:game_id/payment-hash`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubmitPaymentHashRequest {
+    pub player: Player,
+    pub payment_hash: PaymentHash,
+    /// The preimage that hashes to payment_hash (stored for settlement)
+    pub preimage: Preimage,
+}
+
+/// `GET /game/:game_id/payment-hash/:player`, sealed
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PaymentHashResponse {
+    /// Whose payment hash it is
+    pub player: Player,
+    pub payment_hash: PaymentHash,
+}
+
+/// `POST /game/:game_id/invoice`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubmitInvoiceRequest {
+    pub player: Player,
+    /// The actual BOLT11 invoice string
+    pub invoice_string: String,
+}
+
+impl Validate for SubmitInvoiceRequest {
+    fn check(&self, v: &mut Validator) {
+        let invoice = &self.invoice_string;
+        v.not_blank("invoice_string", invoice)
+            .max_len("invoice_string", invoice, MAX_INVOICE_LEN);
+    }
+}
+
+/// `GET /game/:game_id/invoice/:player`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InvoiceResponse {
+    /// The actual BOLT11 invoice string
+    pub invoice_string: String,
+}
+
+/// `POST /game/:game_id/encrypted-preimage`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubmitEncryptedPreimageRequest {
+    pub player: Player,
+    pub encrypted_preimage: EncryptedPreimage,
+}
+
+/// `GET /game/:game_id/encrypted-preimage/:player`, sealed
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EncryptedPreimageResponse {
+    pub encrypted_preimage: EncryptedPreimage,
+}
+
+/// `POST /game/:game_id/funded`; players send a
+/// [`FundingMessage`](fiber_game_core::protocol::FundingMessage), of which
+/// the oracle reads these fields
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubmitFundingRequest {
+    pub player: Player,
+    pub payment_hash: PaymentHash,
+}
+
+/// `POST /game/:game_id/settled`; players send a
+/// [`SettlementMessage`](fiber_game_core::protocol::SettlementMessage), of
+/// which the oracle reads these fields
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubmitSettlementRequest {
+    pub player: Player,
+    pub payment_hash: PaymentHash,
+    pub action: SettlementAction,
+}
+
+/// `POST /game/:game_id/commit`; players send a
+/// [`CommitMessage`](fiber_game_core::protocol::CommitMessage), of which
+/// the oracle reads these fields
+///
+/// Refused unless `amount_shannons` is the game's stake and
+/// `opponent_payment_hash` the hash the opponent submitted.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubmitCommitRequest {
+    pub player: Player,
+    pub commitment: Commitment,
+    pub amount_shannons: u64,
+    pub opponent_payment_hash: PaymentHash,
+}
+
+/// `POST /game/:game_id/reveal`; players send a
+/// [`RevealMessage`](fiber_game_core::protocol::RevealMessage), of which
+/// the oracle reads these fields
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubmitRevealRequest {
+    pub player: Player,
+    pub action: GameAction,
+    pub salt: Salt,
+    pub commit_a: Commitment,
+    pub commit_b: Commitment,
+}
+
+/// `POST /game/:game_id/verdict`, for private games; players send a
+/// [`VerdictMessage`](fiber_game_core::protocol::VerdictMessage), of which
+/// the oracle reads these fields
+///
+/// Answered `game_complete` once both verdicts agree, or
+/// `reveals_requested` if they don't, after which the game goes on through
+/// `/reveal` like a public one.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubmitVerdictRequest {
+    pub player: Player,
+    pub result: GameResult,
+    pub commit_a: Commitment,
+    pub commit_b: Commitment,
+    pub transcript: [u8; 32],
+}
+
+/// Answer to submissions that only change the game's state
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StatusResponse {
+    pub status: String,
+}
+
+/// `GET /game/:game_id/status`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameStatusResponse {
+    /// `waiting_for_opponent`, `in_progress`, `completed` or `cancelled`
+    pub status: String,
+    pub has_opponent: bool,
+    /// Stake each player puts up
+    #[serde(default)]
+    pub amount_shannons: u64,
+    /// Whether commitments wait for both stakes to be confirmed held
+    #[serde(default)]
+    pub funding_required: bool,
+    /// Player B confirmed player A's payment is held
+    #[serde(default)]
+    pub stake_held_a: bool,
+    /// Player A confirmed player B's payment is held
+    #[serde(default)]
+    pub stake_held_b: bool,
+    /// The signed abort or timeout claim, if the game ended early
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ending: Option<GameEnding>,
+    /// Player A's commitment, once made
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit_a: Option<Commitment>,
+    /// Player B's commitment, once made
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit_b: Option<Commitment>,
+    /// A private game the oracle now needs both reveals for, because the
+    /// verdicts disagreed or a player revealed to it anyway
+    #[serde(default)]
+    pub reveals_requested: bool,
+    /// Where the hold invoices stand, once the game has a result
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settlement: Option<SettlementStatus>,
+    /// Seconds left before whoever owes the next step is late: a player
+    /// left behind can then be timed out, or an unresolved invoice is
+    /// overdue. Unset once there is nothing left to do.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub step_deadline_secs: Option<u64>,
+    /// What a draw leads to
+    #[serde(default)]
+    pub draw_policy: DrawPolicy,
+    /// Which round of which match the game is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub round: Option<MatchRound>,
+    /// The game replaying this one after a draw, once player A created it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rematch: Option<GameId>,
+}
+
+/// Where a finished game's hold invoices stand, as the players reported
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SettlementStatus {
+    /// The invoice on player A's node, holding B's stake
+    pub a: SeatSettlement,
+    /// The invoice on player B's node, holding A's stake
+    pub b: SeatSettlement,
+    /// Both invoices have been resolved
+    pub complete: bool,
+}
+
+/// What one player owes with the invoice holding their opponent's stake
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeatSettlement {
+    /// Settle if they won, cancel otherwise
+    pub owed: SettlementAction,
+    /// What they reported doing, if they have
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub done: Option<SettlementAction>,
+    /// Nothing reported a step timeout after the result
+    #[serde(default)]
+    pub overdue: bool,
+}
+
+impl SettlementStatus {
+    /// `player`'s side of the settlement
+    pub fn seat(&self, player: Player) -> &SeatSettlement {
+        match player {
+            Player::A => &self.a,
+            Player::B => &self.b,
+        }
+    }
+
+    /// Whether a draw left both invoices held for the next round of its
+    /// match, which resolves them
+    pub fn carried_over(&self) -> bool {
+        self.a.owed == SettlementAction::CarriedOver
+    }
+}
+
+/// How a game ended before both players revealed, with the signed message
+/// that ended it so either player can check it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum GameEnding {
+    /// An [`AbortMessage`]
+    Aborted(Envelope<serde_json::Value>),
+    /// A [`TimeoutClaim`]
+    TimedOut(Envelope<serde_json::Value>),
+}
+
+impl GameEnding {
+    /// Who ended the game and why, from the signed message. `None` if it
+    /// doesn't open as the message its kind says it is.
+    pub fn cause(&self) -> Option<(Player, AbortReason)> {
+        match self {
+            GameEnding::Aborted(envelope) => {
+                let (_, msg): (_, AbortMessage) = envelope.clone().open_as().ok()?;
+                Some((msg.player, msg.reason))
+            }
+            GameEnding::TimedOut(envelope) => {
+                let (_, claim): (_, TimeoutClaim) = envelope.clone().open_as().ok()?;
+                Some((claim.player.opponent(), AbortReason::TimedOut))
+            }
+        }
+    }
+}
+
+/// `GET /game/:game_id/result`, sealed
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameResultResponse {
+    /// `pending` until both players revealed (or, in a private game, sent
+    /// matching verdicts), then `completed`; `reveals_requested` for a
+    /// private game that needs them
+    pub status: String,
+    pub result: Option<GameResult>,
+    /// Oracle's signature over the result, hex
+    pub signature: Option<String>,
+    pub game_data: Option<GameData>,
+    /// Opponent's preimage for Player A (only set if A won)
+    pub preimage_for_a: Option<Preimage>,
+    /// Opponent's preimage for Player B (only set if B won)
+    pub preimage_for_b: Option<Preimage>,
+}
+
+impl GameResultResponse {
+    /// The opponent's preimage released to `player`
+    pub fn preimage_for(&self, player: Player) -> Option<Preimage> {
+        match player {
+            Player::A => self.preimage_for_a.clone(),
+            Player::B => self.preimage_for_b.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fiber_errors::Validate;
+    use fiber_game_core::money;
+    use fiber_test_fixtures::{game::seal, Keypair};
+    use serde_json::json;
+
+    #[test]
+    fn test_ending_names_who_ended_the_game() {
+        let game_id = GameId::new();
+        let abort = json!({ "game_id": game_id, "player": Player::B, "reason": "withdrawn" });
+        let ending = GameEnding::Aborted(seal(abort, &Keypair::player_b().secret));
+        assert_eq!(ending.cause(), Some((Player::B, AbortReason::Withdrawn)));
+
+        // Whoever claims the timeout blames the opponent
+        let claim = json!({ "game_id": game_id, "player": Player::A });
+        let ending = GameEnding::TimedOut(seal(claim, &Keypair::player_a().secret));
+        assert_eq!(ending.cause(), Some((Player::B, AbortReason::TimedOut)));
+
+        let wire = serde_json::to_value(&ending).unwrap();
+        assert_eq!(wire["kind"], "timed_out");
+        let back: GameEnding = serde_json::from_value(wire).unwrap();
+        assert_eq!(back.cause(), ending.cause());
+    }
+
+    #[test]
+    fn test_stake_must_fit_a_balance() {
+        let create = |amount_shannons| CreateGameRequest {
+            player_a_id: Uuid::new_v4(),
+            game_type: GameType::RockPaperScissors,
+            amount_shannons,
+            p2p_url: None,
+            private: false,
+            draw_policy: DrawPolicy::Refund,
+            rematch_of: None,
+        };
+        assert!(create(money::MAX_SHANNONS).validate().is_ok());
+        for amount in [0, money::MAX_SHANNONS + 1] {
+            let err = create(amount).validate().unwrap_err();
+            assert_eq!(err.fields[0].field, "amount_shannons");
+        }
+    }
+
+    #[test]
+    fn test_ending_of_the_wrong_kind_has_no_cause() {
+        let claim = json!({ "game_id": GameId::new(), "player": Player::A });
+        let ending = GameEnding::Aborted(seal(claim, &Keypair::player_a().secret));
+        assert_eq!(ending.cause(), None);
+    }
+}
@@ -0,0 +1,420 @@
+//! Player API, served under `/api` by a standalone player and under
+//! `/api/<player>` in the combined demo. Its client is the player's web
+//! frontend.
+
+use crate::oracle::SettlementStatus;
+use crate::MAX_INVOICE_LEN;
+use chrono::{DateTime, Utc};
+use fiber_errors::{Validate, Validator};
+use fiber_paging::Page;
+use fiber_game_core::{
+    games::{GameAction, GameType},
+    protocol::{
+        AbortReason, DrawPolicy, Envelope, GameId, GameResult, MatchRound, Player, ResumptionToken,
+    },
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Where a game stands from the player's side
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlayerGamePhase {
+    WaitingForOpponent,
+    ExchangingInvoices,
+    ExchangingEncryptedPreimages,
+    WaitingForAction,
+    Committed,
+    Revealed,
+    WaitingForResult,
+    Settled,
+    /// Cancelled before a result by an abort or timeout
+    Aborted,
+    /// The oracle restarted without the game; abort it to get the stakes
+    /// back
+    OracleLost,
+}
+
+/// Fiber backend the frontend uses for this player's payments
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FiberBackend {
+    /// No real payments; the frontend skips all Fiber calls
+    Mock,
+    /// The player's Fiber node at the configured RPC URL
+    Rpc,
+}
+
+/// `GET /player`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerInfoResponse {
+    pub player_id: Uuid,
+    pub player_name: String,
+    pub fiber_rpc_url: Option<String>,
+}
+
+/// Longest profile ID: it appears in URLs and as a database key
+pub const MAX_PROFILE_ID_LEN: usize = 32;
+
+/// Whether `id` can name a profile: lowercase letters, digits and dashes
+pub fn is_profile_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_PROFILE_ID_LEN
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+}
+
+/// A player hosted besides the service's own, served under `api_base`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProfileResponse {
+    pub id: String,
+    pub name: String,
+    pub player_id: Uuid,
+    pub fiber_rpc_url: Option<String>,
+    /// e.g. `/api/profiles/alice`
+    pub api_base: String,
+}
+
+/// `GET /profiles`, by ID
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProfilesResponse {
+    pub profiles: Vec<ProfileResponse>,
+}
+
+/// `POST /profiles`: host another player, with an identity of its own
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CreateProfileRequest {
+    /// Lowercase letters, digits and dashes, see [`is_profile_id`]
+    pub id: String,
+    /// Display name; the ID if not given
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The profile's own Fiber node; mock payments if not given
+    #[serde(default)]
+    pub fiber_rpc_url: Option<String>,
+}
+
+impl Validate for CreateProfileRequest {
+    fn check(&self, v: &mut Validator) {
+        v.check(
+            is_profile_id(&self.id),
+            "id",
+            format!(
+                "must be 1 to {} lowercase letters, digits or dashes",
+                MAX_PROFILE_ID_LEN
+            ),
+        );
+        if let Some(name) = &self.name {
+            v.not_blank("name", name).max_len("name", name, 64);
+        }
+        if let Some(url) = &self.fiber_rpc_url {
+            v.check(
+                url.starts_with("http://") || url.starts_with("https://"),
+                "fiber_rpc_url",
+                "must be an http(s) URL",
+            )
+            .max_len("fiber_rpc_url", url, 2_048);
+        }
+    }
+}
+
+/// A game on the oracle this player could join
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AvailableGameResponse {
+    pub game_id: GameId,
+    pub game_type: GameType,
+    pub amount_shannons: u64,
+    pub created_at: DateTime<Utc>,
+    /// When the oracle stops offering the game
+    pub expires_at: DateTime<Utc>,
+}
+
+/// `GET /games/available`, a page of the oracle's listing
+pub type AvailableGamesResponse = Page<AvailableGameResponse>;
+
+/// A game this player created or joined
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MyGameResponse {
+    pub game_id: GameId,
+    pub game_type: GameType,
+    pub role: Player,
+    pub phase: PlayerGamePhase,
+    pub amount_shannons: u64,
+    pub result: Option<GameResult>,
+    /// Which round of which match the game is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub round: Option<MatchRound>,
+}
+
+/// `GET /games/mine`, by game id
+pub type MyGamesResponse = Page<MyGameResponse>;
+
+/// `POST /game/create`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CreateGameRequest {
+    pub game_type: GameType,
+    pub amount_shannons: u64,
+    /// Reveal actions to the opponent only, over the direct link; the oracle
+    /// signs the result both players agree on without learning the actions
+    #[serde(default)]
+    pub private: bool,
+    /// What a draw leads to: a refund, or another round played by both
+    /// services without asking
+    #[serde(default)]
+    pub draw_policy: DrawPolicy,
+}
+
+impl Validate for CreateGameRequest {
+    fn check(&self, v: &mut Validator) {
+        crate::check_stake(v, self.amount_shannons);
+        v.check(
+            !(self.private && self.game_type.requires_oracle_secret()),
+            "private",
+            "games with an oracle secret are judged by the oracle",
+        );
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CreateGameResponse {
+    pub game_id: GameId,
+    /// Lets the frontend restore the game if this service loses it
+    pub resume_token: Option<Envelope<ResumptionToken>>,
+}
+
+/// `POST /game/join`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JoinGameRequest {
+    pub game_id: GameId,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JoinGameResponse {
+    pub status: String,
+    pub resume_token: Option<Envelope<ResumptionToken>>,
+}
+
+/// `POST /game/resume`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ResumeRequest {
+    pub token: Envelope<ResumptionToken>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ResumeResponse {
+    pub game_id: GameId,
+    pub phase: PlayerGamePhase,
+}
+
+/// `POST /game/:game_id/play`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayRequest {
+    pub action: GameAction,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayResponse {
+    /// The oracle's answer to our reveal: `waiting_for_opponent` or
+    /// `game_complete`
+    pub status: String,
+}
+
+/// `POST /game/:game_id/abort`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AbortRequest {
+    #[serde(default = "default_abort_reason")]
+    pub reason: AbortReason,
+}
+
+fn default_abort_reason() -> AbortReason {
+    AbortReason::Withdrawn
+}
+
+/// Answer to `abort` and `claim-timeout`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EndGameResponse {
+    pub status: String,
+}
+
+/// `GET /game/:game_id/status`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameStatusResponse {
+    pub role: Player,
+    pub phase: PlayerGamePhase,
+    pub result: Option<GameResult>,
+    pub my_action: Option<GameAction>,
+    pub opponent_action: Option<GameAction>,
+    pub can_settle: bool,
+    /// Opponent's payment_hash (hex) — frontend uses this to create hold invoice
+    pub opponent_payment_hash: Option<String>,
+    /// Opponent's preimage (hex) — revealed by Oracle if this player won, used to settle
+    pub opponent_preimage: Option<String>,
+    /// My payment_hash (hex) — needed for settle/cancel
+    pub my_payment_hash: Option<String>,
+    /// Oracle's secret number for Guess Number games
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oracle_secret_number: Option<u8>,
+    /// Who ended the game early, if it was aborted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aborted_by: Option<Player>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub abort_reason: Option<AbortReason>,
+    /// Whether actions are only revealed to the opponent
+    #[serde(default)]
+    pub private: bool,
+    /// Whether a direct link to the opponent is open
+    #[serde(default)]
+    pub direct_link: bool,
+    /// Whether we told the oracle the opponent's payment is held
+    #[serde(default)]
+    pub stake_confirmed: bool,
+    /// Our node cancelled or expired the invoice holding the opponent's
+    /// payment before the game resolved it
+    #[serde(default)]
+    pub invoice_dropped: bool,
+    /// Where both hold invoices stand after the result, as the oracle has it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settlement: Option<SettlementStatus>,
+    /// What a draw leads to
+    #[serde(default)]
+    pub draw_policy: DrawPolicy,
+    /// Which round of which match the game is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub round: Option<MatchRound>,
+    /// A draw followed by another round, which `rematch` names once set up
+    #[serde(default)]
+    pub replayed: bool,
+    /// The game replaying this one after a draw, once it is set up
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rematch: Option<GameId>,
+    /// A draw whose invoice stays held for the next round: settling leaves
+    /// it alone rather than cancelling it
+    #[serde(default)]
+    pub keeps_invoice: bool,
+    /// This round is played for the invoices held from the previous one,
+    /// so there are none to create or pay
+    #[serde(default)]
+    pub invoices_reused: bool,
+}
+
+/// A step this player owes in a game
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DueStep {
+    /// Choose an action; `play` commits and reveals it
+    Commit,
+    /// Reveal the committed action, as a `play` that stopped short would
+    Reveal,
+    /// Settle or cancel the invoice holding the opponent's payment
+    Settle,
+}
+
+/// `GET /reminders`, each sent as a server-sent event named `reminder`, and
+/// the body POSTed to reminder webhooks
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reminder {
+    pub game_id: GameId,
+    pub step: DueStep,
+    /// Seconds left before the step is late, by the oracle's deadline
+    pub seconds_left: u64,
+}
+
+/// `POST /game/:game_id/settle`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SettleResponse {
+    pub result: GameResult,
+    /// Stake won, negative if lost
+    pub amount_won: i64,
+}
+
+/// `POST /games/settle-all`: every game that had a result to settle, in
+/// game ID order
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SettleAllResponse {
+    pub games: Vec<SettledGame>,
+}
+
+/// One game of a `settle-all`, with what the frontend needs to settle or
+/// cancel its invoice
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SettledGame {
+    pub game_id: GameId,
+    /// The settlement, if the game was marked settled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settled: Option<SettleResponse>,
+    /// Why it wasn't
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Opponent's payment_hash (hex), the invoice to settle or cancel;
+    /// unset for a draw whose invoice is kept for the next round
+    pub opponent_payment_hash: Option<String>,
+    /// Opponent's preimage (hex) if this player won, to settle with
+    pub opponent_preimage: Option<String>,
+}
+
+/// Request from frontend reporting that it created an invoice on its Fiber node
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InvoiceCreatedRequest {
+    pub invoice_string: String,
+}
+
+impl Validate for InvoiceCreatedRequest {
+    fn check(&self, v: &mut Validator) {
+        let invoice = &self.invoice_string;
+        v.not_blank("invoice_string", invoice)
+            .max_len("invoice_string", invoice, MAX_INVOICE_LEN);
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InvoiceCreatedResponse {
+    pub status: String,
+}
+
+/// `GET /game/:game_id/opponent-invoice`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OpponentInvoiceResponse {
+    pub invoice_string: String,
+}
+
+/// Request from frontend reporting that it paid the opponent's invoice
+/// (`/payment-done`), or that the opponent's payment is held on its own node
+/// (`/payment-received`)
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PaymentDoneRequest {
+    // placeholder for future fields if needed
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PaymentDoneResponse {
+    pub status: String,
+}
+
+/// `POST /backend`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SetBackendRequest {
+    pub backend: FiberBackend,
+}
+
+/// `GET /backend`, and the answer to switching it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BackendResponse {
+    pub backend: FiberBackend,
+    /// Backend that takes over once active games are settled
+    pub pending: Option<FiberBackend>,
+    pub active_games: usize,
+    pub fiber_rpc_url: Option<String>,
+}
+
+/// `GET /balance`: what this player's Fiber node can put up as stakes
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BalanceResponse {
+    pub backend: FiberBackend,
+    /// Local balance across the node's channels; unset on the mock backend
+    pub balance_shannons: Option<u64>,
+    /// Stakes of our games that are still to be paid. Paid stakes are
+    /// already held in the channels, off the balance.
+    pub reserved_shannons: u64,
+    /// Balance left for a new stake once the reserved ones are paid
+    pub spendable_shannons: Option<u64>,
+}
@@ -0,0 +1,167 @@
+//! One seat played through the player service's HTTP API, the other through
+//! the core library types, against the same oracle.
+
+use fiber_game_api::oracle::EncryptedPreimageResponse;
+use fiber_game_compat::{read_trace, LibraryPlayer, ServicePlayer, Services};
+use fiber_game_core::{
+    crypto::compute_signature_points,
+    games::{GameAction, GameType, RpsAction},
+    protocol::{AbortReason, GameResult, Player},
+};
+use fiber_game_player::state::PlayerGamePhase;
+
+fn hex0x(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+#[tokio::test]
+async fn test_service_a_against_library_b() {
+    let services = Services::spawn().await.unwrap();
+    let a = ServicePlayer::new(&services);
+    let b = LibraryPlayer::new(&services).await;
+
+    let game_id = a.create(GameType::RockPaperScissors, 1000).await;
+    let (session, seated) = b.join(game_id).await;
+    assert_eq!(seated.game_type, GameType::RockPaperScissors);
+    assert_eq!(seated.amount_shannons, 1000);
+    let session = session.fund();
+    let encrypted = b.send_encrypted_preimage(&session).await;
+
+    a.play(game_id, &GameAction::Rps(RpsAction::Rock)).await;
+    let scissors = GameAction::Rps(RpsAction::Scissors);
+    let session = b.commit(session, scissors.clone()).await;
+    let session = b.reveal(session).await;
+    let (judged, result) = b.result(session).await;
+    let status = a.status(game_id).await;
+
+    let view = read_trace(&b.trace(game_id).await, &b.oracle_pubkey);
+
+    // Everyone agrees on the outcome and the moves
+    assert_eq!(judged.state().result, GameResult::AWins);
+    assert_eq!(result.result, Some(GameResult::AWins));
+    assert_eq!(
+        view.result.as_ref().unwrap().result,
+        Some(GameResult::AWins)
+    );
+    assert_eq!(status.role, Player::A);
+    assert_eq!(status.phase, PlayerGamePhase::WaitingForResult);
+    assert_eq!(status.result, Some(GameResult::AWins));
+    assert_eq!(status.my_action, Some(GameAction::Rps(RpsAction::Rock)));
+    assert_eq!(status.my_action.as_ref(), view.actions.get(&Player::A));
+    assert_eq!(status.opponent_action, Some(scissors.clone()));
+    assert_eq!(view.actions.get(&Player::B), Some(&scissors));
+
+    // Payment hashes went through unchanged in both directions
+    assert_eq!(view.payment_hashes[&Player::B], judged.payment_hash());
+    assert_eq!(
+        status.my_payment_hash,
+        Some(hex0x(view.payment_hashes[&Player::A].as_bytes()))
+    );
+    assert_eq!(
+        judged.state().opponent_payment_hash,
+        view.payment_hashes[&Player::A]
+    );
+
+    // The winner got the loser's preimage, and only the winner
+    assert_eq!(
+        status.opponent_preimage,
+        Some(hex0x(judged.preimage().as_bytes()))
+    );
+    assert!(judged.state().opponent_preimage.is_none());
+
+    // The encrypted preimage B submitted is what the oracle relays, and opens
+    // with the point A learns by winning
+    let relayed: EncryptedPreimageResponse = b
+        .fetch(&format!("/game/{}/encrypted-preimage/B", game_id))
+        .await;
+    assert_eq!(relayed.encrypted_preimage.as_bytes(), encrypted.as_bytes());
+    let points = compute_signature_points(&b.oracle_pubkey, judged.commitment_point(), &game_id);
+    assert_eq!(
+        relayed
+            .encrypted_preimage
+            .decrypt(&points.a_wins)
+            .as_bytes(),
+        judged.preimage().as_bytes()
+    );
+}
+
+#[tokio::test]
+async fn test_library_a_against_service_b() {
+    let services = Services::spawn().await.unwrap();
+    let a = LibraryPlayer::new(&services).await;
+    let b = ServicePlayer::new(&services);
+
+    let (session, seated) = a.create(GameType::GuessNumber, 500).await;
+    let game_id = session.game_id();
+    b.join(game_id).await;
+    let session = a.opponent_joined(session).await.fund();
+
+    let guess = GameAction::GuessNumber(50);
+    let session = a.commit(session, guess.clone()).await;
+    let session = a.reveal(session).await;
+    b.play(game_id, &GameAction::GuessNumber(10)).await;
+    let (judged, result) = a.result(session).await;
+    let status = b.status(game_id).await;
+
+    let view = read_trace(&a.trace(game_id).await, &a.oracle_pubkey);
+
+    assert_eq!(status.role, Player::B);
+    assert_eq!(status.result, Some(judged.state().result));
+    assert_eq!(
+        view.result.as_ref().unwrap().result,
+        Some(judged.state().result)
+    );
+    assert_eq!(status.opponent_action, Some(guess));
+    assert_eq!(status.my_action.as_ref(), view.actions.get(&Player::B));
+
+    // The oracle's secret reads the same in the service and the library, and
+    // opens the commitment it made when the game was created
+    let secret = result.game_data.unwrap().oracle_secret.unwrap();
+    assert_eq!(status.oracle_secret_number, Some(secret.secret_number));
+    let commitment: [u8; 32] = hex::decode(seated.oracle_commitment.unwrap())
+        .unwrap()
+        .try_into()
+        .unwrap();
+    assert!(secret.to_secret().unwrap().verify_commitment(&commitment));
+
+    match judged.state().result {
+        GameResult::AWins => {
+            let preimage = judged.state().opponent_preimage.as_ref().unwrap();
+            assert!(view.payment_hashes[&Player::B].verify(preimage));
+            assert!(status.opponent_preimage.is_none());
+        }
+        GameResult::BWins => {
+            assert_eq!(
+                status.opponent_preimage,
+                Some(hex0x(judged.preimage().as_bytes()))
+            );
+            assert!(judged.state().opponent_preimage.is_none());
+        }
+        GameResult::Draw => {
+            assert!(status.opponent_preimage.is_none());
+            assert!(judged.state().opponent_preimage.is_none());
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_library_abort_seen_by_service() {
+    let services = Services::spawn().await.unwrap();
+    let a = ServicePlayer::new(&services);
+    let b = LibraryPlayer::new(&services).await;
+
+    let game_id = a.create(GameType::RockPaperScissors, 1000).await;
+    b.join(game_id).await;
+    b.abort(game_id, Player::B, AbortReason::PaymentFailed)
+        .await
+        .unwrap();
+
+    let status = a.status(game_id).await;
+    let view = read_trace(&b.trace(game_id).await, &b.oracle_pubkey);
+
+    assert_eq!(status.phase, PlayerGamePhase::Aborted);
+    assert_eq!(status.aborted_by, Some(Player::B));
+    assert_eq!(status.abort_reason, Some(AbortReason::PaymentFailed));
+    assert_eq!(view.aborted, Some((Player::B, AbortReason::PaymentFailed)));
+    assert!(view.result.is_none());
+}
@@ -1,5 +1,7 @@
 //! Commitment and Salt for commit-reveal scheme.
 
+use super::PaymentHash;
+use crate::protocol::GameId;
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
@@ -34,6 +36,27 @@ impl fmt::Debug for Salt {
     }
 }
 
+/// The game a commitment is made in, so its reveal opens it nowhere else
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitContext {
+    pub game_id: GameId,
+    /// Stake each player put up
+    pub amount_shannons: u64,
+    /// Payment hash of the committing player's opponent, which the
+    /// committer's winnings unlock
+    pub opponent_payment_hash: PaymentHash,
+}
+
+impl CommitContext {
+    fn to_bytes(self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(16 + 8 + 32);
+        bytes.extend_from_slice(self.game_id.as_bytes());
+        bytes.extend_from_slice(&self.amount_shannons.to_be_bytes());
+        bytes.extend_from_slice(self.opponent_payment_hash.as_bytes());
+        bytes
+    }
+}
+
 /// Commitment = H(action || salt)
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Commitment([u8; 32]);
@@ -48,6 +71,17 @@ impl Commitment {
         Self(result.into())
     }
 
+    /// Commit to an action in a game: H(action || game_id || amount u64 BE
+    /// || opponent_payment_hash || salt)
+    ///
+    /// The protocol's commitments are all bound, so a reveal can't be
+    /// replayed into another game, or the same one at another stake.
+    pub fn bound(action_bytes: &[u8], context: &CommitContext, salt: &Salt) -> Self {
+        let mut bound = action_bytes.to_vec();
+        bound.extend_from_slice(&context.to_bytes());
+        Self::new(&bound, salt)
+    }
+
     /// Create from raw bytes
     pub fn from_bytes(bytes: [u8; 32]) -> Self {
         Self(bytes)
@@ -62,6 +96,12 @@ impl Commitment {
     pub fn verify(&self, action_bytes: &[u8], salt: &Salt) -> bool {
         *self == Self::new(action_bytes, salt)
     }
+
+    /// Verify that the given action and salt produce this commitment in
+    /// `context`
+    pub fn verify_bound(&self, action_bytes: &[u8], context: &CommitContext, salt: &Salt) -> bool {
+        *self == Self::bound(action_bytes, context, salt)
+    }
 }
 
 impl fmt::Debug for Commitment {
@@ -117,6 +157,35 @@ mod tests {
         assert!(!commitment.verify(b"Paper", &salt));
     }
 
+    #[test]
+    fn test_bound_commitment_opens_only_in_its_context() {
+        let salt = Salt::random();
+        let context = CommitContext {
+            game_id: GameId::new(),
+            amount_shannons: 1000,
+            opponent_payment_hash: crate::crypto::Preimage::random().payment_hash(),
+        };
+        let commitment = Commitment::bound(b"Rock", &context, &salt);
+        assert!(commitment.verify_bound(b"Rock", &context, &salt));
+        assert!(!commitment.verify(b"Rock", &salt));
+
+        let other_game = CommitContext {
+            game_id: GameId::new(),
+            ..context
+        };
+        let other_stake = CommitContext {
+            amount_shannons: 2000,
+            ..context
+        };
+        let other_hash = CommitContext {
+            opponent_payment_hash: crate::crypto::Preimage::random().payment_hash(),
+            ..context
+        };
+        for other in [other_game, other_stake, other_hash] {
+            assert!(!commitment.verify_bound(b"Rock", &other, &salt));
+        }
+    }
+
     #[test]
     fn test_wrong_salt_fails_verification() {
         let action = b"Rock";
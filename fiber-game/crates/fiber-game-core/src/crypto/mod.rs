@@ -4,9 +4,9 @@ mod commitment;
 mod encrypted_preimage;
 mod signature_point;
 
-pub use commitment::{Commitment, Salt};
+pub use commitment::{CommitContext, Commitment, Salt};
 pub use encrypted_preimage::EncryptedPreimage;
 pub use signature_point::{compute_signature_points, SignaturePoint, SignaturePoints};
 
 // Re-export from fiber-core
-pub use fiber_core::{PaymentHash, Preimage};
+pub use fiber_core::{Keyring, KeyringError, PaymentHash, Preimage};
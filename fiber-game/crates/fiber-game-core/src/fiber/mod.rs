@@ -3,5 +3,8 @@
 //! Re-exports from fiber-core for backward compatibility.
 
 pub use fiber_core::{
-    FiberClient, FiberError, HoldInvoice, MockFiberClient, PaymentId, PaymentStatus, RpcFiberClient,
+    FiberClient, FiberError, HoldInvoice, InvoiceMemo, MockCall, MockFiberClient, PaymentId,
+    PaymentStatus,
 };
+#[cfg(feature = "rpc")]
+pub use fiber_core::RpcFiberClient;
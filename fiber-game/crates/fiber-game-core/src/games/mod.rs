@@ -1,9 +1,15 @@
 //! Game definitions and logic.
 
 mod guess_number;
+mod nim;
+mod pennies;
+mod registry;
 mod rps;
 mod traits;
 
 pub use guess_number::{GuessNumberGame, OracleSecret};
+pub use nim::{NimGame, NIM_MAX_TAKE, NIM_PILE};
+pub use pennies::{PenniesAction, PenniesGame};
+pub use registry::{game, GameEntry, GAMES};
 pub use rps::{RpsAction, RpsGame};
 pub use traits::{GameAction, GameJudge, GameType};
@@ -0,0 +1,83 @@
+//! One-shot Nim game implementation.
+
+use super::traits::{GameAction, GameJudge};
+use super::OracleSecret;
+use crate::protocol::GameResult;
+
+/// Stones in the pile at the start of the game
+pub const NIM_PILE: u8 = 8;
+
+/// Most stones a player may take
+pub const NIM_MAX_TAKE: u8 = 3;
+
+/// One-shot Nim
+///
+/// Both players secretly choose how many stones (1-3) to take from a pile of
+/// [`NIM_PILE`]. Player A's take is removed first, then Player B's. The rest
+/// of the pile is then played out perfectly, turns alternating from Player A
+/// and 1-3 stones a turn, and whoever takes the last stone wins. Facing a
+/// multiple of four loses, so Player B wins exactly when the two takes add up
+/// to four.
+pub struct NimGame;
+
+impl GameJudge for NimGame {
+    fn judge(
+        action_a: &GameAction,
+        action_b: &GameAction,
+        _oracle_secret: Option<&OracleSecret>,
+    ) -> GameResult {
+        let (take_a, take_b) = match (action_a, action_b) {
+            (GameAction::Nim(a), GameAction::Nim(b)) => (*a, *b),
+            _ => panic!("Invalid action type for Nim game"),
+        };
+
+        // Player A moves next, on whatever is left
+        let left = NIM_PILE - take_a - take_b;
+        if left.is_multiple_of(NIM_MAX_TAKE + 1) {
+            GameResult::BWins
+        } else {
+            GameResult::AWins
+        }
+    }
+
+    fn validate_action(action: &GameAction) -> bool {
+        matches!(action, GameAction::Nim(n) if (1..=NIM_MAX_TAKE).contains(n))
+    }
+
+    fn requires_oracle_secret() -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn judge_nim(a: u8, b: u8) -> GameResult {
+        NimGame::judge(&GameAction::Nim(a), &GameAction::Nim(b), None)
+    }
+
+    #[test]
+    fn test_nim_b_wins_leaving_a_multiple_of_four() {
+        assert_eq!(judge_nim(1, 3), GameResult::BWins);
+        assert_eq!(judge_nim(2, 2), GameResult::BWins);
+        assert_eq!(judge_nim(3, 1), GameResult::BWins);
+    }
+
+    #[test]
+    fn test_nim_a_wins_otherwise() {
+        assert_eq!(judge_nim(1, 1), GameResult::AWins);
+        assert_eq!(judge_nim(3, 3), GameResult::AWins);
+        assert_eq!(judge_nim(2, 3), GameResult::AWins);
+    }
+
+    #[test]
+    fn test_nim_validate_action() {
+        assert!(NimGame::validate_action(&GameAction::Nim(1)));
+        assert!(NimGame::validate_action(&GameAction::Nim(3)));
+        assert!(!NimGame::validate_action(&GameAction::Nim(0)));
+        assert!(!NimGame::validate_action(&GameAction::Nim(4)));
+        assert!(!NimGame::validate_action(&GameAction::GuessNumber(2)));
+        assert!(!NimGame::requires_oracle_secret());
+    }
+}
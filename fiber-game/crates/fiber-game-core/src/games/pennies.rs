@@ -0,0 +1,100 @@
+//! Matching Pennies game implementation.
+
+use super::traits::{GameAction, GameJudge};
+use super::OracleSecret;
+use crate::protocol::GameResult;
+use serde::{Deserialize, Serialize};
+
+/// Side of the penny a player shows
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PenniesAction {
+    Heads,
+    Tails,
+}
+
+impl PenniesAction {
+    /// Convert to bytes for commitment
+    pub fn to_bytes(&self) -> &[u8] {
+        match self {
+            PenniesAction::Heads => b"Heads",
+            PenniesAction::Tails => b"Tails",
+        }
+    }
+}
+
+/// Matching Pennies game
+///
+/// Player A wins if both pennies show the same side, Player B if they
+/// differ. There are no draws.
+pub struct PenniesGame;
+
+impl GameJudge for PenniesGame {
+    fn judge(
+        action_a: &GameAction,
+        action_b: &GameAction,
+        _oracle_secret: Option<&OracleSecret>,
+    ) -> GameResult {
+        let (penny_a, penny_b) = match (action_a, action_b) {
+            (GameAction::Pennies(a), GameAction::Pennies(b)) => (a, b),
+            _ => panic!("Invalid action type for Matching Pennies game"),
+        };
+
+        if penny_a == penny_b {
+            GameResult::AWins
+        } else {
+            GameResult::BWins
+        }
+    }
+
+    fn validate_action(action: &GameAction) -> bool {
+        matches!(action, GameAction::Pennies(_))
+    }
+
+    fn requires_oracle_secret() -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn judge_pennies(a: PenniesAction, b: PenniesAction) -> GameResult {
+        PenniesGame::judge(&GameAction::Pennies(a), &GameAction::Pennies(b), None)
+    }
+
+    #[test]
+    fn test_pennies_match_wins_for_a() {
+        assert_eq!(
+            judge_pennies(PenniesAction::Heads, PenniesAction::Heads),
+            GameResult::AWins
+        );
+        assert_eq!(
+            judge_pennies(PenniesAction::Tails, PenniesAction::Tails),
+            GameResult::AWins
+        );
+    }
+
+    #[test]
+    fn test_pennies_mismatch_wins_for_b() {
+        assert_eq!(
+            judge_pennies(PenniesAction::Heads, PenniesAction::Tails),
+            GameResult::BWins
+        );
+        assert_eq!(
+            judge_pennies(PenniesAction::Tails, PenniesAction::Heads),
+            GameResult::BWins
+        );
+    }
+
+    #[test]
+    fn test_pennies_validate_action() {
+        assert!(PenniesGame::validate_action(&GameAction::Pennies(
+            PenniesAction::Heads
+        )));
+        assert!(!PenniesGame::validate_action(&GameAction::Rps(
+            crate::games::RpsAction::Rock
+        )));
+        assert!(!PenniesGame::requires_oracle_secret());
+    }
+}
@@ -0,0 +1,93 @@
+//! Game registry: what the protocol knows about each [`GameType`].
+//!
+//! The oracle, the players and trace verification look games up here rather
+//! than matching on the game type, so a new game is its [`GameJudge`]
+//! implementation plus one entry in [`GAMES`].
+
+use super::traits::{GameAction, GameJudge, GameType};
+use super::{GuessNumberGame, NimGame, OracleSecret, PenniesGame, RpsGame};
+use crate::protocol::GameResult;
+
+/// A registered game
+pub struct GameEntry {
+    pub game_type: GameType,
+    /// Human-readable name
+    pub name: &'static str,
+    judge: fn(&GameAction, &GameAction, Option<&OracleSecret>) -> GameResult,
+    validate_action: fn(&GameAction) -> bool,
+    requires_oracle_secret: fn() -> bool,
+}
+
+impl GameEntry {
+    /// Register `G` as the judge of `game_type`.
+    pub const fn new<G: GameJudge>(game_type: GameType, name: &'static str) -> Self {
+        Self {
+            game_type,
+            name,
+            judge: G::judge,
+            validate_action: G::validate_action,
+            requires_oracle_secret: G::requires_oracle_secret,
+        }
+    }
+
+    /// Determine the winner; see [`GameJudge::judge`].
+    pub fn judge(
+        &self,
+        action_a: &GameAction,
+        action_b: &GameAction,
+        oracle_secret: Option<&OracleSecret>,
+    ) -> GameResult {
+        (self.judge)(action_a, action_b, oracle_secret)
+    }
+
+    /// Validate that an action is legal for this game
+    pub fn validate_action(&self, action: &GameAction) -> bool {
+        (self.validate_action)(action)
+    }
+
+    /// Does this game require Oracle to commit a secret beforehand?
+    pub fn requires_oracle_secret(&self) -> bool {
+        (self.requires_oracle_secret)()
+    }
+}
+
+/// Every game the protocol can play
+pub static GAMES: &[GameEntry] = &[
+    GameEntry::new::<RpsGame>(GameType::RockPaperScissors, "Rock Paper Scissors"),
+    GameEntry::new::<GuessNumberGame>(GameType::GuessNumber, "Guess the Number"),
+    GameEntry::new::<PenniesGame>(GameType::MatchingPennies, "Matching Pennies"),
+    GameEntry::new::<NimGame>(GameType::Nim, "Nim"),
+];
+
+/// Look up the registered game for `game_type`.
+pub fn game(game_type: GameType) -> &'static GameEntry {
+    GAMES
+        .iter()
+        .find(|g| g.game_type == game_type)
+        .expect("every game type is registered")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::RpsAction;
+
+    #[test]
+    fn test_every_game_type_is_registered_once() {
+        for game_type in GameType::ALL {
+            let entries = GAMES.iter().filter(|g| g.game_type == game_type).count();
+            assert_eq!(entries, 1, "{game_type:?}");
+        }
+    }
+
+    #[test]
+    fn test_registry_dispatches_to_judge() {
+        let rps = game(GameType::RockPaperScissors);
+        let rock = GameAction::Rps(RpsAction::Rock);
+        let scissors = GameAction::Rps(RpsAction::Scissors);
+        assert_eq!(rps.judge(&rock, &scissors, None), GameResult::AWins);
+        assert!(rps.validate_action(&rock));
+        assert!(!rps.validate_action(&GameAction::Nim(1)));
+        assert!(game(GameType::GuessNumber).requires_oracle_secret());
+    }
+}
@@ -8,15 +8,22 @@ use serde::{Deserialize, Serialize};
 pub enum GameType {
     RockPaperScissors,
     GuessNumber,
+    MatchingPennies,
+    Nim,
 }
 
 impl GameType {
+    /// Every game type
+    pub const ALL: [GameType; 4] = [
+        GameType::RockPaperScissors,
+        GameType::GuessNumber,
+        GameType::MatchingPennies,
+        GameType::Nim,
+    ];
+
     /// Does this game require Oracle to commit a secret beforehand?
     pub fn requires_oracle_secret(&self) -> bool {
-        match self {
-            GameType::RockPaperScissors => false,
-            GameType::GuessNumber => true,
-        }
+        super::game(*self).requires_oracle_secret()
     }
 }
 
@@ -25,6 +32,8 @@ impl GameType {
 pub enum GameAction {
     Rps(super::RpsAction),
     GuessNumber(u8), // 0-99
+    Pennies(super::PenniesAction),
+    Nim(u8), // stones taken, 1-3
 }
 
 impl GameAction {
@@ -33,16 +42,24 @@ impl GameAction {
         match self {
             GameAction::Rps(action) => action.to_bytes().to_vec(),
             GameAction::GuessNumber(n) => vec![*n],
+            GameAction::Pennies(action) => action.to_bytes().to_vec(),
+            GameAction::Nim(n) => vec![*n],
+        }
+    }
+
+    /// The game this action is a move in
+    pub fn game_type(&self) -> GameType {
+        match self {
+            GameAction::Rps(_) => GameType::RockPaperScissors,
+            GameAction::GuessNumber(_) => GameType::GuessNumber,
+            GameAction::Pennies(_) => GameType::MatchingPennies,
+            GameAction::Nim(_) => GameType::Nim,
         }
     }
 
     /// Validate that this action is legal for the given game type
     pub fn validate(&self, game_type: GameType) -> bool {
-        match (self, game_type) {
-            (GameAction::Rps(_), GameType::RockPaperScissors) => true,
-            (GameAction::GuessNumber(n), GameType::GuessNumber) => *n < 100,
-            _ => false,
-        }
+        super::game(game_type).validate_action(self)
     }
 }
 
@@ -0,0 +1,233 @@
+//! Wire encodings for protocol messages.
+//!
+//! Protocol messages can travel as JSON (the default, easy to read in logs
+//! and browsers) or as CBOR, which is more compact: no quotes or separators,
+//! and hashes (byte arrays) take about half the space. Over HTTP the encoding
+//! is chosen by `Content-Type` and `Accept`; on the direct player link by the
+//! WebSocket frame type.
+//!
+//! CBOR carries the same data model as JSON: IDs and keys stay strings
+//! rather than the raw bytes some types switch to in binary formats. A
+//! message can therefore be decoded from either encoding without knowing its
+//! type, and signatures are made over [`canonical_cbor`] of that model, so a
+//! message verifies the same whichever way it was carried.
+
+use ciborium::Value;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Content type for JSON messages
+pub const JSON_CONTENT_TYPE: &str = "application/json";
+
+/// Content type for CBOR messages
+pub const CBOR_CONTENT_TYPE: &str = "application/cbor";
+
+/// Errors from encoding or decoding a message
+#[derive(Debug, Error)]
+pub enum EncodingError {
+    #[error("invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("invalid CBOR: {0}")]
+    Cbor(String),
+}
+
+/// How a message is encoded on the wire
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    #[default]
+    Json,
+    Cbor,
+}
+
+impl Encoding {
+    /// MIME type for this encoding
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Encoding::Json => JSON_CONTENT_TYPE,
+            Encoding::Cbor => CBOR_CONTENT_TYPE,
+        }
+    }
+
+    /// Encoding named by a `Content-Type` header, ignoring parameters such
+    /// as `charset`.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type.split(';').next().unwrap_or("").trim();
+        if mime.eq_ignore_ascii_case(JSON_CONTENT_TYPE) {
+            Some(Encoding::Json)
+        } else if mime.eq_ignore_ascii_case(CBOR_CONTENT_TYPE) {
+            Some(Encoding::Cbor)
+        } else {
+            None
+        }
+    }
+
+    /// Encoding to answer with, given an `Accept` header: CBOR if the client
+    /// lists it, JSON otherwise.
+    pub fn from_accept(accept: &str) -> Self {
+        let wants_cbor = accept
+            .split(',')
+            .any(|item| Self::from_content_type(item) == Some(Encoding::Cbor));
+        if wants_cbor {
+            Encoding::Cbor
+        } else {
+            Encoding::Json
+        }
+    }
+
+    pub fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>, EncodingError> {
+        match self {
+            Encoding::Json => Ok(serde_json::to_vec(value)?),
+            Encoding::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::into_writer(&serde_json::to_value(value)?, &mut bytes)
+                    .map_err(|e| EncodingError::Cbor(e.to_string()))?;
+                Ok(bytes)
+            }
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, EncodingError> {
+        match self {
+            Encoding::Json => Ok(serde_json::from_slice(bytes)?),
+            Encoding::Cbor => {
+                let value: serde_json::Value =
+                    ciborium::from_reader(bytes).map_err(|e| EncodingError::Cbor(e.to_string()))?;
+                Ok(serde_json::from_value(value)?)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Encoding::Json => write!(f, "json"),
+            Encoding::Cbor => write!(f, "cbor"),
+        }
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(Encoding::Json),
+            "cbor" => Ok(Encoding::Cbor),
+            other => Err(format!(
+                "unknown encoding '{}' (expected json or cbor)",
+                other
+            )),
+        }
+    }
+}
+
+/// Deterministic CBOR for `value`, as signed by [`crate::protocol::Envelope`].
+///
+/// Integers use their shortest form (ciborium always does this) and map
+/// entries are sorted by the bytes of their encoded keys, as in RFC 8949
+/// §4.2.1. Struct field order and the encoding a message arrived in
+/// therefore don't change the result.
+pub fn canonical_cbor<T: Serialize>(value: &T) -> Result<Vec<u8>, EncodingError> {
+    let value = Value::serialized(&serde_json::to_value(value)?)
+        .map_err(|e| EncodingError::Cbor(e.to_string()))?;
+    write_cbor(&canonicalize(value)?)
+}
+
+fn write_cbor(value: &Value) -> Result<Vec<u8>, EncodingError> {
+    let mut bytes = Vec::new();
+    ciborium::into_writer(value, &mut bytes).map_err(|e| EncodingError::Cbor(e.to_string()))?;
+    Ok(bytes)
+}
+
+fn canonicalize(value: Value) -> Result<Value, EncodingError> {
+    Ok(match value {
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(canonicalize)
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Map(entries) => {
+            let mut keyed = entries
+                .into_iter()
+                .map(|(k, v)| {
+                    let k = canonicalize(k)?;
+                    Ok((write_cbor(&k)?, k, canonicalize(v)?))
+                })
+                .collect::<Result<Vec<_>, EncodingError>>()?;
+            keyed.sort_by(|a, b| a.0.cmp(&b.0));
+            Value::Map(keyed.into_iter().map(|(_, k, v)| (k, v)).collect())
+        }
+        Value::Tag(tag, inner) => Value::Tag(tag, Box::new(canonicalize(*inner)?)),
+        other => other,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{Commitment, Preimage, Salt};
+    use crate::protocol::{CommitMessage, GameId, Player};
+
+    fn commit_message() -> CommitMessage {
+        CommitMessage {
+            game_id: GameId::new(),
+            player: Player::B,
+            commitment: Commitment::new(b"Paper", &Salt::random()),
+            amount_shannons: 1000,
+            opponent_payment_hash: Preimage::random().payment_hash(),
+        }
+    }
+
+    #[test]
+    fn test_roundtrip_and_size() {
+        let msg = commit_message();
+        let json = Encoding::Json.encode(&msg).unwrap();
+        let cbor = Encoding::Cbor.encode(&msg).unwrap();
+        assert!(cbor.len() < json.len());
+
+        let decoded: CommitMessage = Encoding::Cbor.decode(&cbor).unwrap();
+        assert_eq!(decoded.game_id, msg.game_id);
+        assert_eq!(decoded.commitment, msg.commitment);
+    }
+
+    #[test]
+    fn test_canonical_form_ignores_encoding_and_key_order() {
+        let msg = commit_message();
+        let typed = canonical_cbor(&msg).unwrap();
+
+        // The same message after a trip through either encoding
+        let via_json: serde_json::Value = Encoding::Json
+            .decode(&Encoding::Json.encode(&msg).unwrap())
+            .unwrap();
+        let via_cbor: serde_json::Value = Encoding::Cbor
+            .decode(&Encoding::Cbor.encode(&msg).unwrap())
+            .unwrap();
+        assert_eq!(canonical_cbor(&via_json).unwrap(), typed);
+        assert_eq!(canonical_cbor(&via_cbor).unwrap(), typed);
+
+        let a = serde_json::json!({ "b": 1, "a": [2, { "y": 3, "x": 4 }] });
+        let b = serde_json::json!({ "a": [2, { "x": 4, "y": 3 }], "b": 1 });
+        assert_eq!(canonical_cbor(&a).unwrap(), canonical_cbor(&b).unwrap());
+    }
+
+    #[test]
+    fn test_content_negotiation() {
+        assert_eq!(
+            Encoding::from_content_type("application/json; charset=utf-8"),
+            Some(Encoding::Json)
+        );
+        assert_eq!(Encoding::from_content_type("text/plain"), None);
+        assert_eq!(
+            Encoding::from_accept("application/cbor, application/json;q=0.5"),
+            Encoding::Cbor
+        );
+        assert_eq!(Encoding::from_accept("*/*"), Encoding::Json);
+        assert_eq!("CBOR".parse::<Encoding>(), Ok(Encoding::Cbor));
+    }
+}
@@ -0,0 +1,416 @@
+//! Signed, versioned protocol messages.
+//!
+//! Every message a player sends to the oracle, and every game result the
+//! oracle hands back, travels inside an [`Envelope`]: the payload plus the
+//! protocol version, the sender's public key, a nonce, an optional expiry,
+//! the game it is meant for (if any) and an ECDSA signature over all of
+//! them. The receiver checks the
+//! signature with [`Envelope::open`] (any sender) or [`Envelope::open_from`]
+//! (a sender it already knows) before looking at the payload.
+//!
+//! Nonces only ever increase within a process and start from the clock, so
+//! they keep increasing across restarts too. A receiver that remembers the
+//! last nonce it accepted from a sender can refuse anything at or below it,
+//! which stops a captured message being replayed; [`Envelope::seal_expiring`]
+//! also bounds how long a message can be held back before it's used. Nonces
+//! are counted per sender, not per game, so a receiver tracking them per
+//! game also needs [`Envelope::seal_for`]: the game ID is signed with the
+//! message, and a message sealed for one game is refused by every other,
+//! whatever its nonce. Expiry
+//! is checked by the receiver on arrival ([`Envelope::is_expired`]), not by
+//! [`Envelope::verify`], so a stored message stays verifiable afterwards.
+//!
+//! The signed digest covers the payload in [canonical CBOR](canonical_cbor),
+//! so an envelope verifies whether it travelled as JSON or CBOR. Receivers
+//! that decode into their own request types should take an
+//! `Envelope<serde_json::Value>` and use [`Envelope::open_as`], so the
+//! signature is checked against exactly the fields the sender signed.
+
+use crate::protocol::encoding::{canonical_cbor, EncodingError};
+use crate::protocol::messages::signature_serde;
+use crate::protocol::types::{pubkey_serde, GameId};
+use fiber_errors::{Coded, ErrorCode};
+use secp256k1::{ecdsa::Signature, Message, PublicKey, SecretKey, SECP256K1};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+/// Version of the oracle/player message protocol
+///
+/// Version 2 signs canonical CBOR instead of sorted-key JSON; version 3 adds
+/// the expiry and makes nonces increasing; version 4 binds commitments to
+/// the game, stake and opponent's payment hash; version 5 signs the game a
+/// submission is for.
+pub const PROTOCOL_VERSION: u16 = 5;
+
+/// Domain separator, so envelope signatures can't be confused with any other
+/// signature made by the same key
+const DOMAIN_TAG: &[u8] = b"fiber-game/envelope";
+
+/// Errors from sealing or opening an [`Envelope`]
+#[derive(Debug, Error)]
+pub enum EnvelopeError {
+    #[error("unsupported protocol version {0} (expected {PROTOCOL_VERSION})")]
+    UnsupportedVersion(u16),
+
+    #[error("invalid envelope signature")]
+    InvalidSignature,
+
+    #[error("message signed by an unexpected key")]
+    UnexpectedSender,
+
+    #[error("invalid payload: {0}")]
+    Encoding(#[from] EncodingError),
+}
+
+impl Coded for EnvelopeError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            EnvelopeError::UnsupportedVersion(_) | EnvelopeError::Encoding(_) => {
+                ErrorCode::BadRequest
+            }
+            EnvelopeError::InvalidSignature => ErrorCode::InvalidSignature,
+            EnvelopeError::UnexpectedSender => ErrorCode::Forbidden,
+        }
+    }
+}
+
+/// A payload signed by its sender
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Envelope<T> {
+    pub version: u16,
+    #[serde(with = "pubkey_serde")]
+    pub sender: PublicKey,
+    /// Increases with every envelope the sender seals
+    pub nonce: u64,
+    /// Milliseconds since the Unix epoch after which the receiver should
+    /// refuse this message; `None` for messages meant to be kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at_ms: Option<u64>,
+    /// Game the message is meant for; `None` for messages that aren't about
+    /// one game, such as creating a game
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game_id: Option<GameId>,
+    pub payload: T,
+    #[serde(with = "signature_serde")]
+    pub signature: [u8; 64],
+}
+
+impl<T: Serialize> Envelope<T> {
+    /// Sign `payload` with `secret_key`, with no expiry.
+    pub fn seal(payload: T, secret_key: &SecretKey) -> Result<Self, EnvelopeError> {
+        Self::seal_with(payload, secret_key, None, None)
+    }
+
+    /// Sign `payload` with `secret_key`, to be refused once `ttl` has passed.
+    pub fn seal_expiring(
+        payload: T,
+        secret_key: &SecretKey,
+        ttl: Duration,
+    ) -> Result<Self, EnvelopeError> {
+        let expires_at_ms = now_ms().saturating_add(ttl.as_millis() as u64);
+        Self::seal_with(payload, secret_key, Some(expires_at_ms), None)
+    }
+
+    /// Sign `payload` with `secret_key` for `game_id` only, to be refused
+    /// once `ttl` has passed.
+    pub fn seal_for(
+        game_id: GameId,
+        payload: T,
+        secret_key: &SecretKey,
+        ttl: Duration,
+    ) -> Result<Self, EnvelopeError> {
+        let expires_at_ms = now_ms().saturating_add(ttl.as_millis() as u64);
+        Self::seal_with(payload, secret_key, Some(expires_at_ms), Some(game_id))
+    }
+
+    fn seal_with(
+        payload: T,
+        secret_key: &SecretKey,
+        expires_at_ms: Option<u64>,
+        game_id: Option<GameId>,
+    ) -> Result<Self, EnvelopeError> {
+        let sender = PublicKey::from_secret_key(SECP256K1, secret_key);
+        let nonce = next_nonce();
+        let digest = signing_digest(
+            PROTOCOL_VERSION,
+            &sender,
+            nonce,
+            expires_at_ms,
+            game_id,
+            &payload,
+        )?;
+        let signature = SECP256K1
+            .sign_ecdsa(&digest, secret_key)
+            .serialize_compact();
+
+        Ok(Self {
+            version: PROTOCOL_VERSION,
+            sender,
+            nonce,
+            expires_at_ms,
+            game_id,
+            payload,
+            signature,
+        })
+    }
+
+    /// Check the version and that `sender` signed this envelope.
+    pub fn verify(&self) -> Result<(), EnvelopeError> {
+        if self.version != PROTOCOL_VERSION {
+            return Err(EnvelopeError::UnsupportedVersion(self.version));
+        }
+        let digest = signing_digest(
+            self.version,
+            &self.sender,
+            self.nonce,
+            self.expires_at_ms,
+            self.game_id,
+            &self.payload,
+        )?;
+        let signature = Signature::from_compact(&self.signature)
+            .map_err(|_| EnvelopeError::InvalidSignature)?;
+        SECP256K1
+            .verify_ecdsa(&digest, &signature, &self.sender)
+            .map_err(|_| EnvelopeError::InvalidSignature)
+    }
+
+    /// The 32-byte digest the signature is made over, for checking another
+    /// implementation against this one.
+    pub fn digest(&self) -> Result<[u8; 32], EnvelopeError> {
+        let digest = signing_digest(
+            self.version,
+            &self.sender,
+            self.nonce,
+            self.expires_at_ms,
+            self.game_id,
+            &self.payload,
+        )?;
+        Ok(*digest.as_ref())
+    }
+
+    /// Whether the expiry has passed. Envelopes without one never expire.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at_ms.is_some_and(|at| now_ms() > at)
+    }
+
+    /// Whether the expiry is more than `ttl` from now, keeping the envelope
+    /// usable for longer than the receiver is willing to allow.
+    pub fn expires_after(&self, ttl: Duration) -> bool {
+        let limit = now_ms().saturating_add(ttl.as_millis() as u64);
+        self.expires_at_ms.is_some_and(|at| at > limit)
+    }
+
+    /// Verify the envelope and return the sender with the payload.
+    pub fn open(self) -> Result<(PublicKey, T), EnvelopeError> {
+        self.verify()?;
+        Ok((self.sender, self.payload))
+    }
+
+    /// Verify the envelope was signed by `expected` and return the payload.
+    pub fn open_from(self, expected: &PublicKey) -> Result<T, EnvelopeError> {
+        if self.sender != *expected {
+            return Err(EnvelopeError::UnexpectedSender);
+        }
+        self.open().map(|(_, payload)| payload)
+    }
+}
+
+impl Envelope<serde_json::Value> {
+    /// Verify the envelope, then decode the payload as `T`.
+    pub fn open_as<T: DeserializeOwned>(self) -> Result<(PublicKey, T), EnvelopeError> {
+        let (sender, payload) = self.open()?;
+        let payload = serde_json::from_value(payload).map_err(EncodingError::from)?;
+        Ok((sender, payload))
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Next envelope nonce: the current time in microseconds, or one past the
+/// last nonce if the clock hasn't moved on (or went backwards).
+fn next_nonce() -> u64 {
+    static LAST: AtomicU64 = AtomicU64::new(0);
+    let now_us = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_micros() as u64)
+        .unwrap_or(0);
+    let mut last = LAST.load(Ordering::Relaxed);
+    loop {
+        let next = now_us.max(last + 1);
+        match LAST.compare_exchange_weak(last, next, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => return next,
+            Err(actual) => last = actual,
+        }
+    }
+}
+
+/// SHA-256 over the domain tag, header fields and canonical payload CBOR.
+fn signing_digest<T: Serialize>(
+    version: u16,
+    sender: &PublicKey,
+    nonce: u64,
+    expires_at_ms: Option<u64>,
+    game_id: Option<GameId>,
+    payload: &T,
+) -> Result<Message, EnvelopeError> {
+    let canonical = canonical_cbor(payload)?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(DOMAIN_TAG);
+    hasher.update(version.to_be_bytes());
+    hasher.update(sender.serialize());
+    hasher.update(nonce.to_be_bytes());
+    match expires_at_ms {
+        Some(at) => {
+            hasher.update([1]);
+            hasher.update(at.to_be_bytes());
+        }
+        None => hasher.update([0]),
+    }
+    match game_id {
+        Some(id) => {
+            hasher.update([1]);
+            hasher.update(id.as_uuid().as_bytes());
+        }
+        None => hasher.update([0]),
+    }
+    hasher.update(&canonical);
+    Ok(Message::from_digest(hasher.finalize().into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{Commitment, Preimage, Salt};
+    use crate::protocol::{CommitMessage, Encoding, GameId, Player};
+
+    fn commit_message() -> CommitMessage {
+        CommitMessage {
+            game_id: GameId::new(),
+            player: Player::A,
+            commitment: Commitment::new(b"Rock", &Salt::random()),
+            amount_shannons: 1000,
+            opponent_payment_hash: Preimage::random().payment_hash(),
+        }
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        let key = SecretKey::new(&mut rand::thread_rng());
+        let sender = PublicKey::from_secret_key(SECP256K1, &key);
+        let msg = commit_message();
+
+        let envelope = Envelope::seal(msg.clone(), &key).unwrap();
+        assert_eq!(envelope.version, PROTOCOL_VERSION);
+
+        // Survives the wire in either encoding, and decodes into an untyped
+        // payload too
+        let json = serde_json::to_string(&envelope).unwrap();
+        let typed: Envelope<CommitMessage> = serde_json::from_str(&json).unwrap();
+        let cbor = Encoding::Cbor.encode(&envelope).unwrap();
+        let untyped: Envelope<serde_json::Value> = Encoding::Cbor.decode(&cbor).unwrap();
+
+        let opened = typed.open_from(&sender).unwrap();
+        assert_eq!(opened.commitment, msg.commitment);
+        let (from, decoded) = untyped.open_as::<CommitMessage>().unwrap();
+        assert_eq!(from, sender);
+        assert_eq!(decoded.game_id, msg.game_id);
+    }
+
+    #[test]
+    fn test_tampered_envelope_rejected() {
+        let key = SecretKey::new(&mut rand::thread_rng());
+        let envelope = Envelope::seal(commit_message(), &key).unwrap();
+
+        let mut payload = envelope.clone();
+        payload.payload.player = Player::B;
+        assert!(matches!(
+            payload.verify(),
+            Err(EnvelopeError::InvalidSignature)
+        ));
+
+        let mut nonce = envelope.clone();
+        nonce.nonce ^= 1;
+        assert!(matches!(
+            nonce.verify(),
+            Err(EnvelopeError::InvalidSignature)
+        ));
+
+        let mut expiry = envelope.clone();
+        expiry.expires_at_ms = Some(u64::MAX);
+        assert!(matches!(
+            expiry.verify(),
+            Err(EnvelopeError::InvalidSignature)
+        ));
+
+        let mut game = envelope.clone();
+        game.game_id = Some(GameId::new());
+        assert!(matches!(
+            game.verify(),
+            Err(EnvelopeError::InvalidSignature)
+        ));
+
+        let mut version = envelope.clone();
+        version.version = PROTOCOL_VERSION + 1;
+        assert!(matches!(
+            version.verify(),
+            Err(EnvelopeError::UnsupportedVersion(_))
+        ));
+
+        let other = PublicKey::from_secret_key(SECP256K1, &SecretKey::new(&mut rand::thread_rng()));
+        assert!(matches!(
+            envelope.open_from(&other),
+            Err(EnvelopeError::UnexpectedSender)
+        ));
+    }
+
+    #[test]
+    fn test_nonces_increase_and_expiry() {
+        let key = SecretKey::new(&mut rand::thread_rng());
+        let first = Envelope::seal(commit_message(), &key).unwrap();
+        let second = Envelope::seal(commit_message(), &key).unwrap();
+        assert!(second.nonce > first.nonce);
+        assert!(!first.is_expired());
+
+        let expiring =
+            Envelope::seal_expiring(commit_message(), &key, Duration::from_secs(60)).unwrap();
+        assert!(expiring.nonce > second.nonce);
+        assert!(!expiring.is_expired());
+        assert!(!expiring.expires_after(Duration::from_secs(120)));
+        assert!(expiring.expires_after(Duration::from_secs(30)));
+        expiring.verify().unwrap();
+
+        let stale = Envelope::seal_expiring(commit_message(), &key, Duration::ZERO).unwrap();
+        std::thread::sleep(Duration::from_millis(2));
+        assert!(stale.is_expired());
+        // Still a valid signature, for anyone checking it later
+        stale.verify().unwrap();
+    }
+
+    #[test]
+    fn test_sealed_for_one_game() {
+        let key = SecretKey::new(&mut rand::thread_rng());
+        let msg = commit_message();
+        let envelope =
+            Envelope::seal_for(msg.game_id, msg, &key, Duration::from_secs(60)).unwrap();
+        assert_eq!(envelope.game_id, Some(envelope.payload.game_id));
+        assert!(envelope.expires_at_ms.is_some());
+        envelope.verify().unwrap();
+
+        // Moving it to another game breaks the signature
+        let mut moved = envelope.clone();
+        moved.game_id = Some(GameId::new());
+        assert!(matches!(moved.verify(), Err(EnvelopeError::InvalidSignature)));
+        let mut unbound = envelope;
+        unbound.game_id = None;
+        assert!(matches!(unbound.verify(), Err(EnvelopeError::InvalidSignature)));
+    }
+}
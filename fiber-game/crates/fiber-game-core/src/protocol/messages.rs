@@ -1,9 +1,10 @@
 //! Protocol messages.
 
-use crate::crypto::{Commitment, EncryptedPreimage, PaymentHash};
-use crate::games::GameAction;
-use crate::protocol::{GameId, GameResult, Player};
+use crate::crypto::{CommitContext, Commitment, EncryptedPreimage, PaymentHash, Salt};
+use crate::games::{GameAction, OracleSecret};
+use crate::protocol::{DrawPolicy, GameId, GameResult, MatchRound, Player};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 /// Hold invoice information
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -21,12 +22,43 @@ pub struct EncryptedPreimageExchange {
     pub encrypted_preimage: EncryptedPreimage,
 }
 
+/// Phase 3: a player confirms the opponent's stake is locked
+///
+/// Sent once the hold invoice the player created with the opponent's
+/// `payment_hash` has been paid and is held on their own Fiber node. An
+/// Oracle that requires funding accepts commitments only once each player
+/// has confirmed the other's stake.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FundingMessage {
+    pub game_id: GameId,
+    pub player: Player,
+    /// The opponent's payment hash, which locks the held payment
+    pub payment_hash: PaymentHash,
+}
+
 /// Phase 4: Commitment message
+///
+/// Names the stake and the opponent's payment hash the commitment is bound
+/// to, see [`Commitment::bound`]; the Oracle refuses one that doesn't match
+/// the game.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CommitMessage {
     pub game_id: GameId,
     pub player: Player,
     pub commitment: Commitment,
+    pub amount_shannons: u64,
+    pub opponent_payment_hash: PaymentHash,
+}
+
+impl CommitMessage {
+    /// What the commitment is bound to
+    pub fn context(&self) -> CommitContext {
+        CommitContext {
+            game_id: self.game_id,
+            amount_shannons: self.amount_shannons,
+            opponent_payment_hash: self.opponent_payment_hash,
+        }
+    }
 }
 
 /// Phase 5: Reveal message to Oracle
@@ -40,6 +72,146 @@ pub struct RevealMessage {
     pub commit_b: Commitment,
 }
 
+/// Phase 5 of a private game: a player's verdict, sent to the Oracle in
+/// place of their action once both players have revealed to each other
+///
+/// The Oracle signs `result` when both players send the same verdict for the
+/// commitments it holds; it never sees either action. `transcript` shows the
+/// two verdicts were reached from the same pair of reveals without disclosing
+/// them, see [`VerdictMessage::transcript`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VerdictMessage {
+    pub game_id: GameId,
+    pub player: Player,
+    pub result: GameResult,
+    pub commit_a: Commitment,
+    pub commit_b: Commitment,
+    pub transcript: [u8; 32],
+}
+
+impl VerdictMessage {
+    /// Hash of both players' salts: only someone holding both reveals can
+    /// produce it, and without the salts it says nothing about the actions.
+    pub fn transcript(game_id: &GameId, salt_a: &Salt, salt_b: &Salt) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(b"fiber-game/verdict");
+        hasher.update(game_id.as_bytes());
+        hasher.update(salt_a.as_bytes());
+        hasher.update(salt_b.as_bytes());
+        hasher.finalize().into()
+    }
+}
+
+/// Why a game ended without a result
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AbortReason {
+    /// The player no longer wants to play
+    Withdrawn,
+    /// Creating or paying a hold invoice failed
+    PaymentFailed,
+    /// The player stopped responding and their opponent claimed the timeout
+    TimedOut,
+    /// The oracle restarted and lost the game, so it can't be finished
+    OracleLost,
+}
+
+impl AbortReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AbortReason::Withdrawn => "withdrawn",
+            AbortReason::PaymentFailed => "payment failed",
+            AbortReason::TimedOut => "timed out",
+            AbortReason::OracleLost => "oracle lost",
+        }
+    }
+}
+
+/// A player leaving a game before committing to an action
+///
+/// The oracle cancels the game and releases no preimages, so both hold
+/// invoices are cancelled. Like every message to the oracle it travels in a
+/// signed [`Envelope`](crate::protocol::Envelope), which ties it to the
+/// player's seat.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AbortMessage {
+    pub game_id: GameId,
+    pub player: Player,
+    pub reason: AbortReason,
+}
+
+/// A player's claim that their opponent has stopped responding
+///
+/// The oracle accepts it once the opponent is behind the claimant and nothing
+/// has happened in the game for the oracle's step timeout. If the claimant has
+/// already committed, the opponent forfeits; otherwise the game is cancelled
+/// as if the opponent had aborted with [`AbortReason::TimedOut`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TimeoutClaim {
+    pub game_id: GameId,
+    pub player: Player,
+}
+
+/// What a player did with the hold invoice holding the opponent's stake
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SettlementAction {
+    /// Claimed with the opponent's preimage
+    Settled,
+    /// Cancelled, so the opponent's payment goes back to them
+    Cancelled,
+    /// Left held after a draw: the next round of the match is played for
+    /// it and resolves it
+    CarriedOver,
+}
+
+impl SettlementAction {
+    /// What `player` has to do once the game ended with `result`: the winner
+    /// settles with the preimage the oracle released, everyone else cancels.
+    pub fn owed(result: GameResult, player: Player) -> Self {
+        match (result, player) {
+            (GameResult::AWins, Player::A) | (GameResult::BWins, Player::B) => {
+                SettlementAction::Settled
+            }
+            _ => SettlementAction::Cancelled,
+        }
+    }
+
+    /// What `player` has to do once `round` of a match played under
+    /// `policy` ended with `result`: as [`SettlementAction::owed`], except
+    /// a draw replayed on the same invoices leaves them held.
+    pub fn owed_in(result: GameResult, player: Player, policy: DrawPolicy, round: &MatchRound) -> Self {
+        if policy.carries_invoices(result, round) {
+            SettlementAction::CarriedOver
+        } else {
+            Self::owed(result, player)
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SettlementAction::Settled => "settled",
+            SettlementAction::Cancelled => "cancelled",
+            SettlementAction::CarriedOver => "carried_over",
+        }
+    }
+}
+
+/// Phase 7: a player resolved the hold invoice holding the opponent's stake
+///
+/// Sent once the invoice the player created with the opponent's
+/// `payment_hash` has been settled or cancelled on their own node. The
+/// oracle counts a game's settlement complete when both players have sent
+/// one, and until then tells each what is still owed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SettlementMessage {
+    pub game_id: GameId,
+    pub player: Player,
+    /// The opponent's payment hash, which locks the held payment
+    pub payment_hash: PaymentHash,
+    pub action: SettlementAction,
+}
+
 /// Phase 6: Oracle's signed result
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct OracleResultMessage {
@@ -51,7 +223,7 @@ pub struct OracleResultMessage {
     pub signature: [u8; 64],
 }
 
-mod signature_serde {
+pub(super) mod signature_serde {
     use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
     pub fn serialize<S: Serializer>(bytes: &[u8; 64], s: S) -> Result<S::Ok, S::Error> {
@@ -71,7 +243,7 @@ mod signature_serde {
 }
 
 /// All game data needed to verify the result
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct GameData {
     pub action_a: GameAction,
     pub action_b: GameAction,
@@ -79,11 +251,34 @@ pub struct GameData {
     pub oracle_secret: Option<OracleSecretData>,
 }
 
-/// Oracle's secret data for verification
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// Oracle's secret data for verification, as the oracle publishes it with
+/// the result
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct OracleSecretData {
-    pub secret_value: Vec<u8>,
-    pub nonce: [u8; 32],
+    pub secret_number: u8,
+    /// Commitment nonce, hex
+    pub nonce: String,
+}
+
+impl From<&OracleSecret> for OracleSecretData {
+    fn from(secret: &OracleSecret) -> Self {
+        Self {
+            secret_number: secret.secret_number,
+            nonce: hex::encode(secret.nonce),
+        }
+    }
+}
+
+impl OracleSecretData {
+    /// The secret to judge with and check against the oracle's commitment;
+    /// `None` if the nonce isn't 32 bytes of hex.
+    pub fn to_secret(&self) -> Option<OracleSecret> {
+        let nonce = hex::decode(&self.nonce).ok()?.try_into().ok()?;
+        Some(OracleSecret {
+            secret_number: self.secret_number,
+            nonce,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -97,6 +292,8 @@ mod tests {
             game_id: GameId::new(),
             player: Player::A,
             commitment: Commitment::new(b"Rock", &Salt::random()),
+            amount_shannons: 1000,
+            opponent_payment_hash: Preimage::random().payment_hash(),
         };
 
         let json = serde_json::to_string(&commit_msg).unwrap();
@@ -121,4 +318,24 @@ mod tests {
         assert_eq!(msg.payment_hash, deserialized.payment_hash);
         assert_eq!(msg.amount_shannons, deserialized.amount_shannons);
     }
+
+    #[test]
+    fn test_settlement_owed() {
+        use SettlementAction::{Cancelled, Settled};
+        assert_eq!(SettlementAction::owed(GameResult::AWins, Player::A), Settled);
+        assert_eq!(SettlementAction::owed(GameResult::AWins, Player::B), Cancelled);
+        assert_eq!(SettlementAction::owed(GameResult::BWins, Player::B), Settled);
+        assert_eq!(SettlementAction::owed(GameResult::Draw, Player::A), Cancelled);
+        assert_eq!(SettlementAction::owed(GameResult::Draw, Player::B), Cancelled);
+    }
+
+    #[test]
+    fn test_settlement_owed_in_a_match() {
+        use SettlementAction::{CarriedOver, Cancelled, Settled};
+        let round = MatchRound::first(GameId::new());
+        let owed = |result, policy| SettlementAction::owed_in(result, Player::A, policy, &round);
+        assert_eq!(owed(GameResult::Draw, DrawPolicy::RematchHeld), CarriedOver);
+        assert_eq!(owed(GameResult::Draw, DrawPolicy::Rematch), Cancelled);
+        assert_eq!(owed(GameResult::AWins, DrawPolicy::RematchHeld), Settled);
+    }
 }
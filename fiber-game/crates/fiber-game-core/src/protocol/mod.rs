@@ -1,10 +1,31 @@
 //! Protocol types and messages.
 
+mod encoding;
+mod envelope;
 mod messages;
+mod resume;
+mod session;
+mod timeline;
+mod trace;
 mod types;
 
+pub use encoding::{
+    canonical_cbor, Encoding, EncodingError, CBOR_CONTENT_TYPE, JSON_CONTENT_TYPE,
+};
+pub use envelope::{Envelope, EnvelopeError, PROTOCOL_VERSION};
 pub use messages::{
-    CommitMessage, EncryptedPreimageExchange, HoldInvoiceMessage, OracleResultMessage,
-    RevealMessage,
+    AbortMessage, AbortReason, CommitMessage, EncryptedPreimageExchange, FundingMessage,
+    GameData, HoldInvoiceMessage, OracleResultMessage, OracleSecretData, RevealMessage,
+    SettlementAction, SettlementMessage, TimeoutClaim, VerdictMessage,
+};
+pub use resume::{GameSnapshot, ResumptionToken};
+pub use session::{
+    Aborted, AnySession, Committed, Created, Funded, GameSession, Joined, Judged, Revealed,
+    SessionError, Settled, Stage, Undecided,
+};
+pub use timeline::{merge_timelines, Actor, ProtocolStep, TimelineEvent};
+pub use trace::{
+    verify_trace, Direction, MessageKind, ProtocolRecorder, ProtocolTrace, TraceEntry,
+    TraceError, TraceReport,
 };
-pub use types::{GameId, GameResult, GameSession, Player};
+pub use types::{DrawPolicy, GameId, GameResult, MatchRound, Player, MAX_MATCH_ROUNDS};
@@ -0,0 +1,87 @@
+//! Resuming a game after losing local state.
+//!
+//! When a player creates or joins a game the oracle hands them a
+//! [`ResumptionToken`] sealed with the oracle key. A player service that has
+//! crashed and lost its games (and possibly its protocol key) presents the
+//! token to the oracle in an envelope signed with its current key. The oracle
+//! checks it issued the token, binds the seat to the new key, and answers with
+//! a [`GameSnapshot`] from which the player rebuilds its session.
+//!
+//! The token is a bearer credential for its seat: the snapshot includes the
+//! seat's own preimage, which would let the opponent settle without winning.
+//! Keep it wherever the player keeps its secrets.
+
+use crate::crypto::{Commitment, PaymentHash, Preimage};
+use crate::games::{GameAction, GameType};
+use crate::protocol::types::pubkey_serde;
+use crate::protocol::{AbortReason, GameId, GameResult, Player};
+use secp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Proof that `player_id` holds seat `player` in a game, issued by the oracle
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResumptionToken {
+    pub game_id: GameId,
+    pub player: Player,
+    pub player_id: Uuid,
+    /// Milliseconds since the Unix epoch
+    pub issued_at_ms: u64,
+}
+
+/// Everything about a game a player needs to rebuild their session, as seen
+/// from their seat
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameSnapshot {
+    pub game_id: GameId,
+    pub player: Player,
+    pub game_type: GameType,
+    pub amount_shannons: u64,
+    #[serde(with = "pubkey_serde")]
+    pub commitment_point: PublicKey,
+    /// Player A's direct-connection URL, if they advertised one
+    pub peer_url: Option<String>,
+    /// Our preimage, held by the oracle for settlement
+    pub preimage: Option<Preimage>,
+    pub opponent_payment_hash: Option<PaymentHash>,
+    /// Our hold invoice, if submitted through the oracle
+    pub invoice: Option<String>,
+    pub opponent_invoice: Option<String>,
+    /// Our commitment, if we committed
+    pub commitment: Option<Commitment>,
+    /// Our action, if we revealed it
+    pub revealed_action: Option<GameAction>,
+    pub result: Option<GameResult>,
+    /// Released to us if we won
+    pub opponent_preimage: Option<Preimage>,
+    /// Who ended the game early and why, if it was cancelled
+    pub cancelled: Option<(Player, AbortReason)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{Encoding, Envelope};
+    use secp256k1::{SecretKey, SECP256K1};
+
+    #[test]
+    fn test_token_verifies_against_issuer() {
+        let oracle = SecretKey::new(&mut rand::thread_rng());
+        let token = ResumptionToken {
+            game_id: GameId::new(),
+            player: Player::B,
+            player_id: Uuid::new_v4(),
+            issued_at_ms: 1,
+        };
+        let sealed = Envelope::seal(token.clone(), &oracle).unwrap();
+
+        // Players keep it as JSON and may send it back in either encoding
+        let cbor = Encoding::Cbor.encode(&sealed).unwrap();
+        let back: Envelope<ResumptionToken> = Encoding::Cbor.decode(&cbor).unwrap();
+        let oracle_pubkey = PublicKey::from_secret_key(SECP256K1, &oracle);
+        assert_eq!(back.clone().open_from(&oracle_pubkey).unwrap(), token);
+
+        let other = PublicKey::from_secret_key(SECP256K1, &SecretKey::new(&mut rand::thread_rng()));
+        assert!(back.open_from(&other).is_err());
+    }
+}
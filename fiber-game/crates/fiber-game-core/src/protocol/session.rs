@@ -0,0 +1,861 @@
+//! A player's view of one game, as a type-state machine.
+//!
+//! [`GameSession<S>`] carries everything a player needs across the protocol,
+//! and `S` records how far the game has got:
+//!
+//! ```text
+//! Created → Joined → Funded → Committed → Revealed → Judged → Settled
+//!    └─────────┴────────┴──────────┴──────────┴──→ Aborted
+//! ```
+//!
+//! Each step is a method that consumes the session and returns it in the next
+//! stage, so code that, say, commits before funding or settles before the
+//! oracle has judged does not compile. Services that keep sessions between
+//! requests store them as an [`AnySession`] and use [`AnySession::advance`]
+//! to apply a step, which fails if the game is in another stage.
+//!
+//! Until the oracle has judged, a game can also end without a result: one
+//! player aborts, or times out and the oracle cancels the game (see
+//! [`AbortMessage`](crate::protocol::AbortMessage)).
+
+use crate::crypto::{CommitContext, Commitment, PaymentHash, Preimage, Salt};
+use crate::games::{GameAction, GameType};
+use crate::money::{self, MoneyError};
+use crate::protocol::types::pubkey_serde;
+use crate::protocol::{AbortReason, GameId, GameResult, GameSnapshot, Player};
+use fiber_errors::{Coded, ErrorCode};
+use secp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Errors from moving a session to its next stage
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SessionError {
+    #[error("game is {actual}, not {expected}")]
+    WrongStage {
+        expected: &'static str,
+        actual: &'static str,
+    },
+
+    #[error("action is not valid for this game")]
+    InvalidAction,
+
+    #[error("opponent's preimage does not match their payment hash")]
+    PreimageMismatch,
+
+    #[error("snapshot lacks {0}")]
+    IncompleteSnapshot(&'static str),
+
+    #[error(transparent)]
+    Money(#[from] MoneyError),
+}
+
+impl Coded for SessionError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            SessionError::WrongStage { .. } => ErrorCode::InvalidState,
+            SessionError::InvalidAction => ErrorCode::BadRequest,
+            // Both come from what the oracle handed us
+            SessionError::PreimageMismatch | SessionError::IncompleteSnapshot(_) => {
+                ErrorCode::Upstream
+            }
+            SessionError::Money(e) => e.code(),
+        }
+    }
+}
+
+/// Seated in a game; the opponent's payment hash isn't known yet
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Created;
+
+/// Both players are in and have exchanged payment hashes
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Joined {
+    pub opponent_payment_hash: PaymentHash,
+}
+
+/// Our stake is locked in the opponent's hold invoice
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Funded {
+    pub opponent_payment_hash: PaymentHash,
+}
+
+/// Our action is chosen and its commitment sent to the oracle
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Committed {
+    pub opponent_payment_hash: PaymentHash,
+    pub action: GameAction,
+    pub commitment: Commitment,
+}
+
+/// Our action and salt are revealed to the oracle
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Revealed {
+    pub opponent_payment_hash: PaymentHash,
+    pub action: GameAction,
+    pub commitment: Commitment,
+}
+
+/// The oracle has published the result
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Judged {
+    pub opponent_payment_hash: PaymentHash,
+    pub action: GameAction,
+    pub result: GameResult,
+    /// Released to the winner, who settles their invoice with it
+    pub opponent_preimage: Option<Preimage>,
+}
+
+/// Our invoice is settled or cancelled; the game is over
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Settled {
+    pub opponent_payment_hash: PaymentHash,
+    pub action: GameAction,
+    pub result: GameResult,
+    pub opponent_preimage: Option<Preimage>,
+    /// Stake won (positive), lost (negative) or returned (zero)
+    pub amount_won: i64,
+}
+
+/// The game was cancelled before a result; neither invoice is settled
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Aborted {
+    /// Known if the game got as far as [`Joined`]
+    pub opponent_payment_hash: Option<PaymentHash>,
+    /// Player who aborted or timed out
+    pub by: Player,
+    pub reason: AbortReason,
+}
+
+/// One player's session in a game, in stage `S`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameSession<S> {
+    game_id: GameId,
+    role: Player,
+    game_type: GameType,
+    amount_shannons: u64,
+    /// Key the oracle signs results with
+    #[serde(with = "pubkey_serde")]
+    oracle_pubkey: PublicKey,
+    /// Oracle's commitment point (R) for this game
+    #[serde(with = "pubkey_serde")]
+    commitment_point: PublicKey,
+    /// Unlocks the opponent's payment; released to them only if they win
+    preimage: Preimage,
+    salt: Salt,
+    state: S,
+}
+
+impl<S> GameSession<S> {
+    pub fn game_id(&self) -> GameId {
+        self.game_id
+    }
+
+    pub fn role(&self) -> Player {
+        self.role
+    }
+
+    pub fn game_type(&self) -> GameType {
+        self.game_type
+    }
+
+    pub fn amount_shannons(&self) -> u64 {
+        self.amount_shannons
+    }
+
+    pub fn oracle_pubkey(&self) -> &PublicKey {
+        &self.oracle_pubkey
+    }
+
+    pub fn commitment_point(&self) -> &PublicKey {
+        &self.commitment_point
+    }
+
+    pub fn preimage(&self) -> &Preimage {
+        &self.preimage
+    }
+
+    /// Hash of our preimage, which the opponent's invoice is locked to
+    pub fn payment_hash(&self) -> PaymentHash {
+        self.preimage.payment_hash()
+    }
+
+    pub fn salt(&self) -> &Salt {
+        &self.salt
+    }
+
+    /// Data specific to the current stage
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    fn with_state<T>(self, state: T) -> GameSession<T> {
+        GameSession {
+            game_id: self.game_id,
+            role: self.role,
+            game_type: self.game_type,
+            amount_shannons: self.amount_shannons,
+            oracle_pubkey: self.oracle_pubkey,
+            commitment_point: self.commitment_point,
+            preimage: self.preimage,
+            salt: self.salt,
+            state,
+        }
+    }
+}
+
+impl GameSession<Created> {
+    /// Take seat `role` in a game, with a fresh preimage and salt.
+    pub fn new(
+        game_id: GameId,
+        role: Player,
+        game_type: GameType,
+        amount_shannons: u64,
+        oracle_pubkey: PublicKey,
+        commitment_point: PublicKey,
+    ) -> Self {
+        Self {
+            game_id,
+            role,
+            game_type,
+            amount_shannons,
+            oracle_pubkey,
+            commitment_point,
+            preimage: Preimage::random(),
+            salt: Salt::random(),
+            state: Created,
+        }
+    }
+
+    /// Keep the preimage of the previous round of a match, so that the
+    /// invoices still holding both stakes settle this round too.
+    pub fn reusing(mut self, preimage: Preimage) -> Self {
+        self.preimage = preimage;
+        self
+    }
+
+    /// Record the opponent's payment hash, once both players are in.
+    pub fn joined(self, opponent_payment_hash: PaymentHash) -> GameSession<Joined> {
+        self.with_state(Joined {
+            opponent_payment_hash,
+        })
+    }
+}
+
+impl GameSession<Joined> {
+    /// Record that we have paid the opponent's hold invoice.
+    pub fn fund(self) -> GameSession<Funded> {
+        let opponent_payment_hash = self.state.opponent_payment_hash;
+        self.with_state(Funded {
+            opponent_payment_hash,
+        })
+    }
+}
+
+impl GameSession<Funded> {
+    /// Choose `action` and commit to it with our salt.
+    pub fn commit(self, action: GameAction) -> Result<GameSession<Committed>, SessionError> {
+        if !action.validate(self.game_type) {
+            return Err(SessionError::InvalidAction);
+        }
+        let opponent_payment_hash = self.state.opponent_payment_hash;
+        let context = CommitContext {
+            game_id: self.game_id,
+            amount_shannons: self.amount_shannons,
+            opponent_payment_hash,
+        };
+        let commitment = Commitment::bound(&action.to_bytes(), &context, &self.salt);
+        Ok(self.with_state(Committed {
+            opponent_payment_hash,
+            action,
+            commitment,
+        }))
+    }
+}
+
+impl GameSession<Funded> {
+    /// Pick up a game whose action we revealed before losing our session.
+    ///
+    /// Only for [`AnySession::resume`]: the salt is gone, but once revealed
+    /// it is no longer needed.
+    fn recover_revealed(self, action: GameAction, commitment: Commitment) -> GameSession<Revealed> {
+        let opponent_payment_hash = self.state.opponent_payment_hash;
+        self.with_state(Revealed {
+            opponent_payment_hash,
+            action,
+            commitment,
+        })
+    }
+}
+
+impl GameSession<Committed> {
+    /// Record that the action and salt went to the oracle.
+    pub fn reveal(self) -> GameSession<Revealed> {
+        let Committed {
+            opponent_payment_hash,
+            action,
+            commitment,
+        } = self.state.clone();
+        self.with_state(Revealed {
+            opponent_payment_hash,
+            action,
+            commitment,
+        })
+    }
+}
+
+impl GameSession<Revealed> {
+    /// Accept the oracle's result, with the opponent's preimage if we won.
+    ///
+    /// A preimage that doesn't unlock the opponent's payment hash is refused,
+    /// since settling with it would fail.
+    pub fn judge(
+        self,
+        result: GameResult,
+        opponent_preimage: Option<Preimage>,
+    ) -> Result<GameSession<Judged>, SessionError> {
+        let Revealed {
+            opponent_payment_hash,
+            action,
+            ..
+        } = self.state.clone();
+        if let Some(preimage) = &opponent_preimage {
+            if !opponent_payment_hash.verify(preimage) {
+                return Err(SessionError::PreimageMismatch);
+            }
+        }
+        Ok(self.with_state(Judged {
+            opponent_payment_hash,
+            action,
+            result,
+            opponent_preimage,
+        }))
+    }
+}
+
+/// A stage before the oracle's result, from which the game can be aborted
+pub trait Undecided: Stage {
+    fn opponent_payment_hash(&self) -> Option<PaymentHash>;
+}
+
+impl Undecided for Created {
+    fn opponent_payment_hash(&self) -> Option<PaymentHash> {
+        None
+    }
+}
+
+macro_rules! impl_undecided {
+    ($($stage:ident),*) => {$(
+        impl Undecided for $stage {
+            fn opponent_payment_hash(&self) -> Option<PaymentHash> {
+                Some(self.opponent_payment_hash)
+            }
+        }
+    )*};
+}
+
+impl_undecided!(Joined, Funded, Committed, Revealed);
+
+impl<S: Undecided> GameSession<S> {
+    /// What `player`'s commitment is bound to, once both players are in
+    pub fn commit_context(&self, player: Player) -> Option<CommitContext> {
+        let opponent_payment_hash = self.state.opponent_payment_hash()?;
+        Some(CommitContext {
+            game_id: self.game_id,
+            amount_shannons: self.amount_shannons,
+            opponent_payment_hash: if player == self.role {
+                opponent_payment_hash
+            } else {
+                self.payment_hash()
+            },
+        })
+    }
+
+    /// Record that the game was cancelled because `by` aborted or timed out.
+    pub fn abort(self, by: Player, reason: AbortReason) -> GameSession<Aborted> {
+        let opponent_payment_hash = self.state.opponent_payment_hash();
+        self.with_state(Aborted {
+            opponent_payment_hash,
+            by,
+            reason,
+        })
+    }
+}
+
+impl GameSession<Judged> {
+    /// Stake won (positive), lost (negative) or returned (zero)
+    pub fn amount_won(&self) -> Result<i64, MoneyError> {
+        let stake = self.amount_shannons;
+        match (self.state.result, self.role) {
+            (GameResult::AWins, Player::A) | (GameResult::BWins, Player::B) => money::credit(0, stake),
+            (GameResult::AWins, Player::B) | (GameResult::BWins, Player::A) => money::debit(0, stake),
+            (GameResult::Draw, _) => Ok(0),
+        }
+    }
+
+    /// Record that our invoice was settled or cancelled.
+    pub fn settle(self) -> Result<GameSession<Settled>, SessionError> {
+        let amount_won = self.amount_won()?;
+        let Judged {
+            opponent_payment_hash,
+            action,
+            result,
+            opponent_preimage,
+        } = self.state.clone();
+        Ok(self.with_state(Settled {
+            opponent_payment_hash,
+            action,
+            result,
+            opponent_preimage,
+            amount_won,
+        }))
+    }
+}
+
+/// A session in whichever stage it has reached
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "stage")]
+pub enum AnySession {
+    Created(GameSession<Created>),
+    Joined(GameSession<Joined>),
+    Funded(GameSession<Funded>),
+    Committed(GameSession<Committed>),
+    Revealed(GameSession<Revealed>),
+    Judged(GameSession<Judged>),
+    Settled(GameSession<Settled>),
+    Aborted(GameSession<Aborted>),
+}
+
+/// A session stage: one of [`Created`] through [`Settled`], or [`Aborted`]
+pub trait Stage: Sized {
+    /// Name used in errors
+    const NAME: &'static str;
+
+    /// The session, if it is in this stage
+    fn from_any(session: AnySession) -> Option<GameSession<Self>>;
+
+    fn into_any(session: GameSession<Self>) -> AnySession;
+}
+
+macro_rules! impl_stage {
+    ($($stage:ident),*) => {$(
+        impl Stage for $stage {
+            const NAME: &'static str = stringify!($stage);
+
+            fn from_any(session: AnySession) -> Option<GameSession<Self>> {
+                match session {
+                    AnySession::$stage(s) => Some(s),
+                    _ => None,
+                }
+            }
+
+            fn into_any(session: GameSession<Self>) -> AnySession {
+                AnySession::$stage(session)
+            }
+        }
+
+        impl From<GameSession<$stage>> for AnySession {
+            fn from(session: GameSession<$stage>) -> Self {
+                AnySession::$stage(session)
+            }
+        }
+    )*};
+}
+
+impl_stage!(Created, Joined, Funded, Committed, Revealed, Judged, Settled, Aborted);
+
+/// Apply `$body` to the session in whichever stage it is
+macro_rules! with_session {
+    ($any:expr, $s:ident => $body:expr) => {
+        match $any {
+            AnySession::Created($s) => $body,
+            AnySession::Joined($s) => $body,
+            AnySession::Funded($s) => $body,
+            AnySession::Committed($s) => $body,
+            AnySession::Revealed($s) => $body,
+            AnySession::Judged($s) => $body,
+            AnySession::Settled($s) => $body,
+            AnySession::Aborted($s) => $body,
+        }
+    };
+}
+
+impl AnySession {
+    /// Rebuild a session from the oracle's snapshot of our seat.
+    ///
+    /// The salt is lost with the old session, so a game we committed in but
+    /// had not revealed comes back as [`Funded`] and we commit again. Games
+    /// that were judged come back as [`Judged`] whether or not we had settled,
+    /// since settling twice is harmless.
+    pub fn resume(snapshot: &GameSnapshot, oracle_pubkey: PublicKey) -> Result<Self, SessionError> {
+        let preimage = snapshot
+            .preimage
+            .clone()
+            .ok_or(SessionError::IncompleteSnapshot("our preimage"))?;
+        let created = GameSession {
+            game_id: snapshot.game_id,
+            role: snapshot.player,
+            game_type: snapshot.game_type,
+            amount_shannons: snapshot.amount_shannons,
+            oracle_pubkey,
+            commitment_point: snapshot.commitment_point,
+            preimage,
+            salt: Salt::random(),
+            state: Created,
+        };
+        if let Some((by, reason)) = snapshot.cancelled {
+            return Ok(created.abort(by, reason).into());
+        }
+        let Some(opponent_payment_hash) = snapshot.opponent_payment_hash else {
+            return Ok(created.into());
+        };
+        let joined = created.joined(opponent_payment_hash);
+        if snapshot.commitment.is_none() {
+            return Ok(joined.into());
+        }
+        let funded = joined.fund();
+        let (Some(action), Some(commitment)) = (&snapshot.revealed_action, snapshot.commitment)
+        else {
+            return Ok(funded.into());
+        };
+        let revealed = funded.recover_revealed(action.clone(), commitment);
+        match snapshot.result {
+            Some(result) => Ok(revealed
+                .judge(result, snapshot.opponent_preimage.clone())?
+                .into()),
+            None => Ok(revealed.into()),
+        }
+    }
+
+    /// Name of the current stage
+    pub fn stage(&self) -> &'static str {
+        match self {
+            AnySession::Created(_) => Created::NAME,
+            AnySession::Joined(_) => Joined::NAME,
+            AnySession::Funded(_) => Funded::NAME,
+            AnySession::Committed(_) => Committed::NAME,
+            AnySession::Revealed(_) => Revealed::NAME,
+            AnySession::Judged(_) => Judged::NAME,
+            AnySession::Settled(_) => Settled::NAME,
+            AnySession::Aborted(_) => Aborted::NAME,
+        }
+    }
+
+    /// A copy of the session, if it is in stage `S`
+    pub fn get<S: Stage>(&self) -> Result<GameSession<S>, SessionError> {
+        S::from_any(self.clone()).ok_or(SessionError::WrongStage {
+            expected: S::NAME,
+            actual: self.stage(),
+        })
+    }
+
+    /// Apply `step` if the session is in stage `S`.
+    ///
+    /// On any error the session is left as it was.
+    pub fn advance<S: Stage, T: Stage>(
+        &mut self,
+        step: impl FnOnce(GameSession<S>) -> Result<GameSession<T>, SessionError>,
+    ) -> Result<(), SessionError> {
+        *self = T::into_any(step(self.get::<S>()?)?);
+        Ok(())
+    }
+
+    pub fn game_id(&self) -> GameId {
+        with_session!(self, s => s.game_id)
+    }
+
+    pub fn role(&self) -> Player {
+        with_session!(self, s => s.role)
+    }
+
+    pub fn game_type(&self) -> GameType {
+        with_session!(self, s => s.game_type)
+    }
+
+    pub fn amount_shannons(&self) -> u64 {
+        with_session!(self, s => s.amount_shannons)
+    }
+
+    pub fn oracle_pubkey(&self) -> &PublicKey {
+        with_session!(self, s => &s.oracle_pubkey)
+    }
+
+    pub fn payment_hash(&self) -> PaymentHash {
+        with_session!(self, s => s.payment_hash())
+    }
+
+    /// Our preimage, which the next round of a match replaying a draw on
+    /// the same invoices keeps
+    pub fn preimage(&self) -> &Preimage {
+        with_session!(self, s => s.preimage())
+    }
+
+    /// Opponent's payment hash, known from [`Joined`] on
+    pub fn opponent_payment_hash(&self) -> Option<PaymentHash> {
+        match self {
+            AnySession::Created(_) => None,
+            AnySession::Joined(s) => Some(s.state.opponent_payment_hash),
+            AnySession::Funded(s) => Some(s.state.opponent_payment_hash),
+            AnySession::Committed(s) => Some(s.state.opponent_payment_hash),
+            AnySession::Revealed(s) => Some(s.state.opponent_payment_hash),
+            AnySession::Judged(s) => Some(s.state.opponent_payment_hash),
+            AnySession::Settled(s) => Some(s.state.opponent_payment_hash),
+            AnySession::Aborted(s) => s.state.opponent_payment_hash,
+        }
+    }
+
+    /// Our action, once committed
+    pub fn action(&self) -> Option<&GameAction> {
+        match self {
+            AnySession::Committed(s) => Some(&s.state.action),
+            AnySession::Revealed(s) => Some(&s.state.action),
+            AnySession::Judged(s) => Some(&s.state.action),
+            AnySession::Settled(s) => Some(&s.state.action),
+            _ => None,
+        }
+    }
+
+    /// The oracle's result, once judged
+    pub fn result(&self) -> Option<GameResult> {
+        match self {
+            AnySession::Judged(s) => Some(s.state.result),
+            AnySession::Settled(s) => Some(s.state.result),
+            _ => None,
+        }
+    }
+
+    /// Opponent's preimage, if the oracle released it to us
+    pub fn opponent_preimage(&self) -> Option<&Preimage> {
+        match self {
+            AnySession::Judged(s) => s.state.opponent_preimage.as_ref(),
+            AnySession::Settled(s) => s.state.opponent_preimage.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn is_settled(&self) -> bool {
+        matches!(self, AnySession::Settled(_))
+    }
+
+    /// Settled or aborted: nothing more will happen in this game
+    pub fn is_finished(&self) -> bool {
+        matches!(self, AnySession::Settled(_) | AnySession::Aborted(_))
+    }
+
+    /// Abort from whichever stage before the result the game is in.
+    pub fn abort(&mut self, by: Player, reason: AbortReason) -> Result<(), SessionError> {
+        let aborted = match self.clone() {
+            AnySession::Created(s) => s.abort(by, reason),
+            AnySession::Joined(s) => s.abort(by, reason),
+            AnySession::Funded(s) => s.abort(by, reason),
+            AnySession::Committed(s) => s.abort(by, reason),
+            AnySession::Revealed(s) => s.abort(by, reason),
+            _ => {
+                return Err(SessionError::WrongStage {
+                    expected: "undecided",
+                    actual: self.stage(),
+                })
+            }
+        };
+        *self = aborted.into();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::RpsAction;
+    use secp256k1::{SecretKey, SECP256K1};
+
+    fn pubkey() -> PublicKey {
+        PublicKey::from_secret_key(SECP256K1, &SecretKey::new(&mut rand::thread_rng()))
+    }
+
+    fn session(role: Player) -> GameSession<Created> {
+        GameSession::new(
+            GameId::new(),
+            role,
+            GameType::RockPaperScissors,
+            1000,
+            pubkey(),
+            pubkey(),
+        )
+    }
+
+    #[test]
+    fn test_full_session() {
+        let opponent = Preimage::random();
+        let committed = session(Player::A)
+            .joined(opponent.payment_hash())
+            .fund()
+            .commit(GameAction::Rps(RpsAction::Rock))
+            .unwrap();
+        let commitment = committed.state().commitment;
+        let context = committed.commit_context(Player::A).unwrap();
+        assert_eq!(context.opponent_payment_hash, opponent.payment_hash());
+        assert!(commitment.verify_bound(RpsAction::Rock.to_bytes(), &context, committed.salt()));
+        let theirs = committed.commit_context(Player::B).unwrap();
+        assert_eq!(theirs.opponent_payment_hash, committed.payment_hash());
+
+        let judged = committed
+            .reveal()
+            .judge(GameResult::AWins, Some(opponent))
+            .unwrap();
+        assert_eq!(judged.amount_won(), Ok(1000));
+
+        let settled = judged.settle().unwrap();
+        assert_eq!(settled.state().amount_won, 1000);
+        assert_eq!(settled.state().result, GameResult::AWins);
+    }
+
+    #[test]
+    fn test_next_round_reuses_the_payment_hash() {
+        let round_one = session(Player::B);
+        let round_two = session(Player::B).reusing(round_one.preimage().clone());
+        assert_eq!(round_two.payment_hash(), round_one.payment_hash());
+        assert_ne!(round_two.salt().as_bytes(), round_one.salt().as_bytes());
+    }
+
+    #[test]
+    fn test_settle_refuses_a_stake_too_large_to_count() {
+        let mut created = session(Player::B);
+        created.amount_shannons = money::MAX_SHANNONS + 1;
+        let judged = created
+            .joined(Preimage::random().payment_hash())
+            .fund()
+            .commit(GameAction::Rps(RpsAction::Paper))
+            .unwrap()
+            .reveal()
+            .judge(GameResult::AWins, None)
+            .unwrap();
+        assert_eq!(
+            judged.settle().unwrap_err(),
+            SessionError::Money(MoneyError::TooLarge(money::MAX_SHANNONS + 1))
+        );
+    }
+
+    #[test]
+    fn test_commit_rejects_invalid_action() {
+        let funded = session(Player::B)
+            .joined(Preimage::random().payment_hash())
+            .fund();
+        assert_eq!(
+            funded.commit(GameAction::GuessNumber(5)).unwrap_err(),
+            SessionError::InvalidAction
+        );
+    }
+
+    #[test]
+    fn test_judge_rejects_wrong_preimage() {
+        let revealed = session(Player::B)
+            .joined(Preimage::random().payment_hash())
+            .fund()
+            .commit(GameAction::Rps(RpsAction::Paper))
+            .unwrap()
+            .reveal();
+        assert_eq!(
+            revealed
+                .judge(GameResult::BWins, Some(Preimage::random()))
+                .unwrap_err(),
+            SessionError::PreimageMismatch
+        );
+    }
+
+    #[test]
+    fn test_abort_until_judged() {
+        let opponent = Preimage::random().payment_hash();
+        let mut any = AnySession::from(session(Player::A).joined(opponent).fund());
+        any.abort(Player::B, AbortReason::TimedOut).unwrap();
+        assert!(any.is_finished());
+        assert_eq!(any.opponent_payment_hash(), Some(opponent));
+
+        let judged = session(Player::A)
+            .joined(opponent)
+            .fund()
+            .commit(GameAction::Rps(RpsAction::Rock))
+            .unwrap()
+            .reveal()
+            .judge(GameResult::Draw, None)
+            .unwrap();
+        let mut any = AnySession::from(judged);
+        assert!(any.abort(Player::A, AbortReason::Withdrawn).is_err());
+        assert_eq!(any.stage(), "Judged");
+    }
+
+    fn snapshot(session: &GameSession<Created>) -> GameSnapshot {
+        GameSnapshot {
+            game_id: session.game_id(),
+            player: session.role(),
+            game_type: session.game_type(),
+            amount_shannons: session.amount_shannons(),
+            commitment_point: *session.commitment_point(),
+            peer_url: None,
+            preimage: Some(session.preimage().clone()),
+            opponent_payment_hash: None,
+            invoice: None,
+            opponent_invoice: None,
+            commitment: None,
+            revealed_action: None,
+            result: None,
+            opponent_preimage: None,
+            cancelled: None,
+        }
+    }
+
+    #[test]
+    fn test_resume_from_snapshot() {
+        let lost = session(Player::B);
+        let oracle_pubkey = *lost.oracle_pubkey();
+        let mut snap = snapshot(&lost);
+        assert_eq!(AnySession::resume(&snap, oracle_pubkey).unwrap().stage(), "Created");
+
+        let opponent = Preimage::random();
+        snap.opponent_payment_hash = Some(opponent.payment_hash());
+        snap.commitment = Some(Commitment::new(b"Rock", lost.salt()));
+        // Committed but not revealed: commit again with a fresh salt
+        let resumed = AnySession::resume(&snap, oracle_pubkey).unwrap();
+        assert_eq!(resumed.stage(), "Funded");
+        assert_eq!(resumed.payment_hash(), lost.payment_hash());
+
+        snap.revealed_action = Some(GameAction::Rps(RpsAction::Rock));
+        snap.result = Some(GameResult::BWins);
+        snap.opponent_preimage = Some(opponent);
+        let resumed = AnySession::resume(&snap, oracle_pubkey).unwrap();
+        assert_eq!(resumed.stage(), "Judged");
+        assert_eq!(resumed.result(), Some(GameResult::BWins));
+
+        snap.preimage = None;
+        assert!(AnySession::resume(&snap, oracle_pubkey).is_err());
+    }
+
+    #[test]
+    fn test_advance_checks_stage() {
+        let mut any = AnySession::from(session(Player::A));
+        let before = any.payment_hash();
+
+        let err = any
+            .advance(|s: GameSession<Joined>| Ok(s.fund()))
+            .unwrap_err();
+        assert_eq!(
+            err,
+            SessionError::WrongStage {
+                expected: "Joined",
+                actual: "Created"
+            }
+        );
+
+        let hash = Preimage::random().payment_hash();
+        any.advance(|s: GameSession<Created>| Ok(s.joined(hash)))
+            .unwrap();
+        assert_eq!(any.stage(), "Joined");
+        assert_eq!(any.opponent_payment_hash(), Some(hash));
+        assert_eq!(any.payment_hash(), before);
+
+        // Stored sessions keep their stage and secrets
+        let json = serde_json::to_string(&any).unwrap();
+        let restored: AnySession = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.stage(), "Joined");
+        assert_eq!(restored.payment_hash(), before);
+    }
+}
@@ -0,0 +1,166 @@
+//! Protocol timeline.
+//!
+//! The oracle and each player record the protocol steps they take part in as
+//! [`TimelineEvent`]s: a message from one party to another (or a local step,
+//! where `from == to`) with a wall-clock timestamp. Merging the records of all
+//! three parties with [`merge_timelines`] gives the whole game as a sequence
+//! diagram.
+
+use crate::protocol::Player;
+use serde::{Deserialize, Serialize};
+use fiber_core::clock::{Clock, SystemClock};
+
+/// A party in the protocol
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Actor {
+    Oracle,
+    A,
+    B,
+}
+
+impl From<Player> for Actor {
+    fn from(player: Player) -> Self {
+        match player {
+            Player::A => Actor::A,
+            Player::B => Actor::B,
+        }
+    }
+}
+
+/// A step of the game protocol
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProtocolStep {
+    GameCreated,
+    GameJoined,
+    PaymentHashSubmitted,
+    /// Hold invoice created on the player's own Fiber node
+    InvoiceCreated,
+    InvoiceSubmitted,
+    EncryptedPreimageSubmitted,
+    /// Opponent's hold invoice paid (funds locked)
+    PaymentSent,
+    /// Opponent's payment found held on the player's own Fiber node
+    StakeConfirmed,
+    Committed,
+    Revealed,
+    Judged,
+    ResultReceived,
+    /// Hold invoice settled or cancelled on the Fiber node
+    Settled,
+    /// The player's own Fiber node cancelled or expired a hold invoice the
+    /// game still counted as held
+    InvoiceDropped,
+    /// A player left the game before committing
+    Aborted,
+    /// A player claimed their opponent stopped responding
+    TimeoutClaimed,
+    /// A player rebuilt their session from a resumption token
+    Resumed,
+}
+
+/// One recorded protocol step
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimelineEvent {
+    /// Milliseconds since the Unix epoch
+    pub at_ms: u64,
+    pub from: Actor,
+    pub to: Actor,
+    pub step: ProtocolStep,
+    /// Human-readable detail (amount, result, ...)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl TimelineEvent {
+    /// A step happening now.
+    pub fn new(from: impl Into<Actor>, to: impl Into<Actor>, step: ProtocolStep) -> Self {
+        Self::at(&SystemClock, from, to, step)
+    }
+
+    /// A step happening now by `clock`.
+    pub fn at(
+        clock: &dyn Clock,
+        from: impl Into<Actor>,
+        to: impl Into<Actor>,
+        step: ProtocolStep,
+    ) -> Self {
+        Self {
+            at_ms: clock.now_ms(),
+            from: from.into(),
+            to: to.into(),
+            step,
+            detail: None,
+        }
+    }
+
+    /// Attach a detail string.
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+}
+
+/// Merge timelines recorded by different parties into one, ordered by time.
+///
+/// The sort is stable, so steps with the same timestamp keep the order in
+/// which the timelines were given.
+pub fn merge_timelines<I>(timelines: I) -> Vec<TimelineEvent>
+where
+    I: IntoIterator<Item = Vec<TimelineEvent>>,
+{
+    let mut events: Vec<_> = timelines.into_iter().flatten().collect();
+    events.sort_by_key(|e| e.at_ms);
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event_at(at_ms: u64, step: ProtocolStep) -> TimelineEvent {
+        TimelineEvent {
+            at_ms,
+            ..TimelineEvent::new(Player::A, Actor::Oracle, step)
+        }
+    }
+
+    #[test]
+    fn test_merge_orders_by_time() {
+        let oracle = vec![
+            event_at(10, ProtocolStep::GameCreated),
+            event_at(30, ProtocolStep::Judged),
+        ];
+        let player = vec![
+            event_at(20, ProtocolStep::PaymentSent),
+            event_at(30, ProtocolStep::ResultReceived),
+        ];
+
+        let steps: Vec<_> = merge_timelines([oracle, player])
+            .into_iter()
+            .map(|e| e.step)
+            .collect();
+        assert_eq!(
+            steps,
+            [
+                ProtocolStep::GameCreated,
+                ProtocolStep::PaymentSent,
+                ProtocolStep::Judged,
+                ProtocolStep::ResultReceived,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_event_serialization() {
+        let event = TimelineEvent::new(Player::B, Player::A, ProtocolStep::PaymentSent)
+            .with_detail("1000 shannons");
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["from"], "B");
+        assert_eq!(json["to"], "A");
+        assert_eq!(json["step"], "PaymentSent");
+        assert_eq!(json["detail"], "1000 shannons");
+
+        let plain = TimelineEvent::new(Actor::Oracle, Actor::Oracle, ProtocolStep::Judged);
+        assert!(serde_json::to_value(&plain).unwrap().get("detail").is_none());
+    }
+}
@@ -0,0 +1,583 @@
+//! Recording protocol messages and checking them afterwards.
+//!
+//! A [`ProtocolRecorder`] keeps every signed message a party sends or
+//! receives, per game, as a [`ProtocolTrace`]: plain JSON that can be saved,
+//! mailed around and loaded elsewhere. [`verify_trace`] replays a trace
+//! offline. It checks every signature, that each seat only ever speaks with
+//! the key bound to it, that reveals open the commitments made earlier in
+//! this game at the stake and payment hash they name, and
+//! that the oracle's result is what the revealed actions give. It needs
+//! nothing but the trace and the oracle's public key, so it can settle a
+//! dispute or check that a third-party client speaks the protocol.
+
+use crate::crypto::{CommitContext, Commitment, PaymentHash, Salt};
+use crate::games::{self, GameAction};
+use crate::protocol::{Envelope, GameData, GameId, GameResult, Player, ResumptionToken};
+use secp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+/// Whether the recording party received or sent a message
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+/// What a recorded message is
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageKind {
+    CreateGame,
+    JoinGame,
+    PaymentHash,
+    Invoice,
+    EncryptedPreimage,
+    /// A player's confirmation that the opponent's stake is held
+    Funding,
+    Commit,
+    Reveal,
+    /// A player's verdict on a private game
+    Verdict,
+    Abort,
+    TimeoutClaim,
+    /// A player's report of resolving the hold invoice it created
+    Settlement,
+    Resume,
+    /// The oracle's signed game result
+    Result,
+    /// The oracle's answer to a resume
+    Snapshot,
+}
+
+/// One recorded message
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TraceEntry {
+    /// Milliseconds since the Unix epoch, when it was recorded
+    pub at_ms: u64,
+    pub direction: Direction,
+    pub kind: MessageKind,
+    pub message: Envelope<Value>,
+}
+
+/// Every message recorded for one game, in order
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProtocolTrace {
+    pub game_id: GameId,
+    pub entries: Vec<TraceEntry>,
+}
+
+impl ProtocolTrace {
+    pub fn new(game_id: GameId) -> Self {
+        Self {
+            game_id,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Read a trace saved with [`ProtocolTrace::save`].
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        serde_json::from_slice(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Write the trace as JSON.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_vec_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, json)
+    }
+}
+
+/// Collects the protocol messages a party sends and receives, per game
+///
+/// Traces are kept in memory and, with [`ProtocolRecorder::with_dir`], also
+/// written to `<dir>/<game_id>.json` after every message, picking up where
+/// an earlier run left off.
+#[derive(Default)]
+pub struct ProtocolRecorder {
+    traces: Mutex<HashMap<GameId, ProtocolTrace>>,
+    dir: Option<PathBuf>,
+}
+
+impl ProtocolRecorder {
+    /// A recorder that only keeps traces in memory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A recorder that also keeps a trace file per game in `dir`.
+    pub fn with_dir(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            traces: Mutex::default(),
+            dir: Some(dir),
+        })
+    }
+
+    /// Record `envelope` for `game_id`. The message is kept in memory even if
+    /// writing the trace file fails.
+    pub fn record<T: Serialize>(
+        &self,
+        game_id: GameId,
+        direction: Direction,
+        kind: MessageKind,
+        envelope: &Envelope<T>,
+    ) -> io::Result<()> {
+        let message = serde_json::to_value(envelope)
+            .and_then(serde_json::from_value)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let entry = TraceEntry {
+            at_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            direction,
+            kind,
+            message,
+        };
+
+        let mut traces = self.traces.lock().unwrap();
+        let trace = traces.entry(game_id).or_insert_with(|| {
+            self.path(&game_id)
+                .and_then(|path| ProtocolTrace::load(&path).ok())
+                .unwrap_or_else(|| ProtocolTrace::new(game_id))
+        });
+        trace.entries.push(entry);
+        match self.path(&game_id) {
+            Some(path) => trace.save(&path),
+            None => Ok(()),
+        }
+    }
+
+    /// Everything recorded for `game_id` so far
+    pub fn trace(&self, game_id: &GameId) -> Option<ProtocolTrace> {
+        if let Some(trace) = self.traces.lock().unwrap().get(game_id) {
+            return Some(trace.clone());
+        }
+        self.path(game_id)
+            .and_then(|path| ProtocolTrace::load(&path).ok())
+    }
+
+    fn path(&self, game_id: &GameId) -> Option<PathBuf> {
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.json", game_id)))
+    }
+}
+
+/// Why a trace doesn't check out
+#[derive(Debug, Error)]
+pub enum TraceError {
+    #[error("message {index}: invalid signature")]
+    BadSignature { index: usize },
+
+    #[error("message {index}: not signed by the key holding seat {player:?}")]
+    WrongSigner { index: usize, player: Player },
+
+    #[error("message {index}: not signed by the oracle")]
+    NotFromOracle { index: usize },
+
+    #[error("message {index}: {reason}")]
+    Malformed { index: usize, reason: String },
+
+    #[error("message {index}: player {player:?} revealed without committing")]
+    RevealWithoutCommit { index: usize, player: Player },
+
+    #[error("message {index}: player {player:?}'s reveal doesn't open their commitment")]
+    CommitmentMismatch { index: usize, player: Player },
+
+    #[error(
+        "message {index}: result names a different action for player {player:?} than they revealed"
+    )]
+    ActionMismatch { index: usize, player: Player },
+
+    #[error("message {index}: oracle announced {announced:?} but the actions give {judged:?}")]
+    ResultMismatch {
+        index: usize,
+        announced: GameResult,
+        judged: GameResult,
+    },
+}
+
+/// What [`verify_trace`] checked
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TraceReport {
+    pub messages: usize,
+    /// Reveals checked against their commitments
+    pub reveals: usize,
+    /// The oracle's result, if the trace has one that was re-judged
+    pub judged: Option<GameResult>,
+}
+
+#[derive(Deserialize)]
+struct FromPlayer {
+    player: Player,
+}
+
+#[derive(Deserialize)]
+struct CommitPayload {
+    commitment: Commitment,
+    amount_shannons: u64,
+    opponent_payment_hash: PaymentHash,
+}
+
+#[derive(Deserialize)]
+struct RevealPayload {
+    action: GameAction,
+    salt: Salt,
+}
+
+#[derive(Deserialize)]
+struct ResumePayload {
+    token: Envelope<ResumptionToken>,
+}
+
+#[derive(Deserialize)]
+struct ResultPayload {
+    result: Option<GameResult>,
+    game_data: Option<GameData>,
+}
+
+/// Re-check a recorded game against the oracle's key.
+///
+/// Player messages are attributed to seats as the oracle does: the creator's
+/// key holds seat A, the joiner's seat B, and a resume with a token from the
+/// oracle moves the seat to the key that presented it. A trace that starts
+/// mid-game binds each seat to the first key seen speaking for it.
+pub fn verify_trace(trace: &ProtocolTrace, oracle: &PublicKey) -> Result<TraceReport, TraceError> {
+    let mut report = TraceReport {
+        messages: trace.entries.len(),
+        ..TraceReport::default()
+    };
+    let mut seats: HashMap<Player, PublicKey> = HashMap::new();
+    let mut commits: HashMap<Player, (Commitment, CommitContext)> = HashMap::new();
+    let mut reveals: HashMap<Player, GameAction> = HashMap::new();
+
+    for (index, entry) in trace.entries.iter().enumerate() {
+        let envelope = &entry.message;
+        envelope
+            .verify()
+            .map_err(|_| TraceError::BadSignature { index })?;
+        let sender = envelope.sender;
+
+        match entry.kind {
+            MessageKind::Result | MessageKind::Snapshot if sender != *oracle => {
+                return Err(TraceError::NotFromOracle { index });
+            }
+            MessageKind::CreateGame => {
+                seats.insert(Player::A, sender);
+            }
+            MessageKind::JoinGame => {
+                seats.insert(Player::B, sender);
+            }
+            MessageKind::Resume => {
+                let resume: ResumePayload = decode(index, &envelope.payload)?;
+                let token = resume
+                    .token
+                    .open_from(oracle)
+                    .map_err(|_| TraceError::NotFromOracle { index })?;
+                seats.insert(token.player, sender);
+            }
+            _ => {}
+        }
+
+        if matches!(
+            entry.kind,
+            MessageKind::PaymentHash
+                | MessageKind::Invoice
+                | MessageKind::EncryptedPreimage
+                | MessageKind::Funding
+                | MessageKind::Commit
+                | MessageKind::Reveal
+                | MessageKind::Verdict
+                | MessageKind::Abort
+                | MessageKind::TimeoutClaim
+                | MessageKind::Settlement
+        ) {
+            if sender == *oracle {
+                // The oracle handing out a player's hash or encrypted preimage
+                continue;
+            }
+            let FromPlayer { player } = decode(index, &envelope.payload)?;
+            if *seats.entry(player).or_insert(sender) != sender {
+                return Err(TraceError::WrongSigner { index, player });
+            }
+
+            match entry.kind {
+                MessageKind::Commit => {
+                    let commit: CommitPayload = decode(index, &envelope.payload)?;
+                    let context = CommitContext {
+                        game_id: trace.game_id,
+                        amount_shannons: commit.amount_shannons,
+                        opponent_payment_hash: commit.opponent_payment_hash,
+                    };
+                    commits.insert(player, (commit.commitment, context));
+                }
+                MessageKind::Reveal => {
+                    let reveal: RevealPayload = decode(index, &envelope.payload)?;
+                    let (commitment, context) = commits
+                        .get(&player)
+                        .ok_or(TraceError::RevealWithoutCommit { index, player })?;
+                    if !commitment.verify_bound(&reveal.action.to_bytes(), context, &reveal.salt) {
+                        return Err(TraceError::CommitmentMismatch { index, player });
+                    }
+                    reveals.insert(player, reveal.action);
+                    report.reveals += 1;
+                }
+                _ => {}
+            }
+        }
+
+        if entry.kind == MessageKind::Result {
+            let result: ResultPayload = decode(index, &envelope.payload)?;
+            let (Some(announced), Some(data)) = (result.result, result.game_data) else {
+                // Pending, or decided by a timeout rather than by the actions
+                continue;
+            };
+            for (player, action) in [(Player::A, &data.action_a), (Player::B, &data.action_b)] {
+                if reveals
+                    .get(&player)
+                    .is_some_and(|revealed| revealed != action)
+                {
+                    return Err(TraceError::ActionMismatch { index, player });
+                }
+            }
+            let judged = judge(index, &data)?;
+            if judged != announced {
+                return Err(TraceError::ResultMismatch {
+                    index,
+                    announced,
+                    judged,
+                });
+            }
+            report.judged = Some(judged);
+        }
+    }
+
+    Ok(report)
+}
+
+fn decode<T: serde::de::DeserializeOwned>(index: usize, payload: &Value) -> Result<T, TraceError> {
+    serde_json::from_value(payload.clone()).map_err(|e| TraceError::Malformed {
+        index,
+        reason: e.to_string(),
+    })
+}
+
+/// Judge the actions in a result the way the oracle should have.
+fn judge(index: usize, data: &GameData) -> Result<GameResult, TraceError> {
+    let game_type = data.action_a.game_type();
+    if data.action_b.game_type() != game_type {
+        return Err(TraceError::Malformed {
+            index,
+            reason: "actions are for different games".to_string(),
+        });
+    }
+    let game = games::game(game_type);
+    if !game.validate_action(&data.action_a) || !game.validate_action(&data.action_b) {
+        return Err(TraceError::Malformed {
+            index,
+            reason: format!("invalid {} action", game.name),
+        });
+    }
+    let secret = if game.requires_oracle_secret() {
+        let secret = data.oracle_secret.as_ref().ok_or(TraceError::Malformed {
+            index,
+            reason: format!("{} result without the oracle's secret", game.name),
+        })?;
+        Some(secret.to_secret().ok_or(TraceError::Malformed {
+            index,
+            reason: "invalid oracle secret nonce".to_string(),
+        })?)
+    } else {
+        None
+    };
+    Ok(game.judge(&data.action_a, &data.action_b, secret.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::RpsAction;
+    use secp256k1::{SecretKey, SECP256K1};
+    use serde_json::json;
+
+    struct Game {
+        oracle: SecretKey,
+        a: SecretKey,
+        b: SecretKey,
+        recorder: ProtocolRecorder,
+        game_id: GameId,
+    }
+
+    impl Game {
+        fn new(recorder: ProtocolRecorder) -> Self {
+            let key = || SecretKey::new(&mut rand::thread_rng());
+            Self {
+                oracle: key(),
+                a: key(),
+                b: key(),
+                recorder,
+                game_id: GameId::new(),
+            }
+        }
+
+        fn oracle_pubkey(&self) -> PublicKey {
+            PublicKey::from_secret_key(SECP256K1, &self.oracle)
+        }
+
+        fn record(&self, key: &SecretKey, kind: MessageKind, payload: Value) {
+            let direction = if *key == self.oracle {
+                Direction::Outbound
+            } else {
+                Direction::Inbound
+            };
+            let envelope = Envelope::seal(payload, key).unwrap();
+            self.recorder
+                .record(self.game_id, direction, kind, &envelope)
+                .unwrap();
+        }
+
+        /// Rock against Scissors; the oracle announces `announced`
+        fn play(&self, announced: GameResult) -> ProtocolTrace {
+            self.record(
+                &self.a,
+                MessageKind::CreateGame,
+                json!({ "game_type": "RockPaperScissors" }),
+            );
+            self.record(&self.b, MessageKind::JoinGame, json!({}));
+            let moves = [
+                (Player::A, &self.a, RpsAction::Rock),
+                (Player::B, &self.b, RpsAction::Scissors),
+            ];
+            let mut reveals = Vec::new();
+            for (player, key, action) in moves {
+                let action = GameAction::Rps(action);
+                let salt = Salt::random();
+                let context = CommitContext {
+                    game_id: self.game_id,
+                    amount_shannons: 1000,
+                    opponent_payment_hash: crate::crypto::Preimage::random().payment_hash(),
+                };
+                let commitment = Commitment::bound(&action.to_bytes(), &context, &salt);
+                self.record(
+                    key,
+                    MessageKind::Commit,
+                    json!({
+                        "player": player,
+                        "commitment": commitment,
+                        "amount_shannons": context.amount_shannons,
+                        "opponent_payment_hash": context.opponent_payment_hash,
+                    }),
+                );
+                reveals.push((player, key, action, salt));
+            }
+            for (player, key, action, salt) in reveals {
+                self.record(
+                    key,
+                    MessageKind::Reveal,
+                    json!({ "player": player, "action": action, "salt": salt }),
+                );
+            }
+            self.record(
+                &self.oracle,
+                MessageKind::Result,
+                json!({
+                    "status": "completed",
+                    "result": announced,
+                    "game_data": {
+                        "action_a": { "Rps": "Rock" },
+                        "action_b": { "Rps": "Scissors" },
+                        "oracle_secret": null,
+                    },
+                }),
+            );
+            self.recorder.trace(&self.game_id).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_verify_recorded_game() {
+        let game = Game::new(ProtocolRecorder::new());
+        let trace = game.play(GameResult::AWins);
+        let report = verify_trace(&trace, &game.oracle_pubkey()).unwrap();
+        assert_eq!(report.messages, 7);
+        assert_eq!(report.reveals, 2);
+        assert_eq!(report.judged, Some(GameResult::AWins));
+
+        // Someone else's key can't speak for B
+        let mut forged = trace.clone();
+        let impostor = SecretKey::new(&mut rand::thread_rng());
+        forged.entries[5].message =
+            Envelope::seal(forged.entries[5].message.payload.clone(), &impostor).unwrap();
+        assert!(matches!(
+            verify_trace(&forged, &game.oracle_pubkey()),
+            Err(TraceError::WrongSigner {
+                index: 5,
+                player: Player::B
+            })
+        ));
+
+        let mut tampered = trace.clone();
+        tampered.entries[4].message.payload["action"] = json!({ "Rps": "Paper" });
+        assert!(matches!(
+            verify_trace(&tampered, &game.oracle_pubkey()),
+            Err(TraceError::BadSignature { index: 4 })
+        ));
+
+        // The same messages passed off as another game's
+        let replayed = ProtocolTrace {
+            game_id: GameId::new(),
+            ..trace
+        };
+        assert!(matches!(
+            verify_trace(&replayed, &game.oracle_pubkey()),
+            Err(TraceError::CommitmentMismatch {
+                index: 4,
+                player: Player::A
+            })
+        ));
+    }
+
+    #[test]
+    fn test_wrong_result_detected() {
+        let game = Game::new(ProtocolRecorder::new());
+        let trace = game.play(GameResult::BWins);
+        assert!(matches!(
+            verify_trace(&trace, &game.oracle_pubkey()),
+            Err(TraceError::ResultMismatch {
+                announced: GameResult::BWins,
+                judged: GameResult::AWins,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_trace_file_survives_restart() {
+        let dir = std::env::temp_dir().join(format!("fiber-game-trace-{}", uuid::Uuid::new_v4()));
+        let game = Game::new(ProtocolRecorder::with_dir(&dir).unwrap());
+        game.record(&game.a, MessageKind::CreateGame, json!({}));
+
+        // A new recorder on the same directory carries on the same trace
+        let restarted = Game {
+            recorder: ProtocolRecorder::with_dir(&dir).unwrap(),
+            ..game
+        };
+        restarted.record(&restarted.b, MessageKind::JoinGame, json!({}));
+        let trace = ProtocolTrace::load(&dir.join(format!("{}.json", restarted.game_id))).unwrap();
+        assert_eq!(trace.entries.len(), 2);
+        assert_eq!(trace.entries[1].kind, MessageKind::JoinGame);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,6 +1,5 @@
 //! Protocol types.
 
-use secp256k1::PublicKey;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::str::FromStr;
@@ -85,7 +84,7 @@ impl fmt::Display for GameResult {
 }
 
 /// Player identifier
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Player {
     A,
     B,
@@ -110,24 +109,75 @@ impl fmt::Display for Player {
     }
 }
 
-/// Game session information
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct GameSession {
-    /// Unique game identifier
-    pub game_id: GameId,
-    /// Type of game
-    pub game_type: crate::games::GameType,
-    /// Oracle's public key
-    #[serde(with = "pubkey_serde")]
-    pub oracle_pubkey: PublicKey,
-    /// Oracle's commitment point (R) for this game
-    #[serde(with = "pubkey_serde")]
-    pub oracle_commitment_point: PublicKey,
-    /// Oracle's commitment hash (for games requiring Oracle secret)
-    pub oracle_commitment: Option<[u8; 32]>,
+/// Rounds a match may run to; a draw in the last one is refunded whatever
+/// the [`DrawPolicy`]
+pub const MAX_MATCH_ROUNDS: u32 = 5;
+
+/// What happens to the stakes when a game ends in a draw
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DrawPolicy {
+    /// Both players cancel, so each gets their stake back
+    #[default]
+    Refund,
+    /// Both players cancel and play another round on new invoices
+    Rematch,
+    /// Both invoices stay held and the next round is played for them
+    RematchHeld,
+}
+
+impl DrawPolicy {
+    /// Whether a draw is followed by another round
+    pub fn rematches(self) -> bool {
+        !matches!(self, DrawPolicy::Refund)
+    }
+
+    /// Whether the next round is played for the invoices still held from
+    /// the draw, with the same payment hashes
+    pub fn reuses_invoices(self) -> bool {
+        matches!(self, DrawPolicy::RematchHeld)
+    }
+
+    /// Whether a game that ended with `result` as `round` of its match is
+    /// followed by another round
+    pub fn replays(self, result: GameResult, round: &MatchRound) -> bool {
+        result == GameResult::Draw && self.rematches() && round.number < MAX_MATCH_ROUNDS
+    }
+
+    /// Whether such a game leaves its invoices held for the next round
+    pub fn carries_invoices(self, result: GameResult, round: &MatchRound) -> bool {
+        self.reuses_invoices() && self.replays(result, round)
+    }
+}
+
+/// Where a game stands in its match: the rounds played after draws share
+/// the first round's id as their match id
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MatchRound {
+    pub match_id: GameId,
+    /// Counted from 1
+    pub number: u32,
+}
+
+impl MatchRound {
+    /// The round a match starts with
+    pub fn first(game_id: GameId) -> Self {
+        Self {
+            match_id: game_id,
+            number: 1,
+        }
+    }
+
+    /// The round played after this one
+    pub fn next(&self) -> Self {
+        Self {
+            match_id: self.match_id,
+            number: self.number + 1,
+        }
+    }
 }
 
-mod pubkey_serde {
+pub(super) mod pubkey_serde {
     use secp256k1::PublicKey;
     use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
@@ -165,4 +215,22 @@ mod tests {
         assert_eq!(GameResult::BWins.as_str(), "B wins");
         assert_eq!(GameResult::Draw.as_str(), "Draw");
     }
+
+    #[test]
+    fn test_draw_policy_replays_draws_up_to_the_last_round() {
+        let first = MatchRound::first(GameId::new());
+        assert!(!DrawPolicy::Refund.replays(GameResult::Draw, &first));
+        assert!(DrawPolicy::Rematch.replays(GameResult::Draw, &first));
+        assert!(!DrawPolicy::Rematch.replays(GameResult::AWins, &first));
+        assert!(!DrawPolicy::Rematch.carries_invoices(GameResult::Draw, &first));
+        assert!(DrawPolicy::RematchHeld.carries_invoices(GameResult::Draw, &first));
+
+        let mut last = first;
+        for _ in 1..MAX_MATCH_ROUNDS {
+            last = last.next();
+        }
+        assert_eq!(last.match_id, first.match_id);
+        assert_eq!(last.number, MAX_MATCH_ROUNDS);
+        assert!(!DrawPolicy::RematchHeld.replays(GameResult::Draw, &last));
+    }
 }
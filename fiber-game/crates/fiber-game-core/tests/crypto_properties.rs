@@ -0,0 +1,224 @@
+//! Property tests for the crypto primitives.
+//!
+//! The unit tests next to each primitive check a handful of fixed values.
+//! These run the same claims over generated actions, salts, preimages, keys
+//! and game ids.
+
+use fiber_game_core::{
+    crypto::{
+        compute_signature_points, Commitment, EncryptedPreimage, Preimage, Salt, SignaturePoint,
+    },
+    games::{GameAction, RpsAction},
+    protocol::GameId,
+};
+use proptest::prelude::*;
+use secp256k1::{PublicKey, Scalar, SecretKey, SECP256K1};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+const OUTCOMES: [&str; 3] = ["A wins", "B wins", "Draw"];
+
+fn secret_key() -> impl Strategy<Value = SecretKey> {
+    any::<[u8; 32]>().prop_filter_map("not a valid secret key", |b| SecretKey::from_slice(&b).ok())
+}
+
+fn public_key() -> impl Strategy<Value = PublicKey> {
+    secret_key().prop_map(|sk| PublicKey::from_secret_key(SECP256K1, &sk))
+}
+
+fn game_id() -> impl Strategy<Value = GameId> {
+    any::<u128>().prop_map(|n| GameId::from_uuid(Uuid::from_u128(n)))
+}
+
+fn salt() -> impl Strategy<Value = Salt> {
+    any::<[u8; 32]>().prop_map(Salt::from_bytes)
+}
+
+fn preimage() -> impl Strategy<Value = Preimage> {
+    any::<[u8; 32]>().prop_map(Preimage::from_bytes)
+}
+
+fn action() -> impl Strategy<Value = GameAction> {
+    prop_oneof![
+        prop_oneof![
+            Just(RpsAction::Rock),
+            Just(RpsAction::Paper),
+            Just(RpsAction::Scissors)
+        ]
+        .prop_map(GameAction::Rps),
+        any::<u8>().prop_map(GameAction::GuessNumber),
+    ]
+}
+
+fn signature_point() -> impl Strategy<Value = SignaturePoint> {
+    (
+        public_key(),
+        public_key(),
+        game_id(),
+        prop::sample::select(&OUTCOMES[..]),
+    )
+        .prop_map(|(o, r, id, outcome)| SignaturePoint::compute(&o, &r, &id, outcome))
+}
+
+proptest! {
+    #[test]
+    fn test_commitment_opens_with_its_inputs(
+        action in prop::collection::vec(any::<u8>(), 0..64),
+        salt in salt(),
+    ) {
+        prop_assert!(Commitment::new(&action, &salt).verify(&action, &salt));
+    }
+
+    #[test]
+    fn test_commitment_binds_action_and_salt(
+        action1 in prop::collection::vec(any::<u8>(), 0..64),
+        action2 in prop::collection::vec(any::<u8>(), 0..64),
+        salt1 in salt(),
+        salt2 in salt(),
+    ) {
+        prop_assume!(action1 != action2 || salt1.as_bytes() != salt2.as_bytes());
+        let commitment = Commitment::new(&action1, &salt1);
+        prop_assert!(!commitment.verify(&action2, &salt2));
+        // Moving bytes between the action and the salt opens nothing either:
+        // the salt has a fixed length, so `action || salt` splits one way only
+        if let Some((&last, head)) = action1.split_last() {
+            let mut shifted = [0u8; 32];
+            shifted[0] = last;
+            shifted[1..].copy_from_slice(&salt1.as_bytes()[..31]);
+            prop_assert!(!commitment.verify(head, &Salt::from_bytes(shifted)));
+        }
+    }
+
+    #[test]
+    fn test_commitment_binds_game_actions(
+        action1 in action(),
+        action2 in action(),
+        salt in salt(),
+    ) {
+        prop_assume!(action1 != action2);
+        let commitment = Commitment::new(&action1.to_bytes(), &salt);
+        prop_assert!(!commitment.verify(&action2.to_bytes(), &salt));
+    }
+
+    #[test]
+    fn test_commitment_hides_action(
+        action in action(),
+        salt1 in salt(),
+        salt2 in salt(),
+    ) {
+        prop_assume!(salt1.as_bytes() != salt2.as_bytes());
+        let c1 = Commitment::new(&action.to_bytes(), &salt1);
+        let c2 = Commitment::new(&action.to_bytes(), &salt2);
+        // The same move under fresh salts looks unrelated: about half the
+        // bits differ (eight standard deviations of slack either side)
+        let differing: u32 = c1
+            .as_bytes()
+            .iter()
+            .zip(c2.as_bytes())
+            .map(|(a, b)| (a ^ b).count_ones())
+            .sum();
+        prop_assert!((64..=192).contains(&differing), "{} bits differ", differing);
+    }
+
+    #[test]
+    fn test_encrypted_preimage_roundtrip(preimage in preimage(), point in signature_point()) {
+        let encrypted = EncryptedPreimage::encrypt(&preimage, &point);
+        prop_assert_eq!(*encrypted.decrypt(&point).as_bytes(), *preimage.as_bytes());
+        // The mask is a one-time pad: applying it twice is the identity
+        let again = EncryptedPreimage::from_bytes(*encrypted.as_bytes()).decrypt(&point);
+        prop_assert_eq!(
+            *EncryptedPreimage::encrypt(&again, &point).as_bytes(),
+            *encrypted.as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_encrypted_preimage_needs_the_winning_point(
+        preimage in preimage(),
+        oracle in public_key(),
+        nonce in public_key(),
+        game_id in game_id(),
+    ) {
+        let points = compute_signature_points(&oracle, &nonce, &game_id);
+        let encrypted = EncryptedPreimage::encrypt(&preimage, &points.a_wins);
+        let hash = preimage.payment_hash();
+        prop_assert!(hash.verify(&encrypted.decrypt(&points.a_wins)));
+        prop_assert!(!hash.verify(&encrypted.decrypt(&points.b_wins)));
+        prop_assert!(!hash.verify(&encrypted.decrypt(&points.draw)));
+    }
+
+    #[test]
+    fn test_encrypted_preimage_serde_roundtrip(preimage in preimage(), point in signature_point()) {
+        let encrypted = EncryptedPreimage::encrypt(&preimage, &point);
+        let json = serde_json::to_string(&encrypted).unwrap();
+        let back: EncryptedPreimage = serde_json::from_str(&json).unwrap();
+        prop_assert_eq!(back.as_bytes(), encrypted.as_bytes());
+    }
+
+    #[test]
+    fn test_signature_points_are_deterministic(
+        oracle in public_key(),
+        nonce in public_key(),
+        game_id in game_id(),
+    ) {
+        let first = compute_signature_points(&oracle, &nonce, &game_id);
+        let second = compute_signature_points(&oracle, &nonce, &game_id);
+        prop_assert_eq!(first.a_wins, second.a_wins);
+        prop_assert_eq!(first.b_wins, second.b_wins);
+        prop_assert_eq!(first.draw, second.draw);
+        prop_assert_eq!(
+            first.a_wins,
+            SignaturePoint::compute(&oracle, &nonce, &game_id, "A wins")
+        );
+
+        prop_assert_ne!(first.a_wins, first.b_wins);
+        prop_assert_ne!(first.a_wins, first.draw);
+        prop_assert_ne!(first.b_wins, first.draw);
+    }
+
+    #[test]
+    fn test_signature_points_depend_on_every_input(
+        oracle1 in public_key(),
+        oracle2 in public_key(),
+        nonce1 in public_key(),
+        nonce2 in public_key(),
+        id1 in game_id(),
+        id2 in game_id(),
+    ) {
+        prop_assume!(oracle1 != oracle2 && nonce1 != nonce2 && id1 != id2);
+        let point = SignaturePoint::compute(&oracle1, &nonce1, &id1, "A wins");
+        prop_assert_ne!(point, SignaturePoint::compute(&oracle2, &nonce1, &id1, "A wins"));
+        prop_assert_ne!(point, SignaturePoint::compute(&oracle1, &nonce2, &id1, "A wins"));
+        prop_assert_ne!(point, SignaturePoint::compute(&oracle1, &nonce1, &id2, "A wins"));
+    }
+
+    #[test]
+    fn test_signature_point_is_the_oracle_attestation_times_g(
+        oracle_key in secret_key(),
+        nonce_key in secret_key(),
+        game_id in game_id(),
+        outcome in prop::sample::select(&OUTCOMES[..]),
+    ) {
+        // s = r + H(R || O || game_id || outcome) * o is what the oracle
+        // reveals for the outcome; its point must be the one preimages are
+        // encrypted to
+        let oracle = PublicKey::from_secret_key(SECP256K1, &oracle_key);
+        let nonce = PublicKey::from_secret_key(SECP256K1, &nonce_key);
+        let mut hasher = Sha256::new();
+        hasher.update(nonce.serialize());
+        hasher.update(oracle.serialize());
+        hasher.update(game_id.as_bytes());
+        hasher.update(outcome.as_bytes());
+        let challenge = Scalar::from_be_bytes(hasher.finalize().into()).unwrap();
+        let s = oracle_key
+            .mul_tweak(&challenge)
+            .unwrap()
+            .add_tweak(&Scalar::from(nonce_key))
+            .unwrap();
+
+        prop_assert_eq!(
+            PublicKey::from_secret_key(SECP256K1, &s),
+            *SignaturePoint::compute(&oracle, &nonce, &game_id, outcome).as_pubkey()
+        );
+    }
+}
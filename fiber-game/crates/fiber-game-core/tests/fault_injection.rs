@@ -0,0 +1,316 @@
+//! Fault-injection tests for the full game flow.
+//!
+//! Two seats play through their `GameSession`s while the hold invoices live
+//! on one [`MockFiberClient`] standing in for the Fiber network, as in the
+//! demo's scripted games. Each test breaks a Fiber call at some step, either
+//! refused outright or carried out with the response lost, and checks that
+//! the players recover the way a frontend would (retry, or ask for the
+//! invoice status), that no stake is ever unaccounted for, and that no
+//! invoice is left holding funds once the game is over. Every table is
+//! audited with a [`FundsAuditor`] when the scenario drops it.
+
+use fiber_game_core::{
+    crypto::{compute_signature_points, EncryptedPreimage, PaymentHash, Preimage},
+    fiber::{FiberError, HoldInvoice, MockCall, PaymentStatus},
+    games::{GameAction, GameJudge, GameType, RpsAction, RpsGame},
+    protocol::{AbortReason, GameResult, GameSession, Joined, Player, Settled},
+};
+use fiber_test_fixtures::{
+    game::{players_network, OracleKeys},
+    payments::{STAKE, WALLET},
+    FundsAuditor, MockNetwork,
+};
+
+/// How often a player tries a Fiber call before giving up
+const ATTEMPTS: usize = 3;
+
+/// A point in the game where a Fiber call is made
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Step {
+    /// The player creates its hold invoice
+    CreateInvoice(Player),
+    /// The player pays the opponent's invoice
+    Pay(Player),
+    /// The winner settles its invoice with the opponent's preimage
+    Settle(Player),
+    /// The player cancels its invoice, refunding the opponent
+    Cancel(Player),
+}
+
+impl Step {
+    fn call(self) -> MockCall {
+        match self {
+            Step::CreateInvoice(_) => MockCall::CreateHoldInvoice,
+            Step::Pay(_) => MockCall::PayHoldInvoice,
+            Step::Settle(_) => MockCall::SettleInvoice,
+            Step::Cancel(_) => MockCall::CancelInvoice,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Mode {
+    /// The node refuses the call
+    Refused,
+    /// The node carries out the call, but the response is lost
+    ResponseLost,
+}
+
+const MODES: [Mode; 2] = [Mode::Refused, Mode::ResponseLost];
+
+fn injected() -> FiberError {
+    FiberError::NetworkError("injected failure".to_string())
+}
+
+/// The Fiber network and both players' wallets, with one fault armed
+struct Table {
+    network: MockNetwork<Player>,
+    auditor: FundsAuditor,
+    fault: Option<(Step, Mode)>,
+}
+
+impl Table {
+    fn new(fault: Option<(Step, Mode)>) -> Self {
+        let network = players_network();
+        Self {
+            auditor: FundsAuditor::open(&network),
+            network,
+            fault,
+        }
+    }
+
+    fn wallet(&self, player: Player) -> u64 {
+        self.network.wallet(player)
+    }
+
+    /// Arm the fault if it is meant for `step`
+    fn before(&mut self, step: Step) {
+        let client = self.network.client();
+        match self.fault {
+            Some((at, Mode::Refused)) if at == step => client.fail_next(step.call(), injected()),
+            Some((at, Mode::ResponseLost)) if at == step => {
+                client.lose_next_response(step.call(), injected())
+            }
+            _ => {}
+        }
+    }
+
+    async fn create_invoice(&mut self, owner: Player, payment_hash: &PaymentHash) -> HoldInvoice {
+        self.before(Step::CreateInvoice(owner));
+        for _ in 0..ATTEMPTS {
+            // Creating an invoice again before anyone paid it is harmless
+            if let Ok(invoice) = self.network.invoice(payment_hash).await {
+                return invoice;
+            }
+        }
+        panic!("{:?} could not create an invoice", owner);
+    }
+
+    async fn pay(&mut self, payer: Player, invoice: &HoldInvoice) -> Result<(), FiberError> {
+        self.before(Step::Pay(payer));
+        let mut last_error = None;
+        for _ in 0..ATTEMPTS {
+            if let Err(e) = self.network.pay(payer, invoice).await {
+                // The payment may have gone through anyway; paying again
+                // would be refused, so ask
+                if self.network.status(&invoice.payment_hash).await != Some(PaymentStatus::Held) {
+                    last_error = Some(e);
+                    continue;
+                }
+                self.network.paid(payer, invoice);
+            }
+            self.network.assert_conserved();
+            return Ok(());
+        }
+        Err(last_error.unwrap())
+    }
+
+    async fn settle(&mut self, owner: Player, payment_hash: &PaymentHash, preimage: &Preimage) {
+        self.before(Step::Settle(owner));
+        for _ in 0..ATTEMPTS {
+            if self.network.settle(owner, payment_hash, preimage).await.is_err() {
+                if self.network.status(payment_hash).await != Some(PaymentStatus::Settled) {
+                    continue;
+                }
+                self.network.settled(owner, payment_hash);
+            }
+            self.network.assert_conserved();
+            return;
+        }
+        panic!("{:?} could not settle its invoice", owner);
+    }
+
+    async fn cancel(&mut self, owner: Player, payment_hash: &PaymentHash) {
+        self.before(Step::Cancel(owner));
+        for _ in 0..ATTEMPTS {
+            if self.network.cancel(payment_hash).await.is_err() {
+                if self.network.status(payment_hash).await != Some(PaymentStatus::Cancelled) {
+                    continue;
+                }
+                self.network.cancelled(payment_hash);
+            }
+            self.network.assert_conserved();
+            return;
+        }
+        panic!("{:?} could not cancel its invoice", owner);
+    }
+}
+
+impl Drop for Table {
+    fn drop(&mut self) {
+        // Don't bury the failure the scenario already hit
+        if !std::thread::panicking() {
+            self.auditor.assert_balanced(&self.network);
+        }
+    }
+}
+
+/// Seat both players and have each create its invoice, locked to the
+/// opponent's payment hash
+async fn seat(
+    table: &mut Table,
+) -> (
+    GameSession<Joined>,
+    GameSession<Joined>,
+    HoldInvoice,
+    HoldInvoice,
+) {
+    let (a, b) = OracleKeys::random().seat(GameType::RockPaperScissors);
+    let (hash_a, hash_b) = (a.payment_hash(), b.payment_hash());
+
+    let invoice_a = table.create_invoice(Player::A, &hash_b).await;
+    let invoice_b = table.create_invoice(Player::B, &hash_a).await;
+    (a, b, invoice_a, invoice_b)
+}
+
+/// Play Rock for A against `action_b` with `fault` injected on the way.
+async fn play(
+    action_b: RpsAction,
+    fault: Option<(Step, Mode)>,
+) -> (Table, GameSession<Settled>, GameSession<Settled>) {
+    let mut table = Table::new(fault);
+    let (a, b, invoice_a, invoice_b) = seat(&mut table).await;
+
+    // Each pays the other's invoice
+    table.pay(Player::A, &invoice_b).await.unwrap();
+    table.pay(Player::B, &invoice_a).await.unwrap();
+    let (a, b) = (a.fund(), b.fund());
+
+    // Each encrypts its preimage to the point the opponent learns by winning
+    let points = compute_signature_points(a.oracle_pubkey(), a.commitment_point(), &a.game_id());
+    let for_a = EncryptedPreimage::encrypt(b.preimage(), &points.a_wins);
+    let for_b = EncryptedPreimage::encrypt(a.preimage(), &points.b_wins);
+
+    let a = a.commit(GameAction::Rps(RpsAction::Rock)).unwrap().reveal();
+    let b = b.commit(GameAction::Rps(action_b)).unwrap().reveal();
+
+    let result = RpsGame::judge(&a.state().action, &b.state().action, None);
+    let (preimage_a, preimage_b) = match result {
+        GameResult::AWins => (Some(for_a.decrypt(&points.a_wins)), None),
+        GameResult::BWins => (None, Some(for_b.decrypt(&points.b_wins))),
+        GameResult::Draw => (None, None),
+    };
+    let a = a.judge(result, preimage_a).unwrap();
+    let b = b.judge(result, preimage_b).unwrap();
+
+    // The winner settles its invoice, everyone else cancels theirs
+    for (seat, invoice) in [(&a, &invoice_a), (&b, &invoice_b)] {
+        match seat.state().opponent_preimage.as_ref() {
+            Some(preimage) => {
+                table
+                    .settle(seat.role(), &invoice.payment_hash, preimage)
+                    .await
+            }
+            None => table.cancel(seat.role(), &invoice.payment_hash).await,
+        }
+    }
+
+    (table, a.settle().unwrap(), b.settle().unwrap())
+}
+
+fn assert_paid_out(table: &Table, a: &GameSession<Settled>, b: &GameSession<Settled>) {
+    table.network.assert_released();
+    for seat in [a, b] {
+        assert_eq!(
+            table.wallet(seat.role()) as i64 - WALLET as i64,
+            seat.state().amount_won,
+            "{:?}'s wallet doesn't match the result",
+            seat.role()
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_a_wins_despite_any_single_fiber_failure() {
+    let steps = [
+        Step::CreateInvoice(Player::A),
+        Step::CreateInvoice(Player::B),
+        Step::Pay(Player::A),
+        Step::Pay(Player::B),
+        Step::Settle(Player::A),
+        Step::Cancel(Player::B),
+    ];
+    for step in steps {
+        for mode in MODES {
+            let (table, a, b) = play(RpsAction::Scissors, Some((step, mode))).await;
+            assert_eq!(a.state().result, GameResult::AWins, "{:?} {:?}", step, mode);
+            assert_paid_out(&table, &a, &b);
+            assert_eq!(table.wallet(Player::A), WALLET + STAKE);
+            assert_eq!(table.wallet(Player::B), WALLET - STAKE);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_draw_refunds_both_despite_failed_cancel() {
+    for step in [Step::Cancel(Player::A), Step::Cancel(Player::B)] {
+        for mode in MODES {
+            let (table, a, b) = play(RpsAction::Rock, Some((step, mode))).await;
+            assert_eq!(a.state().result, GameResult::Draw, "{:?} {:?}", step, mode);
+            assert_paid_out(&table, &a, &b);
+            assert_eq!(table.wallet(Player::A), WALLET);
+            assert_eq!(table.wallet(Player::B), WALLET);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_b_wins_despite_lost_settlement() {
+    let fault = Some((Step::Settle(Player::B), Mode::ResponseLost));
+    let (table, a, b) = play(RpsAction::Paper, fault).await;
+    assert_eq!(b.state().result, GameResult::BWins);
+    assert_paid_out(&table, &a, &b);
+    assert_eq!(table.wallet(Player::B), WALLET + STAKE);
+}
+
+#[tokio::test]
+async fn test_payment_that_never_goes_through_aborts_with_refund() {
+    for mode in MODES {
+        let mut table = Table::new(Some((Step::Cancel(Player::B), mode)));
+        let (a, b, invoice_a, invoice_b) = seat(&mut table).await;
+
+        table.pay(Player::A, &invoice_b).await.unwrap();
+        // B's node is down for good
+        for _ in 0..ATTEMPTS {
+            table
+                .network
+                .client()
+                .fail_next(MockCall::PayHoldInvoice, injected());
+        }
+        assert!(table.pay(Player::B, &invoice_a).await.is_err());
+        assert_eq!(table.wallet(Player::B), WALLET);
+
+        // Nobody committed, so the game is called off and both invoices are
+        // cancelled; A gets its payment back even though B's first cancel fails
+        let a = a.fund().abort(Player::B, AbortReason::PaymentFailed);
+        let b = b.abort(Player::B, AbortReason::PaymentFailed);
+        table.cancel(Player::A, &invoice_a.payment_hash).await;
+        table.cancel(Player::B, &invoice_b.payment_hash).await;
+
+        table.network.assert_released();
+        assert_eq!(table.wallet(Player::A), WALLET);
+        assert_eq!(table.wallet(Player::B), WALLET);
+        assert_eq!(a.state().reason, AbortReason::PaymentFailed);
+        assert_eq!(b.state().by, Player::B);
+    }
+}
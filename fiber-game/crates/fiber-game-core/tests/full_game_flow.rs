@@ -3,21 +3,21 @@
 //! These tests simulate complete game sessions from start to finish.
 
 use fiber_game_core::{
-    crypto::{compute_signature_points, Commitment, EncryptedPreimage, Preimage, Salt},
+    crypto::{Commitment, EncryptedPreimage, Preimage, Salt},
     fiber::{FiberClient, MockFiberClient},
     games::{GameAction, GameJudge, GuessNumberGame, OracleSecret, RpsAction, RpsGame},
     protocol::{GameId, GameResult},
 };
+use fiber_test_fixtures::{
+    game::OracleKeys,
+    payments::{EXPIRY_SECS, STAKE, WALLET},
+};
 
 /// Simulate a complete Rock-Paper-Scissors game where A wins
 #[tokio::test]
 async fn test_full_rps_game_a_wins() {
     // Setup: Oracle generates keys
-    let secp = secp256k1::Secp256k1::new();
-    let oracle_sk = secp256k1::SecretKey::new(&mut rand::thread_rng());
-    let oracle_pk = secp256k1::PublicKey::from_secret_key(&secp, &oracle_sk);
-    let commitment_sk = secp256k1::SecretKey::new(&mut rand::thread_rng());
-    let commitment_point = secp256k1::PublicKey::from_secret_key(&secp, &commitment_sk);
+    let oracle = OracleKeys::random();
     let game_id = GameId::new();
 
     // Phase 1: Both players generate preimages and choose actions
@@ -32,15 +32,15 @@ async fn test_full_rps_game_a_wins() {
     let salt_b = Salt::random();
 
     // Phase 2: Setup Fiber clients and exchange hold invoices
-    let fiber_a = MockFiberClient::new(10_000);
-    let fiber_b = MockFiberClient::new(10_000);
+    let fiber_a = MockFiberClient::new(WALLET);
+    let fiber_b = MockFiberClient::new(WALLET);
 
     let invoice_a = fiber_a
-        .create_hold_invoice(&payment_hash_a, 1000, 3600)
+        .create_hold_invoice(&payment_hash_a, STAKE, EXPIRY_SECS)
         .await
         .unwrap();
     let invoice_b = fiber_b
-        .create_hold_invoice(&payment_hash_b, 1000, 3600)
+        .create_hold_invoice(&payment_hash_b, STAKE, EXPIRY_SECS)
         .await
         .unwrap();
 
@@ -49,11 +49,11 @@ async fn test_full_rps_game_a_wins() {
     fiber_b.pay_hold_invoice(&invoice_a).await.unwrap();
 
     // Verify funds are locked
-    assert_eq!(fiber_a.balance(), 9000);
-    assert_eq!(fiber_b.balance(), 9000);
+    assert_eq!(fiber_a.balance(), WALLET - STAKE);
+    assert_eq!(fiber_b.balance(), WALLET - STAKE);
 
     // Phase 3: Compute signature points and create encrypted preimages
-    let sig_points = compute_signature_points(&oracle_pk, &commitment_point, &game_id);
+    let sig_points = oracle.signature_points(&game_id);
 
     // A encrypts their preimage with B_wins point (so B can claim if B wins)
     // B encrypts their preimage with A_wins point (so A can claim if A wins)
@@ -91,20 +91,13 @@ async fn test_full_rps_game_a_wins() {
     fiber_b.cancel_invoice(&payment_hash_a).await.unwrap();
 
     // Final balances: A gained 1000, B lost 1000
-    assert_eq!(fiber_a.balance(), 10000); // 9000 + 1000 from settling B's invoice
-    assert_eq!(fiber_b.balance(), 9000); // Lost the 1000 that was paid to A
+    assert_eq!(fiber_a.balance(), WALLET); // Got its stake back by settling B's invoice
+    assert_eq!(fiber_b.balance(), WALLET - STAKE); // Lost the 1000 that was paid to A
 }
 
 /// Simulate a Rock-Paper-Scissors draw
 #[tokio::test]
 async fn test_full_rps_game_draw() {
-    let secp = secp256k1::Secp256k1::new();
-    let _oracle_sk = secp256k1::SecretKey::new(&mut rand::thread_rng());
-    let _oracle_pk = secp256k1::PublicKey::from_secret_key(&secp, &_oracle_sk);
-    let _commitment_sk = secp256k1::SecretKey::new(&mut rand::thread_rng());
-    let _commitment_point = secp256k1::PublicKey::from_secret_key(&secp, &_commitment_sk);
-    let _game_id = GameId::new();
-
     // Both choose Rock
     let action_a = GameAction::Rps(RpsAction::Rock);
     let action_b = GameAction::Rps(RpsAction::Rock);
@@ -114,15 +107,15 @@ async fn test_full_rps_game_draw() {
     let payment_hash_a = preimage_a.payment_hash();
     let payment_hash_b = preimage_b.payment_hash();
 
-    let fiber_a = MockFiberClient::new(10_000);
-    let fiber_b = MockFiberClient::new(10_000);
+    let fiber_a = MockFiberClient::new(WALLET);
+    let fiber_b = MockFiberClient::new(WALLET);
 
     let invoice_a = fiber_a
-        .create_hold_invoice(&payment_hash_a, 1000, 3600)
+        .create_hold_invoice(&payment_hash_a, STAKE, EXPIRY_SECS)
         .await
         .unwrap();
     let invoice_b = fiber_b
-        .create_hold_invoice(&payment_hash_b, 1000, 3600)
+        .create_hold_invoice(&payment_hash_b, STAKE, EXPIRY_SECS)
         .await
         .unwrap();
 
@@ -139,18 +132,14 @@ async fn test_full_rps_game_draw() {
 
     // Balances unchanged (funds were locked then cancelled, so still 9000 each)
     // Note: In a real system, the payer would get refunded
-    assert_eq!(fiber_a.balance(), 9000);
-    assert_eq!(fiber_b.balance(), 9000);
+    assert_eq!(fiber_a.balance(), WALLET - STAKE);
+    assert_eq!(fiber_b.balance(), WALLET - STAKE);
 }
 
 /// Simulate a Guess the Number game where B wins
 #[tokio::test]
 async fn test_guess_number_b_wins() {
-    let secp = secp256k1::Secp256k1::new();
-    let oracle_sk = secp256k1::SecretKey::new(&mut rand::thread_rng());
-    let oracle_pk = secp256k1::PublicKey::from_secret_key(&secp, &oracle_sk);
-    let commitment_sk = secp256k1::SecretKey::new(&mut rand::thread_rng());
-    let commitment_point = secp256k1::PublicKey::from_secret_key(&secp, &commitment_sk);
+    let oracle = OracleKeys::random();
     let game_id = GameId::new();
 
     // Oracle commits to secret number 50
@@ -169,15 +158,15 @@ async fn test_guess_number_b_wins() {
     let salt_a = Salt::random();
     let salt_b = Salt::random();
 
-    let fiber_a = MockFiberClient::new(10_000);
-    let fiber_b = MockFiberClient::new(10_000);
+    let fiber_a = MockFiberClient::new(WALLET);
+    let fiber_b = MockFiberClient::new(WALLET);
 
     let invoice_a = fiber_a
-        .create_hold_invoice(&payment_hash_a, 1000, 3600)
+        .create_hold_invoice(&payment_hash_a, STAKE, EXPIRY_SECS)
         .await
         .unwrap();
     let invoice_b = fiber_b
-        .create_hold_invoice(&payment_hash_b, 1000, 3600)
+        .create_hold_invoice(&payment_hash_b, STAKE, EXPIRY_SECS)
         .await
         .unwrap();
 
@@ -185,7 +174,7 @@ async fn test_guess_number_b_wins() {
     fiber_b.pay_hold_invoice(&invoice_a).await.unwrap();
 
     // Compute signature points
-    let sig_points = compute_signature_points(&oracle_pk, &commitment_point, &game_id);
+    let sig_points = oracle.signature_points(&game_id);
 
     // Create encrypted preimages
     let encrypted_preimage_a = EncryptedPreimage::encrypt(&preimage_a, &sig_points.b_wins);
@@ -220,24 +209,20 @@ async fn test_guess_number_b_wins() {
     fiber_a.cancel_invoice(&payment_hash_b).await.unwrap();
 
     // Final balances: B gained 1000
-    assert_eq!(fiber_a.balance(), 9000);
-    assert_eq!(fiber_b.balance(), 10000);
+    assert_eq!(fiber_a.balance(), WALLET - STAKE);
+    assert_eq!(fiber_b.balance(), WALLET);
 }
 
 /// Test that using wrong signature point fails to decrypt preimage
 #[tokio::test]
 async fn test_wrong_signature_point_fails_decryption() {
-    let secp = secp256k1::Secp256k1::new();
-    let oracle_sk = secp256k1::SecretKey::new(&mut rand::thread_rng());
-    let oracle_pk = secp256k1::PublicKey::from_secret_key(&secp, &oracle_sk);
-    let commitment_sk = secp256k1::SecretKey::new(&mut rand::thread_rng());
-    let commitment_point = secp256k1::PublicKey::from_secret_key(&secp, &commitment_sk);
+    let oracle = OracleKeys::random();
     let game_id = GameId::new();
 
     let preimage = Preimage::random();
     let payment_hash = preimage.payment_hash();
 
-    let sig_points = compute_signature_points(&oracle_pk, &commitment_point, &game_id);
+    let sig_points = oracle.signature_points(&game_id);
 
     // Encrypt with a_wins point
     let encrypted = EncryptedPreimage::encrypt(&preimage, &sig_points.a_wins);
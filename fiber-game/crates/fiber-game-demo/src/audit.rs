@@ -0,0 +1,182 @@
+//! `/api/audit/game/:game_id`: where every shannon of a game went.
+//!
+//! Puts side by side what the oracle recorded, what each hosted player in the
+//! game did (invoices created, payments made, settlement) and, for each
+//! stake, the state of the hold invoice locking it on the payee's Fiber node:
+//! the configured node for a player switched to RPC, the demo's mock network
+//! otherwise.
+
+use crate::health::NODE_TIMEOUT;
+use crate::{player_slug, AppState, DemoPlayer};
+use axum::{
+    extract::State,
+    Json,
+};
+use fiber_errors::{ApiError, ValidPath};
+use fiber_game_core::{
+    crypto::PaymentHash,
+    fiber::{FiberClient, PaymentStatus},
+    money,
+    protocol::{GameId, Player, TimelineEvent},
+};
+use fiber_game_player::state::{FiberBackend, GameSeat};
+use fiber_service::InstrumentedFiberClient;
+use serde::Serialize;
+use std::sync::Arc;
+
+#[derive(Serialize)]
+pub(crate) struct AuditResponse {
+    game_id: GameId,
+    /// Protocol steps the oracle recorded
+    oracle: Vec<TimelineEvent>,
+    /// Hosted players seated in the game
+    players: Vec<PlayerAudit>,
+    /// Each player's stake and where it is now
+    stakes: Vec<StakeAudit>,
+}
+
+#[derive(Serialize)]
+struct PlayerAudit {
+    id: String,
+    name: String,
+    role: Player,
+    /// Protocol steps the player recorded, its Fiber operations included
+    events: Vec<TimelineEvent>,
+    /// Shannons won, negative if lost, counting settled invoices only
+    net_shannons: i64,
+}
+
+#[derive(Serialize)]
+struct StakeAudit {
+    payer: Player,
+    payee: Player,
+    payment_hash: PaymentHash,
+    amount_shannons: u64,
+    /// The hold invoice on the payee's node; `None` if it couldn't be read
+    status: Option<PaymentStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+pub(crate) async fn audit(
+    State(state): State<Arc<AppState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+) -> Result<Json<AuditResponse>, ApiError> {
+    let oracle = state
+        .oracle
+        .timeline(&game_id)
+        .await
+        .ok_or_else(|| ApiError::not_found("Game not found"))?;
+
+    let mut seated: Vec<(usize, &DemoPlayer, GameSeat)> = Vec::new();
+    for (index, player) in state.players.iter().enumerate() {
+        if let Some(seat) = player.state.seat(&game_id).await {
+            seated.push((index, player, seat));
+        }
+    }
+
+    // Each player's stake is locked with their payment hash, in the invoice
+    // their opponent created
+    let mut stakes = Vec::with_capacity(seated.len());
+    for (_, _, seat) in &seated {
+        let payee = seat.role.opponent();
+        let node = match seated.iter().find(|(_, _, s)| s.role == payee) {
+            Some((_, player, _)) => node(&state, player).await,
+            None => Arc::new(state.network.clone()),
+        };
+        let status = tokio::time::timeout(NODE_TIMEOUT, node.get_payment_status(&seat.payment_hash))
+            .await
+            .map_err(|_| format!("no response within {}s", NODE_TIMEOUT.as_secs()))
+            .and_then(|r| r.map_err(|e| e.to_string()));
+        stakes.push(StakeAudit {
+            payer: seat.role,
+            payee,
+            payment_hash: seat.payment_hash,
+            amount_shannons: seat.amount_shannons,
+            status: status.as_ref().ok().copied(),
+            error: status.err(),
+        });
+    }
+
+    let mut players = Vec::with_capacity(seated.len());
+    for (index, player, seat) in &seated {
+        let net_shannons = stakes
+            .iter()
+            .filter(|s| s.status == Some(PaymentStatus::Settled))
+            .try_fold(0, |net, s| match (s.payer == seat.role, s.payee == seat.role) {
+                (true, _) => money::debit(net, s.amount_shannons),
+                (_, true) => money::credit(net, s.amount_shannons),
+                _ => Ok(net),
+            })?;
+        players.push(PlayerAudit {
+            id: player_slug(*index),
+            name: player.state.player_name().to_string(),
+            role: seat.role,
+            events: player.state.timeline(&game_id).await.unwrap_or_default(),
+            net_shannons,
+        });
+    }
+
+    Ok(Json(AuditResponse {
+        game_id,
+        oracle,
+        players,
+        stakes,
+    }))
+}
+
+/// Where `player`'s invoices are: its configured node when switched to RPC,
+/// the demo's mock network otherwise
+async fn node(state: &AppState, player: &DemoPlayer) -> Arc<dyn FiberClient> {
+    let node: Arc<dyn FiberClient> = match (player.state.fiber_backend().await, &player.rpc) {
+        (FiberBackend::Rpc, Some(rpc)) => rpc.clone(),
+        _ => Arc::new(state.network.clone()),
+    };
+    InstrumentedFiberClient::wrap(node, player.state.metrics().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::{LocalDemo, Script, Simulation};
+    use fiber_errors::ErrorCode;
+    use fiber_game_core::protocol::ProtocolStep;
+
+    #[tokio::test]
+    async fn test_audit_follows_stakes_to_the_winner() {
+        let demo = LocalDemo::spawn().await.unwrap();
+        let script: Script = serde_yaml::from_str(
+            "games: [{name: rps, game_type: RockPaperScissors, stake: 1000, a: Rock, b: Scissors, expect: AWins}]",
+        )
+        .unwrap();
+        let (game_id, _) = Simulation::new(&demo, script.initial_balance)
+            .play(&script.games[0])
+            .await
+            .unwrap();
+
+        let Json(audit) = audit(State(demo.state.clone()), ValidPath(game_id)).await.unwrap();
+        assert!(audit.oracle.iter().any(|e| e.step == ProtocolStep::Judged));
+
+        // B's stake went to A, A's went back to A
+        let status = |payer| audit.stakes.iter().find(|s| s.payer == payer).unwrap().status;
+        assert_eq!(status(Player::B), Some(PaymentStatus::Settled));
+        assert_eq!(status(Player::A), Some(PaymentStatus::Cancelled));
+
+        let net: Vec<_> = audit.players.iter().map(|p| (p.role, p.net_shannons)).collect();
+        assert_eq!(net, [(Player::A, 1000), (Player::B, -1000)]);
+        for player in &audit.players {
+            assert!(player.events.iter().any(|e| e.step == ProtocolStep::InvoiceCreated));
+            assert!(player.events.iter().any(|e| e.step == ProtocolStep::Settled));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_audit_unknown_game() {
+        let demo = LocalDemo::spawn().await.unwrap();
+        let err = audit(State(demo.state.clone()), ValidPath(GameId::new()))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.code, ErrorCode::NotFound);
+    }
+}
@@ -0,0 +1,158 @@
+//! `/api/health`: pre-flight diagnostics for presenters.
+//!
+//! Reports the oracle's key fingerprint and, for every hosted player, which
+//! Fiber backend it uses, whether that node answers and its balance. Responds
+//! `503` when any node is unreachable, so `curl -f` works as a go/no-go check.
+
+use crate::{player_slug, AppState};
+use axum::{extract::State, http::StatusCode, Json};
+use fiber_game_player::state::FiberBackend;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long a player's node gets to answer before it counts as unreachable
+pub(crate) const NODE_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Serialize)]
+pub(crate) struct HealthResponse {
+    /// "ok", or "degraded" when a Fiber node is unreachable
+    status: &'static str,
+    oracle: OracleHealth,
+    players: Vec<PlayerHealth>,
+}
+
+#[derive(Serialize)]
+struct OracleHealth {
+    public_key: String,
+    key_fingerprint: String,
+}
+
+#[derive(Serialize)]
+struct PlayerHealth {
+    id: String,
+    name: String,
+    fiber_backend: FiberBackend,
+    fiber_rpc_url: Option<String>,
+    reachable: bool,
+    balance_shannons: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+pub(crate) async fn health(
+    State(state): State<Arc<AppState>>,
+) -> (StatusCode, Json<HealthResponse>) {
+    // Query all nodes concurrently so one slow node doesn't stack timeouts
+    let mut checks = Vec::with_capacity(state.players.len());
+    for player in &state.players {
+        let fiber = player.fiber().await;
+        checks.push(tokio::spawn(async move {
+            match tokio::time::timeout(NODE_TIMEOUT, fiber.get_balance()).await {
+                Ok(Ok(balance)) => Ok(balance),
+                Ok(Err(e)) => Err(e.to_string()),
+                Err(_) => Err(format!("no response within {}s", NODE_TIMEOUT.as_secs())),
+            }
+        }));
+    }
+
+    let mut players = Vec::with_capacity(checks.len());
+    for (index, (player, check)) in state.players.iter().zip(checks).enumerate() {
+        let balance = check.await.unwrap_or_else(|e| Err(e.to_string()));
+        players.push(PlayerHealth {
+            id: player_slug(index),
+            name: player.state.player_name().to_string(),
+            fiber_backend: player.state.fiber_backend().await,
+            fiber_rpc_url: player.state.fiber_rpc_url().await,
+            reachable: balance.is_ok(),
+            balance_shannons: balance.as_ref().ok().copied(),
+            error: balance.err(),
+        });
+    }
+
+    let healthy = players.iter().all(|p| p.reachable);
+    let response = HealthResponse {
+        status: if healthy { "ok" } else { "degraded" },
+        oracle: OracleHealth {
+            public_key: hex::encode(state.oracle.public_key().serialize()),
+            key_fingerprint: state.oracle.key_fingerprint(),
+        },
+        players,
+    };
+    let code = if healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (code, Json(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{player_name, DemoPlayer, MOCK_BALANCE_SHANNONS};
+    use fiber_game_core::fiber::MockFiberClient;
+    use fiber_game_oracle::OracleState;
+    use fiber_game_player::PlayerState;
+    use uuid::Uuid;
+
+    fn app_state(rpc_urls: &[Option<&str>]) -> Arc<AppState> {
+        let players = rpc_urls
+            .iter()
+            .enumerate()
+            .map(|(i, url)| {
+                DemoPlayer::new(PlayerState::new(
+                    Uuid::new_v4(),
+                    player_name(i),
+                    "http://localhost/api/oracle".to_string(),
+                    url.map(str::to_string),
+                ))
+            })
+            .collect();
+        Arc::new(AppState {
+            oracle: Arc::new(OracleState::new()),
+            players,
+            network: MockFiberClient::new(0),
+            oracle_latency: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_health_mock_players() {
+        let state = app_state(&[None, None]);
+        let (code, Json(health)) = health(State(state.clone())).await;
+
+        assert_eq!(code, StatusCode::OK);
+        assert_eq!(health.status, "ok");
+        assert_eq!(health.oracle.key_fingerprint, state.oracle.key_fingerprint());
+        assert_eq!(health.players.len(), 2);
+        assert_eq!(health.players[1].id, "player-b");
+        assert_eq!(health.players[0].fiber_backend, FiberBackend::Mock);
+        assert_eq!(health.players[0].balance_shannons, Some(MOCK_BALANCE_SHANNONS));
+    }
+
+    #[tokio::test]
+    async fn test_health_unreachable_node() {
+        // Nothing listens on port 1, so the RPC call fails fast
+        let state = app_state(&[None, Some("http://127.0.0.1:1")]);
+        let (code, Json(health)) = health(State(state)).await;
+
+        assert_eq!(code, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(health.status, "degraded");
+        assert!(health.players[0].reachable);
+        assert_eq!(health.players[1].fiber_backend, FiberBackend::Rpc);
+        assert!(!health.players[1].reachable);
+        assert!(health.players[1].error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_health_follows_backend_switch() {
+        let state = app_state(&[None, Some("http://127.0.0.1:1")]);
+        state.players[1].state.request_backend(FiberBackend::Mock).await.unwrap();
+        let (code, Json(health)) = health(State(state)).await;
+
+        assert_eq!(code, StatusCode::OK);
+        assert_eq!(health.players[1].fiber_backend, FiberBackend::Mock);
+        assert_eq!(health.players[1].fiber_rpc_url, None);
+    }
+}
@@ -0,0 +1,461 @@
+//! Fiber Game Demo Service
+//!
+//! Combined service with Oracle and N Players (`PLAYERS`, default 2) on a single port.
+//! All Fiber RPC calls are made by the frontend directly — the backend
+//! only handles game state management and Oracle communication.
+//!
+//! Routes:
+//! - `/` - Unified Web UI with player role switcher
+//! - `/api/oracle/...` - Oracle API
+//! - `/api/players` - Hosted players (role switcher data)
+//! - `/api/health` - Oracle key and per-player Fiber node diagnostics
+//! - `/api/demo/trace/:game_id` - Protocol timeline of a game (sequence diagram data)
+//! - `/api/audit/game/:game_id` - Oracle log, player steps and where each stake went
+//! - `/metrics` - Prometheus metrics of the oracle and all players
+//! - `/api/player-a/...`, `/api/player-b/...`, ... - Player APIs (call Oracle via HTTP)
+//!
+//! With `DEMO_DB_PATH` set, the oracle and all players persist their state to
+//! that SQLite file and restore it on boot, so a demo survives a restart.
+//!
+//! With `--script games.yaml` the demo instead plays the scripted games
+//! against the mock network, prints a pass/fail report and exits (see
+//! [`script`]).
+//!
+//! With `--seed seeds/demo.yaml` players A and B play that file's games at
+//! startup, if the demo has none yet (see [`script::seed_games`]).
+//!
+//! With `--dev-simulate true` the oracle answers slowly and the last hosted
+//! player is played by the backend, for working on the UI alone (see
+//! [`simulation`]).
+//!
+//! Exposed as a library so the unified `fiber-demo` binary can run it too.
+
+use axum::{extract::State, routing::get, Json, Router};
+use clap::ArgAction;
+use fiber_config::ServiceConfig;
+use fiber_flags::{FeatureFlags, FlagArgs};
+use fiber_game_core::crypto::Keyring;
+use fiber_game_core::fiber::{FiberClient, MockFiberClient, RpcFiberClient};
+use fiber_service::{InstrumentedFiberClient, ServerArgs};
+use fiber_game_oracle::{storage::SqliteOracleStore, OracleState};
+use fiber_game_player::{state::FiberBackend, storage::SqlitePlayerStore, PlayerState};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tower_http::cors::CorsLayer;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+mod audit;
+mod health;
+pub mod script;
+pub mod simulation;
+mod trace;
+
+// ============================================================================
+// Combined Application State
+// ============================================================================
+
+/// The oracle and hosted players behind one [`create_app`]
+pub struct AppState {
+    oracle: Arc<OracleState>,
+    /// Hosted players, routed at `/api/player-a`, `/api/player-b`, ...
+    players: Vec<DemoPlayer>,
+    /// Mock Fiber network that players without a node pay over, e.g. in
+    /// scripted runs; it holds every side's invoices, keyed by payment hash
+    network: MockFiberClient,
+    /// Longest random delay added to each request to the oracle, if any
+    oracle_latency: Option<Duration>,
+}
+
+impl AppState {
+    /// Host `players` in order as player A, B, ...; each should be pointed at
+    /// this app's `/api/oracle`.
+    ///
+    /// The players count into the oracle's metrics, so `/metrics` covers the
+    /// whole demo.
+    pub fn new(oracle: OracleState, players: Vec<PlayerState>) -> Self {
+        let metrics = oracle.metrics().clone();
+        Self {
+            oracle: Arc::new(oracle),
+            players: players
+                .into_iter()
+                .map(|p| DemoPlayer::new(p.with_metrics(metrics.clone())))
+                .collect(),
+            network: MockFiberClient::new(u64::MAX / 2),
+            oracle_latency: None,
+        }
+    }
+
+    /// Hold back each request to the oracle a random time of up to `max`
+    pub fn with_oracle_latency(mut self, max: Duration) -> Self {
+        self.oracle_latency = Some(max);
+        self
+    }
+
+    pub fn oracle(&self) -> &Arc<OracleState> {
+        &self.oracle
+    }
+}
+
+/// A hosted player and the Fiber backends it can switch between
+///
+/// Payments are made by the frontend; the backend only uses these clients for
+/// `/api/health`.
+struct DemoPlayer {
+    state: Arc<PlayerState>,
+    mock: Arc<MockFiberClient>,
+    /// Client for the configured node, if the player has one
+    rpc: Option<Arc<RpcFiberClient>>,
+}
+
+/// Balance reported by a mock-mode player's Fiber backend (1000 CKB)
+const MOCK_BALANCE_SHANNONS: u64 = 100_000_000_000;
+
+impl DemoPlayer {
+    fn new(state: PlayerState) -> Self {
+        let rpc = state.configured_rpc_url().map(|url| {
+            Arc::new(RpcFiberClient::new(url).with_headers(fiber_service::trace_headers))
+        });
+        Self {
+            state: Arc::new(state),
+            mock: Arc::new(MockFiberClient::new(MOCK_BALANCE_SHANNONS)),
+            rpc,
+        }
+    }
+
+    /// Client for the backend the player is currently switched to, timed
+    /// into the demo's metrics
+    async fn fiber(&self) -> Arc<dyn FiberClient> {
+        let client: Arc<dyn FiberClient> = match (self.state.fiber_backend().await, &self.rpc) {
+            (FiberBackend::Rpc, Some(rpc)) => rpc.clone(),
+            _ => self.mock.clone(),
+        };
+        InstrumentedFiberClient::wrap(client, self.state.metrics().clone())
+    }
+}
+
+/// Maximum number of hosted players (one per letter)
+const MAX_PLAYERS: usize = 26;
+
+/// Route slug for the player at `index` ("player-a", "player-b", ...)
+fn player_slug(index: usize) -> String {
+    format!("player-{}", (b'a' + index as u8) as char)
+}
+
+/// Display name for the player at `index` ("Player A", "Player B", ...)
+fn player_name(index: usize) -> String {
+    format!("Player {}", (b'A' + index as u8) as char)
+}
+
+#[derive(Serialize)]
+struct HostedPlayer {
+    id: String,
+    name: String,
+    player_id: Uuid,
+    api_base: String,
+    fiber_rpc_url: Option<String>,
+}
+
+/// List hosted players for the UI role switcher
+async fn list_players(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
+    let mut players = Vec::with_capacity(state.players.len());
+    for (i, p) in state.players.iter().enumerate() {
+        players.push(HostedPlayer {
+            id: player_slug(i),
+            name: p.state.player_name().to_string(),
+            player_id: p.state.player_id(),
+            api_base: format!("/api/{}", player_slug(i)),
+            fiber_rpc_url: p.state.fiber_rpc_url().await,
+        });
+    }
+    Json(serde_json::json!({ "players": players }))
+}
+
+// ============================================================================
+// Router Creation
+// ============================================================================
+
+/// Build the combined demo app: oracle and player APIs plus the Web UI.
+pub fn create_app(state: Arc<AppState>) -> Router {
+    let mut oracle_api = fiber_game_oracle::api_router(state.oracle.clone());
+    if let Some(max) = state.oracle_latency {
+        oracle_api = simulation::delayed(oracle_api, max);
+    }
+    let mut app = Router::new()
+        .route("/api/players", get(list_players))
+        .route("/api/health", get(health::health))
+        .route("/api/demo/trace/:game_id", get(trace::trace))
+        .route("/api/audit/game/:game_id", get(audit::audit))
+        .with_state(state.clone())
+        .nest("/api/oracle", oracle_api);
+    for (index, player) in state.players.iter().enumerate() {
+        app = app.nest(
+            &format!("/api/{}", player_slug(index)),
+            fiber_game_player::api_router(player.state.clone()),
+        );
+    }
+    let app = fiber_service::with_metrics(app, state.oracle.metrics().clone());
+    // Serve unified UI at root
+    let app = app.fallback_service(static_ui());
+    fiber_service::request_tracing(app).layer(CorsLayer::permissive())
+}
+
+/// Web UI assets, compiled in with the `embed-ui` feature
+#[cfg(feature = "embed-ui")]
+#[derive(rust_embed::RustEmbed)]
+#[folder = "static"]
+struct StaticAssets;
+
+#[cfg(feature = "embed-ui")]
+fn static_ui() -> Router {
+    fiber_service::embedded_ui::<StaticAssets>()
+}
+
+#[cfg(not(feature = "embed-ui"))]
+fn static_ui() -> Router {
+    fiber_service::static_dir("static")
+}
+
+// ============================================================================
+// Entry Point
+// ============================================================================
+
+/// Combined demo configuration, the `demo` section of a config file
+#[derive(clap::Args, Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    #[command(flatten)]
+    #[serde(flatten)]
+    pub server: ServerArgs,
+    /// Number of hosted players (2-26)
+    #[arg(long, env = "PLAYERS", default_value_t = 2)]
+    pub players: usize,
+    /// SQLite file shared by the oracle and all players (in-memory if unset)
+    #[arg(long, env = "DEMO_DB_PATH")]
+    pub db_path: Option<PathBuf>,
+    /// Bring the database schema up to date at startup; when off, the demo
+    /// refuses to start on a database with migrations not applied
+    #[arg(long, env = "DEMO_AUTO_MIGRATE", default_value_t = true, action = ArgAction::Set)]
+    pub auto_migrate: bool,
+    /// Hex storage keys to encrypt the oracle's and players' secrets in the database with, current
+    /// first; older keys after it are only read, and rewritten under the
+    /// current one at startup (stored in plaintext if neither this nor
+    /// `storage_key_file` is set)
+    #[arg(long, env = "DEMO_STORAGE_KEY")]
+    pub storage_key: Option<String>,
+    /// File holding the storage keys, one per line, current first
+    #[arg(long, env = "DEMO_STORAGE_KEY_FILE")]
+    pub storage_key_file: Option<PathBuf>,
+    /// Play the games in this YAML script against the mock network and exit
+    #[arg(long)]
+    pub script: Option<PathBuf>,
+    /// YAML seed file whose `games` players A and B play on the mock network
+    /// when the demo starts with none, such as `seeds/demo.yaml`
+    #[arg(long, env = "DEMO_SEED")]
+    pub seed: Option<PathBuf>,
+    /// Token for the oracle's operator API at `/api/oracle/admin` and the
+    /// players' feature toggles at `/api/player-a/admin/features`, ... (off
+    /// if unset)
+    #[arg(long, env = "ORACLE_ADMIN_TOKEN")]
+    pub oracle_admin_token: Option<String>,
+    /// Take commitments only once both players confirmed their opponent's
+    /// payment is held on their node
+    #[arg(long, env = "ORACLE_REQUIRE_FUNDING", default_value_t = false, action = ArgAction::Set)]
+    pub require_funding: bool,
+    /// For working on the UI alone: slow down the oracle and let the backend
+    /// play the last hosted player, at random
+    #[arg(long, env = "DEMO_DEV_SIMULATE", default_value_t = false, action = ArgAction::Set)]
+    pub dev_simulate: bool,
+    /// Longest delay `dev_simulate` adds to a request to the oracle
+    #[arg(long, env = "DEMO_DEV_LATENCY_MS", default_value_t = simulation::DEFAULT_LATENCY_MS)]
+    pub dev_latency_ms: u64,
+    /// Percent of games the simulated player stops answering in before its
+    /// move
+    #[arg(
+        long,
+        env = "DEMO_DEV_STALL_PERCENT",
+        default_value_t = simulation::DEFAULT_STALL_PERCENT
+    )]
+    pub dev_stall_percent: u8,
+    /// Player features (`p2p_transport`), shared by every hosted player
+    #[command(flatten)]
+    #[serde(flatten)]
+    pub features: FlagArgs,
+}
+
+impl Config {
+    /// The storage keys configured, if any
+    fn keyring(&self) -> Result<Option<Keyring>, String> {
+        Keyring::load(self.storage_key.as_deref(), self.storage_key_file.as_deref())
+            .map_err(|e| format!("storage_key: {}", e))
+    }
+}
+
+impl ServiceConfig for Config {
+    const SECTION: &'static str = "demo";
+
+    fn validate(&self) -> Result<(), String> {
+        if !(2..=MAX_PLAYERS).contains(&self.players) {
+            return Err(format!("players must be between 2 and {}", MAX_PLAYERS));
+        }
+        if self.dev_stall_percent > 100 {
+            return Err("dev_stall_percent must be at most 100".to_string());
+        }
+        self.keyring()?;
+        self.features.check(fiber_game_player::state::FEATURES)
+    }
+}
+
+/// Run the combined demo until the process exits.
+///
+/// Per-player Fiber RPC URLs are read from `FIBER_PLAYER_<LETTER>_RPC_URL`,
+/// since the number of players is only known at runtime.
+pub async fn run(config: Config) -> std::io::Result<()> {
+    if let Some(path) = &config.script {
+        return run_script_file(path).await;
+    }
+
+    let port = config.server.port_or(3000);
+    let oracle_url = format!("http://localhost:{}/api/oracle", port);
+
+    config
+        .validate()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let player_count = config.players;
+
+    // Oracle and players share one SQLite file (in separate tables)
+    let (oracle, player_store) = match &config.db_path {
+        Some(path) => {
+            info!("Persisting demo state to {}", path.display());
+            let (oracle_store, player_store) = if config.auto_migrate {
+                (SqliteOracleStore::open(path), SqlitePlayerStore::open(path))
+            } else {
+                (
+                    SqliteOracleStore::open_without_migrating(path),
+                    SqlitePlayerStore::open_without_migrating(path),
+                )
+            };
+            let mut oracle_store = oracle_store.expect("failed to open demo database");
+            let mut player_store = player_store.expect("failed to open demo database");
+            if let Some(keyring) = config
+                .keyring()
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?
+            {
+                info!("Encrypting stored secrets with storage key {}", keyring.current_id());
+                oracle_store = oracle_store
+                    .with_keyring(keyring.clone())
+                    .expect("failed to encrypt demo database");
+                player_store = player_store
+                    .with_keyring(keyring)
+                    .expect("failed to encrypt demo database");
+            }
+            let oracle = OracleState::open(Arc::new(oracle_store))
+                .expect("failed to restore oracle state");
+            (oracle, Some(Arc::new(player_store)))
+        }
+        None => (OracleState::new(), None),
+    };
+    let oracle = oracle
+        .with_admin_token(config.oracle_admin_token.clone())
+        .with_require_funding(config.require_funding);
+    // One set of switches, so a toggle through any player applies to all
+    let features = FeatureFlags::configured(fiber_game_player::state::FEATURES, &config.features)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+
+    // Fiber RPC URLs are passed to frontend for direct browser-to-node calls
+    let mut players = Vec::with_capacity(player_count);
+    for index in 0..player_count {
+        let mut name = player_name(index);
+        if config.dev_simulate && index == player_count - 1 {
+            name = format!("{} (simulated)", name);
+        }
+        let env_var = format!(
+            "FIBER_{}_RPC_URL",
+            player_slug(index).to_uppercase().replace('-', "_")
+        );
+        let fiber_rpc_url = std::env::var(&env_var).ok();
+        if let Some(ref url) = fiber_rpc_url {
+            info!("{} Fiber RPC URL: {} (frontend will call directly)", name, url);
+        } else {
+            info!("{}: No {} set (mock mode — no real Fiber payments)", name, env_var);
+        }
+
+        let player = match &player_store {
+            Some(store) => PlayerState::open(
+                store.clone(),
+                &player_slug(index),
+                name,
+                oracle_url.clone(),
+                fiber_rpc_url,
+            )
+            .expect("failed to restore player state"),
+            None => PlayerState::new(Uuid::new_v4(), name, oracle_url.clone(), fiber_rpc_url),
+        };
+        // Players share the process, but still exchange invoices over a socket
+        let player = player
+            .with_p2p_url(Some(format!(
+                "ws://localhost:{}/api/{}/p2p",
+                port,
+                player_slug(index)
+            )))
+            .with_features(features.clone())
+            .with_admin_token(config.oracle_admin_token.clone());
+        info!("{} ID: {}", player.player_name(), player.player_id());
+        players.push(player);
+    }
+
+    let mut state = AppState::new(oracle, players);
+    if config.dev_simulate {
+        info!(
+            "Dev simulation: oracle requests held up to {} ms, {} played by the backend",
+            config.dev_latency_ms,
+            player_name(player_count - 1)
+        );
+        state = state.with_oracle_latency(Duration::from_millis(config.dev_latency_ms));
+        let api = format!("http://localhost:{}/api/{}", port, player_slug(player_count - 1));
+        simulation::Opponent::new(api, config.dev_stall_percent).spawn();
+    }
+    let state = Arc::new(state);
+    for player in &state.players {
+        fiber_game_player::reminders::spawn(&player.state, Vec::new());
+    }
+    if let Some(path) = &config.seed {
+        let seed = script::Script::load(path)?;
+        if config.dev_simulate && player_count == 2 {
+            warn!("Player B is simulated, seed games not played");
+        } else {
+            let base_url = format!("http://localhost:{}", port);
+            tokio::spawn(script::seed_games(state.clone(), base_url, seed));
+        }
+    }
+
+    info!("Oracle public key: {}", hex::encode(state.oracle.public_key().serialize()));
+    info!("Oracle key fingerprint: {}", state.oracle.key_fingerprint());
+
+    let app = create_app(state);
+
+    info!("Fiber Game Demo listening on http://0.0.0.0:{}", port);
+    info!("  UI: http://localhost:{}/", port);
+    info!("  All Fiber RPC calls are made by the frontend directly");
+
+    fiber_service::serve(app, port).await
+}
+
+/// Run a simulation script and print its report; fails if any game failed.
+async fn run_script_file(path: &std::path::Path) -> std::io::Result<()> {
+    let script = script::Script::load(path)?;
+    info!("Running {} scripted games from {}", script.games.len(), path.display());
+
+    let report = script::run_script(&script).await?;
+    println!("{}", report);
+    if report.passed() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!(
+            "{} of {} scripted games failed",
+            report.failures(),
+            report.games.len()
+        )))
+    }
+}
@@ -0,0 +1,775 @@
+//! Scripted simulation mode (`--script games.yaml`).
+//!
+//! Boots the combined demo in-process on an ephemeral port and plays a list of
+//! predefined games through the real HTTP APIs, doing the frontend's Fiber
+//! work (hold invoices, payments, settlement) against a [`MockFiberClient`]
+//! standing in for the network. Each game passes if the oracle's result
+//! matches `expect` and both players' balances end up where that result says
+//! they should.
+//!
+//! ```yaml
+//! initial_balance: 100000   # per player, optional
+//! games:
+//!   - name: rock beats scissors
+//!     game_type: RockPaperScissors
+//!     stake: 1000
+//!     a: Rock
+//!     b: Scissors
+//!     expect: AWins
+//!   - name: closest guess wins
+//!     game_type: GuessNumber
+//!     stake: 500
+//!     oracle_secret: 42     # fixed so the result is deterministic
+//!     a: 40
+//!     b: 50
+//!     expect: AWins
+//! ```
+//!
+//! A seed file (`--seed`, see `seeds/demo.yaml`) lists games the same way:
+//! the running demo plays them as players A and B when it starts with no
+//! games, so it opens with some history ([`seed_games`]).
+
+use crate::{create_app, player_name, player_slug, AppState};
+use fiber_game_api::player::{
+    CreateGameRequest, CreateGameResponse, GameStatusResponse, InvoiceCreatedRequest,
+    InvoiceCreatedResponse, JoinGameRequest, JoinGameResponse, OpponentInvoiceResponse,
+    PaymentDoneRequest, PaymentDoneResponse, PlayRequest, PlayResponse, SettleResponse,
+};
+use fiber_game_core::{
+    crypto::{PaymentHash, Preimage},
+    fiber::{FiberClient, HoldInvoice, InvoiceMemo, MockFiberClient},
+    games::{GameAction, GameType, PenniesAction, RpsAction},
+    money,
+    protocol::{DrawPolicy, GameId, GameResult, Player},
+};
+use fiber_game_oracle::OracleState;
+use fiber_game_player::PlayerState;
+use fiber_service::LocalServer;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Hold invoice expiry used for scripted games
+const INVOICE_EXPIRY_SECS: u64 = 3600;
+
+/// A simulation script
+#[derive(Debug, Deserialize)]
+pub struct Script {
+    /// Balance each player starts every game with, in shannons
+    #[serde(default = "default_initial_balance")]
+    pub initial_balance: u64,
+    #[serde(default)]
+    pub games: Vec<ScriptedGame>,
+}
+
+fn default_initial_balance() -> u64 {
+    100_000
+}
+
+/// One predefined game
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScriptedGame {
+    pub name: String,
+    pub game_type: GameType,
+    /// Stake per player, in shannons
+    pub stake: u64,
+    /// Oracle's secret number (Guess Number only)
+    #[serde(default)]
+    pub oracle_secret: Option<u8>,
+    /// Player A's move
+    pub a: ScriptAction,
+    /// Player B's move
+    pub b: ScriptAction,
+    pub expect: GameResult,
+}
+
+/// A move as written in a script: an RPS action, a penny side, or a number
+/// (a guess, or the stones taken in Nim)
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(untagged)]
+pub enum ScriptAction {
+    Rps(RpsAction),
+    Pennies(PenniesAction),
+    Number(u8),
+}
+
+impl ScriptAction {
+    fn to_action(self, game_type: GameType) -> Result<GameAction, String> {
+        let action = match self {
+            ScriptAction::Rps(a) => GameAction::Rps(a),
+            ScriptAction::Pennies(a) => GameAction::Pennies(a),
+            ScriptAction::Number(n) if game_type == GameType::Nim => GameAction::Nim(n),
+            ScriptAction::Number(n) => GameAction::GuessNumber(n),
+        };
+        if !action.validate(game_type) {
+            return Err(format!("{:?} is not a valid move for {:?}", self, game_type));
+        }
+        Ok(action)
+    }
+}
+
+impl Script {
+    /// Load a script from a YAML file.
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        serde_yaml::from_str(&text)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// Outcome of one scripted game
+#[derive(Debug)]
+pub struct GameOutcome {
+    pub name: String,
+    pub expected: GameResult,
+    /// Actual result, or why the game could not be completed
+    pub actual: Result<GameResult, String>,
+}
+
+impl GameOutcome {
+    pub fn passed(&self) -> bool {
+        matches!(self.actual, Ok(result) if result == self.expected)
+    }
+}
+
+/// Outcome of a whole script
+#[derive(Debug, Default)]
+pub struct ScriptReport {
+    pub games: Vec<GameOutcome>,
+}
+
+impl ScriptReport {
+    pub fn passed(&self) -> bool {
+        self.games.iter().all(GameOutcome::passed)
+    }
+
+    pub fn failures(&self) -> usize {
+        self.games.iter().filter(|g| !g.passed()).count()
+    }
+}
+
+impl fmt::Display for ScriptReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for game in &self.games {
+            match &game.actual {
+                Ok(result) if game.passed() => writeln!(f, "PASS  {} ({:?})", game.name, result)?,
+                Ok(result) => writeln!(
+                    f,
+                    "FAIL  {}: expected {:?}, got {:?}",
+                    game.name, game.expected, result
+                )?,
+                Err(e) => writeln!(f, "FAIL  {}: {}", game.name, e)?,
+            }
+        }
+        write!(
+            f,
+            "{} passed, {} failed",
+            self.games.len() - self.failures(),
+            self.failures()
+        )
+    }
+}
+
+/// Play every game in `script` against a fresh in-process demo.
+pub async fn run_script(script: &Script) -> std::io::Result<ScriptReport> {
+    let demo = LocalDemo::spawn().await?;
+    let sim = Simulation::new(&demo, script.initial_balance);
+
+    let mut report = ScriptReport::default();
+    for game in &script.games {
+        report.games.push(GameOutcome {
+            name: game.name.clone(),
+            expected: game.expect,
+            actual: sim.play(game).await.map(|(_, result)| result),
+        });
+    }
+    Ok(report)
+}
+
+/// Play the seed's games on the demo served at `base_url` once it answers,
+/// if it has no games yet and players A and B pay over the mock network;
+/// returns how many were played.
+pub(crate) async fn seed_games(state: Arc<AppState>, base_url: String, seed: Script) -> usize {
+    if seed.games.is_empty() {
+        return 0;
+    }
+    if state.oracle.game_count().await > 0 {
+        info!("Demo has games already, seed games not played");
+        return 0;
+    }
+    if state.players.iter().take(2).any(|p| p.rpc.is_some()) {
+        warn!("Seed games need players A and B on the mock network, not played");
+        return 0;
+    }
+
+    let sim = Simulation::on(base_url, &state, seed.initial_balance);
+    let mut up = false;
+    for _ in 0..50 {
+        if sim.get::<serde_json::Value>("/api/players").await.is_ok() {
+            up = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    if !up {
+        warn!("Demo did not answer, seed games not played");
+        return 0;
+    }
+
+    let mut played = 0;
+    for game in &seed.games {
+        match sim.play(game).await {
+            Ok((game_id, result)) if result == game.expect => {
+                info!(%game_id, "Seed game {} played ({:?})", game.name, result);
+                played += 1;
+            }
+            Ok((game_id, result)) => warn!(
+                %game_id,
+                "Seed game {} ended {:?}, expected {:?}", game.name, result, game.expect
+            ),
+            Err(e) => warn!("Seed game {} failed: {}", game.name, e),
+        }
+    }
+    played
+}
+
+/// Two-player in-memory demo served on an ephemeral local port; stopped on drop
+pub(crate) struct LocalDemo {
+    pub(crate) base_url: String,
+    pub(crate) state: Arc<AppState>,
+    _server: LocalServer,
+}
+
+impl LocalDemo {
+    pub(crate) async fn spawn() -> std::io::Result<Self> {
+        let mut state = None;
+        let server = LocalServer::spawn_with(|base_url| {
+            let oracle_url = format!("{}/api/oracle", base_url);
+            let ws_url = base_url.replacen("http", "ws", 1);
+            let players = (0..2)
+                .map(|i| {
+                    PlayerState::new(Uuid::new_v4(), player_name(i), oracle_url.clone(), None)
+                        .with_p2p_url(Some(format!("{}/api/{}/p2p", ws_url, player_slug(i))))
+                })
+                .collect();
+            let app_state = Arc::new(AppState::new(OracleState::new(), players));
+            state = Some(app_state.clone());
+            create_app(app_state)
+        })
+        .await?;
+
+        Ok(Self {
+            base_url: server.url(),
+            state: state.expect("app was built"),
+            _server: server,
+        })
+    }
+}
+
+/// The "frontend" driving a [`LocalDemo`]
+pub(crate) struct Simulation {
+    base_url: String,
+    http: reqwest::Client,
+    oracle: Arc<OracleState>,
+    /// The demo's mock network, so `/api/audit` sees the payments
+    network: MockFiberClient,
+    initial_balance: u64,
+}
+
+/// Per-player view of a game while it is being played
+struct Seat {
+    role: Player,
+    api: String,
+    balance: u64,
+    my_payment_hash: PaymentHash,
+    opponent_payment_hash: PaymentHash,
+}
+
+impl Simulation {
+    pub(crate) fn new(demo: &LocalDemo, initial_balance: u64) -> Self {
+        Self::on(demo.base_url.clone(), &demo.state, initial_balance)
+    }
+
+    /// Drive the demo of `state`, served at `base_url`
+    fn on(base_url: String, state: &AppState, initial_balance: u64) -> Self {
+        Self {
+            base_url,
+            http: reqwest::Client::new(),
+            oracle: state.oracle.clone(),
+            network: state.network.clone(),
+            initial_balance,
+        }
+    }
+
+    /// Play one game end to end; returns its ID and the oracle's result.
+    pub(crate) async fn play(&self, game: &ScriptedGame) -> Result<(GameId, GameResult), String> {
+        let actions = [
+            game.a.to_action(game.game_type)?,
+            game.b.to_action(game.game_type)?,
+        ];
+        let apis = [0, 1].map(|i| format!("/api/{}", player_slug(i)));
+
+        // A creates, the oracle secret is pinned, B joins
+        let created: CreateGameResponse = self
+            .post(
+                &format!("{}/game/create", apis[0]),
+                &CreateGameRequest {
+                    game_type: game.game_type,
+                    amount_shannons: game.stake,
+                    private: false,
+                    draw_policy: DrawPolicy::Refund,
+                },
+            )
+            .await?;
+        let game_id = created.game_id;
+        if let Some(secret) = game.oracle_secret {
+            self.oracle
+                .set_oracle_secret(&game_id, secret)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        let _: JoinGameResponse = self
+            .post(&format!("{}/game/join", apis[1]), &JoinGameRequest { game_id })
+            .await?;
+
+        // Each player learns the opponent's payment hash
+        let mut seats = Vec::with_capacity(2);
+        for (api, role) in apis.iter().zip([Player::A, Player::B]) {
+            let status: GameStatusResponse =
+                self.get(&format!("{}/game/{}/status", api, game_id)).await?;
+            seats.push(Seat {
+                role,
+                api: api.clone(),
+                balance: self.initial_balance,
+                my_payment_hash: parse_hash(status.my_payment_hash.as_deref())?,
+                opponent_payment_hash: parse_hash(status.opponent_payment_hash.as_deref())
+                    .map_err(|_| format!("{:?} never received the opponent's payment hash", role))?,
+            });
+        }
+
+        // Invoices are keyed by payment hash, so one mock can hold both sides'
+        // invoices and check every settlement preimage
+        let network = &self.network;
+
+        // Hold invoices: each player invoices the opponent's payment hash,
+        // tagged with the game so the node's records lead back to it
+        let memo = InvoiceMemo::Game(*game_id.as_uuid()).description();
+        for seat in &seats {
+            let invoice = network
+                .create_described_hold_invoice(
+                    &seat.opponent_payment_hash,
+                    game.stake,
+                    INVOICE_EXPIRY_SECS,
+                    &memo,
+                )
+                .await
+                .map_err(|e| e.to_string())?;
+            let _: InvoiceCreatedResponse = self
+                .post(
+                    &format!("{}/game/{}/invoice-created", seat.api, game_id),
+                    &InvoiceCreatedRequest {
+                        invoice_string: invoice.invoice_string,
+                    },
+                )
+                .await?;
+        }
+
+        // ...and pays the opponent's invoice, which is locked to its own hash
+        for seat in &mut seats {
+            let invoice = HoldInvoice {
+                payment_hash: seat.my_payment_hash,
+                amount: game.stake,
+                expiry_secs: INVOICE_EXPIRY_SECS,
+                invoice_string: self.wait_for_invoice(&seat.api, &game_id).await?,
+            };
+            let balance = money::sub(seat.balance, game.stake)
+                .map_err(|_| format!("{:?} cannot afford the stake", seat.role))?;
+            network
+                .pay_hold_invoice(&invoice)
+                .await
+                .map_err(|e| e.to_string())?;
+            seat.balance = balance;
+            let _: PaymentDoneResponse = self
+                .post(
+                    &format!("{}/game/{}/payment-done", seat.api, game_id),
+                    &PaymentDoneRequest::default(),
+                )
+                .await?;
+        }
+
+        // Both players move at once, as two browsers would
+        let play = |seat: &Seat, action: &GameAction| {
+            let path = format!("{}/game/{}/play", seat.api, game_id);
+            let body = PlayRequest {
+                action: action.clone(),
+            };
+            async move { self.post::<_, PlayResponse>(&path, &body).await }
+        };
+        tokio::try_join!(play(&seats[0], &actions[0]), play(&seats[1], &actions[1]))?;
+
+        // Settle like the frontend: the winner claims with the revealed
+        // preimage, everyone else cancels and the payer is refunded
+        let mut result = None;
+        for i in 0..seats.len() {
+            let seat = &seats[i];
+            let status = self.wait_for_result(&seat.api, &game_id).await?;
+            let game_result = status.result.ok_or("No result")?;
+            result = Some(game_result);
+
+            let won = matches!(
+                (game_result, seat.role),
+                (GameResult::AWins, Player::A) | (GameResult::BWins, Player::B)
+            );
+            let api = seat.api.clone();
+            if won {
+                let preimage =
+                    Preimage::from_bytes(parse_hex32(status.opponent_preimage.as_deref())?);
+                network
+                    .settle_invoice(&seat.opponent_payment_hash, &preimage)
+                    .await
+                    .map_err(|e| format!("{:?} could not settle: {}", seat.role, e))?;
+                seats[i].balance =
+                    money::add(seats[i].balance, game.stake).map_err(|e| e.to_string())?;
+            } else {
+                network
+                    .cancel_invoice(&seat.opponent_payment_hash)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                seats[1 - i].balance =
+                    money::add(seats[1 - i].balance, game.stake).map_err(|e| e.to_string())?;
+            }
+            let _: SettleResponse = self
+                .post(&format!("{}/game/{}/settle", api, game_id), &())
+                .await?;
+        }
+        let result = result.ok_or("No result")?;
+
+        // Funds must have moved exactly as the result says
+        for seat in &seats {
+            let expected = match (result, seat.role) {
+                (GameResult::Draw, _) => Ok(self.initial_balance),
+                (GameResult::AWins, Player::A) | (GameResult::BWins, Player::B) => {
+                    money::add(self.initial_balance, game.stake)
+                }
+                _ => money::sub(self.initial_balance, game.stake),
+            }
+            .map_err(|e| e.to_string())?;
+            if seat.balance != expected {
+                return Err(format!(
+                    "{:?} balance is {}, expected {}",
+                    seat.role, seat.balance, expected
+                ));
+            }
+        }
+
+        Ok((game_id, result))
+    }
+
+    /// Invoices sent over the direct player link arrive asynchronously.
+    async fn wait_for_invoice(&self, api: &str, game_id: &GameId) -> Result<String, String> {
+        let path = format!("{}/game/{}/opponent-invoice", api, game_id);
+        for _ in 0..50 {
+            if let Ok(resp) = self.get::<OpponentInvoiceResponse>(&path).await {
+                return Ok(resp.invoice_string);
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        Err("Timed out waiting for the opponent's invoice".to_string())
+    }
+
+    async fn wait_for_result(
+        &self,
+        api: &str,
+        game_id: &GameId,
+    ) -> Result<GameStatusResponse, String> {
+        for _ in 0..50 {
+            let status: GameStatusResponse =
+                self.get(&format!("{}/game/{}/status", api, game_id)).await?;
+            if status.result.is_some() {
+                return Ok(status);
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        Err("Timed out waiting for the oracle result".to_string())
+    }
+
+    async fn get<R: DeserializeOwned>(&self, path: &str) -> Result<R, String> {
+        let resp = self
+            .http
+            .get(format!("{}{}", self.base_url, path))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        Self::json(path, resp).await
+    }
+
+    async fn post<B: Serialize, R: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<R, String> {
+        let resp = self
+            .http
+            .post(format!("{}{}", self.base_url, path))
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        Self::json(path, resp).await
+    }
+
+    async fn json<R: DeserializeOwned>(path: &str, resp: reqwest::Response) -> Result<R, String> {
+        let status = resp.status();
+        let text = resp.text().await.map_err(|e| e.to_string())?;
+        if !status.is_success() {
+            return Err(format!("{} returned {}: {}", path, status, text));
+        }
+        serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+/// Parse a `0x`-prefixed 32-byte hex string from a status response.
+fn parse_hex32(value: Option<&str>) -> Result<[u8; 32], String> {
+    let s = value.ok_or("missing hex value")?;
+    let bytes = hex::decode(s.trim_start_matches("0x")).map_err(|e| e.to_string())?;
+    bytes.try_into().map_err(|_| "expected 32 bytes".to_string())
+}
+
+fn parse_hash(value: Option<&str>) -> Result<PaymentHash, String> {
+    parse_hex32(value).map(PaymentHash::from_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fiber_game_api::player::{
+        AbortRequest, EndGameResponse, PlayerGamePhase, ResumeRequest, ResumeResponse,
+    };
+    use fiber_game_core::protocol::{AbortReason, SettlementAction};
+
+    const SCRIPT: &str = r#"
+games:
+  - name: rock beats scissors
+    game_type: RockPaperScissors
+    stake: 1000
+    a: Rock
+    b: Scissors
+    expect: AWins
+  - name: paper draw
+    game_type: RockPaperScissors
+    stake: 1000
+    a: Paper
+    b: Paper
+    expect: Draw
+  - name: closest guess wins
+    game_type: GuessNumber
+    stake: 500
+    oracle_secret: 42
+    a: 10
+    b: 45
+    expect: BWins
+  - name: pennies match
+    game_type: MatchingPennies
+    stake: 300
+    a: Heads
+    b: Heads
+    expect: AWins
+  - name: nim leaves four
+    game_type: Nim
+    stake: 300
+    a: 1
+    b: 3
+    expect: BWins
+"#;
+
+    #[test]
+    fn test_parse_script() {
+        let script: Script = serde_yaml::from_str(SCRIPT).unwrap();
+        assert_eq!(script.initial_balance, 100_000);
+        assert_eq!(script.games.len(), 5);
+        assert!(matches!(script.games[0].a, ScriptAction::Rps(RpsAction::Rock)));
+        assert!(matches!(script.games[2].b, ScriptAction::Number(45)));
+        assert!(matches!(script.games[3].a, ScriptAction::Pennies(PenniesAction::Heads)));
+        assert_eq!(script.games[2].oracle_secret, Some(42));
+    }
+
+    #[tokio::test]
+    async fn test_run_script() {
+        let script: Script = serde_yaml::from_str(SCRIPT).unwrap();
+        let report = run_script(&script).await.unwrap();
+        assert!(report.passed(), "{}", report);
+    }
+
+    #[tokio::test]
+    async fn test_wrong_expectation_fails() {
+        let mut script: Script = serde_yaml::from_str(SCRIPT).unwrap();
+        script.games.truncate(1);
+        script.games[0].expect = GameResult::BWins;
+        let report = run_script(&script).await.unwrap();
+        assert_eq!(report.failures(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_seed_games_play_once() {
+        let demo = LocalDemo::spawn().await.unwrap();
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../../seeds/demo.yaml");
+        let seed = || Script::load(&path).unwrap();
+
+        let played = seed_games(demo.state.clone(), demo.base_url.clone(), seed()).await;
+        assert_eq!(played, 2);
+        assert_eq!(demo.state.oracle.game_count().await, 2);
+
+        // A demo that has games already is left as it is
+        let played = seed_games(demo.state.clone(), demo.base_url.clone(), seed()).await;
+        assert_eq!(played, 0);
+        assert_eq!(demo.state.oracle.game_count().await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_metrics_cover_oracle_and_players() {
+        let demo = LocalDemo::spawn().await.unwrap();
+        let script: Script = serde_yaml::from_str(SCRIPT).unwrap();
+        Simulation::new(&demo, script.initial_balance)
+            .play(&script.games[0])
+            .await
+            .unwrap();
+
+        let text = reqwest::get(format!("{}/metrics", demo.base_url))
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(text.contains("fiber_invoices_created_total 2"));
+        assert!(text.contains("fiber_games_completed_total{result=\"a_wins\"} 1"));
+        assert!(text.contains(r#"route="/api/player-a/game/:game_id/play",status="200""#));
+    }
+
+    #[tokio::test]
+    async fn test_settlement_reported_to_oracle() {
+        let demo = LocalDemo::spawn().await.unwrap();
+        let script: Script = serde_yaml::from_str(SCRIPT).unwrap();
+        let sim = Simulation::new(&demo, script.initial_balance);
+        let (game_id, _) = sim.play(&script.games[0]).await.unwrap();
+
+        let status: fiber_game_api::oracle::GameStatusResponse = sim
+            .get(&format!("/api/oracle/game/{}/status", game_id))
+            .await
+            .unwrap();
+        let settlement = status.settlement.unwrap();
+        assert!(settlement.complete);
+        assert_eq!(settlement.a.done, Some(SettlementAction::Settled));
+        assert_eq!(settlement.b.done, Some(SettlementAction::Cancelled));
+    }
+
+    fn rps_game() -> CreateGameRequest {
+        CreateGameRequest {
+            game_type: GameType::RockPaperScissors,
+            amount_shannons: 1000,
+            private: false,
+            draw_policy: DrawPolicy::Refund,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_abort_reaches_opponent() {
+        let demo = LocalDemo::spawn().await.unwrap();
+        let sim = Simulation::new(&demo, 0);
+        let created: CreateGameResponse = sim
+            .post("/api/player-a/game/create", &rps_game())
+            .await
+            .unwrap();
+        let game_id = created.game_id;
+        let _: JoinGameResponse = sim
+            .post("/api/player-b/game/join", &JoinGameRequest { game_id })
+            .await
+            .unwrap();
+
+        let abort = AbortRequest {
+            reason: AbortReason::Withdrawn,
+        };
+        let _: EndGameResponse = sim
+            .post(&format!("/api/player-b/game/{}/abort", game_id), &abort)
+            .await
+            .unwrap();
+        // Nothing left to play
+        let rock = PlayRequest {
+            action: GameAction::Rps(RpsAction::Rock),
+        };
+        assert!(sim
+            .post::<_, PlayResponse>(&format!("/api/player-b/game/{}/play", game_id), &rock)
+            .await
+            .is_err());
+
+        let status: GameStatusResponse = sim
+            .get(&format!("/api/player-a/game/{}/status", game_id))
+            .await
+            .unwrap();
+        assert_eq!(status.phase, PlayerGamePhase::Aborted);
+        assert_eq!(status.aborted_by, Some(Player::B));
+        assert_eq!(status.abort_reason, Some(AbortReason::Withdrawn));
+    }
+
+    #[tokio::test]
+    async fn test_resume_seat_on_fresh_player() {
+        let demo = LocalDemo::spawn().await.unwrap();
+        let sim = Simulation::new(&demo, 0);
+        let created: CreateGameResponse = sim
+            .post("/api/player-a/game/create", &rps_game())
+            .await
+            .unwrap();
+        let game_id = created.game_id;
+        let resume = ResumeRequest {
+            token: created.resume_token.unwrap(),
+        };
+        let original: GameStatusResponse = sim
+            .get(&format!("/api/player-a/game/{}/status", game_id))
+            .await
+            .unwrap();
+
+        // Player B's service has never seen the game; it takes over seat A
+        let resumed: ResumeResponse = sim
+            .post("/api/player-b/game/resume", &resume)
+            .await
+            .unwrap();
+        assert_eq!(resumed.game_id, game_id);
+        assert_eq!(resumed.phase, PlayerGamePhase::WaitingForOpponent);
+        let status: GameStatusResponse = sim
+            .get(&format!("/api/player-b/game/{}/status", game_id))
+            .await
+            .unwrap();
+        assert_eq!(status.role, Player::A);
+        assert_eq!(status.my_payment_hash, original.my_payment_hash);
+
+        // A game can only be resumed where it is missing
+        assert!(sim
+            .post::<_, ResumeResponse>("/api/player-b/game/resume", &resume)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_games_play_concurrently() {
+        let script: Script = serde_yaml::from_str(SCRIPT).unwrap();
+        let demo = LocalDemo::spawn().await.unwrap();
+        let sim = Arc::new(Simulation::new(&demo, script.initial_balance));
+
+        let mut games = tokio::task::JoinSet::new();
+        for game in script.games.iter().cycle().take(12).cloned() {
+            let sim = sim.clone();
+            games.spawn(async move { (sim.play(&game).await, game.expect) });
+        }
+        let mut ids = std::collections::HashSet::new();
+        while let Some(played) = games.join_next().await {
+            let (outcome, expected) = played.unwrap();
+            let (game_id, result) = outcome.unwrap();
+            assert_eq!(result, expected);
+            assert!(ids.insert(game_id));
+        }
+        assert_eq!(ids.len(), 12);
+    }
+}
@@ -0,0 +1,355 @@
+//! Developer simulation (`--dev-simulate`).
+//!
+//! The UI's loading and edge states need slow answers and an opponent that
+//! doesn't always behave, which two people at two browsers are a poor way
+//! to get. With the simulation on, every request to the oracle, the hosted
+//! players' own included, first waits a random time of up to
+//! `--dev-latency-ms` ([`delayed`]). The backend also plays the last hosted
+//! player itself ([`Opponent`]): it joins open games after a pause, moves
+//! at random, settles once the oracle has decided, and in
+//! `--dev-stall-percent` of its games stops answering before its move, so
+//! the other side meets the step timeout.
+//!
+//! Like the frontend in mock mode, the opponent makes no Fiber payments.
+
+use axum::{extract::Request, middleware::Next, Router};
+use fiber_game_api::player::{
+    AvailableGamesResponse, JoinGameRequest, JoinGameResponse, MyGamesResponse, PlayRequest,
+    PlayResponse, PlayerGamePhase, SettleResponse,
+};
+use fiber_game_core::games::{GameAction, GameType, PenniesAction, RpsAction, NIM_MAX_TAKE};
+use fiber_game_core::protocol::GameId;
+use rand::Rng;
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{debug, info};
+
+/// Longest delay added to a request to the oracle, unless configured
+/// otherwise
+pub const DEFAULT_LATENCY_MS: u64 = 1_500;
+
+/// Percent of games the simulated opponent stalls in, unless configured
+/// otherwise
+pub const DEFAULT_STALL_PERCENT: u8 = 20;
+
+/// Longest the simulated opponent takes to join a game or make its move
+pub const THINK_TIME: Duration = Duration::from_secs(5);
+
+/// How often the simulated opponent looks for something to do
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// `router` with every request held back a random time of up to `max`
+pub fn delayed(router: Router, max: Duration) -> Router {
+    router.layer(axum::middleware::from_fn(
+        move |req: Request, next: Next| async move {
+            tokio::time::sleep(random_delay(max)).await;
+            next.run(req).await
+        },
+    ))
+}
+
+fn random_delay(max: Duration) -> Duration {
+    Duration::from_millis(rand::thread_rng().gen_range(0..=max.as_millis() as u64))
+}
+
+/// A random legal move in `game_type`
+fn random_action(game_type: GameType) -> GameAction {
+    let mut rng = rand::thread_rng();
+    match game_type {
+        GameType::RockPaperScissors => {
+            let actions = [RpsAction::Rock, RpsAction::Paper, RpsAction::Scissors];
+            GameAction::Rps(actions[rng.gen_range(0..actions.len())])
+        }
+        GameType::GuessNumber => GameAction::GuessNumber(rng.gen_range(0..100)),
+        GameType::MatchingPennies => GameAction::Pennies(if rng.gen() {
+            PenniesAction::Heads
+        } else {
+            PenniesAction::Tails
+        }),
+        GameType::Nim => GameAction::Nim(rng.gen_range(1..=NIM_MAX_TAKE)),
+    }
+}
+
+/// What the opponent means to do next in a game
+#[derive(Debug)]
+enum Plan {
+    Join {
+        at: Instant,
+    },
+    Play {
+        at: Instant,
+        action: GameAction,
+    },
+    /// Left to time out
+    Stalled,
+    /// Nothing due until the game moves on
+    Waiting,
+}
+
+/// A hosted player driven by the backend through its own HTTP API
+pub struct Opponent {
+    /// The player's API, e.g. `http://localhost:3000/api/player-b`
+    api: String,
+    http: reqwest::Client,
+    think_time: Duration,
+    stall_percent: u8,
+    plans: HashMap<GameId, Plan>,
+}
+
+impl Opponent {
+    pub fn new(api: impl Into<String>, stall_percent: u8) -> Self {
+        Self {
+            api: api.into(),
+            http: reqwest::Client::new(),
+            think_time: THINK_TIME,
+            stall_percent,
+            plans: HashMap::new(),
+        }
+    }
+
+    /// Join and move after up to `think_time` rather than [`THINK_TIME`]
+    pub fn with_think_time(mut self, think_time: Duration) -> Self {
+        self.think_time = think_time;
+        self
+    }
+
+    /// Play every [`POLL_INTERVAL`] for as long as the process runs.
+    pub fn spawn(mut self) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(POLL_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.step().await {
+                    debug!(api = %self.api, error = %e, "Simulated opponent step failed");
+                }
+            }
+        });
+    }
+
+    /// Take the games on offer into account and do whatever is due.
+    pub async fn step(&mut self) -> Result<(), String> {
+        let now = Instant::now();
+        let available: AvailableGamesResponse = self.get("/games/available").await?;
+        let mine: MyGamesResponse = self.get("/games/mine").await?;
+
+        for game in &available.items {
+            let at = now + random_delay(self.think_time);
+            self.plans.entry(game.game_id).or_insert(Plan::Join { at });
+        }
+        for game in &mine.items {
+            let plan = self.plans.get(&game.game_id);
+            match game.phase {
+                PlayerGamePhase::WaitingForAction
+                    if !matches!(plan, Some(Plan::Play { .. } | Plan::Stalled)) =>
+                {
+                    let plan = if rand::thread_rng().gen_range(0..100) < self.stall_percent {
+                        info!(game_id = %game.game_id, "Simulated opponent stalls");
+                        Plan::Stalled
+                    } else {
+                        Plan::Play {
+                            at: now + random_delay(self.think_time),
+                            action: random_action(game.game_type),
+                        }
+                    };
+                    self.plans.insert(game.game_id, plan);
+                }
+                PlayerGamePhase::WaitingForResult if game.result.is_some() => {
+                    let path = format!("/game/{}/settle", game.game_id);
+                    let _: SettleResponse = self.post(&path, &()).await?;
+                }
+                _ => {}
+            }
+        }
+
+        // Games taken by someone else, or over, need nothing more
+        self.plans.retain(|id, _| {
+            available.items.iter().any(|g| g.game_id == *id)
+                || mine.items.iter().any(|g| {
+                    g.game_id == *id
+                        && !matches!(g.phase, PlayerGamePhase::Settled | PlayerGamePhase::Aborted)
+                })
+        });
+
+        let due: Vec<_> = self
+            .plans
+            .iter()
+            .filter(|(_, plan)| match plan {
+                Plan::Join { at } | Plan::Play { at, .. } => *at <= now,
+                _ => false,
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        for game_id in due {
+            match self.plans.insert(game_id, Plan::Waiting) {
+                Some(Plan::Join { .. }) => {
+                    let _: JoinGameResponse = self
+                        .post("/game/join", &JoinGameRequest { game_id })
+                        .await?;
+                    info!(%game_id, "Simulated opponent joined");
+                }
+                Some(Plan::Play { action, .. }) => {
+                    let path = format!("/game/{}/play", game_id);
+                    let _: PlayResponse = self.post(&path, &PlayRequest { action }).await?;
+                    info!(%game_id, "Simulated opponent played");
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    async fn get<R: DeserializeOwned>(&self, path: &str) -> Result<R, String> {
+        let resp = self.http.get(format!("{}{}", self.api, path)).send().await;
+        json(path, resp).await
+    }
+
+    async fn post<B: Serialize, R: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<R, String> {
+        let resp = self
+            .http
+            .post(format!("{}{}", self.api, path))
+            .json(body)
+            .send()
+            .await;
+        json(path, resp).await
+    }
+}
+
+async fn json<R: DeserializeOwned>(
+    path: &str,
+    resp: reqwest::Result<reqwest::Response>,
+) -> Result<R, String> {
+    let resp = resp.map_err(|e| e.to_string())?;
+    let status = resp.status();
+    let text = resp.text().await.map_err(|e| e.to_string())?;
+    if !status.is_success() {
+        return Err(format!("{} returned {}: {}", path, status, text));
+    }
+    serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::LocalDemo;
+    use fiber_game_api::player::{CreateGameRequest, CreateGameResponse, GameStatusResponse};
+    use fiber_game_core::protocol::DrawPolicy;
+
+    async fn create_game(demo: &LocalDemo) -> GameId {
+        let created: CreateGameResponse = reqwest::Client::new()
+            .post(format!("{}/api/player-a/game/create", demo.base_url))
+            .json(&CreateGameRequest {
+                game_type: GameType::RockPaperScissors,
+                amount_shannons: 1000,
+                private: false,
+                draw_policy: DrawPolicy::Refund,
+            })
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        created.game_id
+    }
+
+    async fn status(demo: &LocalDemo, player: &str, game_id: GameId) -> GameStatusResponse {
+        let url = format!("{}/api/{}/game/{}/status", demo.base_url, player, game_id);
+        reqwest::get(url).await.unwrap().json().await.unwrap()
+    }
+
+    /// Step `opponent` until player B's side of the game is in `phase`
+    async fn step_until(
+        opponent: &mut Opponent,
+        demo: &LocalDemo,
+        game_id: GameId,
+        phase: PlayerGamePhase,
+    ) {
+        for _ in 0..50 {
+            opponent.step().await.unwrap();
+            if opponent
+                .get::<GameStatusResponse>(&format!("/game/{}/status", game_id))
+                .await
+                .is_ok_and(|s| s.phase == phase)
+            {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!(
+            "Player B never reached {:?}: {:?}",
+            phase,
+            status(demo, "player-b", game_id).await.phase
+        );
+    }
+
+    #[tokio::test]
+    async fn test_opponent_joins_plays_and_settles() {
+        let demo = LocalDemo::spawn().await.unwrap();
+        let api = format!("{}/api/player-b", demo.base_url);
+        let mut opponent = Opponent::new(api, 0).with_think_time(Duration::ZERO);
+        let game_id = create_game(&demo).await;
+
+        step_until(&mut opponent, &demo, game_id, PlayerGamePhase::Revealed).await;
+        let play = PlayRequest {
+            action: GameAction::Rps(RpsAction::Rock),
+        };
+        let resp = reqwest::Client::new()
+            .post(format!(
+                "{}/api/player-a/game/{}/play",
+                demo.base_url, game_id
+            ))
+            .json(&play)
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+
+        step_until(&mut opponent, &demo, game_id, PlayerGamePhase::Settled).await;
+        // Forgotten once over
+        opponent.step().await.unwrap();
+        assert!(opponent.plans.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_stalling_opponent_never_moves() {
+        let demo = LocalDemo::spawn().await.unwrap();
+        let api = format!("{}/api/player-b", demo.base_url);
+        let mut opponent = Opponent::new(api, 100).with_think_time(Duration::ZERO);
+        let game_id = create_game(&demo).await;
+
+        step_until(
+            &mut opponent,
+            &demo,
+            game_id,
+            PlayerGamePhase::WaitingForAction,
+        )
+        .await;
+        for _ in 0..3 {
+            opponent.step().await.unwrap();
+        }
+        let b = status(&demo, "player-b", game_id).await;
+        assert_eq!(b.phase, PlayerGamePhase::WaitingForAction);
+        assert!(matches!(opponent.plans[&game_id], Plan::Stalled));
+    }
+
+    #[tokio::test]
+    async fn test_delayed_requests_still_answer() {
+        let router = Router::new().route("/", axum::routing::get(|| async { "ok" }));
+        let server = fiber_service::LocalServer::spawn(delayed(router, Duration::from_millis(30)))
+            .await
+            .unwrap();
+        let body = reqwest::get(server.url())
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert_eq!(body, "ok");
+    }
+}
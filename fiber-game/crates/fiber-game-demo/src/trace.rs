@@ -0,0 +1,91 @@
+//! `/api/demo/trace/:game_id`: the whole protocol run of one game.
+//!
+//! Merges the timelines recorded by the oracle and by every hosted player in
+//! the game into one time-ordered list of steps, which the UI renders as a
+//! sequence diagram.
+
+use crate::AppState;
+use axum::{
+    extract::State,
+    Json,
+};
+use fiber_errors::{ApiError, ValidPath};
+use fiber_game_core::protocol::{merge_timelines, GameId, TimelineEvent};
+use serde::Serialize;
+use std::sync::Arc;
+
+#[derive(Serialize)]
+pub(crate) struct TraceResponse {
+    game_id: GameId,
+    events: Vec<TimelineEvent>,
+}
+
+pub(crate) async fn trace(
+    State(state): State<Arc<AppState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+) -> Result<Json<TraceResponse>, ApiError> {
+    let oracle = state
+        .oracle
+        .timeline(&game_id)
+        .await
+        .ok_or_else(|| ApiError::not_found("Game not found"))?;
+    let mut players = Vec::new();
+    for player in &state.players {
+        players.extend(player.state.timeline(&game_id).await);
+    }
+
+    Ok(Json(TraceResponse {
+        game_id,
+        events: merge_timelines(std::iter::once(oracle).chain(players)),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::{LocalDemo, Script, Simulation};
+    use fiber_errors::ErrorCode;
+    use fiber_game_core::protocol::{Actor, ProtocolStep};
+
+    #[tokio::test]
+    async fn test_trace_covers_full_game() {
+        let demo = LocalDemo::spawn().await.unwrap();
+        let script: Script = serde_yaml::from_str(
+            "games: [{name: rps, game_type: RockPaperScissors, stake: 1000, a: Rock, b: Scissors, expect: AWins}]",
+        )
+        .unwrap();
+        let (game_id, _) = Simulation::new(&demo, script.initial_balance)
+            .play(&script.games[0])
+            .await
+            .unwrap();
+
+        let Json(trace) = trace(State(demo.state.clone()), ValidPath(game_id)).await.unwrap();
+        let events = trace.events;
+        assert!(events.windows(2).all(|w| w[0].at_ms <= w[1].at_ms));
+
+        let has = |from: Actor, step: ProtocolStep| {
+            events.iter().any(|e| e.from == from && e.step == step)
+        };
+        assert_eq!(events[0].step, ProtocolStep::GameCreated);
+        for player in [Actor::A, Actor::B] {
+            assert!(has(player, ProtocolStep::PaymentHashSubmitted));
+            assert!(has(player, ProtocolStep::InvoiceCreated));
+            assert!(has(player, ProtocolStep::PaymentSent));
+            assert!(has(player, ProtocolStep::Committed));
+            assert!(has(player, ProtocolStep::Revealed));
+            assert!(has(player, ProtocolStep::Settled));
+        }
+        assert!(has(Actor::Oracle, ProtocolStep::Judged));
+        assert!(has(Actor::Oracle, ProtocolStep::ResultReceived));
+    }
+
+    #[tokio::test]
+    async fn test_trace_unknown_game() {
+        let demo = LocalDemo::spawn().await.unwrap();
+        let err = trace(State(demo.state.clone()), ValidPath(GameId::new()))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.code, ErrorCode::NotFound);
+    }
+}
@@ -0,0 +1,506 @@
+//! Load and soak harness for the oracle.
+//!
+//! Plays many Rock Paper Scissors games at once against an in-process oracle
+//! router (no sockets), each game signing its messages like real players
+//! do, and reports throughput, per-step latency percentiles and how often
+//! requests waited on the oracle's game lock.
+//!
+//! Run a fixed number of games:
+//!   cargo bench -p fiber-game-oracle --bench oracle_load -- --games 5000 --concurrency 500
+//!
+//! Soak for ten minutes, reporting every 30 seconds:
+//!   cargo bench -p fiber-game-oracle --bench oracle_load -- --duration-secs 600 --report-every-secs 30
+//!
+//! With `--max-p99-ms` the run exits non-zero if any step is slower, so it
+//! can guard against regressions in CI.
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use clap::Parser;
+use fiber_game_core::{
+    crypto::{CommitContext, Commitment, Preimage, Salt},
+    games::{GameAction, RpsAction},
+    protocol::{Envelope, GameId, Player},
+};
+use fiber_game_oracle::{api_router, LockStats, OracleState};
+use secp256k1::SecretKey;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+/// Oracle load harness
+#[derive(Parser, Debug)]
+struct Args {
+    /// Games to play in total (ignored with --duration-secs)
+    #[arg(long, default_value_t = 2000)]
+    games: usize,
+    /// Games in flight at once
+    #[arg(long, default_value_t = 200)]
+    concurrency: usize,
+    /// Keep playing games for this long instead of a fixed number
+    #[arg(long)]
+    duration_secs: Option<u64>,
+    /// Print interim numbers this often while soaking
+    #[arg(long, default_value_t = 10)]
+    report_every_secs: u64,
+    /// Fail if any step's p99 latency is above this
+    #[arg(long)]
+    max_p99_ms: Option<f64>,
+    /// Runtime worker threads (one per core if unset)
+    #[arg(long)]
+    threads: Option<usize>,
+    /// Passed by `cargo bench`; ignored
+    #[arg(long, hide = true)]
+    bench: bool,
+}
+
+/// Each player's stake in shannons
+const STAKE: u64 = 1000;
+
+/// The requests a game is made of, in order
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Step {
+    Create,
+    Join,
+    PaymentHash,
+    Commit,
+    Reveal,
+    Result,
+}
+
+const STEPS: [Step; 6] = [
+    Step::Create,
+    Step::Join,
+    Step::PaymentHash,
+    Step::Commit,
+    Step::Reveal,
+    Step::Result,
+];
+
+/// Latencies and counters shared by all games
+#[derive(Default)]
+struct Recorder {
+    latencies: Mutex<BTreeMap<Step, Vec<Duration>>>,
+    games: AtomicUsize,
+    requests: AtomicU64,
+    failures: AtomicU64,
+}
+
+impl Recorder {
+    fn record(&self, step: Step, latency: Duration) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.latencies
+            .lock()
+            .unwrap()
+            .entry(step)
+            .or_default()
+            .push(latency);
+    }
+
+    /// Take the latencies recorded so far, leaving none
+    fn drain(&self) -> BTreeMap<Step, Vec<Duration>> {
+        std::mem::take(&mut *self.latencies.lock().unwrap())
+    }
+}
+
+/// One simulated game's two players talking to the oracle
+struct Game<'a> {
+    router: &'a Router,
+    recorder: &'a Recorder,
+    a: SecretKey,
+    b: SecretKey,
+}
+
+impl Game<'_> {
+    /// Send `payload` to `path`, signed with `key` for `game_id` if given
+    async fn send(
+        &self,
+        step: Step,
+        key: Option<&SecretKey>,
+        game_id: Option<GameId>,
+        path: String,
+        payload: Value,
+    ) -> Result<Value, String> {
+        let ttl = Duration::from_secs(60);
+        let req = match key {
+            Some(key) => {
+                let envelope = match game_id {
+                    Some(game_id) => Envelope::seal_for(game_id, payload, key, ttl),
+                    None => Envelope::seal_expiring(payload, key, ttl),
+                }
+                .map_err(|e| e.to_string())?;
+                Request::post(path)
+                    .header("content-type", "application/json")
+                    .body(Body::from(serde_json::to_vec(&envelope).unwrap()))
+            }
+            None => Request::get(path).body(Body::empty()),
+        }
+        .unwrap();
+
+        // Handlers never wait on I/O here, so hand the thread back between
+        // requests the way a network round trip would; otherwise one game
+        // runs start to finish and the reporter never gets scheduled
+        tokio::task::yield_now().await;
+        let start = Instant::now();
+        let resp = self.router.clone().oneshot(req).await.unwrap();
+        let status = resp.status();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .map_err(|e| e.to_string())?;
+        self.recorder.record(step, start.elapsed());
+
+        if status != StatusCode::OK {
+            return Err(format!(
+                "{:?} returned {}: {}",
+                step,
+                status,
+                String::from_utf8_lossy(&bytes)
+            ));
+        }
+        serde_json::from_slice(&bytes).map_err(|e| e.to_string())
+    }
+
+    fn key(&self, player: Player) -> &SecretKey {
+        match player {
+            Player::A => &self.a,
+            Player::B => &self.b,
+        }
+    }
+
+    /// Submit `player`'s payment hash, then their commitment
+    async fn lock_in(&self, game_id: GameId, player: Player, preimage: &Preimage) -> Result<(), String> {
+        self.send(
+            Step::PaymentHash,
+            Some(self.key(player)),
+            Some(game_id),
+            format!("/game/{}/payment-hash", game_id),
+            json!({
+                "player": player,
+                "payment_hash": preimage.payment_hash(),
+                "preimage": preimage,
+            }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn commit(
+        &self,
+        game_id: GameId,
+        player: Player,
+        commitment: Commitment,
+        context: CommitContext,
+    ) -> Result<(), String> {
+        self.send(
+            Step::Commit,
+            Some(self.key(player)),
+            Some(game_id),
+            format!("/game/{}/commit", game_id),
+            json!({
+                "player": player,
+                "commitment": commitment,
+                "amount_shannons": context.amount_shannons,
+                "opponent_payment_hash": context.opponent_payment_hash,
+            }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Play one game from creation to the signed result
+    async fn play(&self) -> Result<(), String> {
+        let created = self
+            .send(
+                Step::Create,
+                Some(&self.a),
+                None,
+                "/game/create".to_string(),
+                json!({
+                    "game_type": "RockPaperScissors",
+                    "player_a_id": Uuid::new_v4(),
+                    "amount_shannons": STAKE,
+                }),
+            )
+            .await?;
+        let game_id: GameId =
+            serde_json::from_value(created["game_id"].clone()).map_err(|e| e.to_string())?;
+        let path = |end: &str| format!("/game/{}/{}", game_id, end);
+
+        self.send(
+            Step::Join,
+            Some(&self.b),
+            Some(game_id),
+            path("join"),
+            json!({ "player_b_id": Uuid::new_v4() }),
+        )
+        .await?;
+
+        // Each step runs both players side by side. A commitment is bound
+        // to the opponent's payment hash, so both go in first.
+        let (preimage_a, preimage_b) = (Preimage::random(), Preimage::random());
+        tokio::try_join!(
+            self.lock_in(game_id, Player::A, &preimage_a),
+            self.lock_in(game_id, Player::B, &preimage_b),
+        )?;
+        let moves = [&preimage_b, &preimage_a].map(|opponent| {
+            let actions = [RpsAction::Rock, RpsAction::Paper, RpsAction::Scissors];
+            let action = GameAction::Rps(actions[rand::random::<usize>() % actions.len()]);
+            let salt = Salt::random();
+            let context = CommitContext {
+                game_id,
+                amount_shannons: STAKE,
+                opponent_payment_hash: opponent.payment_hash(),
+            };
+            let commitment = Commitment::bound(&action.to_bytes(), &context, &salt);
+            (action, salt, commitment, context)
+        });
+        let [(action_a, salt_a, commit_a, context_a), (action_b, salt_b, commit_b, context_b)] = moves;
+        tokio::try_join!(
+            self.commit(game_id, Player::A, commit_a, context_a),
+            self.commit(game_id, Player::B, commit_b, context_b),
+        )?;
+        let reveal = |player, action, salt| {
+            json!({
+                "player": player,
+                "action": action,
+                "salt": salt,
+                "commit_a": commit_a,
+                "commit_b": commit_b,
+            })
+        };
+        let (reveal_a, reveal_b) = (
+            reveal(Player::A, action_a, salt_a),
+            reveal(Player::B, action_b, salt_b),
+        );
+
+        // A reveal needs both commitments in
+        tokio::try_join!(
+            self.send(Step::Reveal, Some(&self.a), Some(game_id), path("reveal"), reveal_a),
+            self.send(Step::Reveal, Some(&self.b), Some(game_id), path("reveal"), reveal_b),
+        )?;
+
+        let result = self
+            .send(Step::Result, None, None, path("result"), Value::Null)
+            .await?;
+        if result["payload"]["status"] != "completed" {
+            return Err(format!("Game {} did not complete: {}", game_id, result));
+        }
+        Ok(())
+    }
+}
+
+fn random_key() -> SecretKey {
+    SecretKey::new(&mut rand::thread_rng())
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    let rank = ((sorted.len() as f64 * p).ceil() as usize).clamp(1, sorted.len());
+    sorted[rank - 1]
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+/// Print throughput, latencies and lock counters for one window; returns
+/// the worst p99 seen
+fn report(
+    elapsed: Duration,
+    games: usize,
+    requests: u64,
+    mut latencies: BTreeMap<Step, Vec<Duration>>,
+    lock: LockStats,
+) -> Duration {
+    let secs = elapsed.as_secs_f64();
+    println!(
+        "{} games, {} requests in {:.1}s: {:.0} games/s, {:.0} requests/s",
+        games,
+        requests,
+        secs,
+        games as f64 / secs,
+        requests as f64 / secs
+    );
+
+    println!(
+        "  {:<12} {:>8} {:>9} {:>9} {:>9} {:>9}",
+        "step", "count", "p50 ms", "p90 ms", "p99 ms", "max ms"
+    );
+    let mut worst_p99 = Duration::ZERO;
+    for step in STEPS {
+        let Some(samples) = latencies.get_mut(&step).filter(|s| !s.is_empty()) else {
+            continue;
+        };
+        samples.sort_unstable();
+        let p99 = percentile(samples, 0.99);
+        worst_p99 = worst_p99.max(p99);
+        println!(
+            "  {:<12} {:>8} {:>9.3} {:>9.3} {:>9.3} {:>9.3}",
+            format!("{:?}", step),
+            samples.len(),
+            ms(percentile(samples, 0.50)),
+            ms(percentile(samples, 0.90)),
+            ms(p99),
+            ms(*samples.last().unwrap()),
+        );
+    }
+
+    let mean_wait = if lock.contended > 0 {
+        lock.wait() / lock.contended as u32
+    } else {
+        Duration::ZERO
+    };
+    println!(
+        "  game lock: {} acquisitions, {} contended ({:.2}%), {:.1} ms waited (mean {:.3} ms)",
+        lock.acquisitions,
+        lock.contended,
+        lock.contended_ratio() * 100.0,
+        ms(lock.wait()),
+        ms(mean_wait),
+    );
+    worst_p99
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let mut runtime = tokio::runtime::Builder::new_multi_thread();
+    if let Some(threads) = args.threads {
+        runtime.worker_threads(threads);
+    }
+    runtime.enable_all().build().unwrap().block_on(run(args))
+}
+
+async fn run(args: Args) -> ExitCode {
+    let state = Arc::new(OracleState::new());
+    let router = api_router(state.clone());
+    let recorder = Arc::new(Recorder::default());
+
+    let deadline = args
+        .duration_secs
+        .map(|secs| Instant::now() + Duration::from_secs(secs));
+    match deadline {
+        Some(_) => println!(
+            "Soaking the oracle for {}s with {} games in flight",
+            args.duration_secs.unwrap(),
+            args.concurrency
+        ),
+        None => println!(
+            "Playing {} games against the oracle, {} in flight",
+            args.games, args.concurrency
+        ),
+    }
+
+    // Workers take game numbers until the quota or the time runs out
+    let next = Arc::new(AtomicUsize::new(0));
+    let start = Instant::now();
+    let workers: Vec<_> = (0..args.concurrency.max(1))
+        .map(|_| {
+            let (router, recorder, next) = (router.clone(), recorder.clone(), next.clone());
+            let games = args.games;
+            tokio::spawn(async move {
+                loop {
+                    let more = match deadline {
+                        Some(deadline) => Instant::now() < deadline,
+                        None => next.fetch_add(1, Ordering::Relaxed) < games,
+                    };
+                    if !more {
+                        break;
+                    }
+                    let game = Game {
+                        router: &router,
+                        recorder: &recorder,
+                        a: random_key(),
+                        b: random_key(),
+                    };
+                    if let Err(e) = game.play().await {
+                        eprintln!("{}", e);
+                        recorder.failures.fetch_add(1, Ordering::Relaxed);
+                    } else {
+                        recorder.games.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            })
+        })
+        .collect();
+
+    // While soaking, report each window on its own so drift shows up
+    let reporter = deadline.map(|deadline| {
+        let (state, recorder) = (state.clone(), recorder.clone());
+        let every = Duration::from_secs(args.report_every_secs.max(1));
+        tokio::spawn(async move {
+            let (mut games, mut requests, mut lock) = (0, 0, LockStats::default());
+            let mut window_start = Instant::now();
+            let mut worst_p99 = Duration::ZERO;
+            while Instant::now() + every < deadline {
+                tokio::time::sleep(every).await;
+                let (g, r, l) = (
+                    recorder.games.load(Ordering::Relaxed),
+                    recorder.requests.load(Ordering::Relaxed),
+                    state.lock_stats(),
+                );
+                println!(
+                    "\n[{:.0}s] {} games held by the oracle",
+                    start.elapsed().as_secs_f64(),
+                    state.game_count().await
+                );
+                let p99 = report(
+                    window_start.elapsed(),
+                    g - games,
+                    r - requests,
+                    recorder.drain(),
+                    l.since(&lock),
+                );
+                worst_p99 = worst_p99.max(p99);
+                (games, requests, lock, window_start) = (g, r, l, Instant::now());
+            }
+            worst_p99
+        })
+    });
+
+    for worker in workers {
+        worker.await.unwrap();
+    }
+    let window_p99 = match reporter {
+        Some(reporter) => reporter.await.unwrap(),
+        None => Duration::ZERO,
+    };
+    let elapsed = start.elapsed();
+
+    // Counts cover the whole run; while soaking, the latencies only cover
+    // the time since the last window was reported
+    match deadline {
+        Some(_) => println!("\nOverall, latencies since the last window:"),
+        None => println!(),
+    }
+    let worst_p99 = window_p99.max(report(
+        elapsed,
+        recorder.games.load(Ordering::Relaxed),
+        recorder.requests.load(Ordering::Relaxed),
+        recorder.drain(),
+        state.lock_stats(),
+    ));
+
+    let failures = recorder.failures.load(Ordering::Relaxed);
+    if failures > 0 {
+        eprintln!("{} games failed", failures);
+        return ExitCode::FAILURE;
+    }
+    if let Some(max) = args.max_p99_ms {
+        if ms(worst_p99) > max {
+            eprintln!(
+                "p99 of {:.3} ms is above the {} ms limit",
+                ms(worst_p99),
+                max
+            );
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,45 @@
+// The SQL files under migrations/ are embedded at compile time; rebuild when
+// one is added or edited.
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+    #[cfg(feature = "grpc")]
+    grpc::generate();
+}
+
+/// Server and client stubs for the service in proto/oracle.proto. Its
+/// messages are written out in src/grpc.rs, so building needs no `protoc`.
+#[cfg(feature = "grpc")]
+mod grpc {
+    use tonic_build::manual::{Builder, Method, Service};
+
+    /// Method, route, request and reply message
+    const METHODS: &[(&str, &str, &str, &str)] = &[
+        ("create_game", "CreateGame", "Submission", "CreateGameReply"),
+        ("join_game", "JoinGame", "Submission", "JoinGameReply"),
+        ("submit_funding", "SubmitFunding", "Submission", "StatusReply"),
+        ("submit_commit", "SubmitCommit", "Submission", "StatusReply"),
+        ("submit_reveal", "SubmitReveal", "Submission", "StatusReply"),
+        ("get_result", "GetResult", "GameRef", "GameResultReply"),
+        ("submit_settlement", "SubmitSettlement", "Submission", "StatusReply"),
+    ];
+
+    pub fn generate() {
+        let service = METHODS.iter().fold(
+            Service::builder().name("Oracle").package("fiber.oracle.v1"),
+            |service, (name, route, request, reply)| {
+                service.method(
+                    Method::builder()
+                        .name(name)
+                        .route_name(route)
+                        .input_type(format!("super::{}", request))
+                        .output_type(format!("super::{}", reply))
+                        .codec_path("tonic::codec::ProstCodec")
+                        .build(),
+                )
+            },
+        );
+        Builder::new()
+            .build_transport(false)
+            .compile(&[service.build()]);
+    }
+}
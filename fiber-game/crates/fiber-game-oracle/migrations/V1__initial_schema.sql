@@ -0,0 +1,11 @@
+-- Tables as the oracle created them before it had migrations; IF NOT EXISTS
+-- lets databases from that time adopt this history as they are.
+CREATE TABLE IF NOT EXISTS oracle_key (
+    id INTEGER PRIMARY KEY CHECK (id = 0),
+    secret_key TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS oracle_games (
+    game_id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
@@ -0,0 +1,7 @@
+-- Keys the oracle rotated to after the one in oracle_key, each signing new
+-- games from activates_at_ms (milliseconds since the epoch).
+CREATE TABLE oracle_rotated_keys (
+    public_key TEXT PRIMARY KEY,
+    secret_key TEXT NOT NULL,
+    activates_at_ms INTEGER NOT NULL
+);
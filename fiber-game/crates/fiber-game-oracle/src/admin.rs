@@ -0,0 +1,181 @@
+//! Operator API: list every game, force-cancel a stuck one and announce the
+//! next signing key.
+//!
+//! Served under `/admin` only when the oracle was given an admin token
+//! ([`OracleState::with_admin_token`]), and only to requests carrying it
+//! ([`AdminToken`]). `fiberctl` is its client.
+
+use crate::state::{GameStatus, OracleState};
+use axum::{
+    extract::State,
+    middleware,
+    routing::{get, post},
+    Json, Router,
+};
+use fiber_auth::{AdminToken, AuthState};
+use fiber_errors::{ApiError, ValidPath};
+use fiber_game_api::oracle::{
+    AdminGame, AdminGamesResponse, AnnounceKeyRequest, OracleKey, StatusResponse,
+};
+use fiber_game_core::protocol::{Actor, GameId, ProtocolStep};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+/// Operator routes, or none if the oracle has no admin token.
+pub(crate) fn admin_router(state: Arc<OracleState>) -> Router {
+    if !state.admin_secret().is_set() {
+        return Router::new();
+    }
+    Router::new()
+        .route("/admin/games", get(list_games))
+        .route("/admin/game/:game_id/cancel", post(force_cancel))
+        .route("/admin/keys", post(announce_key))
+        .route_layer(middleware::from_extractor_with_state::<AdminToken, _>(
+            state.clone(),
+        ))
+        .with_state(state)
+}
+
+/// Every game the oracle holds, oldest first
+async fn list_games(State(state): State<Arc<OracleState>>) -> Json<AdminGamesResponse> {
+    let games = state.games.read().await;
+    let mut games: Vec<AdminGame> = games
+        .iter()
+        .map(|(id, g)| AdminGame {
+            game_id: *id,
+            game_type: g.game_type,
+            amount_shannons: g.amount_shannons,
+            status: g.status.as_str().to_string(),
+            player_a_id: g.player_a_id,
+            player_b_id: g.player_b_id,
+            payment_hash_a: g.payment_hash_a,
+            payment_hash_b: g.payment_hash_b,
+            created_at: g.created_at.into(),
+            updated_at: g.updated_at().into(),
+            idle_secs: g.idle_for(state.clock.as_ref()).as_secs(),
+            settlement: g.settlement(state.clock.as_ref(), state.step_timeout),
+        })
+        .collect();
+    games.sort_by_key(|g| g.created_at);
+    Json(AdminGamesResponse { games })
+}
+
+/// Cancel a game that hasn't ended, whatever step it is at.
+///
+/// Unlike a player's abort this needs no signed message, and works after
+/// commits too: no result is signed, so neither player's preimage is
+/// released and both can cancel their hold invoices.
+async fn force_cancel(
+    State(state): State<Arc<OracleState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+) -> Result<Json<StatusResponse>, ApiError> {
+    let mut games = state.games.write().await;
+    let game = games
+        .get_mut(&game_id)
+        .ok_or_else(|| ApiError::not_found("Game not found"))?;
+    if game.status.is_over() {
+        return Err(ApiError::invalid_state("Game is already over"));
+    }
+
+    game.status = GameStatus::Cancelled;
+    game.timeline.push(
+        state
+            .event(Actor::Oracle, Actor::Oracle, ProtocolStep::Aborted)
+            .with_detail("cancelled_by_operator"),
+    );
+    state.persist(&game_id, game);
+    warn!(%game_id, "Game cancelled by operator");
+
+    Ok(Json(StatusResponse {
+        status: "cancelled".to_string(),
+    }))
+}
+
+/// Announce the key new games are signed with from `activate_in_secs` on,
+/// see [`OracleState::announce_key`].
+async fn announce_key(
+    State(state): State<Arc<OracleState>>,
+    Json(req): Json<AnnounceKeyRequest>,
+) -> Result<Json<OracleKey>, ApiError> {
+    let key = state.announce_key(Duration::from_secs(req.activate_in_secs))?;
+    Ok(Json(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::GameState;
+    use axum::body::Body;
+    use axum::extract::Request;
+    use axum::http::{header::AUTHORIZATION, StatusCode};
+    use fiber_game_core::games::GameType;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    /// An oracle with admin token "s3cret" and one game waiting for player B
+    fn oracle_with_game() -> (Arc<OracleState>, GameId) {
+        let mut state = OracleState::new().with_admin_token(Some("s3cret".to_string()));
+        let game_id = GameId::new();
+        let game = GameState::new(
+            GameType::RockPaperScissors,
+            1000,
+            Uuid::new_v4(),
+            None,
+            state.clock.now(),
+        );
+        state.games.get_mut().insert(game_id, game);
+        (Arc::new(state), game_id)
+    }
+
+    async fn send(state: &Arc<OracleState>, req: Request<Body>) -> (StatusCode, serde_json::Value) {
+        let resp = crate::api_router(state.clone()).oneshot(req).await.unwrap();
+        let status = resp.status();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or_default())
+    }
+
+    fn admin(method: &str, uri: &str, token: &str) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(AUTHORIZATION, format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_admin_routes_need_the_token() {
+        let (state, _) = oracle_with_game();
+        let (status, _) = send(&state, admin("GET", "/admin/games", "wrong")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, body) = send(&state, admin("GET", "/admin/games", "s3cret")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["games"][0]["status"], "waiting_for_opponent");
+    }
+
+    #[tokio::test]
+    async fn test_no_admin_routes_without_a_token() {
+        let state = Arc::new(OracleState::new());
+        let (status, _) = send(&state, admin("GET", "/admin/games", "")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_force_cancel() {
+        let (state, game_id) = oracle_with_game();
+        let uri = format!("/admin/game/{}/cancel", game_id);
+
+        let (status, _) = send(&state, admin("POST", &uri, "s3cret")).await;
+        assert_eq!(status, StatusCode::OK);
+        let timeline = state.timeline(&game_id).await.unwrap();
+        assert_eq!(timeline.last().unwrap().step, ProtocolStep::Aborted);
+
+        let (status, body) = send(&state, admin("POST", &uri, "s3cret")).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "invalid_state");
+    }
+}
@@ -0,0 +1,253 @@
+//! Public game explorer: what the oracle decided, without who played.
+//!
+//! Served under `/explorer` to anyone, each client limited to
+//! [`OracleState::with_explorer_rate_limit`] requests a minute. Nothing here
+//! names a player, a payment or a game ID, so the community can check the
+//! oracle's results over time without seeing private game data:
+//! - `/explorer/stats` counts ended games and sums up completed ones per
+//!   game type
+//! - `/explorer/attestations` lists every completed game as a
+//!   [`GameAttestation`] sealed with the oracle key
+
+use crate::state::{GameState, GameStatus, OracleState};
+use axum::{
+    extract::{Query, State},
+    routing::get,
+    Json, Router,
+};
+use fiber_errors::ApiError;
+use fiber_game_api::oracle::{
+    AttestationsResponse, DecidedBy, ExplorerStats, GameAttestation, GameTypeStats,
+};
+use fiber_game_core::games::GameType;
+use fiber_game_core::money;
+use fiber_game_core::protocol::{GameData, GameId, GameResult, OracleSecretData};
+use fiber_paging::{Page, PageRequest};
+use fiber_service::RateLimit;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+/// Explorer routes, rate limited per client.
+pub(crate) fn explorer_router(state: Arc<OracleState>) -> Router {
+    let limit = RateLimit::per_minute(state.explorer_rate_limit);
+    let router = Router::new()
+        .route("/explorer/stats", get(get_stats))
+        .route("/explorer/attestations", get(list_attestations))
+        .with_state(state);
+    fiber_service::rate_limit(router, limit)
+}
+
+async fn get_stats(State(state): State<Arc<OracleState>>) -> Result<Json<ExplorerStats>, ApiError> {
+    let games = state.games.read().await;
+    let mut stats = ExplorerStats {
+        completed: 0,
+        cancelled: 0,
+        game_types: Vec::new(),
+    };
+    let mut stakes = [0u64; GameType::ALL.len()];
+    let mut per_type: Vec<GameTypeStats> = GameType::ALL
+        .iter()
+        .map(|&game_type| GameTypeStats {
+            game_type,
+            games: 0,
+            a_wins: 0,
+            b_wins: 0,
+            draws: 0,
+            average_stake_shannons: 0,
+        })
+        .collect();
+
+    for game in games.values() {
+        match (game.status, game.result) {
+            (GameStatus::Cancelled, _) => stats.cancelled += 1,
+            (GameStatus::Completed, Some(result)) => {
+                stats.completed += 1;
+                let i = GameType::ALL
+                    .iter()
+                    .position(|t| *t == game.game_type)
+                    .expect("every game type is in GameType::ALL");
+                let entry = &mut per_type[i];
+                entry.games += 1;
+                match result {
+                    GameResult::AWins => entry.a_wins += 1,
+                    GameResult::BWins => entry.b_wins += 1,
+                    GameResult::Draw => entry.draws += 1,
+                }
+                stakes[i] = money::add(stakes[i], game.amount_shannons)?;
+            }
+            _ => {}
+        }
+    }
+
+    for (entry, total) in per_type.iter_mut().zip(stakes) {
+        entry.average_stake_shannons = total.checked_div(entry.games).unwrap_or(0);
+    }
+    stats.game_types = per_type.into_iter().filter(|t| t.games > 0).collect();
+    Ok(Json(stats))
+}
+
+/// Completed games, oldest first, each sealed with the key it was signed
+/// with
+async fn list_attestations(
+    State(state): State<Arc<OracleState>>,
+    Query(page): Query<PageRequest>,
+) -> Result<Json<AttestationsResponse>, ApiError> {
+    let games = state.games.read().await;
+    let completed = games
+        .iter()
+        .filter_map(|(id, g)| Some((attest(id, g)?, g.created_at, state.game_key(g))));
+    // Keyed by the reference, not the game ID, so cursors don't give it away
+    let page = Page::of(completed, &page, |(a, created_at, _)| {
+        (*created_at, a.reference.clone())
+    })?;
+
+    let mut items = Vec::with_capacity(page.items.len());
+    for (attestation, _, key) in page.items {
+        items.push(state.seal(&key, attestation)?);
+    }
+    Ok(Json(Page {
+        items,
+        next_cursor: page.next_cursor,
+    }))
+}
+
+/// What the oracle can say about a completed game without naming its
+/// players; `None` for a game without a result.
+fn attest(game_id: &GameId, game: &GameState) -> Option<GameAttestation> {
+    if game.status != GameStatus::Completed {
+        return None;
+    }
+    let result = game.result?;
+    let decided_by = match (&game.reveal_a, &game.reveal_b, game.verdict_a, game.verdict_b) {
+        (Some(_), Some(_), _, _) => DecidedBy::Reveals,
+        (_, _, Some(_), Some(_)) => DecidedBy::Verdicts,
+        _ => DecidedBy::Forfeit,
+    };
+    // A private game's actions stay between its players
+    let opened = match (&game.reveal_a, &game.reveal_b) {
+        (Some(a), Some(b)) if !game.private => Some((a, b)),
+        _ => None,
+    };
+
+    Some(GameAttestation {
+        reference: hex::encode(Sha256::digest(game_id.to_string().as_bytes())),
+        game_type: game.game_type,
+        amount_shannons: game.amount_shannons,
+        result,
+        decided_by,
+        commitment_a: game.commit_a,
+        commitment_b: game.commit_b,
+        game_data: opened.map(|(a, b)| GameData {
+            action_a: a.action.clone(),
+            action_b: b.action.clone(),
+            oracle_secret: game.oracle_secret.as_ref().map(OracleSecretData::from),
+        }),
+        salt_a: opened.map(|(a, _)| a.salt.clone()),
+        salt_b: opened.map(|(_, b)| b.salt.clone()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::RevealData;
+    use axum::body::Body;
+    use axum::extract::Request;
+    use axum::http::StatusCode;
+    use fiber_game_core::crypto::{Commitment, Preimage, Salt};
+    use fiber_game_core::games::{GameAction, RpsAction};
+    use fiber_game_core::protocol::{Envelope, Player};
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    /// Rock against Scissors, judged by the oracle
+    fn completed_game(state: &OracleState, game_id: &GameId) -> GameState {
+        let mut game = GameState::new(
+            GameType::RockPaperScissors,
+            1000,
+            Uuid::new_v4(),
+            None,
+            state.clock.now(),
+        );
+        game.player_b_id = Some(Uuid::new_v4());
+        game.payment_hash_a = Some(Preimage::random().payment_hash());
+        game.payment_hash_b = Some(Preimage::random().payment_hash());
+        let contexts = [Player::A, Player::B].map(|p| game.commit_context(*game_id, p).unwrap());
+        for ((action, commit, reveal), context) in [
+            (RpsAction::Rock, &mut game.commit_a, &mut game.reveal_a),
+            (RpsAction::Scissors, &mut game.commit_b, &mut game.reveal_b),
+        ]
+        .into_iter()
+        .zip(&contexts)
+        {
+            let action = GameAction::Rps(action);
+            let salt = Salt::random();
+            *commit = Some(Commitment::bound(&action.to_bytes(), context, &salt));
+            *reveal = Some(RevealData { action, salt });
+        }
+        game.complete(game_id, GameResult::AWins, "a_wins", state.clock.as_ref());
+        game
+    }
+
+    async fn get(router: &Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let resp = router.clone().oneshot(req).await.unwrap();
+        let status = resp.status();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn test_attestations_open_their_commitments_and_name_nobody() {
+        let mut state = OracleState::new();
+        let game_id = GameId::new();
+        let game = completed_game(&state, &game_id);
+        let player_a = game.player_a_id;
+        state.games.get_mut().insert(game_id, game);
+        let state = Arc::new(state);
+        let router = crate::api_router(state.clone());
+
+        let (status, stats) = get(&router, "/explorer/stats").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(stats["completed"], 1);
+        assert_eq!(stats["game_types"][0]["a_wins"], 1);
+        assert_eq!(stats["game_types"][0]["average_stake_shannons"], 1000);
+
+        let (status, page) = get(&router, "/explorer/attestations").await;
+        assert_eq!(status, StatusCode::OK);
+        let text = page.to_string();
+        assert!(!text.contains(&game_id.to_string()));
+        assert!(!text.contains(&player_a.to_string()));
+
+        let sealed: Envelope<GameAttestation> =
+            serde_json::from_value(page["items"][0].clone()).unwrap();
+        sealed.verify().unwrap();
+        assert_eq!(sealed.sender, state.public_key());
+        let attestation = sealed.payload;
+        assert_eq!(attestation.decided_by, DecidedBy::Reveals);
+        // A, who knows the game and both payment hashes, can open theirs
+        let data = attestation.game_data.unwrap();
+        let salt_a = attestation.salt_a.unwrap();
+        let context = state.games.read().await[&game_id]
+            .commit_context(game_id, Player::A)
+            .unwrap();
+        assert!(attestation
+            .commitment_a
+            .unwrap()
+            .verify_bound(&data.action_a.to_bytes(), &context, &salt_a));
+    }
+
+    #[tokio::test]
+    async fn test_explorer_is_rate_limited() {
+        let state = Arc::new(OracleState::new().with_explorer_rate_limit(2));
+        let router = crate::api_router(state);
+        for _ in 0..2 {
+            assert_eq!(get(&router, "/explorer/stats").await.0, StatusCode::OK);
+        }
+        let (status, body) = get(&router, "/explorer/stats").await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(body["code"], "rate_limited");
+    }
+}
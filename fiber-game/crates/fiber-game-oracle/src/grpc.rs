@@ -0,0 +1,385 @@
+//! gRPC interface for programs that would rather call the oracle than speak
+//! its HTTP API: a game's create, join, commit, reveal and result, as
+//! described in `proto/oracle.proto`.
+//!
+//! Each call runs the handler behind the matching HTTP route, so the two
+//! can't drift apart. Players sign submissions the same way over both, and
+//! a failure carries its [`ErrorCode`](fiber_errors::ErrorCode) in the
+//! `error-code` metadata entry.
+
+use crate::handlers;
+use crate::state::OracleState;
+use crate::wire::{Accept, Negotiated};
+use axum::{
+    extract::State,
+    Json, Router,
+};
+use fiber_auth::AuthedPlayer;
+use fiber_errors::{ApiError, ValidPath};
+use fiber_game_core::protocol::{Encoding, GameId};
+use serde::{de::DeserializeOwned, Serialize};
+use std::sync::Arc;
+use tonic::{server::NamedService, Request, Response, Status};
+
+use pb::oracle_server::{Oracle, OracleServer};
+
+/// Messages of `fiber.oracle.v1`, with the generated client and server
+pub mod pb {
+    /// A player's signed submission
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Submission {
+        /// Empty for `CreateGame`
+        #[prost(string, tag = "1")]
+        pub game_id: String,
+        /// The signed envelope, CBOR-encoded
+        #[prost(bytes = "vec", tag = "2")]
+        pub envelope: Vec<u8>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct GameRef {
+        #[prost(string, tag = "1")]
+        pub game_id: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct CreateGameReply {
+        #[prost(string, tag = "1")]
+        pub game_id: String,
+        #[prost(string, tag = "2")]
+        pub oracle_pubkey: String,
+        #[prost(string, tag = "3")]
+        pub commitment_point: String,
+        #[prost(string, optional, tag = "4")]
+        pub oracle_commitment: Option<String>,
+        /// CBOR envelope
+        #[prost(bytes = "vec", tag = "5")]
+        pub resume_token: Vec<u8>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct JoinGameReply {
+        #[prost(string, tag = "1")]
+        pub status: String,
+        #[prost(string, tag = "2")]
+        pub game_type: String,
+        #[prost(string, tag = "3")]
+        pub oracle_pubkey: String,
+        #[prost(string, tag = "4")]
+        pub commitment_point: String,
+        #[prost(string, optional, tag = "5")]
+        pub oracle_commitment: Option<String>,
+        #[prost(uint64, tag = "6")]
+        pub amount_shannons: u64,
+        #[prost(string, optional, tag = "7")]
+        pub peer_url: Option<String>,
+        /// CBOR envelope
+        #[prost(bytes = "vec", tag = "8")]
+        pub resume_token: Vec<u8>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct StatusReply {
+        #[prost(string, tag = "1")]
+        pub status: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct GameResultReply {
+        #[prost(string, tag = "1")]
+        pub status: String,
+        #[prost(string, optional, tag = "2")]
+        pub result: Option<String>,
+        /// The sealed `GameResultResponse`, a CBOR envelope
+        #[prost(bytes = "vec", tag = "3")]
+        pub envelope: Vec<u8>,
+    }
+
+    include!(concat!(env!("OUT_DIR"), "/fiber.oracle.v1.Oracle.rs"));
+}
+
+/// gRPC routes, to merge into the oracle's HTTP router
+pub fn router(state: Arc<OracleState>) -> Router {
+    let path = format!("/{}/*rpc", OracleServer::<OracleService>::NAME);
+    Router::new().route_service(&path, OracleServer::new(OracleService { state }))
+}
+
+struct OracleService {
+    state: Arc<OracleState>,
+}
+
+fn game_id(id: &str) -> Result<GameId, ApiError> {
+    id.parse().map_err(|_| ApiError::invalid_param("game_id", id))
+}
+
+fn open<T: DeserializeOwned>(submission: &pb::Submission) -> Result<AuthedPlayer<T>, ApiError> {
+    AuthedPlayer::from_bytes(Encoding::Cbor, &submission.envelope)
+}
+
+fn cbor<T: Serialize>(value: &T) -> Result<Vec<u8>, ApiError> {
+    Encoding::Cbor
+        .encode(value)
+        .map_err(|e| ApiError::internal(e.to_string()))
+}
+
+#[tonic::async_trait]
+impl Oracle for OracleService {
+    async fn create_game(
+        &self,
+        request: Request<pb::Submission>,
+    ) -> Result<Response<pb::CreateGameReply>, Status> {
+        let player = open(request.get_ref())?;
+        let Json(created) = handlers::create_game(State(self.state.clone()), player).await?;
+        Ok(Response::new(pb::CreateGameReply {
+            game_id: created.game_id.to_string(),
+            oracle_pubkey: created.oracle_pubkey,
+            commitment_point: created.commitment_point,
+            oracle_commitment: created.oracle_commitment,
+            resume_token: created
+                .resume_token
+                .as_ref()
+                .map(cbor)
+                .transpose()?
+                .unwrap_or_default(),
+        }))
+    }
+
+    async fn join_game(
+        &self,
+        request: Request<pb::Submission>,
+    ) -> Result<Response<pb::JoinGameReply>, Status> {
+        let game_id = game_id(&request.get_ref().game_id)?;
+        let player = open(request.get_ref())?;
+        let Json(joined) =
+            handlers::join_game(State(self.state.clone()), ValidPath(game_id), player).await?;
+        Ok(Response::new(pb::JoinGameReply {
+            status: joined.status,
+            game_type: format!("{:?}", joined.game_type),
+            oracle_pubkey: joined.oracle_pubkey,
+            commitment_point: joined.commitment_point,
+            oracle_commitment: joined.oracle_commitment,
+            amount_shannons: joined.amount_shannons,
+            peer_url: joined.peer_url,
+            resume_token: joined
+                .resume_token
+                .as_ref()
+                .map(cbor)
+                .transpose()?
+                .unwrap_or_default(),
+        }))
+    }
+
+    async fn submit_funding(
+        &self,
+        request: Request<pb::Submission>,
+    ) -> Result<Response<pb::StatusReply>, Status> {
+        let game_id = game_id(&request.get_ref().game_id)?;
+        let player = open(request.get_ref())?;
+        let Json(reply) =
+            handlers::submit_funding(State(self.state.clone()), ValidPath(game_id), player).await?;
+        Ok(Response::new(pb::StatusReply {
+            status: reply.status,
+        }))
+    }
+
+    async fn submit_commit(
+        &self,
+        request: Request<pb::Submission>,
+    ) -> Result<Response<pb::StatusReply>, Status> {
+        let game_id = game_id(&request.get_ref().game_id)?;
+        let player = open(request.get_ref())?;
+        let Json(reply) =
+            handlers::submit_commit(State(self.state.clone()), ValidPath(game_id), player).await?;
+        Ok(Response::new(pb::StatusReply {
+            status: reply.status,
+        }))
+    }
+
+    async fn submit_reveal(
+        &self,
+        request: Request<pb::Submission>,
+    ) -> Result<Response<pb::StatusReply>, Status> {
+        let game_id = game_id(&request.get_ref().game_id)?;
+        let player = open(request.get_ref())?;
+        let Json(reply) =
+            handlers::submit_reveal(State(self.state.clone()), ValidPath(game_id), player).await?;
+        Ok(Response::new(pb::StatusReply {
+            status: reply.status,
+        }))
+    }
+
+    async fn get_result(
+        &self,
+        request: Request<pb::GameRef>,
+    ) -> Result<Response<pb::GameResultReply>, Status> {
+        let game_id = game_id(&request.get_ref().game_id)?;
+        let Negotiated(_, sealed) = handlers::get_result(
+            State(self.state.clone()),
+            ValidPath(game_id),
+            Accept(Encoding::Cbor),
+        )
+        .await?;
+        Ok(Response::new(pb::GameResultReply {
+            status: sealed.payload.status.clone(),
+            result: sealed.payload.result.map(|result| format!("{:?}", result)),
+            envelope: cbor(&sealed)?,
+        }))
+    }
+
+    async fn submit_settlement(
+        &self,
+        request: Request<pb::Submission>,
+    ) -> Result<Response<pb::StatusReply>, Status> {
+        let game_id = game_id(&request.get_ref().game_id)?;
+        let player = open(request.get_ref())?;
+        let Json(reply) =
+            handlers::submit_settlement(State(self.state.clone()), ValidPath(game_id), player).await?;
+        Ok(Response::new(pb::StatusReply {
+            status: reply.status,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fiber_errors::ERROR_CODE_METADATA;
+    use fiber_game_api::oracle::GameResultResponse;
+    use fiber_game_core::crypto::{Commitment, Preimage, Salt};
+    use fiber_game_core::games::{GameAction, GameType, RpsAction};
+    use fiber_game_core::protocol::{Envelope, Player};
+    use fiber_test_fixtures::game::{seal, seal_for};
+    use fiber_test_fixtures::Keypair;
+    use uuid::Uuid;
+    use pb::oracle_client::OracleClient;
+    use serde_json::{json, Value};
+    use tonic::Code;
+
+    /// `payload` signed for `game_id`, or for no game when it's empty
+    fn submission(game_id: &str, payload: Value, key: &Keypair) -> pb::Submission {
+        let envelope = match game_id.parse() {
+            Ok(id) => seal_for(id, payload, &key.secret),
+            Err(_) => seal(payload, &key.secret),
+        };
+        pb::Submission {
+            game_id: game_id.to_string(),
+            envelope: Encoding::Cbor.encode(&envelope).unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_game_over_grpc() {
+        let state = Arc::new(OracleState::new());
+        let mut client = OracleClient::new(crate::create_router(state.clone()));
+        let (a, b) = (Keypair::player_a(), Keypair::player_b());
+
+        let create = json!({
+            "game_type": GameType::RockPaperScissors,
+            "player_a_id": Uuid::new_v4(),
+            "amount_shannons": 1000,
+        });
+        let created = client
+            .create_game(submission("", create, &a))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(!created.resume_token.is_empty());
+        let game_id = created.game_id;
+
+        let join = json!({ "player_b_id": Uuid::new_v4() });
+        let joined = client
+            .join_game(submission(&game_id, join, &b))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(joined.game_type, "RockPaperScissors");
+        assert_eq!(joined.amount_shannons, 1000);
+
+        // Payment hashes go over HTTP only
+        let id = super::game_id(&game_id).unwrap();
+        let contexts = {
+            let mut games = state.games.write().await;
+            let game = games.get_mut(&id).unwrap();
+            game.payment_hash_a = Some(Preimage::random().payment_hash());
+            game.payment_hash_b = Some(Preimage::random().payment_hash());
+            [Player::A, Player::B].map(|player| game.commit_context(id, player).unwrap())
+        };
+
+        let moves = [
+            (Player::A, &a, RpsAction::Rock, Salt::random()),
+            (Player::B, &b, RpsAction::Scissors, Salt::random()),
+        ];
+        let mut commitments = Vec::new();
+        for ((player, key, action, salt), context) in moves.iter().zip(&contexts) {
+            let commitment = Commitment::bound(&GameAction::Rps(*action).to_bytes(), context, salt);
+            let commit = json!({
+                "player": player,
+                "commitment": commitment,
+                "amount_shannons": context.amount_shannons,
+                "opponent_payment_hash": context.opponent_payment_hash,
+            });
+            client
+                .submit_commit(submission(&game_id, commit, key))
+                .await
+                .unwrap();
+            commitments.push(commitment);
+        }
+        for (player, key, action, salt) in &moves {
+            let reveal = json!({
+                "player": player,
+                "action": GameAction::Rps(*action),
+                "salt": salt,
+                "commit_a": commitments[0],
+                "commit_b": commitments[1],
+            });
+            client
+                .submit_reveal(submission(&game_id, reveal, key))
+                .await
+                .unwrap();
+        }
+
+        let result = client
+            .get_result(pb::GameRef { game_id })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(result.status, "completed");
+        assert_eq!(result.result.as_deref(), Some("AWins"));
+        // The sealed result verifies against the oracle's key
+        let sealed: Envelope<GameResultResponse> = Encoding::Cbor.decode(&result.envelope).unwrap();
+        let (signer, _) = sealed.open().unwrap();
+        assert_eq!(signer, state.public_key());
+    }
+
+    #[tokio::test]
+    async fn test_grpc_errors_carry_the_api_code() {
+        let mut client = OracleClient::new(crate::create_router(Arc::new(OracleState::new())));
+
+        let status = client
+            .get_result(pb::GameRef {
+                game_id: GameId::new().to_string(),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+        assert_eq!(
+            status.metadata().get(ERROR_CODE_METADATA).unwrap(),
+            "not_found"
+        );
+
+        // A submission signed by someone else than its payload claims
+        let mut forged = seal(
+            json!({ "player_b_id": Uuid::new_v4() }),
+            &Keypair::player_b().secret,
+        );
+        forged.payload = json!({ "player_b_id": Uuid::new_v4() });
+        let status = client
+            .join_game(pb::Submission {
+                game_id: GameId::new().to_string(),
+                envelope: Encoding::Cbor.encode(&forged).unwrap(),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+    }
+}
@@ -0,0 +1,2161 @@
+//! HTTP handlers for the oracle API.
+
+use crate::admin;
+use crate::explorer;
+use crate::state::{check_bound, GameState, GameStatus, OracleState, RevealData, Verdict};
+use crate::wire::{Accept, Negotiated};
+use axum::{
+    extract::{Query, State},
+    http::{HeaderName, HeaderValue},
+    routing::{get, post},
+    response::Response,
+    Json, Router,
+};
+use fiber_auth::AuthedPlayer;
+use fiber_errors::{ApiError, ErrorCode, ValidPath, Validate};
+use fiber_game_api::oracle::{
+    AvailableGame, AvailableGamesResponse, CreateGameRequest, CreateGameResponse,
+    EncryptedPreimageResponse, GameEnding, GameResultResponse, GameStatusResponse,
+    InvoiceResponse, JoinGameRequest, JoinGameResponse, OracleKeysResponse, OraclePubkeyResponse,
+    ORACLE_KEY_HEADER,
+    PaymentHashResponse, ResumeRequest, StatusResponse, SubmitCommitRequest,
+    SubmitEncryptedPreimageRequest, SubmitFundingRequest, SubmitInvoiceRequest,
+    SubmitPaymentHashRequest, SubmitRevealRequest, SubmitSettlementRequest, SubmitVerdictRequest,
+};
+use fiber_game_core::{
+    games::{self, OracleSecret},
+    protocol::{
+        AbortMessage, AbortReason, Actor, Direction, Envelope, GameData, GameId,
+        GameResult, GameSnapshot, MatchRound, MessageKind, OracleSecretData, Player, ProtocolStep,
+        ProtocolTrace, TimeoutClaim,
+    },
+};
+use fiber_paging::{Page, PageRequest};
+use fiber_service::Event;
+use std::sync::Arc;
+use tower_http::set_header::SetResponseHeaderLayer;
+use tracing::info;
+
+// === Route handlers ===
+
+async fn get_pubkey(State(state): State<Arc<OracleState>>) -> Json<OraclePubkeyResponse> {
+    Json(OraclePubkeyResponse {
+        pubkey: hex::encode(state.public_key().serialize()),
+    })
+}
+
+async fn get_keys(State(state): State<Arc<OracleState>>) -> Json<OracleKeysResponse> {
+    Json(OracleKeysResponse {
+        keys: state.key_history(),
+    })
+}
+
+async fn get_available_games(
+    State(state): State<Arc<OracleState>>,
+    Query(page): Query<PageRequest>,
+) -> Result<Json<AvailableGamesResponse>, ApiError> {
+    let games = state.games.read().await;
+    let now = state.clock.now();
+    let waiting = games
+        .iter()
+        .filter(|(_, g)| g.is_offered(state.offer_ttl, now) && g.is_open());
+    // Longest-waiting first, so an old offer isn't buried under new ones
+    let page = Page::of(waiting, &page, |(id, g)| (g.created_at, *id.as_uuid()))?;
+
+    Ok(Json(page.map(|(id, g)| AvailableGame {
+        game_id: *id,
+        game_type: g.game_type,
+        amount_shannons: g.amount_shannons,
+        created_at: g.created_at.into(),
+        expires_at: g.offer_expires_at(state.offer_ttl).into(),
+    })))
+}
+
+pub(crate) async fn create_game(
+    State(state): State<Arc<OracleState>>,
+    AuthedPlayer {
+        key: sender,
+        nonce,
+        payload: req,
+        envelope,
+    }: AuthedPlayer<CreateGameRequest>,
+) -> Result<Json<CreateGameResponse>, ApiError> {
+    // Whoever creates the game is player A from now on
+    req.validate()?;
+    let game_id = GameId::new();
+
+    // Generate Oracle secret if needed
+    let oracle_secret = req
+        .game_type
+        .requires_oracle_secret()
+        .then(OracleSecret::random);
+    let mut game_state = GameState::new(
+        req.game_type,
+        req.amount_shannons,
+        req.player_a_id,
+        oracle_secret,
+        state.clock.now(),
+    );
+    // Signed with the key active now until the game is over, however the
+    // keys rotate meanwhile
+    let oracle_key = state.active_key();
+    game_state.oracle_key = Some(oracle_key.public);
+    game_state.player_a_key = Some(sender);
+    game_state.last_nonce_a = nonce;
+    game_state.peer_url_a = req.p2p_url.clone();
+    game_state.private = req.private;
+    game_state.draw_policy = req.draw_policy;
+    game_state.round = Some(MatchRound::first(game_id));
+    let mut detail = format!("{:?}, {} shannons", req.game_type, req.amount_shannons);
+    if req.private {
+        detail.push_str(", private");
+    }
+
+    // A rematch keeps seat B for the drawn game's opponent, and the drawn
+    // game points to it so they can find it
+    let mut games = state.games.write().await;
+    if let Some(drawn_id) = req.rematch_of {
+        let drawn = games
+            .get_mut(&drawn_id)
+            .ok_or_else(|| ApiError::not_found("Drawn game not found"))?;
+        let round = drawn.rematch_round(drawn_id, &req, &sender)?;
+        drawn.rematch = Some(game_id);
+        state.persist(&drawn_id, drawn);
+        game_state.round = Some(round);
+        game_state.reserved_b = drawn.player_b_key;
+        detail.push_str(&format!(", round {}", round.number));
+    }
+    game_state.timeline.push(
+        state.event(Player::A, Actor::Oracle, ProtocolStep::GameCreated).with_detail(detail),
+    );
+    let commitment_point = game_state.commitment_point;
+    let oracle_commitment = game_state.oracle_commitment;
+    let round = game_state.round;
+    let resume_token = state.resumption_token(&oracle_key, game_id, Player::A, req.player_a_id)?;
+
+    state.record(game_id, Direction::Inbound, MessageKind::CreateGame, &envelope);
+    state.persist(&game_id, &game_state);
+    games.insert(game_id, game_state);
+    drop(games);
+
+    info!(%game_id, game_type = ?req.game_type, rematch_of = ?req.rematch_of, "Created game");
+
+    Ok(Json(CreateGameResponse {
+        game_id,
+        oracle_pubkey: hex::encode(oracle_key.public.serialize()),
+        commitment_point: hex::encode(commitment_point.serialize()),
+        oracle_commitment: oracle_commitment.map(hex::encode),
+        resume_token: Some(resume_token),
+        round,
+    }))
+}
+
+pub(crate) async fn join_game(
+    State(state): State<Arc<OracleState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+    AuthedPlayer {
+        key: sender,
+        nonce,
+        payload: req,
+        envelope,
+    }: AuthedPlayer<JoinGameRequest>,
+) -> Result<Json<JoinGameResponse>, ApiError> {
+    let mut games = state.games.write().await;
+    let game = games.get_mut(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+
+    // B may not have got our answer and asks again with the same key
+    let rejoin = game.status == GameStatus::InProgress
+        && game.player_b_key == Some(sender)
+        && game.player_b_id == Some(req.player_b_id);
+    if rejoin {
+        game.admit(&game_id, Player::B, &envelope)?;
+        info!(%game_id, player_id = %req.player_b_id, "Player B rejoined game");
+    } else {
+        if game.status != GameStatus::WaitingForOpponent {
+            return Err(ApiError::conflict("Game is not available to join"));
+        }
+        if !game.is_offered(state.offer_ttl, state.clock.now()) {
+            return Err(ApiError::conflict("Game offer has expired"));
+        }
+        if game.reserved_b.is_some_and(|key| key != sender) {
+            return Err(ApiError::forbidden("Game replays a draw and is kept for its opponent"));
+        }
+        check_bound(&game_id, &envelope)?;
+
+        game.player_b_id = Some(req.player_b_id);
+        game.player_b_key = Some(sender);
+        game.last_nonce_b = nonce;
+        game.status = GameStatus::InProgress;
+        game.timeline
+            .push(state.event(Player::B, Actor::Oracle, ProtocolStep::GameJoined));
+
+        state.record(game_id, Direction::Inbound, MessageKind::JoinGame, &envelope);
+        state.persist(&game_id, game);
+        info!(%game_id, player_id = %req.player_b_id, "Player B joined game");
+    }
+
+    let oracle_key = state.game_key(game);
+    Ok(Json(JoinGameResponse {
+        status: "joined".to_string(),
+        game_type: game.game_type,
+        oracle_pubkey: hex::encode(oracle_key.public.serialize()),
+        commitment_point: hex::encode(game.commitment_point.serialize()),
+        oracle_commitment: game.oracle_commitment.map(hex::encode),
+        amount_shannons: game.amount_shannons,
+        peer_url: game.peer_url_a.clone(),
+        private: game.private,
+        resume_token: Some(state.resumption_token(
+            &oracle_key,
+            game_id,
+            Player::B,
+            req.player_b_id,
+        )?),
+        draw_policy: game.draw_policy,
+        round: Some(game.round_of(game_id)),
+    }))
+}
+
+async fn submit_payment_hash(
+    State(state): State<Arc<OracleState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+    AuthedPlayer {
+        payload: req,
+        envelope,
+        ..
+    }: AuthedPlayer<SubmitPaymentHashRequest>,
+) -> Result<Json<StatusResponse>, ApiError> {
+    let mut games = state.games.write().await;
+    let game = games.get_mut(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+    game.admit(&game_id, req.player, &envelope)?;
+    if !req.payment_hash.verify(&req.preimage) {
+        return Err(ApiError::bad_request("Preimage does not match the payment hash"));
+    }
+
+    let (payment_hash, preimage, held, bound) = match req.player {
+        Player::A => (
+            &mut game.payment_hash_a,
+            &mut game.preimage_a,
+            game.stake_held_a,
+            game.commit_b.is_some(),
+        ),
+        Player::B => (
+            &mut game.payment_hash_b,
+            &mut game.preimage_b,
+            game.stake_held_b,
+            game.commit_a.is_some(),
+        ),
+    };
+    // Once the opponent holds our payment under this hash, a new one would
+    // get the winner a preimage that settles nothing; once their commitment
+    // is bound to it, their reveal would no longer open it
+    if (held || bound) && *payment_hash != Some(req.payment_hash) {
+        return Err(ApiError::conflict("Payment hash is already locked in"));
+    }
+    *payment_hash = Some(req.payment_hash);
+    *preimage = Some(req.preimage);
+    game.timeline.push(state.event(
+        req.player,
+        Actor::Oracle,
+        ProtocolStep::PaymentHashSubmitted,
+    ));
+
+    state.record(game_id, Direction::Inbound, MessageKind::PaymentHash, &envelope);
+    state.persist(&game_id, game);
+    info!(
+        %game_id,
+        player = %req.player,
+        payment_hash = %req.payment_hash.short_hex(),
+        "Received payment_hash"
+    );
+
+    Ok(Json(StatusResponse {
+        status: "payment_hash_received".to_string(),
+    }))
+}
+
+async fn get_payment_hash(
+    State(state): State<Arc<OracleState>>,
+    ValidPath((game_id, player)): ValidPath<(GameId, String)>,
+    Accept(encoding): Accept,
+) -> Result<Negotiated<Envelope<PaymentHashResponse>>, ApiError> {
+    let games = state.games.read().await;
+    let game = games.get(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+
+    let (player, payment_hash) = match player.as_str() {
+        "A" | "a" => (Player::A, game.payment_hash_a),
+        "B" | "b" => (Player::B, game.payment_hash_b),
+        _ => return Err(ApiError::bad_request("Invalid player")),
+    };
+    let payment_hash = payment_hash.ok_or_else(|| {
+        ApiError::not_found(format!("Payment hash {} not submitted", player))
+    })?;
+    let key = state.game_key(game);
+    drop(games);
+
+    Ok(Negotiated(encoding, state.seal_recorded(&key, game_id, MessageKind::PaymentHash, PaymentHashResponse { player, payment_hash })?))
+}
+
+async fn submit_invoice(
+    State(state): State<Arc<OracleState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+    AuthedPlayer {
+        payload: req,
+        envelope,
+        ..
+    }: AuthedPlayer<SubmitInvoiceRequest>,
+) -> Result<Json<StatusResponse>, ApiError> {
+    req.validate()?;
+    let mut games = state.games.write().await;
+    let game = games.get_mut(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+    game.admit(&game_id, req.player, &envelope)?;
+
+    match req.player {
+        Player::A => game.invoice_a = Some(req.invoice_string),
+        Player::B => game.invoice_b = Some(req.invoice_string),
+    }
+    game.timeline
+        .push(state.event(req.player, Actor::Oracle, ProtocolStep::InvoiceSubmitted));
+    state.record(game_id, Direction::Inbound, MessageKind::Invoice, &envelope);
+    state.persist(&game_id, game);
+
+    Ok(Json(StatusResponse {
+        status: "invoice_received".to_string(),
+    }))
+}
+
+async fn get_invoice(
+    State(state): State<Arc<OracleState>>,
+    ValidPath((game_id, player)): ValidPath<(GameId, String)>,
+) -> Result<Json<InvoiceResponse>, ApiError> {
+    let games = state.games.read().await;
+    let game = games.get(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+
+    let invoice_string = match player.as_str() {
+        "A" | "a" => game
+            .invoice_a
+            .as_ref()
+            .ok_or_else(|| ApiError::not_found("Invoice A not submitted"))?,
+        "B" | "b" => game
+            .invoice_b
+            .as_ref()
+            .ok_or_else(|| ApiError::not_found("Invoice B not submitted"))?,
+        _ => return Err(ApiError::bad_request("Invalid player")),
+    };
+
+    Ok(Json(InvoiceResponse {
+        invoice_string: invoice_string.clone(),
+    }))
+}
+
+async fn submit_encrypted_preimage(
+    State(state): State<Arc<OracleState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+    AuthedPlayer {
+        payload: req,
+        envelope,
+        ..
+    }: AuthedPlayer<SubmitEncryptedPreimageRequest>,
+) -> Result<Json<StatusResponse>, ApiError> {
+    let mut games = state.games.write().await;
+    let game = games.get_mut(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+    game.admit(&game_id, req.player, &envelope)?;
+
+    match req.player {
+        Player::A => game.encrypted_preimage_a = Some(req.encrypted_preimage),
+        Player::B => game.encrypted_preimage_b = Some(req.encrypted_preimage),
+    }
+    game.timeline.push(state.event(
+        req.player,
+        Actor::Oracle,
+        ProtocolStep::EncryptedPreimageSubmitted,
+    ));
+    state.record(game_id, Direction::Inbound, MessageKind::EncryptedPreimage, &envelope);
+    state.persist(&game_id, game);
+
+    Ok(Json(StatusResponse {
+        status: "encrypted_preimage_received".to_string(),
+    }))
+}
+
+async fn get_encrypted_preimage(
+    State(state): State<Arc<OracleState>>,
+    ValidPath((game_id, player)): ValidPath<(GameId, String)>,
+    Accept(encoding): Accept,
+) -> Result<Negotiated<Envelope<EncryptedPreimageResponse>>, ApiError> {
+    let games = state.games.read().await;
+    let game = games.get(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+
+    let encrypted_preimage = match player.as_str() {
+        "A" | "a" => game
+            .encrypted_preimage_a
+            .clone()
+            .ok_or_else(|| ApiError::not_found("Encrypted preimage A not submitted"))?,
+        "B" | "b" => game
+            .encrypted_preimage_b
+            .clone()
+            .ok_or_else(|| ApiError::not_found("Encrypted preimage B not submitted"))?,
+        _ => return Err(ApiError::bad_request("Invalid player")),
+    };
+    let key = state.game_key(game);
+    drop(games);
+
+    Ok(Negotiated(encoding, state.seal_recorded(
+        &key,
+        game_id,
+        MessageKind::EncryptedPreimage,
+        EncryptedPreimageResponse { encrypted_preimage },
+    )?))
+}
+
+/// A player found the opponent's payment held on their own node. Once both
+/// have said so, an oracle that requires funding takes commitments.
+pub(crate) async fn submit_funding(
+    State(state): State<Arc<OracleState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+    AuthedPlayer {
+        payload: req,
+        envelope,
+        ..
+    }: AuthedPlayer<SubmitFundingRequest>,
+) -> Result<Json<StatusResponse>, ApiError> {
+    let mut games = state.games.write().await;
+    let game = games.get_mut(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+    game.admit(&game_id, req.player, &envelope)?;
+    if game.status != GameStatus::InProgress {
+        return Err(ApiError::invalid_state("Game is not in progress"));
+    }
+
+    // The held payment is the opponent's, locked with their payment hash
+    let (expected, held) = match req.player {
+        Player::A => (game.payment_hash_b, &mut game.stake_held_b),
+        Player::B => (game.payment_hash_a, &mut game.stake_held_a),
+    };
+    if expected != Some(req.payment_hash) {
+        return Err(ApiError::bad_request("Payment hash is not the opponent's"));
+    }
+    if !*held {
+        *held = true;
+        game.timeline.push(
+            state.event(req.player, Actor::Oracle, ProtocolStep::StakeConfirmed)
+                .with_detail(format!("{} shannons", game.amount_shannons)),
+        );
+        state.record(game_id, Direction::Inbound, MessageKind::Funding, &envelope);
+        state.persist(&game_id, game);
+    }
+
+    let status = if game.stakes_held() {
+        "stakes_held"
+    } else {
+        "waiting_for_opponent"
+    };
+    Ok(Json(StatusResponse {
+        status: status.to_string(),
+    }))
+}
+
+pub(crate) async fn submit_commit(
+    State(state): State<Arc<OracleState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+    AuthedPlayer {
+        payload: req,
+        envelope,
+        ..
+    }: AuthedPlayer<SubmitCommitRequest>,
+) -> Result<Json<StatusResponse>, ApiError> {
+    let mut games = state.games.write().await;
+    let game = games.get_mut(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+    game.admit(&game_id, req.player, &envelope)?;
+    if game.status != GameStatus::InProgress {
+        return Err(ApiError::invalid_state("Game is not in progress"));
+    }
+    if state.require_funding && !game.stakes_held() {
+        return Err(ApiError::invalid_state(
+            "Both stakes must be confirmed held before committing",
+        ));
+    }
+    let context = game
+        .commit_context(game_id, req.player)
+        .ok_or_else(|| ApiError::invalid_state("Opponent's payment hash not submitted"))?;
+    if req.amount_shannons != context.amount_shannons
+        || req.opponent_payment_hash != context.opponent_payment_hash
+    {
+        return Err(ApiError::bad_request(
+            "Commitment is bound to another stake or payment hash",
+        ));
+    }
+
+    match req.player {
+        Player::A => game.commit_a = Some(req.commitment),
+        Player::B => game.commit_b = Some(req.commitment),
+    }
+    game.timeline
+        .push(state.event(req.player, Actor::Oracle, ProtocolStep::Committed));
+    state.record(game_id, Direction::Inbound, MessageKind::Commit, &envelope);
+    state.persist(&game_id, game);
+
+    Ok(Json(StatusResponse {
+        status: "commitment_received".to_string(),
+    }))
+}
+
+pub(crate) async fn submit_reveal(
+    State(state): State<Arc<OracleState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+    AuthedPlayer {
+        payload: req,
+        envelope,
+        ..
+    }: AuthedPlayer<SubmitRevealRequest>,
+) -> Result<Json<StatusResponse>, ApiError> {
+    let mut games = state.games.write().await;
+    let game = games.get_mut(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+    game.admit(&game_id, req.player, &envelope)?;
+
+    // A player that never got our answer sends the same reveal again; tell
+    // it where the game stands rather than refusing
+    let stored = match req.player {
+        Player::A => &game.reveal_a,
+        Player::B => &game.reveal_b,
+    };
+    let repeated = stored.as_ref().is_some_and(|reveal| {
+        reveal.action == req.action && reveal.salt.as_bytes() == req.salt.as_bytes()
+    });
+    let status = match game.status {
+        GameStatus::Completed => Some("game_complete"),
+        GameStatus::InProgress => Some("waiting_for_opponent"),
+        _ => None,
+    };
+    if let (true, Some(status)) = (repeated, status) {
+        return Ok(Json(StatusResponse {
+            status: status.to_string(),
+        }));
+    }
+
+    if game.status != GameStatus::InProgress {
+        return Err(ApiError::invalid_state("Game is not in progress"));
+    }
+
+    // Verify commitment matches
+    let expected_commit = match req.player {
+        Player::A => req.commit_a,
+        Player::B => req.commit_b,
+    };
+
+    let stored_commit = match req.player {
+        Player::A => game
+            .commit_a
+            .ok_or_else(|| ApiError::invalid_state("Commitment A not found"))?,
+        Player::B => game
+            .commit_b
+            .ok_or_else(|| ApiError::invalid_state("Commitment B not found"))?,
+    };
+
+    if expected_commit != stored_commit {
+        return Err(ApiError::bad_request("Commitment mismatch"));
+    }
+
+    if !req.action.validate(game.game_type) {
+        return Err(ApiError::bad_request("Invalid action for this game"));
+    }
+
+    // Verify the reveal opens the commitment, in this game
+    let opens = game.commit_context(game_id, req.player).is_some_and(|context| {
+        stored_commit.verify_bound(&req.action.to_bytes(), &context, &req.salt)
+    });
+    if !opens {
+        return Err(ApiError::bad_request("Reveal does not match commitment"));
+    }
+
+    // Store reveal
+    let reveal = RevealData {
+        action: req.action,
+        salt: req.salt,
+    };
+
+    match req.player {
+        Player::A => game.reveal_a = Some(reveal),
+        Player::B => game.reveal_b = Some(reveal),
+    }
+    game.timeline
+        .push(state.event(req.player, Actor::Oracle, ProtocolStep::Revealed));
+    state.record(game_id, Direction::Inbound, MessageKind::Reveal, &envelope);
+    state.persist(&game_id, game);
+
+    // Check if both reveals are in, then judge
+    if let (Some(reveal_a), Some(reveal_b)) = (&game.reveal_a, &game.reveal_b) {
+        let action_a = &reveal_a.action;
+        let action_b = &reveal_b.action;
+
+        // Judge the game
+        let result = games::game(game.game_type).judge(
+            action_a,
+            action_b,
+            game.oracle_secret.as_ref(),
+        );
+
+        game.complete(&game_id, result, result.as_str(), state.clock.as_ref());
+        state.persist(&game_id, game);
+        state.publish_completed(&game_id, result);
+
+        info!(%game_id, %result, "Game completed");
+
+        Ok(Json(StatusResponse {
+            status: "game_complete".to_string(),
+        }))
+    } else {
+        Ok(Json(StatusResponse {
+            status: "waiting_for_opponent".to_string(),
+        }))
+    }
+}
+
+/// A player's verdict on a private game. The result is signed once both
+/// verdicts agree, without the oracle ever seeing the actions behind them.
+pub(crate) async fn submit_verdict(
+    State(state): State<Arc<OracleState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+    AuthedPlayer {
+        payload: req,
+        envelope,
+        ..
+    }: AuthedPlayer<SubmitVerdictRequest>,
+) -> Result<Json<StatusResponse>, ApiError> {
+    let mut games = state.games.write().await;
+    let game = games.get_mut(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+    game.admit(&game_id, req.player, &envelope)?;
+    if !game.private {
+        return Err(ApiError::invalid_state("Game is not private; reveal the action instead"));
+    }
+
+    let verdict = Verdict {
+        result: req.result,
+        transcript: req.transcript,
+    };
+    let stored = match req.player {
+        Player::A => game.verdict_a,
+        Player::B => game.verdict_b,
+    };
+    // A player that never got our answer sends the same verdict again
+    if stored == Some(verdict) && game.status != GameStatus::Cancelled {
+        let status = if game.status == GameStatus::Completed {
+            "game_complete"
+        } else if game.reveals_requested() {
+            "reveals_requested"
+        } else {
+            "waiting_for_opponent"
+        };
+        return Ok(Json(StatusResponse {
+            status: status.to_string(),
+        }));
+    }
+
+    if game.status != GameStatus::InProgress {
+        return Err(ApiError::invalid_state("Game is not in progress"));
+    }
+    if stored.is_some() {
+        return Err(ApiError::conflict("Already sent a different verdict"));
+    }
+    if game.commit_a != Some(req.commit_a) || game.commit_b != Some(req.commit_b) {
+        return Err(ApiError::bad_request("Commitment mismatch"));
+    }
+
+    match req.player {
+        Player::A => game.verdict_a = Some(verdict),
+        Player::B => game.verdict_b = Some(verdict),
+    }
+    game.timeline.push(
+        state.event(req.player, Actor::Oracle, ProtocolStep::Revealed)
+            .with_detail(format!("verdict: {}", req.result.as_str())),
+    );
+    state.record(game_id, Direction::Inbound, MessageKind::Verdict, &envelope);
+    state.persist(&game_id, game);
+
+    let status = if game.reveals_requested() {
+        info!(%game_id, "Verdicts disagree, asking both players to reveal");
+        "reveals_requested"
+    } else if let (Some(a), Some(_)) = (game.verdict_a, game.verdict_b) {
+        let detail = format!("{}, agreed by both players", a.result.as_str());
+        game.complete(&game_id, a.result, &detail, state.clock.as_ref());
+        state.persist(&game_id, game);
+        state.publish_completed(&game_id, a.result);
+        info!(%game_id, result = %a.result, "Private game completed");
+        "game_complete"
+    } else {
+        "waiting_for_opponent"
+    };
+
+    Ok(Json(StatusResponse {
+        status: status.to_string(),
+    }))
+}
+
+/// A player leaves a game they haven't committed in yet; the game is
+/// cancelled and no preimage is ever released.
+async fn abort_game(
+    State(state): State<Arc<OracleState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+    AuthedPlayer {
+        payload: msg,
+        envelope,
+        ..
+    }: AuthedPlayer<AbortMessage>,
+) -> Result<Json<StatusResponse>, ApiError> {
+    if msg.game_id != game_id {
+        return Err(ApiError::bad_request("Message is for another game"));
+    }
+    if msg.reason == AbortReason::TimedOut {
+        return Err(ApiError::forbidden("Timeouts are claimed by the opponent"));
+    }
+    let mut games = state.games.write().await;
+    let game = games.get_mut(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+    game.admit(&game_id, msg.player, &envelope)?;
+
+    if game.status.is_over() {
+        return Err(ApiError::invalid_state("Game is already over"));
+    }
+    if game.progress(msg.player) >= 2 {
+        return Err(ApiError::invalid_state(
+            "Already committed; the game ends with a result or a timeout claim",
+        ));
+    }
+
+    game.status = GameStatus::Cancelled;
+    game.timeline.push(
+        state.event(msg.player, Actor::Oracle, ProtocolStep::Aborted)
+            .with_detail(msg.reason.as_str()),
+    );
+    state.record(game_id, Direction::Inbound, MessageKind::Abort, &envelope);
+    game.ending = Some(GameEnding::Aborted(envelope));
+    state.persist(&game_id, game);
+    if msg.reason == AbortReason::PaymentFailed {
+        state.events.publish(Event::PaymentFailed {
+            game_id: *game_id.as_uuid(),
+        });
+    }
+    info!(%game_id, player = %msg.player, reason = msg.reason.as_str(), "Player aborted game");
+
+    Ok(Json(StatusResponse {
+        status: "cancelled".to_string(),
+    }))
+}
+
+/// A player claims their opponent stopped responding.
+///
+/// Accepted once the opponent is behind the claimant and the game has been
+/// idle for the step timeout. An opponent who fails to commit or reveal after
+/// the claimant has committed forfeits, so stalling can't be used to dodge a
+/// loss; before that the game is simply cancelled.
+async fn claim_timeout(
+    State(state): State<Arc<OracleState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+    AuthedPlayer {
+        payload: claim,
+        envelope,
+        ..
+    }: AuthedPlayer<TimeoutClaim>,
+) -> Result<Json<StatusResponse>, ApiError> {
+    if claim.game_id != game_id {
+        return Err(ApiError::bad_request("Message is for another game"));
+    }
+    let mut games = state.games.write().await;
+    let game = games.get_mut(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+    game.admit(&game_id, claim.player, &envelope)?;
+
+    let opponent = claim.player.opponent();
+    let Some(overdue) = game.awaited_step(opponent) else {
+        return Err(ApiError::invalid_state("Opponent owes nothing in this game"));
+    };
+    let progress = game.progress(claim.player);
+    if progress <= game.progress(opponent) {
+        return Err(ApiError::invalid_state("Opponent is not behind you"));
+    }
+    let idle = game.idle_for(state.clock.as_ref());
+    if idle < state.step_timeout {
+        return Err(ApiError::invalid_state(format!(
+            "Opponent has {}s left",
+            (state.step_timeout - idle).as_secs()
+        )));
+    }
+
+    let forfeit = progress >= 2;
+    game.timeline.push(
+        state.event(claim.player, Actor::Oracle, ProtocolStep::TimeoutClaimed)
+            .with_detail(format!("{:?} overdue on {:?}", opponent, overdue)),
+    );
+    let status = if forfeit {
+        let result = match claim.player {
+            Player::A => GameResult::AWins,
+            Player::B => GameResult::BWins,
+        };
+        game.complete(
+            &game_id,
+            result,
+            &format!("{}, by forfeit", result.as_str()),
+            state.clock.as_ref(),
+        );
+        state.publish_completed(&game_id, result);
+        "game_complete"
+    } else {
+        game.status = GameStatus::Cancelled;
+        "cancelled"
+    };
+    state.record(game_id, Direction::Inbound, MessageKind::TimeoutClaim, &envelope);
+    game.ending = Some(GameEnding::TimedOut(envelope));
+    state.persist(&game_id, game);
+    info!(
+        %game_id,
+        player = %opponent,
+        overdue = ?overdue,
+        status,
+        "Player timed out"
+    );
+
+    Ok(Json(StatusResponse {
+        status: status.to_string(),
+    }))
+}
+
+/// A player settled or cancelled the invoice holding their opponent's stake.
+/// Once both have, the game's settlement is complete; until then the status
+/// tells each player what the other still owes.
+pub(crate) async fn submit_settlement(
+    State(state): State<Arc<OracleState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+    AuthedPlayer {
+        payload: req,
+        envelope,
+        ..
+    }: AuthedPlayer<SubmitSettlementRequest>,
+) -> Result<Json<StatusResponse>, ApiError> {
+    let mut games = state.games.write().await;
+    let game = games.get_mut(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+    game.admit(&game_id, req.player, &envelope)?;
+    if game.status != GameStatus::Completed {
+        return Err(ApiError::invalid_state("Game has no result"));
+    }
+
+    // The invoice holds the opponent's payment, locked with their hash
+    let (expected, settled) = match req.player {
+        Player::A => (game.payment_hash_b, &mut game.settled_a),
+        Player::B => (game.payment_hash_a, &mut game.settled_b),
+    };
+    if expected != Some(req.payment_hash) {
+        return Err(ApiError::bad_request("Payment hash is not the opponent's"));
+    }
+    if settled.is_none() {
+        *settled = Some(req.action);
+        game.timeline.push(
+            state.event(req.player, Actor::Oracle, ProtocolStep::Settled)
+                .with_detail(req.action.as_str()),
+        );
+        state.record(game_id, Direction::Inbound, MessageKind::Settlement, &envelope);
+        state.persist(&game_id, game);
+    }
+
+    let status = if game.settled_a.is_some() && game.settled_b.is_some() {
+        info!(%game_id, "Settlement complete");
+        "settlement_complete"
+    } else {
+        "waiting_for_opponent"
+    };
+    Ok(Json(StatusResponse {
+        status: status.to_string(),
+    }))
+}
+
+/// A player who lost their session takes their seat back with the token we
+/// issued them. The seat is bound to the key the request is signed with, so
+/// a player that also lost its protocol key can carry on with a new one.
+async fn resume_game(
+    State(state): State<Arc<OracleState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+    Accept(encoding): Accept,
+    AuthedPlayer {
+        key: sender,
+        nonce,
+        payload: req,
+        envelope,
+    }: AuthedPlayer<ResumeRequest>,
+) -> Result<Negotiated<Envelope<GameSnapshot>>, ApiError> {
+    let not_issued = || {
+        ApiError::new(
+            ErrorCode::InvalidSignature,
+            "Resumption token was not issued by this oracle",
+        )
+    };
+    let (issuer, token) = req.token.open().map_err(|_| not_issued())?;
+    if token.game_id != game_id {
+        return Err(ApiError::bad_request("Resumption token is for another game"));
+    }
+
+    let mut games = state.games.write().await;
+    let game = games.get_mut(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+    let key = state.game_key(game);
+    if issuer != key.public {
+        return Err(not_issued());
+    }
+    let seat_id = match token.player {
+        Player::A => Some(game.player_a_id),
+        Player::B => game.player_b_id,
+    };
+    if seat_id != Some(token.player_id) {
+        return Err(ApiError::forbidden("Resumption token does not match this game's players"));
+    }
+    check_bound(&game_id, &envelope)?;
+    game.advance_nonce(token.player, nonce)?;
+
+    match token.player {
+        Player::A => game.player_a_key = Some(sender),
+        Player::B => game.player_b_key = Some(sender),
+    }
+    game.timeline
+        .push(state.event(token.player, Actor::Oracle, ProtocolStep::Resumed));
+    state.record(game_id, Direction::Inbound, MessageKind::Resume, &envelope);
+    state.persist(&game_id, game);
+    let snapshot = game.snapshot(game_id, token.player);
+    drop(games);
+    info!(%game_id, player = %token.player, "Player resumed game");
+
+    Ok(Negotiated(encoding, state.seal_recorded(&key, game_id, MessageKind::Snapshot, snapshot)?))
+}
+
+async fn get_game_status(
+    State(state): State<Arc<OracleState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+) -> Result<Json<GameStatusResponse>, ApiError> {
+    let games = state.games.read().await;
+    let game = games.get(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+
+    Ok(Json(GameStatusResponse {
+        status: game.status.as_str().to_string(),
+        has_opponent: game.player_b_id.is_some(),
+        amount_shannons: game.amount_shannons,
+        funding_required: state.require_funding,
+        stake_held_a: game.stake_held_a,
+        stake_held_b: game.stake_held_b,
+        ending: game.ending.clone(),
+        commit_a: game.commit_a,
+        commit_b: game.commit_b,
+        reveals_requested: game.reveals_requested(),
+        settlement: game.settlement(state.clock.as_ref(), state.step_timeout),
+        step_deadline_secs: game
+            .step_deadline(state.clock.as_ref(), state.step_timeout)
+            .map(|left| left.as_secs()),
+        draw_policy: game.draw_policy,
+        round: Some(game.round_of(game_id)),
+        rematch: game.rematch,
+    }))
+}
+
+/// Every signed message for a game, for checking offline with
+/// [`fiber_game_core::protocol::verify_trace`].
+///
+/// Served only once the game is over, since a reveal would tell the opponent
+/// what to play. Payment hash submissions are left out: they carry the
+/// sender's preimage, which unlocks their stake for as long as the opponent's
+/// invoice holds it.
+async fn get_trace(
+    State(state): State<Arc<OracleState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+) -> Result<Json<ProtocolTrace>, ApiError> {
+    let playing = state.games.read().await.get(&game_id).is_some_and(|g| {
+        matches!(g.status, GameStatus::WaitingForOpponent | GameStatus::InProgress)
+    });
+    if playing {
+        return Err(ApiError::invalid_state("Game is still being played"));
+    }
+
+    let mut trace = state
+        .recorder
+        .trace(&game_id)
+        .ok_or_else(|| ApiError::not_found("No messages recorded for this game"))?;
+    trace
+        .entries
+        .retain(|e| !(e.direction == Direction::Inbound && e.kind == MessageKind::PaymentHash));
+    Ok(Json(trace))
+}
+
+pub(crate) async fn get_result(
+    State(state): State<Arc<OracleState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+    Accept(encoding): Accept,
+) -> Result<Negotiated<Envelope<GameResultResponse>>, ApiError> {
+    let games = state.games.read().await;
+    let game = games.get(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+
+    if game.status != GameStatus::Completed {
+        let status = if game.reveals_requested() {
+            "reveals_requested"
+        } else {
+            "pending"
+        };
+        let key = state.game_key(game);
+        drop(games);
+        return Ok(Negotiated(encoding, state.seal(&key, GameResultResponse {
+            status: status.to_string(),
+            result: None,
+            signature: None,
+            game_data: None,
+            preimage_for_a: None,
+            preimage_for_b: None,
+        })?));
+    }
+
+    let game_data = if let (Some(reveal_a), Some(reveal_b)) = (&game.reveal_a, &game.reveal_b) {
+        Some(GameData {
+            action_a: reveal_a.action.clone(),
+            action_b: reveal_b.action.clone(),
+            oracle_secret: game.oracle_secret.as_ref().map(OracleSecretData::from),
+        })
+    } else {
+        None
+    };
+
+    // Determine which player gets the opponent's preimage based on game result
+    // Winner gets opponent's preimage to settle their own invoice (my_invoice)
+    let (preimage_for_a, preimage_for_b) = match game.result {
+        Some(GameResult::AWins) => {
+            // A wins, so A gets B's preimage to settle A's invoice (paid by B)
+            (game.preimage_b.clone(), None)
+        }
+        Some(GameResult::BWins) => {
+            // B wins, so B gets A's preimage to settle B's invoice (paid by A)
+            (None, game.preimage_a.clone())
+        }
+        Some(GameResult::Draw) | None => {
+            // Draw or no result yet - no preimages revealed
+            (None, None)
+        }
+    };
+    let response = GameResultResponse {
+        status: "completed".to_string(),
+        result: game.result,
+        signature: game.signature.map(hex::encode),
+        game_data,
+        preimage_for_a,
+        preimage_for_b,
+    };
+    let key = state.game_key(game);
+    drop(games);
+
+    Ok(Negotiated(encoding, state.seal_recorded(&key, game_id, MessageKind::Result, response)?))
+}
+
+/// Oracle API routes, without CORS, for nesting into a larger app.
+pub fn api_router(state: Arc<OracleState>) -> Router {
+    Router::new()
+        .route("/oracle/pubkey", get(get_pubkey))
+        .route("/pubkey", get(get_pubkey))
+        .route("/oracle/keys", get(get_keys))
+        .route("/games/available", get(get_available_games))
+        .route("/game/create", post(create_game))
+        .route("/game/:game_id/join", post(join_game))
+        .route("/game/:game_id/payment-hash", post(submit_payment_hash))
+        .route("/game/:game_id/payment-hash/:player", get(get_payment_hash))
+        .route("/game/:game_id/invoice", post(submit_invoice))
+        .route("/game/:game_id/invoice/:player", get(get_invoice))
+        .route(
+            "/game/:game_id/encrypted-preimage",
+            post(submit_encrypted_preimage),
+        )
+        .route(
+            "/game/:game_id/encrypted-preimage/:player",
+            get(get_encrypted_preimage),
+        )
+        .route("/game/:game_id/funded", post(submit_funding))
+        .route("/game/:game_id/commit", post(submit_commit))
+        .route("/game/:game_id/reveal", post(submit_reveal))
+        .route("/game/:game_id/verdict", post(submit_verdict))
+        .route("/game/:game_id/abort", post(abort_game))
+        .route("/game/:game_id/timeout", post(claim_timeout))
+        .route("/game/:game_id/settled", post(submit_settlement))
+        .route("/game/:game_id/resume", post(resume_game))
+        .route("/game/:game_id/status", get(get_game_status))
+        .route("/game/:game_id/result", get(get_result))
+        .route("/game/:game_id/trace", get(get_trace))
+        .with_state(state.clone())
+        .merge(admin::admin_router(state.clone()))
+        .merge(explorer::explorer_router(state.clone()))
+        .layer(SetResponseHeaderLayer::overriding(
+            HeaderName::from_static(ORACLE_KEY_HEADER),
+            move |_: &Response| {
+                let keys = state.keys.header(state.clock.now_ms());
+                Some(HeaderValue::from_str(&keys).expect("hex is a valid header value"))
+            },
+        ))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use fiber_game_core::crypto::{Commitment, Preimage, Salt};
+    use fiber_game_core::games::{GameAction, GameType, RpsAction};
+    use crate::state::DEFAULT_STEP_TIMEOUT;
+    use fiber_game_core::clock::TestClock;
+    use fiber_game_api::oracle::KeyStatus;
+    use fiber_game_core::protocol::{DrawPolicy, VerdictMessage};
+    use fiber_test_fixtures::game::{seal, seal_for};
+    use fiber_test_fixtures::Keypair;
+    use serde_json::{json, Value};
+    use std::time::{Duration, SystemTime};
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    struct Table {
+        state: Arc<OracleState>,
+        router: Router,
+        game_id: GameId,
+        a: Keypair,
+        b: Keypair,
+    }
+
+    impl Table {
+        async fn post(&self, key: &Keypair, path: &str, payload: Value) -> (StatusCode, Value) {
+            self.send(self.game_id, path, &seal_for(self.game_id, payload, &key.secret)).await
+        }
+
+        async fn send(
+            &self,
+            game_id: GameId,
+            path: &str,
+            envelope: &Envelope<Value>,
+        ) -> (StatusCode, Value) {
+            let body = serde_json::to_vec(envelope).unwrap();
+            let resp = self
+                .router
+                .clone()
+                .oneshot(
+                    Request::post(format!("/game/{}/{}", game_id, path))
+                        .header("content-type", "application/json")
+                        .body(Body::from(body))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let status = resp.status();
+            let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+        }
+
+        /// POST `envelope` to `path`, off the game's routes
+        async fn send_to(&self, path: &str, envelope: &Envelope<Value>) -> Value {
+            let body = serde_json::to_vec(envelope).unwrap();
+            let resp = self
+                .router
+                .clone()
+                .oneshot(
+                    Request::post(path)
+                        .header("content-type", "application/json")
+                        .body(Body::from(body))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice(&bytes).unwrap()
+        }
+
+        async fn get(&self, path: &str) -> Value {
+            let resp = self
+                .router
+                .clone()
+                .oneshot(
+                    Request::get(format!("/game/{}/{}", self.game_id, path))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice(&bytes).unwrap()
+        }
+
+        /// `player`'s commitment to `action`, bound to this game, and the
+        /// commit message for it
+        async fn commit(&self, player: Player, action: &GameAction, salt: &Salt) -> (Commitment, Value) {
+            let context = self.state.games.read().await[&self.game_id]
+                .commit_context(self.game_id, player)
+                .unwrap();
+            let commitment = Commitment::bound(&action.to_bytes(), &context, salt);
+            let commit = json!({
+                "player": player,
+                "commitment": commitment,
+                "amount_shannons": context.amount_shannons,
+                "opponent_payment_hash": context.opponent_payment_hash,
+            });
+            (commitment, commit)
+        }
+
+        /// `player` commits to Rock and reveals it.
+        async fn play(&self, player: Player) {
+            let key = match player {
+                Player::A => &self.a,
+                Player::B => &self.b,
+            };
+            let action = GameAction::Rps(RpsAction::Rock);
+            let salt = Salt::random();
+            let (commitment, commit) = self.commit(player, &action, &salt).await;
+            let (status, _) = self.post(key, "commit", commit).await;
+            assert_eq!(status, StatusCode::OK);
+            let reveal = json!({
+                "player": player,
+                "action": action,
+                "salt": salt,
+                "commit_a": commitment,
+                "commit_b": commitment,
+            });
+            let (status, _) = self.post(key, "reveal", reveal).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+    }
+
+    /// A game where A is seated with a payment hash and B, if `join`, too.
+    fn table(step_timeout: Duration, join: bool) -> Table {
+        table_on(OracleState::new().with_step_timeout(step_timeout), join)
+    }
+
+    /// [`table`], on an oracle set up by the test.
+    fn table_on(mut state: OracleState, join: bool) -> Table {
+        let (a, b) = (Keypair::random(), Keypair::random());
+        let game_id = GameId::new();
+        let mut game = GameState::new(
+            GameType::RockPaperScissors,
+            1000,
+            Uuid::new_v4(),
+            None,
+            state.clock.now(),
+        );
+        game.player_a_key = Some(a.public);
+        game.preimage_a = Some(Preimage::random());
+        game.payment_hash_a = Some(Preimage::random().payment_hash());
+        if join {
+            let preimage_b = Preimage::random();
+            game.status = GameStatus::InProgress;
+            game.player_b_id = Some(Uuid::new_v4());
+            game.player_b_key = Some(b.public);
+            game.payment_hash_b = Some(preimage_b.payment_hash());
+            game.preimage_b = Some(preimage_b);
+        }
+        state.games.get_mut().insert(game_id, game);
+        let state = Arc::new(state);
+        Table {
+            router: api_router(state.clone()),
+            state,
+            game_id,
+            a,
+            b,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_abort_before_commit_only() {
+        let t = table(DEFAULT_STEP_TIMEOUT, false);
+        let abort = |player| json!({ "game_id": t.game_id, "player": player, "reason": "withdrawn" });
+
+        // B isn't seated, so can't abort on anyone's behalf
+        let (status, _) = t.post(&t.b, "abort", abort(Player::B)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, body) = t.post(&t.a, "abort", abort(Player::A)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "cancelled");
+        let game = t.get("status").await;
+        assert_eq!(game["status"], "cancelled");
+        assert_eq!(game["ending"]["kind"], "aborted");
+
+        let t = table(DEFAULT_STEP_TIMEOUT, true);
+        t.play(Player::A).await;
+        let (status, _) = t.post(&t.a, "abort", abort(Player::A)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_game_id_rejected_with_error_body() {
+        let t = table(DEFAULT_STEP_TIMEOUT, false);
+        for path in ["/game/nope/status", "/game/nope/invoice/A"] {
+            let resp = t
+                .router
+                .clone()
+                .oneshot(Request::get(path).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+            let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+            let body: Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(body, json!({ "error": "Invalid game_id: \"nope\"", "code": "bad_request" }));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timeout_claim_forfeits_stalled_opponent() {
+        let t = table(Duration::ZERO, true);
+        let claim = |player| json!({ "game_id": t.game_id, "player": player });
+
+        // Level with each other: nobody is waiting on anyone
+        let (status, _) = t.post(&t.a, "timeout", claim(Player::A)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        t.play(Player::A).await;
+        // B is the one behind
+        let (status, _) = t.post(&t.b, "timeout", claim(Player::B)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, body) = t.post(&t.a, "timeout", claim(Player::A)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "game_complete");
+        assert_eq!(t.get("status").await["ending"]["kind"], "timed_out");
+        let result = t.get("result").await;
+        assert_eq!(result["payload"]["result"], "AWins");
+        assert!(result["payload"]["preimage_for_a"].is_array());
+
+        // The stalled player can no longer play
+        let rock = GameAction::Rps(RpsAction::Rock);
+        let (_, commit) = t.commit(Player::B, &rock, &Salt::random()).await;
+        let (status, _) = t.post(&t.b, "commit", commit).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_outcomes_counted_in_metrics() {
+        let t = table(Duration::ZERO, true);
+        t.play(Player::A).await;
+        let claim = json!({ "game_id": t.game_id, "player": Player::A });
+        assert_eq!(t.post(&t.a, "timeout", claim).await.0, StatusCode::OK);
+
+        let u = table(DEFAULT_STEP_TIMEOUT, true);
+        let abort = json!({ "game_id": u.game_id, "player": Player::B, "reason": "payment_failed" });
+        assert_eq!(u.post(&u.b, "abort", abort).await.0, StatusCode::OK);
+
+        let counted = t.state.metrics().render();
+        assert!(counted.contains(r#"fiber_games_completed_total{result="a_wins"} 1"#));
+        assert!(u.state.metrics().render().contains("fiber_payments_failed_total 1"));
+    }
+
+    #[tokio::test]
+    async fn test_timeout_claim_waits_for_step_timeout() {
+        let t = table(DEFAULT_STEP_TIMEOUT, true);
+        t.play(Player::A).await;
+        let claim = json!({ "game_id": t.game_id, "player": Player::A });
+        let (status, _) = t.post(&t.a, "timeout", claim).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(t.get("status").await["status"], "in_progress");
+    }
+
+    #[tokio::test]
+    async fn test_timeout_claim_follows_oracle_clock() {
+        let clock = TestClock::new();
+        let t = table_on(OracleState::new().with_clock(clock.shared()), true);
+        t.play(Player::A).await;
+        let claim = json!({ "game_id": t.game_id, "player": Player::A });
+
+        // Nothing happens on the OS clock; only the oracle's counts
+        clock.advance(DEFAULT_STEP_TIMEOUT - Duration::from_secs(1));
+        let (status, _) = t.post(&t.a, "timeout", claim.clone()).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        clock.advance(Duration::from_secs(1));
+        let (status, body) = t.post(&t.a, "timeout", claim).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "game_complete");
+    }
+
+    #[tokio::test]
+    async fn test_resume_rebinds_seat() {
+        let t = table(DEFAULT_STEP_TIMEOUT, true);
+        t.play(Player::A).await;
+        let player_a_id = t.state.games.read().await[&t.game_id].player_a_id;
+        let token = t
+            .state
+            .resumption_token(&t.state.active_key(), t.game_id, Player::A, player_a_id)
+            .unwrap();
+
+        // A lost everything, key included
+        let new_key = Keypair::random();
+        let (status, body) = t.post(&new_key, "resume", json!({ "token": token })).await;
+        assert_eq!(status, StatusCode::OK);
+        let snapshot: GameSnapshot = serde_json::from_value(body["payload"].clone()).unwrap();
+        assert_eq!(snapshot.player, Player::A);
+        assert!(snapshot.preimage.is_some());
+        assert_eq!(snapshot.revealed_action, Some(GameAction::Rps(RpsAction::Rock)));
+
+        // The old key no longer speaks for A, the new one does
+        let game = &t.state.games.read().await[&t.game_id];
+        assert!(game.check_signer(Player::A, &t.a.public).is_err());
+        assert!(game.check_signer(Player::A, &new_key.public).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_resume_rejects_foreign_token() {
+        let t = table(DEFAULT_STEP_TIMEOUT, true);
+        let player_b_id = t.state.games.read().await[&t.game_id].player_b_id.unwrap();
+        let foreign = OracleState::new();
+        let token = foreign
+            .resumption_token(&foreign.active_key(), t.game_id, Player::B, player_b_id)
+            .unwrap();
+        let (status, _) = t.post(&Keypair::random(), "resume", json!({ "token": token })).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // Right oracle, wrong player
+        let token = t
+            .state
+            .resumption_token(&t.state.active_key(), t.game_id, Player::B, Uuid::new_v4())
+            .unwrap();
+        let (status, _) = t.post(&Keypair::random(), "resume", json!({ "token": token })).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_games_keep_their_key_across_rotation() {
+        let clock = TestClock::new();
+        let t = table_on(OracleState::new().with_clock(clock.shared()), true);
+        let first = t.state.public_key();
+        let player_a_id = t.state.games.read().await[&t.game_id].player_a_id;
+        let token = t
+            .state
+            .resumption_token(&t.state.active_key(), t.game_id, Player::A, player_a_id)
+            .unwrap();
+
+        let next = t.state.announce_key(Duration::from_secs(60)).unwrap();
+        assert_eq!(next.status, KeyStatus::Next);
+        assert_eq!(t.state.public_key(), first);
+        let again = t.state.announce_key(Duration::from_secs(60)).unwrap_err();
+        assert_eq!(again.code, ErrorCode::Conflict);
+
+        clock.advance(Duration::from_secs(60));
+        assert_ne!(t.state.public_key(), first);
+        let created = t
+            .send_to(
+                "/game/create",
+                &seal(
+                    json!({
+                        "game_type": "RockPaperScissors",
+                        "player_a_id": Uuid::new_v4(),
+                        "amount_shannons": 1000,
+                    }),
+                    &t.a.secret,
+                ),
+            )
+            .await;
+        assert_eq!(created["oracle_pubkey"], next.pubkey);
+
+        // The game from before plays out, and resumes, under the first key
+        t.play(Player::A).await;
+        t.play(Player::B).await;
+        let result = t.get("result").await;
+        assert_eq!(result["payload"]["status"], "completed");
+        assert_eq!(result["sender"], serde_json::to_value(first).unwrap());
+        let (status, body) = t.post(&Keypair::random(), "resume", json!({ "token": token })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["sender"], serde_json::to_value(first).unwrap());
+
+        // Both keys are listed, and named on every response
+        let resp = t
+            .router
+            .clone()
+            .oneshot(Request::get("/oracle/keys").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let header = resp.headers()[ORACLE_KEY_HEADER].to_str().unwrap().to_string();
+        assert_eq!(header, format!("{},{}", next.pubkey, hex::encode(first.serialize())));
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let keys: OracleKeysResponse = serde_json::from_slice(&bytes).unwrap();
+        let statuses: Vec<_> = keys.keys.iter().map(|k| k.status).collect();
+        assert_eq!(statuses, [KeyStatus::Retired, KeyStatus::Active]);
+    }
+
+    #[tokio::test]
+    async fn test_replayed_and_stale_submissions_rejected() {
+        let t = table(DEFAULT_STEP_TIMEOUT, true);
+        let rock = GameAction::Rps(RpsAction::Rock);
+        let (_, commit) = t.commit(Player::A, &rock, &Salt::random()).await;
+        let captured = seal_for(t.game_id, commit.clone(), &t.a.secret);
+        assert_eq!(t.send(t.game_id, "commit", &captured).await.0, StatusCode::OK);
+        assert_eq!(t.send(t.game_id, "commit", &captured).await.0, StatusCode::CONFLICT);
+
+        // Submissions must say when they expire, not have expired yet, and
+        // not stay valid for too long
+        let forever = Envelope::seal(commit.clone(), &t.b.secret).unwrap();
+        assert_eq!(t.send(t.game_id, "commit", &forever).await.0, StatusCode::BAD_REQUEST);
+        let stale = Envelope::seal_for(t.game_id, commit.clone(), &t.b.secret, Duration::ZERO).unwrap();
+        tokio::time::sleep(Duration::from_millis(2)).await;
+        assert_eq!(t.send(t.game_id, "commit", &stale).await.0, StatusCode::BAD_REQUEST);
+        let distant = Envelope::seal_for(t.game_id, commit, &t.b.secret, Duration::from_secs(86_400));
+        let (status, body) = t.send(t.game_id, "commit", &distant.unwrap()).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["fields"][0]["field"], "expires_at_ms");
+        assert!(t.state.games.read().await[&t.game_id].commit_b.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_message_replayed_into_another_game_rejected() {
+        // Two games between the same keys at once, each with its own nonces
+        let t = table(DEFAULT_STEP_TIMEOUT, true);
+        let other = GameId::new();
+        let game = t.state.games.read().await[&t.game_id].clone();
+        t.state.games.write().await.insert(other, game);
+
+        let rock = GameAction::Rps(RpsAction::Rock);
+        let (_, commit) = t.commit(Player::A, &rock, &Salt::random()).await;
+        let captured = seal_for(t.game_id, commit.clone(), &t.a.secret);
+        assert_eq!(t.send(t.game_id, "commit", &captured).await.0, StatusCode::OK);
+
+        // Its nonce is still fresh in the other game, but it wasn't signed
+        // for it; nor is a message signed for no game at all
+        let (status, body) = t.send(other, "commit", &captured).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["error"], "Message was not signed for this game");
+        let unbound = seal(commit.clone(), &t.a.secret);
+        assert_eq!(t.send(other, "commit", &unbound).await.0, StatusCode::FORBIDDEN);
+        assert!(t.state.games.read().await[&other].commit_a.is_none());
+
+        // A's own commit to the other game still goes through
+        let sealed = seal_for(other, commit, &t.a.secret);
+        assert_eq!(t.send(other, "commit", &sealed).await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_expired_offer_is_withdrawn() {
+        let clock = TestClock::new();
+        let state = OracleState::new()
+            .with_clock(clock.shared())
+            .with_offer_ttl(Duration::from_secs(60));
+        let t = table_on(state, false);
+        let available = || async {
+            let resp = t
+                .router
+                .clone()
+                .oneshot(Request::get("/games/available").body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<AvailableGamesResponse>(&bytes).unwrap()
+        };
+
+        let listed = available().await;
+        let created_at = chrono::DateTime::<chrono::Utc>::from(t.state.clock.now());
+        assert_eq!(listed.items[0].created_at, created_at);
+        assert_eq!(listed.items[0].expires_at, created_at + chrono::Duration::seconds(60));
+
+        clock.advance(Duration::from_secs(60));
+        assert!(available().await.items.is_empty());
+        let (status, body) = t.post(&t.b, "join", json!({ "player_b_id": Uuid::new_v4() })).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["error"], "Game offer has expired");
+    }
+
+    #[tokio::test]
+    async fn test_repeated_join_by_same_player() {
+        let t = table(DEFAULT_STEP_TIMEOUT, false);
+        let join = json!({ "player_b_id": Uuid::new_v4() });
+
+        let (status, first) = t.post(&t.b, "join", join.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        // B lost the answer and joins again
+        let (status, again) = t.post(&t.b, "join", join.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(again["commitment_point"], first["commitment_point"]);
+
+        // The seat is still B's alone
+        let (status, _) = t.post(&Keypair::random(), "join", join).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let (status, _) = t
+            .post(&t.b, "join", json!({ "player_b_id": Uuid::new_v4() }))
+            .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_reveal_is_acknowledged() {
+        let t = table(DEFAULT_STEP_TIMEOUT, true);
+        t.play(Player::A).await;
+
+        let action = GameAction::Rps(RpsAction::Paper);
+        let salt = Salt::random();
+        let (commitment, commit) = t.commit(Player::B, &action, &salt).await;
+        let (status, _) = t.post(&t.b, "commit", commit).await;
+        assert_eq!(status, StatusCode::OK);
+        let reveal = |salt: &Salt| {
+            json!({
+                "player": Player::B,
+                "action": action,
+                "salt": salt,
+                "commit_a": commitment,
+                "commit_b": commitment,
+            })
+        };
+        let (status, body) = t.post(&t.b, "reveal", reveal(&salt)).await;
+        assert_eq!((status, &body["status"]), (StatusCode::OK, &json!("game_complete")));
+
+        // B never saw that answer and sends the reveal again
+        let (status, body) = t.post(&t.b, "reveal", reveal(&salt)).await;
+        assert_eq!((status, &body["status"]), (StatusCode::OK, &json!("game_complete")));
+
+        // Anything else is still refused once the game is over
+        let (status, _) = t.post(&t.b, "reveal", reveal(&Salt::random())).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(t.get("result").await["payload"]["result"], "BWins");
+    }
+
+    #[tokio::test]
+    async fn test_commits_wait_for_both_stakes() {
+        let t = table_on(OracleState::new().with_require_funding(true), true);
+        let (hash_a, hash_b) = {
+            let games = t.state.games.read().await;
+            let game = &games[&t.game_id];
+            (game.payment_hash_a.unwrap(), game.payment_hash_b.unwrap())
+        };
+        let rock = GameAction::Rps(RpsAction::Rock);
+        let (_, commit) = t.commit(Player::A, &rock, &Salt::random()).await;
+        let (status, body) = t.post(&t.a, "commit", commit.clone()).await;
+        assert_eq!((status, &body["code"]), (StatusCode::CONFLICT, &json!("invalid_state")));
+
+        // A vouches for the payment locked with B's hash, not their own
+        let (status, _) = t
+            .post(&t.a, "funded", json!({ "player": Player::A, "payment_hash": hash_a }))
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, body) = t
+            .post(&t.a, "funded", json!({ "player": Player::A, "payment_hash": hash_b }))
+            .await;
+        assert_eq!((status, &body["status"]), (StatusCode::OK, &json!("waiting_for_opponent")));
+        assert_eq!(t.post(&t.a, "commit", commit.clone()).await.0, StatusCode::CONFLICT);
+
+        let (status, body) = t
+            .post(&t.b, "funded", json!({ "player": Player::B, "payment_hash": hash_a }))
+            .await;
+        assert_eq!((status, &body["status"]), (StatusCode::OK, &json!("stakes_held")));
+        let status = t.get("status").await;
+        assert_eq!(
+            (&status["funding_required"], &status["stake_held_a"], &status["stake_held_b"]),
+            (&json!(true), &json!(true), &json!(true))
+        );
+        assert_eq!(t.post(&t.a, "commit", commit).await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_settlement_tracked_until_both_invoices_resolved() {
+        let clock = TestClock::new();
+        let t = table_on(OracleState::new().with_clock(clock.shared()), true);
+        let (hash_a, hash_b) = {
+            let games = t.state.games.read().await;
+            let game = &games[&t.game_id];
+            (game.payment_hash_a.unwrap(), game.payment_hash_b.unwrap())
+        };
+        let settled = |player, payment_hash, action| {
+            json!({ "player": player, "payment_hash": payment_hash, "action": action })
+        };
+        let (status, _) = t
+            .post(&t.a, "settled", settled(Player::A, hash_b, "cancelled"))
+            .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(t.get("status").await.get("settlement").is_none());
+
+        // Rock against Rock: both owe a cancel
+        t.play(Player::A).await;
+        t.play(Player::B).await;
+        let settlement = &t.get("status").await["settlement"];
+        assert_eq!(settlement["a"]["owed"], "cancelled");
+        assert_eq!(settlement["complete"], false);
+
+        let (status, _) = t
+            .post(&t.a, "settled", settled(Player::A, hash_a, "cancelled"))
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        for _ in 0..2 {
+            let (status, body) = t
+                .post(&t.a, "settled", settled(Player::A, hash_b, "cancelled"))
+                .await;
+            assert_eq!((status, &body["status"]), (StatusCode::OK, &json!("waiting_for_opponent")));
+        }
+
+        // B drags its feet past the step timeout
+        clock.advance(DEFAULT_STEP_TIMEOUT);
+        let settlement = &t.get("status").await["settlement"];
+        assert_eq!(settlement["a"]["done"], "cancelled");
+        assert_eq!((&settlement["a"]["overdue"], &settlement["b"]["overdue"]), (&json!(false), &json!(true)));
+
+        let (status, body) = t
+            .post(&t.b, "settled", settled(Player::B, hash_a, "cancelled"))
+            .await;
+        assert_eq!((status, &body["status"]), (StatusCode::OK, &json!("settlement_complete")));
+        assert_eq!(t.get("status").await["settlement"]["complete"], true);
+    }
+
+    #[tokio::test]
+    async fn test_draw_is_replayed_for_the_same_opponent() {
+        let t = table(DEFAULT_STEP_TIMEOUT, true);
+        if let Some(game) = t.state.games.write().await.get_mut(&t.game_id) {
+            game.draw_policy = DrawPolicy::RematchHeld;
+            game.round = Some(MatchRound::first(t.game_id));
+        }
+        let rematch = |key: &Keypair, amount_shannons: u64| {
+            seal(
+                json!({
+                    "game_type": "RockPaperScissors",
+                    "player_a_id": Uuid::new_v4(),
+                    "amount_shannons": amount_shannons,
+                    "draw_policy": "rematch_held",
+                    "rematch_of": t.game_id,
+                }),
+                &key.secret,
+            )
+        };
+        let created = t.send_to("/game/create", &rematch(&t.a, 1000)).await;
+        assert!(created.get("game_id").is_none(), "replayed before the draw");
+
+        // Rock against Rock; the invoices stay held for the next round
+        t.play(Player::A).await;
+        t.play(Player::B).await;
+        assert_eq!(t.get("status").await["settlement"]["a"]["owed"], "carried_over");
+        for refused in [rematch(&t.b, 1000), rematch(&t.a, 2000)] {
+            assert!(t.send_to("/game/create", &refused).await.get("game_id").is_none());
+        }
+
+        let created = t.send_to("/game/create", &rematch(&t.a, 1000)).await;
+        let next: GameId = serde_json::from_value(created["game_id"].clone()).unwrap();
+        assert_eq!(created["round"], json!({ "match_id": t.game_id, "number": 2 }));
+        assert_eq!(t.get("status").await["rematch"], json!(next));
+        let again = t.send_to("/game/create", &rematch(&t.a, 1000)).await;
+        assert!(again.get("game_id").is_none(), "replayed twice");
+
+        // Seat B is kept for the drawn game's opponent
+        let join = |key: &Keypair| seal_for(next, json!({ "player_b_id": Uuid::new_v4() }), &key.secret);
+        let (status, _) = t.send(next, "join", &join(&Keypair::random())).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, joined) = t.send(next, "join", &join(&t.b)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(joined["round"]["number"], 2);
+        assert_eq!(joined["draw_policy"], "rematch_held");
+    }
+
+    #[tokio::test]
+    async fn test_step_deadline_counts_down_until_settled() {
+        let clock = TestClock::new();
+        let t = table_on(OracleState::new().with_clock(clock.shared()), true);
+        let timeout = DEFAULT_STEP_TIMEOUT.as_secs();
+        assert_eq!(t.get("status").await["step_deadline_secs"], timeout);
+        clock.advance(Duration::from_secs(100));
+        assert_eq!(t.get("status").await["step_deadline_secs"], timeout - 100);
+
+        // After the result the invoices are what's due
+        t.play(Player::A).await;
+        t.play(Player::B).await;
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(t.get("status").await["step_deadline_secs"], timeout - 10);
+
+        let (hash_a, hash_b) = {
+            let games = t.state.games.read().await;
+            let game = &games[&t.game_id];
+            (game.payment_hash_a.unwrap(), game.payment_hash_b.unwrap())
+        };
+        for (player, key, payment_hash) in [(Player::A, &t.a, hash_b), (Player::B, &t.b, hash_a)] {
+            let (status, _) = t
+                .post(
+                    key,
+                    "settled",
+                    json!({ "player": player, "payment_hash": payment_hash, "action": "cancelled" }),
+                )
+                .await;
+            assert_eq!(status, StatusCode::OK);
+        }
+        assert!(t.get("status").await.get("step_deadline_secs").is_none());
+    }
+
+    /// A private table where both players have committed: A to Rock, B to
+    /// Scissors. Returns each one's salt and commitment.
+    async fn private_table() -> (Table, [(Salt, Commitment); 2]) {
+        let t = table(DEFAULT_STEP_TIMEOUT, true);
+        t.state.games.write().await.get_mut(&t.game_id).unwrap().private = true;
+        let mut commits = Vec::new();
+        for (player, key, action) in [
+            (Player::A, &t.a, RpsAction::Rock),
+            (Player::B, &t.b, RpsAction::Scissors),
+        ] {
+            let salt = Salt::random();
+            let (commitment, commit) = t.commit(player, &GameAction::Rps(action), &salt).await;
+            let (status, _) = t.post(key, "commit", commit).await;
+            assert_eq!(status, StatusCode::OK);
+            commits.push((salt, commitment));
+        }
+        let commits = [commits[0].clone(), commits[1].clone()];
+        (t, commits)
+    }
+
+    fn verdict(
+        t: &Table,
+        player: Player,
+        result: GameResult,
+        [(salt_a, commit_a), (salt_b, commit_b)]: &[(Salt, Commitment); 2],
+    ) -> Value {
+        json!({
+            "game_id": t.game_id,
+            "player": player,
+            "result": result,
+            "commit_a": commit_a,
+            "commit_b": commit_b,
+            "transcript": VerdictMessage::transcript(&t.game_id, salt_a, salt_b),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_private_game_signs_agreed_verdict() {
+        let (t, commits) = private_table().await;
+
+        let (status, body) = t
+            .post(&t.a, "verdict", verdict(&t, Player::A, GameResult::AWins, &commits))
+            .await;
+        assert_eq!((status, &body["status"]), (StatusCode::OK, &json!("waiting_for_opponent")));
+        let (status, body) = t
+            .post(&t.b, "verdict", verdict(&t, Player::B, GameResult::AWins, &commits))
+            .await;
+        assert_eq!((status, &body["status"]), (StatusCode::OK, &json!("game_complete")));
+
+        let result = t.get("result").await;
+        assert_eq!(result["payload"]["result"], "AWins");
+        assert!(result["payload"]["preimage_for_a"].is_array());
+        // Nothing about the actions was ever sent
+        assert!(result["payload"]["game_data"].is_null());
+
+        // A verdict for commitments the oracle doesn't hold is refused, as
+        // is one on a public game
+        let t = table(DEFAULT_STEP_TIMEOUT, true);
+        let (status, _) = t
+            .post(&t.a, "verdict", verdict(&t, Player::A, GameResult::AWins, &commits))
+            .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let (t, _) = private_table().await;
+        let (status, _) = t
+            .post(&t.a, "verdict", verdict(&t, Player::A, GameResult::AWins, &commits))
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_disagreeing_verdicts_fall_back_to_reveals() {
+        let (t, commits) = private_table().await;
+        t.post(&t.a, "verdict", verdict(&t, Player::A, GameResult::AWins, &commits))
+            .await;
+        let (status, body) = t
+            .post(&t.b, "verdict", verdict(&t, Player::B, GameResult::BWins, &commits))
+            .await;
+        assert_eq!((status, &body["status"]), (StatusCode::OK, &json!("reveals_requested")));
+        assert_eq!(t.get("status").await["reveals_requested"], true);
+        assert_eq!(t.get("result").await["payload"]["status"], "reveals_requested");
+
+        for (player, key, action, (salt, commitment)) in [
+            (Player::A, &t.a, RpsAction::Rock, &commits[0]),
+            (Player::B, &t.b, RpsAction::Scissors, &commits[1]),
+        ] {
+            let reveal = json!({
+                "player": player,
+                "action": GameAction::Rps(action),
+                "salt": salt,
+                "commit_a": commitment,
+                "commit_b": commitment,
+            });
+            let (status, _) = t.post(key, "reveal", reveal).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+        let result = t.get("result").await;
+        assert_eq!(result["payload"]["result"], "AWins");
+        assert!(result["payload"]["game_data"].is_object());
+    }
+
+    #[tokio::test]
+    async fn test_commitment_is_bound_to_the_game() {
+        let t = table(DEFAULT_STEP_TIMEOUT, true);
+        let rock = GameAction::Rps(RpsAction::Rock);
+        let salt = Salt::random();
+
+        let (_, mut commit) = t.commit(Player::A, &rock, &salt).await;
+        commit["amount_shannons"] = json!(2000);
+        assert_eq!(t.post(&t.a, "commit", commit).await.0, StatusCode::BAD_REQUEST);
+
+        // A commitment and reveal lifted from another game at this stake
+        let context = {
+            let games = t.state.games.read().await;
+            games[&t.game_id].commit_context(GameId::new(), Player::A).unwrap()
+        };
+        let replayed = Commitment::bound(&rock.to_bytes(), &context, &salt);
+        let (_, mut commit) = t.commit(Player::A, &rock, &salt).await;
+        commit["commitment"] = json!(replayed);
+        assert_eq!(t.post(&t.a, "commit", commit).await.0, StatusCode::OK);
+        let reveal = json!({
+            "player": Player::A,
+            "action": rock,
+            "salt": salt,
+            "commit_a": replayed,
+            "commit_b": replayed,
+        });
+        let (status, body) = t.post(&t.a, "reveal", reveal).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "Reveal does not match commitment");
+
+        // A's commitment names B's hash, which B can no longer swap
+        let preimage = Preimage::random();
+        let swap = json!({
+            "player": Player::B,
+            "payment_hash": preimage.payment_hash(),
+            "preimage": preimage,
+        });
+        assert_eq!(t.post(&t.b, "payment-hash", swap).await.0, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_payment_hash_is_checked_and_kept() {
+        let t = table(DEFAULT_STEP_TIMEOUT, true);
+        let preimage = Preimage::random();
+        let submit = |preimage: &Preimage, payment_hash| {
+            json!({ "player": Player::B, "payment_hash": payment_hash, "preimage": preimage })
+        };
+
+        // A preimage that doesn't unlock the hash would leave the winner
+        // unable to settle
+        let other = Preimage::random().payment_hash();
+        let (status, _) = t.post(&t.b, "payment-hash", submit(&preimage, other)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // Once A holds B's payment under the hash B joined with, only that
+        // one is taken
+        let (held, hash) = {
+            let games = t.state.games.read().await;
+            let game = &games[&t.game_id];
+            (game.preimage_b.clone().unwrap(), game.payment_hash_b.unwrap())
+        };
+        let funded = json!({ "player": Player::A, "payment_hash": hash });
+        assert_eq!(t.post(&t.a, "funded", funded).await.0, StatusCode::OK);
+        let (status, body) = t
+            .post(&t.b, "payment-hash", submit(&preimage, preimage.payment_hash()))
+            .await;
+        assert_eq!((status, &body["code"]), (StatusCode::CONFLICT, &json!("conflict")));
+        let (status, _) = t.post(&t.b, "payment-hash", submit(&held, hash)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        t.play(Player::A).await;
+        t.play(Player::B).await;
+        let trace: ProtocolTrace = serde_json::from_value(t.get("trace").await).unwrap();
+        assert!(trace.entries.iter().all(|e| e.kind != MessageKind::PaymentHash));
+    }
+
+    #[tokio::test]
+    async fn test_trace_verifies_offline() {
+        let t = table(DEFAULT_STEP_TIMEOUT, true);
+        t.play(Player::A).await;
+        // A's reveal would tell B what to play
+        assert_eq!(t.get("trace").await["code"], "invalid_state");
+        t.play(Player::B).await;
+        assert_eq!(t.get("result").await["payload"]["result"], "Draw");
+
+        let trace: ProtocolTrace = serde_json::from_value(t.get("trace").await).unwrap();
+        let kinds: Vec<_> = trace.entries.iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                MessageKind::Commit,
+                MessageKind::Reveal,
+                MessageKind::Commit,
+                MessageKind::Reveal,
+                MessageKind::Result,
+            ]
+        );
+        let report = fiber_game_core::protocol::verify_trace(&trace, &t.state.public_key()).unwrap();
+        assert_eq!(report.reveals, 2);
+        assert_eq!(report.judged, Some(GameResult::Draw));
+    }
+
+    #[tokio::test]
+    async fn test_registered_games_are_judged() {
+        let t = table(DEFAULT_STEP_TIMEOUT, true);
+        t.state.games.write().await.get_mut(&t.game_id).unwrap().game_type = GameType::Nim;
+
+        // Takes of 1 and 3 leave A facing four stones
+        let moves = [
+            (Player::A, &t.a, GameAction::Nim(1)),
+            (Player::B, &t.b, GameAction::Nim(3)),
+        ];
+        let salts = [Salt::random(), Salt::random()];
+        let mut commits = Vec::new();
+        for ((player, key, action), salt) in moves.iter().zip(&salts) {
+            let (commitment, commit) = t.commit(*player, action, salt).await;
+            let (status, _) = t.post(key, "commit", commit).await;
+            assert_eq!(status, StatusCode::OK);
+            commits.push(commitment);
+        }
+        let reveal = |player: Player, action: &GameAction, salt: &Salt| {
+            json!({
+                "player": player,
+                "action": action,
+                "salt": salt,
+                "commit_a": commits[0],
+                "commit_b": commits[1],
+            })
+        };
+
+        // A move from another game is refused before it is checked against
+        // the commitment
+        let rock = reveal(Player::A, &GameAction::Rps(RpsAction::Rock), &salts[0]);
+        let (status, _) = t.post(&t.a, "reveal", rock).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        for ((player, key, action), salt) in moves.iter().zip(&salts) {
+            let (status, _) = t.post(key, "reveal", reveal(*player, action, salt)).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+        assert_eq!(t.get("result").await["payload"]["result"], "BWins");
+
+        let trace: ProtocolTrace = serde_json::from_value(t.get("trace").await).unwrap();
+        let report = fiber_game_core::protocol::verify_trace(&trace, &t.state.public_key()).unwrap();
+        assert_eq!(report.judged, Some(GameResult::BWins));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_joins_seat_one_player() {
+        let t = Arc::new(table(DEFAULT_STEP_TIMEOUT, false));
+        let joins: Vec<_> = (0..32)
+            .map(|_| {
+                let t = t.clone();
+                tokio::spawn(async move {
+                    let key = Keypair::random();
+                    let join = seal_for(t.game_id, json!({ "player_b_id": Uuid::new_v4() }), &key.secret);
+                    (t.send(t.game_id, "join", &join).await.0, key.public)
+                })
+            })
+            .collect();
+        let mut seated = Vec::new();
+        for join in joins {
+            let (status, key) = join.await.unwrap();
+            if status == StatusCode::OK {
+                seated.push(key);
+            }
+        }
+
+        assert_eq!(seated.len(), 1, "{} players joined", seated.len());
+        let games = t.state.games.read().await;
+        let game = &games[&t.game_id];
+        assert_eq!(game.player_b_key, Some(seated[0]));
+        let joined = game
+            .timeline
+            .iter()
+            .filter(|e| e.step == ProtocolStep::GameJoined)
+            .count();
+        assert_eq!(joined, 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_reveals_complete_once() {
+        for _ in 0..16 {
+            let t = Arc::new(table(DEFAULT_STEP_TIMEOUT, true));
+            let action = GameAction::Rps(RpsAction::Rock);
+            let mut reveals = Vec::new();
+            for player in [Player::A, Player::B] {
+                let key = match player {
+                    Player::A => t.a,
+                    Player::B => t.b,
+                };
+                let salt = Salt::random();
+                let (commitment, commit) = t.commit(player, &action, &salt).await;
+                let reveal = json!({
+                    "player": player,
+                    "action": action,
+                    "salt": salt,
+                    "commit_a": commitment,
+                    "commit_b": commitment,
+                });
+                reveals.push((key, commit, reveal));
+            }
+            for (key, commit, _) in &reveals {
+                assert_eq!(t.post(key, "commit", commit.clone()).await.0, StatusCode::OK);
+            }
+
+            let sent: Vec<_> = reveals
+                .into_iter()
+                .map(|(key, _, reveal)| {
+                    let t = t.clone();
+                    tokio::spawn(async move { t.post(&key, "reveal", reveal).await })
+                })
+                .collect();
+            let mut completions = 0;
+            for reveal in sent {
+                let (status, body) = reveal.await.unwrap();
+                assert_eq!(status, StatusCode::OK, "{}", body);
+                if body["status"] == "game_complete" {
+                    completions += 1;
+                }
+            }
+
+            assert_eq!(completions, 1);
+            assert_eq!(t.get("result").await["payload"]["result"], "Draw");
+            let trace: ProtocolTrace = serde_json::from_value(t.get("trace").await).unwrap();
+            let results = trace
+                .entries
+                .iter()
+                .filter(|e| e.kind == MessageKind::Result)
+                .count();
+            assert_eq!(results, 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_available_games_are_paged_oldest_first() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let mut state = OracleState::new().with_clock(TestClock::at(start).shared());
+        let mut waiting = Vec::new();
+        for age in [3u64, 1, 2] {
+            let game_id = GameId::new();
+            let mut game = GameState::new(
+                GameType::RockPaperScissors,
+                1000,
+                Uuid::new_v4(),
+                None,
+                start - Duration::from_secs(age),
+            );
+            game.player_a_key = Some(Keypair::random().public);
+            state.games.get_mut().insert(game_id, game);
+            waiting.push((age, game_id));
+        }
+        waiting.sort_by_key(|(age, _)| std::cmp::Reverse(*age));
+        // The seated game is in progress and never listed
+        let t = table_on(state, true);
+
+        let mut listed = Vec::new();
+        let mut query = "limit=2".to_string();
+        loop {
+            let resp = t
+                .router
+                .clone()
+                .oneshot(
+                    Request::get(format!("/games/available?{}", query))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+            let page: AvailableGamesResponse = serde_json::from_slice(&bytes).unwrap();
+            assert!(page.items.len() <= 2);
+            listed.extend(page.items.iter().map(|g| g.game_id));
+            match page.next_cursor {
+                Some(cursor) => query = format!("limit=2&cursor={}", cursor),
+                None => break,
+            }
+        }
+        let expected: Vec<GameId> = waiting.iter().map(|(_, id)| *id).collect();
+        assert_eq!(listed, expected);
+
+        let resp = t
+            .router
+            .clone()
+            .oneshot(
+                Request::get("/games/available?limit=0")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
@@ -0,0 +1,175 @@
+//! The oracle's signing keys and their rotation.
+//!
+//! A game is signed, from its first response to its attestation, with the
+//! key that was active when it was created. An operator announces the next
+//! key ahead of time ([`OracleState::announce_key`], `POST /admin/keys`);
+//! from its activation new games are signed with it, while the keys before
+//! it stay on hand for the games created under them. All of them are listed
+//! at `/oracle/keys` and in the [`ORACLE_KEY_HEADER`] of every response.
+//!
+//! [`OracleState::announce_key`]: crate::OracleState::announce_key
+//! [`ORACLE_KEY_HEADER`]: fiber_game_api::oracle::ORACLE_KEY_HEADER
+
+use fiber_game_api::oracle::{KeyStatus, OracleKey};
+use secp256k1::{PublicKey, SecretKey, SECP256K1};
+use sha2::{Digest, Sha256};
+use std::sync::RwLock;
+
+/// One of the oracle's signing keys
+#[derive(Clone, Copy)]
+pub(crate) struct SigningKey {
+    pub(crate) secret: SecretKey,
+    pub(crate) public: PublicKey,
+    /// When new games start being signed with it, in milliseconds since the
+    /// epoch
+    pub(crate) activates_at_ms: u64,
+}
+
+impl SigningKey {
+    pub(crate) fn new(secret: SecretKey, activates_at_ms: u64) -> Self {
+        Self {
+            secret,
+            public: PublicKey::from_secret_key(SECP256K1, &secret),
+            activates_at_ms,
+        }
+    }
+
+    /// The first 8 bytes of the SHA-256 of the compressed public key, in hex
+    pub(crate) fn fingerprint(&self) -> String {
+        let digest = Sha256::digest(self.public.serialize());
+        hex::encode(&digest[..8])
+    }
+
+    /// The key as `/oracle/keys` lists it
+    pub(crate) fn describe(&self, status: KeyStatus) -> OracleKey {
+        OracleKey {
+            pubkey: hex::encode(self.public.serialize()),
+            fingerprint: self.fingerprint(),
+            activates_at_ms: self.activates_at_ms,
+            status,
+        }
+    }
+}
+
+/// Every key the oracle signed or will sign games with, in order of
+/// activation
+pub(crate) struct SigningKeys(RwLock<Vec<SigningKey>>);
+
+impl SigningKeys {
+    /// Just `first`, which has signed games since the oracle's first run
+    pub(crate) fn new(first: SecretKey) -> Self {
+        Self(RwLock::new(vec![SigningKey::new(first, 0)]))
+    }
+
+    /// Add a key restored from storage
+    pub(crate) fn restore(&self, key: SigningKey) {
+        let mut keys = self.0.write().unwrap();
+        keys.push(key);
+        keys.sort_by_key(|k| k.activates_at_ms);
+    }
+
+    /// Add `key` unless another announced key isn't active yet at `now_ms`.
+    /// `save` runs first, under the lock, so a key only signs once stored.
+    pub(crate) fn announce<E>(
+        &self,
+        key: SigningKey,
+        now_ms: u64,
+        save: impl FnOnce(&SigningKey) -> Result<(), E>,
+    ) -> Result<bool, E> {
+        let mut keys = self.0.write().unwrap();
+        if keys.iter().any(|k| k.activates_at_ms > now_ms) {
+            return Ok(false);
+        }
+        save(&key)?;
+        keys.push(key);
+        keys.sort_by_key(|k| k.activates_at_ms);
+        Ok(true)
+    }
+
+    /// The key new games are signed with at `now_ms`
+    pub(crate) fn active(&self, now_ms: u64) -> SigningKey {
+        let keys = self.0.read().unwrap();
+        *keys
+            .iter()
+            .rev()
+            .find(|k| k.activates_at_ms <= now_ms)
+            .unwrap_or(&keys[0])
+    }
+
+    /// The first key, which signed the games from before keys rotated
+    pub(crate) fn first(&self) -> SigningKey {
+        self.0.read().unwrap()[0]
+    }
+
+    /// Our key with public half `public`, if we have it
+    pub(crate) fn get(&self, public: &PublicKey) -> Option<SigningKey> {
+        self.0.read().unwrap().iter().find(|k| k.public == *public).copied()
+    }
+
+    /// Every key and where it stands at `now_ms`, oldest first
+    pub(crate) fn history(&self, now_ms: u64) -> Vec<OracleKey> {
+        let active = self.active(now_ms).public;
+        let keys = self.0.read().unwrap();
+        let mut status = KeyStatus::Retired;
+        keys.iter()
+            .map(|k| {
+                if k.public == active {
+                    status = KeyStatus::Active;
+                } else if status == KeyStatus::Active {
+                    status = KeyStatus::Next;
+                }
+                k.describe(status)
+            })
+            .collect()
+    }
+
+    /// Every public key in hex, comma separated, the active one at `now_ms`
+    /// first: the [`ORACLE_KEY_HEADER`](fiber_game_api::oracle::ORACLE_KEY_HEADER)
+    pub(crate) fn header(&self, now_ms: u64) -> String {
+        let active = self.active(now_ms).public;
+        let keys = self.0.read().unwrap();
+        std::iter::once(&active)
+            .chain(keys.iter().rev().map(|k| &k.public).filter(|k| **k != active))
+            .map(|k| hex::encode(k.serialize()))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_key(activates_at_ms: u64) -> SigningKey {
+        SigningKey::new(SecretKey::new(&mut rand::thread_rng()), activates_at_ms)
+    }
+
+    #[test]
+    fn test_announced_key_activates_on_time() {
+        let keys = SigningKeys::new(SecretKey::new(&mut rand::thread_rng()));
+        let first = keys.first();
+        let next = random_key(1_000);
+        assert_eq!(keys.announce(next, 0, |_| Ok::<_, ()>(())), Ok(true));
+        // One announced key at a time
+        assert_eq!(keys.announce(random_key(2_000), 0, |_| Ok::<_, ()>(())), Ok(false));
+
+        let statuses = |now_ms| keys.history(now_ms).iter().map(|k| k.status).collect::<Vec<_>>();
+        assert_eq!(keys.active(999).public, first.public);
+        assert_eq!(statuses(999), [KeyStatus::Active, KeyStatus::Next]);
+        assert_eq!(keys.active(1_000).public, next.public);
+        assert_eq!(statuses(1_000), [KeyStatus::Retired, KeyStatus::Active]);
+
+        // Both stay on hand, the active one named first
+        assert!(keys.get(&first.public).is_some());
+        let header = keys.header(1_000);
+        assert!(header.starts_with(&hex::encode(next.public.serialize())));
+        assert!(header.ends_with(&hex::encode(first.public.serialize())));
+    }
+
+    #[test]
+    fn test_key_not_saved_is_not_announced() {
+        let keys = SigningKeys::new(SecretKey::new(&mut rand::thread_rng()));
+        assert_eq!(keys.announce(random_key(1_000), 0, |_| Err("disk full")), Err("disk full"));
+        assert_eq!(keys.history(0).len(), 1);
+    }
+}
@@ -0,0 +1,253 @@
+//! Fiber Game Oracle Service
+//!
+//! HTTP service that manages game sessions, collects reveals, and signs results.
+//! The Oracle stores payment hashes, preimages, and invoice strings for
+//! frontend-driven Fiber payment flows. It makes zero Fiber RPC calls.
+//!
+//! The oracle can optionally persist its keys and games through an
+//! [`storage::OracleStore`], which the combined demo also uses. With the
+//! `grpc` feature (on by default) it also answers gRPC on the same port.
+//! Judged results can be published elsewhere too, see [`publish`].
+
+mod admin;
+mod explorer;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+mod handlers;
+mod keys;
+pub mod lock;
+pub mod publish;
+pub mod state;
+pub mod storage;
+mod wire;
+
+use axum::Router;
+use clap::ArgAction;
+use fiber_auth::webhook::WebhookSigner;
+use fiber_config::ServiceConfig;
+use fiber_game_core::crypto::Keyring;
+use fiber_game_core::protocol::ProtocolRecorder;
+use fiber_service::ServerArgs;
+use publish::{FilePublisher, NostrPublisher, ResultPublisher, WebhookPublisher};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use storage::SqliteOracleStore;
+use tower_http::cors::CorsLayer;
+use tracing::info;
+
+pub use handlers::api_router;
+pub use lock::LockStats;
+pub use state::{
+    OracleState, DEFAULT_EXPLORER_RATE_LIMIT, DEFAULT_OFFER_TTL, DEFAULT_STEP_TIMEOUT,
+};
+
+/// Standalone oracle service router.
+pub fn create_router(state: Arc<OracleState>) -> Router {
+    let metrics = state.metrics().clone();
+    #[cfg(feature = "grpc")]
+    let app = api_router(state.clone()).merge(grpc::router(state));
+    #[cfg(not(feature = "grpc"))]
+    let app = api_router(state);
+    let app = fiber_service::with_metrics(app, metrics);
+    fiber_service::request_tracing(app).layer(CorsLayer::permissive())
+}
+
+/// Oracle service configuration, the `oracle` section of a config file
+#[derive(clap::Args, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Config {
+    #[command(flatten)]
+    #[serde(flatten)]
+    pub server: ServerArgs,
+    /// SQLite file to persist the oracle key and games to (in-memory if unset)
+    #[arg(long, env = "ORACLE_DB_PATH")]
+    pub db_path: Option<PathBuf>,
+    /// Bring the database schema up to date at startup; when off, the
+    /// oracle refuses to start on a database with migrations not applied
+    #[arg(long, env = "ORACLE_AUTO_MIGRATE", default_value_t = true, action = ArgAction::Set)]
+    pub auto_migrate: bool,
+    /// Hex storage keys to encrypt the oracle key and games in the database with, current
+    /// first; older keys after it are only read, and rewritten under the
+    /// current one at startup (stored in plaintext if neither this nor
+    /// `storage_key_file` is set)
+    #[arg(long, env = "ORACLE_STORAGE_KEY")]
+    pub storage_key: Option<String>,
+    /// File holding the storage keys, one per line, current first
+    #[arg(long, env = "ORACLE_STORAGE_KEY_FILE")]
+    pub storage_key_file: Option<PathBuf>,
+    /// Seconds a game may sit idle before a player can claim their opponent
+    /// timed out (default 300)
+    #[arg(long, env = "ORACLE_STEP_TIMEOUT_SECS")]
+    pub step_timeout_secs: Option<u64>,
+    /// Seconds a game is offered to second players before it is no longer
+    /// listed or joinable (default 3600)
+    #[arg(long, env = "ORACLE_OFFER_TTL_SECS")]
+    pub offer_ttl_secs: Option<u64>,
+    /// Take commitments only once both players confirmed their opponent's
+    /// payment is held on their node
+    #[arg(long, env = "ORACLE_REQUIRE_FUNDING", default_value_t = false, action = ArgAction::Set)]
+    pub require_funding: bool,
+    /// Requests per minute each client may make to the public game explorer
+    /// under `/explorer` (default 60)
+    #[arg(long, env = "ORACLE_EXPLORER_RATE_LIMIT")]
+    pub explorer_rate_limit: Option<u32>,
+    /// Directory to write a protocol trace file per game to (traces are
+    /// only kept in memory if unset)
+    #[arg(long, env = "ORACLE_TRACE_DIR")]
+    pub trace_dir: Option<PathBuf>,
+    /// Token operators (`fiberctl`) send to list and force-cancel games under
+    /// `/admin`; the operator API is off if unset
+    #[arg(long, env = "ORACLE_ADMIN_TOKEN")]
+    pub admin_token: Option<String>,
+    /// File to append every signed result to, one JSON line each
+    #[arg(long, env = "ORACLE_PUBLISH_FILE")]
+    pub publish_file: Option<PathBuf>,
+    /// URLs to POST every signed result to
+    #[arg(long = "publish-webhook", env = "ORACLE_PUBLISH_WEBHOOKS", value_delimiter = ',')]
+    #[serde(default)]
+    pub publish_webhooks: Vec<String>,
+    /// Key published webhooks are signed with, `hmac:<secret>` or
+    /// `ed25519:<seed hex>`; without one they are sent unsigned
+    #[arg(long, env = "ORACLE_WEBHOOK_SIGNING_KEY")]
+    pub webhook_signing_key: Option<String>,
+    /// Nostr relays (`wss://...`) to post every signed result to as a note
+    /// from the oracle key
+    #[arg(long = "publish-nostr-relay", env = "ORACLE_NOSTR_RELAYS", value_delimiter = ',')]
+    #[serde(default)]
+    pub nostr_relays: Vec<String>,
+}
+
+impl Config {
+    /// The storage keys configured, if any
+    fn keyring(&self) -> Result<Option<Keyring>, String> {
+        Keyring::load(self.storage_key.as_deref(), self.storage_key_file.as_deref())
+            .map_err(|e| format!("storage_key: {}", e))
+    }
+
+    /// The key webhooks are signed with, if any
+    fn webhook_signer(&self) -> Result<Option<WebhookSigner>, String> {
+        self.webhook_signing_key
+            .as_deref()
+            .map(str::parse)
+            .transpose()
+            .map_err(|e| format!("webhook_signing_key: {}", e))
+    }
+
+    /// The result publishers configured, signing Nostr notes with `state`'s
+    /// active key
+    fn publishers(&self, state: &OracleState) -> Result<Vec<Arc<dyn ResultPublisher>>, String> {
+        let mut publishers: Vec<Arc<dyn ResultPublisher>> = Vec::new();
+        if let Some(path) = &self.publish_file {
+            publishers.push(Arc::new(FilePublisher::new(path)));
+        }
+        let signer = self.webhook_signer()?;
+        if let Some(key) = signer.as_ref().and_then(WebhookSigner::public_key) {
+            info!("Signing result webhooks, verify with {}", key);
+        }
+        for url in &self.publish_webhooks {
+            publishers.push(Arc::new(WebhookPublisher::new(url).with_signer(signer.clone())));
+        }
+        for relay in &self.nostr_relays {
+            publishers.push(Arc::new(NostrPublisher::new(relay, &state.active_key().secret)));
+        }
+        Ok(publishers)
+    }
+}
+
+impl ServiceConfig for Config {
+    const SECTION: &'static str = "oracle";
+
+    fn validate(&self) -> Result<(), String> {
+        if self.step_timeout_secs == Some(0) {
+            return Err("step_timeout_secs must be at least 1".to_string());
+        }
+        if self.offer_ttl_secs == Some(0) {
+            return Err("offer_ttl_secs must be at least 1".to_string());
+        }
+        if self.explorer_rate_limit == Some(0) {
+            return Err("explorer_rate_limit must be at least 1".to_string());
+        }
+        self.keyring()?;
+        self.webhook_signer()?;
+        if let Some(url) = self.publish_webhooks.iter().find(|url| !url.starts_with("http")) {
+            return Err(format!("publish webhook {} must be an http(s) URL", url));
+        }
+        if let Some(relay) = self.nostr_relays.iter().find(|relay| !relay.starts_with("ws")) {
+            return Err(format!("Nostr relay {} must be a ws(s) URL", relay));
+        }
+        Ok(())
+    }
+}
+
+/// Run the standalone oracle service until the process exits.
+pub async fn run(config: Config) -> std::io::Result<()> {
+    let port = config.server.port_or(3000);
+
+    let state = match &config.db_path {
+        Some(path) => {
+            let store = if config.auto_migrate {
+                SqliteOracleStore::open(path)
+            } else {
+                SqliteOracleStore::open_without_migrating(path)
+            }
+            .expect("failed to open oracle database");
+            let store = match config.keyring() {
+                Ok(Some(keyring)) => {
+                    info!("Encrypting stored secrets with storage key {}", keyring.current_id());
+                    store.with_keyring(keyring).expect("failed to encrypt oracle database")
+                }
+                Ok(None) => store,
+                Err(e) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, e)),
+            };
+            info!("Persisting oracle state to {}", path.display());
+            OracleState::open(Arc::new(store)).expect("failed to restore oracle state")
+        }
+        None => OracleState::new(),
+    };
+    let state = match config.step_timeout_secs {
+        Some(secs) => state.with_step_timeout(Duration::from_secs(secs)),
+        None => state,
+    };
+    let state = match config.offer_ttl_secs {
+        Some(secs) => state.with_offer_ttl(Duration::from_secs(secs)),
+        None => state,
+    };
+    if config.require_funding {
+        info!("Commitments wait for both stakes to be confirmed held");
+    }
+    let state = state.with_require_funding(config.require_funding);
+    let state = match config.explorer_rate_limit {
+        Some(per_minute) => state.with_explorer_rate_limit(per_minute),
+        None => state,
+    };
+    let state = match &config.trace_dir {
+        Some(dir) => {
+            let recorder = ProtocolRecorder::with_dir(dir).expect("failed to open trace directory");
+            info!("Writing protocol traces to {}", dir.display());
+            state.with_recorder(recorder)
+        }
+        None => state,
+    };
+    if config.admin_token.is_some() {
+        info!("Operator API enabled under /admin");
+    }
+    let publishers = config
+        .publishers(&state)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let state = Arc::new(state.with_admin_token(config.admin_token));
+    for publisher in &publishers {
+        info!("Publishing results to {}", publisher.name());
+    }
+    publish::follow(&state, publishers);
+
+    info!(
+        "Oracle public key: {}",
+        hex::encode(state.public_key().serialize())
+    );
+
+    info!("Oracle service listening on http://0.0.0.0:{}", port);
+    info!("  All Fiber RPC calls are made by player frontends directly");
+
+    fiber_service::serve(create_router(state), port).await
+}
@@ -0,0 +1,151 @@
+//! A read-write lock that counts how often callers had to wait for it.
+//!
+//! The oracle keeps every game behind one lock; [`MeteredRwLock`] records
+//! acquisitions and time spent waiting so load runs can show how much that
+//! costs. An uncontended acquire costs one extra `try_` call.
+//!
+//! The lock is Tokio's: a handler waiting for it yields its worker thread to
+//! other requests instead of blocking it.
+
+use serde::Serialize;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// A [`RwLock`] with contention counters
+#[derive(Default)]
+pub struct MeteredRwLock<T> {
+    inner: RwLock<T>,
+    acquisitions: AtomicU64,
+    contended: AtomicU64,
+    wait_nanos: AtomicU64,
+}
+
+/// Counters of a [`MeteredRwLock`] since it was created
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct LockStats {
+    /// Read and write acquisitions
+    pub acquisitions: u64,
+    /// Acquisitions that found the lock taken and had to wait
+    pub contended: u64,
+    /// Total time spent waiting, in nanoseconds
+    pub wait_nanos: u64,
+}
+
+impl LockStats {
+    /// Share of acquisitions that had to wait, from 0 to 1
+    pub fn contended_ratio(&self) -> f64 {
+        if self.acquisitions == 0 {
+            return 0.0;
+        }
+        self.contended as f64 / self.acquisitions as f64
+    }
+
+    /// Total time spent waiting
+    pub fn wait(&self) -> Duration {
+        Duration::from_nanos(self.wait_nanos)
+    }
+
+    /// Counters accumulated since `earlier` was taken
+    pub fn since(&self, earlier: &LockStats) -> LockStats {
+        LockStats {
+            acquisitions: self.acquisitions - earlier.acquisitions,
+            contended: self.contended - earlier.contended,
+            wait_nanos: self.wait_nanos - earlier.wait_nanos,
+        }
+    }
+}
+
+impl<T> MeteredRwLock<T> {
+    /// Wrap `value` in a lock with zeroed counters
+    pub fn new(value: T) -> Self {
+        Self {
+            inner: RwLock::new(value),
+            acquisitions: AtomicU64::new(0),
+            contended: AtomicU64::new(0),
+            wait_nanos: AtomicU64::new(0),
+        }
+    }
+
+    /// Acquire shared access, waiting while a writer holds the lock
+    pub async fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.acquisitions.fetch_add(1, Ordering::Relaxed);
+        match self.inner.try_read() {
+            Ok(guard) => guard,
+            Err(_) => self.waited(self.inner.read()).await,
+        }
+    }
+
+    /// Acquire exclusive access, waiting while anyone holds the lock
+    pub async fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.acquisitions.fetch_add(1, Ordering::Relaxed);
+        match self.inner.try_write() {
+            Ok(guard) => guard,
+            Err(_) => self.waited(self.inner.write()).await,
+        }
+    }
+
+    /// Current counters
+    pub fn stats(&self) -> LockStats {
+        LockStats {
+            acquisitions: self.acquisitions.load(Ordering::Relaxed),
+            contended: self.contended.load(Ordering::Relaxed),
+            wait_nanos: self.wait_nanos.load(Ordering::Relaxed),
+        }
+    }
+
+    /// The value, when nothing else can be holding the lock
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut()
+    }
+
+    async fn waited<G>(&self, acquire: impl Future<Output = G>) -> G {
+        let start = Instant::now();
+        let guard = acquire.await;
+        self.contended.fetch_add(1, Ordering::Relaxed);
+        self.wait_nanos
+            .fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        guard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_uncontended_acquisitions_do_not_wait() {
+        let lock = MeteredRwLock::new(0);
+        *lock.write().await += 1;
+        assert_eq!(*lock.read().await, 1);
+        let stats = lock.stats();
+        assert_eq!(stats.acquisitions, 2);
+        assert_eq!(stats.contended, 0);
+        assert_eq!(stats.contended_ratio(), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_blocked_writer_is_counted() {
+        let lock = Arc::new(MeteredRwLock::new(0));
+        let guard = lock.read().await;
+        let writer = {
+            let lock = lock.clone();
+            tokio::spawn(async move { *lock.write().await += 1 })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(guard);
+        writer.await.unwrap();
+
+        let stats = lock.stats();
+        assert_eq!(stats.acquisitions, 2);
+        assert_eq!(stats.contended, 1);
+        assert!(
+            stats.wait() >= Duration::from_millis(40),
+            "{:?}",
+            stats.wait()
+        );
+        assert_eq!(stats.since(&stats), LockStats::default());
+    }
+}
@@ -0,0 +1,472 @@
+//! Publishing signed results outside the oracle.
+//!
+//! Once a game is judged the oracle seals a [`ResultAttestation`] with its
+//! key and hands it to every configured [`ResultPublisher`], so outcomes are
+//! timestamped somewhere the oracle does not control and can be checked
+//! without trusting its own API. [`follow`] drives the publishers from the
+//! oracle's [`EventBus`](fiber_service::EventBus); handlers only publish
+//! [`Event::GameCompleted`] as before.
+//!
+//! Three publishers ship with the oracle: [`FilePublisher`] appends JSON
+//! lines to a file, [`WebhookPublisher`] POSTs to a URL, and
+//! [`NostrPublisher`] posts a note signed with the oracle key to a Nostr
+//! relay. A failed publication is logged, not retried. Given a
+//! [`WebhookSigner`], the webhook POSTs also carry a signature over the
+//! request itself, for receivers that check it with
+//! [`fiber_auth::webhook::WebhookVerifier`] rather than the attestation.
+
+use crate::state::OracleState;
+use async_trait::async_trait;
+use fiber_auth::webhook::{WebhookSigner, SIGNATURE_HEADER};
+use fiber_game_core::games::GameType;
+use fiber_game_core::protocol::{Envelope, EnvelopeError, GameId, GameResult, ProtocolStep};
+use fiber_service::Event;
+use futures_util::{SinkExt, StreamExt};
+use secp256k1::{Keypair, Message, SecretKey, SECP256K1};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_tungstenite::tungstenite::Message as Frame;
+use tracing::{debug, warn};
+
+/// How long a webhook or relay gets to accept a result
+const PUBLISH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Nostr event kind results are posted as (a plain text note)
+const NOSTR_KIND: u16 = 1;
+
+/// Hashtag every result note carries, for relays to be queried by
+const NOSTR_TAG: &str = "fiber-game";
+
+/// What the oracle publishes about a judged game
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResultAttestation {
+    pub game_id: GameId,
+    pub game_type: GameType,
+    pub result: GameResult,
+    /// The oracle's result signature, hex, as served by `/game/:id/result`
+    pub signature: String,
+    /// Milliseconds since the Unix epoch at which the game was judged
+    pub judged_at_ms: u64,
+}
+
+/// A result attestation signed with the oracle key
+pub type SignedAttestation = Envelope<ResultAttestation>;
+
+/// Publication error
+#[derive(Debug, thiserror::Error)]
+pub enum PublishError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("http error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("relay error: {0}")]
+    Relay(String),
+}
+
+/// Somewhere judged results are made public
+#[async_trait]
+pub trait ResultPublisher: Send + Sync {
+    /// Short description for logs, e.g. the file or URL published to
+    fn name(&self) -> String;
+
+    async fn publish(&self, attestation: &SignedAttestation) -> Result<(), PublishError>;
+}
+
+/// Appends each attestation as one JSON line to a file
+pub struct FilePublisher {
+    path: PathBuf,
+}
+
+impl FilePublisher {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl ResultPublisher for FilePublisher {
+    fn name(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    async fn publish(&self, attestation: &SignedAttestation) -> Result<(), PublishError> {
+        let mut line = serde_json::to_vec(attestation)?;
+        line.push(b'\n');
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        file.write_all(&line).await?;
+        // tokio writes in the background; make sure the line is down
+        // before reporting it published
+        file.flush().await?;
+        Ok(())
+    }
+}
+
+/// POSTs each attestation as JSON to a URL
+pub struct WebhookPublisher {
+    url: String,
+    client: reqwest::Client,
+    signer: Option<WebhookSigner>,
+}
+
+impl WebhookPublisher {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            client: reqwest::Client::new(),
+            signer: None,
+        }
+    }
+
+    /// Sign each POST with `signer`, if there is one
+    pub fn with_signer(mut self, signer: Option<WebhookSigner>) -> Self {
+        self.signer = signer;
+        self
+    }
+}
+
+#[async_trait]
+impl ResultPublisher for WebhookPublisher {
+    fn name(&self) -> String {
+        self.url.clone()
+    }
+
+    async fn publish(&self, attestation: &SignedAttestation) -> Result<(), PublishError> {
+        let request = self.client.post(&self.url).timeout(PUBLISH_TIMEOUT);
+        let request = match &self.signer {
+            Some(signer) => {
+                let signed = signer.sign(attestation)?;
+                request
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .header(SIGNATURE_HEADER, signed.signature)
+                    .body(signed.body)
+            }
+            None => request.json(attestation),
+        };
+        request
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// A NIP-01 Nostr event
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NostrEvent {
+    pub id: String,
+    pub pubkey: String,
+    pub created_at: u64,
+    pub kind: u16,
+    pub tags: Vec<Vec<String>>,
+    pub content: String,
+    pub sig: String,
+}
+
+impl NostrEvent {
+    /// A note carrying `attestation` as its content, signed with `keypair`.
+    fn note(attestation: &SignedAttestation, keypair: &Keypair) -> Result<Self, PublishError> {
+        let pubkey = hex::encode(keypair.x_only_public_key().0.serialize());
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let tags = vec![
+            vec!["t".to_string(), NOSTR_TAG.to_string()],
+            vec!["game".to_string(), attestation.payload.game_id.to_string()],
+        ];
+        let content = serde_json::to_string(attestation)?;
+
+        let id: [u8; 32] = Sha256::digest(serde_json::to_vec(&(
+            0, &pubkey, created_at, NOSTR_KIND, &tags, &content,
+        ))?)
+        .into();
+        let sig = SECP256K1.sign_schnorr(&Message::from_digest(id), keypair);
+
+        Ok(Self {
+            id: hex::encode(id),
+            pubkey,
+            created_at,
+            kind: NOSTR_KIND,
+            tags,
+            content,
+            sig: hex::encode(sig.serialize()),
+        })
+    }
+}
+
+/// Posts each attestation as a note to a Nostr relay, signed with the
+/// oracle key so followers of the oracle's pubkey see its results
+pub struct NostrPublisher {
+    relay: String,
+    keypair: Keypair,
+}
+
+impl NostrPublisher {
+    pub fn new(relay: impl Into<String>, secret_key: &SecretKey) -> Self {
+        Self {
+            relay: relay.into(),
+            keypair: Keypair::from_secret_key(SECP256K1, secret_key),
+        }
+    }
+
+    /// Send `event` and wait for the relay to accept it.
+    async fn send(&self, event: &NostrEvent) -> Result<(), PublishError> {
+        let relay_error =
+            |e: tokio_tungstenite::tungstenite::Error| PublishError::Relay(e.to_string());
+        let (mut socket, _) = tokio_tungstenite::connect_async(&self.relay)
+            .await
+            .map_err(relay_error)?;
+        let request = serde_json::to_string(&("EVENT", event))?;
+        socket
+            .send(Frame::text(request))
+            .await
+            .map_err(relay_error)?;
+
+        while let Some(frame) = socket.next().await {
+            let Frame::Text(text) = frame.map_err(relay_error)? else {
+                continue;
+            };
+            // ["OK", <event id>, <accepted>, <message>]
+            let Ok((kind, id, accepted, message)) =
+                serde_json::from_str::<(String, String, bool, String)>(&text)
+            else {
+                continue;
+            };
+            if kind != "OK" || id != event.id {
+                continue;
+            }
+            let _ = socket.close(None).await;
+            return if accepted {
+                Ok(())
+            } else {
+                Err(PublishError::Relay(format!("event rejected: {}", message)))
+            };
+        }
+        Err(PublishError::Relay(
+            "connection closed before the event was accepted".to_string(),
+        ))
+    }
+}
+
+#[async_trait]
+impl ResultPublisher for NostrPublisher {
+    fn name(&self) -> String {
+        self.relay.clone()
+    }
+
+    async fn publish(&self, attestation: &SignedAttestation) -> Result<(), PublishError> {
+        let event = NostrEvent::note(attestation, &self.keypair)?;
+        tokio::time::timeout(PUBLISH_TIMEOUT, self.send(&event))
+            .await
+            .map_err(|_| PublishError::Relay("timed out waiting for the relay".to_string()))?
+    }
+}
+
+impl OracleState {
+    /// Seal the attestation for a judged game; `None` if the game is unknown
+    /// or has no signed result.
+    pub async fn attest(
+        &self,
+        game_id: &GameId,
+    ) -> Option<Result<SignedAttestation, EnvelopeError>> {
+        let games = self.games.read().await;
+        let game = games.get(game_id)?;
+        let attestation = ResultAttestation {
+            game_id: *game_id,
+            game_type: game.game_type,
+            result: game.result?,
+            signature: hex::encode(game.signature?),
+            judged_at_ms: game
+                .timeline
+                .iter()
+                .rev()
+                .find(|event| event.step == ProtocolStep::Judged)
+                .map_or(0, |event| event.at_ms),
+        };
+        let key = self.game_key(game);
+        drop(games);
+        Some(self.seal(&key, attestation))
+    }
+}
+
+/// Publish the result of every game `state` judges from now on to each of
+/// `publishers`, until the oracle is dropped.
+pub fn follow(state: &Arc<OracleState>, publishers: Vec<Arc<dyn ResultPublisher>>) {
+    if publishers.is_empty() {
+        return;
+    }
+    let mut events = state.events().subscribe();
+    let state: Weak<OracleState> = Arc::downgrade(state);
+    tokio::spawn(async move {
+        loop {
+            let game_id = match events.recv().await {
+                Ok(Event::GameCompleted { game_id, .. }) => GameId::from_uuid(game_id),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => {
+                    warn!(
+                        missed,
+                        "Result publication fell behind, results not published"
+                    );
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            let Some(state) = state.upgrade() else { break };
+            let attestation = match state.attest(&game_id).await {
+                Some(Ok(attestation)) => attestation,
+                Some(Err(e)) => {
+                    warn!(%game_id, error = %e, "Failed to seal result attestation");
+                    continue;
+                }
+                None => continue,
+            };
+            drop(state);
+
+            for publisher in &publishers {
+                match publisher.publish(&attestation).await {
+                    Ok(()) => debug!(%game_id, to = %publisher.name(), "Result published"),
+                    Err(e) => warn!(
+                        %game_id,
+                        to = %publisher.name(),
+                        error = %e,
+                        "Failed to publish result"
+                    ),
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::GameState;
+    use fiber_game_core::clock::SystemClock;
+    use std::sync::Mutex;
+    use uuid::Uuid;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<SignedAttestation>>);
+
+    #[async_trait]
+    impl ResultPublisher for Recorder {
+        fn name(&self) -> String {
+            "recorder".to_string()
+        }
+
+        async fn publish(&self, attestation: &SignedAttestation) -> Result<(), PublishError> {
+            self.0.lock().unwrap().push(attestation.clone());
+            Ok(())
+        }
+    }
+
+    async fn judged_game(state: &OracleState) -> GameId {
+        let game_id = GameId::new();
+        let mut game = GameState::new(
+            GameType::RockPaperScissors,
+            1000,
+            Uuid::new_v4(),
+            None,
+            SystemTime::now(),
+        );
+        game.complete(&game_id, GameResult::BWins, "b_wins", &SystemClock);
+        state.games.write().await.insert(game_id, game);
+        game_id
+    }
+
+    #[tokio::test]
+    async fn test_judged_results_reach_publishers() {
+        let state = Arc::new(OracleState::new());
+        let recorder = Arc::new(Recorder::default());
+        follow(&state, vec![recorder.clone()]);
+
+        let game_id = judged_game(&state).await;
+        state.publish_completed(&game_id, GameResult::BWins);
+
+        for _ in 0..50 {
+            if !recorder.0.lock().unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let published = recorder.0.lock().unwrap().clone();
+        assert_eq!(published.len(), 1);
+        let attestation = &published[0];
+        assert!(attestation.verify().is_ok());
+        assert_eq!(attestation.sender, state.public_key());
+        assert_eq!(attestation.payload.game_id, game_id);
+        assert_eq!(attestation.payload.result, GameResult::BWins);
+        assert!(attestation.payload.judged_at_ms > 0);
+    }
+
+    #[tokio::test]
+    async fn test_file_publisher_appends_lines() {
+        let state = OracleState::new();
+        let path = std::env::temp_dir().join(format!("results-{}.jsonl", Uuid::new_v4()));
+        let publisher = FilePublisher::new(&path);
+
+        for _ in 0..2 {
+            let game_id = judged_game(&state).await;
+            let attestation = state.attest(&game_id).await.unwrap().unwrap();
+            publisher.publish(&attestation).await.unwrap();
+        }
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<SignedAttestation> = written
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|a| a.verify().is_ok()));
+    }
+
+    #[tokio::test]
+    async fn test_nostr_note_is_signed_by_oracle_key() {
+        let state = OracleState::new();
+        let game_id = judged_game(&state).await;
+        let attestation = state.attest(&game_id).await.unwrap().unwrap();
+        let keypair = Keypair::from_secret_key(SECP256K1, &state.active_key().secret);
+
+        let event = NostrEvent::note(&attestation, &keypair).unwrap();
+
+        let (oracle_key, _) = state.public_key().x_only_public_key();
+        assert_eq!(event.pubkey, hex::encode(oracle_key.serialize()));
+        let id: [u8; 32] = hex::decode(&event.id).unwrap().try_into().unwrap();
+        let sig =
+            secp256k1::schnorr::Signature::from_slice(&hex::decode(&event.sig).unwrap()).unwrap();
+        assert!(SECP256K1
+            .verify_schnorr(&sig, &Message::from_digest(id), &oracle_key)
+            .is_ok());
+        let content: SignedAttestation = serde_json::from_str(&event.content).unwrap();
+        assert_eq!(content.payload.game_id, game_id);
+    }
+
+    #[tokio::test]
+    async fn test_unjudged_games_are_not_attested() {
+        let state = OracleState::new();
+        let game_id = GameId::new();
+        state.games.write().await.insert(
+            game_id,
+            GameState::new(
+                GameType::RockPaperScissors,
+                1000,
+                Uuid::new_v4(),
+                None,
+                SystemTime::now(),
+            ),
+        );
+        assert!(state.attest(&game_id).await.is_none());
+    }
+}
@@ -0,0 +1,936 @@
+//! Oracle state: the signing keys and all game sessions.
+//!
+//! Every mutation of a game goes through [`OracleState::persist`], which
+//! writes the game to the attached [`OracleStore`] (if any) so the oracle can
+//! be restored after a restart.
+
+use crate::keys::{SigningKey, SigningKeys};
+use crate::lock::{LockStats, MeteredRwLock};
+use crate::storage::{OracleStore, StorageError};
+use fiber_auth::{AdminSecret, AuthState};
+use fiber_errors::ApiError;
+use fiber_game_api::oracle::{
+    CreateGameRequest, GameEnding, KeyStatus, OracleKey, SeatSettlement, SettlementStatus,
+};
+use fiber_game_core::{
+    crypto::{CommitContext, Commitment, EncryptedPreimage, PaymentHash, Preimage, Salt},
+    games::{GameAction, GameType, OracleSecret},
+    protocol::{
+        AbortReason, Actor, Direction, DrawPolicy, Envelope, EnvelopeError, GameId, GameResult,
+        GameSnapshot, MatchRound, MessageKind, Player, ProtocolRecorder, ProtocolStep,
+        ResumptionToken, SettlementAction, TimelineEvent,
+    },
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use fiber_game_core::clock::{Clock, SharedClock, SystemClock};
+use fiber_service::{Event, EventBus, GameOutcome, Metrics};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// How long a game may sit idle before a player can claim their opponent
+/// timed out, unless set with [`OracleState::with_step_timeout`]
+pub const DEFAULT_STEP_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How long a game waits for its second player before it is no longer
+/// offered, unless set with [`OracleState::with_offer_ttl`]
+pub const DEFAULT_OFFER_TTL: Duration = Duration::from_secs(3600);
+
+/// Requests per minute each client may make to the public explorer, unless
+/// set with [`OracleState::with_explorer_rate_limit`]
+pub const DEFAULT_EXPLORER_RATE_LIMIT: u32 = 60;
+
+/// Oracle state
+pub struct OracleState {
+    /// Keys games are signed with, see [`crate::keys`]
+    pub(crate) keys: SigningKeys,
+    /// Active games
+    pub(crate) games: MeteredRwLock<HashMap<GameId, GameState>>,
+    /// Where games are persisted, if anywhere
+    store: Option<Arc<dyn OracleStore>>,
+    /// Idle time after which a timeout claim is accepted
+    pub(crate) step_timeout: Duration,
+    /// How long a game waits for its second player
+    pub(crate) offer_ttl: Duration,
+    /// Where game creation, protocol steps and idle time are timed from
+    pub(crate) clock: SharedClock,
+    /// Every signed message received or sent, per game
+    pub(crate) recorder: ProtocolRecorder,
+    /// Request and game counters served at `/metrics`
+    pub(crate) metrics: Arc<Metrics>,
+    /// Where game events are published; the metrics follow it
+    pub(crate) events: EventBus,
+    /// Bearer token for the operator API; it is not served without one
+    pub(crate) admin_token: AdminSecret,
+    /// Refuse commitments until each player confirmed the other's stake held
+    pub(crate) require_funding: bool,
+    /// Requests per minute each client may make under `/explorer`
+    pub(crate) explorer_rate_limit: u32,
+}
+
+/// State of a game session
+#[derive(Clone, Serialize, Deserialize)]
+#[allow(dead_code)]
+pub struct GameState {
+    pub(crate) game_type: GameType,
+    pub(crate) amount_shannons: u64,
+    pub(crate) status: GameStatus,
+    /// Secret half of the per-game commitment keypair
+    pub(crate) commitment_key: secp256k1::SecretKey,
+    pub(crate) commitment_point: secp256k1::PublicKey,
+    /// Key the oracle signs this game with, the one active when it was
+    /// created; games from before keys rotated have none and use the first
+    #[serde(default)]
+    pub(crate) oracle_key: Option<secp256k1::PublicKey>,
+    pub(crate) oracle_secret: Option<OracleSecret>,
+    pub(crate) oracle_commitment: Option<[u8; 32]>,
+    pub(crate) player_a_id: Uuid,
+    pub(crate) player_b_id: Option<Uuid>,
+    /// Key player A signs their messages with, bound when the game is created
+    #[serde(default)]
+    pub(crate) player_a_key: Option<secp256k1::PublicKey>,
+    /// Key player B signs their messages with, bound when they join
+    #[serde(default)]
+    pub(crate) player_b_key: Option<secp256k1::PublicKey>,
+    /// Highest envelope nonce accepted from player A, see [`GameState::admit`]
+    #[serde(default)]
+    pub(crate) last_nonce_a: u64,
+    /// Highest envelope nonce accepted from player B
+    #[serde(default)]
+    pub(crate) last_nonce_b: u64,
+    /// Where player A accepts direct connections from their opponent
+    #[serde(default)]
+    pub(crate) peer_url_a: Option<String>,
+    /// Player A's payment_hash (opponent uses this to create their invoice)
+    pub(crate) payment_hash_a: Option<PaymentHash>,
+    /// Player B's payment_hash (opponent uses this to create their invoice)
+    pub(crate) payment_hash_b: Option<PaymentHash>,
+    /// Player A's preimage (for settlement - revealed to winner)
+    pub(crate) preimage_a: Option<Preimage>,
+    /// Player B's preimage (for settlement - revealed to winner)
+    pub(crate) preimage_b: Option<Preimage>,
+    /// Player A's invoice string (created by A's frontend, for B to pay)
+    pub(crate) invoice_a: Option<String>,
+    /// Player B's invoice string (created by B's frontend, for A to pay)
+    pub(crate) invoice_b: Option<String>,
+    pub(crate) encrypted_preimage_a: Option<EncryptedPreimage>,
+    pub(crate) encrypted_preimage_b: Option<EncryptedPreimage>,
+    pub(crate) commit_a: Option<Commitment>,
+    pub(crate) commit_b: Option<Commitment>,
+    pub(crate) reveal_a: Option<RevealData>,
+    pub(crate) reveal_b: Option<RevealData>,
+    pub(crate) result: Option<GameResult>,
+    #[serde(with = "signature_serde")]
+    pub(crate) signature: Option<[u8; 64]>,
+    pub(crate) created_at: SystemTime,
+    /// Protocol steps seen by the oracle
+    #[serde(default)]
+    pub(crate) timeline: Vec<TimelineEvent>,
+    /// The signed message that ended the game early, if one did
+    #[serde(default)]
+    pub(crate) ending: Option<GameEnding>,
+    /// Players reveal to each other and send us verdicts, see
+    /// [`GameState::reveals_requested`]
+    #[serde(default)]
+    pub(crate) private: bool,
+    #[serde(default)]
+    pub(crate) verdict_a: Option<Verdict>,
+    #[serde(default)]
+    pub(crate) verdict_b: Option<Verdict>,
+    /// Player B confirmed player A's payment is held on B's node
+    #[serde(default)]
+    pub(crate) stake_held_a: bool,
+    /// Player A confirmed player B's payment is held on A's node
+    #[serde(default)]
+    pub(crate) stake_held_b: bool,
+    /// What player A reported doing with the invoice holding B's stake
+    #[serde(default)]
+    pub(crate) settled_a: Option<SettlementAction>,
+    /// What player B reported doing with the invoice holding A's stake
+    #[serde(default)]
+    pub(crate) settled_b: Option<SettlementAction>,
+    /// What a draw leads to
+    #[serde(default)]
+    pub(crate) draw_policy: DrawPolicy,
+    /// Which round of its match the game is; unset for games from before
+    /// rematches, each a match of its own
+    #[serde(default)]
+    pub(crate) round: Option<MatchRound>,
+    /// The game replaying this one after a draw, see
+    /// [`GameState::rematch_round`]
+    #[serde(default)]
+    pub(crate) rematch: Option<GameId>,
+    /// Key seat B is kept for: the opponent of the drawn game this one
+    /// replays
+    #[serde(default)]
+    pub(crate) reserved_b: Option<secp256k1::PublicKey>,
+}
+
+/// What a player of a private game claims the result is
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Verdict {
+    pub(crate) result: GameResult,
+    pub(crate) transcript: [u8; 32],
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[allow(dead_code)]
+pub(crate) struct RevealData {
+    pub(crate) action: GameAction,
+    pub(crate) salt: Salt,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameStatus {
+    WaitingForOpponent,
+    InProgress,
+    Completed,
+    Cancelled,
+}
+
+impl GameStatus {
+    /// Name used in API responses
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GameStatus::WaitingForOpponent => "waiting_for_opponent",
+            GameStatus::InProgress => "in_progress",
+            GameStatus::Completed => "completed",
+            GameStatus::Cancelled => "cancelled",
+        }
+    }
+
+    /// Whether the game has ended, with a result or without
+    pub fn is_over(self) -> bool {
+        matches!(self, GameStatus::Completed | GameStatus::Cancelled)
+    }
+}
+
+/// serde only implements arrays up to 32 elements, so signatures are stored as hex.
+mod signature_serde {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(sig: &Option<[u8; 64]>, s: S) -> Result<S::Ok, S::Error> {
+        sig.map(hex::encode).serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<[u8; 64]>, D::Error> {
+        let Some(hex_str) = Option::<String>::deserialize(d)? else {
+            return Ok(None);
+        };
+        let bytes = hex::decode(&hex_str).map_err(serde::de::Error::custom)?;
+        let sig = bytes
+            .try_into()
+            .map_err(|_| serde::de::Error::custom("signature must be 64 bytes"))?;
+        Ok(Some(sig))
+    }
+}
+
+impl GameState {
+    /// Create a game waiting for an opponent, with a fresh commitment keypair.
+    pub(crate) fn new(
+        game_type: GameType,
+        amount_shannons: u64,
+        player_a_id: Uuid,
+        oracle_secret: Option<OracleSecret>,
+        created_at: SystemTime,
+    ) -> Self {
+        let secp = secp256k1::Secp256k1::new();
+        let commitment_key = secp256k1::SecretKey::new(&mut rand::thread_rng());
+        let commitment_point = secp256k1::PublicKey::from_secret_key(&secp, &commitment_key);
+        let oracle_commitment = oracle_secret.as_ref().map(|s| s.commitment());
+
+        Self {
+            game_type,
+            amount_shannons,
+            status: GameStatus::WaitingForOpponent,
+            commitment_key,
+            commitment_point,
+            oracle_key: None,
+            oracle_secret,
+            oracle_commitment,
+            player_a_id,
+            player_b_id: None,
+            player_a_key: None,
+            player_b_key: None,
+            peer_url_a: None,
+            payment_hash_a: None,
+            payment_hash_b: None,
+            preimage_a: None,
+            preimage_b: None,
+            invoice_a: None,
+            invoice_b: None,
+            encrypted_preimage_a: None,
+            encrypted_preimage_b: None,
+            commit_a: None,
+            commit_b: None,
+            reveal_a: None,
+            reveal_b: None,
+            result: None,
+            signature: None,
+            created_at,
+            timeline: Vec::new(),
+            ending: None,
+            last_nonce_a: 0,
+            last_nonce_b: 0,
+            private: false,
+            verdict_a: None,
+            verdict_b: None,
+            stake_held_a: false,
+            stake_held_b: false,
+            settled_a: None,
+            settled_b: None,
+            draw_policy: DrawPolicy::default(),
+            round: None,
+            rematch: None,
+            reserved_b: None,
+        }
+    }
+
+    /// Which round of its match game `game_id` is
+    pub(crate) fn round_of(&self, game_id: GameId) -> MatchRound {
+        self.round.unwrap_or_else(|| MatchRound::first(game_id))
+    }
+
+    /// The round replaying drawn game `game_id`, if `sender` may create it
+    /// with `req`: its player A, before anyone else did, on the same terms,
+    /// and under a policy that replays this draw.
+    pub(crate) fn rematch_round(
+        &self,
+        game_id: GameId,
+        req: &CreateGameRequest,
+        sender: &secp256k1::PublicKey,
+    ) -> Result<MatchRound, ApiError> {
+        if self.player_a_key.as_ref() != Some(sender) {
+            return Err(ApiError::forbidden("Only the drawn game's player A may replay it"));
+        }
+        let round = self.round_of(game_id);
+        let replays = self.status == GameStatus::Completed
+            && self.result.is_some_and(|r| self.draw_policy.replays(r, &round));
+        if !replays {
+            return Err(ApiError::invalid_state("Game is not a draw to be replayed"));
+        }
+        if self.rematch.is_some() {
+            return Err(ApiError::conflict("Game has been replayed already"));
+        }
+        let same_terms = req.game_type == self.game_type
+            && req.amount_shannons == self.amount_shannons
+            && req.private == self.private
+            && req.draw_policy == self.draw_policy;
+        if !same_terms {
+            return Err(ApiError::bad_request("A rematch is played on the drawn game's terms"));
+        }
+        Ok(round.next())
+    }
+
+    /// Whether both players confirmed their opponent's payment is held.
+    pub(crate) fn stakes_held(&self) -> bool {
+        self.stake_held_a && self.stake_held_b
+    }
+
+    /// What `player`'s commitment must be bound to, once their opponent's
+    /// payment hash is in.
+    pub(crate) fn commit_context(&self, game_id: GameId, player: Player) -> Option<CommitContext> {
+        let opponent_payment_hash = match player {
+            Player::A => self.payment_hash_b,
+            Player::B => self.payment_hash_a,
+        }?;
+        Some(CommitContext {
+            game_id,
+            amount_shannons: self.amount_shannons,
+            opponent_payment_hash,
+        })
+    }
+
+    /// Where the hold invoices stand once the game has a result: what each
+    /// player owes, what they reported, and who has left it undone for
+    /// `timeout` since the result.
+    pub(crate) fn settlement(&self, clock: &dyn Clock, timeout: Duration) -> Option<SettlementStatus> {
+        let result = self.result?;
+        let late = clock.since(self.judged_at()) >= timeout;
+        let owed = |player| match &self.round {
+            Some(round) => SettlementAction::owed_in(result, player, self.draw_policy, round),
+            None => SettlementAction::owed(result, player),
+        };
+        let seat = |player, done: Option<SettlementAction>| SeatSettlement {
+            owed: owed(player),
+            done,
+            overdue: done.is_none() && late,
+        };
+        Some(SettlementStatus {
+            a: seat(Player::A, self.settled_a),
+            b: seat(Player::B, self.settled_b),
+            complete: self.settled_a.is_some() && self.settled_b.is_some(),
+        })
+    }
+
+    /// When the game was judged, or created if it has no Judged event
+    fn judged_at(&self) -> SystemTime {
+        self.timeline
+            .iter()
+            .rev()
+            .find(|e| e.step == ProtocolStep::Judged)
+            .map(|e| UNIX_EPOCH + Duration::from_millis(e.at_ms))
+            .unwrap_or(self.created_at)
+    }
+
+    /// Time left before a player who owes the next step is late: before the
+    /// game has been idle for `timeout` while in progress, or before the
+    /// invoices have gone unresolved for it since the result.
+    pub(crate) fn step_deadline(&self, clock: &dyn Clock, timeout: Duration) -> Option<Duration> {
+        let since = match self.status {
+            GameStatus::InProgress => self.idle_for(clock),
+            GameStatus::Completed if self.settled_a.is_none() || self.settled_b.is_none() => {
+                clock.since(self.judged_at())
+            }
+            _ => return None,
+        };
+        Some(timeout.saturating_sub(since))
+    }
+
+    /// Whether a private game has to be judged from reveals after all: the
+    /// verdicts disagree, or a player revealed to us anyway, so their
+    /// opponent should too.
+    pub(crate) fn reveals_requested(&self) -> bool {
+        let disagree = matches!(
+            (self.verdict_a, self.verdict_b),
+            (Some(a), Some(b)) if a != b
+        );
+        self.private && (disagree || self.reveal_a.is_some() || self.reveal_b.is_some())
+    }
+
+    /// How many of payment hash, commit and reveal `player` has submitted.
+    ///
+    /// A verdict stands in for the reveal until reveals are requested.
+    pub(crate) fn progress(&self, player: Player) -> usize {
+        let verdict_only = !self.reveals_requested();
+        let steps = match player {
+            Player::A => [
+                self.payment_hash_a.is_some(),
+                self.commit_a.is_some(),
+                self.reveal_a.is_some() || (verdict_only && self.verdict_a.is_some()),
+            ],
+            Player::B => [
+                self.payment_hash_b.is_some(),
+                self.commit_b.is_some(),
+                self.reveal_b.is_some() || (verdict_only && self.verdict_b.is_some()),
+            ],
+        };
+        steps.iter().take_while(|done| **done).count()
+    }
+
+    /// Next step the oracle is waiting for `player` to take, if any.
+    pub(crate) fn awaited_step(&self, player: Player) -> Option<ProtocolStep> {
+        if self.status != GameStatus::InProgress {
+            return None;
+        }
+        [
+            ProtocolStep::PaymentHashSubmitted,
+            ProtocolStep::Committed,
+            ProtocolStep::Revealed,
+        ]
+        .get(self.progress(player))
+        .copied()
+    }
+
+    /// When the last protocol step was taken, or the game created.
+    pub(crate) fn updated_at(&self) -> SystemTime {
+        self.timeline
+            .last()
+            .map(|e| UNIX_EPOCH + Duration::from_millis(e.at_ms))
+            .unwrap_or(self.created_at)
+    }
+
+    /// Time since the last protocol step, or since the game was created.
+    pub(crate) fn idle_for(&self, clock: &dyn Clock) -> Duration {
+        clock.since(self.updated_at())
+    }
+
+    /// When a game offered for `ttl` stops being offered
+    pub(crate) fn offer_expires_at(&self, ttl: Duration) -> SystemTime {
+        self.created_at + ttl
+    }
+
+    /// Whether the game is still waiting for its second player, and
+    /// offered to one at `now`
+    pub(crate) fn is_offered(&self, ttl: Duration, now: SystemTime) -> bool {
+        self.status == GameStatus::WaitingForOpponent && now < self.offer_expires_at(ttl)
+    }
+
+    /// Whether anyone may take seat B, rather than only the opponent of the
+    /// drawn game this one replays
+    pub(crate) fn is_open(&self) -> bool {
+        self.reserved_b.is_none()
+    }
+
+    /// Who ended the game early and why, if it was cancelled.
+    pub(crate) fn cancellation(&self) -> Option<(Player, AbortReason)> {
+        if self.status != GameStatus::Cancelled {
+            return None;
+        }
+        self.ending.as_ref()?.cause()
+    }
+
+    /// The game as seen from `player`'s seat, for resuming it.
+    pub(crate) fn snapshot(&self, game_id: GameId, player: Player) -> GameSnapshot {
+        let (preimage, opponent_payment_hash, invoice, opponent_invoice) = match player {
+            Player::A => (&self.preimage_a, self.payment_hash_b, &self.invoice_a, &self.invoice_b),
+            Player::B => (&self.preimage_b, self.payment_hash_a, &self.invoice_b, &self.invoice_a),
+        };
+        let (commitment, reveal) = match player {
+            Player::A => (self.commit_a, &self.reveal_a),
+            Player::B => (self.commit_b, &self.reveal_b),
+        };
+        // The winner gets the loser's preimage, as from the result endpoint
+        let opponent_preimage = match (self.result, player) {
+            (Some(GameResult::AWins), Player::A) => self.preimage_b.clone(),
+            (Some(GameResult::BWins), Player::B) => self.preimage_a.clone(),
+            _ => None,
+        };
+        GameSnapshot {
+            game_id,
+            player,
+            game_type: self.game_type,
+            amount_shannons: self.amount_shannons,
+            commitment_point: self.commitment_point,
+            peer_url: self.peer_url_a.clone(),
+            preimage: preimage.clone(),
+            opponent_payment_hash,
+            invoice: invoice.clone(),
+            opponent_invoice: opponent_invoice.clone(),
+            commitment,
+            revealed_action: reveal.as_ref().map(|r| r.action.clone()),
+            result: self.result,
+            opponent_preimage,
+            cancelled: self.cancellation(),
+        }
+    }
+
+    /// Record `result` and sign it.
+    pub(crate) fn complete(
+        &mut self,
+        game_id: &GameId,
+        result: GameResult,
+        detail: &str,
+        clock: &dyn Clock,
+    ) {
+        self.result = Some(result);
+        self.status = GameStatus::Completed;
+        self.timeline.push(
+            TimelineEvent::at(clock, Actor::Oracle, Actor::Oracle, ProtocolStep::Judged)
+                .with_detail(detail),
+        );
+
+        // Sign the result (simplified - in real implementation would use proper Schnorr)
+        let mut sig = [0u8; 64];
+        let msg = format!("{}:{}", game_id, result.as_str());
+        let hash = Sha256::digest(msg.as_bytes());
+        sig[..32].copy_from_slice(&hash);
+        self.signature = Some(sig);
+    }
+
+    /// Check that a message on behalf of `player` was signed by the key that
+    /// player created or joined the game with.
+    pub(crate) fn check_signer(
+        &self,
+        player: Player,
+        sender: &secp256k1::PublicKey,
+    ) -> Result<(), ApiError> {
+        let bound = match player {
+            Player::A => self.player_a_key,
+            Player::B => self.player_b_key,
+        };
+        match bound {
+            Some(key) if key == *sender => Ok(()),
+            Some(_) => Err(ApiError::forbidden("Message not signed by this player's key")),
+            None => Err(ApiError::forbidden("No signing key on record for this player")),
+        }
+    }
+
+    /// Record `nonce` as the latest from `player`, refusing it unless it is
+    /// newer than everything they sent before. A message captured from this
+    /// game is older than what the player has sent here since, so it can't
+    /// be replayed; one captured from another game is refused by
+    /// [`check_bound`] whatever its nonce.
+    pub(crate) fn advance_nonce(&mut self, player: Player, nonce: u64) -> Result<(), ApiError> {
+        let last = match player {
+            Player::A => &mut self.last_nonce_a,
+            Player::B => &mut self.last_nonce_b,
+        };
+        if nonce <= *last {
+            return Err(ApiError::conflict(
+                "Message is a replay or older than one already received",
+            ));
+        }
+        *last = nonce;
+        Ok(())
+    }
+
+    /// Accept a submission to this game, `game_id`, from `player`:
+    /// [`check_bound`], [`GameState::check_signer`], then
+    /// [`GameState::advance_nonce`].
+    pub(crate) fn admit<T>(
+        &mut self,
+        game_id: &GameId,
+        player: Player,
+        envelope: &Envelope<T>,
+    ) -> Result<(), ApiError> {
+        check_bound(game_id, envelope)?;
+        self.check_signer(player, &envelope.sender)?;
+        self.advance_nonce(player, envelope.nonce)
+    }
+}
+
+/// Refuse a submission that wasn't sealed for `game_id`. Nonces are per
+/// sender, not per game, so without this a message a player signed for one
+/// of their games could be replayed into another whose last nonce is older.
+pub(crate) fn check_bound<T>(game_id: &GameId, envelope: &Envelope<T>) -> Result<(), ApiError> {
+    if envelope.game_id != Some(*game_id) {
+        return Err(ApiError::forbidden("Message was not signed for this game"));
+    }
+    Ok(())
+}
+
+impl AuthState for OracleState {
+    fn admin_secret(&self) -> &AdminSecret {
+        &self.admin_token
+    }
+}
+
+impl OracleState {
+    /// Create an oracle with a fresh random key and no persistence.
+    pub fn new() -> Self {
+        Self::with_secret_key(secp256k1::SecretKey::new(&mut rand::thread_rng()))
+    }
+
+    fn with_secret_key(secret_key: secp256k1::SecretKey) -> Self {
+        let events = EventBus::new();
+        let metrics = Arc::new(Metrics::new());
+        metrics.follow(&events);
+
+        Self {
+            keys: SigningKeys::new(secret_key),
+            games: MeteredRwLock::new(HashMap::new()),
+            store: None,
+            step_timeout: DEFAULT_STEP_TIMEOUT,
+            offer_ttl: DEFAULT_OFFER_TTL,
+            clock: SystemClock::shared(),
+            recorder: ProtocolRecorder::new(),
+            metrics,
+            events,
+            admin_token: AdminSecret::default(),
+            require_funding: false,
+            explorer_rate_limit: DEFAULT_EXPLORER_RATE_LIMIT,
+        }
+    }
+
+    /// Serve the operator API under `/admin`, to requests bearing `token`.
+    pub fn with_admin_token(mut self, token: Option<String>) -> Self {
+        self.admin_token = AdminSecret::new(token);
+        self
+    }
+
+    /// Accept commitments only once both players confirmed, with a
+    /// [`FundingMessage`](fiber_game_core::protocol::FundingMessage), that
+    /// the opponent's payment is held on their node.
+    pub fn with_require_funding(mut self, require: bool) -> Self {
+        self.require_funding = require;
+        self
+    }
+
+    /// Let each client make `per_minute` requests to the public explorer.
+    pub fn with_explorer_rate_limit(mut self, per_minute: u32) -> Self {
+        self.explorer_rate_limit = per_minute;
+        self
+    }
+
+    /// Accept timeout claims once a game has been idle for `timeout`.
+    pub fn with_step_timeout(mut self, timeout: Duration) -> Self {
+        self.step_timeout = timeout;
+        self
+    }
+
+    /// Offer a game to second players for `ttl` after it was created.
+    /// Older offers are no longer listed or joinable; their creator can
+    /// still abort them.
+    pub fn with_offer_ttl(mut self, ttl: Duration) -> Self {
+        self.offer_ttl = ttl;
+        self
+    }
+
+    /// Read the time from `clock` instead of the OS, e.g. a `TestClock`
+    /// that tests advance past the step timeout.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Record protocol messages with `recorder`, e.g. one that writes trace
+    /// files.
+    pub fn with_recorder(mut self, recorder: ProtocolRecorder) -> Self {
+        self.recorder = recorder;
+        self
+    }
+
+    /// Count into `metrics`, e.g. ones shared with the players of a combined
+    /// demo.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        metrics.follow(&self.events);
+        self.metrics = metrics;
+        self
+    }
+
+    /// Create an oracle backed by `store`.
+    ///
+    /// The signing keys and games saved by a previous run are restored; on
+    /// first use a fresh key is generated and saved.
+    pub fn open(store: Arc<dyn OracleStore>) -> Result<Self, StorageError> {
+        let mut state = match store.load_key()? {
+            Some(secret_key) => Self::with_secret_key(secret_key),
+            None => {
+                let state = Self::new();
+                store.save_key(&state.keys.first().secret)?;
+                state
+            }
+        };
+        for (secret_key, activates_at_ms) in store.load_rotated_keys()? {
+            state.keys.restore(SigningKey::new(secret_key, activates_at_ms));
+        }
+
+        let games = store.load_games()?;
+        if !games.is_empty() {
+            info!("Restored {} oracle games", games.len());
+        }
+        state.games = MeteredRwLock::new(games.into_iter().collect());
+        state.store = Some(store);
+        Ok(state)
+    }
+
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// Announce that `game_id` just ended with `result`
+    pub(crate) fn publish_completed(&self, game_id: &GameId, result: GameResult) {
+        self.events.publish(Event::GameCompleted {
+            game_id: *game_id.as_uuid(),
+            outcome: match result {
+                GameResult::AWins => GameOutcome::AWins,
+                GameResult::BWins => GameOutcome::BWins,
+                GameResult::Draw => GameOutcome::Draw,
+            },
+        });
+    }
+
+    /// Public key new games are signed with
+    pub fn public_key(&self) -> secp256k1::PublicKey {
+        self.active_key().public
+    }
+
+    /// Short, stable identifier for the key new games are signed with: the
+    /// first 8 bytes of the SHA-256 of the compressed public key, in hex.
+    ///
+    /// Lets operators check at a glance that a restarted oracle kept its key.
+    pub fn key_fingerprint(&self) -> String {
+        self.active_key().fingerprint()
+    }
+
+    /// The key new games are signed with
+    pub(crate) fn active_key(&self) -> SigningKey {
+        self.keys.active(self.clock.now_ms())
+    }
+
+    /// The key `game` is signed with
+    pub(crate) fn game_key(&self, game: &GameState) -> SigningKey {
+        game.oracle_key
+            .and_then(|public| self.keys.get(&public))
+            .unwrap_or_else(|| self.keys.first())
+    }
+
+    /// Every key the oracle signed or will sign games with, oldest first.
+    pub fn key_history(&self) -> Vec<OracleKey> {
+        self.keys.history(self.clock.now_ms())
+    }
+
+    /// Announce a fresh key that new games are signed with once `activate_in`
+    /// has passed; games created until then keep the current key. The key
+    /// is saved to the store before it is announced, and only one key can be
+    /// waiting to activate at a time.
+    pub fn announce_key(&self, activate_in: Duration) -> Result<OracleKey, ApiError> {
+        let now_ms = self.clock.now_ms();
+        let activates_at_ms = now_ms.saturating_add(activate_in.as_millis() as u64);
+        let key = SigningKey::new(secp256k1::SecretKey::new(&mut rand::thread_rng()), activates_at_ms);
+        let announced = self
+            .keys
+            .announce(key, now_ms, |key| match &self.store {
+                Some(store) => store.save_rotated_key(&key.secret, key.activates_at_ms),
+                None => Ok(()),
+            })
+            .map_err(|e| ApiError::internal(format!("Failed to save the key: {}", e)))?;
+        if !announced {
+            return Err(ApiError::conflict("Another key is already waiting to activate"));
+        }
+
+        info!(fingerprint = %key.fingerprint(), activates_at_ms, "Announced the next oracle key");
+        let status = if activates_at_ms > now_ms { KeyStatus::Next } else { KeyStatus::Active };
+        Ok(key.describe(status))
+    }
+
+    /// Sign a response to a player with `key`.
+    pub(crate) fn seal<T: Serialize>(
+        &self,
+        key: &SigningKey,
+        payload: T,
+    ) -> Result<Envelope<T>, EnvelopeError> {
+        Envelope::seal(payload, &key.secret)
+    }
+
+    /// [`OracleState::seal`], recording the response in the game's trace.
+    pub(crate) fn seal_recorded<T: Serialize>(
+        &self,
+        key: &SigningKey,
+        game_id: GameId,
+        kind: MessageKind,
+        payload: T,
+    ) -> Result<Envelope<T>, EnvelopeError> {
+        let envelope = self.seal(key, payload)?;
+        self.record(game_id, Direction::Outbound, kind, &envelope);
+        Ok(envelope)
+    }
+
+    /// Add a message to the game's trace. Failing to write a trace file is
+    /// only logged.
+    pub(crate) fn record<T: Serialize>(
+        &self,
+        game_id: GameId,
+        direction: Direction,
+        kind: MessageKind,
+        envelope: &Envelope<T>,
+    ) {
+        if let Err(e) = self.recorder.record(game_id, direction, kind, envelope) {
+            warn!(%game_id, ?kind, error = %e, "Failed to record protocol message");
+        }
+    }
+
+    /// Token letting `player_id` take seat `player` back after losing their
+    /// session, see [`GameSnapshot`].
+    pub(crate) fn resumption_token(
+        &self,
+        key: &SigningKey,
+        game_id: GameId,
+        player: Player,
+        player_id: Uuid,
+    ) -> Result<Envelope<ResumptionToken>, EnvelopeError> {
+        self.seal(key, ResumptionToken {
+            game_id,
+            player,
+            player_id,
+            issued_at_ms: self.clock.now_ms(),
+        })
+    }
+
+    /// A protocol step happening now, by the oracle's clock.
+    pub(crate) fn event(
+        &self,
+        from: impl Into<Actor>,
+        to: impl Into<Actor>,
+        step: ProtocolStep,
+    ) -> TimelineEvent {
+        TimelineEvent::at(self.clock.as_ref(), from, to, step)
+    }
+
+    /// How often requests waited on the lock over all games.
+    pub fn lock_stats(&self) -> LockStats {
+        self.games.stats()
+    }
+
+    /// Number of games held, in any status.
+    pub async fn game_count(&self) -> usize {
+        self.games.read().await.len()
+    }
+
+    /// Protocol steps the oracle has recorded for a game.
+    pub async fn timeline(&self, game_id: &GameId) -> Option<Vec<TimelineEvent>> {
+        let games = self.games.read().await;
+        games.get(game_id).map(|g| g.timeline.clone())
+    }
+
+    /// Fix the secret number of a Guess Number game before an opponent joins.
+    ///
+    /// Used by scripted demo runs to make results deterministic; the oracle
+    /// commitment is recomputed so players still see a consistent game.
+    pub async fn set_oracle_secret(
+        &self,
+        game_id: &GameId,
+        secret_number: u8,
+    ) -> Result<(), ApiError> {
+        if secret_number >= 100 {
+            return Err(ApiError::bad_request("Secret number must be 0-99"));
+        }
+        let mut games = self.games.write().await;
+        let game = games
+            .get_mut(game_id)
+            .ok_or_else(|| ApiError::not_found("Game not found"))?;
+        if !game.game_type.requires_oracle_secret() {
+            return Err(ApiError::bad_request("Game does not use an oracle secret"));
+        }
+        if game.status != GameStatus::WaitingForOpponent {
+            return Err(ApiError::invalid_state(
+                "Oracle secret can only be set before an opponent joins",
+            ));
+        }
+
+        let secret = OracleSecret::with_number(secret_number);
+        game.oracle_commitment = Some(secret.commitment());
+        game.oracle_secret = Some(secret);
+        self.persist(game_id, game);
+        Ok(())
+    }
+
+    /// Write a game to the store. Failures are logged rather than surfaced so a
+    /// broken disk never blocks an in-progress game.
+    pub(crate) fn persist(&self, game_id: &GameId, game: &GameState) {
+        if let Some(store) = &self.store {
+            if let Err(e) = store.save_game(game_id, game) {
+                warn!(%game_id, error = %e, "Failed to persist oracle game");
+            }
+        }
+    }
+}
+
+impl Default for OracleState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_pubkey() -> secp256k1::PublicKey {
+        let secret = secp256k1::SecretKey::new(&mut rand::thread_rng());
+        secp256k1::PublicKey::from_secret_key(secp256k1::SECP256K1, &secret)
+    }
+
+    #[test]
+    fn test_check_signer() {
+        let (a, b) = (random_pubkey(), random_pubkey());
+        let mut game = GameState::new(GameType::RockPaperScissors, 1000, Uuid::new_v4(), None, SystemTime::now());
+        game.player_a_key = Some(a);
+
+        assert!(game.check_signer(Player::A, &a).is_ok());
+        assert!(game.check_signer(Player::A, &b).is_err());
+        // B hasn't joined, so nothing may be submitted on their behalf yet
+        assert!(game.check_signer(Player::B, &a).is_err());
+
+        game.player_b_key = Some(b);
+        assert!(game.check_signer(Player::B, &b).is_ok());
+    }
+}
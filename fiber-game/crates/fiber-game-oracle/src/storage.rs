@@ -0,0 +1,418 @@
+//! Oracle persistence.
+//!
+//! [`OracleStore`] abstracts where the oracle keeps its signing keys and game
+//! sessions; [`SqliteOracleStore`] is the SQLite implementation. Games are
+//! stored as JSON blobs keyed by game ID, so the table layout does not need
+//! to change whenever [`GameState`] gains a field.
+//!
+//! When the layout does change, it does so through a new versioned SQL file
+//! in `migrations/` (`V2__what_changed.sql`, ...). The files are compiled in
+//! and [`SqliteOracleStore::open`] applies the ones a database is missing.
+//!
+//! Given a [`Keyring`], the store encrypts the signing keys and games (which
+//! hold the oracle's secrets) before they reach the database.
+
+use crate::state::GameState;
+use fiber_game_core::crypto::{Keyring, KeyringError};
+use fiber_game_core::protocol::GameId;
+use fiber_core::storage::{self, SchemaError};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::info;
+
+mod embedded {
+    refinery::embed_migrations!("migrations");
+}
+
+/// Table recording which of the oracle's migrations have run, apart from
+/// the players' so both can share a database file
+const MIGRATION_TABLE: &str = "oracle_schema_history";
+
+/// Columns holding secrets, encrypted when the store has a [`Keyring`]
+const SECRET_COLUMNS: [(&str, &str); 3] = [
+    ("oracle_key", "secret_key"),
+    ("oracle_rotated_keys", "secret_key"),
+    ("oracle_games", "data"),
+];
+
+/// Storage error
+#[derive(Debug, thiserror::Error)]
+pub enum StorageError {
+    #[error("database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("corrupt record: {0}")]
+    Corrupt(String),
+    #[error(transparent)]
+    Schema(#[from] SchemaError),
+    #[error("encrypted record: {0}")]
+    Encryption(#[from] KeyringError),
+    #[error("record is encrypted but no storage key is configured")]
+    Locked,
+}
+
+/// Persistent storage for oracle state
+pub trait OracleStore: Send + Sync {
+    /// Load the oracle's signing key, if one was saved.
+    fn load_key(&self) -> Result<Option<secp256k1::SecretKey>, StorageError>;
+
+    /// Save the oracle's signing key.
+    fn save_key(&self, key: &secp256k1::SecretKey) -> Result<(), StorageError>;
+
+    /// Load the keys the oracle rotated to after the first, each with when
+    /// it started signing new games in milliseconds since the epoch.
+    fn load_rotated_keys(&self) -> Result<Vec<(secp256k1::SecretKey, u64)>, StorageError>;
+
+    /// Save a key the oracle rotates to at `activates_at_ms`.
+    fn save_rotated_key(
+        &self,
+        key: &secp256k1::SecretKey,
+        activates_at_ms: u64,
+    ) -> Result<(), StorageError>;
+
+    /// Load all saved games.
+    fn load_games(&self) -> Result<Vec<(GameId, GameState)>, StorageError>;
+
+    /// Insert or replace a game.
+    fn save_game(&self, game_id: &GameId, game: &GameState) -> Result<(), StorageError>;
+}
+
+/// SQLite-backed [`OracleStore`]
+///
+/// Uses the `oracle_*` tables, so the same database file can be shared with
+/// other stores (e.g. the players of the combined demo).
+pub struct SqliteOracleStore {
+    conn: Mutex<Connection>,
+    keyring: Option<Keyring>,
+}
+
+impl SqliteOracleStore {
+    /// Open (or create) the database at `path`, applying any schema
+    /// migrations it is missing.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StorageError> {
+        Self::from_connection(Connection::open(path)?, true)
+    }
+
+    /// Open the database at `path` as it is: fails with
+    /// [`SchemaError::Pending`] unless its schema is up to date.
+    pub fn open_without_migrating(path: impl AsRef<Path>) -> Result<Self, StorageError> {
+        Self::from_connection(Connection::open(path)?, false)
+    }
+
+    /// Open a private in-memory database.
+    pub fn open_in_memory() -> Result<Self, StorageError> {
+        Self::from_connection(Connection::open_in_memory()?, true)
+    }
+
+    fn from_connection(mut conn: Connection, migrate: bool) -> Result<Self, StorageError> {
+        let applied =
+            storage::prepare(&mut conn, embedded::migrations::runner(), MIGRATION_TABLE, migrate)?;
+        for migration in applied {
+            info!(%migration, "Applied oracle schema migration");
+        }
+        Ok(Self {
+            conn: Mutex::new(conn),
+            keyring: None,
+        })
+    }
+
+    /// Encrypt secrets with `keyring` from now on, and re-encrypt those
+    /// already stored that aren't under its current key: plaintext ones
+    /// from before encryption was set up, and ones under a rotated-out key.
+    pub fn with_keyring(mut self, keyring: Keyring) -> Result<Self, StorageError> {
+        self.keyring = Some(keyring);
+        let resealed = self.reseal()?;
+        if resealed > 0 {
+            info!(resealed, "Re-encrypted oracle secrets under the current storage key");
+        }
+        Ok(self)
+    }
+
+    /// Bring every secret column under the current storage key
+    fn reseal(&self) -> Result<usize, StorageError> {
+        let Some(keyring) = &self.keyring else {
+            return Ok(0);
+        };
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut resealed = 0;
+        for (table, column) in SECRET_COLUMNS {
+            let rows = tx
+                .prepare(&format!("SELECT rowid, {column} FROM {table}"))?
+                .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+                .collect::<Result<Vec<_>, _>>()?;
+            for (rowid, value) in rows {
+                let value = if keyring.is_current(&value) {
+                    continue;
+                } else if Keyring::is_sealed(&value) {
+                    keyring.rewrap(&value)?
+                } else {
+                    keyring.seal(value.as_bytes())
+                };
+                tx.execute(
+                    &format!("UPDATE {table} SET {column} = ?1 WHERE rowid = ?2"),
+                    params![value, rowid],
+                )?;
+                resealed += 1;
+            }
+        }
+        tx.commit()?;
+        Ok(resealed)
+    }
+
+    /// `value` as it is to be stored in a secret column
+    fn seal(&self, value: String) -> String {
+        match &self.keyring {
+            Some(keyring) => keyring.seal(value.as_bytes()),
+            None => value,
+        }
+    }
+
+    /// A secret column's `value` as it was before [`Self::seal`]
+    fn unseal(&self, value: String) -> Result<String, StorageError> {
+        if !Keyring::is_sealed(&value) {
+            return Ok(value);
+        }
+        let keyring = self.keyring.as_ref().ok_or(StorageError::Locked)?;
+        String::from_utf8(keyring.open(&value)?).map_err(|e| StorageError::Corrupt(e.to_string()))
+    }
+
+    /// A signing key from the hex a secret column holds it as
+    fn unseal_key(&self, hex_key: String) -> Result<secp256k1::SecretKey, StorageError> {
+        let bytes = hex::decode(self.unseal(hex_key)?)
+            .map_err(|e| StorageError::Corrupt(e.to_string()))?;
+        secp256k1::SecretKey::from_slice(&bytes).map_err(|e| StorageError::Corrupt(e.to_string()))
+    }
+}
+
+impl OracleStore for SqliteOracleStore {
+    fn load_key(&self) -> Result<Option<secp256k1::SecretKey>, StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let hex_key: Option<String> = conn
+            .query_row("SELECT secret_key FROM oracle_key WHERE id = 0", [], |row| {
+                row.get(0)
+            })
+            .optional()?;
+
+        hex_key.map(|hex_key| self.unseal_key(hex_key)).transpose()
+    }
+
+    fn save_key(&self, key: &secp256k1::SecretKey) -> Result<(), StorageError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO oracle_key (id, secret_key) VALUES (0, ?1)",
+            params![self.seal(hex::encode(key.secret_bytes()))],
+        )?;
+        Ok(())
+    }
+
+    fn load_rotated_keys(&self) -> Result<Vec<(secp256k1::SecretKey, u64)>, StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt =
+            conn.prepare("SELECT secret_key, activates_at_ms FROM oracle_rotated_keys")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })?;
+
+        let mut keys = Vec::new();
+        for row in rows {
+            let (hex_key, activates_at_ms) = row?;
+            keys.push((self.unseal_key(hex_key)?, activates_at_ms as u64));
+        }
+        Ok(keys)
+    }
+
+    fn save_rotated_key(
+        &self,
+        key: &secp256k1::SecretKey,
+        activates_at_ms: u64,
+    ) -> Result<(), StorageError> {
+        let public_key = key.public_key(secp256k1::SECP256K1);
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO oracle_rotated_keys (public_key, secret_key, activates_at_ms)
+             VALUES (?1, ?2, ?3)",
+            params![
+                hex::encode(public_key.serialize()),
+                self.seal(hex::encode(key.secret_bytes())),
+                activates_at_ms as i64
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn load_games(&self) -> Result<Vec<(GameId, GameState)>, StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT game_id, data FROM oracle_games")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+
+        let mut games = Vec::new();
+        for row in rows {
+            let (game_id, data) = row?;
+            let game_id = game_id
+                .parse()
+                .map_err(|e: uuid::Error| StorageError::Corrupt(e.to_string()))?;
+            games.push((game_id, serde_json::from_str(&self.unseal(data)?)?));
+        }
+        Ok(games)
+    }
+
+    fn save_game(&self, game_id: &GameId, game: &GameState) -> Result<(), StorageError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO oracle_games (game_id, data) VALUES (?1, ?2)",
+            params![game_id.to_string(), self.seal(serde_json::to_string(game)?)],
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{GameStatus, OracleState};
+    use fiber_game_core::games::{GameType, OracleSecret};
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+    use uuid::Uuid;
+
+    #[test]
+    fn test_key_roundtrip() {
+        let store = SqliteOracleStore::open_in_memory().unwrap();
+        assert!(store.load_key().unwrap().is_none());
+
+        let key = secp256k1::SecretKey::new(&mut rand::thread_rng());
+        store.save_key(&key).unwrap();
+        assert_eq!(store.load_key().unwrap(), Some(key));
+    }
+
+    #[test]
+    fn test_game_roundtrip() {
+        let store = SqliteOracleStore::open_in_memory().unwrap();
+        let game_id = GameId::new();
+        let mut game = GameState::new(
+            GameType::GuessNumber,
+            1000,
+            Uuid::new_v4(),
+            Some(OracleSecret::random()),
+            SystemTime::now(),
+        );
+        game.signature = Some([7u8; 64]);
+        store.save_game(&game_id, &game).unwrap();
+
+        game.status = GameStatus::InProgress;
+        store.save_game(&game_id, &game).unwrap();
+
+        let games = store.load_games().unwrap();
+        assert_eq!(games.len(), 1);
+        let (loaded_id, loaded) = &games[0];
+        assert_eq!(*loaded_id, game_id);
+        assert_eq!(loaded.status, GameStatus::InProgress);
+        assert_eq!(loaded.commitment_point, game.commitment_point);
+        assert_eq!(loaded.oracle_commitment, game.oracle_commitment);
+        assert_eq!(loaded.signature, Some([7u8; 64]));
+    }
+
+    #[test]
+    fn test_secrets_encrypted_and_rotated() {
+        let store = SqliteOracleStore::open_in_memory().unwrap();
+        let key = secp256k1::SecretKey::new(&mut rand::thread_rng());
+        store.save_key(&key).unwrap();
+        let stored = |store: &SqliteOracleStore| -> String {
+            store
+                .conn
+                .lock()
+                .unwrap()
+                .query_row("SELECT secret_key FROM oracle_key", [], |row| row.get(0))
+                .unwrap()
+        };
+        assert_eq!(stored(&store), hex::encode(key.secret_bytes()));
+
+        // A key saved before encryption was set up is sealed on first use
+        let old = Keyring::new([1u8; 32]);
+        let store = store.with_keyring(old.clone()).unwrap();
+        assert!(old.is_current(&stored(&store)));
+        assert_eq!(store.load_key().unwrap(), Some(key));
+
+        // Rotating rewraps it under the new key, after which the old key
+        // isn't needed
+        let store = store
+            .with_keyring(Keyring::new([2u8; 32]).with_old_key([1u8; 32]))
+            .unwrap();
+        let store = store.with_keyring(Keyring::new([2u8; 32])).unwrap();
+        assert_eq!(store.load_key().unwrap(), Some(key));
+
+        // Without a key the sealed record can't be read
+        let locked = SqliteOracleStore {
+            conn: store.conn,
+            keyring: None,
+        };
+        assert!(matches!(locked.load_key(), Err(StorageError::Locked)));
+    }
+
+    #[tokio::test]
+    async fn test_open_restores_key_and_games() {
+        let store: Arc<dyn OracleStore> = Arc::new(SqliteOracleStore::open_in_memory().unwrap());
+        let first = OracleState::open(store.clone()).unwrap();
+        let game_id = GameId::new();
+        let game = GameState::new(
+            GameType::RockPaperScissors,
+            500,
+            Uuid::new_v4(),
+            None,
+            SystemTime::now(),
+        );
+        first.persist(&game_id, &game);
+
+        let restored = OracleState::open(store).unwrap();
+        assert_eq!(restored.public_key(), first.public_key());
+        assert_eq!(restored.key_fingerprint(), first.key_fingerprint());
+        assert!(restored.games.read().await.contains_key(&game_id));
+    }
+
+    #[test]
+    fn test_open_restores_rotated_keys() {
+        let store: Arc<dyn OracleStore> = Arc::new(SqliteOracleStore::open_in_memory().unwrap());
+        let first = OracleState::open(store.clone()).unwrap();
+        let next = first.announce_key(Duration::ZERO).unwrap();
+
+        let restored = OracleState::open(store).unwrap();
+        assert_eq!(restored.key_fingerprint(), next.fingerprint);
+        assert_eq!(restored.key_history().len(), 2);
+    }
+
+    #[test]
+    fn test_migrations_adopt_a_pre_migration_database() {
+        // Tables as created before migrations existed
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE oracle_key (id INTEGER PRIMARY KEY CHECK (id = 0), secret_key TEXT NOT NULL);
+             CREATE TABLE oracle_games (game_id TEXT PRIMARY KEY, data TEXT NOT NULL);",
+        )
+        .unwrap();
+        let key = secp256k1::SecretKey::new(&mut rand::thread_rng());
+        conn.execute(
+            "INSERT INTO oracle_key (id, secret_key) VALUES (0, ?1)",
+            params![hex::encode(key.secret_bytes())],
+        )
+        .unwrap();
+
+        let store = SqliteOracleStore::from_connection(conn, true).unwrap();
+        assert_eq!(store.load_key().unwrap(), Some(key));
+
+        // Up to date now, so it opens without migrating too
+        let conn = store.conn.into_inner().unwrap();
+        assert!(SqliteOracleStore::from_connection(conn, false).is_ok());
+    }
+
+    #[test]
+    fn test_unmigrated_database_refused_without_auto_migrate() {
+        let conn = Connection::open_in_memory().unwrap();
+        let result = SqliteOracleStore::from_connection(conn, false);
+        assert!(matches!(result, Err(StorageError::Schema(SchemaError::Pending(2)))));
+    }
+}
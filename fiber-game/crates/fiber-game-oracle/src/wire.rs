@@ -0,0 +1,113 @@
+//! Content negotiation for protocol messages.
+//!
+//! Signed envelopes can arrive as JSON or CBOR, told apart by `Content-Type`
+//! ([`fiber_auth::AuthedPlayer`] decodes either), and the envelopes the
+//! oracle hands out are encoded as the client's `Accept` header asks
+//! ([`Accept`], [`Negotiated`]). Plain status responses stay JSON.
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts},
+    response::{IntoResponse, Response},
+};
+use fiber_errors::ApiError;
+use fiber_game_core::protocol::Encoding;
+use serde::Serialize;
+use std::convert::Infallible;
+
+/// Encoding the client wants responses in
+pub(crate) struct Accept(pub Encoding);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Accept {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let encoding = parts
+            .headers
+            .get(header::ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .map(Encoding::from_accept)
+            .unwrap_or_default();
+        Ok(Accept(encoding))
+    }
+}
+
+/// A response body in the encoding from [`Accept`]
+pub(crate) struct Negotiated<T>(pub Encoding, pub T);
+
+impl<T: Serialize> IntoResponse for Negotiated<T> {
+    fn into_response(self) -> Response {
+        let Negotiated(encoding, value) = self;
+        match encoding.encode(&value) {
+            Ok(body) => ([(header::CONTENT_TYPE, encoding.content_type())], body).into_response(),
+            Err(e) => ApiError::internal(e.to_string()).into_response(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, extract::Request, http::StatusCode, routing::post, Router};
+    use fiber_auth::AuthedPlayer;
+    use fiber_game_core::protocol::{CBOR_CONTENT_TYPE, JSON_CONTENT_TYPE};
+    use fiber_test_fixtures::{game::seal, Keypair};
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    async fn echo(Accept(encoding): Accept, player: AuthedPlayer<Value>) -> Negotiated<Value> {
+        Negotiated(encoding, player.payload)
+    }
+
+    async fn call(content_type: &str, accept: &str, body: Vec<u8>) -> Response {
+        Router::new()
+            .route("/", post(echo))
+            .oneshot(
+                Request::post("/")
+                    .header(header::CONTENT_TYPE, content_type)
+                    .header(header::ACCEPT, accept)
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_cbor_in_json_out_and_back() {
+        let msg = json!({ "player": "A", "payment_hash": [1, 2, 3] });
+        let envelope = seal(msg.clone(), &Keypair::player_a().secret);
+
+        let resp = call(
+            CBOR_CONTENT_TYPE,
+            JSON_CONTENT_TYPE,
+            Encoding::Cbor.encode(&envelope).unwrap(),
+        )
+        .await;
+        assert_eq!(resp.headers()[header::CONTENT_TYPE], JSON_CONTENT_TYPE);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(Encoding::Json.decode::<Value>(&body).unwrap(), msg);
+
+        let resp = call(
+            JSON_CONTENT_TYPE,
+            CBOR_CONTENT_TYPE,
+            Encoding::Json.encode(&envelope).unwrap(),
+        )
+        .await;
+        assert_eq!(resp.headers()[header::CONTENT_TYPE], CBOR_CONTENT_TYPE);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(Encoding::Cbor.decode::<Value>(&body).unwrap(), msg);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_content_type_rejected() {
+        let resp = call("text/plain", "*/*", b"{}".to_vec()).await;
+        assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+}
@@ -0,0 +1,5 @@
+// The SQL files under migrations/ are embedded at compile time; rebuild when
+// one is added or edited.
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
@@ -0,0 +1,18 @@
+-- Tables as the player created them before it had migrations; IF NOT EXISTS
+-- lets databases from that time adopt this history as they are.
+CREATE TABLE IF NOT EXISTS player_profiles (
+    profile TEXT PRIMARY KEY,
+    player_id TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS player_keys (
+    profile TEXT PRIMARY KEY,
+    secret_key TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS player_games (
+    profile TEXT NOT NULL,
+    game_id TEXT NOT NULL,
+    data TEXT NOT NULL,
+    PRIMARY KEY (profile, game_id)
+);
@@ -0,0 +1,6 @@
+-- Profiles hosted besides the service's own player, restored at startup
+CREATE TABLE player_profile_settings (
+    profile TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    fiber_rpc_url TEXT
+);
@@ -0,0 +1,1392 @@
+//! HTTP handlers for the player API.
+
+use crate::p2p::{self, PeerMessage};
+use crate::privacy;
+use crate::rematch::{self, Rematch};
+use crate::reminders;
+use crate::settlement;
+use crate::state::{
+    oracle_error, BackendSwitch, FiberBackend, PlayerGameState, PlayerState, PrivateExchange,
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    middleware,
+    routing::{get, post},
+    Json, Router,
+};
+use fiber_auth::{AdminToken, AuthState};
+use fiber_errors::{ApiError, ErrorCode, ValidJson, ValidPath};
+use fiber_game_api::{
+    oracle,
+    player::{
+        AbortRequest, AvailableGameResponse, AvailableGamesResponse, BackendResponse, BalanceResponse,
+        CreateGameRequest, CreateGameResponse, EndGameResponse, GameStatusResponse,
+        InvoiceCreatedRequest, InvoiceCreatedResponse, JoinGameRequest, JoinGameResponse,
+        MyGameResponse, MyGamesResponse, OpponentInvoiceResponse, PaymentDoneRequest,
+        PaymentDoneResponse, PlayRequest, PlayResponse, PlayerInfoResponse, ResumeRequest,
+        ResumeResponse, SetBackendRequest, SettleAllResponse, SettleResponse, SettledGame,
+    },
+};
+use fiber_game_core::protocol::{
+    AbortMessage, AbortReason, Actor, AnySession, CommitMessage, Committed, Created, Funded,
+    FundingMessage, GameId, GameSession, GameSnapshot, Joined, Judged, Player, ProtocolStep, RevealMessage,
+    Revealed, Stage, TimelineEvent, TimeoutClaim,
+};
+use fiber_paging::{Page, PageRequest};
+use fiber_service::Event;
+use futures_util::{stream, StreamExt};
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+/// Player API state for the frontend, see [`PlayerState::request_backend`]
+async fn backend_response(state: &PlayerState) -> BackendResponse {
+    BackendResponse {
+        backend: state.fiber_backend().await,
+        pending: state.pending_backend().await,
+        active_games: state.active_games().await,
+        fiber_rpc_url: state.fiber_rpc_url().await,
+    }
+}
+
+// === Route handlers ===
+
+async fn get_player_info(State(state): State<Arc<PlayerState>>) -> Result<Json<PlayerInfoResponse>, ApiError> {
+    Ok(Json(PlayerInfoResponse {
+        player_id: state.player_id,
+        player_name: state.player_name.clone(),
+        fiber_rpc_url: state.fiber_rpc_url().await,
+    }))
+}
+
+async fn get_available_games(
+    State(state): State<Arc<PlayerState>>,
+    Query(page): Query<PageRequest>,
+) -> Result<Json<AvailableGamesResponse>, ApiError> {
+    // The oracle's cursor is ours: pages are the oracle's, minus our own games
+    let url = format!("{}/games/available", state.oracle_url);
+    let resp = state
+        .oracle_get(&url)
+        .query(&page)
+        .send()
+        .await
+        .map_err(|e| ApiError::upstream(e.to_string()))?;
+    if !resp.status().is_success() {
+        return Err(oracle_error(resp).await);
+    }
+    let resp: oracle::AvailableGamesResponse =
+        resp.json().await.map_err(|e| ApiError::upstream(e.to_string()))?;
+
+    // Get the set of game IDs this player has already joined/created
+    let my_game_ids: std::collections::HashSet<GameId> = {
+        let games = state.games.read().await;
+        games.keys().copied().collect()
+    };
+
+    // Skip games this player already has
+    let games = AvailableGamesResponse {
+        items: resp
+            .items
+            .into_iter()
+            .filter(|g| !my_game_ids.contains(&g.game_id))
+            .map(|g| AvailableGameResponse {
+                game_id: g.game_id,
+                game_type: g.game_type,
+                amount_shannons: g.amount_shannons,
+                created_at: g.created_at,
+                expires_at: g.expires_at,
+            })
+            .collect(),
+        next_cursor: resp.next_cursor,
+    };
+
+    Ok(Json(games))
+}
+
+/// For a game still waiting for its opponent, ask the Oracle whether B has
+/// joined and, if so, move on to Joined with B's payment_hash.
+async fn check_opponent_joined(state: &PlayerState, game_id: GameId) {
+    let oracle_pubkey = {
+        let games = state.games.read().await;
+        match games.get(&game_id) {
+            Some(g) if matches!(g.session, AnySession::Created(_)) => *g.session.oracle_pubkey(),
+            _ => return,
+        }
+    };
+
+    let url = format!("{}/game/{}/status", state.oracle_url, game_id);
+    let Ok(resp) = state.oracle_get(&url).send().await else {
+        return;
+    };
+    if state.check_oracle(&game_id, &resp).await.is_err() {
+        return;
+    }
+    let Ok(status_data) = resp.json::<oracle::GameStatusResponse>().await else {
+        return;
+    };
+    if !status_data.has_opponent {
+        return;
+    }
+
+    // Opponent has joined! Get their payment_hash so frontend can create invoice
+    let get_hash_url = format!("{}/game/{}/payment-hash/B", state.oracle_url, game_id);
+    let hash_data: oracle::PaymentHashResponse =
+        match state.oracle_get_sealed(&get_hash_url, Some(&oracle_pubkey)).await {
+            Ok(hash_data) => hash_data,
+            Err(e) => {
+                info!(player = %state.player_name, %game_id, error = %e, "B's payment_hash not available yet");
+                return;
+            }
+        };
+    let opponent_payment_hash = hash_data.payment_hash;
+
+    // The hash may have arrived over the direct link meanwhile
+    let mut games = state.games.write().await;
+    let Some(game) = games.get_mut(&game_id) else {
+        return;
+    };
+    let joined = game
+        .session
+        .advance(|s: GameSession<Created>| Ok(s.joined(opponent_payment_hash)));
+    if joined.is_ok() {
+        state.persist(&game_id, game);
+        info!(
+            player = %state.player_name,
+            %game_id,
+            payment_hash = %opponent_payment_hash.short_hex(),
+            "Opponent joined, got their payment_hash"
+        );
+        drop(games);
+        rematch::carry_over(state, game_id).await;
+    }
+}
+
+async fn get_my_games(
+    State(state): State<Arc<PlayerState>>,
+    Query(page): Query<PageRequest>,
+) -> Result<Json<MyGamesResponse>, ApiError> {
+    // Check Oracle for games waiting for opponent
+    let games_to_check: Vec<GameId> = {
+        let games = state.games.read().await;
+        games
+            .iter()
+            .filter(|(_, g)| matches!(g.session, AnySession::Created(_)))
+            .map(|(id, _)| *id)
+            .collect()
+    };
+    for game_id in games_to_check {
+        check_opponent_joined(&state, game_id).await;
+    }
+    for game_id in rematch::due(&state).await {
+        rematch::advance(&state, game_id).await;
+    }
+
+    let games = state.games.read().await;
+    let page = Page::of(games.iter(), &page, |(id, _)| *id.as_uuid())?;
+
+    Ok(Json(page.map(|(id, g)| MyGameResponse {
+        game_id: *id,
+        game_type: g.session.game_type(),
+        role: g.role(),
+        phase: g.phase(),
+        amount_shannons: g.session.amount_shannons(),
+        result: g.session.result(),
+        round: g.round,
+    })))
+}
+
+/// Oracle public key and commitment point from a create or join response.
+fn parse_oracle_keys(
+    oracle_pubkey: &str,
+    commitment_point: &str,
+) -> Result<(secp256k1::PublicKey, secp256k1::PublicKey), ApiError> {
+    let key = |field: &str, value: &str| {
+        hex::decode(value)
+            .ok()
+            .and_then(|b| secp256k1::PublicKey::from_slice(&b).ok())
+            .ok_or_else(|| ApiError::upstream(format!("Oracle response has no valid {}", field)))
+    };
+    Ok((
+        key("oracle_pubkey", oracle_pubkey)?,
+        key("commitment_point", commitment_point)?,
+    ))
+}
+
+async fn create_game(
+    State(state): State<Arc<PlayerState>>,
+    ValidJson(req): ValidJson<CreateGameRequest>,
+) -> Result<Json<CreateGameResponse>, ApiError> {
+    open_game(&state, &req, None).await.map(Json)
+}
+
+/// Create a game at the Oracle as player A and hand it our payment hash;
+/// with `rematch`, as the next round of a drawn game.
+pub(crate) async fn open_game(
+    state: &PlayerState,
+    req: &CreateGameRequest,
+    rematch: Option<&Rematch>,
+) -> Result<CreateGameResponse, ApiError> {
+    state.check_accepting_games().await?;
+    // A stake still held from the draw is paid already
+    let reused_preimage = rematch.and_then(|r| r.preimage.clone());
+    if reused_preimage.is_none() {
+        state.check_funds(req.amount_shannons).await?;
+    }
+
+    let url = format!("{}/game/create", state.oracle_url);
+
+    let body = oracle::CreateGameRequest {
+        game_type: req.game_type,
+        player_a_id: state.player_id,
+        amount_shannons: req.amount_shannons,
+        p2p_url: state.advertised_p2p_url(),
+        private: req.private,
+        draw_policy: req.draw_policy,
+        rematch_of: rematch.map(|r| r.of),
+    };
+
+    let resp = state
+        .oracle_post(&url, None, &body)?
+        .send()
+        .await
+        .map_err(|e| ApiError::upstream(e.to_string()))?;
+    if !resp.status().is_success() {
+        return Err(oracle_error(resp).await);
+    }
+    let resp: oracle::CreateGameResponse =
+        resp.json().await.map_err(|e| ApiError::upstream(e.to_string()))?;
+    let game_id = resp.game_id;
+
+    let (oracle_pubkey, commitment_point) =
+        parse_oracle_keys(&resp.oracle_pubkey, &resp.commitment_point)?;
+    let mut session = GameSession::new(
+        game_id,
+        Player::A,
+        req.game_type,
+        req.amount_shannons,
+        oracle_pubkey,
+        commitment_point,
+    );
+    if let Some(preimage) = reused_preimage {
+        session = session.reusing(preimage);
+    }
+
+    // Submit payment_hash to Oracle immediately so opponent can get it when they join
+    let submit_hash_url = format!("{}/game/{}/payment-hash", state.oracle_url, game_id);
+    let submit_hash_body = oracle::SubmitPaymentHashRequest {
+        player: Player::A,
+        payment_hash: session.payment_hash(),
+        preimage: session.preimage().clone(),
+    };
+
+    let hash_resp = state.oracle_post(&submit_hash_url, Some(game_id), &submit_hash_body)?
+        .send()
+        .await
+        .map_err(|e| ApiError::upstream(format!("Failed to submit payment hash: {}", e)))?;
+    if !hash_resp.status().is_success() {
+        let err = oracle_error(hash_resp).await;
+        return Err(ApiError::new(err.code, format!("Failed to submit payment hash: {}", err)));
+    }
+
+    info!(
+        player = %state.player_name,
+        %game_id,
+        payment_hash = %submit_hash_body.payment_hash.short_hex(),
+        "Submitted payment_hash to Oracle"
+    );
+
+    let resume_token = resp.resume_token;
+    let mut game_state = PlayerGameState::new(session);
+    game_state.resume_token = resume_token.clone();
+    game_state.private = req.private.then(PrivateExchange::default);
+    game_state.draw_policy = req.draw_policy;
+    game_state.round = resp.round;
+    game_state.replays = rematch.map(|r| r.of);
+
+    state.persist(&game_id, &game_state);
+    state.games.write().await.insert(game_id, game_state);
+
+    info!(player = %state.player_name, %game_id, "Created game");
+
+    Ok(CreateGameResponse { game_id, resume_token })
+}
+
+async fn join_game(
+    State(state): State<Arc<PlayerState>>,
+    Json(req): Json<JoinGameRequest>,
+) -> Result<Json<JoinGameResponse>, ApiError> {
+    take_seat(&state, req, None).await.map(Json)
+}
+
+/// Join a game at the Oracle as player B and swap payment hashes; with
+/// `rematch`, as the next round of a drawn game.
+pub(crate) async fn take_seat(
+    state: &Arc<PlayerState>,
+    req: JoinGameRequest,
+    rematch: Option<&Rematch>,
+) -> Result<JoinGameResponse, ApiError> {
+    state.check_accepting_games().await?;
+
+    // The stake must be covered before we take the seat, unless it is
+    // still held from the draw
+    let reused_preimage = rematch.and_then(|r| r.preimage.clone());
+    if reused_preimage.is_none() {
+        let status_url = format!("{}/game/{}/status", state.oracle_url, req.game_id);
+        let resp = state
+            .oracle_get(&status_url)
+            .send()
+            .await
+            .map_err(|e| ApiError::upstream(e.to_string()))?;
+        if !resp.status().is_success() {
+            return Err(oracle_error(resp).await);
+        }
+        let status: oracle::GameStatusResponse =
+            resp.json().await.map_err(|e| ApiError::upstream(e.to_string()))?;
+        state.check_funds(status.amount_shannons).await?;
+    }
+
+    let url = format!("{}/game/{}/join", state.oracle_url, req.game_id);
+    info!(player = %state.player_name, game_id = %req.game_id, %url, "Joining game");
+
+    let body = oracle::JoinGameRequest {
+        player_b_id: state.player_id,
+    };
+
+    let response = state
+        .oracle_post(&url, Some(req.game_id), &body)?
+        .send()
+        .await
+        .map_err(|e| {
+            error!(player = %state.player_name, game_id = %req.game_id, error = %e, "Failed to send join request");
+            ApiError::upstream(e.to_string())
+        })?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let err = oracle_error(response).await;
+        error!(player = %state.player_name, game_id = %req.game_id, error = %err, "Oracle refused join");
+        return Err(err);
+    }
+    let text = response.text().await.map_err(|e| {
+        error!(player = %state.player_name, game_id = %req.game_id, error = %e, "Failed to read join response");
+        ApiError::upstream(e.to_string())
+    })?;
+
+    info!(player = %state.player_name, game_id = %req.game_id, %status, body = %text, "Join response");
+
+    let resp: oracle::JoinGameResponse = serde_json::from_str(&text).map_err(|e| {
+        error!(player = %state.player_name, game_id = %req.game_id, error = %e, "Failed to parse join response");
+        ApiError::upstream(format!("Invalid JSON response: {}", e))
+    })?;
+
+    let (oracle_pubkey, commitment_point) =
+        parse_oracle_keys(&resp.oracle_pubkey, &resp.commitment_point)?;
+
+    let mut session = GameSession::new(
+        req.game_id,
+        Player::B,
+        resp.game_type,
+        resp.amount_shannons,
+        oracle_pubkey,
+        commitment_point,
+    );
+    if let Some(preimage) = reused_preimage {
+        session = session.reusing(preimage);
+    }
+
+    // =========================================================================
+    // Payment hash setup: B submits its hash, gets A's hash
+    // Invoice creation is handled by the frontend via direct Fiber RPC
+    // =========================================================================
+
+    // 1. Submit MY (B's) payment_hash to Oracle (so A can get it to create their invoice)
+    let submit_hash_url = format!("{}/game/{}/payment-hash", state.oracle_url, req.game_id);
+    let submit_hash_body = oracle::SubmitPaymentHashRequest {
+        player: Player::B,
+        payment_hash: session.payment_hash(),
+        preimage: session.preimage().clone(),
+    };
+
+    let hash_resp = state.oracle_post(&submit_hash_url, Some(req.game_id), &submit_hash_body)?
+        .send()
+        .await
+        .map_err(|e| ApiError::upstream(format!("Failed to submit payment hash: {}", e)))?;
+    if !hash_resp.status().is_success() {
+        let err = oracle_error(hash_resp).await;
+        return Err(ApiError::new(err.code, format!("Failed to submit payment hash: {}", err)));
+    }
+
+    info!(
+        player = %state.player_name,
+        game_id = %req.game_id,
+        payment_hash = %submit_hash_body.payment_hash.short_hex(),
+        "Submitted payment_hash to Oracle"
+    );
+
+    // 2. Get opponent's (A's) payment_hash from Oracle
+    let get_hash_url = format!("{}/game/{}/payment-hash/A", state.oracle_url, req.game_id);
+    let opponent_hash_data: oracle::PaymentHashResponse = state
+        .oracle_get_sealed(&get_hash_url, Some(&oracle_pubkey))
+        .await
+        .map_err(|e| ApiError::new(e.code, format!("Failed to get opponent payment hash: {}", e)))?;
+    let opponent_payment_hash = opponent_hash_data.payment_hash;
+
+    info!(
+        player = %state.player_name,
+        game_id = %req.game_id,
+        payment_hash = %opponent_payment_hash.short_hex(),
+        "Got opponent's payment_hash"
+    );
+
+    // Note: Invoice creation and payment are now handled by the frontend
+    // The frontend will:
+    // 1. Create a hold invoice on its Fiber node using opponent's payment_hash
+    // 2. Report it via POST /api/game/{id}/invoice-created (we send it to A
+    //    directly, or sign and forward it to the Oracle)
+    // 3. Get opponent's invoice via GET /api/game/{id}/opponent-invoice and
+    //    pay via Fiber RPC
+    // 4. Report back via POST /api/game/{id}/payment-done
+
+    // Save game state
+    let resume_token = resp.resume_token;
+    let mut game_state = PlayerGameState::new(session.joined(opponent_payment_hash));
+    game_state.resume_token = resume_token.clone();
+    game_state.private = resp.private.then(PrivateExchange::default);
+    game_state.draw_policy = resp.draw_policy;
+    game_state.round = resp.round;
+    game_state.replays = rematch.map(|r| r.of);
+
+    state.persist(&req.game_id, &game_state);
+    state.games.write().await.insert(req.game_id, game_state);
+    rematch::carry_over(state, req.game_id).await;
+
+    // Exchange invoices with A directly if they accept connections
+    if let Some(peer_url) = resp.peer_url {
+        tokio::spawn(p2p::dial(state.clone(), req.game_id, peer_url));
+    }
+
+    info!(player = %state.player_name, game_id = %req.game_id, "Joined game");
+
+    Ok(JoinGameResponse {
+        status: "joined".to_string(),
+        resume_token,
+    })
+}
+
+/// Take back a seat this service has lost track of (after a crash, or on a
+/// fresh install) using the token the oracle issued when it was taken.
+async fn resume_game(
+    State(state): State<Arc<PlayerState>>,
+    Json(req): Json<ResumeRequest>,
+) -> Result<Json<ResumeResponse>, ApiError> {
+    // The game may be signed with a key the oracle has since rotated away from
+    let keys_url = format!("{}/oracle/keys", state.oracle_url);
+    let keys_resp: oracle::OracleKeysResponse = state
+        .oracle_get(&keys_url)
+        .send()
+        .await
+        .map_err(|e| ApiError::upstream(e.to_string()))?
+        .json()
+        .await
+        .map_err(|e| ApiError::upstream(e.to_string()))?;
+    let not_issued = || {
+        ApiError::new(
+            ErrorCode::InvalidSignature,
+            "Resumption token was not issued by this oracle",
+        )
+    };
+    let (oracle_pubkey, token) = req.token.clone().open().map_err(|_| not_issued())?;
+    if !keys_resp
+        .keys
+        .iter()
+        .any(|key| key.pubkey == hex::encode(oracle_pubkey.serialize()))
+    {
+        return Err(not_issued());
+    }
+    if state.games.read().await.contains_key(&token.game_id) {
+        return Err(ApiError::conflict("Game is already active on this player"));
+    }
+
+    let url = format!("{}/game/{}/resume", state.oracle_url, token.game_id);
+    let body = oracle::ResumeRequest {
+        token: req.token.clone(),
+    };
+    let snapshot: GameSnapshot = state
+        .oracle_post_sealed(&url, token.game_id, &body, &oracle_pubkey)
+        .await
+        .map_err(|e| ApiError::new(e.code, format!("Oracle refused to resume game: {}", e)))?;
+    if snapshot.game_id != token.game_id || snapshot.player != token.player {
+        return Err(ApiError::upstream("Oracle sent a snapshot of another seat"));
+    }
+
+    let mut game_state = PlayerGameState::new(AnySession::resume(&snapshot, oracle_pubkey)?);
+    game_state.my_invoice_string = snapshot.invoice.clone();
+    game_state.opponent_invoice_string = snapshot.opponent_invoice.clone();
+    game_state.resume_token = Some(req.token);
+    game_state
+        .timeline
+        .push(state.event(snapshot.player, Actor::Oracle, ProtocolStep::Resumed));
+    let phase = game_state.phase();
+
+    state.persist(&token.game_id, &game_state);
+    state.games.write().await.insert(token.game_id, game_state);
+
+    // Invoices not yet exchanged can still go over a direct link
+    if let (Player::B, Some(peer_url), None) =
+        (snapshot.player, &snapshot.peer_url, &snapshot.opponent_invoice)
+    {
+        tokio::spawn(p2p::dial(state.clone(), token.game_id, peer_url.clone()));
+    }
+
+    info!(
+        player = %state.player_name,
+        game_id = %token.game_id,
+        role = %snapshot.player,
+        ?phase,
+        "Resumed game"
+    );
+
+    Ok(Json(ResumeResponse { game_id: token.game_id, phase }))
+}
+
+async fn play(
+    State(state): State<Arc<PlayerState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+    Json(req): Json<PlayRequest>,
+) -> Result<Json<PlayResponse>, ApiError> {
+    // =========================================================================
+    // Game flow: commit + reveal
+    //
+    // Invoice creation and payment are handled entirely by the frontend
+    // via direct Fiber RPC calls. The backend only manages game state.
+    // =========================================================================
+    check_opponent_joined(&state, game_id).await;
+    let mock = state.fiber_backend().await == FiberBackend::Mock;
+    let (committed, commit_sent, private) = {
+        let mut games = state.games.write().await;
+        let game = games.get_mut(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+        if game.session.stage() == Joined::NAME {
+            // The mock frontend makes no payments, so there is nothing to wait for
+            if !mock {
+                return Err(ApiError::invalid_state("Pay the opponent's invoice before playing"));
+            }
+            game.session.advance(|s: GameSession<Joined>| Ok(s.fund()))?;
+            state.persist(&game_id, game);
+        }
+        if game.session.stage() == Committed::NAME {
+            // An earlier attempt got the commitment to the Oracle but not the
+            // reveal; send the same reveal again
+            let committed = game.session.get::<Committed>()?;
+            if committed.state().action != req.action {
+                return Err(ApiError::conflict("Already committed to a different action"));
+            }
+            (committed, true, game.private.is_some())
+        } else {
+            let committed = game.session.get::<Funded>()?.commit(req.action)?;
+            (committed, false, game.private.is_some())
+        }
+    };
+    let role = committed.role();
+    let commitment = committed.state().commitment;
+
+    if mock && !commit_sent {
+        confirm_stake(&state, game_id).await?;
+    }
+    if !commit_sent {
+        // Submit commitment to Oracle
+        let commit_url = format!("{}/game/{}/commit", state.oracle_url, game_id);
+        let commit_body = CommitMessage {
+            game_id,
+            player: role,
+            commitment,
+            amount_shannons: committed.amount_shannons(),
+            opponent_payment_hash: committed.state().opponent_payment_hash,
+        };
+
+        let resp = state
+            .oracle_post(&commit_url, Some(game_id), &commit_body)?
+            .send()
+            .await
+            .map_err(|e| ApiError::upstream(e.to_string()))?;
+        state.check_oracle(&game_id, &resp).await?;
+        if !resp.status().is_success() {
+            return Err(oracle_error(resp).await);
+        }
+
+        info!(player = %state.player_name, %game_id, "Submitted commitment");
+
+        let mut games = state.games.write().await;
+        let game = games.get_mut(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+        game.session
+            .advance(|_: GameSession<Funded>| Ok(committed.clone()))?;
+        state.persist(&game_id, game);
+    }
+
+    if private {
+        let status = privacy::advance(&state, game_id).await?;
+        return Ok(Json(PlayResponse { status }));
+    }
+    let status = reveal_to_oracle(&state, game_id).await?;
+    Ok(Json(PlayResponse { status }))
+}
+
+/// Send our action and salt to the oracle, answering with its status.
+pub(crate) async fn reveal_to_oracle(
+    state: &PlayerState,
+    game_id: GameId,
+) -> Result<String, ApiError> {
+    let committed = {
+        let games = state.games.read().await;
+        let game = games.get(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+        game.session.get::<Committed>()?
+    };
+    let role = committed.role();
+    let commitment = committed.state().commitment;
+
+    let reveal_url = format!("{}/game/{}/reveal", state.oracle_url, game_id);
+    let reveal_body = RevealMessage {
+        game_id,
+        player: role,
+        action: committed.state().action.clone(),
+        salt: committed.salt().clone(),
+        commit_a: commitment,
+        commit_b: commitment,
+    };
+
+    let reveal_resp = state
+        .oracle_post(&reveal_url, Some(game_id), &reveal_body)?
+        .send()
+        .await
+        .map_err(|e| ApiError::upstream(e.to_string()))?;
+    state.check_oracle(&game_id, &reveal_resp).await?;
+    if !reveal_resp.status().is_success() {
+        return Err(oracle_error(reveal_resp).await);
+    }
+
+    let reveal_result: oracle::StatusResponse = reveal_resp
+        .json()
+        .await
+        .map_err(|e| ApiError::upstream(e.to_string()))?;
+    let status = reveal_result.status;
+
+    info!(player = %state.player_name, %game_id, %status, "Submitted reveal");
+
+    let mut games = state.games.write().await;
+    let game = games.get_mut(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+    game.session
+        .advance(|s: GameSession<Committed>| Ok(s.reveal()))?;
+    state.persist(&game_id, game);
+    Ok(status)
+}
+
+async fn get_game_status(
+    State(state): State<Arc<PlayerState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+) -> Result<Json<GameStatusResponse>, ApiError> {
+    // If waiting for opponent, check if opponent has joined
+    // (Frontend will handle invoice creation via direct Fiber RPC)
+    check_opponent_joined(&state, game_id).await;
+    check_cancelled(&state, game_id).await;
+    rematch::carry_over(&state, game_id).await;
+    rematch::advance(&state, game_id).await;
+
+    // The mock frontend makes no payments, so there is nothing to find held
+    if state.fiber_backend().await == FiberBackend::Mock && awaits_stake(&state, &game_id).await {
+        if let Err(e) = confirm_stake(&state, game_id).await {
+            warn!(player = %state.player_name, %game_id, error = %e, "Could not confirm opponent's stake");
+        }
+    }
+
+    // A private game exchanges reveals with the opponent before there is
+    // anything to ask the Oracle for
+    if privacy::is_exchanging(&state, &game_id).await {
+        if let Err(e) = privacy::advance(&state, game_id).await {
+            warn!(player = %state.player_name, %game_id, error = %e, "Private reveal exchange stalled");
+        }
+    }
+
+    // Check if we need to poll Oracle for result
+    let (should_poll, oracle_pubkey) = {
+        let games = state.games.read().await;
+        let game = games.get(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+        let verdict_sent = game.private.as_ref().is_some_and(|p| p.verdict_sent);
+        let stage = game.session.stage();
+        (
+            !game.oracle_lost
+                && (stage == Revealed::NAME || (stage == Committed::NAME && verdict_sent)),
+            *game.session.oracle_pubkey(),
+        )
+    };
+
+    if should_poll {
+        // The result decides who gets paid, so only trust one signed by the
+        // oracle this game was set up with
+        let url = format!("{}/game/{}/result", state.oracle_url, game_id);
+        let result_data: oracle::GameResultResponse = state
+            .oracle_get_sealed(&url, Some(&oracle_pubkey))
+            .await?;
+
+        if let ("completed", Some(result)) = (result_data.status.as_str(), result_data.result) {
+            let mut games = state.games.write().await;
+            let game = games.get_mut(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+            let role = game.role();
+
+            // Extract opponent's preimage if we won (Oracle returns it)
+            let opponent_preimage = result_data.preimage_for(role);
+            let with_preimage = opponent_preimage.is_some();
+            if with_preimage {
+                info!(player = %state.player_name, %game_id, "Got opponent's preimage from Oracle");
+            }
+
+            if game.session.stage() == Committed::NAME {
+                // Private game decided by our verdicts; nothing went to the
+                // Oracle to reveal
+                game.session.advance(|s: GameSession<Committed>| {
+                    s.reveal().judge(result, opponent_preimage)
+                })?;
+            } else {
+                game.session
+                    .advance(|s: GameSession<Revealed>| s.judge(result, opponent_preimage))?;
+            }
+
+            if let Some(game_data) = result_data.game_data {
+                game.opponent_action = Some(match role {
+                    Player::A => game_data.action_b,
+                    Player::B => game_data.action_a,
+                });
+                // Oracle's secret number for Guess Number games
+                game.oracle_secret_number = game_data.oracle_secret.map(|s| s.secret_number);
+            }
+
+            let mut detail = result.as_str().to_string();
+            if with_preimage {
+                detail.push_str(", with opponent's preimage");
+            }
+            game.timeline.push(
+                state.event(Actor::Oracle, role, ProtocolStep::ResultReceived)
+                    .with_detail(detail),
+            );
+            state.persist(&game_id, game);
+        }
+    }
+
+    let settlement = settlement::track(&state, game_id).await;
+
+    let games = state.games.read().await;
+    let game = games.get(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+    let session = &game.session;
+
+    // Winner, loser, and draw can all settle
+    // Winner: settle_invoice (claim funds) on frontend
+    // Loser: cancel_invoice (release held funds) on frontend
+    // Draw: cancel_invoice on frontend
+    let can_settle = session.stage() == Judged::NAME;
+
+    // Provide hex-encoded hashes/preimage for frontend Fiber RPC calls
+    let (opponent_payment_hash_hex, opponent_preimage_hex) = opponent_invoice_hex(session);
+    let my_payment_hash_hex = Some(format!("0x{}", hex::encode(session.payment_hash().as_bytes())));
+    let (aborted_by, abort_reason) = match session {
+        AnySession::Aborted(s) => (Some(s.state().by), Some(s.state().reason)),
+        _ => (None, None),
+    };
+
+    Ok(Json(GameStatusResponse {
+        role: game.role(),
+        phase: game.phase(),
+        result: session.result(),
+        my_action: session.action().cloned(),
+        opponent_action: game.opponent_action.clone(),
+        can_settle,
+        opponent_payment_hash: opponent_payment_hash_hex,
+        opponent_preimage: opponent_preimage_hex,
+        my_payment_hash: my_payment_hash_hex,
+        oracle_secret_number: game.oracle_secret_number,
+        aborted_by,
+        abort_reason,
+        private: game.private.is_some(),
+        direct_link: state.peers.is_open(&game_id),
+        stake_confirmed: game.stake_confirmed,
+        invoice_dropped: game.invoice_dropped,
+        settlement,
+        draw_policy: game.draw_policy,
+        round: game.round,
+        replayed: game.replays_draw(),
+        rematch: game.rematch,
+        keeps_invoice: game.keeps_invoice(),
+        invoices_reused: game.invoices_reused,
+    }))
+}
+
+/// The invoice holding the opponent's payment and, if we won, the preimage
+/// settling it, hex-encoded for the frontend's Fiber RPC calls
+fn opponent_invoice_hex(session: &AnySession) -> (Option<String>, Option<String>) {
+    let payment_hash = session
+        .opponent_payment_hash()
+        .map(|h| format!("0x{}", hex::encode(h.as_bytes())));
+    let preimage = session
+        .opponent_preimage()
+        .map(|p| format!("0x{}", hex::encode(p.as_bytes())));
+    (payment_hash, preimage)
+}
+
+async fn settle(
+    State(state): State<Arc<PlayerState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+) -> Result<Json<SettleResponse>, ApiError> {
+    let settled = settle_game(&state, game_id).await?;
+    rematch::advance(&state, game_id).await;
+    Ok(Json(settled))
+}
+
+/// How many games `settle-all` settles at a time
+const SETTLE_ALL_PARALLELISM: usize = 8;
+
+/// Settle every game that has a result, a few at a time. As with `/settle`,
+/// the frontend settles or cancels each game's invoice on its own node; the
+/// report carries the hashes and preimages to do that with.
+async fn settle_all(State(state): State<Arc<PlayerState>>) -> Json<SettleAllResponse> {
+    let ready: Vec<GameId> = {
+        let games = state.games.read().await;
+        games
+            .iter()
+            .filter(|(_, g)| g.session.stage() == Judged::NAME)
+            .map(|(id, _)| *id)
+            .collect()
+    };
+
+    let mut games: Vec<SettledGame> = stream::iter(ready)
+        .map(|game_id| {
+            let state = state.clone();
+            async move {
+                let (settled, error) = match settle_game(&state, game_id).await {
+                    Ok(settled) => (Some(settled), None),
+                    Err(e) => {
+                        warn!(player = %state.player_name, %game_id, error = %e, "Could not settle game");
+                        (None, Some(e.message))
+                    }
+                };
+                let games = state.games.read().await;
+                // A draw replayed on its invoices leaves them alone
+                let (opponent_payment_hash, opponent_preimage) = games
+                    .get(&game_id)
+                    .filter(|g| !g.keeps_invoice())
+                    .map(|g| opponent_invoice_hex(&g.session))
+                    .unwrap_or_default();
+                SettledGame {
+                    game_id,
+                    settled,
+                    error,
+                    opponent_payment_hash,
+                    opponent_preimage,
+                }
+            }
+        })
+        .buffer_unordered(SETTLE_ALL_PARALLELISM)
+        .collect()
+        .await;
+    games.sort_by_key(|g| *g.game_id.as_uuid());
+    info!(player = %state.player_name, games = games.len(), "Settled all games with a result");
+    Json(SettleAllResponse { games })
+}
+
+/// Mark a decided game settled and report it to the Oracle.
+async fn settle_game(state: &PlayerState, game_id: GameId) -> Result<SettleResponse, ApiError> {
+    let mut games = state.games.write().await;
+    let game = games.get_mut(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+
+    if game.session.is_settled() {
+        return Err(ApiError::invalid_state("Game already settled"));
+    }
+    let judged = game
+        .session
+        .get::<Judged>()
+        .map_err(|_| ApiError::invalid_state("Game not complete"))?;
+    let (result, role) = (judged.state().result, judged.role());
+    let amount_won = judged.amount_won()?;
+
+    // Settlement logic (Hold Invoice security model):
+    //
+    // All Fiber RPC calls (settle_invoice, cancel_invoice) are now performed
+    // by the frontend directly on the player's own Fiber node.
+    //
+    // The backend only tracks the game phase transition.
+    //
+    // Winner frontend: calls settle_invoice with opponent's preimage (from /status response)
+    // Loser frontend: calls cancel_invoice to refund opponent
+    // Draw frontend: both call cancel_invoice
+
+    info!(
+        player = %state.player_name,
+        %game_id,
+        %role,
+        amount_won,
+        "Marking game as settled"
+    );
+
+    game.session.advance(|_: GameSession<Judged>| judged.settle())?;
+    if let (Some(payment_hash), true) = (game.session.opponent_payment_hash(), amount_won > 0) {
+        state.events.publish(Event::InvoiceSettled { payment_hash });
+    }
+    let detail = match amount_won {
+        0 if game.keeps_invoice() => "draw, invoice kept for the next round".to_string(),
+        0 => "draw, invoice cancelled".to_string(),
+        n if n > 0 => format!("won {} shannons, invoice settled", n),
+        _ => "lost, invoice cancelled".to_string(),
+    };
+    game.timeline
+        .push(state.event(role, role, ProtocolStep::Settled).with_detail(detail));
+    state.persist(&game_id, game);
+    drop(games);
+
+    state.peers.remove(&game_id);
+    state.finish_drain().await;
+
+    if let Err(e) = settlement::report(state, game_id).await {
+        warn!(player = %state.player_name, %game_id, error = %e, "Could not report settlement, will retry");
+    }
+
+    Ok(SettleResponse { result, amount_won })
+}
+
+/// Leave a game before committing. The Oracle cancels it, and the frontend
+/// cancels our hold invoice so the opponent gets their payment back.
+///
+/// A game the oracle lost is aborted here only: there is nobody to tell, and
+/// the opponent's service finds out the same way we did.
+async fn abort(
+    State(state): State<Arc<PlayerState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+    Json(req): Json<AbortRequest>,
+) -> Result<Json<EndGameResponse>, ApiError> {
+    let role = undecided_role(&state, &game_id).await?;
+    if is_oracle_lost(&state, &game_id).await {
+        return Ok(Json(abort_lost(&state, &game_id, role).await));
+    }
+
+    let url = format!("{}/game/{}/abort", state.oracle_url, game_id);
+    let msg = AbortMessage {
+        game_id,
+        player: role,
+        reason: req.reason,
+    };
+    let resp = state
+        .oracle_post(&url, Some(game_id), &msg)?
+        .send()
+        .await
+        .map_err(|e| ApiError::upstream(e.to_string()))?;
+    if let Err(e) = state.check_oracle(&game_id, &resp).await {
+        // Found out just now; abort it here instead
+        if is_oracle_lost(&state, &game_id).await {
+            return Ok(Json(abort_lost(&state, &game_id, role).await));
+        }
+        return Err(e);
+    }
+    if !resp.status().is_success() {
+        return Err(oracle_error(resp).await);
+    }
+
+    info!(player = %state.player_name, %game_id, reason = req.reason.as_str(), "Aborted game");
+    record_abort(
+        &state,
+        &game_id,
+        role,
+        req.reason,
+        state.event(role, Actor::Oracle, ProtocolStep::Aborted)
+            .with_detail(req.reason.as_str()),
+    )
+    .await;
+    Ok(Json(EndGameResponse {
+        status: "cancelled".to_string(),
+    }))
+}
+
+/// Claim the opponent stopped responding. If we had already committed they
+/// forfeit and the result arrives with the next status poll; otherwise the
+/// game is cancelled.
+async fn claim_timeout(
+    State(state): State<Arc<PlayerState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+) -> Result<Json<EndGameResponse>, ApiError> {
+    let role = undecided_role(&state, &game_id).await?;
+
+    let url = format!("{}/game/{}/timeout", state.oracle_url, game_id);
+    let claim = TimeoutClaim {
+        game_id,
+        player: role,
+    };
+    let resp = state
+        .oracle_post(&url, Some(game_id), &claim)?
+        .send()
+        .await
+        .map_err(|e| ApiError::upstream(e.to_string()))?;
+    state.check_oracle(&game_id, &resp).await?;
+    if !resp.status().is_success() {
+        return Err(oracle_error(resp).await);
+    }
+    let body: oracle::StatusResponse = resp.json().await.map_err(|e| ApiError::upstream(e.to_string()))?;
+    let status = body.status;
+
+    info!(player = %state.player_name, %game_id, %status, "Opponent timed out");
+    let event = state.event(role, Actor::Oracle, ProtocolStep::TimeoutClaimed)
+        .with_detail(status.as_str());
+    if status == "cancelled" {
+        record_abort(&state, &game_id, role.opponent(), AbortReason::TimedOut, event).await;
+    } else {
+        let mut games = state.games.write().await;
+        if let Some(game) = games.get_mut(&game_id) {
+            game.timeline.push(event);
+            state.persist(&game_id, game);
+        }
+    }
+    Ok(Json(EndGameResponse { status }))
+}
+
+/// Our role in a game that has no result yet.
+async fn undecided_role(state: &PlayerState, game_id: &GameId) -> Result<Player, ApiError> {
+    let games = state.games.read().await;
+    let game = games.get(game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+    if game.session.is_finished() || game.session.result().is_some() {
+        return Err(ApiError::invalid_state("Game is already decided"));
+    }
+    Ok(game.role())
+}
+
+/// Abort a game the oracle lost, without the oracle.
+async fn abort_lost(state: &PlayerState, game_id: &GameId, role: Player) -> EndGameResponse {
+    info!(player = %state.player_name, %game_id, "Aborted game the oracle lost");
+    record_abort(
+        state,
+        game_id,
+        role,
+        AbortReason::OracleLost,
+        state.event(role, role, ProtocolStep::Aborted)
+            .with_detail(AbortReason::OracleLost.as_str()),
+    )
+    .await;
+    EndGameResponse {
+        status: "cancelled".to_string(),
+    }
+}
+
+/// Whether the oracle restarted without this game, see
+/// [`PlayerState::check_oracle`]
+async fn is_oracle_lost(state: &PlayerState, game_id: &GameId) -> bool {
+    let games = state.games.read().await;
+    games.get(game_id).is_some_and(|g| g.oracle_lost)
+}
+
+/// Move a game to Aborted and close its direct link.
+async fn record_abort(
+    state: &PlayerState,
+    game_id: &GameId,
+    by: Player,
+    reason: AbortReason,
+    event: TimelineEvent,
+) {
+    {
+        let mut games = state.games.write().await;
+        let Some(game) = games.get_mut(game_id) else {
+            return;
+        };
+        if game.session.abort(by, reason).is_err() {
+            return;
+        }
+        game.timeline.push(event);
+        state.persist(game_id, game);
+    }
+    state.peers.remove(game_id);
+    state.finish_drain().await;
+}
+
+/// For a game without a result, ask the Oracle whether it was cancelled by
+/// the opponent's abort or a timeout claim, and record who ended it.
+async fn check_cancelled(state: &PlayerState, game_id: GameId) {
+    let role = match undecided_role(state, &game_id).await {
+        Ok(role) => role,
+        Err(_) => return,
+    };
+
+    let url = format!("{}/game/{}/status", state.oracle_url, game_id);
+    let Ok(resp) = state.oracle_get(&url).send().await else {
+        return;
+    };
+    if state.check_oracle(&game_id, &resp).await.is_err() {
+        return;
+    }
+    let Ok(status_data) = resp.json::<oracle::GameStatusResponse>().await else {
+        return;
+    };
+    if status_data.status != "cancelled" {
+        return;
+    }
+    let Some((by, reason)) = status_data.ending.as_ref().and_then(oracle::GameEnding::cause) else {
+        return;
+    };
+
+    info!(player = %state.player_name, %game_id, by = %by, reason = reason.as_str(), "Game was cancelled");
+    let event = state.event(Actor::Oracle, role, ProtocolStep::Aborted)
+        .with_detail(format!("{:?} {}", by, reason.as_str()));
+    record_abort(state, &game_id, by, reason, event).await;
+}
+
+// ============================================================================
+// Frontend-to-Backend notification handlers
+// ============================================================================
+
+/// Frontend reports that it created an invoice on its Fiber node; we send it
+/// to the opponent directly, or sign and submit it to the Oracle to relay
+async fn player_invoice_created(
+    State(state): State<Arc<PlayerState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+    ValidJson(req): ValidJson<InvoiceCreatedRequest>,
+) -> Result<Json<InvoiceCreatedResponse>, ApiError> {
+    let role = {
+        let games = state.games.read().await;
+        games.get(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?.role()
+    };
+
+    let direct = PeerMessage::Invoice {
+        game_id,
+        player: role,
+        invoice_string: req.invoice_string.clone(),
+    };
+    if !state.send_to_peer(&game_id, direct) {
+        let url = format!("{}/game/{}/invoice", state.oracle_url, game_id);
+        let body = oracle::SubmitInvoiceRequest {
+            player: role,
+            invoice_string: req.invoice_string.clone(),
+        };
+        let resp = state
+            .oracle_post(&url, Some(game_id), &body)?
+            .send()
+            .await
+            .map_err(|e| ApiError::upstream(format!("Failed to submit invoice: {}", e)))?;
+        state.check_oracle(&game_id, &resp).await?;
+        if !resp.status().is_success() {
+            let err = oracle_error(resp).await;
+            return Err(ApiError::new(err.code, format!("Oracle rejected invoice: {}", err)));
+        }
+    }
+
+    let mut games = state.games.write().await;
+    let game = games.get_mut(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+
+    game.my_invoice_string = Some(req.invoice_string);
+    game.timeline.push(
+        state.event(role, role, ProtocolStep::InvoiceCreated)
+            .with_detail(format!("{} shannons", game.session.amount_shannons())),
+    );
+    state.persist(&game_id, game);
+
+    // The invoice on our node is locked to the opponent's payment hash
+    if let Some(payment_hash) = game.session.opponent_payment_hash() {
+        state.events.publish(Event::InvoiceCreated { payment_hash });
+    }
+    info!(player = %state.player_name, %game_id, "Frontend reported invoice created");
+
+    Ok(Json(InvoiceCreatedResponse {
+        status: "ok".to_string(),
+    }))
+}
+
+/// Opponent's invoice for the frontend to pay: the one they sent directly,
+/// or else the one relayed through the Oracle
+async fn get_opponent_invoice(
+    State(state): State<Arc<PlayerState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+) -> Result<Json<OpponentInvoiceResponse>, ApiError> {
+    let (known, role) = {
+        let games = state.games.read().await;
+        let game = games.get(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+        (game.opponent_invoice_string.clone(), game.role())
+    };
+    if let Some(invoice_string) = known {
+        return Ok(Json(OpponentInvoiceResponse { invoice_string }));
+    }
+
+    let url = format!("{}/game/{}/invoice/{:?}", state.oracle_url, game_id, role.opponent());
+    let resp = state
+        .oracle_get(&url)
+        .send()
+        .await
+        .map_err(|e| ApiError::upstream(e.to_string()))?;
+    state.check_oracle(&game_id, &resp).await?;
+    if !resp.status().is_success() {
+        return Err(ApiError::not_found("Opponent invoice not available yet"));
+    }
+    let data: oracle::InvoiceResponse = resp
+        .json()
+        .await
+        .map_err(|_| ApiError::upstream("Invalid invoice response"))?;
+    let invoice_string = data.invoice_string;
+
+    let mut games = state.games.write().await;
+    if let Some(game) = games.get_mut(&game_id) {
+        game.opponent_invoice_string = Some(invoice_string.clone());
+        state.persist(&game_id, game);
+    }
+
+    Ok(Json(OpponentInvoiceResponse { invoice_string }))
+}
+
+/// Frontend reports that it paid the opponent's invoice via Fiber RPC
+async fn player_payment_done(
+    State(state): State<Arc<PlayerState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+    Json(_req): Json<PaymentDoneRequest>,
+) -> Result<Json<PaymentDoneResponse>, ApiError> {
+    let mut games = state.games.write().await;
+    let game = games.get_mut(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+
+    match game.session.advance(|s: GameSession<Joined>| Ok(s.fund())) {
+        Ok(()) => {
+            let role = game.role();
+            game.timeline.push(
+                state.event(role, role.opponent(), ProtocolStep::PaymentSent)
+                    .with_detail(format!("{} shannons", game.session.amount_shannons())),
+            );
+            state.persist(&game_id, game);
+            info!(player = %state.player_name, %game_id, "Frontend reported payment done");
+        }
+        // Reported again, e.g. after a page reload
+        Err(_) if game.session.stage() == Funded::NAME => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    Ok(Json(PaymentDoneResponse {
+        status: "ok".to_string(),
+    }))
+}
+
+/// Frontend reports the opponent's payment is held on its Fiber node
+async fn player_payment_received(
+    State(state): State<Arc<PlayerState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+    Json(_req): Json<PaymentDoneRequest>,
+) -> Result<Json<PaymentDoneResponse>, ApiError> {
+    confirm_stake(&state, game_id).await?;
+    Ok(Json(PaymentDoneResponse {
+        status: "ok".to_string(),
+    }))
+}
+
+/// Whether the opponent's stake could be confirmed but hasn't been yet
+async fn awaits_stake(state: &PlayerState, game_id: &GameId) -> bool {
+    let games = state.games.read().await;
+    games.get(game_id).is_some_and(|g| {
+        let stage = g.session.stage();
+        !g.stake_confirmed && (stage == Joined::NAME || stage == Funded::NAME)
+    })
+}
+
+/// Tell the Oracle the opponent's payment is held on our node, so that one
+/// requiring funding takes commitments.
+pub(crate) async fn confirm_stake(state: &PlayerState, game_id: GameId) -> Result<(), ApiError> {
+    let (role, payment_hash) = {
+        let games = state.games.read().await;
+        let game = games.get(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+        if game.stake_confirmed {
+            return Ok(());
+        }
+        let payment_hash = game
+            .session
+            .opponent_payment_hash()
+            .ok_or_else(|| ApiError::invalid_state("Opponent's payment hash not known yet"))?;
+        (game.role(), payment_hash)
+    };
+
+    let url = format!("{}/game/{}/funded", state.oracle_url, game_id);
+    let body = FundingMessage {
+        game_id,
+        player: role,
+        payment_hash,
+    };
+    let resp = state
+        .oracle_post(&url, Some(game_id), &body)?
+        .send()
+        .await
+        .map_err(|e| ApiError::upstream(e.to_string()))?;
+    state.check_oracle(&game_id, &resp).await?;
+    if !resp.status().is_success() {
+        return Err(oracle_error(resp).await);
+    }
+    info!(player = %state.player_name, %game_id, "Confirmed opponent's stake is held");
+
+    let mut games = state.games.write().await;
+    if let Some(game) = games.get_mut(&game_id) {
+        game.stake_confirmed = true;
+        game.timeline.push(
+            state.event(role, Actor::Oracle, ProtocolStep::StakeConfirmed)
+                .with_detail(format!("{} shannons", game.session.amount_shannons())),
+        );
+        state.persist(&game_id, game);
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Fiber backend switch
+// ============================================================================
+
+async fn get_backend(State(state): State<Arc<PlayerState>>) -> Json<BackendResponse> {
+    Json(backend_response(&state).await)
+}
+
+/// Switch between the mock and the configured Fiber node; `202 Accepted`
+/// while waiting for active games to settle.
+async fn set_backend(
+    State(state): State<Arc<PlayerState>>,
+    Json(req): Json<SetBackendRequest>,
+) -> Result<(StatusCode, Json<BackendResponse>), ApiError> {
+    let code = match state.request_backend(req.backend).await? {
+        BackendSwitch::Switched => StatusCode::OK,
+        BackendSwitch::Draining { .. } => StatusCode::ACCEPTED,
+    };
+    Ok((code, Json(backend_response(&state).await)))
+}
+
+async fn get_balance(
+    State(state): State<Arc<PlayerState>>,
+) -> Result<Json<BalanceResponse>, ApiError> {
+    state.balance().await.map(Json)
+}
+
+/// Player API routes, relative to the API mount point (`/api` when standalone).
+pub fn api_router(state: Arc<PlayerState>) -> Router {
+    let admin = admin_router(&state);
+    Router::new()
+        .route("/player", get(get_player_info))
+        .route("/backend", get(get_backend).post(set_backend))
+        .route("/balance", get(get_balance))
+        .route("/games/available", get(get_available_games))
+        .route("/games/mine", get(get_my_games))
+        .route("/games/settle-all", post(settle_all))
+        .route("/reminders", get(reminders::stream))
+        .route("/game/create", post(create_game))
+        .route("/game/join", post(join_game))
+        .route("/game/resume", post(resume_game))
+        .route("/game/:game_id/play", post(play))
+        .route("/game/:game_id/status", get(get_game_status))
+        .route("/game/:game_id/settle", post(settle))
+        .route("/game/:game_id/abort", post(abort))
+        .route("/game/:game_id/claim-timeout", post(claim_timeout))
+        .route("/game/:game_id/invoice-created", post(player_invoice_created))
+        .route("/game/:game_id/opponent-invoice", get(get_opponent_invoice))
+        .route("/game/:game_id/payment-done", post(player_payment_done))
+        .route("/game/:game_id/payment-received", post(player_payment_received))
+        .route("/p2p/:game_id", get(p2p::accept))
+        .with_state(state)
+        .merge(admin)
+}
+
+/// Feature toggles under `/admin`, or none if the player has no admin token
+fn admin_router(state: &Arc<PlayerState>) -> Router {
+    if !state.admin_secret().is_set() {
+        return Router::new();
+    }
+    Router::new()
+        .nest("/admin", fiber_flags::router(state.features.clone()))
+        .route_layer(middleware::from_extractor_with_state::<AdminToken, _>(
+            state.clone(),
+        ))
+}
@@ -0,0 +1,336 @@
+//! Fiber Game Player Service
+//!
+//! HTTP service with Web UI for players to create/join games and play.
+//! All Fiber payments are made by the frontend directly — the backend
+//! handles game state management and Oracle communication, and only reads
+//! the node to check held invoices ([`reconcile`]).
+//!
+//! Player identity and games can optionally be persisted through a
+//! [`storage::PlayerStore`], which the combined demo also uses. One service
+//! can host further players besides its own ([`profiles`]).
+
+mod handlers;
+mod p2p;
+mod privacy;
+pub mod profiles;
+pub mod reconcile;
+pub mod reminders;
+mod rematch;
+mod settlement;
+pub mod state;
+pub mod storage;
+
+use axum::Router;
+use clap::ArgAction;
+use fiber_auth::webhook::WebhookSigner;
+use fiber_config::ServiceConfig;
+use fiber_flags::{FeatureFlags, FlagArgs};
+use fiber_game_core::crypto::Keyring;
+use fiber_game_core::protocol::Encoding;
+use fiber_service::ServerArgs;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use profiles::{MakePlayer, Profiles};
+use reminders::{Notifier, WebhookNotifier};
+use std::sync::Arc;
+use std::time::Duration;
+use storage::{PlayerStore, SqlitePlayerStore};
+use tower_http::cors::CorsLayer;
+use tracing::info;
+use uuid::Uuid;
+
+pub use handlers::api_router;
+pub use state::PlayerState;
+
+/// Standalone player service router: the API under `/api`, `/metrics` and
+/// the Web UI.
+pub fn create_router(state: Arc<PlayerState>) -> Router {
+    let metrics = state.metrics().clone();
+    app(api_router(state), metrics)
+}
+
+/// [`create_router`], also serving `profiles` under `/api/profiles`
+pub fn create_router_with_profiles(state: Arc<PlayerState>, profiles: Arc<Profiles>) -> Router {
+    let metrics = state.metrics().clone();
+    app(api_router(state).merge(profiles::router(profiles)), metrics)
+}
+
+fn app(api: Router, metrics: Arc<fiber_service::Metrics>) -> Router {
+    let api = Router::new().nest("/api", api);
+    let app = fiber_service::with_metrics(api, metrics).fallback_service(static_ui());
+    fiber_service::request_tracing(app).layer(CorsLayer::permissive())
+}
+
+/// Web UI assets, compiled in with the `embed-ui` feature
+#[cfg(feature = "embed-ui")]
+#[derive(rust_embed::RustEmbed)]
+#[folder = "static"]
+struct StaticAssets;
+
+#[cfg(feature = "embed-ui")]
+fn static_ui() -> Router {
+    fiber_service::embedded_ui::<StaticAssets>()
+}
+
+#[cfg(not(feature = "embed-ui"))]
+fn static_ui() -> Router {
+    fiber_service::static_dir("static")
+}
+
+/// Player service configuration, the `player` section of a config file
+#[derive(clap::Args, Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    #[command(flatten)]
+    #[serde(flatten)]
+    pub server: ServerArgs,
+    /// Display name shown in the UI
+    #[arg(long, env = "PLAYER_NAME", default_value = "Player")]
+    pub player_name: String,
+    /// Base URL of the Oracle service
+    #[arg(long, env = "ORACLE_URL", default_value = "http://localhost:3000")]
+    pub oracle_url: String,
+    /// Fiber node RPC URL, passed to the frontend for direct calls
+    #[arg(long, env = "FIBER_RPC_URL")]
+    pub fiber_rpc_url: Option<String>,
+    /// SQLite file to persist the player ID and games to (in-memory if unset)
+    #[arg(long, env = "PLAYER_DB_PATH")]
+    pub db_path: Option<PathBuf>,
+    /// Bring the database schema up to date at startup; when off, the
+    /// player refuses to start on a database with migrations not applied
+    #[arg(long, env = "PLAYER_AUTO_MIGRATE", default_value_t = true, action = ArgAction::Set)]
+    pub auto_migrate: bool,
+    /// Hex storage keys to encrypt the signing key and games in the database with, current
+    /// first; older keys after it are only read, and rewritten under the
+    /// current one at startup (stored in plaintext if neither this nor
+    /// `storage_key_file` is set)
+    #[arg(long, env = "PLAYER_STORAGE_KEY")]
+    pub storage_key: Option<String>,
+    /// File holding the storage keys, one per line, current first
+    #[arg(long, env = "PLAYER_STORAGE_KEY_FILE")]
+    pub storage_key_file: Option<PathBuf>,
+    /// WebSocket URL of this service's `/api/p2p` endpoint as reachable by
+    /// opponents; invoices are relayed through the oracle if unset
+    #[arg(long, env = "PLAYER_P2P_URL")]
+    pub p2p_url: Option<String>,
+    /// Encoding for protocol messages we send: `json` or `cbor`
+    #[arg(long, env = "PLAYER_ENCODING", default_value = "json")]
+    pub encoding: Encoding,
+    /// Bearer token for the feature toggles at `/api/admin/features` (not
+    /// served if unset)
+    #[arg(long, env = "PLAYER_ADMIN_TOKEN")]
+    pub admin_token: Option<String>,
+    /// Secret key (hex) of the identity shared with the other Fiber
+    /// services; it signs our protocol messages and names our player ID
+    #[arg(long, env = "PLAYER_IDENTITY_KEY")]
+    pub identity_key: Option<String>,
+    /// Remind of a step once its deadline is this many seconds away
+    /// (default 60)
+    #[arg(long, env = "PLAYER_REMIND_WITHIN_SECS")]
+    pub remind_within_secs: Option<u64>,
+    /// URLs to POST reminders of steps coming due to
+    #[arg(long = "reminder-webhook", env = "PLAYER_REMINDER_WEBHOOKS", value_delimiter = ',')]
+    #[serde(default)]
+    pub reminder_webhooks: Vec<String>,
+    /// Key reminder webhooks are signed with, `hmac:<secret>` or
+    /// `ed25519:<seed hex>`; without one they are sent unsigned
+    #[arg(long, env = "PLAYER_WEBHOOK_SIGNING_KEY")]
+    pub webhook_signing_key: Option<String>,
+    /// Further players to host under `/api/profiles/<id>`, each `id` or
+    /// `id=fiber_rpc_url`; more can be added with the admin token
+    #[arg(long = "profile", env = "PLAYER_PROFILES", value_delimiter = ',')]
+    #[serde(default)]
+    pub profiles: Vec<String>,
+    /// `p2p_transport`, on unless switched off
+    #[command(flatten)]
+    #[serde(flatten)]
+    pub features: FlagArgs,
+}
+
+impl Config {
+    /// The storage keys configured, if any
+    fn keyring(&self) -> Result<Option<Keyring>, String> {
+        Keyring::load(self.storage_key.as_deref(), self.storage_key_file.as_deref())
+            .map_err(|e| format!("storage_key: {}", e))
+    }
+}
+
+impl ServiceConfig for Config {
+    const SECTION: &'static str = "player";
+
+    fn validate(&self) -> Result<(), String> {
+        fiber_config::check_url("oracle_url", &self.oracle_url, &["http", "https"])?;
+        if let Some(url) = &self.fiber_rpc_url {
+            fiber_config::check_url("fiber_rpc_url", url, &["http", "https"])?;
+        }
+        if let Some(url) = &self.p2p_url {
+            fiber_config::check_url("p2p_url", url, &["ws", "wss"])?;
+        }
+        for url in &self.reminder_webhooks {
+            fiber_config::check_url("reminder_webhooks", url, &["http", "https"])?;
+        }
+        if let Some(key) = &self.webhook_signing_key {
+            key.parse::<WebhookSigner>()
+                .map_err(|e| format!("webhook_signing_key: {}", e))?;
+        }
+        for spec in &self.profiles {
+            profiles::parse_profile(spec)?;
+        }
+        self.keyring()?;
+        if let Some(key) = &self.identity_key {
+            key.parse::<secp256k1::SecretKey>()
+                .map_err(|_| "identity_key: not a hex secp256k1 secret key".to_string())?;
+        }
+        self.features.check(state::FEATURES)
+    }
+}
+
+/// Run the standalone player service until the process exits.
+pub async fn run(config: Config) -> std::io::Result<()> {
+    let port = config.server.port_or(3001);
+
+    if let Some(ref url) = config.fiber_rpc_url {
+        info!("Fiber RPC URL: {} (frontend will call directly)", url);
+    } else {
+        info!("No FIBER_RPC_URL set (mock mode — no real Fiber payments)");
+    }
+
+    let store: Option<Arc<dyn PlayerStore>> = match &config.db_path {
+        Some(path) => {
+            let store = if config.auto_migrate {
+                SqlitePlayerStore::open(path)
+            } else {
+                SqlitePlayerStore::open_without_migrating(path)
+            }
+            .expect("failed to open player database");
+            let store = match config.keyring() {
+                Ok(Some(keyring)) => {
+                    info!("Encrypting stored secrets with storage key {}", keyring.current_id());
+                    store.with_keyring(keyring).expect("failed to encrypt player database")
+                }
+                Ok(None) => store,
+                Err(e) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, e)),
+            };
+            info!("Persisting player state to {}", path.display());
+            Some(Arc::new(store))
+        }
+        None => None,
+    };
+    let state = match &store {
+        Some(store) => PlayerState::open(
+            store.clone(),
+            "player",
+            config.player_name,
+            config.oracle_url.clone(),
+            config.fiber_rpc_url,
+        )
+        .expect("failed to restore player state"),
+        None => PlayerState::new(
+            Uuid::new_v4(),
+            config.player_name,
+            config.oracle_url.clone(),
+            config.fiber_rpc_url,
+        ),
+    };
+    if let Some(ref url) = config.p2p_url {
+        info!("Accepting direct opponent connections at {}", url);
+    }
+    let features = FeatureFlags::configured(state::FEATURES, &config.features)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let state = match config.identity_key.as_deref().map(str::parse) {
+        Some(Ok(secret)) => {
+            let state = state.with_identity(secret);
+            info!("Playing as identity {}", state.player_id);
+            state
+        }
+        _ => state,
+    };
+    let remind_within = config.remind_within_secs.map(Duration::from_secs);
+    let state = match remind_within {
+        Some(within) => state.with_remind_within(within),
+        None => state,
+    };
+    let state = Arc::new(
+        state
+            .with_p2p_url(config.p2p_url.clone())
+            .with_encoding(config.encoding)
+            .with_features(features.clone())
+            .with_admin_token(config.admin_token.clone()),
+    );
+    let signer = config
+        .webhook_signing_key
+        .as_deref()
+        .map(str::parse::<WebhookSigner>)
+        .transpose()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    if let Some(key) = signer.as_ref().and_then(WebhookSigner::public_key) {
+        info!("Signing reminder webhooks, verify with {}", key);
+    }
+    let notifiers: Vec<Arc<dyn Notifier>> = config
+        .reminder_webhooks
+        .iter()
+        .map(|url| {
+            info!("Sending reminders to {}", url);
+            Arc::new(WebhookNotifier::new(url).with_signer(signer.clone())) as Arc<dyn Notifier>
+        })
+        .collect();
+    reminders::spawn(&state, notifiers.clone());
+    reconcile::spawn(&state);
+
+    // Profiles are set up like the service's own player, with identities
+    // kept apart from it under `profile-<id>`
+    let make: MakePlayer = {
+        let (store, oracle_url) = (store.clone(), config.oracle_url.clone());
+        let (p2p_url, admin_token) = (config.p2p_url.clone(), config.admin_token.clone());
+        let (encoding, metrics) = (config.encoding, state.metrics().clone());
+        Box::new(move |id, settings| {
+            let (name, fiber_rpc_url) = (settings.name.clone(), settings.fiber_rpc_url.clone());
+            let player = match &store {
+                Some(store) => PlayerState::open(
+                    store.clone(),
+                    &format!("profile-{}", id),
+                    name,
+                    oracle_url.clone(),
+                    fiber_rpc_url,
+                )?,
+                None => PlayerState::new(Uuid::new_v4(), name, oracle_url.clone(), fiber_rpc_url),
+            };
+            let player = match remind_within {
+                Some(within) => player.with_remind_within(within),
+                None => player,
+            };
+            // `.../api/p2p` becomes `.../api/profiles/<id>/p2p`
+            let p2p_url = p2p_url
+                .as_deref()
+                .and_then(|url| url.strip_suffix("/p2p"))
+                .map(|base| format!("{}/profiles/{}/p2p", base, id));
+            Ok(player
+                .with_p2p_url(p2p_url)
+                .with_encoding(encoding)
+                .with_features(features.clone())
+                .with_admin_token(admin_token.clone())
+                .with_metrics(metrics.clone()))
+        })
+    };
+    let mut profiles = Profiles::new(make)
+        .with_notifiers(notifiers)
+        .with_admin_token(config.admin_token);
+    if let Some(store) = store {
+        profiles = profiles
+            .with_store(store)
+            .expect("failed to restore player profiles");
+    }
+    for spec in &config.profiles {
+        let (id, settings) = profiles::parse_profile(spec)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        profiles
+            .add(&id, &settings)
+            .expect("failed to save player profile");
+    }
+
+    info!("Player '{}' ID: {}", state.player_name(), state.player_id());
+    info!("Player service listening on http://0.0.0.0:{}", port);
+    info!("  All Fiber RPC calls are made by the frontend directly");
+
+    let app = create_router_with_profiles(state, Arc::new(profiles));
+    fiber_service::serve(app, port).await
+}
@@ -0,0 +1,521 @@
+//! Direct player-to-player channel.
+//!
+//! Payment hashes and hold invoices only concern the two players, so once
+//! both are in a game they exchange them over a WebSocket rather than
+//! through the oracle. Player A advertises the URL of its `/p2p` endpoint
+//! when creating the game; the oracle hands it to B on join and B dials in.
+//!
+//! Every frame is a [`PeerMessage`] sealed in an [`Envelope`] with the
+//! sender's protocol key, as a text frame of JSON or a binary frame of CBOR
+//! depending on the sender's configured encoding. The first verified frame pins the opponent's key,
+//! and later frames signed by anyone else end the link.
+//!
+//! The link is an optimisation, not a dependency: if B can't connect, or the
+//! link drops, both sides carry on through the oracle relay. Private games
+//! also reveal over it, see [`crate::privacy`].
+
+use crate::state::{PlayerState, P2P_TRANSPORT};
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use fiber_errors::ValidPath;
+use fiber_game_core::{
+    crypto::{PaymentHash, Salt},
+    games::GameAction,
+    protocol::{
+        Created, Encoding, EncodingError, Envelope, GameId, GameSession, Player, ProtocolStep,
+    },
+};
+use futures_util::{Sink, SinkExt, Stream, StreamExt};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::ready;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+/// How long B waits for A's endpoint before settling for the oracle relay
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A message between the two players of a game
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PeerMessage {
+    /// Sender's payment hash, for the receiver to create their invoice with
+    PaymentHash {
+        game_id: GameId,
+        player: Player,
+        payment_hash: PaymentHash,
+    },
+    /// Sender's hold invoice, for the receiver to pay
+    Invoice {
+        game_id: GameId,
+        player: Player,
+        invoice_string: String,
+    },
+    /// Sender's action and salt, in a private game once both have committed
+    Reveal {
+        game_id: GameId,
+        player: Player,
+        action: GameAction,
+        salt: Salt,
+    },
+}
+
+impl PeerMessage {
+    fn game_id(&self) -> GameId {
+        match self {
+            PeerMessage::PaymentHash { game_id, .. }
+            | PeerMessage::Invoice { game_id, .. }
+            | PeerMessage::Reveal { game_id, .. } => *game_id,
+        }
+    }
+
+    fn player(&self) -> Player {
+        match self {
+            PeerMessage::PaymentHash { player, .. }
+            | PeerMessage::Invoice { player, .. }
+            | PeerMessage::Reveal { player, .. } => *player,
+        }
+    }
+}
+
+/// One WebSocket frame: JSON travels as text, CBOR as binary
+#[derive(Clone, Debug)]
+enum Frame {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+impl Frame {
+    fn encode<T: Serialize>(encoding: Encoding, value: &T) -> Result<Self, EncodingError> {
+        let bytes = encoding.encode(value)?;
+        Ok(match encoding {
+            Encoding::Json => Frame::Text(String::from_utf8(bytes).expect("JSON is UTF-8")),
+            Encoding::Cbor => Frame::Binary(bytes),
+        })
+    }
+
+    fn decode<T: DeserializeOwned>(&self) -> Result<T, EncodingError> {
+        match self {
+            Frame::Text(text) => Encoding::Json.decode(text.as_bytes()),
+            Frame::Binary(bytes) => Encoding::Cbor.decode(bytes),
+        }
+    }
+}
+
+/// Open links to opponents, keyed by game
+#[derive(Default)]
+pub(crate) struct PeerLinks(Mutex<HashMap<GameId, mpsc::UnboundedSender<Frame>>>);
+
+impl PeerLinks {
+    fn insert(&self, game_id: GameId, tx: mpsc::UnboundedSender<Frame>) {
+        self.0.lock().unwrap().insert(game_id, tx);
+    }
+
+    /// Drop the link for `game_id`, which closes the socket.
+    pub(crate) fn remove(&self, game_id: &GameId) {
+        self.0.lock().unwrap().remove(game_id);
+    }
+
+    /// Whether a link for `game_id` is open.
+    pub(crate) fn is_open(&self, game_id: &GameId) -> bool {
+        let links = self.0.lock().unwrap();
+        links.get(game_id).is_some_and(|tx| !tx.is_closed())
+    }
+
+    /// Queue `frame` for the opponent; `false` if there is no live link.
+    fn send(&self, game_id: &GameId, frame: Frame) -> bool {
+        let links = self.0.lock().unwrap();
+        links.get(game_id).is_some_and(|tx| tx.send(frame).is_ok())
+    }
+}
+
+impl PlayerState {
+    /// Send `message` straight to the opponent.
+    ///
+    /// Returns `false` if there is no direct link for the game, in which case
+    /// the caller should relay through the oracle.
+    pub(crate) fn send_to_peer(&self, game_id: &GameId, message: PeerMessage) -> bool {
+        if !self.features.is_enabled(&P2P_TRANSPORT) {
+            return false;
+        }
+        match self.seal(message).map(|e| Frame::encode(self.encoding, &e)) {
+            Ok(Ok(frame)) => self.peers.send(game_id, frame),
+            Ok(Err(e)) => {
+                warn!("{}: Failed to encode peer message: {}", self.player_name, e);
+                false
+            }
+            Err(e) => {
+                warn!("{}: Failed to seal peer message: {}", self.player_name, e);
+                false
+            }
+        }
+    }
+}
+
+/// `GET /p2p/:game_id`: accept the opponent's connection for a game we created.
+pub(crate) async fn accept(
+    ws: WebSocketUpgrade,
+    State(state): State<Arc<PlayerState>>,
+    ValidPath(game_id): ValidPath<GameId>,
+) -> Response {
+    let is_host = {
+        let games = state.games.read().await;
+        games.get(&game_id).is_some_and(|g| g.role() == Player::A)
+    };
+    if !is_host {
+        return (StatusCode::NOT_FOUND, "No game hosted here with that ID").into_response();
+    }
+    if let Err(e) = state.features.require(&P2P_TRANSPORT) {
+        return e.into_response();
+    }
+
+    ws.on_upgrade(move |socket: WebSocket| {
+        let (sink, stream) = socket.split();
+        let sink = sink.with(|frame: Frame| {
+            ready(Ok::<_, axum::Error>(match frame {
+                Frame::Text(text) => Message::Text(text),
+                Frame::Binary(bytes) => Message::Binary(bytes),
+            }))
+        });
+        let stream = stream.filter_map(|msg| {
+            ready(match msg {
+                Ok(Message::Text(text)) => Some(Frame::Text(text)),
+                Ok(Message::Binary(bytes)) => Some(Frame::Binary(bytes)),
+                _ => None,
+            })
+        });
+        run_link(state, game_id, sink, stream)
+    })
+}
+
+/// Connect to the opponent's endpoint for a game we joined.
+///
+/// Failure is only logged: without a link every exchange falls back to the
+/// oracle relay.
+pub(crate) async fn dial(state: Arc<PlayerState>, game_id: GameId, peer_url: String) {
+    use tokio_tungstenite::tungstenite::Message;
+
+    if !state.features.is_enabled(&P2P_TRANSPORT) {
+        return;
+    }
+    let url = format!("{}/{}", peer_url.trim_end_matches('/'), game_id);
+    let socket =
+        match tokio::time::timeout(CONNECT_TIMEOUT, tokio_tungstenite::connect_async(&url)).await {
+            Ok(Ok((socket, _))) => socket,
+            Ok(Err(e)) => {
+                warn!(
+                    "{}: Can't reach opponent at {} ({}), using oracle relay",
+                    state.player_name, url, e
+                );
+                return;
+            }
+            Err(_) => {
+                warn!(
+                    "{}: Timed out connecting to opponent at {}, using oracle relay",
+                    state.player_name, url
+                );
+                return;
+            }
+        };
+
+    let (sink, stream) = socket.split();
+    let sink = sink.with(|frame: Frame| {
+        ready(Ok::<_, tokio_tungstenite::tungstenite::Error>(
+            match frame {
+                Frame::Text(text) => Message::Text(text),
+                Frame::Binary(bytes) => Message::Binary(bytes),
+            },
+        ))
+    });
+    let stream = stream.filter_map(|msg| {
+        ready(match msg {
+            Ok(Message::Text(text)) => Some(Frame::Text(text)),
+            Ok(Message::Binary(bytes)) => Some(Frame::Binary(bytes)),
+            _ => None,
+        })
+    });
+    run_link(state, game_id, sink, stream).await
+}
+
+/// Drive one link until either side closes it or the game is settled.
+async fn run_link<Si, St>(state: Arc<PlayerState>, game_id: GameId, mut sink: Si, mut stream: St)
+where
+    Si: Sink<Frame> + Unpin,
+    St: Stream<Item = Frame> + Unpin,
+{
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    state.peers.insert(game_id, tx);
+    info!(
+        "{}: Direct link to opponent open for game {:?}",
+        state.player_name, game_id
+    );
+
+    // Open with our payment hash, so the opponent needn't ask the oracle
+    let hello = {
+        let games = state.games.read().await;
+        games.get(&game_id).map(|g| PeerMessage::PaymentHash {
+            game_id,
+            player: g.role(),
+            payment_hash: g.session.payment_hash(),
+        })
+    };
+    if let Some(hello) = hello {
+        state.send_to_peer(&game_id, hello);
+    }
+
+    loop {
+        tokio::select! {
+            outgoing = rx.recv() => match outgoing {
+                Some(frame) => {
+                    if sink.send(frame).await.is_err() {
+                        break;
+                    }
+                }
+                // Link removed, e.g. because the game was settled
+                None => break,
+            },
+            incoming = stream.next() => match incoming {
+                Some(frame) => {
+                    if let Err(e) = receive(&state, &game_id, &frame).await {
+                        warn!(player = %state.player_name, %game_id, error = %e, "Dropping peer link");
+                        break;
+                    }
+                }
+                None => break,
+            },
+        }
+    }
+
+    state.peers.remove(&game_id);
+    info!(
+        "{}: Direct link to opponent closed for game {:?}",
+        state.player_name, game_id
+    );
+}
+
+/// Verify and apply one frame from the opponent.
+async fn receive(state: &PlayerState, game_id: &GameId, frame: &Frame) -> Result<(), String> {
+    let envelope: Envelope<serde_json::Value> = frame.decode().map_err(|e| e.to_string())?;
+    let (sender, message): (_, PeerMessage) = envelope.open_as().map_err(|e| e.to_string())?;
+
+    if message.game_id() != *game_id {
+        return Err("message for another game".to_string());
+    }
+
+    let mut games = state.games.write().await;
+    let game = games.get_mut(game_id).ok_or("game not found")?;
+    let role = game.role();
+    let opponent = role.opponent();
+    if message.player() != opponent {
+        return Err("message not from our opponent".to_string());
+    }
+    match game.peer_key {
+        Some(key) if key != sender => return Err("opponent's key changed".to_string()),
+        Some(_) => {}
+        None => game.peer_key = Some(sender),
+    }
+
+    match message {
+        PeerMessage::PaymentHash { payment_hash, .. } => match game.session.opponent_payment_hash() {
+            Some(known) if known != payment_hash => {
+                return Err("payment hash differs from the oracle's copy".to_string());
+            }
+            Some(_) => {}
+            None => {
+                game.session
+                    .advance(|s: GameSession<Created>| Ok(s.joined(payment_hash)))
+                    .map_err(|e| e.to_string())?;
+                game.timeline.push(
+                    state.event(opponent, role, ProtocolStep::PaymentHashSubmitted)
+                        .with_detail("direct"),
+                );
+                info!(
+                    "{}: Got opponent's payment_hash directly for game {:?}",
+                    state.player_name, game_id
+                );
+            }
+        },
+        PeerMessage::Invoice { invoice_string, .. } => {
+            game.opponent_invoice_string = Some(invoice_string);
+            game.timeline.push(
+                state.event(opponent, role, ProtocolStep::InvoiceSubmitted)
+                    .with_detail("direct"),
+            );
+            info!(
+                "{}: Got opponent's invoice directly for game {:?}",
+                state.player_name, game_id
+            );
+        }
+        PeerMessage::Reveal { action, salt, .. } => {
+            let private = game
+                .private
+                .as_mut()
+                .ok_or("reveal for a game that is not private")?;
+            match &private.opponent_reveal {
+                Some((known, _)) if *known != action => {
+                    return Err("opponent changed their reveal".to_string());
+                }
+                Some(_) => {}
+                None => {
+                    private.opponent_reveal = Some((action, salt));
+                    game.timeline.push(
+                        state.event(opponent, role, ProtocolStep::Revealed)
+                            .with_detail("direct"),
+                    );
+                    info!(
+                        "{}: Got opponent's reveal directly for game {:?}",
+                        state.player_name, game_id
+                    );
+                }
+            }
+        }
+    }
+    state.persist(game_id, game);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::tests::{add_game, player, session};
+    use crate::state::PrivateExchange;
+    use fiber_game_core::{crypto::Preimage, games::RpsAction};
+
+    fn frame(key: &secp256k1::SecretKey, message: PeerMessage) -> Frame {
+        Frame::encode(Encoding::Json, &Envelope::seal(message, key).unwrap()).unwrap()
+    }
+
+    fn invoice(game_id: GameId, player: Player) -> PeerMessage {
+        PeerMessage::Invoice {
+            game_id,
+            player,
+            invoice_string: "fibt1000".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_receive_pins_opponent_key() {
+        let a = player(None);
+        let game_id = add_game(&a, session()).await;
+        let b_key = secp256k1::SecretKey::new(&mut secp256k1::rand::thread_rng());
+        let payment_hash = Preimage::random().payment_hash();
+
+        let hello = PeerMessage::PaymentHash {
+            game_id,
+            player: Player::B,
+            payment_hash,
+        };
+        receive(&a, &game_id, &frame(&b_key, hello)).await.unwrap();
+
+        let intruder = secp256k1::SecretKey::new(&mut secp256k1::rand::thread_rng());
+        assert!(
+            receive(&a, &game_id, &frame(&intruder, invoice(game_id, Player::B)))
+                .await
+                .is_err()
+        );
+        // Same key over CBOR, as an opponent configured for it would send
+        let cbor = Frame::encode(
+            Encoding::Cbor,
+            &Envelope::seal(invoice(game_id, Player::B), &b_key).unwrap(),
+        )
+        .unwrap();
+        assert!(matches!(cbor, Frame::Binary(_)));
+        receive(&a, &game_id, &cbor).await.unwrap();
+
+        let games = a.games.read().await;
+        let game = &games[&game_id];
+        assert_eq!(game.session.opponent_payment_hash(), Some(payment_hash));
+        assert_eq!(game.session.stage(), "Joined");
+        assert_eq!(game.opponent_invoice_string.as_deref(), Some("fibt1000"));
+    }
+
+    #[tokio::test]
+    async fn test_receive_reveal_only_for_private_games() {
+        let a = player(None);
+        let game_id = add_game(&a, session().joined(Preimage::random().payment_hash())).await;
+        let key = secp256k1::SecretKey::new(&mut secp256k1::rand::thread_rng());
+        let reveal = |action| PeerMessage::Reveal {
+            game_id,
+            player: Player::B,
+            action: GameAction::Rps(action),
+            salt: Salt::random(),
+        };
+
+        assert!(receive(&a, &game_id, &frame(&key, reveal(RpsAction::Paper)))
+            .await
+            .is_err());
+
+        a.games.write().await.get_mut(&game_id).unwrap().private = Some(PrivateExchange::default());
+        receive(&a, &game_id, &frame(&key, reveal(RpsAction::Paper)))
+            .await
+            .unwrap();
+        // A second look at the same action is fine, a different one is not
+        receive(&a, &game_id, &frame(&key, reveal(RpsAction::Paper)))
+            .await
+            .unwrap();
+        assert!(receive(&a, &game_id, &frame(&key, reveal(RpsAction::Rock)))
+            .await
+            .is_err());
+
+        let games = a.games.read().await;
+        let (action, _) = games[&game_id].private.as_ref().unwrap().opponent_reveal.clone().unwrap();
+        assert_eq!(action, GameAction::Rps(RpsAction::Paper));
+    }
+
+    #[tokio::test]
+    async fn test_receive_rejects_misaddressed_messages() {
+        let a = player(None);
+        let game_id = add_game(&a, session().joined(Preimage::random().payment_hash())).await;
+        let key = secp256k1::SecretKey::new(&mut secp256k1::rand::thread_rng());
+
+        // Claims to be from ourselves
+        assert!(
+            receive(&a, &game_id, &frame(&key, invoice(game_id, Player::A)))
+                .await
+                .is_err()
+        );
+        // Replayed from another game
+        assert!(receive(
+            &a,
+            &game_id,
+            &frame(&key, invoice(GameId::new(), Player::B))
+        )
+        .await
+        .is_err());
+        // Not an envelope at all
+        assert!(receive(&a, &game_id, &Frame::Text("{}".to_string()))
+            .await
+            .is_err());
+        assert!(receive(&a, &game_id, &Frame::Binary(vec![0xff]))
+            .await
+            .is_err());
+
+        assert!(a.games.read().await[&game_id]
+            .opponent_invoice_string
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_switched_off_transport_falls_back_to_relay() {
+        let a = player(None).with_p2p_url(Some("ws://a.example/api/p2p".to_string()));
+        let game_id = add_game(&a, session()).await;
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        a.peers.insert(game_id, tx);
+        assert!(a.send_to_peer(&game_id, invoice(game_id, Player::A)));
+        assert!(rx.try_recv().is_ok());
+
+        a.features().set(P2P_TRANSPORT.name, false);
+        assert_eq!(a.advertised_p2p_url(), None);
+        // An open link is no longer used either
+        assert!(!a.send_to_peer(&game_id, invoice(game_id, Player::A)));
+        assert!(rx.try_recv().is_err());
+    }
+}
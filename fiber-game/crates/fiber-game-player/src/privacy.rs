@@ -0,0 +1,190 @@
+//! Privacy mode: the oracle signs a result without learning the actions.
+//!
+//! Players of a game created with `private` still commit through the oracle,
+//! but reveal only to each other, over the direct link ([`crate::p2p`]).
+//! Each then judges the game itself and sends the oracle a
+//! [`VerdictMessage`]: the result, both commitments and a transcript hash
+//! showing both reveals were seen. The oracle signs the result once the two
+//! verdicts agree.
+//!
+//! Our reveal goes to the opponent only once the oracle holds their
+//! commitment, so seeing our action can't change theirs. Whenever the
+//! exchange can't go on privately (no direct link, a reveal that doesn't open
+//! its commitment, verdicts that disagree, or an opponent who revealed to the
+//! oracle anyway) we reveal to the oracle and the game finishes like a
+//! public one.
+
+use crate::handlers::reveal_to_oracle;
+use crate::p2p::PeerMessage;
+use crate::state::{oracle_error, PlayerState};
+use fiber_errors::ApiError;
+use fiber_game_api::oracle;
+use fiber_game_core::{
+    games,
+    protocol::{Actor, Committed, GameId, Player, ProtocolStep, Stage, VerdictMessage},
+};
+use tracing::{info, warn};
+
+/// Whether `game_id` is a private game still exchanging reveals.
+pub(crate) async fn is_exchanging(state: &PlayerState, game_id: &GameId) -> bool {
+    let games = state.games.read().await;
+    games
+        .get(game_id)
+        .is_some_and(|g| g.private.is_some() && g.session.stage() == Committed::NAME)
+}
+
+/// Take the next step of a private game we have committed to, answering
+/// with where the game stands.
+pub(crate) async fn advance(state: &PlayerState, game_id: GameId) -> Result<String, ApiError> {
+    let (committed, exchange) = {
+        let games = state.games.read().await;
+        let game = games.get(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+        let exchange = game
+            .private
+            .clone()
+            .ok_or_else(|| ApiError::invalid_state("Game is not private"))?;
+        (game.session.get::<Committed>()?, exchange)
+    };
+    let role = committed.role();
+
+    let url = format!("{}/game/{}/status", state.oracle_url, game_id);
+    let resp = state
+        .oracle_get(&url)
+        .send()
+        .await
+        .map_err(|e| ApiError::upstream(e.to_string()))?;
+    state.check_oracle(&game_id, &resp).await?;
+    if !resp.status().is_success() {
+        return Err(oracle_error(resp).await);
+    }
+    let status: oracle::GameStatusResponse = resp
+        .json()
+        .await
+        .map_err(|e| ApiError::upstream(e.to_string()))?;
+
+    if status.reveals_requested {
+        info!(player = %state.player_name, %game_id, "Oracle asked for reveals");
+        return reveal_to_oracle(state, game_id).await;
+    }
+    if exchange.verdict_sent {
+        return Ok("waiting_for_opponent".to_string());
+    }
+    let opponent_commitment = match role {
+        Player::A => status.commit_b,
+        Player::B => status.commit_a,
+    };
+    let Some(opponent_commitment) = opponent_commitment else {
+        return Ok("waiting_for_opponent".to_string());
+    };
+
+    if !exchange.reveal_sent {
+        let reveal = PeerMessage::Reveal {
+            game_id,
+            player: role,
+            action: committed.state().action.clone(),
+            salt: committed.salt().clone(),
+        };
+        if !state.send_to_peer(&game_id, reveal) {
+            warn!(player = %state.player_name, %game_id, "No direct link to the opponent, revealing to the oracle");
+            return reveal_to_oracle(state, game_id).await;
+        }
+        let mut games = state.games.write().await;
+        let game = games.get_mut(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+        if let Some(private) = game.private.as_mut() {
+            private.reveal_sent = true;
+            private.opponent_commitment = Some(opponent_commitment);
+        }
+        game.timeline.push(
+            state.event(role, role.opponent(), ProtocolStep::Revealed)
+                .with_detail("direct"),
+        );
+        state.persist(&game_id, game);
+        info!(player = %state.player_name, %game_id, "Revealed to opponent directly");
+    }
+
+    // The opponent's reveal may have arrived at any point so far
+    let opponent_reveal = {
+        let games = state.games.read().await;
+        games
+            .get(&game_id)
+            .and_then(|g| g.private.as_ref())
+            .and_then(|p| p.opponent_reveal.clone())
+    };
+    let Some((opponent_action, opponent_salt)) = opponent_reveal else {
+        return Ok("waiting_for_opponent".to_string());
+    };
+    let opens = committed.commit_context(role.opponent()).is_some_and(|context| {
+        opponent_commitment.verify_bound(&opponent_action.to_bytes(), &context, &opponent_salt)
+    });
+    if !opens {
+        warn!(player = %state.player_name, %game_id, "Opponent's reveal doesn't open their commitment, revealing to the oracle");
+        return reveal_to_oracle(state, game_id).await;
+    }
+
+    let mine = (
+        &committed.state().action,
+        committed.salt(),
+        committed.state().commitment,
+    );
+    let theirs = (&opponent_action, &opponent_salt, opponent_commitment);
+    let ((action_a, salt_a, commit_a), (action_b, salt_b, commit_b)) = match role {
+        Player::A => (mine, theirs),
+        Player::B => (theirs, mine),
+    };
+    let game = games::game(committed.game_type());
+    if game.requires_oracle_secret() {
+        return Err(ApiError::invalid_state(format!(
+            "{} games are judged by the oracle",
+            game.name
+        )));
+    }
+    if !game.validate_action(&opponent_action) {
+        warn!(player = %state.player_name, %game_id, "Opponent revealed an invalid action, revealing to the oracle");
+        return reveal_to_oracle(state, game_id).await;
+    }
+    let result = game.judge(action_a, action_b, None);
+    let verdict = VerdictMessage {
+        game_id,
+        player: role,
+        result,
+        commit_a,
+        commit_b,
+        transcript: VerdictMessage::transcript(&game_id, salt_a, salt_b),
+    };
+
+    let url = format!("{}/game/{}/verdict", state.oracle_url, game_id);
+    let resp = state
+        .oracle_post(&url, Some(game_id), &verdict)?
+        .send()
+        .await
+        .map_err(|e| ApiError::upstream(e.to_string()))?;
+    state.check_oracle(&game_id, &resp).await?;
+    if !resp.status().is_success() {
+        return Err(oracle_error(resp).await);
+    }
+    let status = resp
+        .json::<oracle::StatusResponse>()
+        .await
+        .map_err(|e| ApiError::upstream(e.to_string()))?
+        .status;
+    info!(player = %state.player_name, %game_id, %result, %status, "Submitted verdict");
+
+    {
+        let mut games = state.games.write().await;
+        let game = games.get_mut(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+        if let Some(private) = game.private.as_mut() {
+            private.verdict_sent = true;
+        }
+        game.opponent_action = Some(opponent_action);
+        game.timeline.push(
+            state.event(role, Actor::Oracle, ProtocolStep::Revealed)
+                .with_detail(format!("verdict: {}", result.as_str())),
+        );
+        state.persist(&game_id, game);
+    }
+
+    if status == "reveals_requested" {
+        return reveal_to_oracle(state, game_id).await;
+    }
+    Ok(status)
+}
@@ -0,0 +1,330 @@
+//! Profiles: further players hosted by one player service.
+//!
+//! A player service is one player, served under `/api`. It can host more
+//! as profiles, each a [`PlayerState`] of its own with its own player ID,
+//! signing key, Fiber node and games, so one deployment can back several
+//! UI users. A profile is served under `/api/profiles/:profile_id/` with
+//! the same routes as `/api`.
+//!
+//! Profiles come from the `profiles` setting at startup or from
+//! `POST /api/profiles`, which takes the admin token. With a
+//! [`PlayerStore`] their settings are saved and they are hosted again after
+//! a restart, under the same identity: the standalone service keeps each
+//! profile's player ID, key and games under `profile-<id>`, apart from its
+//! own player's.
+
+use crate::handlers::api_router;
+use crate::reconcile;
+use crate::reminders::{self, Notifier};
+use crate::state::PlayerState;
+use crate::storage::{PlayerStore, ProfileSettings, StorageError};
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{any, get},
+    Json, Router,
+};
+use fiber_auth::{AdminSecret, AdminToken, AuthState};
+use fiber_errors::{ApiError, ValidJson, ValidPath};
+use fiber_game_api::player::{
+    is_profile_id, CreateProfileRequest, ProfileResponse, ProfilesResponse,
+};
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use tower::ServiceExt;
+use tracing::info;
+
+/// Builds the player for a profile from its ID and settings
+pub type MakePlayer =
+    Box<dyn Fn(&str, &ProfileSettings) -> Result<PlayerState, StorageError> + Send + Sync>;
+
+/// A hosted profile and the routes it is served with
+struct Hosted {
+    state: Arc<PlayerState>,
+    router: Router,
+}
+
+/// The profiles a player service hosts, by ID
+pub struct Profiles {
+    hosted: RwLock<BTreeMap<String, Hosted>>,
+    make: MakePlayer,
+    /// Where profile settings are saved, if anywhere
+    store: Option<Arc<dyn PlayerStore>>,
+    notifiers: Vec<Arc<dyn Notifier>>,
+    admin_token: AdminSecret,
+}
+
+impl Profiles {
+    /// Host no profiles yet; each added one is built by `make`, which
+    /// should apply the service's own settings (oracle, encoding, ...).
+    pub fn new(make: MakePlayer) -> Self {
+        Self {
+            hosted: RwLock::new(BTreeMap::new()),
+            make,
+            store: None,
+            notifiers: Vec::new(),
+            admin_token: AdminSecret::default(),
+        }
+    }
+
+    /// Save profile settings to `store`, and host the profiles saved there.
+    pub fn with_store(mut self, store: Arc<dyn PlayerStore>) -> Result<Self, StorageError> {
+        for (id, settings) in store.load_profile_settings()? {
+            self.host(&id, &settings)?;
+        }
+        self.store = Some(store);
+        Ok(self)
+    }
+
+    /// Send each profile's reminders to `notifiers` as well.
+    pub fn with_notifiers(mut self, notifiers: Vec<Arc<dyn Notifier>>) -> Self {
+        self.notifiers = notifiers;
+        self
+    }
+
+    /// Let requests bearing `token` add profiles.
+    pub fn with_admin_token(mut self, token: Option<String>) -> Self {
+        self.admin_token = AdminSecret::new(token);
+        self
+    }
+
+    /// Host `id` with `settings`, replacing a profile of that ID, and save
+    /// the settings.
+    pub fn add(&self, id: &str, settings: &ProfileSettings) -> Result<Arc<PlayerState>, StorageError> {
+        let state = self.host(id, settings)?;
+        if let Some(store) = &self.store {
+            store.save_profile_settings(id, settings)?;
+        }
+        Ok(state)
+    }
+
+    fn host(&self, id: &str, settings: &ProfileSettings) -> Result<Arc<PlayerState>, StorageError> {
+        let state = Arc::new((self.make)(id, settings)?);
+        reminders::spawn(&state, self.notifiers.clone());
+        reconcile::spawn(&state);
+        info!(profile = id, player_id = %state.player_id(), "Hosting profile");
+        let hosted = Hosted {
+            router: api_router(state.clone()),
+            state: state.clone(),
+        };
+        self.hosted.write().unwrap().insert(id.to_string(), hosted);
+        Ok(state)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<PlayerState>> {
+        self.hosted.read().unwrap().get(id).map(|h| h.state.clone())
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.hosted.read().unwrap().contains_key(id)
+    }
+
+    async fn list(&self) -> Vec<ProfileResponse> {
+        let hosted: Vec<_> = {
+            let hosted = self.hosted.read().unwrap();
+            hosted.iter().map(|(id, h)| (id.clone(), h.state.clone())).collect()
+        };
+        let mut profiles = Vec::with_capacity(hosted.len());
+        for (id, state) in hosted {
+            profiles.push(profile_response(&id, &state).await);
+        }
+        profiles
+    }
+}
+
+impl AuthState for Profiles {
+    fn admin_secret(&self) -> &AdminSecret {
+        &self.admin_token
+    }
+}
+
+/// A profile as given in the `profiles` setting: `id`, or
+/// `id=fiber_rpc_url` for a profile with a Fiber node
+pub fn parse_profile(spec: &str) -> Result<(String, ProfileSettings), String> {
+    let (id, fiber_rpc_url) = match spec.split_once('=') {
+        Some((id, url)) => (id, Some(url)),
+        None => (spec, None),
+    };
+    if !is_profile_id(id) {
+        return Err(format!(
+            "profile {:?}: ID must be lowercase letters, digits or dashes",
+            id
+        ));
+    }
+    if let Some(url) = fiber_rpc_url {
+        fiber_config::check_url("fiber_rpc_url", url, &["http", "https"])?;
+    }
+    let settings = ProfileSettings {
+        name: id.to_string(),
+        fiber_rpc_url: fiber_rpc_url.map(String::from),
+    };
+    Ok((id.to_string(), settings))
+}
+
+async fn profile_response(id: &str, state: &PlayerState) -> ProfileResponse {
+    ProfileResponse {
+        id: id.to_string(),
+        name: state.player_name().to_string(),
+        player_id: state.player_id(),
+        fiber_rpc_url: state.fiber_rpc_url().await,
+        api_base: format!("/api/profiles/{}", id),
+    }
+}
+
+async fn list_profiles(State(profiles): State<Arc<Profiles>>) -> Json<ProfilesResponse> {
+    Json(ProfilesResponse {
+        profiles: profiles.list().await,
+    })
+}
+
+async fn create_profile(
+    State(profiles): State<Arc<Profiles>>,
+    _: AdminToken,
+    ValidJson(req): ValidJson<CreateProfileRequest>,
+) -> Result<(StatusCode, Json<ProfileResponse>), ApiError> {
+    if profiles.contains(&req.id) {
+        return Err(ApiError::conflict(format!("Profile {} already exists", req.id)));
+    }
+    let settings = ProfileSettings {
+        name: req.name.unwrap_or_else(|| req.id.clone()),
+        fiber_rpc_url: req.fiber_rpc_url,
+    };
+    let state = profiles
+        .add(&req.id, &settings)
+        .map_err(|e| ApiError::internal(e.to_string()))?;
+    Ok((StatusCode::CREATED, Json(profile_response(&req.id, &state).await)))
+}
+
+/// Hand the request to the profile's own routes, as if they were mounted
+/// at `/profiles/:profile_id`
+async fn dispatch(
+    State(profiles): State<Arc<Profiles>>,
+    ValidPath((profile_id, _)): ValidPath<(String, String)>,
+    mut req: Request,
+) -> Result<Response, ApiError> {
+    let router = profiles
+        .hosted
+        .read()
+        .unwrap()
+        .get(&profile_id)
+        .map(|h| h.router.clone())
+        .ok_or_else(|| ApiError::not_found("Profile not found"))?;
+    let prefix = format!("/profiles/{}", profile_id);
+    let uri = req.uri();
+    let path = uri.path().strip_prefix(&prefix).unwrap_or("/");
+    let uri = match uri.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_string(),
+    };
+    *req.uri_mut() = uri
+        .parse()
+        .map_err(|_| ApiError::bad_request("Invalid path"))?;
+    let Ok(response) = router.oneshot(req).await;
+    Ok(response.into_response())
+}
+
+/// Profile routes, relative to the API mount point like [`api_router`]
+pub fn router(profiles: Arc<Profiles>) -> Router {
+    Router::new()
+        .route("/profiles", get(list_profiles).post(create_profile))
+        .route("/profiles/:profile_id/*rest", any(dispatch))
+        .with_state(profiles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::SqlitePlayerStore;
+    use axum::body::Body;
+    use axum::http::header::AUTHORIZATION;
+
+    fn profiles(store: Arc<dyn PlayerStore>) -> Arc<Profiles> {
+        let make: MakePlayer = {
+            let store = store.clone();
+            Box::new(move |id, settings| {
+                PlayerState::open(
+                    store.clone(),
+                    id,
+                    settings.name.clone(),
+                    "http://localhost:3000".into(),
+                    settings.fiber_rpc_url.clone(),
+                )
+            })
+        };
+        Arc::new(
+            Profiles::new(make)
+                .with_admin_token(Some("s3cret".into()))
+                .with_store(store)
+                .unwrap(),
+        )
+    }
+
+    async fn send(app: &Router, req: Request) -> (StatusCode, serde_json::Value) {
+        let resp = app.clone().oneshot(req).await.unwrap();
+        let status = resp.status();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn create(id: &str, token: &str) -> Request {
+        Request::post("/profiles")
+            .header("content-type", "application/json")
+            .header(AUTHORIZATION, format!("Bearer {}", token))
+            .body(Body::from(format!(r#"{{"id":"{}","name":"Alice"}}"#, id)))
+            .unwrap()
+    }
+
+    fn get(uri: &str) -> Request {
+        Request::get(uri).body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_profiles_are_separate_players() {
+        let store: Arc<dyn PlayerStore> = Arc::new(SqlitePlayerStore::open_in_memory().unwrap());
+        let app = router(profiles(store.clone()));
+
+        let (status, _) = send(&app, create("alice", "wrong")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, alice) = send(&app, create("alice", "s3cret")).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(alice["api_base"], "/api/profiles/alice");
+        let (status, _) = send(&app, create("alice", "s3cret")).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let (status, body) = send(&app, create("Not An ID", "s3cret")).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["fields"][0]["field"], "id");
+        send(&app, create("bob", "s3cret")).await;
+
+        // Each profile answers as its own player
+        let (status, info) = send(&app, get("/profiles/alice/player")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(info["player_id"], alice["player_id"]);
+        assert_eq!(info["player_name"], "Alice");
+        let (_, bob) = send(&app, get("/profiles/bob/player")).await;
+        assert_ne!(bob["player_id"], alice["player_id"]);
+        let (status, games) = send(&app, get("/profiles/bob/games/mine?limit=5")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(games["items"], serde_json::json!([]));
+        let (status, _) = send(&app, get("/profiles/carol/player")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // Hosted again, as the same players, after a restart
+        let (_, listed) = send(&app, get("/profiles")).await;
+        let restarted = router(profiles(store));
+        let (_, relisted) = send(&restarted, get("/profiles")).await;
+        assert_eq!(relisted, listed);
+        assert_eq!(relisted["profiles"][0]["player_id"], alice["player_id"]);
+        assert_eq!(relisted["profiles"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_parse_profile() {
+        let (id, settings) = parse_profile("alice=http://node:8227").unwrap();
+        assert_eq!(id, "alice");
+        assert_eq!(settings.fiber_rpc_url.as_deref(), Some("http://node:8227"));
+        assert_eq!(parse_profile("bob").unwrap().1.fiber_rpc_url, None);
+        assert!(parse_profile("Bob").is_err());
+        assert!(parse_profile("bob=ftp://node").is_err());
+    }
+}
@@ -0,0 +1,119 @@
+//! Reconciling held invoices with our Fiber node.
+//!
+//! Once we told the oracle the opponent's payment is held on our node, the
+//! game counts on it until we settle or cancel that invoice. If the node
+//! cancels it or lets it expire first, the opponent has their stake back
+//! and the game doesn't know. While on the RPC backend, [`spawn`] asks the
+//! node every [`RECONCILE_INTERVAL`] about every such invoice; one the node
+//! dropped is no longer counted as held, the game is marked
+//! `invoice_dropped` with a [`ProtocolStep::InvoiceDropped`] on its
+//! timeline, and an [`Event::InvoiceDiverged`] is published for the metrics
+//! along with a warning in the log.
+
+use crate::state::PlayerState;
+use fiber_game_core::fiber::PaymentStatus;
+use fiber_game_core::protocol::{GameId, ProtocolStep};
+use fiber_service::Event;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// How often held invoices are checked against our node
+pub const RECONCILE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Check held invoices every [`RECONCILE_INTERVAL`] until `state` is
+/// dropped.
+pub fn spawn(state: &Arc<PlayerState>) {
+    let state: Weak<PlayerState> = Arc::downgrade(state);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RECONCILE_INTERVAL);
+        loop {
+            interval.tick().await;
+            let Some(state) = state.upgrade() else { break };
+            reconcile(&state).await;
+        }
+    });
+}
+
+/// One pass over the invoices our games count as held; returns the games
+/// whose invoice the node had dropped.
+pub async fn reconcile(state: &PlayerState) -> Vec<GameId> {
+    let Some(node) = state.node().await else {
+        return Vec::new();
+    };
+    let held: Vec<_> = {
+        let games = state.games.read().await;
+        games
+            .iter()
+            .filter(|(_, g)| g.stake_confirmed && !g.session.is_finished())
+            .filter_map(|(id, g)| Some((*id, g.session.opponent_payment_hash()?)))
+            .collect()
+    };
+
+    let mut dropped = Vec::new();
+    for (game_id, payment_hash) in held {
+        match node.get_payment_status(&payment_hash).await {
+            Ok(PaymentStatus::Cancelled) => {}
+            Ok(_) => continue,
+            Err(e) => {
+                debug!(%game_id, error = %e, "Could not check held invoice");
+                continue;
+            }
+        }
+
+        let mut games = state.games.write().await;
+        let Some(game) = games.get_mut(&game_id) else { continue };
+        // Settled or aborted meanwhile: the node was asked to drop it
+        if !game.stake_confirmed || game.session.is_finished() {
+            continue;
+        }
+        game.stake_confirmed = false;
+        game.invoice_dropped = true;
+        let role = game.role();
+        game.timeline.push(state.event(role, role, ProtocolStep::InvoiceDropped));
+        state.persist(&game_id, game);
+        drop(games);
+
+        warn!(
+            player = %state.player_name,
+            %game_id,
+            payment_hash = %payment_hash.to_hex(),
+            "Our node dropped the invoice holding the opponent's payment"
+        );
+        state.events.publish(Event::InvoiceDiverged { payment_hash });
+        dropped.push(game_id);
+    }
+    dropped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::tests::{add_game, player, session};
+    use fiber_game_core::crypto::Preimage;
+    use fiber_game_core::fiber::{FiberClient, MockFiberClient};
+
+    #[tokio::test]
+    async fn test_invoice_dropped_by_the_node_is_no_longer_held() {
+        let node = Arc::new(MockFiberClient::new(10_000));
+        let state = player(Some("http://127.0.0.1:8227")).with_fiber_client(node.clone());
+        let payment_hash = Preimage::random().payment_hash();
+        let game_id = add_game(&state, session().joined(payment_hash)).await;
+        state.games.write().await.get_mut(&game_id).unwrap().stake_confirmed = true;
+
+        let invoice = node.create_hold_invoice(&payment_hash, 1000, 3600).await.unwrap();
+        node.pay_hold_invoice(&invoice).await.unwrap();
+        assert!(reconcile(&state).await.is_empty());
+
+        node.cancel_invoice(&payment_hash).await.unwrap();
+        assert_eq!(reconcile(&state).await, [game_id]);
+        let games = state.games.read().await;
+        let game = &games[&game_id];
+        assert!(game.invoice_dropped && !game.stake_confirmed);
+        assert_eq!(game.timeline.last().unwrap().step, ProtocolStep::InvoiceDropped);
+        drop(games);
+
+        assert!(reconcile(&state).await.is_empty());
+        assert!(state.metrics.render().contains("fiber_invoices_diverged_total 1"));
+    }
+}
@@ -0,0 +1,175 @@
+//! Rematches: playing a drawn game again, as its [`DrawPolicy`] says.
+//!
+//! Once a draw the policy replays is settled, player A's service creates
+//! the next round at the Oracle, which keeps seat B for the same opponent
+//! and points the drawn game to it; player B's service finds it there on a
+//! later poll and joins. Neither waits for its frontend. Every round of a
+//! match carries the first round's id as its match id, up to
+//! [`MAX_MATCH_ROUNDS`](fiber_game_core::protocol::MAX_MATCH_ROUNDS).
+//!
+//! Under [`DrawPolicy::RematchHeld`] both sides keep their preimage, so the
+//! next round has the same payment hashes and is played for the invoices
+//! still held from the draw: the draw's settlement is reported as carried
+//! over, and the new round is funded and its stakes confirmed as soon as
+//! both are in, with no invoice created or paid. Should the opponent come
+//! with another payment hash, the round falls back to new invoices.
+//!
+//! [`DrawPolicy`]: fiber_game_core::protocol::DrawPolicy
+//! [`DrawPolicy::RematchHeld`]: fiber_game_core::protocol::DrawPolicy::RematchHeld
+
+use crate::handlers::{self, confirm_stake};
+use crate::state::{oracle_error, PlayerGameState, PlayerState};
+use fiber_errors::ApiError;
+use fiber_game_api::{
+    oracle,
+    player::{CreateGameRequest, JoinGameRequest},
+};
+use fiber_game_core::crypto::Preimage;
+use fiber_game_core::protocol::{GameId, GameSession, Joined, Player};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// The drawn game a new round replays
+pub(crate) struct Rematch {
+    pub(crate) of: GameId,
+    /// Our preimage in the drawn game, when the new round reuses its
+    /// invoices
+    pub(crate) preimage: Option<Preimage>,
+}
+
+/// Whether `game` is a settled draw its policy replays, with no next round
+/// set up yet
+fn is_due(game: &PlayerGameState) -> bool {
+    game.replays_draw() && game.session.is_settled() && game.rematch.is_none()
+}
+
+/// Drawn games waiting for their next round
+pub(crate) async fn due(state: &PlayerState) -> Vec<GameId> {
+    let games = state.games.read().await;
+    games
+        .iter()
+        .filter(|(_, g)| is_due(g))
+        .map(|(id, _)| *id)
+        .collect()
+}
+
+/// Set up the next round of drawn game `game_id` if it is due one: create
+/// it as player A, or join it as player B once A has. Failures are logged
+/// and retried on the next poll.
+pub(crate) async fn advance(state: &Arc<PlayerState>, game_id: GameId) {
+    let (role, terms, rematch) = {
+        let games = state.games.read().await;
+        let Some(game) = games.get(&game_id).filter(|g| is_due(g)) else {
+            return;
+        };
+        let terms = CreateGameRequest {
+            game_type: game.session.game_type(),
+            amount_shannons: game.session.amount_shannons(),
+            private: game.private.is_some(),
+            draw_policy: game.draw_policy,
+        };
+        let rematch = Rematch {
+            of: game_id,
+            preimage: game
+                .draw_policy
+                .reuses_invoices()
+                .then(|| game.session.preimage().clone()),
+        };
+        (game.role(), terms, rematch)
+    };
+    // Status and game list polls may both get here
+    if !state.rematching.lock().unwrap().insert(game_id) {
+        return;
+    }
+
+    let next = match role {
+        Player::A => handlers::open_game(state, &terms, Some(&rematch))
+            .await
+            .map(|created| Some(created.game_id)),
+        Player::B => join_next(state, &rematch).await,
+    };
+    state.rematching.lock().unwrap().remove(&game_id);
+
+    match next {
+        Ok(Some(next)) => {
+            let mut games = state.games.write().await;
+            if let Some(game) = games.get_mut(&game_id) {
+                game.rematch = Some(next);
+                state.persist(&game_id, game);
+            }
+            info!(player = %state.player_name, %game_id, rematch = %next, "Draw goes to another round");
+        }
+        Ok(None) => {}
+        Err(e) => {
+            warn!(player = %state.player_name, %game_id, error = %e, "Could not set up the next round, will retry");
+        }
+    }
+}
+
+/// Join the round replaying `rematch.of`, once the Oracle says player A
+/// has created it.
+async fn join_next(state: &Arc<PlayerState>, rematch: &Rematch) -> Result<Option<GameId>, ApiError> {
+    let url = format!("{}/game/{}/status", state.oracle_url, rematch.of);
+    let resp = state
+        .oracle_get(&url)
+        .send()
+        .await
+        .map_err(|e| ApiError::upstream(e.to_string()))?;
+    if !resp.status().is_success() {
+        return Err(oracle_error(resp).await);
+    }
+    let status: oracle::GameStatusResponse =
+        resp.json().await.map_err(|e| ApiError::upstream(e.to_string()))?;
+    let Some(next) = status.rematch else {
+        return Ok(None);
+    };
+    handlers::take_seat(state, JoinGameRequest { game_id: next }, Some(rematch)).await?;
+    Ok(Some(next))
+}
+
+/// For a round replaying a draw on its invoices, take them over once both
+/// players are in with the same payment hashes as in the draw: the stakes
+/// are held already, so the round is funded as it is and the Oracle told.
+pub(crate) async fn carry_over(state: &PlayerState, game_id: GameId) {
+    {
+        let mut games = state.games.write().await;
+        let Some(game) = games.get(&game_id) else {
+            return;
+        };
+        if !game.invoices_reused {
+            let Some(drawn_id) = game.replays.filter(|_| game.draw_policy.reuses_invoices()) else {
+                return;
+            };
+            let Some(held) = game.session.opponent_payment_hash() else {
+                return;
+            };
+            let Some(drawn) = games.get(&drawn_id) else {
+                return;
+            };
+            if drawn.session.opponent_payment_hash() != Some(held) {
+                warn!(player = %state.player_name, %game_id, "Opponent came with a new payment hash, the round needs new invoices");
+                if let Some(game) = games.get_mut(&game_id) {
+                    game.replays = None;
+                    state.persist(&game_id, game);
+                }
+                return;
+            }
+            let invoices = (drawn.my_invoice_string.clone(), drawn.opponent_invoice_string.clone());
+            let Some(game) = games.get_mut(&game_id) else {
+                return;
+            };
+            if game.session.advance(|s: GameSession<Joined>| Ok(s.fund())).is_err() {
+                return;
+            }
+            (game.my_invoice_string, game.opponent_invoice_string) = invoices;
+            game.invoices_reused = true;
+            state.persist(&game_id, game);
+            info!(player = %state.player_name, %game_id, drawn = %drawn_id, "Playing for the invoices held from the draw");
+        }
+    }
+
+    // Retried on later polls until the Oracle has it
+    if let Err(e) = confirm_stake(state, game_id).await {
+        warn!(player = %state.player_name, %game_id, error = %e, "Could not confirm the carried-over stake");
+    }
+}
@@ -0,0 +1,297 @@
+//! Reminders for steps this player owes before the oracle's deadline.
+//!
+//! A game left in a browser tab nobody looks at is eventually timed out by
+//! the opponent, or its invoice goes overdue after the result. [`spawn`]
+//! checks every [`CHECK_INTERVAL`] which games wait on us to commit, reveal
+//! or settle, asks the oracle how long the step has left, and once that is
+//! within [`PlayerState::with_remind_within`] sends a [`Reminder`]: to
+//! every `/reminders` stream (server-sent events) and to each configured
+//! [`Notifier`]. Each step of a game is reminded of once; a failed
+//! notification is logged, not retried. Webhooks are signed if the
+//! [`WebhookNotifier`] is given a [`WebhookSigner`].
+
+use crate::state::{PlayerGameState, PlayerState};
+use async_trait::async_trait;
+use axum::{
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use fiber_auth::webhook::{WebhookSigner, SIGNATURE_HEADER};
+use fiber_game_api::{
+    oracle,
+    player::{DueStep, Reminder},
+};
+use fiber_game_core::protocol::AnySession;
+use futures_util::stream::{self, Stream};
+use std::convert::Infallible;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, info, warn};
+
+/// How often games are checked for steps coming due
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long a webhook gets to accept a reminder
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Notification error
+#[derive(Debug, thiserror::Error)]
+pub enum NotifyError {
+    #[error("http error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+/// Somewhere reminders are sent outside the player's own UI
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Short description for logs, e.g. the URL notified
+    fn name(&self) -> String;
+
+    async fn notify(&self, reminder: &Reminder) -> Result<(), NotifyError>;
+}
+
+/// POSTs each reminder as JSON to a URL
+pub struct WebhookNotifier {
+    url: String,
+    client: reqwest::Client,
+    signer: Option<WebhookSigner>,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            client: reqwest::Client::new(),
+            signer: None,
+        }
+    }
+
+    /// Sign each reminder with `signer`, if there is one
+    pub fn with_signer(mut self, signer: Option<WebhookSigner>) -> Self {
+        self.signer = signer;
+        self
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> String {
+        self.url.clone()
+    }
+
+    async fn notify(&self, reminder: &Reminder) -> Result<(), NotifyError> {
+        let request = self.client.post(&self.url).timeout(NOTIFY_TIMEOUT);
+        let request = match &self.signer {
+            Some(signer) => {
+                let signed = signer.sign(reminder)?;
+                request
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .header(SIGNATURE_HEADER, signed.signature)
+                    .body(signed.body)
+            }
+            None => request.json(reminder),
+        };
+        request
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// The step a game waits on us for, if any
+fn due_step(game: &PlayerGameState) -> Option<DueStep> {
+    if game.oracle_lost {
+        return None;
+    }
+    match game.session {
+        AnySession::Joined(_) | AnySession::Funded(_) => Some(DueStep::Commit),
+        AnySession::Committed(_) => Some(DueStep::Reveal),
+        AnySession::Judged(_) => Some(DueStep::Settle),
+        _ => None,
+    }
+}
+
+/// Remind of every step due within the player's reminder window that has
+/// not been reminded of yet, answering with the reminders sent.
+pub async fn check(state: &PlayerState) -> Vec<Reminder> {
+    let due: Vec<_> = {
+        let games = state.games.read().await;
+        let reminded = state.reminded.lock().unwrap();
+        games
+            .iter()
+            .filter_map(|(game_id, game)| Some((*game_id, due_step(game)?)))
+            .filter(|due| !reminded.contains(due))
+            .collect()
+    };
+
+    let mut sent = Vec::new();
+    for (game_id, step) in due {
+        let url = format!("{}/game/{}/status", state.oracle_url, game_id);
+        let status: oracle::GameStatusResponse = match state.oracle_get(&url).send().await {
+            Ok(resp) if state.check_oracle(&game_id, &resp).await.is_err() => continue,
+            Ok(resp) if resp.status().is_success() => match resp.json().await {
+                Ok(status) => status,
+                Err(_) => continue,
+            },
+            _ => continue,
+        };
+        let Some(seconds_left) = status.step_deadline_secs else {
+            continue;
+        };
+        if Duration::from_secs(seconds_left) > state.remind_within {
+            continue;
+        }
+
+        state.reminded.lock().unwrap().insert((game_id, step));
+        let reminder = Reminder {
+            game_id,
+            step,
+            seconds_left,
+        };
+        info!(player = %state.player_name, %game_id, ?step, seconds_left, "Step coming due");
+        // Nobody may be listening; the notifiers still get it
+        let _ = state.reminders.send(reminder.clone());
+        sent.push(reminder);
+    }
+    sent
+}
+
+/// Check for steps coming due every [`CHECK_INTERVAL`] until `state` is
+/// dropped, passing each reminder on to `notifiers` as well.
+pub fn spawn(state: &Arc<PlayerState>, notifiers: Vec<Arc<dyn Notifier>>) {
+    let state: Weak<PlayerState> = Arc::downgrade(state);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            let Some(state) = state.upgrade() else { break };
+            let reminders = check(&state).await;
+            drop(state);
+
+            for reminder in &reminders {
+                for notifier in &notifiers {
+                    match notifier.notify(reminder).await {
+                        Ok(()) => debug!(game_id = %reminder.game_id, to = %notifier.name(), "Reminder sent"),
+                        Err(e) => warn!(
+                            game_id = %reminder.game_id,
+                            to = %notifier.name(),
+                            error = %e,
+                            "Failed to send reminder"
+                        ),
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// `GET /reminders`: reminders from now on, as server-sent events
+pub(crate) async fn stream(
+    State(state): State<Arc<PlayerState>>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let receiver = state.reminders.subscribe();
+    let events = stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(reminder) => {
+                    let event = Event::default()
+                        .event("reminder")
+                        .json_data(&reminder)
+                        .unwrap_or_default();
+                    return Some((Ok(event), receiver));
+                }
+                Err(RecvError::Lagged(missed)) => {
+                    warn!(missed, "Reminder stream fell behind; reminders lost");
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::tests::{add_game, committed, session};
+    use axum::{routing::get, Json, Router};
+    use fiber_game_core::{crypto::Preimage, protocol::GameResult};
+    use fiber_service::LocalServer;
+    use serde_json::{json, Value};
+    use uuid::Uuid;
+
+    /// An oracle whose every game has `seconds_left` to its deadline
+    async fn oracle(seconds_left: u64) -> LocalServer {
+        let status = json!({
+            "status": "in_progress",
+            "has_opponent": true,
+            "step_deadline_secs": seconds_left,
+        });
+        let router = Router::new().route(
+            "/game/:game_id/status",
+            get(move || {
+                let status = status.clone();
+                async move { Json::<Value>(status) }
+            }),
+        );
+        LocalServer::spawn(router).await.unwrap()
+    }
+
+    fn player(oracle: &LocalServer) -> PlayerState {
+        PlayerState::new(Uuid::new_v4(), "Player A".into(), oracle.url(), None)
+    }
+
+    #[tokio::test]
+    async fn test_due_steps_are_reminded_once() {
+        let oracle = oracle(30).await;
+        let p = player(&oracle);
+        let joined = add_game(&p, session().joined(Preimage::random().payment_hash())).await;
+        let judged = add_game(
+            &p,
+            committed().reveal().judge(GameResult::Draw, None).unwrap(),
+        )
+        .await;
+        let mut stream = p.reminders.subscribe();
+
+        let mut sent = check(&p).await;
+        sent.sort_by_key(|r| r.step != DueStep::Commit);
+        assert_eq!(
+            sent,
+            [
+                Reminder {
+                    game_id: joined,
+                    step: DueStep::Commit,
+                    seconds_left: 30
+                },
+                Reminder {
+                    game_id: judged,
+                    step: DueStep::Settle,
+                    seconds_left: 30
+                },
+            ]
+        );
+        assert!(stream.try_recv().is_ok());
+        assert!(stream.try_recv().is_ok());
+
+        assert!(check(&p).await.is_empty());
+        assert!(stream.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_steps_far_from_their_deadline_wait() {
+        let oracle = oracle(120).await;
+        let p = player(&oracle);
+        add_game(&p, committed()).await;
+        assert!(check(&p).await.is_empty());
+
+        let p = player(&oracle).with_remind_within(Duration::from_secs(120));
+        let game_id = add_game(&p, committed()).await;
+        let sent = check(&p).await;
+        assert_eq!(sent.len(), 1);
+        assert_eq!((sent[0].game_id, sent[0].step), (game_id, DueStep::Reveal));
+    }
+}
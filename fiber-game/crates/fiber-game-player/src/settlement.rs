@@ -0,0 +1,104 @@
+//! Settlement coordination: making sure both hold invoices get resolved.
+//!
+//! Once a game has a result, the winner settles the invoice holding the
+//! loser's payment and the loser cancels the one holding the winner's; on a
+//! draw both cancel, unless the next round is played for the same invoices
+//! ([`crate::rematch`]) and they carry them over. Each frontend does that on its own node, so a player
+//! who never does it leaves the opponent's payment locked. After the
+//! frontend has called `/settle` we report what we did to the Oracle with a
+//! [`SettlementMessage`], retried on later status polls if it fails. Until
+//! the Oracle counts both invoices resolved, the game's status carries its
+//! [`SettlementStatus`]: what each side owes, what they reported and who is
+//! overdue, so a frontend can act on it rather than wait for a click.
+
+use crate::state::{oracle_error, PlayerState};
+use fiber_errors::ApiError;
+use fiber_game_api::oracle::{self, SettlementStatus};
+use fiber_game_core::protocol::{GameId, Judged, SettlementAction, SettlementMessage, Settled, Stage};
+use tracing::{info, warn};
+
+/// Tell the Oracle how we resolved the invoice holding the opponent's
+/// payment. Does nothing if we already have, or haven't settled yet.
+pub(crate) async fn report(state: &PlayerState, game_id: GameId) -> Result<(), ApiError> {
+    let msg = {
+        let games = state.games.read().await;
+        let game = games.get(&game_id).ok_or_else(|| ApiError::not_found("Game not found"))?;
+        if game.settlement_reported || game.session.stage() != Settled::NAME {
+            return Ok(());
+        }
+        let (Some(result), Some(payment_hash)) =
+            (game.session.result(), game.session.opponent_payment_hash())
+        else {
+            return Ok(());
+        };
+        SettlementMessage {
+            game_id,
+            player: game.role(),
+            payment_hash,
+            action: match &game.round {
+                Some(round) => SettlementAction::owed_in(result, game.role(), game.draw_policy, round),
+                None => SettlementAction::owed(result, game.role()),
+            },
+        }
+    };
+
+    let url = format!("{}/game/{}/settled", state.oracle_url, game_id);
+    let resp = state
+        .oracle_post(&url, Some(game_id), &msg)?
+        .send()
+        .await
+        .map_err(|e| ApiError::upstream(e.to_string()))?;
+    if !resp.status().is_success() {
+        return Err(oracle_error(resp).await);
+    }
+    let status = resp
+        .json::<oracle::StatusResponse>()
+        .await
+        .map_err(|e| ApiError::upstream(e.to_string()))?
+        .status;
+    info!(player = %state.player_name, %game_id, action = msg.action.as_str(), %status, "Reported settlement");
+
+    let mut games = state.games.write().await;
+    if let Some(game) = games.get_mut(&game_id) {
+        game.settlement_reported = true;
+        game.settlement_complete = status == "settlement_complete";
+        state.persist(&game_id, game);
+    }
+    Ok(())
+}
+
+/// Where both invoices of a decided game stand, as the Oracle has it;
+/// `None` before the result and once settlement is complete.
+pub(crate) async fn track(state: &PlayerState, game_id: GameId) -> Option<SettlementStatus> {
+    let role = {
+        let games = state.games.read().await;
+        let game = games.get(&game_id)?;
+        let stage = game.session.stage();
+        if game.settlement_complete || (stage != Judged::NAME && stage != Settled::NAME) {
+            return None;
+        }
+        game.role()
+    };
+
+    if let Err(e) = report(state, game_id).await {
+        warn!(player = %state.player_name, %game_id, error = %e, "Could not report settlement, will retry");
+    }
+
+    let url = format!("{}/game/{}/status", state.oracle_url, game_id);
+    let status: oracle::GameStatusResponse = match state.oracle_get(&url).send().await {
+        Ok(resp) if resp.status().is_success() => resp.json().await.ok()?,
+        _ => return None,
+    };
+    let settlement = status.settlement?;
+    if settlement.seat(role.opponent()).overdue {
+        warn!(player = %state.player_name, %game_id, "Opponent has not resolved their invoice");
+    }
+    if settlement.complete {
+        let mut games = state.games.write().await;
+        if let Some(game) = games.get_mut(&game_id) {
+            game.settlement_complete = true;
+            state.persist(&game_id, game);
+        }
+    }
+    Some(settlement)
+}
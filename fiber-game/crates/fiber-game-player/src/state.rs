@@ -0,0 +1,982 @@
+//! Player state: identity, oracle connection and the games this player is in.
+//!
+//! Game mutations are followed by [`PlayerState::persist`], which writes the
+//! game to the attached [`PlayerStore`] (if any) under this player's profile.
+
+use crate::p2p::PeerLinks;
+use crate::storage::{PlayerStore, StorageError};
+use fiber_auth::{AdminSecret, AuthState, Identity};
+use fiber_errors::{ApiError, ErrorBody, ErrorCode};
+use fiber_flags::{Feature, FeatureFlags};
+pub use fiber_game_api::player::{FiberBackend, PlayerGamePhase};
+use fiber_game_api::oracle::ORACLE_KEY_HEADER;
+use fiber_game_api::player::{BalanceResponse, DueStep, Reminder};
+use fiber_game_core::{
+    clock::{SharedClock, SystemClock},
+    crypto::{Commitment, PaymentHash, Salt},
+    fiber::{FiberClient, RpcFiberClient},
+    games::GameAction,
+    money,
+    protocol::{
+        Actor, AnySession, DrawPolicy, Encoding, Envelope, EnvelopeError, GameId, GameResult,
+        MatchRound, Player, ProtocolStep, ResumptionToken, TimelineEvent,
+    },
+};
+use fiber_service::{EventBus, InstrumentedFiberClient, Metrics};
+use reqwest::{Client, RequestBuilder};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, RwLock};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// How long the oracle may take to receive a submission before refusing it
+pub(crate) const SUBMISSION_TTL: Duration = Duration::from_secs(120);
+
+/// How close to its deadline a step is reminded of, unless set with
+/// [`PlayerState::with_remind_within`]
+pub const DEFAULT_REMIND_WITHIN: Duration = Duration::from_secs(60);
+
+/// Reminders a `/reminders` stream can fall behind by before it misses some
+const REMINDER_CAPACITY: usize = 64;
+
+/// Invoices go over a direct link to the opponent when both sides take one
+pub const P2P_TRANSPORT: Feature = Feature {
+    name: "p2p_transport",
+    description: "Exchange invoices over a direct WebSocket instead of the oracle relay",
+    default: true,
+};
+
+/// Features an operator can switch off
+pub const FEATURES: &[Feature] = &[P2P_TRANSPORT];
+
+/// Player state
+pub struct PlayerState {
+    pub(crate) player_id: Uuid,
+    pub(crate) player_name: String,
+    pub(crate) oracle_url: String,
+    pub(crate) http_client: Client,
+    /// Key every message to the oracle is signed with; the oracle binds it to
+    /// our seat when we create or join a game
+    signing_key: secp256k1::SecretKey,
+    /// WebSocket URL advertised to opponents for direct exchange, see [`crate::p2p`]
+    pub(crate) p2p_url: Option<String>,
+    /// Direct links to opponents, by game
+    pub(crate) peers: PeerLinks,
+    /// Encoding for messages we send to the oracle and opponents
+    pub(crate) encoding: Encoding,
+    /// Fiber RPC URL for this player's node (configured via env var, exposed to frontend)
+    pub(crate) fiber_rpc_url: Option<String>,
+    /// Which backend the frontend currently uses, see [`PlayerState::request_backend`]
+    backend: RwLock<BackendState>,
+    /// Reads the balance stakes are paid from while on the RPC backend, see
+    /// [`PlayerState::balance`]
+    fiber: Option<Arc<dyn FiberClient>>,
+    pub(crate) games: RwLock<HashMap<GameId, PlayerGameState>>,
+    /// Store and profile key games are persisted under, if any
+    store: Option<(Arc<dyn PlayerStore>, String)>,
+    /// What timeline events are stamped with
+    clock: SharedClock,
+    /// Request and invoice counters served at `/metrics`
+    pub(crate) metrics: Arc<Metrics>,
+    /// Where invoice events are published; the metrics follow it
+    pub(crate) events: EventBus,
+    /// Which of [`FEATURES`] are on
+    pub(crate) features: FeatureFlags,
+    /// Bearer token the `/admin` routes require; they aren't served without
+    admin_token: AdminSecret,
+    /// Where reminders of steps coming due are sent, see [`crate::reminders`]
+    pub(crate) reminders: broadcast::Sender<Reminder>,
+    /// How close to its deadline a step is reminded of
+    pub(crate) remind_within: Duration,
+    /// Steps already reminded of, each only once
+    pub(crate) reminded: Mutex<HashSet<(GameId, DueStep)>>,
+    /// Drawn games whose next round is being set up, see [`crate::rematch`]
+    pub(crate) rematching: Mutex<HashSet<GameId>>,
+}
+
+/// State of a game from player's perspective
+///
+/// Protocol progress, with our preimage and salt, lives in `session`; the
+/// other fields are what this service adds around it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlayerGameState {
+    pub(crate) session: AnySession,
+    /// Opponent's action, published by the Oracle with the result
+    pub(crate) opponent_action: Option<GameAction>,
+    /// My invoice string (created by frontend on my Fiber node)
+    pub(crate) my_invoice_string: Option<String>,
+    /// Opponent's invoice string (sent directly or retrieved from Oracle, paid by frontend)
+    pub(crate) opponent_invoice_string: Option<String>,
+    /// Oracle's secret number for Guess Number games (revealed with result)
+    pub(crate) oracle_secret_number: Option<u8>,
+    /// Protocol steps only this player sees (Fiber payments, settlement)
+    #[serde(default)]
+    pub(crate) timeline: Vec<TimelineEvent>,
+    /// Opponent's protocol key, pinned by the first message on a direct link
+    #[serde(default)]
+    pub(crate) peer_key: Option<secp256k1::PublicKey>,
+    /// Oracle-signed token for taking our seat back after losing this state
+    #[serde(default)]
+    pub(crate) resume_token: Option<Envelope<ResumptionToken>>,
+    /// Set for games in privacy mode, see [`crate::privacy`]
+    #[serde(default)]
+    pub(crate) private: Option<PrivateExchange>,
+    /// We told the Oracle the opponent's payment is held on our node
+    #[serde(default)]
+    pub(crate) stake_confirmed: bool,
+    /// We told the Oracle how we resolved the invoice holding the
+    /// opponent's payment, see [`crate::settlement`]
+    #[serde(default)]
+    pub(crate) settlement_reported: bool,
+    /// The Oracle counts both invoices resolved
+    #[serde(default)]
+    pub(crate) settlement_complete: bool,
+    /// The oracle answers with another key than the game was created under:
+    /// it restarted and lost the game, see [`PlayerState::check_oracle`]
+    #[serde(default)]
+    pub(crate) oracle_lost: bool,
+    /// Our node cancelled or expired the invoice holding the opponent's
+    /// payment before the game resolved it, see [`crate::reconcile`]
+    #[serde(default)]
+    pub(crate) invoice_dropped: bool,
+    /// What a draw leads to, as player A chose
+    #[serde(default)]
+    pub(crate) draw_policy: DrawPolicy,
+    /// Which round of which match the game is, as the Oracle has it
+    #[serde(default)]
+    pub(crate) round: Option<MatchRound>,
+    /// The drawn game this one replays, see [`crate::rematch`]
+    #[serde(default)]
+    pub(crate) replays: Option<GameId>,
+    /// The game replaying this one after a draw
+    #[serde(default)]
+    pub(crate) rematch: Option<GameId>,
+    /// Played for the invoices held from the drawn game it replays
+    #[serde(default)]
+    pub(crate) invoices_reused: bool,
+}
+
+/// A player's seat in a game, see [`PlayerState::seat`]
+#[derive(Clone, Debug)]
+pub struct GameSeat {
+    pub role: Player,
+    /// Stake each player pays, in shannons
+    pub amount_shannons: u64,
+    /// Locks our stake, in the invoice the opponent created
+    pub payment_hash: PaymentHash,
+    /// Locks the opponent's stake, in our invoice; known once they joined
+    pub opponent_payment_hash: Option<PaymentHash>,
+    pub result: Option<GameResult>,
+}
+
+/// How far a private game's exchange of reveals has got
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub(crate) struct PrivateExchange {
+    /// Opponent's commitment, as the oracle holds it
+    pub(crate) opponent_commitment: Option<Commitment>,
+    /// Opponent's reveal from the direct link, not yet checked against
+    /// `opponent_commitment`
+    pub(crate) opponent_reveal: Option<(GameAction, Salt)>,
+    /// Our reveal went to the opponent
+    pub(crate) reveal_sent: bool,
+    /// Our verdict went to the oracle
+    pub(crate) verdict_sent: bool,
+}
+
+impl PlayerGameState {
+    pub(crate) fn new(session: impl Into<AnySession>) -> Self {
+        Self {
+            session: session.into(),
+            opponent_action: None,
+            my_invoice_string: None,
+            opponent_invoice_string: None,
+            oracle_secret_number: None,
+            timeline: Vec::new(),
+            peer_key: None,
+            resume_token: None,
+            private: None,
+            stake_confirmed: false,
+            settlement_reported: false,
+            settlement_complete: false,
+            oracle_lost: false,
+            invoice_dropped: false,
+            draw_policy: DrawPolicy::default(),
+            round: None,
+            replays: None,
+            rematch: None,
+            invoices_reused: false,
+        }
+    }
+
+    /// Whether the game is a draw its policy follows with another round
+    pub(crate) fn replays_draw(&self) -> bool {
+        match (self.session.result(), &self.round) {
+            (Some(result), Some(round)) => self.draw_policy.replays(result, round),
+            _ => false,
+        }
+    }
+
+    /// Whether the game is a draw whose invoices stay held for the next
+    /// round, rather than being cancelled
+    pub(crate) fn keeps_invoice(&self) -> bool {
+        self.replays_draw() && self.draw_policy.reuses_invoices()
+    }
+
+    pub(crate) fn role(&self) -> Player {
+        self.session.role()
+    }
+
+    /// Phase shown to the frontend
+    pub(crate) fn phase(&self) -> PlayerGamePhase {
+        if self.oracle_lost && !self.session.is_finished() {
+            return PlayerGamePhase::OracleLost;
+        }
+        match self.session {
+            AnySession::Created(_) => PlayerGamePhase::WaitingForOpponent,
+            AnySession::Joined(_) | AnySession::Funded(_) => PlayerGamePhase::WaitingForAction,
+            AnySession::Committed(_) => PlayerGamePhase::Committed,
+            AnySession::Revealed(_) => PlayerGamePhase::Revealed,
+            AnySession::Judged(_) => PlayerGamePhase::WaitingForResult,
+            AnySession::Settled(_) => PlayerGamePhase::Settled,
+            AnySession::Aborted(_) => PlayerGamePhase::Aborted,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct BackendState {
+    active: FiberBackend,
+    /// Switch requested while games were in flight
+    pending: Option<FiberBackend>,
+}
+
+/// Outcome of [`PlayerState::request_backend`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BackendSwitch {
+    /// The requested backend is active now
+    Switched,
+    /// New games are refused until the active ones are settled, then the
+    /// switch happens
+    Draining { active_games: usize },
+}
+
+impl PlayerState {
+    /// Create a player with no persistence.
+    pub fn new(
+        player_id: Uuid,
+        player_name: String,
+        oracle_url: String,
+        fiber_rpc_url: Option<String>,
+    ) -> Self {
+        let backend = BackendState {
+            active: if fiber_rpc_url.is_some() {
+                FiberBackend::Rpc
+            } else {
+                FiberBackend::Mock
+            },
+            pending: None,
+        };
+        let events = EventBus::new();
+        let metrics = Arc::new(Metrics::new());
+        metrics.follow(&events);
+        Self {
+            player_id,
+            player_name,
+            oracle_url,
+            http_client: Client::new(),
+            signing_key: secp256k1::SecretKey::new(&mut secp256k1::rand::thread_rng()),
+            p2p_url: None,
+            peers: PeerLinks::default(),
+            encoding: Encoding::default(),
+            fiber: fiber_rpc_url
+                .clone()
+                .map(|url| Arc::new(RpcFiberClient::new(url)) as Arc<dyn FiberClient>),
+            fiber_rpc_url,
+            backend: RwLock::new(backend),
+            games: RwLock::new(HashMap::new()),
+            store: None,
+            clock: SystemClock::shared(),
+            metrics,
+            events,
+            features: FeatureFlags::new(FEATURES),
+            admin_token: AdminSecret::default(),
+            reminders: broadcast::channel(REMINDER_CAPACITY).0,
+            remind_within: DEFAULT_REMIND_WITHIN,
+            reminded: Mutex::new(HashSet::new()),
+            rematching: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Create a player backed by `store` under `profile`.
+    ///
+    /// The player ID, signing key and games saved for `profile` by a previous
+    /// run are restored, so the oracle still recognises this player after a
+    /// restart. On first use a fresh ID and key are generated and saved.
+    pub fn open(
+        store: Arc<dyn PlayerStore>,
+        profile: &str,
+        player_name: String,
+        oracle_url: String,
+        fiber_rpc_url: Option<String>,
+    ) -> Result<Self, StorageError> {
+        let player_id = match store.load_player_id(profile)? {
+            Some(id) => id,
+            None => {
+                let id = Uuid::new_v4();
+                store.save_player_id(profile, id)?;
+                id
+            }
+        };
+
+        let mut state = Self::new(player_id, player_name, oracle_url, fiber_rpc_url);
+        match store.load_signing_key(profile)? {
+            Some(key) => state.signing_key = key,
+            None => store.save_signing_key(profile, &state.signing_key)?,
+        }
+        let games = store.load_games(profile)?;
+        if !games.is_empty() {
+            info!("{}: Restored {} games", state.player_name, games.len());
+        }
+        state.games = RwLock::new(games.into_iter().collect());
+        state.store = Some((store, profile.to_string()));
+        Ok(state)
+    }
+
+    /// Accept direct connections from opponents at `url`.
+    ///
+    /// `url` is this service's `/p2p` endpoint as reachable by the opponent's
+    /// player service, e.g. `ws://player-a.example:3001/api/p2p`. Without it
+    /// every exchange is relayed through the oracle.
+    pub fn with_p2p_url(mut self, url: Option<String>) -> Self {
+        self.p2p_url = url;
+        self
+    }
+
+    /// The URL opponents may dial, unless [`P2P_TRANSPORT`] is switched off
+    pub(crate) fn advertised_p2p_url(&self) -> Option<String> {
+        self.p2p_url
+            .clone()
+            .filter(|_| self.features.is_enabled(&P2P_TRANSPORT))
+    }
+
+    /// Start with `features` instead of every feature at its default; the
+    /// combined demo hands all its players one set.
+    pub fn with_features(mut self, features: FeatureFlags) -> Self {
+        self.features = features;
+        self
+    }
+
+    pub fn features(&self) -> &FeatureFlags {
+        &self.features
+    }
+
+    /// Serve the operator routes under `/admin`, to requests carrying `token`
+    pub fn with_admin_token(mut self, token: Option<String>) -> Self {
+        self.admin_token = AdminSecret::new(token);
+        self
+    }
+
+    /// Read the balance from `client` instead of the node at the Fiber RPC URL
+    pub fn with_fiber_client(mut self, client: Arc<dyn FiberClient>) -> Self {
+        self.fiber = Some(client);
+        self
+    }
+
+    /// Play as the user `secret` identifies at every Fiber service: sign
+    /// protocol messages with it and take its user ID as our player ID.
+    /// Set it before playing; games in flight stay bound to the old key.
+    pub fn with_identity(mut self, secret: secp256k1::SecretKey) -> Self {
+        self.player_id = Identity::of(&secret).user_id();
+        self.signing_key = secret;
+        self
+    }
+
+    /// Remind of steps once their deadline is `within` away, instead of
+    /// [`DEFAULT_REMIND_WITHIN`]
+    pub fn with_remind_within(mut self, within: Duration) -> Self {
+        self.remind_within = within;
+        self
+    }
+
+    /// Send protocol messages as `encoding`.
+    ///
+    /// Messages we receive are decoded by their content type or frame type,
+    /// so the two players don't need to agree on this.
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Stamp timeline events with `clock` instead of the OS clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Count into `metrics` instead of a registry of our own, e.g. the one
+    /// the combined demo serves.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        metrics.follow(&self.events);
+        self.metrics = metrics;
+        self
+    }
+
+    /// A protocol step happening now, by this player's clock.
+    pub(crate) fn event(
+        &self,
+        from: impl Into<Actor>,
+        to: impl Into<Actor>,
+        step: ProtocolStep,
+    ) -> TimelineEvent {
+        TimelineEvent::at(self.clock.as_ref(), from, to, step)
+    }
+
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// This player's ID as known to the oracle
+    pub fn player_id(&self) -> Uuid {
+        self.player_id
+    }
+
+    /// Public half of the key this player signs oracle messages with
+    pub fn public_key(&self) -> secp256k1::PublicKey {
+        secp256k1::PublicKey::from_secret_key(secp256k1::SECP256K1, &self.signing_key)
+    }
+
+    /// Display name
+    pub fn player_name(&self) -> &str {
+        &self.player_name
+    }
+
+    /// Fiber RPC URL exposed to the frontend; `None` while on the mock backend
+    pub async fn fiber_rpc_url(&self) -> Option<String> {
+        match self.fiber_backend().await {
+            FiberBackend::Rpc => self.fiber_rpc_url.clone(),
+            FiberBackend::Mock => None,
+        }
+    }
+
+    /// Fiber RPC URL this player was configured with, whichever backend is active
+    pub fn configured_rpc_url(&self) -> Option<&str> {
+        self.fiber_rpc_url.as_deref()
+    }
+
+    /// Backend currently in use
+    pub async fn fiber_backend(&self) -> FiberBackend {
+        self.backend.read().await.active
+    }
+
+    /// Backend waiting for active games to drain, if a switch is pending
+    pub async fn pending_backend(&self) -> Option<FiberBackend> {
+        self.backend.read().await.pending
+    }
+
+    /// Games not yet settled
+    pub async fn active_games(&self) -> usize {
+        let games = self.games.read().await;
+        games
+            .values()
+            .filter(|g| !g.session.is_finished())
+            .count()
+    }
+
+    /// Switch the frontend to `target`.
+    ///
+    /// A game's hold invoices must all live on one backend, so with games in
+    /// flight the switch is deferred: new games are refused and the switch
+    /// happens when the last active game is settled.
+    pub async fn request_backend(&self, target: FiberBackend) -> Result<BackendSwitch, ApiError> {
+        if target == FiberBackend::Rpc && self.fiber_rpc_url.is_none() {
+            return Err(ApiError::bad_request("No Fiber RPC URL configured for this player"));
+        }
+
+        let mut backend = self.backend.write().await;
+        if target == backend.active {
+            backend.pending = None;
+            return Ok(BackendSwitch::Switched);
+        }
+
+        let active_games = self.active_games().await;
+        if active_games == 0 {
+            backend.active = target;
+            backend.pending = None;
+            info!("{}: Switched Fiber backend to {:?}", self.player_name, target);
+            Ok(BackendSwitch::Switched)
+        } else {
+            backend.pending = Some(target);
+            info!(
+                "{}: Switching Fiber backend to {:?} after {} active games",
+                self.player_name, target, active_games
+            );
+            Ok(BackendSwitch::Draining { active_games })
+        }
+    }
+
+    /// Refuse new games while a backend switch is draining.
+    pub(crate) async fn check_accepting_games(&self) -> Result<(), ApiError> {
+        match self.pending_backend().await {
+            Some(_) => Err(ApiError::invalid_state(
+                "Fiber backend switch in progress; settle active games first",
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// The node our invoices live on, while on the RPC backend. Its calls
+    /// are timed into our metrics.
+    pub(crate) async fn node(&self) -> Option<Arc<dyn FiberClient>> {
+        match self.fiber_backend().await {
+            FiberBackend::Rpc => self
+                .fiber
+                .clone()
+                .map(|fiber| InstrumentedFiberClient::wrap(fiber, self.metrics.clone())),
+            FiberBackend::Mock => None,
+        }
+    }
+
+    /// What the node can put up as stakes. The balance is only known on the
+    /// RPC backend; mock payments are never short of funds.
+    pub async fn balance(&self) -> Result<BalanceResponse, ApiError> {
+        let backend = self.fiber_backend().await;
+        let reserved_shannons = {
+            let games = self.games.read().await;
+            money::sum(
+                games
+                    .values()
+                    .filter(|g| matches!(g.session, AnySession::Created(_) | AnySession::Joined(_)))
+                    .map(|g| g.session.amount_shannons()),
+            )?
+        };
+        let balance_shannons = match self.node().await {
+            Some(fiber) => Some(fiber.get_balance().await.map_err(|e| {
+                ApiError::upstream(format!("Failed to read Fiber balance: {}", e))
+            })?),
+            None => None,
+        };
+        Ok(BalanceResponse {
+            backend,
+            balance_shannons,
+            reserved_shannons,
+            // Nothing is spendable once the stakes owed pass the balance
+            spendable_shannons: balance_shannons
+                .map(|b| money::sub(b, reserved_shannons).unwrap_or(0)),
+        })
+    }
+
+    /// Refuse a game whose stake the node can't pay on top of the stakes we
+    /// already owe, before anyone has paid into it.
+    ///
+    /// A balance that can't be read doesn't stop the game: the frontend may
+    /// reach the node when we can't.
+    pub(crate) async fn check_funds(&self, stake: u64) -> Result<(), ApiError> {
+        let balance = match self.balance().await {
+            Ok(balance) => balance,
+            Err(e) => {
+                warn!(player = %self.player_name, error = %e, "Skipping balance check");
+                return Ok(());
+            }
+        };
+        match balance.spendable_shannons {
+            Some(spendable) if spendable < stake => Err(ApiError::new(
+                ErrorCode::InsufficientFunds,
+                format!(
+                    "Stake of {} shannons exceeds the spendable balance of {} shannons",
+                    stake, spendable
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Apply a pending backend switch once no games are active.
+    pub(crate) async fn finish_drain(&self) {
+        let mut backend = self.backend.write().await;
+        if let Some(target) = backend.pending {
+            if self.active_games().await == 0 {
+                backend.active = target;
+                backend.pending = None;
+                info!("{}: Games drained, switched Fiber backend to {:?}", self.player_name, target);
+            }
+        }
+    }
+
+    /// Protocol steps this player has recorded for a game, if it is in it.
+    pub async fn timeline(&self, game_id: &GameId) -> Option<Vec<TimelineEvent>> {
+        let games = self.games.read().await;
+        games.get(game_id).map(|g| g.timeline.clone())
+    }
+
+    /// Our seat in a game and the payment hashes locking both stakes, if we
+    /// are in it.
+    pub async fn seat(&self, game_id: &GameId) -> Option<GameSeat> {
+        let games = self.games.read().await;
+        let session = &games.get(game_id)?.session;
+        Some(GameSeat {
+            role: session.role(),
+            amount_shannons: session.amount_shannons(),
+            payment_hash: session.payment_hash(),
+            opponent_payment_hash: session.opponent_payment_hash(),
+            result: session.result(),
+        })
+    }
+
+    /// GET from the oracle, forwarding the current request ID.
+    pub(crate) fn oracle_get(&self, url: &str) -> RequestBuilder {
+        with_request_id(self.http_client.get(url))
+    }
+
+    /// Sign `payload` with our protocol key.
+    pub(crate) fn seal<T: Serialize>(&self, payload: T) -> Result<Envelope<T>, EnvelopeError> {
+        Envelope::seal(payload, &self.signing_key)
+    }
+
+    /// POST `payload` to the oracle, sealed in an envelope signed with our
+    /// key that expires after [`SUBMISSION_TTL`], forwarding the current
+    /// request ID. Submissions to a game are sealed for its `game_id`, so
+    /// the oracle refuses them in any other game.
+    pub(crate) fn oracle_post<T: Serialize>(
+        &self,
+        url: &str,
+        game_id: Option<GameId>,
+        payload: T,
+    ) -> Result<RequestBuilder, EnvelopeError> {
+        let envelope = match game_id {
+            Some(game_id) => {
+                Envelope::seal_for(game_id, payload, &self.signing_key, SUBMISSION_TTL)?
+            }
+            None => Envelope::seal_expiring(payload, &self.signing_key, SUBMISSION_TTL)?,
+        };
+        let body = self.encoding.encode(&envelope)?;
+        Ok(with_request_id(self.http_client.post(url))
+            .header(reqwest::header::CONTENT_TYPE, self.encoding.content_type())
+            .body(body))
+    }
+
+    /// GET a response the oracle signed, checking it came from `oracle_pubkey`.
+    pub(crate) async fn oracle_get_sealed<R: DeserializeOwned>(
+        &self,
+        url: &str,
+        oracle_pubkey: Option<&secp256k1::PublicKey>,
+    ) -> Result<R, ApiError> {
+        let oracle_pubkey =
+            oracle_pubkey.ok_or_else(|| ApiError::invalid_state("Oracle public key unknown"))?;
+        let resp = self
+            .oracle_get(url)
+            .header(reqwest::header::ACCEPT, self.encoding.content_type())
+            .send()
+            .await
+            .map_err(|e| ApiError::upstream(e.to_string()))?;
+        open_sealed(resp, oracle_pubkey).await
+    }
+
+    /// POST `payload` to the oracle like [`PlayerState::oracle_post`] and
+    /// open the signed response, checking it came from `oracle_pubkey`.
+    pub(crate) async fn oracle_post_sealed<T: Serialize, R: DeserializeOwned>(
+        &self,
+        url: &str,
+        game_id: GameId,
+        payload: T,
+        oracle_pubkey: &secp256k1::PublicKey,
+    ) -> Result<R, ApiError> {
+        let resp = self
+            .oracle_post(url, Some(game_id), payload)?
+            .header(reqwest::header::ACCEPT, self.encoding.content_type())
+            .send()
+            .await
+            .map_err(|e| ApiError::upstream(e.to_string()))?;
+        open_sealed(resp, oracle_pubkey).await
+    }
+
+    /// Check the oracle answering `resp` about `game_id` still has the key
+    /// the game was created under. An oracle without it restarted
+    /// without its state and can't finish the game, so an undecided game is
+    /// marked lost and the caller gets an error telling the user to abort
+    /// it, which gets both stakes back.
+    pub(crate) async fn check_oracle(
+        &self,
+        game_id: &GameId,
+        resp: &reqwest::Response,
+    ) -> Result<(), ApiError> {
+        let Some(keys) = advertised_keys(resp) else {
+            return Ok(());
+        };
+        let mut games = self.games.write().await;
+        let Some(game) = games.get_mut(game_id) else {
+            return Ok(());
+        };
+        if keys.contains(game.session.oracle_pubkey())
+            || game.session.is_finished()
+            || game.session.result().is_some()
+        {
+            return Ok(());
+        }
+        if !game.oracle_lost {
+            warn!(
+                player = %self.player_name,
+                %game_id,
+                oracle_keys = ?keys,
+                "Oracle no longer has the key the game was created under; marking it lost"
+            );
+            game.oracle_lost = true;
+            self.persist(game_id, game);
+        }
+        Err(oracle_lost())
+    }
+
+    /// Write a game to the store. Failures are only logged: the in-memory
+    /// state stays authoritative while the process is running.
+    pub(crate) fn persist(&self, game_id: &GameId, game: &PlayerGameState) {
+        if let Some((store, profile)) = &self.store {
+            if let Err(e) = store.save_game(profile, game_id, game) {
+                warn!(player = %self.player_name, %game_id, error = %e, "Failed to persist game");
+            }
+        }
+    }
+}
+
+/// Decode a signed oracle response in whichever encoding it came in. The
+/// signature covers the payload as sent, so it is checked before the payload
+/// is read as `R`.
+async fn open_sealed<R: DeserializeOwned>(
+    resp: reqwest::Response,
+    oracle_pubkey: &secp256k1::PublicKey,
+) -> Result<R, ApiError> {
+    if advertised_keys(&resp).is_some_and(|keys| !keys.contains(oracle_pubkey)) {
+        return Err(oracle_lost());
+    }
+    if !resp.status().is_success() {
+        return Err(oracle_error(resp).await);
+    }
+    let encoding = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(Encoding::from_content_type)
+        .unwrap_or_default();
+    let body = resp
+        .bytes()
+        .await
+        .map_err(|e| ApiError::upstream(e.to_string()))?;
+    let envelope: Envelope<serde_json::Value> = encoding
+        .decode(&body)
+        .map_err(|e| ApiError::upstream(e.to_string()))?;
+    let payload = envelope.open_from(oracle_pubkey)?;
+    serde_json::from_value(payload).map_err(|e| ApiError::upstream(e.to_string()))
+}
+
+/// The keys an oracle response says its oracle holds, if it names any
+fn advertised_keys(resp: &reqwest::Response) -> Option<Vec<secp256k1::PublicKey>> {
+    resp.headers()
+        .get(ORACLE_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(',').map(|key| key.trim().parse().ok()).collect())
+}
+
+/// A game the oracle no longer knows, see [`PlayerState::check_oracle`]
+pub(crate) fn oracle_lost() -> ApiError {
+    ApiError::new(
+        ErrorCode::Gone,
+        "The oracle restarted and lost this game; abort it to get the stakes back",
+    )
+}
+
+/// The error in a failed oracle response, keeping the oracle's code when it
+/// sent one.
+pub(crate) async fn oracle_error(resp: reqwest::Response) -> ApiError {
+    let status = resp.status();
+    let text = resp.text().await.unwrap_or_default();
+    match serde_json::from_str::<ErrorBody>(&text) {
+        Ok(body) => body.into(),
+        Err(_) => ApiError::upstream(format!("Oracle returned {}: {}", status, text)),
+    }
+}
+
+/// Tag an outgoing request with the ID of the request being handled, so the
+/// oracle's logs for it line up with ours, and with our trace context, so its
+/// spans join the same trace.
+fn with_request_id(builder: RequestBuilder) -> RequestBuilder {
+    let builder = builder.headers(fiber_service::trace_headers());
+    match fiber_service::current_request_id() {
+        Some(id) => builder.header(fiber_service::REQUEST_ID_HEADER, id),
+        None => builder,
+    }
+}
+
+
+impl AuthState for PlayerState {
+    fn admin_secret(&self) -> &AdminSecret {
+        &self.admin_token
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use fiber_game_core::{
+        crypto::Preimage,
+        games::{GameType, RpsAction},
+        protocol::{Committed, Created, GameResult, GameSession, SessionError, Settled},
+        fiber::{FiberError, MockCall, MockFiberClient},
+    };
+
+    pub(crate) fn player(fiber_rpc_url: Option<&str>) -> PlayerState {
+        PlayerState::new(
+            Uuid::new_v4(),
+            "Player A".into(),
+            "http://localhost:3000".into(),
+            fiber_rpc_url.map(str::to_string),
+        )
+    }
+
+    /// A fresh session where we are A.
+    pub(crate) fn session() -> GameSession<Created> {
+        let key = || {
+            let secret = secp256k1::SecretKey::new(&mut secp256k1::rand::thread_rng());
+            secp256k1::PublicKey::from_secret_key(secp256k1::SECP256K1, &secret)
+        };
+        GameSession::new(
+            GameId::new(),
+            Player::A,
+            GameType::RockPaperScissors,
+            1000,
+            key(),
+            key(),
+        )
+    }
+
+    /// A session where we are A, played up to the commitment.
+    pub(crate) fn committed() -> GameSession<Committed> {
+        session()
+            .joined(Preimage::random().payment_hash())
+            .fund()
+            .commit(GameAction::Rps(RpsAction::Rock))
+            .unwrap()
+    }
+
+    /// Finish a committed game in a draw.
+    pub(crate) fn finish(s: GameSession<Committed>) -> Result<GameSession<Settled>, SessionError> {
+        s.reveal().judge(GameResult::Draw, None)?.settle()
+    }
+
+    pub(crate) async fn add_game(player: &PlayerState, session: impl Into<AnySession>) -> GameId {
+        let game = PlayerGameState::new(session);
+        let game_id = game.session.game_id();
+        player.games.write().await.insert(game_id, game);
+        game_id
+    }
+
+    #[tokio::test]
+    async fn test_backend_defaults_to_configuration() {
+        assert_eq!(player(None).fiber_backend().await, FiberBackend::Mock);
+        let rpc = player(Some("http://127.0.0.1:8227"));
+        assert_eq!(rpc.fiber_backend().await, FiberBackend::Rpc);
+        assert_eq!(
+            rpc.fiber_rpc_url().await.as_deref(),
+            Some("http://127.0.0.1:8227")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rpc_backend_requires_url() {
+        assert!(player(None)
+            .request_backend(FiberBackend::Rpc)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_switch_when_idle() {
+        let p = player(Some("http://127.0.0.1:8227"));
+        add_game(&p, finish(committed()).unwrap()).await;
+
+        assert_eq!(
+            p.request_backend(FiberBackend::Mock).await,
+            Ok(BackendSwitch::Switched)
+        );
+        assert_eq!(p.fiber_backend().await, FiberBackend::Mock);
+        assert_eq!(p.fiber_rpc_url().await, None);
+        assert_eq!(p.configured_rpc_url(), Some("http://127.0.0.1:8227"));
+    }
+
+    #[tokio::test]
+    async fn test_switch_drains_active_games() {
+        let p = player(Some("http://127.0.0.1:8227"));
+        let game_id = add_game(&p, committed()).await;
+
+        assert_eq!(
+            p.request_backend(FiberBackend::Mock).await,
+            Ok(BackendSwitch::Draining { active_games: 1 })
+        );
+        assert_eq!(p.fiber_backend().await, FiberBackend::Rpc);
+        assert!(p.check_accepting_games().await.is_err());
+
+        p.games
+            .write()
+            .await
+            .get_mut(&game_id)
+            .unwrap()
+            .session
+            .advance(finish)
+            .unwrap();
+        p.finish_drain().await;
+        assert_eq!(p.fiber_backend().await, FiberBackend::Mock);
+        assert_eq!(p.pending_backend().await, None);
+        assert!(p.check_accepting_games().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_requesting_active_backend_cancels_pending_switch() {
+        let p = player(Some("http://127.0.0.1:8227"));
+        add_game(&p, committed()).await;
+
+        p.request_backend(FiberBackend::Mock).await.unwrap();
+        assert_eq!(
+            p.request_backend(FiberBackend::Rpc).await,
+            Ok(BackendSwitch::Switched)
+        );
+        assert_eq!(p.pending_backend().await, None);
+        assert!(p.check_accepting_games().await.is_ok());
+    }
+
+    #[test]
+    fn test_identity_sets_player_id_and_key() {
+        let secret = secp256k1::SecretKey::new(&mut secp256k1::rand::thread_rng());
+        let p = player(None).with_identity(secret);
+        assert_eq!(p.player_id, Identity::of(&secret).user_id());
+        assert_eq!(p.seal(()).unwrap().sender, Identity::of(&secret).key);
+    }
+
+    #[tokio::test]
+    async fn test_unpaid_stakes_are_reserved() {
+        let fiber = Arc::new(MockFiberClient::new(2500));
+        let p = player(Some("http://127.0.0.1:8227")).with_fiber_client(fiber.clone());
+        add_game(&p, session()).await;
+        add_game(&p, session().joined(Preimage::random().payment_hash())).await;
+        add_game(&p, committed()).await;
+
+        let balance = p.balance().await.unwrap();
+        assert_eq!(balance.balance_shannons, Some(2500));
+        assert_eq!(balance.reserved_shannons, 2000);
+        assert_eq!(balance.spendable_shannons, Some(500));
+
+        assert!(p.check_funds(500).await.is_ok());
+        let err = p.check_funds(501).await.unwrap_err();
+        assert_eq!(err.code, ErrorCode::InsufficientFunds);
+
+        // Unreadable balances and mock payments are no reason to refuse a game
+        fiber.fail_next(MockCall::GetBalance, FiberError::NetworkError("down".into()));
+        assert!(p.check_funds(10_000).await.is_ok());
+        let mock = player(None).with_fiber_client(fiber);
+        assert_eq!(mock.balance().await.unwrap().spendable_shannons, None);
+        assert!(mock.check_funds(10_000).await.is_ok());
+    }
+}
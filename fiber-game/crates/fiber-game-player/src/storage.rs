@@ -0,0 +1,426 @@
+//! Player persistence.
+//!
+//! A single [`PlayerStore`] can hold several players, each under its own
+//! profile key (the combined demo uses `player-a`, `player-b`, ...).
+//! [`SqlitePlayerStore`] keeps them in `player_*` tables, which do not clash
+//! with the oracle's tables when both share one database file. Its schema
+//! is the versioned SQL files in `migrations/`, applied by
+//! [`SqlitePlayerStore::open`].
+//!
+//! Given a [`Keyring`], the store encrypts the signing keys and games (which
+//! hold salts and preimages) before they reach the database.
+
+use crate::state::PlayerGameState;
+use fiber_game_core::crypto::{Keyring, KeyringError};
+use fiber_game_core::protocol::GameId;
+use fiber_core::storage::{self, SchemaError};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+mod embedded {
+    refinery::embed_migrations!("migrations");
+}
+
+/// Table recording which of the player's migrations have run, apart from
+/// the oracle's so both can share a database file
+const MIGRATION_TABLE: &str = "player_schema_history";
+
+/// Columns holding secrets, encrypted when the store has a [`Keyring`]
+const SECRET_COLUMNS: [(&str, &str); 2] = [("player_keys", "secret_key"), ("player_games", "data")];
+
+/// Storage error
+#[derive(Debug, thiserror::Error)]
+pub enum StorageError {
+    #[error("database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("corrupt record: {0}")]
+    Corrupt(String),
+    #[error(transparent)]
+    Schema(#[from] SchemaError),
+    #[error("encrypted record: {0}")]
+    Encryption(#[from] KeyringError),
+    #[error("record is encrypted but no storage key is configured")]
+    Locked,
+}
+
+/// How a hosted profile was set up, see [`crate::profiles`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProfileSettings {
+    pub name: String,
+    pub fiber_rpc_url: Option<String>,
+}
+
+/// Persistent storage for player state
+pub trait PlayerStore: Send + Sync {
+    /// Load the player ID saved for `profile`.
+    fn load_player_id(&self, profile: &str) -> Result<Option<Uuid>, StorageError>;
+
+    /// Save the player ID for `profile`.
+    fn save_player_id(&self, profile: &str, player_id: Uuid) -> Result<(), StorageError>;
+
+    /// Load the key `profile` signs its protocol messages with.
+    fn load_signing_key(&self, profile: &str) -> Result<Option<secp256k1::SecretKey>, StorageError>;
+
+    /// Save the key `profile` signs its protocol messages with.
+    fn save_signing_key(&self, profile: &str, key: &secp256k1::SecretKey) -> Result<(), StorageError>;
+
+    /// Load all games saved for `profile`.
+    fn load_games(&self, profile: &str) -> Result<Vec<(GameId, PlayerGameState)>, StorageError>;
+
+    /// Insert or replace one of `profile`'s games.
+    fn save_game(
+        &self,
+        profile: &str,
+        game_id: &GameId,
+        game: &PlayerGameState,
+    ) -> Result<(), StorageError>;
+
+    /// Load the settings of every hosted profile, by profile.
+    fn load_profile_settings(&self) -> Result<Vec<(String, ProfileSettings)>, StorageError>;
+
+    /// Insert or replace the settings `profile` is hosted with.
+    fn save_profile_settings(
+        &self,
+        profile: &str,
+        settings: &ProfileSettings,
+    ) -> Result<(), StorageError>;
+}
+
+/// SQLite-backed [`PlayerStore`]
+pub struct SqlitePlayerStore {
+    conn: Mutex<Connection>,
+    keyring: Option<Keyring>,
+}
+
+impl SqlitePlayerStore {
+    /// Open (or create) the database at `path`, applying any schema
+    /// migrations it is missing.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StorageError> {
+        Self::from_connection(Connection::open(path)?, true)
+    }
+
+    /// Open the database at `path` as it is: fails with
+    /// [`SchemaError::Pending`] unless its schema is up to date.
+    pub fn open_without_migrating(path: impl AsRef<Path>) -> Result<Self, StorageError> {
+        Self::from_connection(Connection::open(path)?, false)
+    }
+
+    /// Open a private in-memory database.
+    pub fn open_in_memory() -> Result<Self, StorageError> {
+        Self::from_connection(Connection::open_in_memory()?, true)
+    }
+
+    fn from_connection(mut conn: Connection, migrate: bool) -> Result<Self, StorageError> {
+        let applied =
+            storage::prepare(&mut conn, embedded::migrations::runner(), MIGRATION_TABLE, migrate)?;
+        for migration in applied {
+            info!(%migration, "Applied player schema migration");
+        }
+        Ok(Self {
+            conn: Mutex::new(conn),
+            keyring: None,
+        })
+    }
+
+    /// Encrypt secrets with `keyring` from now on, and re-encrypt those
+    /// already stored that aren't under its current key: plaintext ones
+    /// from before encryption was set up, and ones under a rotated-out key.
+    pub fn with_keyring(mut self, keyring: Keyring) -> Result<Self, StorageError> {
+        self.keyring = Some(keyring);
+        let resealed = self.reseal()?;
+        if resealed > 0 {
+            info!(resealed, "Re-encrypted player secrets under the current storage key");
+        }
+        Ok(self)
+    }
+
+    /// Bring every secret column under the current storage key
+    fn reseal(&self) -> Result<usize, StorageError> {
+        let Some(keyring) = &self.keyring else {
+            return Ok(0);
+        };
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut resealed = 0;
+        for (table, column) in SECRET_COLUMNS {
+            let rows = tx
+                .prepare(&format!("SELECT rowid, {column} FROM {table}"))?
+                .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+                .collect::<Result<Vec<_>, _>>()?;
+            for (rowid, value) in rows {
+                let value = if keyring.is_current(&value) {
+                    continue;
+                } else if Keyring::is_sealed(&value) {
+                    keyring.rewrap(&value)?
+                } else {
+                    keyring.seal(value.as_bytes())
+                };
+                tx.execute(
+                    &format!("UPDATE {table} SET {column} = ?1 WHERE rowid = ?2"),
+                    params![value, rowid],
+                )?;
+                resealed += 1;
+            }
+        }
+        tx.commit()?;
+        Ok(resealed)
+    }
+
+    /// `value` as it is to be stored in a secret column
+    fn seal(&self, value: String) -> String {
+        match &self.keyring {
+            Some(keyring) => keyring.seal(value.as_bytes()),
+            None => value,
+        }
+    }
+
+    /// A secret column's `value` as it was before [`Self::seal`]
+    fn unseal(&self, value: String) -> Result<String, StorageError> {
+        if !Keyring::is_sealed(&value) {
+            return Ok(value);
+        }
+        let keyring = self.keyring.as_ref().ok_or(StorageError::Locked)?;
+        String::from_utf8(keyring.open(&value)?).map_err(|e| StorageError::Corrupt(e.to_string()))
+    }
+}
+
+impl PlayerStore for SqlitePlayerStore {
+    fn load_player_id(&self, profile: &str) -> Result<Option<Uuid>, StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let player_id: Option<String> = conn
+            .query_row(
+                "SELECT player_id FROM player_profiles WHERE profile = ?1",
+                params![profile],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        player_id
+            .map(|id| Uuid::parse_str(&id).map_err(|e| StorageError::Corrupt(e.to_string())))
+            .transpose()
+    }
+
+    fn save_player_id(&self, profile: &str, player_id: Uuid) -> Result<(), StorageError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO player_profiles (profile, player_id) VALUES (?1, ?2)",
+            params![profile, player_id.to_string()],
+        )?;
+        Ok(())
+    }
+
+    fn load_signing_key(&self, profile: &str) -> Result<Option<secp256k1::SecretKey>, StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let hex_key: Option<String> = conn
+            .query_row(
+                "SELECT secret_key FROM player_keys WHERE profile = ?1",
+                params![profile],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        hex_key
+            .map(|hex_key| {
+                let hex_key = self.unseal(hex_key)?;
+                let bytes =
+                    hex::decode(&hex_key).map_err(|e| StorageError::Corrupt(e.to_string()))?;
+                secp256k1::SecretKey::from_slice(&bytes)
+                    .map_err(|e| StorageError::Corrupt(e.to_string()))
+            })
+            .transpose()
+    }
+
+    fn save_signing_key(&self, profile: &str, key: &secp256k1::SecretKey) -> Result<(), StorageError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO player_keys (profile, secret_key) VALUES (?1, ?2)",
+            params![profile, self.seal(hex::encode(key.secret_bytes()))],
+        )?;
+        Ok(())
+    }
+
+    fn load_games(&self, profile: &str) -> Result<Vec<(GameId, PlayerGameState)>, StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT game_id, data FROM player_games WHERE profile = ?1")?;
+        let rows = stmt.query_map(params![profile], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+
+        let mut games = Vec::new();
+        for row in rows {
+            let (game_id, data) = row?;
+            let game_id = game_id
+                .parse()
+                .map_err(|e: uuid::Error| StorageError::Corrupt(e.to_string()))?;
+            let data = self.unseal(data)?;
+            match serde_json::from_str(&data) {
+                Ok(game) => games.push((game_id, game)),
+                // Saved by an older version, before games were kept as sessions
+                Err(e) => warn!("Skipping saved game {} for {}: {}", game_id, profile, e),
+            }
+        }
+        Ok(games)
+    }
+
+    fn save_game(
+        &self,
+        profile: &str,
+        game_id: &GameId,
+        game: &PlayerGameState,
+    ) -> Result<(), StorageError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO player_games (profile, game_id, data) VALUES (?1, ?2, ?3)",
+            params![profile, game_id.to_string(), self.seal(serde_json::to_string(game)?)],
+        )?;
+        Ok(())
+    }
+
+    fn load_profile_settings(&self) -> Result<Vec<(String, ProfileSettings)>, StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT profile, name, fiber_rpc_url FROM player_profile_settings ORDER BY profile",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                ProfileSettings {
+                    name: row.get(1)?,
+                    fiber_rpc_url: row.get(2)?,
+                },
+            ))
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn save_profile_settings(
+        &self,
+        profile: &str,
+        settings: &ProfileSettings,
+    ) -> Result<(), StorageError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO player_profile_settings (profile, name, fiber_rpc_url) \
+             VALUES (?1, ?2, ?3)",
+            params![profile, settings.name, settings.fiber_rpc_url],
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::tests::session;
+    use crate::state::PlayerState;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_profiles_are_independent() {
+        let store = SqlitePlayerStore::open_in_memory().unwrap();
+        let a = Uuid::new_v4();
+        store.save_player_id("player-a", a).unwrap();
+
+        assert_eq!(store.load_player_id("player-a").unwrap(), Some(a));
+        assert_eq!(store.load_player_id("player-b").unwrap(), None);
+        assert!(store.load_games("player-b").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_games_roundtrip_and_skip_unreadable() {
+        let store = SqlitePlayerStore::open_in_memory().unwrap();
+        let session = session();
+        let game_id = session.game_id();
+        let payment_hash = session.payment_hash();
+        store
+            .save_game("player-a", &game_id, &PlayerGameState::new(session))
+            .unwrap();
+        store
+            .conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO player_games (profile, game_id, data) VALUES ('player-a', ?1, '{}')",
+                params![GameId::new().to_string()],
+            )
+            .unwrap();
+
+        let games = store.load_games("player-a").unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].0, game_id);
+        assert_eq!(games[0].1.session.payment_hash(), payment_hash);
+    }
+
+    #[test]
+    fn test_secrets_encrypted_at_rest() {
+        let keyring = Keyring::random();
+        let store = SqlitePlayerStore::open_in_memory()
+            .unwrap()
+            .with_keyring(keyring.clone())
+            .unwrap();
+        let key = secp256k1::SecretKey::new(&mut secp256k1::rand::thread_rng());
+        store.save_signing_key("player-a", &key).unwrap();
+        let session = session();
+        let game_id = session.game_id();
+        let payment_hash = session.payment_hash();
+        store
+            .save_game("player-a", &game_id, &PlayerGameState::new(session))
+            .unwrap();
+
+        {
+            let conn = store.conn.lock().unwrap();
+            let stored_key: String = conn
+                .query_row("SELECT secret_key FROM player_keys", [], |row| row.get(0))
+                .unwrap();
+            let stored_game: String = conn
+                .query_row("SELECT data FROM player_games", [], |row| row.get(0))
+                .unwrap();
+            assert!(keyring.is_current(&stored_key));
+            assert!(keyring.is_current(&stored_game));
+            assert!(!stored_game.contains(&game_id.to_string()));
+        }
+        assert_eq!(store.load_signing_key("player-a").unwrap(), Some(key));
+        let games = store.load_games("player-a").unwrap();
+        assert_eq!(games[0].1.session.payment_hash(), payment_hash);
+
+        let other = SqlitePlayerStore {
+            conn: store.conn,
+            keyring: Some(Keyring::random()),
+        };
+        assert!(matches!(
+            other.load_signing_key("player-a"),
+            Err(StorageError::Encryption(KeyringError::UnknownKey(_)))
+        ));
+    }
+
+    #[test]
+    fn test_open_restores_identity() {
+        let store: Arc<dyn PlayerStore> = Arc::new(SqlitePlayerStore::open_in_memory().unwrap());
+        let url = "http://localhost:3000".to_string();
+
+        let first = PlayerState::open(store.clone(), "player-a", "A".into(), url.clone(), None).unwrap();
+        let again = PlayerState::open(store.clone(), "player-a", "A".into(), url.clone(), None).unwrap();
+        let other = PlayerState::open(store, "player-b", "B".into(), url, None).unwrap();
+
+        assert_eq!(first.player_id(), again.player_id());
+        assert_ne!(first.player_id(), other.player_id());
+        assert_eq!(first.public_key(), again.public_key());
+        assert_ne!(first.public_key(), other.public_key());
+    }
+
+    #[test]
+    fn test_unmigrated_database_refused_without_auto_migrate() {
+        let conn = Connection::open_in_memory().unwrap();
+        let result = SqlitePlayerStore::from_connection(conn, false);
+        assert!(matches!(result, Err(StorageError::Schema(SchemaError::Pending(2)))));
+
+        let store = SqlitePlayerStore::from_connection(Connection::open_in_memory().unwrap(), true);
+        let conn = store.unwrap().conn.into_inner().unwrap();
+        assert!(SqlitePlayerStore::from_connection(conn, false).is_ok());
+    }
+}
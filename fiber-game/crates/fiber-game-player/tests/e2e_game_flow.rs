@@ -0,0 +1,252 @@
+//! End-to-end integration tests for the game flow.
+//!
+//! These tests verify the full HTTP interaction between Oracle and Player
+//! services, each served in-process on a random port.
+//!
+//! Run with: cargo test -p fiber-game-player --test e2e_game_flow -- --nocapture
+
+use fiber_game_api::player::{
+    CreateGameRequest, CreateGameResponse, GameStatusResponse, JoinGameRequest, JoinGameResponse,
+    MyGamesResponse, PlayRequest, PlayResponse, PlayerGamePhase, SettleAllResponse, SettleResponse,
+};
+use fiber_game_core::{
+    games::{GameAction, GameType, RpsAction},
+    protocol::{DrawPolicy, GameResult},
+};
+use fiber_test_fixtures::services::GameServices;
+
+fn rps_game() -> CreateGameRequest {
+    CreateGameRequest {
+        game_type: GameType::RockPaperScissors,
+        amount_shannons: 1000,
+        private: false,
+        draw_policy: DrawPolicy::Refund,
+    }
+}
+
+fn play(action: RpsAction) -> PlayRequest {
+    PlayRequest {
+        action: GameAction::Rps(action),
+    }
+}
+
+/// Test that Player A sees status update after Player B joins
+///
+/// This test verifies the bug fix where Player A was stuck on "WaitingForOpponent"
+/// even after Player B joined the game.
+#[tokio::test]
+async fn test_player_a_sees_opponent_joined() {
+    let services = GameServices::start().await;
+    let (a, b) = (&services.player_a, &services.player_b);
+
+    // Player A creates a game
+    let create_resp: CreateGameResponse = services.post(a, "/game/create", &rps_game()).await;
+    let game_id = create_resp.game_id;
+    println!("Created game: {}", game_id);
+
+    // Verify Player A sees WaitingForOpponent
+    let my_games: MyGamesResponse = services.get(a, "/games/mine").await;
+    assert_eq!(my_games.items[0].phase, PlayerGamePhase::WaitingForOpponent);
+
+    // Player B joins the game
+    let join_resp: JoinGameResponse = services
+        .post(b, "/game/join", &JoinGameRequest { game_id })
+        .await;
+
+    assert_eq!(join_resp.status, "joined");
+    println!("Player B joined game");
+
+    // KEY TEST: Player A should now see WaitingForAction, not WaitingForOpponent
+    let my_games_after: MyGamesResponse = services.get(a, "/games/mine").await;
+    let phase = my_games_after.items[0].phase;
+    assert_eq!(
+        phase,
+        PlayerGamePhase::WaitingForAction,
+        "Player A should see WaitingForAction after B joins, but got {:?}",
+        phase
+    );
+
+    println!("Test passed: Player A correctly sees WaitingForAction after B joins");
+}
+
+/// Test complete game flow: create, join, play, settle
+#[tokio::test]
+async fn test_full_rps_game_with_http_services() {
+    let services = GameServices::start().await;
+    let (a, b) = (&services.player_a, &services.player_b);
+
+    // 1. Player A creates a game
+    let create_resp: CreateGameResponse = services.post(a, "/game/create", &rps_game()).await;
+    let game_id = create_resp.game_id;
+    println!("Created game: {}", game_id);
+
+    // 2. Player B joins
+    let join_resp: JoinGameResponse = services
+        .post(b, "/game/join", &JoinGameRequest { game_id })
+        .await;
+
+    assert_eq!(join_resp.status, "joined");
+    println!("Player B joined");
+
+    // 3. Both players make their moves
+    // Player A plays Rock
+    let play_a_resp: PlayResponse = services
+        .post(
+            a,
+            &format!("/game/{}/play", game_id),
+            &play(RpsAction::Rock),
+        )
+        .await;
+
+    // First player to reveal will see "waiting_for_opponent"
+    assert_eq!(play_a_resp.status, "waiting_for_opponent");
+    println!("Player A played Rock");
+
+    // Player B plays Scissors
+    let play_b_resp: PlayResponse = services
+        .post(
+            b,
+            &format!("/game/{}/play", game_id),
+            &play(RpsAction::Scissors),
+        )
+        .await;
+
+    // Second player to reveal will see "game_complete"
+    assert_eq!(play_b_resp.status, "game_complete");
+    println!("Player B played Scissors");
+
+    // 4. Check game status - fetches the result from the oracle
+    let status_a: GameStatusResponse = services.get(a, &format!("/game/{}/status", game_id)).await;
+    println!("Game status for A: {:?}", status_a);
+    assert_eq!(status_a.result, Some(GameResult::AWins));
+
+    // 5. Settle the game
+    let settle_resp: SettleResponse = services
+        .post(a, &format!("/game/{}/settle", game_id), &())
+        .await;
+
+    println!("Settle response: {:?}", settle_resp);
+
+    // Player A should have won (Rock beats Scissors)
+    let amount_won = settle_resp.amount_won;
+    assert_eq!(amount_won, 1000, "Player A should have won the stake");
+
+    println!(
+        "Test passed: Full game flow completed. A won {} shannons",
+        amount_won
+    );
+}
+
+/// `settle-all` settles every decided game at once and leaves nothing for a
+/// second call
+#[tokio::test]
+async fn test_settle_all_settles_every_decided_game() {
+    let services = GameServices::start().await;
+    let (a, b) = (&services.player_a, &services.player_b);
+
+    let mut game_ids = Vec::new();
+    for b_action in [RpsAction::Scissors, RpsAction::Paper] {
+        let created: CreateGameResponse = services.post(a, "/game/create", &rps_game()).await;
+        let game_id = created.game_id;
+        let _: JoinGameResponse = services
+            .post(b, "/game/join", &JoinGameRequest { game_id })
+            .await;
+        let _: PlayResponse = services
+            .post(
+                a,
+                &format!("/game/{}/play", game_id),
+                &play(RpsAction::Rock),
+            )
+            .await;
+        let _: PlayResponse = services
+            .post(b, &format!("/game/{}/play", game_id), &play(b_action))
+            .await;
+        let status: GameStatusResponse =
+            services.get(a, &format!("/game/{}/status", game_id)).await;
+        assert!(status.can_settle);
+        game_ids.push(game_id);
+    }
+    let (won, lost) = (game_ids[0], game_ids[1]);
+
+    let report: SettleAllResponse = services.post(a, "/games/settle-all", &()).await;
+    assert_eq!(report.games.len(), 2);
+    for game in &report.games {
+        assert!(game.error.is_none(), "{:?}", game);
+        assert!(game.opponent_payment_hash.is_some());
+        let settled = game.settled.as_ref().unwrap();
+        if game.game_id == won {
+            assert_eq!(settled.amount_won, 1000);
+            assert!(game.opponent_preimage.is_some());
+        } else {
+            assert_eq!(game.game_id, lost);
+            assert_eq!(settled.amount_won, -1000);
+            assert!(game.opponent_preimage.is_none());
+        }
+    }
+    for game_id in game_ids {
+        let status: GameStatusResponse =
+            services.get(a, &format!("/game/{}/status", game_id)).await;
+        assert_eq!(status.phase, PlayerGamePhase::Settled);
+    }
+
+    let again: SettleAllResponse = services.post(a, "/games/settle-all", &()).await;
+    assert!(again.games.is_empty());
+}
+
+/// A draw under a rematch policy is followed by another round, set up by
+/// both services on their own and played for the invoices still held
+#[tokio::test]
+async fn test_draw_is_replayed_on_the_held_invoices() {
+    let services = GameServices::start().await;
+    let (a, b) = (&services.player_a, &services.player_b);
+
+    let request = CreateGameRequest {
+        draw_policy: DrawPolicy::RematchHeld,
+        ..rps_game()
+    };
+    let created: CreateGameResponse = services.post(a, "/game/create", &request).await;
+    let drawn = created.game_id;
+    let _: JoinGameResponse = services
+        .post(b, "/game/join", &JoinGameRequest { game_id: drawn })
+        .await;
+    for player in [a, b] {
+        let _: PlayResponse = services
+            .post(player, &format!("/game/{}/play", drawn), &play(RpsAction::Rock))
+            .await;
+    }
+    for player in [a, b] {
+        let status: GameStatusResponse =
+            services.get(player, &format!("/game/{}/status", drawn)).await;
+        assert_eq!(status.result, Some(GameResult::Draw));
+        assert!(status.keeps_invoice && status.replayed);
+        let settled: SettleResponse = services
+            .post(player, &format!("/game/{}/settle", drawn), &())
+            .await;
+        assert_eq!(settled.amount_won, 0);
+    }
+
+    let status_a: GameStatusResponse = services.get(a, &format!("/game/{}/status", drawn)).await;
+    let status_b: GameStatusResponse = services.get(b, &format!("/game/{}/status", drawn)).await;
+    let next = status_a.rematch.expect("A set up the next round");
+    assert_eq!(status_b.rematch, Some(next));
+
+    let round = services.get::<GameStatusResponse>(a, &format!("/game/{}/status", next)).await;
+    assert_eq!(round.round.map(|r| (r.match_id, r.number)), Some((drawn, 2)));
+    assert!(round.invoices_reused);
+    assert_eq!(round.phase, PlayerGamePhase::WaitingForAction);
+    assert_eq!(round.my_payment_hash, status_a.my_payment_hash);
+
+    let _: PlayResponse = services
+        .post(a, &format!("/game/{}/play", next), &play(RpsAction::Rock))
+        .await;
+    let _: PlayResponse = services
+        .post(b, &format!("/game/{}/play", next), &play(RpsAction::Scissors))
+        .await;
+    let status: GameStatusResponse = services.get(a, &format!("/game/{}/status", next)).await;
+    assert_eq!(status.result, Some(GameResult::AWins));
+    assert!(!status.keeps_invoice);
+    let settled: SettleResponse = services
+        .post(a, &format!("/game/{}/settle", next), &())
+        .await;
+    assert_eq!(settled.amount_won, 1000);
+}
@@ -0,0 +1,596 @@
+//! Adversarial opponents against the Oracle and an honest player.
+//!
+//! The honest player is a player service on the RPC backend, seated as A,
+//! with a frontend that keeps to the rules: it only pays an invoice locked
+//! to its own payment hash, confirms the opponent's stake once its node
+//! holds it, settles with a preimage only if it unlocks the opponent's
+//! payment and cancels otherwise. The adversary sits as B and talks to the
+//! Oracle directly with its own key. It reveals moves it never committed
+//! to, forges commitments, lies about its preimage, replays captured
+//! messages, never pays, scrapes every public answer for a preimage and
+//! settles twice. Payments go over a [`MockNetwork`]; whatever the adversary
+//! does, the honest player ends up with what the game's result says and no
+//! stake is left locked.
+//!
+//! Run with: cargo test -p fiber-game-player --test malicious_player
+
+use fiber_game_core::clock::TestClock;
+use fiber_game_core::fiber::{HoldInvoice, PaymentStatus};
+use fiber_game_core::{
+    CommitContext, Commitment, GameAction, GameId, PaymentHash, Player, Preimage, RpsAction, Salt,
+};
+use fiber_game_oracle::OracleState;
+use fiber_game_player::PlayerState;
+use fiber_service::LocalServer;
+use fiber_test_fixtures::game::seal_for;
+use fiber_test_fixtures::payments::{hold_invoice, STAKE, WALLET};
+use fiber_test_fixtures::{Keypair, MockNetwork};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// How long the Oracle waits on a player before their opponent can claim
+/// the timeout
+const STEP_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Party {
+    Honest,
+    Adversary,
+}
+
+fn rps(action: RpsAction) -> GameAction {
+    GameAction::Rps(action)
+}
+
+/// What a node reads from an invoice string `MockFiberClient` handed out
+fn decode(invoice_string: &str) -> HoldInvoice {
+    let hex = invoice_string
+        .strip_prefix("mock_invoice_")
+        .expect("not a mock invoice");
+    hold_invoice(&PaymentHash::from_hex(hex).unwrap())
+}
+
+/// Every 32 bytes in `value` that could be a preimage, as a byte array or
+/// in hex
+fn candidates(value: &Value, found: &mut Vec<Preimage>) {
+    match value {
+        Value::Array(items) if items.len() == 32 => {
+            let bytes: Option<Vec<u8>> = items
+                .iter()
+                .map(|b| b.as_u64().and_then(|b| u8::try_from(b).ok()))
+                .collect();
+            match bytes {
+                Some(bytes) => found.push(Preimage::from_bytes(bytes.try_into().unwrap())),
+                None => items.iter().for_each(|v| candidates(v, found)),
+            }
+        }
+        Value::Array(items) => items.iter().for_each(|v| candidates(v, found)),
+        Value::Object(fields) => fields.values().for_each(|v| candidates(v, found)),
+        Value::String(s) => found.extend(Preimage::from_hex(s).ok()),
+        _ => {}
+    }
+}
+
+/// Seat B, played straight against the Oracle's API with its own key
+struct Adversary {
+    key: Keypair,
+    preimage: Preimage,
+    game_id: GameId,
+    game_url: String,
+    http: reqwest::Client,
+}
+
+impl Adversary {
+    fn new(oracle: &LocalServer, game_id: &str) -> Self {
+        Self {
+            key: Keypair::random(),
+            preimage: Preimage::random(),
+            game_id: game_id.parse().unwrap(),
+            game_url: format!("{}/game/{}", oracle.url(), game_id),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// POST an envelope to `/game/:game_id/<path>`
+    async fn post(&self, path: &str, envelope: &Value) -> (StatusCode, Value) {
+        let resp = self
+            .http
+            .post(format!("{}/{}", self.game_url, path))
+            .json(envelope)
+            .send()
+            .await
+            .unwrap();
+        let status = resp.status();
+        (status, resp.json().await.unwrap_or(Value::Null))
+    }
+
+    /// Sign `payload` for this game and POST it, returning the envelope to
+    /// replay later
+    async fn send(&self, path: &str, payload: Value) -> (StatusCode, Value, Value) {
+        let envelope = serde_json::to_value(seal_for(self.game_id, payload, &self.key.secret)).unwrap();
+        let (status, body) = self.post(path, &envelope).await;
+        (status, body, envelope)
+    }
+
+    async fn get(&self, path: &str) -> (StatusCode, Value) {
+        let resp = self
+            .http
+            .get(format!("{}/{}", self.game_url, path))
+            .send()
+            .await
+            .unwrap();
+        let status = resp.status();
+        (status, resp.json().await.unwrap_or(Value::Null))
+    }
+
+    async fn commit(&self, commitment: &Commitment, context: &CommitContext) -> (StatusCode, Value, Value) {
+        let commit = json!({
+            "player": Player::B,
+            "commitment": commitment,
+            "amount_shannons": context.amount_shannons,
+            "opponent_payment_hash": context.opponent_payment_hash,
+        });
+        self.send("commit", commit).await
+    }
+
+    async fn reveal(&self, action: &GameAction, salt: &Salt, commitment: &Commitment) -> StatusCode {
+        let reveal = json!({
+            "player": Player::B,
+            "action": action,
+            "salt": salt,
+            "commit_a": commitment,
+            "commit_b": commitment,
+        });
+        self.send("reveal", reveal).await.0
+    }
+
+    /// Read everything the Oracle answers anyone about the game, and keep
+    /// whatever unlocks `payment_hash`
+    async fn loot(&self, payment_hash: &PaymentHash) -> Option<Preimage> {
+        let mut found = Vec::new();
+        for path in ["status", "result", "trace", "payment-hash/A", "invoice/A"] {
+            candidates(&self.get(path).await.1, &mut found);
+        }
+        found.into_iter().find(|p| payment_hash.verify(p))
+    }
+}
+
+/// One game between the honest player (A) and the adversary (B)
+struct Game {
+    id: String,
+    adversary: Adversary,
+    /// The honest player's payment hash, locking the adversary's invoice
+    hash_a: PaymentHash,
+    /// The adversary's, locking the honest player's invoice
+    hash_b: PaymentHash,
+}
+
+impl Game {
+    /// What the adversary's commitments are bound to
+    fn context(&self) -> CommitContext {
+        CommitContext {
+            game_id: self.id.parse().unwrap(),
+            amount_shannons: STAKE,
+            opponent_payment_hash: self.hash_a,
+        }
+    }
+}
+
+/// An Oracle that requires funding, the honest player and the Fiber network
+/// between them
+struct Arena {
+    clock: TestClock,
+    oracle: LocalServer,
+    honest: LocalServer,
+    network: MockNetwork<Party>,
+    http: reqwest::Client,
+}
+
+impl Arena {
+    async fn start() -> Self {
+        let clock = TestClock::new();
+        let oracle = OracleState::new()
+            .with_require_funding(true)
+            .with_step_timeout(STEP_TIMEOUT)
+            .with_clock(clock.shared());
+        let oracle = LocalServer::spawn(fiber_game_oracle::create_router(Arc::new(oracle)))
+            .await
+            .expect("Failed to start oracle");
+
+        let network = MockNetwork::new([(Party::Honest, WALLET), (Party::Adversary, WALLET)]);
+        let player = PlayerState::new(
+            Uuid::new_v4(),
+            "Honest".to_string(),
+            oracle.url(),
+            Some("http://127.0.0.1:8227".to_string()),
+        )
+        .with_fiber_client(Arc::new(network.client().clone()));
+        let honest = LocalServer::spawn(fiber_game_player::create_router(Arc::new(player)))
+            .await
+            .expect("Failed to start player");
+
+        Self {
+            clock,
+            oracle,
+            honest,
+            network,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Call the honest player's API: a POST with `body`, or a GET without
+    async fn honest(&self, path: &str, body: Option<Value>) -> Result<Value, String> {
+        let url = format!("{}/api/game/{}", self.honest.url(), path);
+        let req = match body {
+            Some(body) => self.http.post(url).json(&body),
+            None => self.http.get(url),
+        };
+        let resp = req.send().await.map_err(|e| e.to_string())?;
+        if !resp.status().is_success() {
+            return Err(resp.text().await.unwrap_or_default());
+        }
+        resp.json().await.map_err(|e| e.to_string())
+    }
+
+    async fn status(&self, game: &Game) -> Value {
+        self.honest(&format!("{}/status", game.id), None).await.unwrap()
+    }
+
+    /// The honest player creates a game and the adversary joins it with its
+    /// own payment hash
+    async fn open(&self) -> Game {
+        let create = json!({ "game_type": "RockPaperScissors", "amount_shannons": STAKE });
+        let created = self.honest("create", Some(create)).await.unwrap();
+        let id = created["game_id"].as_str().unwrap().to_string();
+
+        let adversary = Adversary::new(&self.oracle, &id);
+        let (status, _, _) = adversary.send("join", json!({ "player_b_id": Uuid::new_v4() })).await;
+        assert_eq!(status, StatusCode::OK);
+        let hash_b = adversary.preimage.payment_hash();
+        let submit = json!({ "player": Player::B, "payment_hash": hash_b, "preimage": adversary.preimage });
+        assert_eq!(adversary.send("payment-hash", submit).await.0, StatusCode::OK);
+
+        let (_, released) = adversary.get("payment-hash/A").await;
+        let hash_a = serde_json::from_value(released["payload"]["payment_hash"].clone()).unwrap();
+        Game {
+            id,
+            adversary,
+            hash_a,
+            hash_b,
+        }
+    }
+
+    /// The adversary offers `invoice` for the honest player to pay
+    async fn offer(&self, game: &Game, invoice: &HoldInvoice) {
+        let submit = json!({ "player": Player::B, "invoice_string": invoice.invoice_string });
+        assert_eq!(game.adversary.send("invoice", submit).await.0, StatusCode::OK);
+    }
+
+    /// The honest frontend creates the invoice the adversary is to pay, and
+    /// pays the adversary's if it is locked to its own payment hash
+    async fn pay(&mut self, game: &Game) -> Result<(), String> {
+        let status = self.status(game).await;
+        let opponent = status["opponent_payment_hash"].as_str().unwrap();
+        let invoice = self
+            .network
+            .invoice(&PaymentHash::from_hex(opponent).unwrap())
+            .await
+            .unwrap();
+        let created = json!({ "invoice_string": invoice.invoice_string });
+        self.honest(&format!("{}/invoice-created", game.id), Some(created))
+            .await?;
+
+        let offered = self
+            .honest(&format!("{}/opponent-invoice", game.id), None)
+            .await?;
+        let invoice = decode(offered["invoice_string"].as_str().unwrap());
+        let mine = PaymentHash::from_hex(status["my_payment_hash"].as_str().unwrap()).unwrap();
+        if invoice.payment_hash != mine {
+            return Err("Opponent's invoice is not locked to our payment hash".to_string());
+        }
+        self.network
+            .pay(Party::Honest, &invoice)
+            .await
+            .map_err(|e| e.to_string())?;
+        self.honest(&format!("{}/payment-done", game.id), Some(json!({})))
+            .await
+            .map(drop)
+    }
+
+    /// The adversary pays the honest player's invoice and vouches for the
+    /// honest player's payment to itself
+    async fn adversary_pays(&mut self, game: &Game) {
+        let (_, offered) = game.adversary.get("invoice/A").await;
+        let invoice = decode(offered["invoice_string"].as_str().unwrap());
+        self.network.pay(Party::Adversary, &invoice).await.unwrap();
+        let funded = json!({ "player": Player::B, "payment_hash": game.hash_a });
+        assert_eq!(game.adversary.send("funded", funded).await.0, StatusCode::OK);
+    }
+
+    /// The honest frontend confirms the adversary's stake, if its node
+    /// holds it
+    async fn confirm(&self, game: &Game) -> Result<(), String> {
+        if self.network.status(&game.hash_b).await != Some(PaymentStatus::Held) {
+            return Err("Opponent's payment is not held".to_string());
+        }
+        self.honest(&format!("{}/payment-received", game.id), Some(json!({})))
+            .await
+            .map(drop)
+    }
+
+    /// A game with both stakes paid and confirmed, the adversary's invoice
+    /// locked to the honest player's hash
+    async fn funded(&mut self) -> Game {
+        let game = self.open().await;
+        let invoice = self.network.invoice(&game.hash_a).await.unwrap();
+        self.offer(&game, &invoice).await;
+        self.pay(&game).await.unwrap();
+        self.adversary_pays(&game).await;
+        self.confirm(&game).await.unwrap();
+        game
+    }
+
+    async fn play(&self, game: &Game, action: RpsAction) -> Result<Value, String> {
+        let play = json!({ "action": rps(action) });
+        self.honest(&format!("{}/play", game.id), Some(play)).await
+    }
+
+    /// The honest frontend settles the invoice holding the adversary's
+    /// payment if it won, else cancels it, and reports it
+    async fn resolve(&mut self, game: &Game) -> Value {
+        let status = self.status(game).await;
+        let preimage = status["opponent_preimage"]
+            .as_str()
+            .map(|p| Preimage::from_hex(p).unwrap());
+        match preimage {
+            Some(preimage) if game.hash_b.verify(&preimage) => self
+                .network
+                .settle(Party::Honest, &game.hash_b, &preimage)
+                .await
+                .unwrap(),
+            _ => self.network.cancel(&game.hash_b).await.unwrap(),
+        }
+        if status["can_settle"] == true {
+            self.honest(&format!("{}/settle", game.id), Some(Value::Null))
+                .await
+                .unwrap();
+        }
+        status
+    }
+
+    /// The adversary's invoice runs out, returning whatever it still holds
+    async fn expire(&mut self, game: &Game) {
+        if self.network.status(&game.hash_a).await == Some(PaymentStatus::Held) {
+            self.network.cancel(&game.hash_a).await.unwrap();
+        }
+    }
+
+    /// Nothing is locked or missing, and the honest player holds `wallet`
+    fn assert_honest_holds(&self, wallet: u64) {
+        self.network.assert_released();
+        assert_eq!(self.network.wallet(Party::Honest), wallet);
+    }
+}
+
+#[tokio::test]
+async fn test_reveal_must_open_the_commitment() {
+    let mut arena = Arena::start().await;
+    let game = arena.funded().await;
+    let adversary = &game.adversary;
+
+    let salt = Salt::random();
+    let scissors = rps(RpsAction::Scissors);
+    let commitment = Commitment::bound(&scissors.to_bytes(), &game.context(), &salt);
+    assert_eq!(adversary.commit(&commitment, &game.context()).await.0, StatusCode::OK);
+    let played = arena.play(&game, RpsAction::Rock).await.unwrap();
+    assert_eq!(played["status"], "waiting_for_opponent");
+
+    // Scissors loses to Rock: claim Paper, or Scissors with another salt
+    let paper = rps(RpsAction::Paper);
+    assert_eq!(adversary.reveal(&paper, &salt, &commitment).await, StatusCode::BAD_REQUEST);
+    let other = Salt::random();
+    assert_eq!(adversary.reveal(&scissors, &other, &commitment).await, StatusCode::BAD_REQUEST);
+    assert!(adversary.loot(&game.hash_a).await.is_none());
+
+    // Then it goes quiet, and forfeits
+    arena.clock.advance(STEP_TIMEOUT + Duration::from_secs(1));
+    let claimed = arena
+        .honest(&format!("{}/claim-timeout", game.id), Some(Value::Null))
+        .await
+        .unwrap();
+    assert_eq!(claimed["status"], "game_complete");
+    let status = arena.resolve(&game).await;
+    assert_eq!(status["result"], "AWins");
+    assert!(adversary.loot(&game.hash_a).await.is_none());
+
+    arena.expire(&game).await;
+    arena.assert_honest_holds(WALLET + STAKE);
+}
+
+#[tokio::test]
+async fn test_forged_commitments_and_preimages_are_refused() {
+    let mut arena = Arena::start().await;
+    let game = arena.open().await;
+    let adversary = &game.adversary;
+
+    // A preimage that doesn't unlock its hash, or a new hash once the honest
+    // player holds its payment under the first, would leave a win unpaid
+    let liar = Preimage::random();
+    let hash = Preimage::random().payment_hash();
+    let submit = json!({ "player": Player::B, "payment_hash": hash, "preimage": liar });
+    assert_eq!(adversary.send("payment-hash", submit).await.0, StatusCode::BAD_REQUEST);
+
+    let invoice = arena.network.invoice(&game.hash_a).await.unwrap();
+    arena.offer(&game, &invoice).await;
+    arena.pay(&game).await.unwrap();
+    arena.adversary_pays(&game).await;
+    arena.confirm(&game).await.unwrap();
+    let hash = liar.payment_hash();
+    let submit = json!({ "player": Player::B, "payment_hash": hash, "preimage": liar });
+    assert_eq!(adversary.send("payment-hash", submit).await.0, StatusCode::CONFLICT);
+
+    let salt = Salt::random();
+    let scissors = rps(RpsAction::Scissors);
+    let commitment = Commitment::bound(&scissors.to_bytes(), &game.context(), &salt);
+    assert_eq!(adversary.commit(&commitment, &game.context()).await.0, StatusCode::OK);
+    arena.play(&game, RpsAction::Rock).await.unwrap();
+
+    // The honest reveal is in, but nothing tells the adversary what it was
+    assert_eq!(adversary.get("trace").await.0, StatusCode::CONFLICT);
+    assert!(adversary.loot(&game.hash_a).await.is_none());
+
+    // A reveal made against some other commitment than the one on record
+    let paper = rps(RpsAction::Paper);
+    let forged = Commitment::bound(&paper.to_bytes(), &game.context(), &salt);
+    assert_eq!(adversary.reveal(&paper, &salt, &forged).await, StatusCode::BAD_REQUEST);
+
+    assert_eq!(adversary.reveal(&scissors, &salt, &commitment).await, StatusCode::OK);
+    let status = arena.resolve(&game).await;
+    assert_eq!(status["result"], "AWins");
+    assert!(adversary.loot(&game.hash_a).await.is_none());
+
+    arena.expire(&game).await;
+    arena.assert_honest_holds(WALLET + STAKE);
+}
+
+#[tokio::test]
+async fn test_replayed_messages_are_refused() {
+    let mut arena = Arena::start().await;
+    let game = arena.funded().await;
+    let adversary = &game.adversary;
+
+    let salt = Salt::random();
+    let scissors = rps(RpsAction::Scissors);
+    let commitment = Commitment::bound(&scissors.to_bytes(), &game.context(), &salt);
+    let (status, _, captured) = adversary.commit(&commitment, &game.context()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(adversary.post("commit", &captured).await.0, StatusCode::CONFLICT);
+
+    arena.play(&game, RpsAction::Rock).await.unwrap();
+    assert_eq!(adversary.reveal(&scissors, &salt, &commitment).await, StatusCode::OK);
+    assert_eq!(arena.resolve(&game).await["result"], "AWins");
+    arena.expire(&game).await;
+
+    // The finished game's trace holds everything the honest player signed;
+    // none of it is taken again in the next game
+    let (_, trace) = adversary.get("trace").await;
+    let honest: Vec<_> = trace["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|e| e["direction"] == "inbound")
+        .filter(|e| e["message"]["sender"] != captured["sender"])
+        .collect();
+    assert!(!honest.is_empty());
+
+    let next = arena.open().await;
+    for entry in honest {
+        let path = match entry["kind"].as_str().unwrap() {
+            "commit" => "commit",
+            "reveal" => "reveal",
+            "funding" => "funded",
+            "settlement" => "settled",
+            _ => continue,
+        };
+        let (status, body) = next.adversary.post(path, &entry["message"]).await;
+        assert!(!status.is_success(), "{} replayed: {}", path, body);
+    }
+    let (_, oracle) = next.adversary.get("status").await;
+    assert!(oracle["commit_a"].is_null() && oracle["stake_held_b"] == false);
+
+    // Nothing was paid in the next game, so leaving it costs nothing
+    let abort = json!({ "reason": "withdrawn" });
+    arena.honest(&format!("{}/abort", next.id), Some(abort)).await.unwrap();
+    arena.assert_honest_holds(WALLET + STAKE);
+}
+
+#[tokio::test]
+async fn test_opponent_who_never_pays_cannot_play() {
+    let mut arena = Arena::start().await;
+    let game = arena.open().await;
+    let adversary = &game.adversary;
+
+    let invoice = arena.network.invoice(&game.hash_a).await.unwrap();
+    arena.offer(&game, &invoice).await;
+    arena.pay(&game).await.unwrap();
+
+    // It vouches for the honest payment but never makes its own
+    let funded = json!({ "player": Player::B, "payment_hash": game.hash_a });
+    assert_eq!(adversary.send("funded", funded).await.0, StatusCode::OK);
+    assert!(arena.confirm(&game).await.is_err());
+
+    let salt = Salt::random();
+    let commitment = Commitment::bound(&rps(RpsAction::Paper).to_bytes(), &game.context(), &salt);
+    assert_eq!(adversary.commit(&commitment, &game.context()).await.0, StatusCode::CONFLICT);
+    assert!(arena.play(&game, RpsAction::Rock).await.is_err());
+
+    let abort = json!({ "reason": "payment_failed" });
+    let aborted = arena
+        .honest(&format!("{}/abort", game.id), Some(abort))
+        .await
+        .unwrap();
+    assert_eq!(aborted["status"], "cancelled");
+    arena.resolve(&game).await;
+    assert!(adversary.loot(&game.hash_a).await.is_none());
+
+    arena.expire(&game).await;
+    arena.assert_honest_holds(WALLET);
+}
+
+#[tokio::test]
+async fn test_invoice_locked_to_another_hash_is_not_paid() {
+    let mut arena = Arena::start().await;
+    let game = arena.open().await;
+
+    // An invoice it could settle itself, with its own preimage
+    arena.offer(&game, &hold_invoice(&game.hash_b)).await;
+    assert!(arena.pay(&game).await.is_err());
+
+    let abort = json!({ "reason": "payment_failed" });
+    arena.honest(&format!("{}/abort", game.id), Some(abort)).await.unwrap();
+    arena.resolve(&game).await;
+    arena.assert_honest_holds(WALLET);
+}
+
+#[tokio::test]
+async fn test_winner_settles_once() {
+    let mut arena = Arena::start().await;
+    let game = arena.funded().await;
+    let adversary = &game.adversary;
+
+    let salt = Salt::random();
+    let paper = rps(RpsAction::Paper);
+    let commitment = Commitment::bound(&paper.to_bytes(), &game.context(), &salt);
+    assert_eq!(adversary.commit(&commitment, &game.context()).await.0, StatusCode::OK);
+    arena.play(&game, RpsAction::Rock).await.unwrap();
+    assert_eq!(adversary.reveal(&paper, &salt, &commitment).await, StatusCode::OK);
+
+    // A fair win gets the adversary the honest preimage, and one payment
+    let preimage = adversary.loot(&game.hash_a).await.expect("winner gets the preimage");
+    arena
+        .network
+        .settle(Party::Adversary, &game.hash_a, &preimage)
+        .await
+        .unwrap();
+    assert!(arena
+        .network
+        .settle(Party::Adversary, &game.hash_a, &preimage)
+        .await
+        .is_err());
+    let settled = json!({ "player": Player::B, "payment_hash": game.hash_a, "action": "settled" });
+    for _ in 0..2 {
+        let (status, body, _) = adversary.send("settled", settled.clone()).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    }
+    // Reporting the honest player's invoice as its own changes nothing
+    let wrong = json!({ "player": Player::B, "payment_hash": game.hash_b, "action": "settled" });
+    assert_eq!(adversary.send("settled", wrong).await.0, StatusCode::BAD_REQUEST);
+
+    let status = arena.resolve(&game).await;
+    assert_eq!(status["result"], "BWins");
+    arena.assert_honest_holds(WALLET - STAKE);
+    assert_eq!(arena.network.wallet(Party::Adversary), WALLET + STAKE);
+}
@@ -0,0 +1,288 @@
+//! Fault-injection tests for the Oracle's HTTP API.
+//!
+//! Two player services play Rock against Scissors through an in-process
+//! Oracle whose router fails chosen requests: either the Oracle is
+//! unavailable and never sees the request, or it handles the request and
+//! the response is lost. Every call the failure hits must return an error,
+//! and retrying it must finish the game with the winner holding the loser's
+//! preimage, so no stake is left locked.
+//!
+//! An Oracle that restarts without its games is found out by its key, and
+//! the players abort the game themselves.
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+};
+use fiber_service::LocalServer;
+use fiber_test_fixtures::services::GameServices;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+/// How often a step is tried before the test gives up
+const ATTEMPTS: usize = 3;
+
+#[derive(Clone, Copy, Debug)]
+enum Mode {
+    /// The Oracle never sees the request
+    Unavailable,
+    /// The Oracle handles the request, but the response is lost
+    ResponseLost,
+}
+
+/// A step of the game, as driven through the player APIs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Step {
+    Create,
+    Join,
+    PlayA,
+    PlayB,
+    Status,
+}
+
+/// Failures armed on the Oracle, by the last segment of the request path
+#[derive(Clone, Default)]
+struct Faults(Arc<Mutex<Vec<(&'static str, Mode)>>>);
+
+impl Faults {
+    fn fail_next(&self, path_end: &'static str, mode: Mode) {
+        self.0.lock().unwrap().push((path_end, mode));
+    }
+
+    fn take(&self, path: &str) -> Option<Mode> {
+        let mut faults = self.0.lock().unwrap();
+        let i = faults.iter().position(|(end, _)| path.ends_with(end))?;
+        Some(faults.remove(i).1)
+    }
+}
+
+async fn inject(State(faults): State<Faults>, req: Request, next: Next) -> Response {
+    match faults.take(req.uri().path()) {
+        None => next.run(req).await,
+        Some(Mode::Unavailable) => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+        Some(Mode::ResponseLost) => {
+            next.run(req).await;
+            StatusCode::BAD_GATEWAY.into_response()
+        }
+    }
+}
+
+/// The game services, with failures injectable on the Oracle
+struct Services {
+    services: GameServices,
+    faults: Faults,
+}
+
+impl Services {
+    async fn start() -> Self {
+        let faults = Faults::default();
+        let layer = middleware::from_fn_with_state(faults.clone(), inject);
+        let services = GameServices::start_with(|router| router.layer(layer)).await;
+        Self { services, faults }
+    }
+
+    async fn call(
+        &self,
+        player: &LocalServer,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<Value, String> {
+        self.services.call(player, path, body).await
+    }
+
+    /// Run `step`, with the fault armed first if it is meant for it, and
+    /// retry until it goes through
+    async fn step(
+        &self,
+        step: Step,
+        fault: Option<(Step, &'static str, Mode)>,
+        player: &LocalServer,
+        path: &str,
+        body: Option<Value>,
+    ) -> Value {
+        let armed = match fault {
+            Some((at, path_end, mode)) if at == step => {
+                self.faults.fail_next(path_end, mode);
+                true
+            }
+            _ => false,
+        };
+        for attempt in 0..ATTEMPTS {
+            match self.call(player, path, body.clone()).await {
+                Ok(resp) => {
+                    assert!(
+                        !armed || attempt > 0,
+                        "{:?} succeeded despite the failure",
+                        step
+                    );
+                    return resp;
+                }
+                Err(e) => println!("{:?} attempt {} failed: {}", step, attempt + 1, e),
+            }
+        }
+        panic!("{:?} did not recover", step);
+    }
+}
+
+/// Play Rock (A) against Scissors (B) with `fault` injected, and check the
+/// game ends as if nothing had gone wrong.
+async fn play_through(fault: Option<(Step, &'static str, Mode)>) {
+    let services = Services::start().await;
+    let (a, b) = (&services.services.player_a, &services.services.player_b);
+
+    let created = services
+        .step(
+            Step::Create,
+            fault,
+            a,
+            "/game/create",
+            Some(json!({ "game_type": "RockPaperScissors", "amount_shannons": 1000 })),
+        )
+        .await;
+    let game_id = created["game_id"].as_str().unwrap().to_string();
+
+    services
+        .step(
+            Step::Join,
+            fault,
+            b,
+            "/game/join",
+            Some(json!({ "game_id": game_id })),
+        )
+        .await;
+
+    let play = format!("/game/{}/play", game_id);
+    let played_a = services
+        .step(
+            Step::PlayA,
+            fault,
+            a,
+            &play,
+            Some(json!({ "action": { "Rps": "Rock" } })),
+        )
+        .await;
+    assert_eq!(played_a["status"], "waiting_for_opponent", "{:?}", fault);
+    let played_b = services
+        .step(
+            Step::PlayB,
+            fault,
+            b,
+            &play,
+            Some(json!({ "action": { "Rps": "Scissors" } })),
+        )
+        .await;
+    assert_eq!(played_b["status"], "game_complete", "{:?}", fault);
+
+    let status = format!("/game/{}/status", game_id);
+    let status_a = services.step(Step::Status, fault, a, &status, None).await;
+    let status_b = services.call(b, &status, None).await.unwrap();
+    assert_eq!(status_a["result"], "AWins", "{:?}", fault);
+    assert_eq!(status_b["result"], "AWins", "{:?}", fault);
+
+    // A can claim B's stake with the preimage behind the payment hash B paid
+    // to, and B's own invoice is only ever cancelled
+    assert!(status_a["opponent_preimage"].is_string(), "{:?}", fault);
+    let preimage =
+        fiber_game_core::Preimage::from_hex(status_a["opponent_preimage"].as_str().unwrap())
+            .unwrap();
+    let hash_b = status_b["my_payment_hash"].as_str().unwrap();
+    assert_eq!(preimage.payment_hash().to_hex(), hash_b, "{:?}", fault);
+    assert!(status_b["opponent_preimage"].is_null());
+
+    let settle = format!("/game/{}/settle", game_id);
+    let won_a = services.call(a, &settle, Some(Value::Null)).await.unwrap();
+    let won_b = services.call(b, &settle, Some(Value::Null)).await.unwrap();
+    assert_eq!(won_a["amount_won"], 1000);
+    assert_eq!(won_b["amount_won"], -1000);
+}
+
+#[tokio::test]
+async fn test_game_completes_without_faults() {
+    play_through(None).await;
+}
+
+#[tokio::test]
+async fn test_game_recovers_from_any_single_oracle_failure() {
+    let faults = [
+        (Step::Create, "/game/create"),
+        (Step::Create, "/payment-hash"),
+        (Step::Join, "/join"),
+        (Step::Join, "/payment-hash"),
+        (Step::Join, "/payment-hash/A"),
+        (Step::PlayA, "/commit"),
+        (Step::PlayA, "/reveal"),
+        (Step::PlayB, "/commit"),
+        (Step::PlayB, "/reveal"),
+    ];
+    for (step, path_end) in faults {
+        for mode in [Mode::Unavailable, Mode::ResponseLost] {
+            play_through(Some((step, path_end, mode))).await;
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_result_poll_recovers_from_unavailable_oracle() {
+    play_through(Some((Step::Status, "/result", Mode::Unavailable))).await;
+}
+
+/// A key the restarted Oracle signs with, other than the one games were
+/// created under
+const RESTARTED_KEY: &str = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+
+async fn restart(State(restarted): State<Arc<Mutex<bool>>>, req: Request, next: Next) -> Response {
+    let mut resp = next.run(req).await;
+    if *restarted.lock().unwrap() {
+        resp.headers_mut().insert(
+            fiber_game_api::oracle::ORACLE_KEY_HEADER,
+            HeaderValue::from_static(RESTARTED_KEY),
+        );
+    }
+    resp
+}
+
+#[tokio::test]
+async fn test_game_lost_by_restarted_oracle_can_be_aborted() {
+    let restarted = Arc::new(Mutex::new(false));
+    let layer = middleware::from_fn_with_state(restarted.clone(), restart);
+    let services = GameServices::start_with(|router| router.layer(layer)).await;
+    let (a, b) = (&services.player_a, &services.player_b);
+
+    let created = services
+        .call(
+            a,
+            "/game/create",
+            Some(json!({ "game_type": "RockPaperScissors", "amount_shannons": 1000 })),
+        )
+        .await
+        .unwrap();
+    let game_id = created["game_id"].as_str().unwrap().to_string();
+    services
+        .call(b, "/game/join", Some(json!({ "game_id": game_id })))
+        .await
+        .unwrap();
+    *restarted.lock().unwrap() = true;
+
+    // A finds out while polling for its opponent
+    let status = format!("/game/{}/status", game_id);
+    let lost = services.call(a, &status, None).await.unwrap();
+    assert_eq!(lost["phase"], "OracleLost");
+
+    let abort = format!("/game/{}/abort", game_id);
+    let aborted = services
+        .call(a, &abort, Some(json!({ "reason": "oracle_lost" })))
+        .await
+        .unwrap();
+    assert_eq!(aborted["status"], "cancelled");
+    let ended = services.call(a, &status, None).await.unwrap();
+    assert_eq!(ended["phase"], "Aborted");
+    assert_eq!(ended["abort_reason"], "oracle_lost");
+
+    // B finds out on its next call to the Oracle and aborts the same way
+    let aborted = services.call(b, &abort, Some(json!({}))).await.unwrap();
+    assert_eq!(aborted["status"], "cancelled");
+    let ended = services.call(b, &status, None).await.unwrap();
+    assert_eq!(ended["abort_reason"], "oracle_lost");
+}
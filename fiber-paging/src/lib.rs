@@ -0,0 +1,225 @@
+//! Fiber Paging
+//!
+//! Cursor pagination for the listing endpoints of every demo service
+//! (`/games/available`, `/games/mine`, `/api/products`, `/api/orders/mine`):
+//! - [`PageRequest`] is the `?cursor=&limit=` query a listing takes
+//! - [`Page`] is the `{"items", "next_cursor"}` envelope it answers with
+//!
+//! Pages are cut by key, not by offset: a cursor holds the key of the last
+//! item handed out, and the next page starts after it. Items created or
+//! removed between two requests therefore never shift a page, so a client
+//! walking a listing sees every item that stayed put exactly once.
+
+use fiber_errors::{ApiError, FieldError, Validate, Validator};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// Page size when the request does not name one
+pub const DEFAULT_LIMIT: u32 = 50;
+
+/// Largest page a client may ask for
+pub const MAX_LIMIT: u32 = 200;
+
+/// Longest cursor accepted; the ones handed out are far shorter
+const MAX_CURSOR_LEN: usize = 512;
+
+/// Which page of a listing to return
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PageRequest {
+    /// `next_cursor` of the previous page; the first page without one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    /// At most this many items, [`DEFAULT_LIMIT`] if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+}
+
+impl PageRequest {
+    /// The page after `cursor`
+    pub fn after(cursor: impl Into<String>) -> Self {
+        Self {
+            cursor: Some(cursor.into()),
+            limit: None,
+        }
+    }
+
+    pub fn with_limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Page size to use, clamped to `1..=MAX_LIMIT`
+    pub fn limit(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT) as usize
+    }
+}
+
+impl Validate for PageRequest {
+    fn check(&self, v: &mut Validator) {
+        if let Some(limit) = self.limit {
+            v.range("limit", limit, 1..=MAX_LIMIT);
+        }
+        if let Some(cursor) = &self.cursor {
+            v.max_len("cursor", cursor, MAX_CURSOR_LEN);
+        }
+    }
+}
+
+/// One page of a listing
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Pass back as `cursor` for the next page; `None` on the last one
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// Cut the page `req` asks for out of `items`, ordered by `key`
+    ///
+    /// Keys must be unique within the listing (add the id as a tiebreaker
+    /// when ordering by time), or items sharing the key of a page's last
+    /// item are skipped. A cursor that does not decode to a key is a 422 on
+    /// `cursor`.
+    pub fn of<K, F>(
+        items: impl IntoIterator<Item = T>,
+        req: &PageRequest,
+        key: F,
+    ) -> Result<Self, ApiError>
+    where
+        K: Ord + Serialize + DeserializeOwned,
+        F: Fn(&T) -> K,
+    {
+        req.validate()?;
+        let after = match &req.cursor {
+            Some(cursor) => Some(decode::<K>(cursor)?),
+            None => None,
+        };
+
+        let mut keyed: Vec<(K, T)> = items
+            .into_iter()
+            .map(|item| (key(&item), item))
+            .filter(|(k, _)| after.as_ref().is_none_or(|after| k > after))
+            .collect();
+        keyed.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let limit = req.limit();
+        let next_cursor = if keyed.len() > limit {
+            Some(encode(&keyed[limit - 1].0))
+        } else {
+            None
+        };
+        keyed.truncate(limit);
+
+        Ok(Self {
+            items: keyed.into_iter().map(|(_, item)| item).collect(),
+            next_cursor,
+        })
+    }
+
+    /// The same page with every item converted
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            next_cursor: self.next_cursor,
+        }
+    }
+}
+
+/// Cursors are the page's last key as hex-encoded JSON: opaque to clients,
+/// safe in a query string, and readable when debugging
+fn encode<K: Serialize>(key: &K) -> String {
+    hex::encode(serde_json::to_vec(key).expect("page keys serialize"))
+}
+
+fn decode<K: DeserializeOwned>(cursor: &str) -> Result<K, ApiError> {
+    hex::decode(cursor)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .ok_or_else(|| {
+            ApiError::validation(vec![FieldError {
+                field: "cursor".to_string(),
+                message: "is not a cursor from this listing".to_string(),
+            }])
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fiber_errors::ErrorCode;
+    use std::cmp::Reverse;
+
+    fn walk(items: &[u32], limit: u32) -> Vec<Vec<u32>> {
+        let mut pages = Vec::new();
+        let mut req = PageRequest::default().with_limit(limit);
+        loop {
+            let page = Page::of(items.iter().copied(), &req, |n| *n).unwrap();
+            pages.push(page.items);
+            match page.next_cursor {
+                Some(cursor) => req = PageRequest::after(cursor).with_limit(limit),
+                None => return pages,
+            }
+        }
+    }
+
+    #[test]
+    fn test_pages_cover_the_listing_once_in_key_order() {
+        let items = [5, 3, 9, 1, 7];
+        assert_eq!(walk(&items, 2), vec![vec![1, 3], vec![5, 7], vec![9]]);
+        assert_eq!(walk(&items, 5), vec![vec![1, 3, 5, 7, 9]]);
+        assert_eq!(walk(&[], 5), vec![Vec::<u32>::new()]);
+    }
+
+    #[test]
+    fn test_cursor_is_stable_when_items_come_and_go() {
+        let req = PageRequest::default().with_limit(2);
+        let first = Page::of([10u32, 20, 30, 40], &req, |n| *n).unwrap();
+        assert_eq!(first.items, vec![10, 20]);
+
+        // 20 is removed and 15 added before the client asks for more
+        let req = PageRequest::after(first.next_cursor.unwrap()).with_limit(2);
+        let second = Page::of([10u32, 15, 30, 40], &req, |n| *n).unwrap();
+        assert_eq!(second.items, vec![30, 40]);
+        assert_eq!(second.next_cursor, None);
+    }
+
+    #[test]
+    fn test_composite_keys_order_newest_first() {
+        let items = [(1u64, "a"), (3, "b"), (3, "c"), (2, "d")];
+        let req = PageRequest::default().with_limit(2);
+        let key = |item: &(u64, &str)| (Reverse(item.0), item.1.to_string());
+        let first = Page::of(items, &req, key).unwrap();
+        assert_eq!(first.items, vec![(3, "b"), (3, "c")]);
+        let req = PageRequest::after(first.next_cursor.unwrap());
+        let second = Page::of(items, &req, key).unwrap();
+        assert_eq!(second.items, vec![(2, "d"), (1, "a")]);
+    }
+
+    #[test]
+    fn test_bad_requests_are_validation_errors() {
+        let bad_limit = PageRequest::default().with_limit(MAX_LIMIT + 1);
+        let err = Page::of([1u32], &bad_limit, |n| *n).unwrap_err();
+        assert_eq!(err.code, ErrorCode::ValidationFailed);
+        assert_eq!(err.fields[0].field, "limit");
+
+        for cursor in ["not hex", "7b7d"] {
+            let err = Page::of([1u32], &PageRequest::after(cursor), |n| *n).unwrap_err();
+            assert_eq!(err.code, ErrorCode::ValidationFailed);
+            assert_eq!(err.fields[0].field, "cursor");
+        }
+    }
+
+    #[test]
+    fn test_page_json_shape() {
+        let page = Page {
+            items: vec![1],
+            next_cursor: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&page).unwrap(),
+            serde_json::json!({ "items": [1], "next_cursor": null })
+        );
+        let req: PageRequest = serde_json::from_str(r#"{"limit": 10}"#).unwrap();
+        assert_eq!(req.limit(), 10);
+        assert_eq!(PageRequest::default().limit(), DEFAULT_LIMIT as usize);
+    }
+}
@@ -0,0 +1,171 @@
+//! In-process domain event bus.
+//!
+//! Handlers publish what happened ([`Event`]) on their service's [`EventBus`]
+//! and carry on; whatever needs to react (the metrics, and later streams or
+//! webhooks) subscribes instead of being called from the handler. The bus is a
+//! `tokio::sync::broadcast` channel: every subscriber sees every event
+//! published after it subscribed, and one that falls more than
+//! [`EVENT_CAPACITY`] events behind misses the oldest.
+
+use fiber_core::PaymentHash;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+/// Events a subscriber can fall behind by before it starts missing them
+pub const EVENT_CAPACITY: usize = 1024;
+
+/// Something that happened in a game or an order
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// A player's or seller's node created a hold invoice
+    InvoiceCreated { payment_hash: PaymentHash },
+    /// A game's winner settled the opponent's hold invoice
+    InvoiceSettled { payment_hash: PaymentHash },
+    /// A node had cancelled or expired a hold invoice its service still
+    /// counted as held
+    InvoiceDiverged { payment_hash: PaymentHash },
+    /// A game was aborted because a hold invoice could not be created or paid
+    PaymentFailed { game_id: Uuid },
+    /// The oracle signed a game's result
+    GameCompleted { game_id: Uuid, outcome: GameOutcome },
+    /// The buyer paid an escrow order's hold invoice
+    OrderFunded { order_id: Uuid },
+    /// An escrow order was released to the seller
+    OrderSettled { order_id: Uuid },
+    /// The buyer disputed an escrow order
+    DisputeOpened { order_id: Uuid },
+    /// The arbiter decided a dispute; a decision for the seller is followed
+    /// by [`Event::OrderSettled`]
+    DisputeResolved { order_id: Uuid },
+    /// An escrow order still holds the buyer's payment close to its hold
+    /// invoice's expiry, after which the seller's node refunds it
+    SettlementDeadline { order_id: Uuid, level: AlertLevel },
+}
+
+/// How a game ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameOutcome {
+    AWins,
+    BWins,
+    Draw,
+}
+
+impl GameOutcome {
+    /// Snake-case name, as used in metric labels
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GameOutcome::AWins => "a_wins",
+            GameOutcome::BWins => "b_wins",
+            GameOutcome::Draw => "draw",
+        }
+    }
+}
+
+/// How close an alert says its deadline is, least urgent first
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertLevel {
+    Warning,
+    Critical,
+}
+
+impl AlertLevel {
+    /// Snake-case name, as used in metric labels
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AlertLevel::Warning => "warning",
+            AlertLevel::Critical => "critical",
+        }
+    }
+}
+
+/// One service's event channel; clones publish to the same subscribers
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<Event>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CAPACITY);
+        Self { sender }
+    }
+
+    /// Send `event` to every current subscriber. Never blocks, and an event
+    /// nobody is subscribed to is dropped.
+    pub fn publish(&self, event: Event) {
+        debug!(?event, "Event published");
+        let _ = self.sender.send(event);
+    }
+
+    /// Receive every event published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Pass every event `receiver` has queued to `handle`, without waiting for
+/// more. For subscribers that catch up when read rather than in a task of
+/// their own.
+pub(crate) fn drain(receiver: &mut broadcast::Receiver<Event>, mut handle: impl FnMut(Event)) {
+    loop {
+        match receiver.try_recv() {
+            Ok(event) => handle(event),
+            Err(broadcast::error::TryRecvError::Lagged(missed)) => {
+                warn!(missed, "Event subscriber fell behind; events lost");
+            }
+            Err(_) => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_subscriber_sees_every_event() {
+        let bus = EventBus::new();
+        let mut first = bus.subscribe();
+        let mut second = bus.clone().subscribe();
+        let order_id = Uuid::new_v4();
+
+        bus.publish(Event::OrderFunded { order_id });
+        bus.publish(Event::OrderSettled { order_id });
+
+        for receiver in [&mut first, &mut second] {
+            let mut seen = Vec::new();
+            drain(receiver, |event| seen.push(event));
+            assert_eq!(
+                seen,
+                [
+                    Event::OrderFunded { order_id },
+                    Event::OrderSettled { order_id }
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_lagging_subscriber_keeps_the_latest() {
+        let bus = EventBus::new();
+        let mut receiver = bus.subscribe();
+        for _ in 0..EVENT_CAPACITY + 5 {
+            bus.publish(Event::PaymentFailed {
+                game_id: Uuid::new_v4(),
+            });
+        }
+
+        let mut count = 0;
+        drain(&mut receiver, |_| count += 1);
+        assert_eq!(count, EVENT_CAPACITY);
+    }
+}
@@ -0,0 +1,198 @@
+//! Latency and error rates of Fiber node calls.
+//!
+//! Payments are only as quick as the node behind them, and a slow or
+//! failing node otherwise shows up only as slow HTTP requests somewhere
+//! else. [`InstrumentedFiberClient`] wraps any [`FiberClient`] and records
+//! each call in a service's [`Metrics`]: `fiber_node_call_duration_seconds`
+//! by method, and `fiber_node_calls_total` by method and outcome, which is
+//! `ok` or the kind of error (`network_error`, `expired`, ...).
+
+use crate::Metrics;
+use async_trait::async_trait;
+use fiber_core::fiber::{FiberClient, FiberError, HoldInvoice, PaymentId, PaymentStatus};
+use fiber_core::{PaymentHash, Preimage};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// A [`FiberClient`] that times every call into [`Metrics`]
+pub struct InstrumentedFiberClient {
+    inner: Arc<dyn FiberClient>,
+    metrics: Arc<Metrics>,
+}
+
+impl InstrumentedFiberClient {
+    pub fn new(inner: Arc<dyn FiberClient>, metrics: Arc<Metrics>) -> Self {
+        Self { inner, metrics }
+    }
+
+    /// `inner`, instrumented, as the trait object call sites hold
+    pub fn wrap(inner: Arc<dyn FiberClient>, metrics: Arc<Metrics>) -> Arc<dyn FiberClient> {
+        Arc::new(Self::new(inner, metrics))
+    }
+
+    async fn observe<T>(
+        &self,
+        method: &str,
+        call: impl Future<Output = Result<T, FiberError>>,
+    ) -> Result<T, FiberError> {
+        let started = Instant::now();
+        let result = call.await;
+        let outcome = match &result {
+            Ok(_) => "ok",
+            Err(e) => error_kind(e),
+        };
+        self.metrics
+            .observe_node_call(method, started.elapsed(), outcome);
+        result
+    }
+}
+
+/// The `outcome` label of a failed call
+fn error_kind(error: &FiberError) -> &'static str {
+    match error {
+        FiberError::InvoiceNotFound(_) => "invoice_not_found",
+        FiberError::InvalidPreimage => "invalid_preimage",
+        FiberError::AlreadySettled => "already_settled",
+        FiberError::AlreadyCancelled => "already_cancelled",
+        FiberError::Expired => "expired",
+        FiberError::InsufficientFunds => "insufficient_funds",
+        FiberError::InsufficientInboundCapacity { .. } => "insufficient_inbound_capacity",
+        FiberError::PaymentFailed(_) => "payment_failed",
+        FiberError::NetworkError(_) => "network_error",
+        FiberError::Money(_) => "money",
+    }
+}
+
+#[async_trait]
+impl FiberClient for InstrumentedFiberClient {
+    /// The wrapped client, so callers can still downcast to it
+    fn as_any(&self) -> &dyn std::any::Any {
+        self.inner.as_any()
+    }
+
+    async fn create_hold_invoice(
+        &self,
+        payment_hash: &PaymentHash,
+        amount: u64,
+        expiry_secs: u64,
+    ) -> Result<HoldInvoice, FiberError> {
+        self.observe(
+            "create_hold_invoice",
+            self.inner
+                .create_hold_invoice(payment_hash, amount, expiry_secs),
+        )
+        .await
+    }
+
+    async fn create_described_hold_invoice(
+        &self,
+        payment_hash: &PaymentHash,
+        amount: u64,
+        expiry_secs: u64,
+        description: &str,
+    ) -> Result<HoldInvoice, FiberError> {
+        // The same node call as an undescribed one
+        self.observe(
+            "create_hold_invoice",
+            self.inner
+                .create_described_hold_invoice(payment_hash, amount, expiry_secs, description),
+        )
+        .await
+    }
+
+    async fn create_invoice(
+        &self,
+        amount: u64,
+        expiry_secs: u64,
+        preimage: Option<&Preimage>,
+    ) -> Result<HoldInvoice, FiberError> {
+        self.observe(
+            "create_invoice",
+            self.inner.create_invoice(amount, expiry_secs, preimage),
+        )
+        .await
+    }
+
+    async fn pay_hold_invoice(&self, invoice: &HoldInvoice) -> Result<PaymentId, FiberError> {
+        self.observe("pay_hold_invoice", self.inner.pay_hold_invoice(invoice))
+            .await
+    }
+
+    async fn settle_invoice(
+        &self,
+        payment_hash: &PaymentHash,
+        preimage: &Preimage,
+    ) -> Result<(), FiberError> {
+        self.observe(
+            "settle_invoice",
+            self.inner.settle_invoice(payment_hash, preimage),
+        )
+        .await
+    }
+
+    async fn cancel_invoice(&self, payment_hash: &PaymentHash) -> Result<(), FiberError> {
+        self.observe("cancel_invoice", self.inner.cancel_invoice(payment_hash))
+            .await
+    }
+
+    async fn get_payment_status(
+        &self,
+        payment_hash: &PaymentHash,
+    ) -> Result<PaymentStatus, FiberError> {
+        self.observe(
+            "get_payment_status",
+            self.inner.get_payment_status(payment_hash),
+        )
+        .await
+    }
+
+    async fn get_invoice_description(
+        &self,
+        payment_hash: &PaymentHash,
+    ) -> Result<Option<String>, FiberError> {
+        self.observe(
+            "get_invoice_description",
+            self.inner.get_invoice_description(payment_hash),
+        )
+        .await
+    }
+
+    async fn get_balance(&self) -> Result<u64, FiberError> {
+        self.observe("get_balance", self.inner.get_balance()).await
+    }
+
+    async fn get_inbound_capacity(&self) -> Result<u64, FiberError> {
+        self.observe("get_inbound_capacity", self.inner.get_inbound_capacity())
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fiber_core::fiber::MockFiberClient;
+
+    #[tokio::test]
+    async fn test_calls_are_timed_and_counted_by_outcome() {
+        let metrics = Arc::new(Metrics::new());
+        let mock = Arc::new(MockFiberClient::new(10_000));
+        let client = InstrumentedFiberClient::wrap(mock, metrics.clone());
+
+        assert_eq!(client.get_balance().await.unwrap(), 10_000);
+        let preimage = Preimage::random();
+        let hash = preimage.payment_hash();
+        client.create_hold_invoice(&hash, 1_000, 60).await.unwrap();
+        assert!(client.settle_invoice(&hash, &Preimage::random()).await.is_err());
+
+        let text = metrics.render();
+        assert!(text.contains(r#"fiber_node_calls_total{method="get_balance",outcome="ok"} 1"#));
+        assert!(text.contains(
+            r#"fiber_node_calls_total{method="settle_invoice",outcome="invalid_preimage"} 1"#
+        ));
+        assert!(
+            text.contains(r#"fiber_node_call_duration_seconds_count{method="create_hold_invoice"} 1"#)
+        );
+        assert!(client.as_any().downcast_ref::<MockFiberClient>().is_some());
+    }
+}
@@ -0,0 +1,68 @@
+//! Fiber Service Bootstrap
+//!
+//! Shared startup code for the demo services, so each `main` (and the unified
+//! `fiber-demo` binary) configures logging and serving the same way:
+//! - [`init_logging`] installs the tracing subscriber, as text or JSON
+//!   ([`LogFormat`]), plus OTLP export (see [`trace_headers`])
+//! - [`ServerArgs`] is the common `--port` / `PORT` option
+//! - [`serve`] binds and runs an axum app
+//! - [`LocalServer`] runs one in-process on a random port, for tests
+//! - [`static_dir`] / `embedded_ui` serve a service's web UI
+//! - [`request_tracing`] tags every request with an ID for log correlation
+//! - [`EventBus`] carries domain [`Event`]s from handlers to subscribers
+//! - [`Metrics`] / [`with_metrics`] count requests and domain events and
+//!   serve them at `/metrics`; [`InstrumentedFiberClient`] adds the latency
+//!   and errors of Fiber node calls
+//! - [`rate_limit`] caps how often each client may call public routes
+
+mod events;
+mod instrument;
+mod local;
+mod logging;
+mod metrics;
+mod rate_limit;
+mod request_id;
+mod static_files;
+mod telemetry;
+
+use axum::Router;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+
+#[cfg(feature = "embed-ui")]
+pub use static_files::embedded_ui;
+pub use events::{AlertLevel, Event, EventBus, GameOutcome, EVENT_CAPACITY};
+pub use instrument::InstrumentedFiberClient;
+pub use local::LocalServer;
+pub use logging::{init_logging, LogFormat, LOG_FORMAT_ENV};
+pub use metrics::{with_metrics, Metrics, METRICS_PATH};
+pub use rate_limit::{rate_limit, RateLimit};
+pub use request_id::{current_request_id, request_tracing, REQUEST_ID_HEADER};
+pub use static_files::{static_dir, STATIC_DIR_ENV};
+pub use telemetry::trace_headers;
+
+/// Options shared by every HTTP service
+///
+/// Flattened into each service's config, so the file key is plain `port`.
+#[derive(clap::Args, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ServerArgs {
+    /// HTTP port to listen on (defaults to the service's usual port)
+    #[arg(long, env = "PORT")]
+    pub port: Option<u16>,
+}
+
+impl ServerArgs {
+    /// Configured port, or `default` when none was given.
+    pub fn port_or(&self, default: u16) -> u16 {
+        self.port.unwrap_or(default)
+    }
+}
+
+/// Serve `app` on `0.0.0.0:port` until the process exits. Connections may
+/// speak HTTP/1.1 or cleartext HTTP/2, so gRPC clients share the port.
+pub async fn serve(app: Router, port: u16) -> std::io::Result<()> {
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    let listener = TcpListener::bind(addr).await?;
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await
+}
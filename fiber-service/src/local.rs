@@ -0,0 +1,78 @@
+//! Serving an app in-process on a random local port.
+//!
+//! Tests start the real router with [`LocalServer::spawn`] instead of
+//! launching the service binary: there is nothing to build and nothing to
+//! wait for, since the listener is bound before `spawn` returns.
+
+use axum::Router;
+use std::io;
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+/// An axum app served on `127.0.0.1` by a background task; stopped on drop
+pub struct LocalServer {
+    addr: SocketAddr,
+    task: JoinHandle<io::Result<()>>,
+}
+
+impl LocalServer {
+    /// Bind a free port and start serving `app` on the current runtime.
+    pub async fn spawn(app: Router) -> io::Result<Self> {
+        Self::spawn_with(|_| app).await
+    }
+
+    /// Like [`spawn`](Self::spawn), for apps that need their own base URL,
+    /// e.g. to point services in the same app at each other.
+    pub async fn spawn_with(app: impl FnOnce(&str) -> Router) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let addr = listener.local_addr()?;
+        let app = app(&format!("http://{}", addr));
+        let app = app.into_make_service_with_connect_info::<SocketAddr>();
+        let task = tokio::spawn(async move { axum::serve(listener, app).await });
+        Ok(Self { addr, task })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Base URL, e.g. `http://127.0.0.1:40123`
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+}
+
+impl Drop for LocalServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_serves_until_dropped() {
+        let app = Router::new().route("/ping", get(|| async { "pong" }));
+        let server = LocalServer::spawn(app).await.unwrap();
+        let addr = server.addr();
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /ping HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("pong"));
+
+        drop(server);
+        tokio::task::yield_now().await;
+        assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+    }
+}
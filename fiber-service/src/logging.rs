@@ -0,0 +1,146 @@
+//! The tracing subscriber every service logs through.
+//!
+//! Lines are human-readable text by default. `LOG_FORMAT=json` writes one JSON
+//! object per event instead, ready for a log aggregator to index: the event's
+//! fields (`game_id`, `order_id`, `payment_hash`, ...) are top-level keys and
+//! those of the request it happened in (`request_id`, `method`, `uri`) are
+//! under `span`.
+
+use crate::telemetry;
+use std::str::FromStr;
+use tracing::Subscriber;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+/// Env var choosing the [`LogFormat`]
+pub const LOG_FORMAT_ENV: &str = "LOG_FORMAT";
+
+/// How log lines are written
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// One line of text per event
+    #[default]
+    Text,
+    /// One JSON object per event
+    Json,
+}
+
+impl LogFormat {
+    /// Format set by `LOG_FORMAT`; text when unset or unrecognised.
+    pub fn from_env() -> Self {
+        match std::env::var(LOG_FORMAT_ENV) {
+            Ok(value) => value.parse().unwrap_or_else(|e| {
+                eprintln!("{}; logging as text", e);
+                LogFormat::Text
+            }),
+            Err(_) => LogFormat::Text,
+        }
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!(
+                "Unknown {} {:?} (expected text or json)",
+                LOG_FORMAT_ENV, s
+            )),
+        }
+    }
+}
+
+/// Install the global tracing subscriber.
+///
+/// Logs at `info` by default; `RUST_LOG` overrides the filter and `LOG_FORMAT`
+/// the [`LogFormat`]. With an OTLP endpoint configured, spans are also
+/// exported as `service` (unless `OTEL_SERVICE_NAME` says otherwise). Call
+/// from within the Tokio runtime; calling this more than once is harmless.
+pub fn init_logging(service: &str) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let _ = tracing_subscriber::registry()
+        .with(filter)
+        .with(log_layer(LogFormat::from_env(), std::io::stdout))
+        .with(telemetry::otlp_layer(service))
+        .try_init();
+}
+
+fn log_layer<S, W>(format: LogFormat, writer: W) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer().with_writer(writer);
+    match format {
+        LogFormat::Text => layer.boxed(),
+        LogFormat::Json => layer
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(false)
+            .boxed(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    /// Collects everything written to it
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'w> MakeWriter<'w> for Captured {
+        type Writer = Captured;
+
+        fn make_writer(&'w self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!("json".parse(), Ok(LogFormat::Json));
+        assert_eq!("TEXT".parse(), Ok(LogFormat::Text));
+        assert!("yaml".parse::<LogFormat>().is_err());
+    }
+
+    #[test]
+    fn test_json_lines_carry_event_and_span_fields() {
+        let out = Captured::default();
+        let subscriber =
+            tracing_subscriber::registry().with(log_layer(LogFormat::Json, out.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("request", request_id = "req-1");
+            let _entered = span.enter();
+            tracing::info!(game_id = "g-1", payment_hash = "00f067aa", "Game created");
+        });
+
+        let text = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+        let line: serde_json::Value = serde_json::from_str(text.trim()).unwrap();
+        assert_eq!(line["message"], "Game created");
+        assert_eq!(line["game_id"], "g-1");
+        assert_eq!(line["payment_hash"], "00f067aa");
+        assert_eq!(line["span"]["request_id"], "req-1");
+    }
+}
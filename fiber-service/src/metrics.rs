@@ -0,0 +1,368 @@
+//! Prometheus metrics.
+//!
+//! Each service owns one [`Metrics`] and mounts it with [`with_metrics`],
+//! which serves `GET /metrics` in the Prometheus text format and counts every
+//! routed request by method, route template and status. The domain counters
+//! (invoices, games, orders, disputes) count the [`Event`]s of every bus the
+//! metrics [follow](Metrics::follow). Calls to Fiber nodes are timed by
+//! [`InstrumentedFiberClient`](crate::InstrumentedFiberClient).
+//!
+//! A service that hosts others (the combined demo) hands them the same
+//! `Arc<Metrics>` so one scrape covers all of them.
+
+use crate::events::{self, Event, EventBus};
+use axum::extract::{MatchedPath, Request};
+use axum::http::header::CONTENT_TYPE;
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+/// Path the metrics are served at
+pub const METRICS_PATH: &str = "/metrics";
+
+/// Metrics of one service, in a registry of its own
+///
+/// Keeping a registry per instance (rather than the process-wide default)
+/// lets tests run several services side by side without their counts mixing.
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_duration: HistogramVec,
+    /// Hold invoices reported created by a player's or seller's node
+    invoices_created: IntCounter,
+    /// Games aborted because a hold invoice could not be created or paid
+    payments_failed: IntCounter,
+    /// Hold invoices found cancelled or expired on the node while still
+    /// counted as held
+    invoices_diverged: IntCounter,
+    /// Games that ended with a signed result, by result
+    games_completed: IntCounterVec,
+    /// Escrow orders released to the seller
+    orders_settled: IntCounter,
+    /// Escrow orders currently disputed
+    disputes_open: IntGauge,
+    /// Escrow orders found close to their hold invoice's expiry, by level
+    settlement_alerts: IntCounterVec,
+    /// Calls to a Fiber node, by method and outcome (`ok` or the error)
+    node_calls: IntCounterVec,
+    node_duration: HistogramVec,
+    /// Subscriptions to the buses followed, with events not counted yet
+    feeds: Mutex<Vec<broadcast::Receiver<Event>>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry =
+            Registry::new_custom(Some("fiber".to_string()), None).expect("valid metrics prefix");
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests handled"),
+            &["method", "route", "status"],
+        )
+        .expect("valid metric");
+        let http_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time taken to handle an HTTP request",
+            ),
+            &["method", "route"],
+        )
+        .expect("valid metric");
+        let invoices_created = IntCounter::new("invoices_created_total", "Hold invoices created")
+            .expect("valid metric");
+        let payments_failed = IntCounter::new(
+            "payments_failed_total",
+            "Games aborted because a payment failed",
+        )
+        .expect("valid metric");
+        let invoices_diverged = IntCounter::new(
+            "invoices_diverged_total",
+            "Held invoices the node had cancelled or expired",
+        )
+        .expect("valid metric");
+        let games_completed = IntCounterVec::new(
+            Opts::new("games_completed_total", "Games that ended with a result"),
+            &["result"],
+        )
+        .expect("valid metric");
+        let orders_settled = IntCounter::new(
+            "orders_settled_total",
+            "Escrow orders released to the seller",
+        )
+        .expect("valid metric");
+        let disputes_open = IntGauge::new("disputes_open", "Escrow orders currently disputed")
+            .expect("valid metric");
+        let settlement_alerts = IntCounterVec::new(
+            Opts::new(
+                "settlement_alerts_total",
+                "Escrow orders still held close to their invoice's expiry",
+            ),
+            &["level"],
+        )
+        .expect("valid metric");
+
+        let node_calls = IntCounterVec::new(
+            Opts::new("node_calls_total", "Calls made to a Fiber node"),
+            &["method", "outcome"],
+        )
+        .expect("valid metric");
+        let node_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "node_call_duration_seconds",
+                "Time taken by a Fiber node to answer a call",
+            ),
+            &["method"],
+        )
+        .expect("valid metric");
+
+        let collectors: [Box<dyn prometheus::core::Collector>; 11] = [
+            Box::new(http_requests.clone()),
+            Box::new(http_duration.clone()),
+            Box::new(invoices_created.clone()),
+            Box::new(payments_failed.clone()),
+            Box::new(invoices_diverged.clone()),
+            Box::new(games_completed.clone()),
+            Box::new(orders_settled.clone()),
+            Box::new(disputes_open.clone()),
+            Box::new(settlement_alerts.clone()),
+            Box::new(node_calls.clone()),
+            Box::new(node_duration.clone()),
+        ];
+        for collector in collectors {
+            registry
+                .register(collector)
+                .expect("metric registered once");
+        }
+
+        Self {
+            registry,
+            http_requests,
+            http_duration,
+            invoices_created,
+            payments_failed,
+            invoices_diverged,
+            games_completed,
+            orders_settled,
+            disputes_open,
+            settlement_alerts,
+            node_calls,
+            node_duration,
+            feeds: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Count the events published on `bus` from now on.
+    ///
+    /// Queued events are counted whenever the metrics are rendered, so a
+    /// scrape right after a request reflects it, and (inside a Tokio runtime)
+    /// every half second, so a busy bus can't overflow between scrapes.
+    pub fn follow(self: &Arc<Self>, bus: &EventBus) {
+        self.feeds.lock().unwrap().push(bus.subscribe());
+
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let metrics = Arc::downgrade(self);
+        runtime.spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_millis(500));
+            loop {
+                interval.tick().await;
+                match metrics.upgrade() {
+                    Some(metrics) => metrics.catch_up(),
+                    None => break,
+                }
+            }
+        });
+    }
+
+    fn catch_up(&self) {
+        for feed in self.feeds.lock().unwrap().iter_mut() {
+            events::drain(feed, |event| self.count(&event));
+        }
+    }
+
+    fn count(&self, event: &Event) {
+        match event {
+            Event::InvoiceCreated { .. } => self.invoices_created.inc(),
+            Event::PaymentFailed { .. } => self.payments_failed.inc(),
+            Event::InvoiceDiverged { .. } => self.invoices_diverged.inc(),
+            Event::GameCompleted { outcome, .. } => self
+                .games_completed
+                .with_label_values(&[outcome.as_str()])
+                .inc(),
+            Event::OrderSettled { .. } => self.orders_settled.inc(),
+            Event::DisputeOpened { .. } => self.disputes_open.inc(),
+            Event::DisputeResolved { .. } => self.disputes_open.dec(),
+            Event::SettlementDeadline { level, .. } => self
+                .settlement_alerts
+                .with_label_values(&[level.as_str()])
+                .inc(),
+            Event::InvoiceSettled { .. } | Event::OrderFunded { .. } => {}
+        }
+    }
+
+    /// Record a call to a Fiber node that took `elapsed` and ended in
+    /// `outcome`
+    pub(crate) fn observe_node_call(&self, method: &str, elapsed: Duration, outcome: &str) {
+        self.node_duration
+            .with_label_values(&[method])
+            .observe(elapsed.as_secs_f64());
+        self.node_calls
+            .with_label_values(&[method, outcome])
+            .inc();
+    }
+
+    /// Current values in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        self.catch_up();
+        let mut buf = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buf)
+            .expect("text encoding cannot fail");
+        String::from_utf8(buf).expect("text encoding is UTF-8")
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Serve `metrics` at [`METRICS_PATH`] and record every route of `app` in it.
+///
+/// Requests are labelled by the matched route template (`/game/:id`), not the
+/// raw path, so game and order IDs don't each become a series. Fallbacks such
+/// as static files are not counted.
+pub fn with_metrics(app: Router, metrics: Arc<Metrics>) -> Router {
+    let scrape = metrics.clone();
+    app.route(METRICS_PATH, get(move || async move { render(&scrape) }))
+        .route_layer(middleware::from_fn(move |req, next| {
+            track(metrics.clone(), req, next)
+        }))
+}
+
+async fn track(metrics: Arc<Metrics>, req: Request, next: Next) -> Response {
+    let method = req.method().to_string();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_default();
+    let started = Instant::now();
+
+    let resp = next.run(req).await;
+
+    metrics
+        .http_duration
+        .with_label_values(&[&method, &route])
+        .observe(started.elapsed().as_secs_f64());
+    metrics
+        .http_requests
+        .with_label_values(&[&method, &route, resp.status().as_str()])
+        .inc();
+    resp
+}
+
+fn render(metrics: &Metrics) -> Response {
+    ([(CONTENT_TYPE, prometheus::TEXT_FORMAT)], metrics.render()).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{AlertLevel, GameOutcome};
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use tower::ServiceExt;
+
+    async fn get_body(app: Router, uri: &str) -> (StatusCode, String) {
+        let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        let status = resp.status();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_requests_counted_by_route_template() {
+        let metrics = Arc::new(Metrics::new());
+        let app = with_metrics(
+            Router::new().route("/game/:id", get(|| async { "ok" })),
+            metrics.clone(),
+        );
+
+        get_body(app.clone(), "/game/1").await;
+        get_body(app.clone(), "/game/2").await;
+        let (status, text) = get_body(app, METRICS_PATH).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(text.contains(
+            r#"fiber_http_requests_total{method="GET",route="/game/:id",status="200"} 2"#
+        ));
+        assert!(!text.contains("/game/1"));
+    }
+
+    #[tokio::test]
+    async fn test_domain_counters_count_events() {
+        let metrics = Arc::new(Metrics::new());
+        let bus = EventBus::new();
+        metrics.follow(&bus);
+        let order_id = uuid::Uuid::new_v4();
+        bus.publish(Event::InvoiceCreated {
+            payment_hash: fiber_core::Preimage::random().payment_hash(),
+        });
+        bus.publish(Event::GameCompleted {
+            game_id: uuid::Uuid::new_v4(),
+            outcome: GameOutcome::Draw,
+        });
+        bus.publish(Event::DisputeOpened { order_id });
+        bus.publish(Event::DisputeResolved { order_id });
+        bus.publish(Event::SettlementDeadline {
+            order_id,
+            level: AlertLevel::Critical,
+        });
+
+        let (_, text) = get_body(with_metrics(Router::new(), metrics), METRICS_PATH).await;
+        assert!(text.contains("fiber_invoices_created_total 1"));
+        assert!(text.contains(r#"fiber_games_completed_total{result="draw"} 1"#));
+        assert!(text.contains("fiber_disputes_open 0"));
+        assert!(text.contains(r#"fiber_settlement_alerts_total{level="critical"} 1"#));
+    }
+
+    #[test]
+    fn test_instances_do_not_share_counts() {
+        let (a, b) = (Arc::new(Metrics::new()), Arc::new(Metrics::new()));
+        let (bus_a, bus_b) = (EventBus::new(), EventBus::new());
+        a.follow(&bus_a);
+        b.follow(&bus_b);
+        bus_a.publish(Event::OrderSettled {
+            order_id: uuid::Uuid::new_v4(),
+        });
+        assert!(a.render().contains("fiber_orders_settled_total 1"));
+        assert!(b.render().contains("fiber_orders_settled_total 0"));
+    }
+
+    #[test]
+    fn test_one_metrics_can_follow_several_buses() {
+        let metrics = Arc::new(Metrics::new());
+        let buses = [EventBus::new(), EventBus::new()];
+        for bus in &buses {
+            metrics.follow(bus);
+            bus.publish(Event::PaymentFailed {
+                game_id: uuid::Uuid::new_v4(),
+            });
+        }
+        assert!(metrics.render().contains("fiber_payments_failed_total 2"));
+    }
+}
@@ -0,0 +1,119 @@
+//! Per-client request limits.
+//!
+//! [`rate_limit`] lets each client make a fixed number of requests per
+//! window and answers the rest with `429 rate_limited` and a `Retry-After`
+//! header until its window is over. Clients are told apart by the peer
+//! address that [`crate::serve`] and [`crate::LocalServer`] hand the app as
+//! [`ConnectInfo`]; requests without one (an app served some other way)
+//! share a single allowance.
+
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::header::RETRY_AFTER;
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use fiber_errors::{ApiError, ErrorCode};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Clients tracked before those whose window is over are dropped
+const MAX_TRACKED: usize = 10_000;
+
+/// How many requests each client may make per window
+pub struct RateLimit {
+    per_window: u32,
+    window: Duration,
+    /// Start of each client's current window and requests made in it
+    clients: Mutex<HashMap<Option<IpAddr>, (Instant, u32)>>,
+}
+
+impl RateLimit {
+    pub fn new(per_window: u32, window: Duration) -> Self {
+        Self {
+            per_window,
+            window,
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn per_minute(requests: u32) -> Self {
+        Self::new(requests, Duration::from_secs(60))
+    }
+
+    /// Count a request from `client`, or say how long until it may make
+    /// another.
+    fn admit(&self, client: Option<IpAddr>, now: Instant) -> Result<(), Duration> {
+        let mut clients = self.clients.lock().unwrap();
+        if clients.len() >= MAX_TRACKED && !clients.contains_key(&client) {
+            clients.retain(|_, (start, _)| now.duration_since(*start) < self.window);
+        }
+        let (start, count) = clients.entry(client).or_insert((now, 0));
+        if now.duration_since(*start) >= self.window {
+            (*start, *count) = (now, 0);
+        }
+        if *count >= self.per_window {
+            return Err(self.window - now.duration_since(*start));
+        }
+        *count += 1;
+        Ok(())
+    }
+}
+
+/// Wrap `app` so each client gets `limit`.
+pub fn rate_limit(app: Router, limit: RateLimit) -> Router {
+    app.layer(middleware::from_fn_with_state(Arc::new(limit), limit_requests))
+}
+
+async fn limit_requests(State(limit): State<Arc<RateLimit>>, req: Request, next: Next) -> Response {
+    let client = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    match limit.admit(client, Instant::now()) {
+        Ok(()) => next.run(req).await,
+        Err(wait) => {
+            let retry_after = wait.as_secs().max(1).to_string();
+            let error = ApiError::new(ErrorCode::RateLimited, "Too many requests");
+            ([(RETRY_AFTER, retry_after)], error).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_each_client_gets_its_own_window() {
+        let limit = RateLimit::new(2, Duration::from_secs(60));
+        let (a, b) = (Some(IpAddr::from([10, 0, 0, 1])), Some(IpAddr::from([10, 0, 0, 2])));
+        let now = Instant::now();
+
+        assert!(limit.admit(a, now).is_ok());
+        assert!(limit.admit(a, now).is_ok());
+        let wait = limit.admit(a, now + Duration::from_secs(15)).unwrap_err();
+        assert_eq!(wait, Duration::from_secs(45));
+        assert!(limit.admit(b, now).is_ok());
+
+        assert!(limit.admit(a, now + Duration::from_secs(60)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_refused_requests_say_when_to_retry() {
+        let app = Router::new().route("/ping", get(|| async { "pong" }));
+        let app = rate_limit(app, RateLimit::per_minute(1));
+        let ping = || Request::builder().uri("/ping").body(Body::empty()).unwrap();
+
+        let resp = app.clone().oneshot(ping()).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = app.oneshot(ping()).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(resp.headers().contains_key(RETRY_AFTER));
+    }
+}
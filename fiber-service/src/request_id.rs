@@ -0,0 +1,111 @@
+//! Request IDs.
+//!
+//! [`request_tracing`] gives every request an `x-request-id` (keeping one sent
+//! by the caller), runs the handler inside a tracing span carrying that ID,
+//! and echoes it on the response. Handlers that call other services read it
+//! with [`current_request_id`] and forward it, so a single game's logs can be
+//! followed from the player through the oracle. The span also continues any
+//! OpenTelemetry trace the caller sent (see [`crate::trace_headers`]).
+
+use axum::extract::Request;
+use axum::http::HeaderName;
+use axum::middleware::{self, Next};
+use axum::response::Response;
+use axum::Router;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::trace::TraceLayer;
+
+/// Header carrying the request ID
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// ID of the request being handled, when called from inside a handler wrapped
+/// by [`request_tracing`].
+///
+/// Task-local: work moved to a `tokio::spawn`ed task does not see it.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Wrap `app` so every request gets an ID, a tracing span and an
+/// `x-request-id` response header.
+pub fn request_tracing(app: Router) -> Router {
+    let header = HeaderName::from_static(REQUEST_ID_HEADER);
+    // Outermost layer last: assign ID -> echo it -> span -> task-local
+    app.layer(middleware::from_fn(scope_request_id))
+        .layer(TraceLayer::new_for_http().make_span_with(|req: &Request| {
+            let span = tracing::info_span!(
+                "request",
+                method = %req.method(),
+                uri = %req.uri(),
+                request_id = request_id_of(req),
+            );
+            crate::telemetry::continue_trace(&span, req.headers());
+            span
+        }))
+        .layer(PropagateRequestIdLayer::new(header.clone()))
+        .layer(SetRequestIdLayer::new(header, MakeRequestUuid))
+}
+
+fn request_id_of(req: &Request) -> &str {
+    req.headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+}
+
+async fn scope_request_id(req: Request, next: Next) -> Response {
+    let id = request_id_of(&req).to_string();
+    REQUEST_ID.scope(id, next.run(req)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::routing::get;
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        request_tracing(Router::new().route(
+            "/",
+            get(|| async { current_request_id().unwrap_or_default() }),
+        ))
+    }
+
+    async fn body_string(resp: Response) -> String {
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_incoming_request_id_is_kept() {
+        let req = Request::builder()
+            .uri("/")
+            .header(REQUEST_ID_HEADER, "game-42")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app().oneshot(req).await.unwrap();
+
+        assert_eq!(resp.headers()[REQUEST_ID_HEADER], "game-42");
+        assert_eq!(body_string(resp).await, "game-42");
+    }
+
+    #[tokio::test]
+    async fn test_request_id_is_generated() {
+        let req = Request::builder().uri("/").body(Body::empty()).unwrap();
+        let resp = app().oneshot(req).await.unwrap();
+
+        let header = resp.headers()[REQUEST_ID_HEADER].to_str().unwrap().to_string();
+        assert!(!header.is_empty());
+        assert_eq!(body_string(resp).await, header);
+    }
+
+    #[test]
+    fn test_no_request_id_outside_handler() {
+        assert_eq!(current_request_id(), None);
+    }
+}
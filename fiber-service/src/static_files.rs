@@ -0,0 +1,64 @@
+//! Web UI serving.
+//!
+//! Services ship their UI in a `static/` directory next to their manifest.
+//! Serving it with [`static_dir`] only works when the binary runs from that
+//! crate directory; with the `embed-ui` feature, [`embedded_ui`] serves a copy
+//! compiled into the binary instead. Either way, setting `STATIC_DIR` serves
+//! the UI from that directory on disk, so frontend changes show up without a
+//! rebuild.
+
+use axum::http::{header, HeaderValue};
+use axum::Router;
+use tower_http::services::ServeDir;
+use tower_http::set_header::SetResponseHeaderLayer;
+
+#[cfg(feature = "embed-ui")]
+use axum::{
+    http::{StatusCode, Uri},
+    response::{IntoResponse, Response},
+};
+
+/// Environment variable overriding where the UI is served from
+pub const STATIC_DIR_ENV: &str = "STATIC_DIR";
+
+/// Serve the UI from `STATIC_DIR`, or from `default_dir` when it is unset.
+pub fn static_dir(default_dir: &str) -> Router {
+    let dir = std::env::var(STATIC_DIR_ENV).unwrap_or_else(|_| default_dir.to_string());
+    no_cache(Router::new().fallback_service(ServeDir::new(dir)))
+}
+
+/// Serve the UI embedded as `E`, unless `STATIC_DIR` points at a directory.
+#[cfg(feature = "embed-ui")]
+pub fn embedded_ui<E: rust_embed::RustEmbed + Send + Sync + 'static>() -> Router {
+    match std::env::var(STATIC_DIR_ENV) {
+        Ok(dir) => {
+            tracing::info!("Serving UI from {}", dir);
+            static_dir(&dir)
+        }
+        Err(_) => no_cache(Router::new().fallback(serve_embedded::<E>)),
+    }
+}
+
+#[cfg(feature = "embed-ui")]
+async fn serve_embedded<E: rust_embed::RustEmbed>(uri: Uri) -> Response {
+    let path = uri.path().trim_start_matches('/');
+    let path = if path.is_empty() || path.ends_with('/') {
+        format!("{}index.html", path)
+    } else {
+        path.to_string()
+    };
+
+    match E::get(&path) {
+        Some(file) => ([(header::CONTENT_TYPE, file.metadata.mimetype().to_string())], file.data)
+            .into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// No-cache, so browsers don't keep stale UI files across demos
+fn no_cache(router: Router) -> Router {
+    router.layer(SetResponseHeaderLayer::overriding(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("no-cache"),
+    ))
+}
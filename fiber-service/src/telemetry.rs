@@ -0,0 +1,169 @@
+//! OpenTelemetry tracing.
+//!
+//! When an OTLP endpoint is configured (`OTEL_EXPORTER_OTLP_ENDPOINT`, or
+//! `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT`), [`init_logging`](crate::init_logging)
+//! also exports every span over OTLP/HTTP, e.g. to Jaeger or Tempo.
+//!
+//! Spans of different services are joined with W3C `traceparent` headers:
+//! [`request_tracing`](crate::request_tracing) continues the trace an incoming
+//! request carries, and [`trace_headers`] is what a handler adds to the calls
+//! it makes, so one game or order shows up as a single trace.
+
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use opentelemetry::propagation::{Extractor, Injector};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::{global, Context, KeyValue};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::TracerProvider;
+use opentelemetry_sdk::{runtime, Resource};
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Set whenever spans should be exported
+const ENDPOINT_VARS: [&str; 2] = [
+    "OTEL_EXPORTER_OTLP_ENDPOINT",
+    "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+];
+
+/// Overrides the service name passed to `init_logging`
+const SERVICE_NAME_VAR: &str = "OTEL_SERVICE_NAME";
+
+/// Layer exporting spans to the configured OTLP endpoint, if there is one.
+///
+/// Must be called from within a Tokio runtime: spans are sent in batches by a
+/// background task.
+pub(crate) fn otlp_layer<S>(service: &str) -> Option<impl Layer<S>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    if !ENDPOINT_VARS
+        .iter()
+        .any(|var| std::env::var_os(var).is_some())
+    {
+        return None;
+    }
+
+    let exporter = match opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .build()
+    {
+        Ok(exporter) => exporter,
+        Err(e) => {
+            eprintln!("Not exporting traces: {}", e);
+            return None;
+        }
+    };
+    let name = std::env::var(SERVICE_NAME_VAR).unwrap_or_else(|_| service.to_string());
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new([KeyValue::new("service.name", name)]))
+        .build();
+    let tracer = provider.tracer("fiber-service");
+    global::set_tracer_provider(provider);
+    Some(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+/// Headers continuing the current span's trace on an outgoing request.
+///
+/// Empty when spans are not being exported.
+pub fn trace_headers() -> HeaderMap {
+    let context = tracing::Span::current().context();
+    let mut headers = HeaderMap::new();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut HeaderInjector(&mut headers))
+    });
+    headers
+}
+
+/// Make `span` a child of the trace `headers` carry, if any.
+pub(crate) fn continue_trace(span: &tracing::Span, headers: &HeaderMap) {
+    let parent: Context =
+        global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)));
+    span.set_parent(parent);
+}
+
+struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(key.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(HeaderName::as_str).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request_tracing;
+    use axum::body::Body;
+    use axum::extract::Request;
+    use axum::routing::get;
+    use axum::Router;
+    use tower::ServiceExt;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    /// Record spans with an OpenTelemetry tracer that exports nowhere
+    fn traced() -> tracing::subscriber::DefaultGuard {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let tracer = TracerProvider::builder().build().tracer("test");
+        let subscriber =
+            tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+        tracing::subscriber::set_default(subscriber)
+    }
+
+    fn trace_id(traceparent: &str) -> &str {
+        traceparent.split('-').nth(1).unwrap()
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_incoming_trace_is_continued() {
+        let _guard = traced();
+        let app = request_tracing(Router::new().route(
+            "/",
+            get(|| async { trace_headers()["traceparent"].to_str().unwrap().to_string() }),
+        ));
+
+        let req = Request::builder()
+            .uri("/")
+            .header("traceparent", TRACEPARENT)
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let outgoing = String::from_utf8(bytes.to_vec()).unwrap();
+
+        assert_eq!(trace_id(&outgoing), trace_id(TRACEPARENT));
+        assert_ne!(
+            outgoing, TRACEPARENT,
+            "outgoing call gets its own parent span"
+        );
+    }
+
+    #[test]
+    fn test_no_headers_outside_a_trace() {
+        assert!(trace_headers().is_empty());
+    }
+}
@@ -0,0 +1,168 @@
+//! An end-of-scenario audit of a [`MockNetwork`]'s books.
+//!
+//! [`FundsAuditor`] takes the network's opening total and, when asked,
+//! checks it against every wallet plus whatever is still held in invoices.
+//! It also cross-checks each invoice's final status on the client with the
+//! settlements and cancellations the test recorded, so a settlement path
+//! that both pays the winner and refunds the loser shows up even when the
+//! totals happen to line up.
+
+use fiber_core::{PaymentHash, PaymentStatus};
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::Hash;
+
+use crate::network::MockNetwork;
+
+/// Something [`FundsAuditor::audit`] found wrong
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// The parties hold more or less than they started with
+    FundsChanged { expected: u64, actual: u64 },
+    /// An invoice holds funds nobody was recorded paying
+    HeldUnpaid(PaymentHash),
+    /// An invoice was settled and also cancelled
+    SettledAndCancelled(PaymentHash),
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::FundsChanged { expected, actual } => {
+                write!(
+                    f,
+                    "funds unaccounted for: started with {}, now {}",
+                    expected, actual
+                )
+            }
+            Violation::HeldUnpaid(hash) => write!(f, "invoice {} is held but nobody paid it", hash),
+            Violation::SettledAndCancelled(hash) => {
+                write!(f, "invoice {} was both settled and cancelled", hash)
+            }
+        }
+    }
+}
+
+/// Checks that a [`MockNetwork`] neither created nor lost funds
+pub struct FundsAuditor {
+    /// What the parties held, in wallets and held invoices, when audited first
+    total: u64,
+}
+
+impl FundsAuditor {
+    /// Open the books on `network` as it stands now
+    pub fn open<K: Copy + Eq + Hash + fmt::Debug>(network: &MockNetwork<K>) -> Self {
+        Self::with_total(Self::funds(network).0)
+    }
+
+    pub(crate) fn with_total(total: u64) -> Self {
+        Self { total }
+    }
+
+    /// Everything wrong with `network` since the books were opened
+    pub fn audit<K: Copy + Eq + Hash + fmt::Debug>(
+        &self,
+        network: &MockNetwork<K>,
+    ) -> Vec<Violation> {
+        let (actual, mut violations) = Self::funds(network);
+
+        let statuses = network.client().get_all_invoices();
+        let hashes: HashSet<PaymentHash> = statuses
+            .iter()
+            .map(|(hash, _)| *hash)
+            .chain(network.resolutions.keys().copied())
+            .collect();
+        for hash in hashes {
+            let current = statuses.iter().find(|(h, _)| *h == hash).map(|(_, s)| *s);
+            let recorded = network
+                .resolutions
+                .get(&hash)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let seen = |status| current == Some(status) || recorded.contains(&status);
+            if seen(PaymentStatus::Settled) && seen(PaymentStatus::Cancelled) {
+                violations.push(Violation::SettledAndCancelled(hash));
+            }
+        }
+
+        if actual != self.total {
+            violations.push(Violation::FundsChanged {
+                expected: self.total,
+                actual,
+            });
+        }
+        violations
+    }
+
+    /// Panic with every violation, if there are any
+    pub fn assert_balanced<K: Copy + Eq + Hash + fmt::Debug>(&self, network: &MockNetwork<K>) {
+        let violations = self.audit(network);
+        if !violations.is_empty() {
+            let lines: Vec<String> = violations.iter().map(|v| format!("  {}", v)).collect();
+            panic!("funds audit failed:\n{}", lines.join("\n"));
+        }
+    }
+
+    /// Wallets plus held invoices, and any held invoice without a payer
+    fn funds<K: Copy + Eq + Hash + fmt::Debug>(network: &MockNetwork<K>) -> (u64, Vec<Violation>) {
+        let mut violations = Vec::new();
+        let mut total: u64 = network.wallets.values().sum();
+        for (hash, status) in network.client().get_all_invoices() {
+            if status != PaymentStatus::Held {
+                continue;
+            }
+            match network.payments.get(&hash) {
+                Some((_, amount)) => total += amount,
+                None => violations.push(Violation::HeldUnpaid(hash)),
+            }
+        }
+        (total, violations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payments::{preimage, STAKE, WALLET};
+    use fiber_core::FiberClient;
+
+    #[tokio::test]
+    async fn test_settling_and_refunding_one_invoice_is_caught() {
+        let mut network = MockNetwork::new([("alice", WALLET), ("bob", WALLET)]);
+        let auditor = FundsAuditor::open(&network);
+        let to_alice = preimage(1);
+        let invoice = network.invoice(&to_alice.payment_hash()).await.unwrap();
+        network.pay("bob", &invoice).await.unwrap();
+        assert!(auditor.audit(&network).is_empty());
+
+        network
+            .settle("alice", &invoice.payment_hash, &to_alice)
+            .await
+            .unwrap();
+        // A buggy path refunds bob as well
+        network.cancelled(&invoice.payment_hash);
+        assert_eq!(
+            auditor.audit(&network),
+            [
+                Violation::SettledAndCancelled(invoice.payment_hash),
+                Violation::FundsChanged {
+                    expected: 2 * WALLET,
+                    actual: 2 * WALLET + STAKE,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_held_invoice_without_payer_is_caught() {
+        let network = MockNetwork::new([("alice", WALLET)]);
+        let auditor = FundsAuditor::open(&network);
+        let invoice = network.invoice(&preimage(1).payment_hash()).await.unwrap();
+        // Paid on the client, but never recorded
+        network.client().pay_hold_invoice(&invoice).await.unwrap();
+        assert_eq!(
+            auditor.audit(&network),
+            [Violation::HeldUnpaid(invoice.payment_hash)]
+        );
+    }
+}
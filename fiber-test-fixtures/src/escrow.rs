@@ -0,0 +1,146 @@
+//! A seeded escrow marketplace, in memory or served over HTTP.
+
+use fiber_core::Preimage;
+use fiber_escrow_service::{
+    create_app,
+    models::{Order, Product, User},
+    seed::Seed,
+    AppState,
+};
+use fiber_service::LocalServer;
+
+use crate::payments::STAKE;
+
+/// Seed of the served escrow: a buyer, a seller and an arbiter
+const SEED: &str = include_str!("../seeds/escrow.yaml");
+
+/// A seller with one product for [`STAKE`], a buyer and an arbiter
+pub struct Marketplace {
+    pub state: AppState,
+    pub seller: User,
+    pub buyer: User,
+    pub arbiter: User,
+    pub product: Product,
+}
+
+impl Marketplace {
+    /// Register the users and list the product on a fresh state
+    pub async fn new() -> Self {
+        Self::with_state(AppState::new()).await
+    }
+
+    /// Register the users and list the product on `state`, e.g. one
+    /// reading a [`fiber_core::TestClock`]
+    pub async fn with_state(state: AppState) -> Self {
+        let seller = state.register_user("seller".to_string()).await;
+        let buyer = state.register_user("buyer".to_string()).await;
+        let arbiter = state.register_user("arbiter".to_string()).await;
+        let product = state
+            .create_product(
+                seller.id,
+                "Item".to_string(),
+                String::new(),
+                STAKE,
+                None,
+                None,
+            )
+            .await;
+        Self {
+            state,
+            seller,
+            buyer,
+            arbiter,
+            product,
+        }
+    }
+
+    /// A new order of the product by the buyer, locked to a fresh
+    /// preimage only the buyer knows
+    pub async fn order(&self) -> (Order, Preimage) {
+        let preimage = Preimage::random();
+        let order = self
+            .state
+            .create_order(&self.product, self.buyer.id, preimage.payment_hash())
+            .await;
+        (order, preimage)
+    }
+}
+
+/// The escrow service, served in-process on a random port with a runtime
+/// of its own, so blocking clients can drive it; stopped on drop
+pub struct EscrowServer {
+    server: LocalServer,
+    _runtime: tokio::runtime::Runtime,
+}
+
+impl EscrowServer {
+    /// Serve a state seeded with a buyer, a seller and an arbiter
+    pub fn start() -> Self {
+        let state = AppState::with_fiber_rpc_urls(None, None);
+        let runtime = runtime();
+        let seed = Seed::parse(SEED).expect("test seed parses");
+        runtime
+            .block_on(seed.apply(&state))
+            .expect("test seed applies");
+        Self::serve(runtime, state)
+    }
+
+    /// Serve `state`
+    pub fn start_with(state: AppState) -> Self {
+        Self::serve(runtime(), state)
+    }
+
+    fn serve(runtime: tokio::runtime::Runtime, state: AppState) -> Self {
+        let server = runtime
+            .block_on(LocalServer::spawn(create_app(state)))
+            .expect("Failed to start escrow service");
+        Self {
+            server,
+            _runtime: runtime,
+        }
+    }
+
+    /// Base URL of the service
+    pub fn url(&self) -> String {
+        self.server.url()
+    }
+}
+
+fn runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Runtime::new().expect("Failed to create runtime")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fiber_core::TestClock;
+    use fiber_escrow_service::models::OrderStatus;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_order_is_locked_to_buyer_preimage() {
+        let market = Marketplace::new().await;
+        let (order, preimage) = market.order().await;
+        assert_eq!(order.seller_id, market.seller.id);
+        assert_eq!(order.buyer_id, market.buyer.id);
+        assert_eq!(order.amount_shannons, STAKE);
+        assert!(order.payment_hash.verify(&preimage));
+        assert_eq!(market.state.get_order(order.id).await.unwrap().id, order.id);
+    }
+
+    #[tokio::test]
+    async fn test_shipped_order_expires_on_clock() {
+        let clock = TestClock::new();
+        let market = Marketplace::with_state(AppState::new().with_clock(clock.shared())).await;
+        let (order, _) = market.order().await;
+        market
+            .state
+            .update_order_status(order.id, OrderStatus::Shipped)
+            .await;
+
+        clock.advance(Duration::from_secs(24 * 3600 - 1));
+        assert!(market.state.process_expired_orders().await.is_empty());
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(market.state.process_expired_orders().await, vec![order.id]);
+    }
+}
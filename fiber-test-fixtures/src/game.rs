@@ -0,0 +1,120 @@
+//! Oracle keys, sealed submissions and seated game sessions.
+
+use fiber_game_core::{
+    crypto::{compute_signature_points, CommitContext, Commitment, Salt, SignaturePoints},
+    games::{GameAction, GameType},
+    protocol::{Envelope, GameId, GameSession, Joined, Player},
+};
+use secp256k1::SecretKey;
+use serde_json::Value;
+use std::time::Duration;
+
+use crate::keys::Keypair;
+use crate::network::MockNetwork;
+use crate::payments::{STAKE, WALLET};
+
+/// How long a sealed submission stays fresh
+pub const SUBMISSION_TTL: Duration = Duration::from_secs(60);
+
+/// Seed of the nonce behind [`OracleKeys::fixed`]'s commitment point
+const COMMITMENT_SEED: u8 = 0xc0;
+
+/// The oracle's signing key and the nonce it commits to for a game
+#[derive(Clone, Copy, Debug)]
+pub struct OracleKeys {
+    pub signing: Keypair,
+    /// `R`: the public half is the commitment point players see
+    pub commitment: Keypair,
+}
+
+impl OracleKeys {
+    /// The same keys on every run
+    pub fn fixed() -> Self {
+        Self {
+            signing: Keypair::oracle(),
+            commitment: Keypair::from_seed(COMMITMENT_SEED),
+        }
+    }
+
+    /// Fresh keys
+    pub fn random() -> Self {
+        Self {
+            signing: Keypair::random(),
+            commitment: Keypair::random(),
+        }
+    }
+
+    /// The points each outcome's signature will lie on for `game_id`
+    pub fn signature_points(&self, game_id: &GameId) -> SignaturePoints {
+        compute_signature_points(&self.signing.public, &self.commitment.public, game_id)
+    }
+
+    /// Both seats of a new [`STAKE`] game of `game_type`, each holding the
+    /// other's payment hash
+    pub fn seat(&self, game_type: GameType) -> (GameSession<Joined>, GameSession<Joined>) {
+        let game_id = GameId::new();
+        let new = |role| {
+            GameSession::new(
+                game_id,
+                role,
+                game_type,
+                STAKE,
+                self.signing.public,
+                self.commitment.public,
+            )
+        };
+        let (a, b) = (new(Player::A), new(Player::B));
+        let (hash_a, hash_b) = (a.payment_hash(), b.payment_hash());
+        (a.joined(hash_b), b.joined(hash_a))
+    }
+}
+
+/// Both players on one network, with a [`WALLET`] each
+pub fn players_network() -> MockNetwork<Player> {
+    MockNetwork::new([(Player::A, WALLET), (Player::B, WALLET)])
+}
+
+/// A commitment to `action` in `context` under a fresh salt
+pub fn commit(action: &GameAction, context: &CommitContext) -> (Salt, Commitment) {
+    let salt = Salt::random();
+    let commitment = Commitment::bound(&action.to_bytes(), context, &salt);
+    (salt, commitment)
+}
+
+/// Sign `payload` as the oracle expects a submission that opens a game
+pub fn seal(payload: Value, key: &SecretKey) -> Envelope<Value> {
+    Envelope::seal_expiring(payload, key, SUBMISSION_TTL).expect("payload serializes")
+}
+
+/// Sign `payload` as the oracle expects a seated player's submission to
+/// `game_id`
+pub fn seal_for(game_id: GameId, payload: Value, key: &SecretKey) -> Envelope<Value> {
+    Envelope::seal_for(game_id, payload, key, SUBMISSION_TTL).expect("payload serializes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fiber_game_core::crypto::EncryptedPreimage;
+
+    #[test]
+    fn test_seated_sessions_reference_each_other() {
+        let oracle = OracleKeys::fixed();
+        let (a, b) = oracle.seat(GameType::RockPaperScissors);
+        assert_eq!(a.game_id(), b.game_id());
+
+        // A's preimage, encrypted to the point B learns by winning
+        let points = oracle.signature_points(&a.game_id());
+        let for_b = EncryptedPreimage::encrypt(a.preimage(), &points.b_wins);
+        assert!(a.payment_hash().verify(&for_b.decrypt(&points.b_wins)));
+    }
+
+    #[test]
+    fn test_sealed_payload_opens_as_signer() {
+        let key = Keypair::player_a();
+        let envelope = seal(serde_json::json!({ "player": "A" }), &key.secret);
+        let (signer, payload) = envelope.open().unwrap();
+        assert_eq!(signer, key.public);
+        assert_eq!(payload["player"], "A");
+    }
+}
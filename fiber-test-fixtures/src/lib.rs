@@ -0,0 +1,25 @@
+//! Shared fixtures for the Fiber demo test suites and examples.
+//!
+//! Canned keys, payments and a bookkept mock Fiber network with an auditor
+//! for its funds are always available; the rest sits behind features so a
+//! suite only pulls in the services it exercises:
+//!
+//! - `game`: oracle keys, sealed envelopes and seated game sessions
+//! - `services`: an oracle and two players served in-process
+//! - `escrow`: a seeded escrow marketplace, in memory or served
+
+pub mod audit;
+pub mod keys;
+pub mod network;
+pub mod payments;
+
+#[cfg(feature = "escrow")]
+pub mod escrow;
+#[cfg(feature = "game")]
+pub mod game;
+#[cfg(feature = "services")]
+pub mod services;
+
+pub use audit::FundsAuditor;
+pub use keys::Keypair;
+pub use network::MockNetwork;
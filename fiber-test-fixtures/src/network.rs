@@ -0,0 +1,178 @@
+//! A mock Fiber network with the wallets of everyone on it.
+//!
+//! [`MockNetwork`] puts every hold invoice on one `MockFiberClient` and
+//! keeps each party's wallet next to it. Callers drive the client however
+//! the test needs (failing calls, retrying, asking for the status) and
+//! report what went through with [`MockNetwork::paid`],
+//! [`MockNetwork::settled`] and [`MockNetwork::cancelled`]; the network
+//! moves the funds and can then check that none went missing (see
+//! [`FundsAuditor`]).
+
+use fiber_core::{
+    FiberClient, FiberError, HoldInvoice, MockFiberClient, PaymentHash, PaymentStatus, Preimage,
+};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use crate::audit::FundsAuditor;
+use crate::payments::{EXPIRY_SECS, STAKE};
+
+/// One Fiber network shared by the parties `K`
+pub struct MockNetwork<K> {
+    client: MockFiberClient,
+    pub(crate) wallets: HashMap<K, u64>,
+    /// Sum of the starting wallets
+    total: u64,
+    /// Who paid each invoice, and how much
+    pub(crate) payments: HashMap<PaymentHash, (K, u64)>,
+    /// Every settlement and cancellation recorded, per invoice
+    pub(crate) resolutions: HashMap<PaymentHash, Vec<PaymentStatus>>,
+}
+
+impl<K: Copy + Eq + Hash + Debug> MockNetwork<K> {
+    /// A network where each party starts with the given wallet
+    pub fn new(wallets: impl IntoIterator<Item = (K, u64)>) -> Self {
+        let wallets: HashMap<K, u64> = wallets.into_iter().collect();
+        Self {
+            // Balances are tracked per party here; the client's own is
+            // only a single pool, so it must never run dry
+            client: MockFiberClient::new(u64::MAX / 2),
+            total: wallets.values().sum(),
+            wallets,
+            payments: HashMap::new(),
+            resolutions: HashMap::new(),
+        }
+    }
+
+    /// The client every invoice lives on
+    pub fn client(&self) -> &MockFiberClient {
+        &self.client
+    }
+
+    /// What `party` holds outside of invoices
+    pub fn wallet(&self, party: K) -> u64 {
+        self.wallets[&party]
+    }
+
+    /// Current status of an invoice, or `None` if the network doesn't know it
+    pub async fn status(&self, payment_hash: &PaymentHash) -> Option<PaymentStatus> {
+        self.client.get_payment_status(payment_hash).await.ok()
+    }
+
+    /// Create a [`STAKE`] invoice locked to `payment_hash`
+    pub async fn invoice(&self, payment_hash: &PaymentHash) -> Result<HoldInvoice, FiberError> {
+        self.client
+            .create_hold_invoice(payment_hash, STAKE, EXPIRY_SECS)
+            .await
+    }
+
+    /// Pay `invoice` from `payer`'s wallet
+    pub async fn pay(&mut self, payer: K, invoice: &HoldInvoice) -> Result<(), FiberError> {
+        self.client.pay_hold_invoice(invoice).await?;
+        self.paid(payer, invoice);
+        Ok(())
+    }
+
+    /// Settle an invoice to `owner`'s wallet
+    pub async fn settle(
+        &mut self,
+        owner: K,
+        payment_hash: &PaymentHash,
+        preimage: &Preimage,
+    ) -> Result<(), FiberError> {
+        self.client.settle_invoice(payment_hash, preimage).await?;
+        self.settled(owner, payment_hash);
+        Ok(())
+    }
+
+    /// Cancel an invoice, refunding whoever paid it
+    pub async fn cancel(&mut self, payment_hash: &PaymentHash) -> Result<(), FiberError> {
+        self.client.cancel_invoice(payment_hash).await?;
+        self.cancelled(payment_hash);
+        Ok(())
+    }
+
+    /// Record that `payer` paid `invoice`
+    pub fn paid(&mut self, payer: K, invoice: &HoldInvoice) {
+        *self.wallet_mut(payer) -= invoice.amount;
+        self.payments
+            .insert(invoice.payment_hash, (payer, invoice.amount));
+    }
+
+    /// Record that `owner` settled the invoice for `payment_hash`
+    pub fn settled(&mut self, owner: K, payment_hash: &PaymentHash) {
+        let (_, amount) = self.payments[payment_hash];
+        *self.wallet_mut(owner) += amount;
+        self.resolved(payment_hash, PaymentStatus::Settled);
+    }
+
+    /// Record that the invoice for `payment_hash` was cancelled
+    pub fn cancelled(&mut self, payment_hash: &PaymentHash) {
+        // A cancelled hold invoice returns the payment, if there was one
+        if let Some(&(payer, amount)) = self.payments.get(payment_hash) {
+            *self.wallet_mut(payer) += amount;
+        }
+        self.resolved(payment_hash, PaymentStatus::Cancelled);
+    }
+
+    fn resolved(&mut self, payment_hash: &PaymentHash, status: PaymentStatus) {
+        self.resolutions
+            .entry(*payment_hash)
+            .or_default()
+            .push(status);
+    }
+
+    fn wallet_mut(&mut self, party: K) -> &mut u64 {
+        self.wallets
+            .get_mut(&party)
+            .unwrap_or_else(|| panic!("{:?} has no wallet", party))
+    }
+
+    /// Every shannon is in a wallet or locked in a held invoice, and no
+    /// invoice was both settled and cancelled
+    pub fn assert_conserved(&self) {
+        FundsAuditor::with_total(self.total).assert_balanced(self);
+    }
+
+    /// Nothing is left locked, and nothing went missing
+    pub fn assert_released(&self) {
+        for (hash, status) in self.client.get_all_invoices() {
+            assert!(
+                matches!(status, PaymentStatus::Settled | PaymentStatus::Cancelled),
+                "invoice {} left {:?}",
+                hash,
+                status
+            );
+        }
+        self.assert_conserved();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payments::{preimage, WALLET};
+
+    #[tokio::test]
+    async fn test_settled_and_cancelled_invoices_are_accounted_for() {
+        let mut network = MockNetwork::new([("alice", WALLET), ("bob", WALLET)]);
+        let (to_alice, to_bob) = (preimage(1), preimage(2));
+        let invoice_alice = network.invoice(&to_alice.payment_hash()).await.unwrap();
+        let invoice_bob = network.invoice(&to_bob.payment_hash()).await.unwrap();
+
+        network.pay("bob", &invoice_alice).await.unwrap();
+        network.pay("alice", &invoice_bob).await.unwrap();
+        network.assert_conserved();
+        assert_eq!(network.wallet("alice"), WALLET - STAKE);
+
+        network
+            .settle("alice", &invoice_alice.payment_hash, &to_alice)
+            .await
+            .unwrap();
+        network.cancel(&invoice_bob.payment_hash).await.unwrap();
+        network.assert_released();
+        assert_eq!(network.wallet("alice"), WALLET + STAKE);
+        assert_eq!(network.wallet("bob"), WALLET - STAKE);
+    }
+}
@@ -0,0 +1,26 @@
+//! Amounts, preimages and hold invoices the suites share.
+
+use fiber_core::{HoldInvoice, PaymentHash, Preimage};
+
+/// Stake per player, or the price of a product, in shannons
+pub const STAKE: u64 = 1000;
+/// What each party starts with, in shannons
+pub const WALLET: u64 = 10_000;
+/// Hold invoice expiry
+pub const EXPIRY_SECS: u64 = 3600;
+
+/// A fixed preimage; distinct seeds give distinct payment hashes
+pub fn preimage(seed: u8) -> Preimage {
+    Preimage::from_bytes([seed; 32])
+}
+
+/// A hold invoice for [`STAKE`] locked to `payment_hash`, shaped like the
+/// ones `MockFiberClient` hands out
+pub fn hold_invoice(payment_hash: &PaymentHash) -> HoldInvoice {
+    HoldInvoice {
+        payment_hash: *payment_hash,
+        amount: STAKE,
+        expiry_secs: EXPIRY_SECS,
+        invoice_string: format!("mock_invoice_{}", payment_hash.to_hex()),
+    }
+}
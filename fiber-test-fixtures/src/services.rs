@@ -0,0 +1,93 @@
+//! An oracle and two player services, each served in-process.
+
+use axum::Router;
+use fiber_game_oracle::OracleState;
+use fiber_game_player::PlayerState;
+use fiber_service::LocalServer;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// An oracle and players A and B talking to it over HTTP; stopped on drop
+pub struct GameServices {
+    pub oracle: LocalServer,
+    pub player_a: LocalServer,
+    pub player_b: LocalServer,
+    pub client: reqwest::Client,
+}
+
+impl GameServices {
+    /// Start the services with a fresh oracle
+    pub async fn start() -> Self {
+        Self::start_with(|router| router).await
+    }
+
+    /// Start the services, serving the oracle's router as `wrap` returns
+    /// it, e.g. with a layer that breaks chosen requests
+    pub async fn start_with(wrap: impl FnOnce(Router) -> Router) -> Self {
+        let router = fiber_game_oracle::create_router(Arc::new(OracleState::new()));
+        let oracle = LocalServer::spawn(wrap(router))
+            .await
+            .expect("Failed to start oracle");
+        let player = |name: &str| {
+            let state = PlayerState::new(Uuid::new_v4(), name.to_string(), oracle.url(), None);
+            LocalServer::spawn(fiber_game_player::create_router(Arc::new(state)))
+        };
+        let player_a = player("Player A").await.expect("Failed to start player A");
+        let player_b = player("Player B").await.expect("Failed to start player B");
+
+        Self {
+            oracle,
+            player_a,
+            player_b,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Call a player API: a POST with `body`, or a GET without. Returns the
+    /// error body if the call failed.
+    pub async fn call(
+        &self,
+        player: &LocalServer,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<Value, String> {
+        let url = format!("{}/api{}", player.url(), path);
+        let req = match body {
+            Some(body) => self.client.post(url).json(&body),
+            None => self.client.get(url),
+        };
+        let resp = req.send().await.map_err(|e| e.to_string())?;
+        if !resp.status().is_success() {
+            return Err(resp.text().await.unwrap_or_default());
+        }
+        resp.json().await.map_err(|e| e.to_string())
+    }
+
+    /// GET a player API, panicking if it fails or answers with something
+    /// other than `R`
+    pub async fn get<R: DeserializeOwned>(&self, player: &LocalServer, path: &str) -> R {
+        let resp = self
+            .call(player, path, None)
+            .await
+            .unwrap_or_else(|e| panic!("GET {} failed: {}", path, e));
+        serde_json::from_value(resp).unwrap_or_else(|e| panic!("GET {}: {}", path, e))
+    }
+
+    /// POST to a player API, panicking if it fails or answers with
+    /// something other than `R`
+    pub async fn post<B: Serialize, R: DeserializeOwned>(
+        &self,
+        player: &LocalServer,
+        path: &str,
+        body: &B,
+    ) -> R {
+        let body = serde_json::to_value(body).expect("Request body is not JSON");
+        let resp = self
+            .call(player, path, Some(body))
+            .await
+            .unwrap_or_else(|e| panic!("POST {} failed: {}", path, e));
+        serde_json::from_value(resp).unwrap_or_else(|e| panic!("POST {}: {}", path, e))
+    }
+}
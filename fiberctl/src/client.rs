@@ -0,0 +1,211 @@
+//! HTTP calls to the services and the escrow payloads fiberctl reads.
+
+use fiber_errors::ErrorBody;
+use reqwest::{RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use url::Url;
+use uuid::Uuid;
+
+/// One service, reached under its base URL
+pub struct Client {
+    http: reqwest::Client,
+    base: Url,
+    token: Option<String>,
+}
+
+impl Client {
+    /// `token`, if any, is sent as `Authorization: Bearer <token>`.
+    pub fn new(base: &str, token: Option<&str>) -> Result<Self, String> {
+        Ok(Self {
+            http: reqwest::Client::new(),
+            base: base_url(base)?,
+            token: token.map(str::to_string),
+        })
+    }
+
+    pub async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, String> {
+        let resp = self.send(self.http.get(self.url(path)?)).await?;
+        resp.json().await.map_err(|e| e.to_string())
+    }
+
+    /// As [`Client::get`], but `None` if there is nothing at `path`
+    pub async fn find<T: DeserializeOwned>(&self, path: &str) -> Result<Option<T>, String> {
+        let builder = self.authorize(self.http.get(self.url(path)?));
+        let resp = builder.send().await.map_err(|e| e.to_string())?;
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let resp = check(resp).await?;
+        resp.json().await.map(Some).map_err(|e| e.to_string())
+    }
+
+    pub async fn get_text(&self, path: &str) -> Result<String, String> {
+        let resp = self.send(self.http.get(self.url(path)?)).await?;
+        resp.text().await.map_err(|e| e.to_string())
+    }
+
+    pub async fn post<B: Serialize, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<T, String> {
+        let resp = self
+            .send(self.http.post(self.url(path)?).json(body))
+            .await?;
+        resp.json().await.map_err(|e| e.to_string())
+    }
+
+    pub async fn put<B: Serialize, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<T, String> {
+        let resp = self.send(self.http.put(self.url(path)?).json(body)).await?;
+        resp.json().await.map_err(|e| e.to_string())
+    }
+
+    /// `path` is relative to the base URL, or to the host if it starts with `/`.
+    fn url(&self, path: &str) -> Result<Url, String> {
+        self.base.join(path).map_err(|e| e.to_string())
+    }
+
+    fn authorize(&self, builder: RequestBuilder) -> RequestBuilder {
+        match &self.token {
+            Some(token) => builder.bearer_auth(token),
+            None => builder,
+        }
+    }
+
+    async fn send(&self, builder: RequestBuilder) -> Result<Response, String> {
+        let resp = self
+            .authorize(builder)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        check(resp).await
+    }
+}
+
+/// `resp` if it succeeded, or the error the service answered with
+async fn check(resp: Response) -> Result<Response, String> {
+    if resp.status().is_success() {
+        return Ok(resp);
+    }
+    let status = resp.status();
+    let text = resp.text().await.unwrap_or_default();
+    Err(match serde_json::from_str::<ErrorBody>(&text) {
+        Ok(body) => format!("{} ({})", body.error, body.code),
+        Err(_) => format!("{}: {}", status, text),
+    })
+}
+
+/// `base` with a trailing slash, so paths join under it rather than
+/// replacing its last segment.
+fn base_url(base: &str) -> Result<Url, String> {
+    let mut url = Url::parse(base).map_err(|e| format!("{:?}: {}", base, e))?;
+    if !url.path().ends_with('/') {
+        url.set_path(&format!("{}/", url.path()));
+    }
+    Ok(url)
+}
+
+/// `GET /api/arbiter/disputes`
+#[derive(Deserialize)]
+pub struct DisputesResponse {
+    pub disputes: Vec<DisputedOrder>,
+}
+
+/// The parts of an escrow order fiberctl shows
+#[derive(Deserialize)]
+pub struct DisputedOrder {
+    pub id: Uuid,
+    pub product_title: String,
+    pub amount_shannons: u64,
+    pub dispute: Option<Dispute>,
+}
+
+#[derive(Deserialize)]
+pub struct Dispute {
+    pub reason: String,
+    pub created_at: String,
+}
+
+/// `POST /api/arbiter/disputes/:id/resolve`
+#[derive(Serialize)]
+pub struct ResolveRequest {
+    /// `buyer` or `seller`
+    pub resolution: String,
+    /// One of [`REASONS`]
+    pub reason: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// Reasons the escrow accepts for a resolution
+pub const REASONS: [&str; 6] = [
+    "item_not_received",
+    "not_as_described",
+    "seller_unresponsive",
+    "delivered_as_described",
+    "buyer_abuse",
+    "other",
+];
+
+/// `POST /api/system/tick`
+#[derive(Serialize)]
+pub struct TickRequest {
+    /// How far to move the escrow's clock first
+    pub seconds: i64,
+}
+
+#[derive(Deserialize)]
+pub struct TickResponse {
+    pub expired_orders: Vec<Uuid>,
+    pub renewal_orders: Vec<Uuid>,
+    pub suspended_subscriptions: Vec<Uuid>,
+}
+
+/// `GET /api/admin/alerts`
+#[derive(Deserialize)]
+pub struct AlertsResponse {
+    pub alerts: Vec<SettlementAlert>,
+}
+
+/// A held order close to its hold invoice's expiry
+#[derive(Deserialize)]
+pub struct SettlementAlert {
+    pub order_id: Uuid,
+    pub product_title: String,
+    pub amount_shannons: u64,
+    pub status: String,
+    /// `warning` or `critical`
+    pub level: String,
+    pub seconds_left: i64,
+}
+
+/// `GET /api/admin/invoices/:payment_hash`
+#[derive(Deserialize)]
+pub struct InvoiceOwner {
+    pub order_id: Uuid,
+    pub product_title: String,
+    pub status: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paths_join_under_the_base() {
+        let client = Client::new("http://localhost:3000/api/oracle", None).unwrap();
+        assert_eq!(
+            client.url("admin/games").unwrap().as_str(),
+            "http://localhost:3000/api/oracle/admin/games"
+        );
+        assert_eq!(
+            client.url("/metrics").unwrap().as_str(),
+            "http://localhost:3000/metrics"
+        );
+    }
+}
@@ -0,0 +1,518 @@
+//! fiberctl
+//!
+//! Operator CLI for the demo services, in place of hand-written curl calls:
+//!
+//! ```text
+//! fiberctl games [--all]                  games the oracle holds
+//! fiberctl cancel <GAME_ID>               force-cancel a stuck game
+//! fiberctl keys                           the oracle's signing keys
+//! fiberctl rotate-key [--in-hours N]      announce the oracle's next key
+//! fiberctl invoices [--stuck]             each game's hold invoices on a Fiber node
+//! fiberctl invoice <PAYMENT_HASH>         the game or order an invoice on the node is for
+//! fiberctl disputes                       open escrow disputes
+//! fiberctl resolve <ORDER_ID> <buyer|seller> --reason <REASON> [--note TEXT]
+//! fiberctl sweep                          run the escrow expiry/billing sweep now
+//! fiberctl alerts                         held orders whose invoice expires soon
+//! fiberctl features                       the escrow's feature flags
+//! fiberctl feature <NAME> <on|off>        switch an escrow feature
+//! fiberctl metrics [oracle|escrow|URL] [--grep TEXT]
+//! ```
+//!
+//! The oracle commands need the oracle's admin token (`ORACLE_ADMIN_TOKEN`).
+//! Service URLs and the token are flags, environment variables or the
+//! `fiberctl` section of a `--config` file, as for the services themselves.
+
+mod client;
+
+use clap::{FromArgMatches, Parser, Subcommand};
+use client::{
+    AlertsResponse, Client, DisputesResponse, InvoiceOwner, ResolveRequest, TickRequest,
+    TickResponse, REASONS,
+};
+use fiber_config::ServiceConfig;
+use fiber_core::fiber::{FiberClient, InvoiceMemo, PaymentStatus, RpcFiberClient};
+use fiber_core::PaymentHash;
+use fiber_flags::{FeaturesResponse, FlagStatus, SetFlagRequest};
+use fiber_game_api::oracle::{
+    AdminGame, AdminGamesResponse, AnnounceKeyRequest, OracleKey, OracleKeysResponse,
+    StatusResponse,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Operator CLI for the Fiber demo services
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(flatten)]
+    config: Config,
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// Where the services are, the `fiberctl` section of a config file
+#[derive(clap::Args, Debug, Clone, Serialize, Deserialize)]
+struct Config {
+    /// Oracle base URL (`http://host:3000/api/oracle` for the combined demo)
+    #[arg(
+        long,
+        env = "FIBER_ORACLE_URL",
+        default_value = "http://localhost:3000",
+        global = true
+    )]
+    oracle_url: String,
+    /// Escrow service base URL
+    #[arg(
+        long,
+        env = "FIBER_ESCROW_URL",
+        default_value = "http://localhost:3000",
+        global = true
+    )]
+    escrow_url: String,
+    /// Fiber node RPC URL to look invoices up on
+    #[arg(long, env = "FIBER_RPC_URL", global = true)]
+    fiber_rpc_url: Option<String>,
+    /// The oracle's admin token
+    #[arg(long, env = "ORACLE_ADMIN_TOKEN", global = true)]
+    admin_token: Option<String>,
+    /// The escrow service's admin token, if it was started with one
+    #[arg(long, env = "ESCROW_ADMIN_TOKEN", global = true)]
+    escrow_admin_token: Option<String>,
+}
+
+impl ServiceConfig for Config {
+    const SECTION: &'static str = "fiberctl";
+
+    fn validate(&self) -> Result<(), String> {
+        fiber_config::check_url("oracle_url", &self.oracle_url, &["http", "https"])?;
+        fiber_config::check_url("escrow_url", &self.escrow_url, &["http", "https"])?;
+        if let Some(url) = &self.fiber_rpc_url {
+            fiber_config::check_url("fiber_rpc_url", url, &["http", "https"])?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List games that haven't ended, oldest first
+    Games {
+        /// Include completed and cancelled games
+        #[arg(long)]
+        all: bool,
+    },
+    /// Cancel a game that hasn't ended; no preimage is released
+    Cancel { game_id: Uuid },
+    /// List the keys the oracle signed or will sign games with
+    Keys,
+    /// Announce the key the oracle signs new games with from then on;
+    /// games created before keep their key
+    RotateKey {
+        /// Hours until new games are signed with it
+        #[arg(long, default_value_t = 24)]
+        in_hours: u64,
+    },
+    /// Look up every game's hold invoices on the Fiber node
+    Invoices {
+        /// Only list invoices still holding funds for a game that has ended
+        #[arg(long)]
+        stuck: bool,
+    },
+    /// Trace an invoice on the Fiber node to the game or order it pays for,
+    /// by the memo in its description or else its payment hash
+    Invoice {
+        #[arg(value_parser = parse_payment_hash)]
+        payment_hash: PaymentHash,
+    },
+    /// List escrow orders in dispute
+    Disputes,
+    /// Release a disputed order's funds to the buyer or the seller
+    Resolve {
+        order_id: Uuid,
+        #[arg(value_parser = ["buyer", "seller"])]
+        to: String,
+        /// Why: one of the escrow's decision templates, which must decide
+        /// for the same side
+        #[arg(long, value_parser = REASONS)]
+        reason: String,
+        /// Explanation kept with the decision; required for `other`
+        #[arg(long)]
+        note: Option<String>,
+    },
+    /// Auto-complete expired escrow orders and bill due subscriptions now
+    Sweep,
+    /// List escrow orders still held close to their invoice's expiry,
+    /// soonest first
+    Alerts,
+    /// List the escrow's features and whether each is on
+    Features,
+    /// Switch an escrow feature on or off until the service restarts
+    Feature {
+        name: String,
+        #[arg(value_parser = ["on", "off"])]
+        state: String,
+    },
+    /// Print a service's Prometheus metrics
+    Metrics {
+        /// `oracle`, `escrow` or the service's URL
+        #[arg(default_value = "oracle")]
+        service: String,
+        /// Only lines containing this text
+        #[arg(long)]
+        grep: Option<String>,
+    },
+}
+
+#[tokio::main]
+async fn main() {
+    let matches = fiber_config::command::<Cli>().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let config = fiber_config::load(cli.config, &matches);
+
+    if let Err(e) = run(config, cli.command).await {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(config: Config, command: Command) -> Result<(), String> {
+    let oracle = Client::new(&config.oracle_url, config.admin_token.as_deref())?;
+    let escrow = Client::new(&config.escrow_url, config.escrow_admin_token.as_deref())?;
+
+    match command {
+        Command::Games { all } => {
+            let games = oracle_games(&oracle).await?;
+            print_games(games.iter().filter(|g| all || !is_over(&g.status)));
+        }
+        Command::Cancel { game_id } => {
+            let resp: StatusResponse = oracle
+                .post(&format!("admin/game/{}/cancel", game_id), &())
+                .await?;
+            println!("{}: {}", game_id, resp.status);
+        }
+        Command::Keys => {
+            let resp: OracleKeysResponse = oracle.get("oracle/keys").await?;
+            println!("{:<16}  {:<8}  {:>14}  PUBKEY", "FINGERPRINT", "STATUS", "ACTIVATES (MS)");
+            for key in &resp.keys {
+                print_key(key);
+            }
+        }
+        Command::RotateKey { in_hours } => {
+            let request = AnnounceKeyRequest {
+                activate_in_secs: in_hours * 3600,
+            };
+            let key: OracleKey = oracle.post("admin/keys", &request).await?;
+            print_key(&key);
+        }
+        Command::Invoices { stuck } => {
+            let fiber = fiber_node(&config)?;
+            let games = oracle_games(&oracle).await?;
+            print_invoices(&fiber, &games, stuck).await;
+        }
+        Command::Invoice { payment_hash } => {
+            let fiber = fiber_node(&config)?;
+            let status = fiber
+                .get_payment_status(&payment_hash)
+                .await
+                .map_err(|e| format!("Not an invoice on this node: {}", e))?;
+            let description = fiber
+                .get_invoice_description(&payment_hash)
+                .await
+                .unwrap_or_default();
+            let memo = description.as_deref().and_then(InvoiceMemo::find);
+            let owner = invoice_owner(&oracle, &escrow, &payment_hash, memo).await?;
+
+            println!("Payment hash  {}", payment_hash.to_hex());
+            println!("Node          {:?}", status);
+            println!("Description   {}", description.as_deref().unwrap_or("-"));
+            match &owner {
+                Owner::Game(game) => println!("Game          {} ({})", game.game_id, game.status),
+                Owner::Order(order) => println!(
+                    "Order         {} ({}, {})",
+                    order.order_id, order.status, order.product_title
+                ),
+                Owner::Unknown => println!(
+                    "Owner         none found{}",
+                    memo.map_or(String::new(), |m| format!(" for {}", m))
+                ),
+            }
+            if is_orphaned(status, owner.is_live()) {
+                println!("ORPHANED: the node holds a payment nothing will settle or cancel");
+            }
+        }
+        Command::Disputes => {
+            let resp: DisputesResponse = escrow.get("api/arbiter/disputes").await?;
+            if resp.disputes.is_empty() {
+                println!("No open disputes");
+            }
+            for order in resp.disputes {
+                let (opened, reason) = order
+                    .dispute
+                    .map_or_else(Default::default, |d| (d.created_at, d.reason));
+                println!(
+                    "{}  {:>12} shannons  {}  opened {}: {}",
+                    order.id, order.amount_shannons, order.product_title, opened, reason
+                );
+            }
+        }
+        Command::Resolve {
+            order_id,
+            to,
+            reason,
+            note,
+        } => {
+            let path = format!("api/arbiter/disputes/{}/resolve", order_id);
+            let request = ResolveRequest {
+                resolution: to.clone(),
+                reason: reason.clone(),
+                note,
+            };
+            let _: serde_json::Value = escrow.post(&path, &request).await?;
+            println!("{}: resolved to {} ({})", order_id, to, reason);
+        }
+        Command::Sweep => {
+            let resp: TickResponse = escrow
+                .post("api/system/tick", &TickRequest { seconds: 0 })
+                .await?;
+            println!("Auto-completed orders: {}", resp.expired_orders.len());
+            for id in &resp.expired_orders {
+                println!("  {}", id);
+            }
+            println!("Renewal orders created: {}", resp.renewal_orders.len());
+            println!(
+                "Subscriptions suspended: {}",
+                resp.suspended_subscriptions.len()
+            );
+        }
+        Command::Alerts => {
+            let resp: AlertsResponse = escrow.get("api/admin/alerts").await?;
+            if resp.alerts.is_empty() {
+                println!("No held orders near their invoice's expiry");
+            }
+            for alert in resp.alerts {
+                println!(
+                    "{}  {:<8}  {:>12} shannons  {}  {}, {} min left",
+                    alert.order_id,
+                    alert.level,
+                    alert.amount_shannons,
+                    alert.product_title,
+                    alert.status,
+                    alert.seconds_left / 60
+                );
+            }
+        }
+        Command::Features => {
+            let resp: FeaturesResponse = escrow.get("api/admin/features").await?;
+            for feature in &resp.features {
+                print_feature(feature);
+            }
+        }
+        Command::Feature { name, state } => {
+            let request = SetFlagRequest {
+                enabled: state == "on",
+            };
+            let feature: FlagStatus = escrow
+                .put(&format!("api/admin/features/{}", name), &request)
+                .await?;
+            print_feature(&feature);
+        }
+        Command::Metrics { service, grep } => {
+            let client = match service.as_str() {
+                "oracle" => &oracle,
+                "escrow" => &escrow,
+                url => &Client::new(url, None)?,
+            };
+            let text = client.get_text("/metrics").await?;
+            for line in text.lines() {
+                if grep.as_deref().is_none_or(|g| line.contains(g)) {
+                    println!("{}", line);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse_payment_hash(s: &str) -> Result<PaymentHash, String> {
+    PaymentHash::from_hex(s).map_err(|e| e.to_string())
+}
+
+fn fiber_node(config: &Config) -> Result<RpcFiberClient, String> {
+    let url = config
+        .fiber_rpc_url
+        .as_deref()
+        .ok_or("No Fiber node to ask: set --fiber-rpc-url or FIBER_RPC_URL")?;
+    Ok(RpcFiberClient::new(url))
+}
+
+async fn oracle_games(oracle: &Client) -> Result<Vec<AdminGame>, String> {
+    let resp: AdminGamesResponse = oracle.get("admin/games").await?;
+    Ok(resp.games)
+}
+
+fn print_feature(feature: &FlagStatus) {
+    let state = |on: bool| if on { "on" } else { "off" };
+    println!(
+        "{:<24}  {:<3}  (default {:<3})  {}",
+        feature.name,
+        state(feature.enabled),
+        state(feature.default),
+        feature.description
+    );
+}
+
+fn print_key(key: &OracleKey) {
+    println!(
+        "{:<16}  {:<8}  {:>14}  {}",
+        key.fingerprint,
+        format!("{:?}", key.status).to_lowercase(),
+        key.activates_at_ms,
+        key.pubkey
+    );
+}
+
+fn is_over(status: &str) -> bool {
+    matches!(status, "completed" | "cancelled")
+}
+
+fn print_games<'a>(games: impl Iterator<Item = &'a AdminGame>) {
+    println!(
+        "{:<36}  {:<20}  {:>12}  {:<20}  {:>8}",
+        "GAME", "STATUS", "SHANNONS", "CREATED", "IDLE"
+    );
+    for game in games {
+        println!(
+            "{:<36}  {:<20}  {:>12}  {:<20}  {:>7}s",
+            game.game_id,
+            game.status,
+            game.amount_shannons,
+            game.created_at.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            game.idle_secs
+        );
+    }
+}
+
+/// An invoice is stuck when it still holds a payment after its game ended:
+/// nobody will settle or cancel it unless told to.
+fn is_stuck(game_status: &str, invoice: Option<PaymentStatus>) -> bool {
+    is_over(game_status) && invoice == Some(PaymentStatus::Held)
+}
+
+async fn print_invoices(fiber: &dyn FiberClient, games: &[AdminGame], stuck_only: bool) {
+    println!(
+        "{:<36}  {:<7}  {:<18}  {:<20}  {:<10}",
+        "GAME", "INVOICE", "PAYMENT HASH", "GAME STATUS", "NODE"
+    );
+    for game in games {
+        for (holder, hash) in [("A", game.payment_hash_a), ("B", game.payment_hash_b)] {
+            let Some(hash) = hash else { continue };
+            // Only the holder's node knows the invoice; others answer an error
+            let status = fiber.get_payment_status(&hash).await.ok();
+            // A draw's invoices held for the next round belong to that game
+            let carried_over = game.settlement.as_ref().is_some_and(|s| s.carried_over());
+            let stuck = is_stuck(&game.status, status) && !carried_over;
+            if stuck_only && !stuck {
+                continue;
+            }
+            println!(
+                "{:<36}  {:<7}  {:<18}  {:<20}  {:<10}{}",
+                game.game_id,
+                holder,
+                hash.short_hex(),
+                game.status,
+                status.map_or("-".to_string(), |s| format!("{:?}", s)),
+                if stuck { "  STUCK" } else { "" }
+            );
+        }
+    }
+}
+
+/// What an invoice on the node pays for, as the services know it
+enum Owner {
+    Game(AdminGame),
+    Order(InvoiceOwner),
+    Unknown,
+}
+
+impl Owner {
+    /// Whether a game or order still counts on the invoice, and so will
+    /// settle or cancel it in the end
+    fn is_live(&self) -> bool {
+        match self {
+            Self::Game(game) => !is_over(&game.status),
+            Self::Order(order) => !matches!(order.status.as_str(), "completed" | "refunded"),
+            Self::Unknown => false,
+        }
+    }
+}
+
+/// The game or order behind `payment_hash`. The invoice's memo says which
+/// service to ask; an invoice without one is looked for in both, among
+/// whichever of them answer.
+async fn invoice_owner(
+    oracle: &Client,
+    escrow: &Client,
+    payment_hash: &PaymentHash,
+    memo: Option<InvoiceMemo>,
+) -> Result<Owner, String> {
+    let game_id = match memo {
+        Some(InvoiceMemo::Game(id)) => Some(id),
+        _ => None,
+    };
+    // The rounds of a match replaying draws on the same invoices share
+    // their hashes; the latest is the one resolving them
+    let find_game = |games: Vec<AdminGame>| {
+        games.into_iter().rfind(|g| {
+            game_id.as_ref() == Some(g.game_id.as_uuid())
+                || [g.payment_hash_a, g.payment_hash_b].contains(&Some(*payment_hash))
+        })
+    };
+    let order_path = format!("api/admin/invoices/{}", payment_hash.to_hex());
+    Ok(match memo {
+        Some(InvoiceMemo::Game(_)) => find_game(oracle_games(oracle).await?).map(Owner::Game),
+        Some(InvoiceMemo::Order(_)) => escrow.find(&order_path).await?.map(Owner::Order),
+        None => match oracle_games(oracle).await.ok().and_then(find_game) {
+            Some(game) => Some(Owner::Game(game)),
+            None => escrow.find(&order_path).await.ok().flatten().map(Owner::Order),
+        },
+    }
+    .unwrap_or(Owner::Unknown))
+}
+
+/// An invoice is orphaned when the node holds a payment for it that no
+/// live game or order claims: nobody will ever settle or cancel it.
+fn is_orphaned(invoice: PaymentStatus, claimed: bool) -> bool {
+    invoice == PaymentStatus::Held && !claimed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_held_invoices_of_ended_games_are_stuck() {
+        assert!(is_stuck("cancelled", Some(PaymentStatus::Held)));
+        assert!(is_stuck("completed", Some(PaymentStatus::Held)));
+        assert!(!is_stuck("in_progress", Some(PaymentStatus::Held)));
+        assert!(!is_stuck("completed", Some(PaymentStatus::Settled)));
+        assert!(!is_stuck("cancelled", None));
+    }
+
+    #[test]
+    fn test_only_unclaimed_held_invoices_are_orphaned() {
+        assert!(is_orphaned(PaymentStatus::Held, false));
+        assert!(!is_orphaned(PaymentStatus::Held, true));
+        assert!(!is_orphaned(PaymentStatus::Pending, false));
+        assert!(!is_orphaned(PaymentStatus::Settled, false));
+    }
+
+    #[test]
+    fn test_options_after_the_subcommand() {
+        let matches = fiber_config::command::<Cli>()
+            .try_get_matches_from(["fiberctl", "games", "--oracle-url", "http://o:1/api/oracle"])
+            .unwrap();
+        let cli = Cli::from_arg_matches(&matches).unwrap();
+        let config = fiber_config::resolve(cli.config, &matches).unwrap();
+        assert_eq!(config.oracle_url, "http://o:1/api/oracle");
+    }
+}
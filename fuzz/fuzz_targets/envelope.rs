@@ -0,0 +1,41 @@
+//! Decoding and verifying signed protocol envelopes.
+//!
+//! The first byte picks JSON or CBOR, the rest is the envelope as it would
+//! arrive in a request body or WebSocket frame. Decoding must never panic,
+//! and an envelope that verifies must still verify after being re-encoded in
+//! either encoding, since signatures cover the canonical form.
+
+#![no_main]
+
+use fiber_game_core::protocol::{Encoding, Envelope};
+use libfuzzer_sys::fuzz_target;
+use serde_json::Value;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&selector, body)) = data.split_first() else {
+        return;
+    };
+    let encoding = if selector & 1 == 0 {
+        Encoding::Json
+    } else {
+        Encoding::Cbor
+    };
+
+    let Ok(envelope) = encoding.decode::<Envelope<Value>>(body) else {
+        return;
+    };
+    let _ = envelope.is_expired();
+    let _ = envelope.digest();
+    if envelope.verify().is_err() {
+        assert!(envelope.clone().open().is_err());
+        return;
+    }
+
+    for other in [Encoding::Json, Encoding::Cbor] {
+        let bytes = other.encode(&envelope).unwrap();
+        let decoded: Envelope<Value> = other.decode(&bytes).unwrap();
+        decoded.verify().unwrap();
+    }
+    let (sender, _) = envelope.clone().open().unwrap();
+    assert_eq!(sender, envelope.sender);
+});
@@ -0,0 +1,33 @@
+//! Hex and JSON decoding of preimages and payment hashes.
+//!
+//! Both arrive from players and buyers as hex strings or byte arrays. Any
+//! value that parses must print back to the same bytes, and a preimage must
+//! still verify against its own hash.
+
+#![no_main]
+
+use fiber_core::{PaymentHash, Preimage};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(s) = std::str::from_utf8(data) {
+        if let Ok(preimage) = Preimage::from_hex(s) {
+            let again = Preimage::from_hex(&preimage.to_hex()).unwrap();
+            assert_eq!(again.as_bytes(), preimage.as_bytes());
+            assert!(preimage.payment_hash().verify(&preimage));
+        }
+        if let Ok(hash) = PaymentHash::from_hex(s) {
+            assert_eq!(PaymentHash::from_hex(&hash.to_hex()).unwrap(), hash);
+        }
+    }
+
+    if let Ok(preimage) = serde_json::from_slice::<Preimage>(data) {
+        let json = serde_json::to_vec(&preimage).unwrap();
+        let again: Preimage = serde_json::from_slice(&json).unwrap();
+        assert_eq!(again.as_bytes(), preimage.as_bytes());
+    }
+    if let Ok(hash) = serde_json::from_slice::<PaymentHash>(data) {
+        let json = serde_json::to_vec(&hash).unwrap();
+        assert_eq!(serde_json::from_slice::<PaymentHash>(&json).unwrap(), hash);
+    }
+});
@@ -0,0 +1,87 @@
+//! Invoice strings, as sellers submit them to the escrow service.
+//!
+//! Invoices are stored and handed to the buyer's frontend as given, so what
+//! the services decode is the request carrying them: the body is fuzzed
+//! against a seller's order awaiting an invoice, and whatever is accepted
+//! must come back unchanged in the order details. The same string is also
+//! read as a `HoldInvoice`, the shape the Fiber clients pass invoices in.
+
+#![no_main]
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use fiber_core::HoldInvoice;
+use fiber_escrow_service::create_app;
+use fiber_test_fixtures::escrow::Marketplace;
+use libfuzzer_sys::fuzz_target;
+use serde_json::Value;
+use std::sync::OnceLock;
+use tokio::runtime::Runtime;
+use tower::ServiceExt;
+
+struct Escrow {
+    runtime: Runtime,
+    router: Router,
+    market: Marketplace,
+    seller: String,
+}
+
+fn escrow() -> &'static Escrow {
+    static ESCROW: OnceLock<Escrow> = OnceLock::new();
+    ESCROW.get_or_init(|| {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let market = runtime.block_on(Marketplace::new());
+        Escrow {
+            runtime,
+            router: create_app(market.state.clone()),
+            seller: market.seller.id.0.to_string(),
+            market,
+        }
+    })
+}
+
+fuzz_target!(|data: &[u8]| {
+    let _ = serde_json::from_slice::<HoldInvoice>(data);
+
+    // A fresh order per input, so earlier invoices don't mask this one
+    let escrow = escrow();
+    let (order, _) = escrow.runtime.block_on(escrow.market.order());
+
+    let send = |req: Request<Body>| {
+        escrow.runtime.block_on(async {
+            let resp = escrow.router.clone().oneshot(req).await.unwrap();
+            let status = resp.status();
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, body)
+        })
+    };
+    let (status, _) = send(
+        Request::post(format!("/api/orders/{}/invoice", order.id.0))
+            .header("content-type", "application/json")
+            .header("X-User-Id", &escrow.seller)
+            .body(Body::from(data.to_vec()))
+            .unwrap(),
+    );
+    assert!(!status.is_server_error());
+    if status != StatusCode::OK {
+        return;
+    }
+
+    let submitted: Value = serde_json::from_slice(data).unwrap();
+    let (status, body) = send(
+        Request::get(format!("/api/orders/{}", order.id.0))
+            .header("X-User-Id", &escrow.seller)
+            .body(Body::empty())
+            .unwrap(),
+    );
+    assert_eq!(status, StatusCode::OK);
+    let details: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(details["invoice_string"], submitted["invoice"]);
+});
@@ -0,0 +1,129 @@
+//! Oracle request bodies, through the real router.
+//!
+//! The first byte picks a route and how the body is built, the rest is
+//! either the whole body or a payload that gets sealed into an envelope by
+//! the player who created the game. Raw bodies exercise envelope decoding;
+//! sealed ones get past the signature check, so the request types and the
+//! handlers behind them see the fuzzed payload too. Whatever the input, the
+//! oracle must answer without panicking and never with a server error.
+
+#![no_main]
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use fiber_game_core::protocol::{Encoding, Envelope, GameId};
+use fiber_game_oracle::{api_router, OracleState};
+use libfuzzer_sys::fuzz_target;
+use secp256k1::SecretKey;
+use serde_json::{json, Value};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::runtime::Runtime;
+use tower::ServiceExt;
+
+const ROUTES: [&str; 12] = [
+    "create",
+    "join",
+    "payment-hash",
+    "invoice",
+    "encrypted-preimage",
+    "funded",
+    "commit",
+    "reveal",
+    "abort",
+    "timeout",
+    "resume",
+    "settled",
+];
+
+struct Oracle {
+    runtime: Runtime,
+    router: Router,
+    /// Key of the player who created `game_id`
+    player: SecretKey,
+    game_id: GameId,
+}
+
+impl Oracle {
+    fn post(&self, path: String, content_type: &str, body: Vec<u8>) -> (StatusCode, Vec<u8>) {
+        let req = Request::post(path)
+            .header("content-type", content_type)
+            .body(Body::from(body))
+            .unwrap();
+        self.runtime.block_on(async {
+            let resp = self.router.clone().oneshot(req).await.unwrap();
+            let status = resp.status();
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, body.to_vec())
+        })
+    }
+
+    /// Sign `payload` for `game_id`, or unbound for a create
+    fn seal(&self, game_id: Option<GameId>, payload: Value, encoding: Encoding) -> Vec<u8> {
+        let ttl = Duration::from_secs(60);
+        let envelope = match game_id {
+            Some(game_id) => Envelope::seal_for(game_id, payload, &self.player, ttl),
+            None => Envelope::seal_expiring(payload, &self.player, ttl),
+        }
+        .unwrap();
+        encoding.encode(&envelope).unwrap()
+    }
+}
+
+fn oracle() -> &'static Oracle {
+    static ORACLE: OnceLock<Oracle> = OnceLock::new();
+    ORACLE.get_or_init(|| {
+        let mut oracle = Oracle {
+            runtime: tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap(),
+            router: api_router(Arc::new(OracleState::new())),
+            player: SecretKey::new(&mut rand::thread_rng()),
+            game_id: GameId::new(),
+        };
+        let create = json!({
+            "game_type": "RockPaperScissors",
+            "player_a_id": uuid::Uuid::new_v4(),
+            "amount_shannons": 1000,
+        });
+        let body = oracle.seal(None, create, Encoding::Json);
+        let (status, body) = oracle.post("/game/create".to_string(), "application/json", body);
+        assert_eq!(status, StatusCode::OK);
+        let created: Value = serde_json::from_slice(&body).unwrap();
+        oracle.game_id = serde_json::from_value(created["game_id"].clone()).unwrap();
+        oracle
+    })
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&selector, rest)) = data.split_first() else {
+        return;
+    };
+    let oracle = oracle();
+    let route = ROUTES[selector as usize % ROUTES.len()];
+    let (path, game_id) = match route {
+        "create" => ("/game/create".to_string(), None),
+        _ => (format!("/game/{}/{}", oracle.game_id, route), Some(oracle.game_id)),
+    };
+    let encoding = if selector & 0x10 == 0 {
+        Encoding::Json
+    } else {
+        Encoding::Cbor
+    };
+
+    let body = if selector & 0x20 == 0 {
+        rest.to_vec()
+    } else {
+        let Ok(payload) = encoding.decode::<Value>(rest) else {
+            return;
+        };
+        oracle.seal(game_id, payload, encoding)
+    };
+    let (status, _) = oracle.post(path, encoding.content_type(), body);
+    assert!(!status.is_server_error(), "{} answered {}", route, status);
+});